@@ -0,0 +1,119 @@
+// bindings/rust/zrraw/src/color.rs - Colour calibration for one to three illuminants
+use zrraw_sys::ZrRawColorMatrices;
+
+/// EXIF LightSource value a calibration was made under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Illuminant(pub u16);
+
+impl Illuminant {
+    pub const DAYLIGHT: Illuminant = Illuminant(1);
+    pub const FLUORESCENT: Illuminant = Illuminant(2);
+    pub const TUNGSTEN: Illuminant = Illuminant(3);
+    pub const FLASH: Illuminant = Illuminant(4);
+    pub const STANDARD_LIGHT_A: Illuminant = Illuminant(17);
+    pub const D55: Illuminant = Illuminant(20);
+    pub const D65: Illuminant = Illuminant(21);
+    pub const D75: Illuminant = Illuminant(22);
+    pub const D50: Illuminant = Illuminant(23);
+    /// Described by the DNG 1.6 IlluminantData tag, which zrraw does not read
+    pub const OTHER: Illuminant = Illuminant(255);
+
+    /// Correlated colour temperature in kelvin the DNG SDK assigns to the
+    /// light source, `None` for unknown or custom ones
+    pub fn temperature(self) -> Option<f32> {
+        let kelvin = match self.0 {
+            3 | 17 => 2850.0,
+            24 => 3200.0,
+            23 | 13 => 5000.0,
+            1 | 4 | 9 | 18 | 20 => 5500.0,
+            10 | 19 | 21 => 6500.0,
+            11 | 22 => 7500.0,
+            12 => 6400.0,
+            2 | 14 => 4150.0,
+            15 => 3450.0,
+            16 => 2925.0,
+            _ => return None,
+        };
+        Some(kelvin)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    pub illuminant: Illuminant,
+    /// XYZ to reference camera space, row-major
+    pub color_matrix: [f32; 9],
+    /// Reference camera space to this body's own response
+    pub camera_calibration: Option<[f32; 9]>,
+    /// White-balanced camera space to XYZ D50
+    pub forward_matrix: Option<[f32; 9]>,
+}
+
+/// The DNG colour tags of a file. Formats with a single camera to XYZ
+/// matrix are described as one D65 calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorProfile {
+    /// In the file's order: ColorMatrix1, 2 and, for DNG 1.6, 3
+    pub calibrations: Vec<Calibration>,
+    pub analog_balance: [f32; 3],
+    pub as_shot_neutral: Option<[f32; 3]>,
+    /// CIE 1931 chromaticity, for files that give the white this way
+    pub as_shot_white_xy: Option<[f32; 2]>,
+}
+
+impl ColorProfile {
+    /// The calibration made under `illuminant`, if any
+    pub fn calibration(&self, illuminant: Illuminant) -> Option<&Calibration> {
+        self.calibrations.iter().find(|c| c.illuminant == illuminant)
+    }
+}
+
+/// What `ZrRaw::color_matrices` renders as neutral
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WhitePoint {
+    /// The white balance the camera recorded
+    #[default]
+    AsShot,
+    /// Camera response to the white, as in DNG AsShotNeutral
+    Neutral([f32; 3]),
+    /// Correlated colour temperature and tint, in the DNG SDK's units
+    Temperature { kelvin: f32, tint: f32 },
+}
+
+/// Matrices interpolated between the calibration illuminants as the DNG
+/// specification describes, for one white point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrices {
+    /// Camera space, before white balance, to XYZ D50; the neutral maps
+    /// to the D50 white with Y = 1
+    pub camera_to_xyz: [f32; 9],
+    /// Camera space, before white balance, to linear sRGB
+    pub camera_to_srgb: [f32; 9],
+    /// Camera response to the white, largest component 1
+    pub neutral: [f32; 3],
+    pub white_xy: [f32; 2],
+    /// Kelvin
+    pub temperature: f32,
+    pub tint: f32,
+}
+
+impl ColorMatrices {
+    /// White balance multipliers relative to green for the white point
+    pub fn white_balance(&self) -> [f32; 3] {
+        let n = self.neutral;
+        [n[1] / n[0], 1.0, n[1] / n[2]]
+    }
+}
+
+impl From<ZrRawColorMatrices> for ColorMatrices {
+    fn from(m: ZrRawColorMatrices) -> Self {
+        ColorMatrices {
+            camera_to_xyz: m.camera_to_xyz,
+            camera_to_srgb: m.camera_to_srgb,
+            neutral: m.neutral,
+            white_xy: m.white_xy,
+            temperature: m.temperature,
+            tint: m.tint,
+        }
+    }
+}
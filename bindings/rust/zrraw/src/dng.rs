@@ -0,0 +1,612 @@
+// bindings/rust/zrraw/src/dng.rs - DNG 1.4 writer
+use crate::exif::{Directory, Exif, ExifValue};
+use crate::ljpeg;
+use crate::{RawMetadata, SensorData, ZrRawError};
+use image::{DynamicImage, RgbImage};
+use md5::{Digest, Md5};
+use std::io::Write;
+
+// TIFF field types
+const BYTE: u16 = 1;
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const SBYTE: u16 = 6;
+const UNDEFINED: u16 = 7;
+const SSHORT: u16 = 8;
+const SLONG: u16 = 9;
+const SRATIONAL: u16 = 10;
+const FLOAT: u16 = 11;
+const DOUBLE: u16 = 12;
+
+// TIFF/EP and EXIF tags
+const NEW_SUBFILE_TYPE: u16 = 0x00FE;
+const IMAGE_WIDTH: u16 = 0x0100;
+const IMAGE_LENGTH: u16 = 0x0101;
+const BITS_PER_SAMPLE: u16 = 0x0102;
+const COMPRESSION: u16 = 0x0103;
+const PHOTOMETRIC: u16 = 0x0106;
+const MAKE: u16 = 0x010F;
+const MODEL: u16 = 0x0110;
+const STRIP_OFFSETS: u16 = 0x0111;
+const ORIENTATION: u16 = 0x0112;
+const SAMPLES_PER_PIXEL: u16 = 0x0115;
+const ROWS_PER_STRIP: u16 = 0x0116;
+const STRIP_BYTE_COUNTS: u16 = 0x0117;
+const PLANAR_CONFIGURATION: u16 = 0x011C;
+const SOFTWARE: u16 = 0x0131;
+const TILE_WIDTH: u16 = 0x0142;
+const TILE_LENGTH: u16 = 0x0143;
+const TILE_OFFSETS: u16 = 0x0144;
+const TILE_BYTE_COUNTS: u16 = 0x0145;
+const SUB_IFDS: u16 = 0x014A;
+const CFA_REPEAT_PATTERN_DIM: u16 = 0x828D;
+const CFA_PATTERN: u16 = 0x828E;
+const EXPOSURE_TIME: u16 = 0x829A;
+const F_NUMBER: u16 = 0x829D;
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+const ISO: u16 = 0x8827;
+const EXIF_VERSION: u16 = 0x9000;
+const FOCAL_LENGTH: u16 = 0x920A;
+const MAKER_NOTE: u16 = 0x927C;
+const INTEROP_IFD: u16 = 0xA005;
+
+// DNG tags
+const DNG_VERSION: u16 = 0xC612;
+const DNG_BACKWARD_VERSION: u16 = 0xC613;
+const UNIQUE_CAMERA_MODEL: u16 = 0xC614;
+const CFA_PLANE_COLOR: u16 = 0xC616;
+const CFA_LAYOUT: u16 = 0xC617;
+const BLACK_LEVEL_REPEAT_DIM: u16 = 0xC619;
+const BLACK_LEVEL: u16 = 0xC61A;
+const WHITE_LEVEL: u16 = 0xC61D;
+const COLOR_MATRIX_1: u16 = 0xC621;
+const COLOR_MATRIX_2: u16 = 0xC622;
+const CAMERA_CALIBRATION_1: u16 = 0xC623;
+const CAMERA_CALIBRATION_2: u16 = 0xC624;
+const ANALOG_BALANCE: u16 = 0xC627;
+const AS_SHOT_NEUTRAL: u16 = 0xC628;
+const CALIBRATION_ILLUMINANT_1: u16 = 0xC65A;
+const CALIBRATION_ILLUMINANT_2: u16 = 0xC65B;
+const FORWARD_MATRIX_1: u16 = 0xC714;
+const FORWARD_MATRIX_2: u16 = 0xC715;
+const PREVIEW_COLOR_SPACE: u16 = 0xC71A;
+const NEW_RAW_IMAGE_DIGEST: u16 = 0xC7A7;
+
+const COMPRESSION_NONE: u16 = 1;
+const COMPRESSION_JPEG: u16 = 7;
+const PHOTOMETRIC_RGB: u16 = 2;
+const PHOTOMETRIC_YCBCR: u16 = 6;
+const PHOTOMETRIC_CFA: u16 = 32803;
+const PHOTOMETRIC_LINEAR_RAW: u16 = 34892;
+const ILLUMINANT_D65: u16 = 21;
+const PREVIEW_SRGB: u32 = 2;
+
+const TILE_SIZE: u32 = 256;
+const PREVIEW_SIZE: u32 = 1024;
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// How the raw image is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DngCompression {
+    /// 16-bit samples in a single strip
+    #[default]
+    Uncompressed,
+    /// Lossless JPEG in 256x256 tiles
+    LosslessJpeg,
+}
+
+/// Writes sensor data and its metadata as a DNG 1.4 file.
+///
+/// With a preview, IFD0 holds an 8-bit thumbnail and the raw image and a
+/// JPEG preview live in SubIFDs; without one, IFD0 is the raw image.
+pub struct DngWriter<'a> {
+    sensor: &'a SensorData,
+    metadata: &'a RawMetadata,
+    compression: DngCompression,
+    preview: Option<&'a DynamicImage>,
+}
+
+impl<'a> DngWriter<'a> {
+    pub fn new(sensor: &'a SensorData, metadata: &'a RawMetadata) -> Self {
+        DngWriter { sensor, metadata, compression: DngCompression::default(), preview: None }
+    }
+
+    pub fn compression(mut self, compression: DngCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Embeds a rendered image as the preview and thumbnail
+    pub fn preview(mut self, image: &'a DynamicImage) -> Self {
+        self.preview = Some(image);
+        self
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> Result<(), ZrRawError> {
+        let bytes = self.to_bytes()?;
+        out.write_all(&bytes).map_err(|_| ZrRawError::IoError)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ZrRawError> {
+        let sensor = self.sensor;
+        let samples = sensor.samples_per_pixel as usize;
+        if sensor.width == 0 || sensor.height == 0 || !(samples == 1 || samples == 3) {
+            return Err(ZrRawError::InvalidInput);
+        }
+        if sensor.data.len() != sensor.width as usize * sensor.height as usize * samples {
+            return Err(ZrRawError::InvalidInput);
+        }
+        if samples == 1 && sensor.cfa.colors.len() != (sensor.cfa.width * sensor.cfa.height) as usize {
+            return Err(ZrRawError::InvalidInput);
+        }
+
+        let mut tiff = TiffWriter::new();
+        let mut raw = self.raw_ifd(&mut tiff)?;
+        let exif = tiff.ifd(self.exif_ifd())?;
+
+        let mut ifd0 = vec![
+            (MAKE, Field::Ascii(self.metadata.make.clone())),
+            (MODEL, Field::Ascii(self.metadata.model.clone())),
+            (ORIENTATION, Field::Short(vec![self.metadata.orientation.to_exif()])),
+            (SOFTWARE, Field::Ascii(concat!("zrraw ", env!("CARGO_PKG_VERSION")).to_string())),
+            (EXIF_IFD, Field::Long(vec![exif])),
+            (DNG_VERSION, Field::Byte(vec![1, 4, 0, 0])),
+            (DNG_BACKWARD_VERSION, Field::Byte(vec![1, 1, 0, 0])),
+            (UNIQUE_CAMERA_MODEL, Field::Ascii(unique_camera_model(self.metadata))),
+            (AS_SHOT_NEUTRAL, Field::Rational(as_shot_neutral(&self.metadata.white_balance))),
+            (NEW_RAW_IMAGE_DIGEST, Field::Byte(new_raw_image_digest(sensor).to_vec())),
+        ];
+        ifd0.extend(color_fields(self.metadata));
+        let gps = copied_tags(self.metadata.exif(), Directory::Gps);
+        if !gps.is_empty() {
+            ifd0.push((GPS_IFD, Field::Long(vec![tiff.ifd(gps)?])));
+        }
+
+        match self.preview {
+            Some(image) => {
+                let preview_fields = preview_ifd(&mut tiff, image)?;
+                let preview = tiff.ifd(preview_fields)?;
+                let raw_offset = tiff.ifd(raw)?;
+                ifd0.push((SUB_IFDS, Field::Long(vec![raw_offset, preview])));
+                ifd0.extend(thumbnail_fields(&mut tiff, image)?);
+            }
+            None => ifd0.append(&mut raw),
+        }
+
+        let first = tiff.ifd(ifd0)?;
+        Ok(tiff.finish(first))
+    }
+
+    fn raw_ifd(&self, tiff: &mut TiffWriter) -> Result<Vec<(u16, Field)>, ZrRawError> {
+        let sensor = self.sensor;
+        let metadata = self.metadata;
+        let samples = sensor.samples_per_pixel;
+        let cfa = samples == 1;
+
+        let mut fields = vec![
+            (NEW_SUBFILE_TYPE, Field::Long(vec![0])),
+            (IMAGE_WIDTH, Field::Long(vec![sensor.width])),
+            (IMAGE_LENGTH, Field::Long(vec![sensor.height])),
+            (PHOTOMETRIC, Field::Short(vec![if cfa { PHOTOMETRIC_CFA } else { PHOTOMETRIC_LINEAR_RAW }])),
+            (SAMPLES_PER_PIXEL, Field::Short(vec![samples as u16])),
+            (PLANAR_CONFIGURATION, Field::Short(vec![1])),
+        ];
+
+        if cfa {
+            let pattern = &sensor.cfa;
+            fields.extend([
+                (CFA_REPEAT_PATTERN_DIM, Field::Short(vec![pattern.height as u16, pattern.width as u16])),
+                (CFA_PATTERN, Field::Byte(pattern.colors.clone())),
+                (CFA_PLANE_COLOR, Field::Byte(vec![0, 1, 2])),
+                (CFA_LAYOUT, Field::Short(vec![1])),
+                (BLACK_LEVEL_REPEAT_DIM, Field::Short(vec![2, 2])),
+                (BLACK_LEVEL, Field::Rational(metadata.black_level.iter().map(|&b| level(b)).collect())),
+            ]);
+        } else {
+            fields.extend([
+                (BLACK_LEVEL_REPEAT_DIM, Field::Short(vec![1, 1])),
+                (BLACK_LEVEL, Field::Rational(vec![level(metadata.black_level[0]); 3])),
+            ]);
+        }
+
+        let precision = precision(sensor);
+        let white = match metadata.white_level[0] {
+            0 => (1u32 << precision) - 1,
+            w => w,
+        };
+        fields.push((WHITE_LEVEL, Field::Long(vec![white; samples as usize])));
+
+        match self.compression {
+            DngCompression::Uncompressed => {
+                let bytes: Vec<u8> = sensor.data.iter().flat_map(|v| v.to_le_bytes()).collect();
+                let count = bytes.len() as u32;
+                let offset = tiff.blob(&bytes)?;
+                fields.extend([
+                    (BITS_PER_SAMPLE, Field::Short(vec![16; samples as usize])),
+                    (COMPRESSION, Field::Short(vec![COMPRESSION_NONE])),
+                    (STRIP_OFFSETS, Field::Long(vec![offset])),
+                    (ROWS_PER_STRIP, Field::Long(vec![sensor.height])),
+                    (STRIP_BYTE_COUNTS, Field::Long(vec![count])),
+                ]);
+            }
+            DngCompression::LosslessJpeg => {
+                let mut offsets = Vec::new();
+                let mut counts = Vec::new();
+                for y in (0..sensor.height).step_by(TILE_SIZE as usize) {
+                    for x in (0..sensor.width).step_by(TILE_SIZE as usize) {
+                        let tile = encode_tile(sensor, x, y, precision)?;
+                        offsets.push(tiff.blob(&tile)?);
+                        counts.push(tile.len() as u32);
+                    }
+                }
+                fields.extend([
+                    (BITS_PER_SAMPLE, Field::Short(vec![precision as u16; samples as usize])),
+                    (COMPRESSION, Field::Short(vec![COMPRESSION_JPEG])),
+                    (TILE_WIDTH, Field::Long(vec![TILE_SIZE])),
+                    (TILE_LENGTH, Field::Long(vec![TILE_SIZE])),
+                    (TILE_OFFSETS, Field::Long(offsets)),
+                    (TILE_BYTE_COUNTS, Field::Long(counts)),
+                ]);
+            }
+        }
+        Ok(fields)
+    }
+
+    /// The source file's EXIF directory, with the decoded capture settings
+    /// in place of the file's own
+    fn exif_ifd(&self) -> Vec<(u16, Field)> {
+        let metadata = self.metadata;
+        let mut fields = vec![(ISO, Field::Short(vec![metadata.iso.min(u16::MAX as u32) as u16]))];
+        if metadata.shutter_speed.is_known() {
+            let time = metadata.shutter_speed.0;
+            fields.push((EXPOSURE_TIME, Field::Rational(vec![(time.num, time.den)])));
+        }
+        if metadata.aperture.is_known() {
+            let f_number = metadata.aperture.0;
+            fields.push((F_NUMBER, Field::Rational(vec![(f_number.num, f_number.den)])));
+        }
+        if metadata.focal_length > 0.0 {
+            fields.push((FOCAL_LENGTH, Field::Rational(vec![rational(metadata.focal_length, 100)])));
+        }
+        for (tag, field) in copied_tags(metadata.exif(), Directory::Exif) {
+            if !fields.iter().any(|(own, _)| *own == tag) {
+                fields.push((tag, field));
+            }
+        }
+        if !fields.iter().any(|(tag, _)| *tag == EXIF_VERSION) {
+            fields.push((EXIF_VERSION, Field::Undefined(b"0230".to_vec())));
+        }
+        fields
+    }
+}
+
+/// The tags of one directory of the source file, first of each id only.
+/// Pointers to other directories are left out, and so is the makernote,
+/// whose internal offsets would no longer hold.
+fn copied_tags(exif: &Exif, directory: Directory) -> Vec<(u16, Field)> {
+    let mut fields: Vec<(u16, Field)> = Vec::new();
+    for tag in exif.tags().iter().filter(|tag| tag.directory == directory) {
+        let skipped = matches!(tag.id, EXIF_IFD | GPS_IFD | INTEROP_IFD | MAKER_NOTE);
+        if skipped || fields.iter().any(|(id, _)| *id == tag.id) {
+            continue;
+        }
+        fields.push((tag.id, Field::from(&tag.value)));
+    }
+    fields
+}
+
+/// NewRawImageDigest as defined by the DNG SDK: the MD5 of the MD5s of each
+/// 256x256 tile of the stored samples (little-endian, interleaved), in
+/// row-major tile order
+pub fn new_raw_image_digest(sensor: &SensorData) -> [u8; 16] {
+    let width = sensor.width as usize;
+    let samples = sensor.samples_per_pixel as usize;
+    let tile = TILE_SIZE as usize;
+
+    let mut digests = Md5::new();
+    for y in (0..sensor.height as usize).step_by(tile) {
+        for x in (0..width).step_by(tile) {
+            let mut hasher = Md5::new();
+            for row in y..(y + tile).min(sensor.height as usize) {
+                let start = (row * width + x) * samples;
+                let end = (row * width + (x + tile).min(width)) * samples;
+                for v in &sensor.data[start..end] {
+                    hasher.update(v.to_le_bytes());
+                }
+            }
+            digests.update(hasher.finalize());
+        }
+    }
+    digests.finalize().into()
+}
+
+/// Smallest sample precision that holds both the declared bit depth and the data
+fn precision(sensor: &SensorData) -> u32 {
+    let max = sensor.data.iter().copied().max().unwrap_or(0);
+    let needed = 16 - max.leading_zeros();
+    needed.max(sensor.bits_per_sample.min(16)).max(2)
+}
+
+/// One lossless JPEG tile. CFA tiles are coded as two components of half
+/// the width so that each sample predicts from the same colour; edge tiles
+/// are padded by repeating the last row and column.
+fn encode_tile(sensor: &SensorData, x: u32, y: u32, precision: u32) -> Result<Vec<u8>, ZrRawError> {
+    let samples = sensor.samples_per_pixel as usize;
+    let mut tile = Vec::with_capacity((TILE_SIZE * TILE_SIZE) as usize * samples);
+    for row in 0..TILE_SIZE {
+        let src_row = (y + row).min(sensor.height - 1) as usize;
+        for col in 0..TILE_SIZE {
+            let src_col = (x + col).min(sensor.width - 1) as usize;
+            let start = (src_row * sensor.width as usize + src_col) * samples;
+            tile.extend_from_slice(&sensor.data[start..start + samples]);
+        }
+    }
+
+    let components = if samples == 1 { 2 } else { samples as u32 };
+    let frame = ljpeg::Frame { width: TILE_SIZE * samples as u32 / components, height: TILE_SIZE, components, precision };
+    ljpeg::Encoder::new(&tile, frame).encode()
+}
+
+/// Large preview as a baseline JPEG in its own IFD
+fn preview_ifd(tiff: &mut TiffWriter, image: &DynamicImage) -> Result<Vec<(u16, Field)>, ZrRawError> {
+    let rgb = fit(image, PREVIEW_SIZE);
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 90)
+        .encode_image(&rgb)
+        .map_err(|_| ZrRawError::InvalidInput)?;
+    let offset = tiff.blob(&jpeg)?;
+
+    Ok(vec![
+        (NEW_SUBFILE_TYPE, Field::Long(vec![1])),
+        (IMAGE_WIDTH, Field::Long(vec![rgb.width()])),
+        (IMAGE_LENGTH, Field::Long(vec![rgb.height()])),
+        (BITS_PER_SAMPLE, Field::Short(vec![8; 3])),
+        (COMPRESSION, Field::Short(vec![COMPRESSION_JPEG])),
+        (PHOTOMETRIC, Field::Short(vec![PHOTOMETRIC_YCBCR])),
+        (STRIP_OFFSETS, Field::Long(vec![offset])),
+        (SAMPLES_PER_PIXEL, Field::Short(vec![3])),
+        (ROWS_PER_STRIP, Field::Long(vec![rgb.height()])),
+        (STRIP_BYTE_COUNTS, Field::Long(vec![jpeg.len() as u32])),
+        (PLANAR_CONFIGURATION, Field::Short(vec![1])),
+        (PREVIEW_COLOR_SPACE, Field::Long(vec![PREVIEW_SRGB])),
+    ])
+}
+
+/// Uncompressed 8-bit RGB thumbnail fields for IFD0
+fn thumbnail_fields(tiff: &mut TiffWriter, image: &DynamicImage) -> Result<Vec<(u16, Field)>, ZrRawError> {
+    let rgb = fit(image, THUMBNAIL_SIZE);
+    let offset = tiff.blob(rgb.as_raw())?;
+
+    Ok(vec![
+        (NEW_SUBFILE_TYPE, Field::Long(vec![1])),
+        (IMAGE_WIDTH, Field::Long(vec![rgb.width()])),
+        (IMAGE_LENGTH, Field::Long(vec![rgb.height()])),
+        (BITS_PER_SAMPLE, Field::Short(vec![8; 3])),
+        (COMPRESSION, Field::Short(vec![COMPRESSION_NONE])),
+        (PHOTOMETRIC, Field::Short(vec![PHOTOMETRIC_RGB])),
+        (STRIP_OFFSETS, Field::Long(vec![offset])),
+        (SAMPLES_PER_PIXEL, Field::Short(vec![3])),
+        (ROWS_PER_STRIP, Field::Long(vec![rgb.height()])),
+        (STRIP_BYTE_COUNTS, Field::Long(vec![rgb.as_raw().len() as u32])),
+        (PLANAR_CONFIGURATION, Field::Short(vec![1])),
+    ])
+}
+
+/// 8-bit RGB copy no larger than `size` on its long side
+fn fit(image: &DynamicImage, size: u32) -> RgbImage {
+    if image.width() > size || image.height() > size {
+        image.thumbnail(size, size).to_rgb8()
+    } else {
+        image.to_rgb8()
+    }
+}
+
+fn unique_camera_model(metadata: &RawMetadata) -> String {
+    if metadata.model.starts_with(&metadata.make) {
+        metadata.model.clone()
+    } else {
+        format!("{} {}", metadata.make, metadata.model).trim().to_string()
+    }
+}
+
+/// The calibrations of the source file, up to the two DNG 1.4 holds, or
+/// its single camera to XYZ matrix as a D65 ColorMatrix1
+fn color_fields(metadata: &RawMetadata) -> Vec<(u16, Field)> {
+    let Some(profile) = metadata.extended.color.as_ref().filter(|p| !p.calibrations.is_empty()) else {
+        return vec![
+            (COLOR_MATRIX_1, Field::SRational(color_matrix(&metadata.color_matrix))),
+            (CALIBRATION_ILLUMINANT_1, Field::Short(vec![ILLUMINANT_D65])),
+        ];
+    };
+
+    let tags = [
+        (COLOR_MATRIX_1, CALIBRATION_ILLUMINANT_1, CAMERA_CALIBRATION_1, FORWARD_MATRIX_1),
+        (COLOR_MATRIX_2, CALIBRATION_ILLUMINANT_2, CAMERA_CALIBRATION_2, FORWARD_MATRIX_2),
+    ];
+    let mut fields = Vec::new();
+    for (calibration, (matrix, illuminant, camera, forward)) in profile.calibrations.iter().zip(tags) {
+        fields.push((matrix, Field::SRational(srationals(&calibration.color_matrix))));
+        fields.push((illuminant, Field::Short(vec![calibration.illuminant.0])));
+        if let Some(m) = &calibration.camera_calibration {
+            fields.push((camera, Field::SRational(srationals(m))));
+        }
+        if let Some(m) = &calibration.forward_matrix {
+            fields.push((forward, Field::SRational(srationals(m))));
+        }
+    }
+    if profile.analog_balance != [1.0; 3] {
+        fields.push((ANALOG_BALANCE, Field::Rational(profile.analog_balance.iter().map(|&v| rational(v, 10000)).collect())));
+    }
+    fields
+}
+
+/// ColorMatrix1 maps XYZ to camera space, the inverse of the metadata's
+/// camera to XYZ matrix. Without calibration the identity keeps the file
+/// valid for readers that require the tag.
+fn color_matrix(cam_to_xyz: &[f32; 9]) -> Vec<(i32, i32)> {
+    let inverse = invert_3x3(cam_to_xyz.map(|v| v as f64)).unwrap_or([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    inverse.iter().map(|&v| ((v * 10000.0).round() as i32, 10000)).collect()
+}
+
+fn srationals(m: &[f32; 9]) -> Vec<(i32, i32)> {
+    m.iter().map(|&v| ((v as f64 * 10000.0).round() as i32, 10000)).collect()
+}
+
+/// Inverse of a row-major 3x3 matrix, None if it is singular
+pub(crate) fn invert_3x3(m: [f64; 9]) -> Option<[f64; 9]> {
+    let det = m[0] * (m[4] * m[8] - m[5] * m[7]) - m[1] * (m[3] * m[8] - m[5] * m[6])
+        + m[2] * (m[3] * m[7] - m[4] * m[6]);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    Some([
+        (m[4] * m[8] - m[5] * m[7]) / det,
+        (m[2] * m[7] - m[1] * m[8]) / det,
+        (m[1] * m[5] - m[2] * m[4]) / det,
+        (m[5] * m[6] - m[3] * m[8]) / det,
+        (m[0] * m[8] - m[2] * m[6]) / det,
+        (m[2] * m[3] - m[0] * m[5]) / det,
+        (m[3] * m[7] - m[4] * m[6]) / det,
+        (m[1] * m[6] - m[0] * m[7]) / det,
+        (m[0] * m[4] - m[1] * m[3]) / det,
+    ])
+}
+
+/// The camera's response to white is the inverse of the multipliers
+fn as_shot_neutral(wb: &[f32; 3]) -> Vec<(u32, u32)> {
+    if !wb.iter().all(|&w| w > 0.0) {
+        return vec![(1, 1); 3];
+    }
+    let scaled = |v: f32| (v as f64 * 1_000_000.0).round() as u32;
+    vec![(scaled(wb[1]), scaled(wb[0])), (1, 1), (scaled(wb[1]), scaled(wb[2]))]
+}
+
+fn level(value: f32) -> (u32, u32) {
+    rational(value.max(0.0), 100)
+}
+
+fn rational(value: f32, denominator: u32) -> (u32, u32) {
+    ((value as f64 * denominator as f64).round() as u32, denominator)
+}
+
+enum Field {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SRational(Vec<(i32, i32)>),
+    Undefined(Vec<u8>),
+    SByte(Vec<i8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+impl From<&ExifValue> for Field {
+    fn from(value: &ExifValue) -> Self {
+        match value {
+            ExifValue::Byte(v) => Field::Byte(v.clone()),
+            ExifValue::Ascii(s) => Field::Ascii(s.clone()),
+            ExifValue::Short(v) => Field::Short(v.clone()),
+            ExifValue::Long(v) => Field::Long(v.clone()),
+            ExifValue::Rational(v) => Field::Rational(v.iter().map(|r| (r.num, r.den)).collect()),
+            ExifValue::SByte(v) => Field::SByte(v.clone()),
+            ExifValue::Undefined(v) => Field::Undefined(v.clone()),
+            ExifValue::SShort(v) => Field::SShort(v.clone()),
+            ExifValue::SLong(v) => Field::SLong(v.clone()),
+            ExifValue::SRational(v) => Field::SRational(v.iter().map(|r| (r.num, r.den)).collect()),
+            ExifValue::Float(v) => Field::Float(v.clone()),
+            ExifValue::Double(v) => Field::Double(v.clone()),
+        }
+    }
+}
+
+impl Field {
+    /// (type, count, little-endian value bytes)
+    fn encode(self) -> (u16, u32, Vec<u8>) {
+        match self {
+            Field::Byte(v) => (BYTE, v.len() as u32, v),
+            Field::Ascii(s) => {
+                let mut v = s.into_bytes();
+                v.push(0);
+                (ASCII, v.len() as u32, v)
+            }
+            Field::Short(v) => (SHORT, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+            Field::Long(v) => (LONG, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+            Field::Rational(v) => (
+                RATIONAL,
+                v.len() as u32,
+                v.iter().flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect(),
+            ),
+            Field::SRational(v) => (
+                SRATIONAL,
+                v.len() as u32,
+                v.iter().flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect(),
+            ),
+            Field::Undefined(v) => (UNDEFINED, v.len() as u32, v),
+            Field::SByte(v) => (SBYTE, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+            Field::SShort(v) => (SSHORT, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+            Field::SLong(v) => (SLONG, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+            Field::Float(v) => (FLOAT, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+            Field::Double(v) => (DOUBLE, v.len() as u32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+        }
+    }
+}
+
+/// Little-endian TIFF serialiser. Image data is appended first so that every
+/// IFD can be written once with its final offsets.
+struct TiffWriter {
+    buf: Vec<u8>,
+}
+
+impl TiffWriter {
+    fn new() -> Self {
+        TiffWriter { buf: vec![b'I', b'I', 42, 0, 0, 0, 0, 0] }
+    }
+
+    fn position(&mut self) -> Result<u32, ZrRawError> {
+        if self.buf.len() % 2 == 1 {
+            self.buf.push(0);
+        }
+        u32::try_from(self.buf.len()).map_err(|_| ZrRawError::InvalidInput)
+    }
+
+    fn blob(&mut self, bytes: &[u8]) -> Result<u32, ZrRawError> {
+        let offset = self.position()?;
+        self.buf.extend_from_slice(bytes);
+        Ok(offset)
+    }
+
+    fn ifd(&mut self, mut fields: Vec<(u16, Field)>) -> Result<u32, ZrRawError> {
+        fields.sort_by_key(|(tag, _)| *tag);
+        let start = self.position()?;
+        let entries = start as usize + 2;
+        self.buf.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+        self.buf.resize(entries + fields.len() * 12 + 4, 0);
+
+        for (i, (tag, field)) in fields.into_iter().enumerate() {
+            let (ty, count, value) = field.encode();
+            let pos = entries + i * 12;
+            self.buf[pos..pos + 2].copy_from_slice(&tag.to_le_bytes());
+            self.buf[pos + 2..pos + 4].copy_from_slice(&ty.to_le_bytes());
+            self.buf[pos + 4..pos + 8].copy_from_slice(&count.to_le_bytes());
+            if value.len() <= 4 {
+                self.buf[pos + 8..pos + 8 + value.len()].copy_from_slice(&value);
+            } else {
+                let offset = self.blob(&value)?;
+                self.buf[pos + 8..pos + 12].copy_from_slice(&offset.to_le_bytes());
+            }
+        }
+        Ok(start)
+    }
+
+    fn finish(mut self, first_ifd: u32) -> Vec<u8> {
+        self.buf[4..8].copy_from_slice(&first_ifd.to_le_bytes());
+        self.buf
+    }
+}
@@ -0,0 +1,568 @@
+// bindings/rust/zrraw/src/edit.rs - In-place metadata edits of TIFF-based raw files
+use crate::exif::{self, DateTime, Directory, Exif, ExifValue, GpsPosition, Rational, Tiff};
+use crate::xmp::{Xmp, NS_DC, NS_XMP};
+use crate::ZrRawError;
+use std::collections::HashSet;
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_INTEROP_IFD: u16 = 0xA005;
+const TAG_XML_PACKET: u16 = 0x02BC;
+const TAG_RATING: u16 = 0x4746;
+
+/// Tags that locate other data; rewriting them would orphan it
+const STRUCTURAL: &[u16] = &[
+    0x0111, 0x0117, 0x0144, 0x0145, TAG_SUB_IFDS, 0x0201, 0x0202, TAG_EXIF_IFD, TAG_GPS_IFD, 0x927C, TAG_INTEROP_IFD,
+];
+
+/// (offset, length) tag pairs of image data
+const IMAGE_DATA: &[(u16, u16)] = &[(0x0111, 0x0117), (0x0144, 0x0145), (0x0201, 0x0202)];
+
+const NS_EXIF: &str = "http://ns.adobe.com/exif/1.0/";
+const NS_PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
+
+/// Metadata changes for a TIFF-based raw (NEF, ARW, CR2, DNG, ORF, RW2, PEF, ...).
+///
+/// Values that keep their size are overwritten where they are. A directory
+/// that gains entries or larger values is copied to the end of the file and
+/// the pointer to it is relinked. The copied entries keep their offsets, so
+/// the makernote and the image data stay where they were.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataEdit {
+    values: Vec<(Directory, u16, ExifValue)>,
+    date_shift: i64,
+    artist: Option<String>,
+    copyright: Option<String>,
+    rating: Option<i32>,
+}
+
+impl MetadataEdit {
+    pub fn new() -> Self {
+        MetadataEdit::default()
+    }
+
+    /// Moves DateTime, DateTimeOriginal and DateTimeDigitized, and the XMP
+    /// dates, by `seconds`, as when fixing a camera clock
+    pub fn shift_dates(mut self, seconds: i64) -> Self {
+        self.date_shift = seconds;
+        self
+    }
+
+    /// Artist, and dc:creator of an embedded XMP packet
+    pub fn artist(mut self, artist: &str) -> Self {
+        self.artist = Some(artist.to_string());
+        self
+    }
+
+    /// Copyright, and dc:rights of an embedded XMP packet
+    pub fn copyright(mut self, copyright: &str) -> Self {
+        self.copyright = Some(copyright.to_string());
+        self
+    }
+
+    /// xmp:Rating of the embedded packet, which is added if missing, and
+    /// the IFD0 Rating tag. -1 marks a reject.
+    pub fn rating(mut self, rating: i32) -> Self {
+        self.rating = Some(rating.clamp(-1, 5));
+        self
+    }
+
+    /// Replaces the position in the GPS directory, creating it if needed
+    pub fn gps(mut self, position: GpsPosition) -> Self {
+        let total = |degrees: f64| (degrees.abs() * 3_600_000.0).round() as u64;
+        let dms = |milliseconds: u64| {
+            let whole = |v: u64| Rational::new(v as u32, 1);
+            let seconds = Rational::new((milliseconds % 60_000) as u32, 1000);
+            ExifValue::Rational(vec![whole(milliseconds / 3_600_000), whole(milliseconds / 60_000 % 60), seconds])
+        };
+        let reference = |negative: bool, names: [&str; 2]| ExifValue::Ascii(names[negative as usize].to_string());
+        self.values.extend([
+            (Directory::Gps, 0x0000, ExifValue::Byte(vec![2, 3, 0, 0])),
+            (Directory::Gps, 0x0001, reference(position.latitude < 0.0, ["N", "S"])),
+            (Directory::Gps, 0x0002, dms(total(position.latitude))),
+            (Directory::Gps, 0x0003, reference(position.longitude < 0.0, ["E", "W"])),
+            (Directory::Gps, 0x0004, dms(total(position.longitude))),
+        ]);
+        if let Some(altitude) = position.altitude {
+            let millimetres = (altitude.abs() * 1000.0).round() as u32;
+            self.values.extend([
+                (Directory::Gps, 0x0005, ExifValue::Byte(vec![(altitude < 0.0) as u8])),
+                (Directory::Gps, 0x0006, ExifValue::Rational(vec![Rational::new(millimetres, 1000)])),
+            ]);
+        }
+        self
+    }
+
+    /// Sets any other tag of IFD0 or the EXIF, GPS and Interop directories
+    pub fn set(mut self, directory: Directory, id: u16, value: ExifValue) -> Self {
+        self.values.push((directory, id, value));
+        self
+    }
+
+    /// Works out the bytes the edit writes without changing anything
+    pub fn plan(&self, data: &[u8]) -> Result<EditPlan, ZrRawError> {
+        let tiff = Tiff::open(data).ok_or(ZrRawError::UnsupportedFormat)?;
+        let exif = Exif::read(data);
+        let mut changes = self.values.clone();
+        if changes.iter().any(|(directory, id, _)| *directory != Directory::Gps && STRUCTURAL.contains(id)) {
+            return Err(ZrRawError::InvalidInput);
+        }
+        if self.date_shift != 0 {
+            for (directory, id) in [(Directory::Ifd0, 0x0132), (Directory::Exif, 0x9003), (Directory::Exif, 0x9004)] {
+                let Some(text) = exif.get(directory, id).and_then(|tag| tag.value.as_str()) else { continue };
+                let shifted = shift_timestamp(text, self.date_shift).ok_or(ZrRawError::InvalidInput)?;
+                changes.push((directory, id, ExifValue::Ascii(shifted)));
+            }
+        }
+        if let Some(artist) = &self.artist {
+            changes.push((Directory::Ifd0, 0x013B, ExifValue::Ascii(artist.clone())));
+        }
+        if let Some(copyright) = &self.copyright {
+            changes.push((Directory::Ifd0, 0x8298, ExifValue::Ascii(copyright.clone())));
+        }
+        if let Some(rating) = self.rating {
+            changes.push((Directory::Ifd0, TAG_RATING, ExifValue::Short(vec![rating.max(0) as u16])));
+        }
+        if let Some(packet) = self.xmp_packet(data, &exif)? {
+            let byte = exif.get(Directory::Ifd0, TAG_XML_PACKET).is_none_or(|tag| tag.tiff_type != 7);
+            let value = if byte { ExifValue::Byte(packet) } else { ExifValue::Undefined(packet) };
+            changes.push((Directory::Ifd0, TAG_XML_PACKET, value));
+        }
+
+        let protected = image_data_ranges(data);
+        let mut plan = Planner { tiff: &tiff, protected, patches: Vec::new(), appended: Vec::new() };
+        if let Some(makernote) = exif::makernote_location(data) {
+            plan.protected.push(makernote.start..makernote.start + makernote.len);
+        }
+        // Children first, so a parent that is rewritten anyway takes the
+        // new pointer in the same pass
+        for (directory, parent, pointer) in [
+            (Directory::Interop, Directory::Exif, TAG_INTEROP_IFD),
+            (Directory::Gps, Directory::Ifd0, TAG_GPS_IFD),
+            (Directory::Exif, Directory::Ifd0, TAG_EXIF_IFD),
+            (Directory::Ifd0, Directory::Ifd0, 0),
+        ] {
+            let mut values: Vec<(u16, ExifValue)> = Vec::new();
+            for (_, id, value) in changes.iter().filter(|(d, _, _)| *d == directory) {
+                values.retain(|(existing, _)| existing != id);
+                values.push((*id, value.clone()));
+            }
+            if values.is_empty() {
+                continue;
+            }
+            let offset = directory_offset(&tiff, directory);
+            let Some(moved) = plan.directory(offset, &values)? else { continue };
+            if directory == Directory::Ifd0 {
+                plan.patch(4, tiff.u32_bytes(moved).to_vec())?;
+            } else {
+                changes.push((parent, pointer, ExifValue::Long(vec![moved])));
+            }
+        }
+
+        let original_len = data.len();
+        let mut edit = EditPlan { original_len, changes: Vec::new() };
+        for (offset, bytes) in plan.patches {
+            edit.changes.extend(differing_runs(offset, &data[offset..offset + bytes.len()], &bytes));
+        }
+        edit.changes.sort_by_key(|change| change.offset);
+        if !plan.appended.is_empty() {
+            edit.changes.push(Change { offset: original_len as u64, before: Vec::new(), after: plan.appended });
+        }
+        Ok(edit)
+    }
+
+    /// Plans the edit of a file and, unless `dry_run`, writes it in place
+    pub fn apply_to_file(&self, path: impl AsRef<Path>, dry_run: bool) -> Result<EditPlan, ZrRawError> {
+        let data = std::fs::read(&path).map_err(|_| ZrRawError::IoError)?;
+        let plan = self.plan(&data)?;
+        if !dry_run {
+            plan.write_to_file(path)?;
+        }
+        Ok(plan)
+    }
+
+    /// The embedded packet with this edit applied, if it changes
+    fn xmp_packet(&self, data: &[u8], exif: &Exif) -> Result<Option<Vec<u8>>, ZrRawError> {
+        let existing = Xmp::from_file(data, exif);
+        let Some(mut xmp) = existing.clone().or_else(|| self.rating.map(|_| Xmp::new())) else { return Ok(None) };
+        if let Some(rating) = self.rating {
+            xmp.set_text(NS_XMP, "Rating", &rating.to_string());
+        }
+        if let Some(artist) = &self.artist {
+            xmp.set_array(NS_DC, "creator", std::slice::from_ref(artist), true);
+        }
+        if let Some(copyright) = &self.copyright {
+            xmp.set_default_text(NS_DC, "rights", copyright);
+        }
+        if self.date_shift != 0 {
+            let dates = [
+                (NS_XMP, "CreateDate"),
+                (NS_XMP, "ModifyDate"),
+                (NS_EXIF, "DateTimeOriginal"),
+                (NS_PHOTOSHOP, "DateCreated"),
+            ];
+            for (namespace, name) in dates {
+                let Some(text) = xmp.text(namespace, name) else { continue };
+                let shifted = shift_timestamp(&text, self.date_shift).ok_or(ZrRawError::InvalidInput)?;
+                xmp.set_text(namespace, name, &shifted);
+            }
+        }
+        Ok((existing.as_ref() != Some(&xmp)).then(|| xmp.to_bytes()))
+    }
+}
+
+/// A run of bytes an edit writes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub offset: u64,
+    /// The bytes there now; empty for data appended to the file
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// The bytes an edit writes: overwritten runs in file order, then any
+/// appended data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditPlan {
+    original_len: usize,
+    changes: Vec<Change>,
+}
+
+impl EditPlan {
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn apply(&self, data: &mut Vec<u8>) -> Result<(), ZrRawError> {
+        if data.len() != self.original_len {
+            return Err(ZrRawError::InvalidInput);
+        }
+        for change in &self.changes {
+            let offset = change.offset as usize;
+            if change.before.is_empty() {
+                data.extend_from_slice(&change.after);
+            } else {
+                data[offset..offset + change.after.len()].copy_from_slice(&change.after);
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends before patching, so the new directories are complete on
+    /// disk before anything points at them
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), ZrRawError> {
+        let io = |_| ZrRawError::IoError;
+        let mut file = std::fs::OpenOptions::new().write(true).open(path).map_err(io)?;
+        if file.metadata().map_err(io)?.len() != self.original_len as u64 {
+            return Err(ZrRawError::InvalidInput);
+        }
+        for change in self.changes.iter().filter(|c| c.before.is_empty()) {
+            file.seek(SeekFrom::Start(change.offset)).map_err(io)?;
+            file.write_all(&change.after).map_err(io)?;
+        }
+        file.sync_data().map_err(io)?;
+        for change in self.changes.iter().filter(|c| !c.before.is_empty()) {
+            file.seek(SeekFrom::Start(change.offset)).map_err(io)?;
+            file.write_all(&change.after).map_err(io)?;
+        }
+        file.sync_data().map_err(io)
+    }
+}
+
+/// Byte ranges of the strips, tiles and embedded JPEGs of every IFD and
+/// SubIFD; edits never write to them
+pub fn image_data_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let Some(tiff) = Tiff::open(data) else { return ranges };
+    let mut visited = HashSet::new();
+    let mut pending: Vec<usize> = tiff.u32(4).map(|first| vec![first as usize]).unwrap_or_default();
+    while let Some(ifd) = pending.pop() {
+        let Some(count) = tiff.u16(ifd).filter(|_| ifd != 0 && visited.insert(ifd)) else { continue };
+        let values = |id| {
+            let entry = tiff.find(ifd, id)?;
+            let value = tiff.value(entry, tiff.u16(entry + 2)?, tiff.u32(entry + 4)? as usize)?;
+            match value {
+                ExifValue::Short(v) => Some(v.into_iter().map(usize::from).collect::<Vec<_>>()),
+                ExifValue::Long(v) => Some(v.into_iter().map(|x| x as usize).collect()),
+                _ => None,
+            }
+        };
+        for &(offsets, lengths) in IMAGE_DATA {
+            if let (Some(offsets), Some(lengths)) = (values(offsets), values(lengths)) {
+                ranges.extend(offsets.iter().zip(lengths).map(|(&start, length)| start..start.saturating_add(length)));
+            }
+        }
+        pending.extend(values(TAG_SUB_IFDS).unwrap_or_default());
+        if let Some(next) = tiff.u32(ifd + 2 + count as usize * 12) {
+            pending.push(next as usize);
+        }
+    }
+    ranges
+}
+
+struct Planner<'a> {
+    tiff: &'a Tiff<'a>,
+    protected: Vec<Range<usize>>,
+    /// In-place writes as (offset, bytes)
+    patches: Vec<(usize, Vec<u8>)>,
+    appended: Vec<u8>,
+}
+
+impl Planner<'_> {
+    fn patch(&mut self, offset: usize, bytes: Vec<u8>) -> Result<(), ZrRawError> {
+        let range = offset..offset + bytes.len();
+        let overlaps = |r: &Range<usize>| r.start < range.end && range.start < r.end;
+        if range.end > self.tiff.data.len() || self.protected.iter().any(overlaps) {
+            return Err(ZrRawError::CorruptedData);
+        }
+        self.patches.push((offset, bytes));
+        Ok(())
+    }
+
+    /// Applies `values` to the directory at `offset`, or to a new one.
+    /// Returns the directory's new offset if it had to move.
+    fn directory(&mut self, offset: Option<usize>, values: &[(u16, ExifValue)]) -> Result<Option<u32>, ZrRawError> {
+        let encoded: Vec<(u16, u16, u32, Vec<u8>)> = values
+            .iter()
+            .map(|(id, value)| {
+                let (tiff_type, count, bytes) = encode(value, self.tiff.big_endian);
+                (*id, tiff_type, count, bytes)
+            })
+            .collect();
+        if let Some(offset) = offset {
+            let in_place: Option<Vec<(usize, Vec<u8>)>> =
+                encoded.iter().map(|(id, tiff_type, _, bytes)| self.slot(offset, *id, *tiff_type, bytes)).collect();
+            if let Some(writes) = in_place {
+                for (at, bytes) in writes {
+                    self.patch(at, bytes)?;
+                }
+                return Ok(None);
+            }
+        }
+        self.append_directory(offset, encoded).map(Some)
+    }
+
+    /// Where a value can be overwritten: an existing entry of the same
+    /// type and size
+    fn slot(&self, ifd: usize, id: u16, tiff_type: u16, bytes: &[u8]) -> Option<(usize, Vec<u8>)> {
+        let entry = self.tiff.find(ifd, id)?;
+        let old_type = self.tiff.u16(entry + 2)?;
+        // Pointers may be stored with the IFD type
+        if old_type != tiff_type && !(old_type == 13 && tiff_type == 4) {
+            return None;
+        }
+        if type_size(old_type)? * self.tiff.u32(entry + 4)? as usize != bytes.len() {
+            return None;
+        }
+        let at = if bytes.len() <= 4 { entry + 8 } else { self.tiff.u32(entry + 8)? as usize };
+        Some((at, bytes.to_vec()))
+    }
+
+    /// Copies the directory at `offset`, with `values` merged in, to the end
+    /// of the file; its other entries are copied byte for byte
+    fn append_directory(
+        &mut self,
+        offset: Option<usize>,
+        values: Vec<(u16, u16, u32, Vec<u8>)>,
+    ) -> Result<u32, ZrRawError> {
+        let tiff = self.tiff;
+        let (mut entries, next) = match offset {
+            Some(ifd) => {
+                let count = tiff.u16(ifd).ok_or(ZrRawError::CorruptedData)? as usize;
+                let end = ifd + 2 + count * 12;
+                let raw = tiff.data.get(ifd + 2..end + 4).ok_or(ZrRawError::CorruptedData)?;
+                let entries = raw[..count * 12]
+                    .chunks(12)
+                    .enumerate()
+                    .map(|(i, entry)| (tiff.u16(ifd + 2 + i * 12).unwrap_or(0), Entry::Raw(entry.to_vec())))
+                    .collect::<Vec<_>>();
+                (entries, raw[count * 12..].to_vec())
+            }
+            None => (Vec::new(), vec![0; 4]),
+        };
+        for (id, tiff_type, count, bytes) in values {
+            entries.retain(|(existing, _)| *existing != id);
+            entries.push((id, Entry::Value(tiff_type, count, bytes)));
+        }
+        entries.sort_by_key(|(id, _)| *id);
+
+        let base = self.tiff.data.len();
+        if (base + self.appended.len()) % 2 == 1 {
+            self.appended.push(0);
+        }
+        let start = base + self.appended.len();
+        let mut blob = start + 2 + entries.len() * 12 + 4;
+        let mut ifd = tiff.u16_bytes(entries.len() as u16).to_vec();
+        let mut values = Vec::new();
+        for (id, entry) in entries {
+            match entry {
+                Entry::Raw(raw) => ifd.extend(raw),
+                Entry::Value(tiff_type, count, bytes) => {
+                    ifd.extend(tiff.u16_bytes(id));
+                    ifd.extend(tiff.u16_bytes(tiff_type));
+                    ifd.extend(tiff.u32_bytes(count));
+                    if bytes.len() <= 4 {
+                        ifd.extend(&bytes);
+                        ifd.resize(ifd.len() + 4 - bytes.len(), 0);
+                    } else {
+                        ifd.extend(tiff.u32_bytes(u32::try_from(blob).map_err(|_| ZrRawError::InvalidInput)?));
+                        blob += bytes.len() + bytes.len() % 2;
+                        values.extend(&bytes);
+                        values.resize(values.len() + bytes.len() % 2, 0);
+                    }
+                }
+            }
+        }
+        ifd.extend(next);
+        u32::try_from(blob).map_err(|_| ZrRawError::InvalidInput)?;
+        self.appended.extend(ifd);
+        self.appended.extend(values);
+        Ok(start as u32)
+    }
+}
+
+enum Entry {
+    /// An unchanged entry, copied as it is
+    Raw(Vec<u8>),
+    /// (type, count, value bytes)
+    Value(u16, u32, Vec<u8>),
+}
+
+fn directory_offset(tiff: &Tiff, directory: Directory) -> Option<usize> {
+    let pointer = |ifd: usize, id| {
+        let entry = tiff.find(ifd, id)?;
+        tiff.u32(entry + 8).filter(|&offset| offset != 0).map(|offset| offset as usize)
+    };
+    let ifd0 = tiff.u32(4)? as usize;
+    match directory {
+        Directory::Ifd0 => Some(ifd0),
+        Directory::Exif => pointer(ifd0, TAG_EXIF_IFD),
+        Directory::Gps => pointer(ifd0, TAG_GPS_IFD),
+        Directory::Interop => pointer(pointer(ifd0, TAG_EXIF_IFD)?, TAG_INTEROP_IFD),
+    }
+}
+
+fn type_size(tiff_type: u16) -> Option<usize> {
+    match tiff_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// (type, count, value bytes in the file's byte order)
+fn encode(value: &ExifValue, big_endian: bool) -> (u16, u32, Vec<u8>) {
+    let short = |x: u16| if big_endian { x.to_be_bytes() } else { x.to_le_bytes() };
+    let long = |x: u32| if big_endian { x.to_be_bytes() } else { x.to_le_bytes() };
+    let (tiff_type, count, bytes) = match value {
+        ExifValue::Byte(v) => (1, v.len(), v.clone()),
+        ExifValue::Ascii(text) => {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(0);
+            (2, bytes.len(), bytes)
+        }
+        ExifValue::Short(v) => (3, v.len(), v.iter().flat_map(|&x| short(x)).collect()),
+        ExifValue::Long(v) => (4, v.len(), v.iter().flat_map(|&x| long(x)).collect()),
+        ExifValue::Rational(v) => (5, v.len(), v.iter().flat_map(|r| [long(r.num), long(r.den)].concat()).collect()),
+        ExifValue::SByte(v) => (6, v.len(), v.iter().map(|&x| x as u8).collect()),
+        ExifValue::Undefined(v) => (7, v.len(), v.clone()),
+        ExifValue::SShort(v) => (8, v.len(), v.iter().flat_map(|&x| short(x as u16)).collect()),
+        ExifValue::SLong(v) => (9, v.len(), v.iter().flat_map(|&x| long(x as u32)).collect()),
+        ExifValue::SRational(v) => {
+            (10, v.len(), v.iter().flat_map(|r| [long(r.num as u32), long(r.den as u32)].concat()).collect())
+        }
+        ExifValue::Float(v) => (11, v.len(), v.iter().flat_map(|x| long(x.to_bits())).collect()),
+        ExifValue::Double(v) => {
+            let bytes = v.iter().flat_map(|x| if big_endian { x.to_be_bytes() } else { x.to_le_bytes() }).collect();
+            (12, v.len(), bytes)
+        }
+    };
+    (tiff_type, count as u32, bytes)
+}
+
+/// The runs where `after` differs from `before`
+fn differing_runs(offset: usize, before: &[u8], after: &[u8]) -> Vec<Change> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < after.len() {
+        if before[i] == after[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < after.len() && before[i] != after[i] {
+            i += 1;
+        }
+        runs.push(Change {
+            offset: (offset + start) as u64,
+            before: before[start..i].to_vec(),
+            after: after[start..i].to_vec(),
+        });
+    }
+    runs
+}
+
+/// Moves an EXIF ("2024:05:01 10:00:00") or XMP ("2024-05-01T10:00:00")
+/// timestamp by `seconds`, keeping its separators and whatever follows
+/// the seconds
+fn shift_timestamp(text: &str, seconds: i64) -> Option<String> {
+    let date = DateTime::parse(text)?;
+    if !text.get(..19)?.is_ascii() {
+        return None;
+    }
+    let days = days_from_civil(date.year as i64, date.month as i64, date.day as i64);
+    let time = date.hour as i64 * 3600 + date.minute as i64 * 60 + date.second as i64;
+    let total = days * 86400 + time + seconds;
+    let (year, month, day) = civil_from_days(total.div_euclid(86400));
+    let time = total.rem_euclid(86400);
+    if !(1..=9999).contains(&year) {
+        return None;
+    }
+    let separator = |at: usize| &text[at..at + 1];
+    Some(format!(
+        "{year:04}{}{month:02}{}{day:02}{}{:02}{}{:02}{}{:02}{}",
+        separator(4),
+        separator(7),
+        separator(10),
+        time / 3600,
+        separator(13),
+        time / 60 % 60,
+        separator(16),
+        time % 60,
+        &text[19..]
+    ))
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar, after
+// Howard Hinnant's date algorithms
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
@@ -0,0 +1,972 @@
+// bindings/rust/zrraw/src/exif.rs - EXIF tags of IFD0 and the EXIF, GPS and Interop directories
+use std::fmt;
+
+/// The TIFF directory a tag was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Directory {
+    Ifd0,
+    Exif,
+    Gps,
+    Interop,
+}
+
+/// An unsigned TIFF RATIONAL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rational {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl Rational {
+    pub fn new(num: u32, den: u32) -> Self {
+        Rational { num, den }
+    }
+
+    /// None for a zero denominator, which cameras use for "unknown"
+    pub fn to_f64(self) -> Option<f64> {
+        (self.den != 0).then(|| self.num as f64 / self.den as f64)
+    }
+
+    /// The same value in lowest terms
+    pub fn reduced(self) -> Self {
+        let (mut a, mut b) = (self.num, self.den);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        if a <= 1 { self } else { Rational { num: self.num / a, den: self.den / a } }
+    }
+}
+
+/// A signed TIFF SRATIONAL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SRational {
+    pub num: i32,
+    pub den: i32,
+}
+
+impl SRational {
+    pub fn to_f64(self) -> Option<f64> {
+        (self.den != 0).then(|| self.num as f64 / self.den as f64)
+    }
+}
+
+/// A tag's values, decoded according to its TIFF type
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Byte(Vec<u8>),
+    /// ASCII up to the first NUL, trailing spaces removed
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<Rational>),
+    SByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<SRational>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+impl ExifValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ExifValue::Ascii(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The first value of an integer tag
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ExifValue::Byte(v) | ExifValue::Undefined(v) => v.first().map(|&x| x as u32),
+            ExifValue::Short(v) => v.first().map(|&x| x as u32),
+            ExifValue::Long(v) => v.first().copied(),
+            _ => None,
+        }
+    }
+
+    pub fn as_rational(&self) -> Option<Rational> {
+        match self {
+            ExifValue::Rational(v) => v.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// The first value of any numeric tag
+    pub fn as_f64(&self) -> Option<f64> {
+        self.f64_at(0)
+    }
+
+    fn f64_at(&self, index: usize) -> Option<f64> {
+        match self {
+            ExifValue::Byte(v) | ExifValue::Undefined(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::Short(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::Long(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::Rational(v) => v.get(index)?.to_f64(),
+            ExifValue::SByte(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::SShort(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::SLong(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::SRational(v) => v.get(index)?.to_f64(),
+            ExifValue::Float(v) => v.get(index).map(|&x| x as f64),
+            ExifValue::Double(v) => v.get(index).copied(),
+            ExifValue::Ascii(_) => None,
+        }
+    }
+}
+
+/// One entry of a directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExifTag {
+    pub directory: Directory,
+    pub id: u16,
+    /// The EXIF 3.0 / TIFF 6.0 name, None for private tags
+    pub name: Option<&'static str>,
+    /// TIFF field type as stored (3 = SHORT, 5 = RATIONAL, ...)
+    pub tiff_type: u16,
+    pub value: ExifValue,
+}
+
+/// A local or UTC date and time as EXIF records it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub nanosecond: u32,
+    /// Minutes east of UTC, if the file records the offset
+    pub offset_minutes: Option<i16>,
+}
+
+impl DateTime {
+    /// Parses "YYYY:MM:DD HH:MM:SS"
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let number = |range: std::ops::Range<usize>| text.get(range)?.trim().parse().ok();
+        if text.len() < 19 {
+            return None;
+        }
+        let date = DateTime {
+            year: number(0..4)?,
+            month: number(5..7)? as u8,
+            day: number(8..10)? as u8,
+            hour: number(11..13)? as u8,
+            minute: number(14..16)? as u8,
+            second: number(17..19)? as u8,
+            nanosecond: 0,
+            offset_minutes: None,
+        };
+        (date.month != 0).then_some(date)
+    }
+
+    /// Adds SubSecTime digits, which are a decimal fraction of a second
+    pub(crate) fn with_subsec(mut self, digits: Option<&str>) -> Self {
+        if let Some(digits) = digits.map(str::trim).filter(|d| !d.is_empty() && d.bytes().all(|b| b.is_ascii_digit())) {
+            let padded = format!("{:0<9}", &digits[..digits.len().min(9)]);
+            self.nanosecond = padded.parse().unwrap_or(0);
+        }
+        self
+    }
+
+    /// Adds an OffsetTime value such as "+02:00"
+    pub(crate) fn with_offset(mut self, offset: Option<&str>) -> Self {
+        let Some(offset) = offset.filter(|o| o.len() == 6) else { return self };
+        let sign = match offset.as_bytes()[0] {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return self,
+        };
+        // get() rather than indexing: a multi-byte character can straddle the fields
+        let (Some(hours), Some(minutes)) = (offset.get(1..3), offset.get(4..6)) else { return self };
+        if let (Ok(hours), Ok(minutes)) = (hours.parse::<i16>(), minutes.parse::<i16>()) {
+            self.offset_minutes = Some(sign * (hours * 60 + minutes));
+        }
+        self
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// ISO 8601, with the fraction and offset when known
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        if self.nanosecond != 0 {
+            let fraction = format!("{:09}", self.nanosecond);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        if let Some(offset) = self.offset_minutes {
+            let sign = if offset < 0 { '-' } else { '+' };
+            write!(f, "{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)?;
+        }
+        Ok(())
+    }
+}
+
+/// ExposureProgram (0x8822)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExposureProgram {
+    NotDefined,
+    Manual,
+    Normal,
+    AperturePriority,
+    ShutterPriority,
+    Creative,
+    Action,
+    Portrait,
+    Landscape,
+    Other(u16),
+}
+
+impl From<u16> for ExposureProgram {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => ExposureProgram::NotDefined,
+            1 => ExposureProgram::Manual,
+            2 => ExposureProgram::Normal,
+            3 => ExposureProgram::AperturePriority,
+            4 => ExposureProgram::ShutterPriority,
+            5 => ExposureProgram::Creative,
+            6 => ExposureProgram::Action,
+            7 => ExposureProgram::Portrait,
+            8 => ExposureProgram::Landscape,
+            other => ExposureProgram::Other(other),
+        }
+    }
+}
+
+/// MeteringMode (0x9207)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeteringMode {
+    Unknown,
+    Average,
+    CenterWeightedAverage,
+    Spot,
+    MultiSpot,
+    Pattern,
+    Partial,
+    Other(u16),
+}
+
+impl From<u16> for MeteringMode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => MeteringMode::Unknown,
+            1 => MeteringMode::Average,
+            2 => MeteringMode::CenterWeightedAverage,
+            3 => MeteringMode::Spot,
+            4 => MeteringMode::MultiSpot,
+            5 => MeteringMode::Pattern,
+            6 => MeteringMode::Partial,
+            other => MeteringMode::Other(other),
+        }
+    }
+}
+
+/// The Flash (0x9209) bit field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flash(pub u16);
+
+impl Flash {
+    pub fn fired(self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    /// 0 unknown, 1 compulsory firing, 2 compulsory suppression, 3 auto
+    pub fn mode(self) -> u8 {
+        ((self.0 >> 3) & 0x03) as u8
+    }
+
+    /// False when the camera reports having no flash function
+    pub fn present(self) -> bool {
+        self.0 & 0x20 == 0
+    }
+
+    pub fn red_eye_reduction(self) -> bool {
+        self.0 & 0x40 != 0
+    }
+}
+
+/// A position from the GPS directory, in degrees and metres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    /// Positive north of the equator
+    pub latitude: f64,
+    /// Positive east of Greenwich
+    pub longitude: f64,
+    /// Metres above sea level
+    pub altitude: Option<f64>,
+}
+
+// IFD0 pointers and the EXIF IFD's pointer to the Interop IFD
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_INTEROP_IFD: u16 = 0xA005;
+const TAG_MAKERNOTE: u16 = 0x927C;
+
+/// The tags of a file's IFD0 and the EXIF, GPS and Interop directories
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Exif {
+    tags: Vec<ExifTag>,
+}
+
+impl Exif {
+    /// Reads the EXIF structure of a raw file: the file's own TIFF
+    /// structure, the EXIF block of a RAF's embedded JPEG or the TTW block
+    /// of an MRW. Files without one give an empty map.
+    pub fn read(data: &[u8]) -> Exif {
+        let mut exif = Exif::default();
+        let Some(tiff) = locate(data).and_then(Tiff::open) else { return exif };
+        let Some(first) = tiff.u32(4) else { return exif };
+        exif.read_ifd(&tiff, first, Directory::Ifd0);
+        if let Some(offset) = exif.pointer(Directory::Ifd0, TAG_EXIF_IFD) {
+            exif.read_ifd(&tiff, offset, Directory::Exif);
+        }
+        if let Some(offset) = exif.pointer(Directory::Ifd0, TAG_GPS_IFD) {
+            exif.read_ifd(&tiff, offset, Directory::Gps);
+        }
+        if let Some(offset) = exif.pointer(Directory::Exif, TAG_INTEROP_IFD) {
+            exif.read_ifd(&tiff, offset, Directory::Interop);
+        }
+        exif
+    }
+
+    fn pointer(&self, directory: Directory, id: u16) -> Option<u32> {
+        self.get(directory, id)?.value.as_u32().filter(|&offset| offset != 0)
+    }
+
+    fn read_ifd(&mut self, tiff: &Tiff, offset: u32, directory: Directory) {
+        let offset = offset as usize;
+        let Some(count) = tiff.u16(offset) else { return };
+        for i in 0..count as usize {
+            let pos = offset + 2 + i * 12;
+            let (Some(id), Some(tiff_type), Some(n)) = (tiff.u16(pos), tiff.u16(pos + 2), tiff.u32(pos + 4)) else {
+                return;
+            };
+            if let Some(value) = tiff.value(pos, tiff_type, n as usize) {
+                self.tags.push(ExifTag { directory, id, name: tag_name(directory, id), tiff_type, value });
+            }
+        }
+    }
+
+    /// All tags in file order
+    pub fn tags(&self) -> &[ExifTag] {
+        &self.tags
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn get(&self, directory: Directory, id: u16) -> Option<&ExifTag> {
+        self.tags.iter().find(|tag| tag.directory == directory && tag.id == id)
+    }
+
+    /// A tag of IFD0 or the EXIF IFD, which share one numbering
+    fn value(&self, id: u16) -> Option<&ExifValue> {
+        self.tags
+            .iter()
+            .find(|tag| tag.id == id && matches!(tag.directory, Directory::Exif | Directory::Ifd0))
+            .map(|tag| &tag.value)
+    }
+
+    fn text(&self, id: u16) -> Option<&str> {
+        self.value(id)?.as_str().filter(|text| !text.is_empty())
+    }
+
+    fn short(&self, id: u16) -> Option<u16> {
+        self.value(id)?.as_u32().map(|v| v as u16)
+    }
+
+    pub fn make(&self) -> Option<&str> {
+        self.text(0x010F)
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.text(0x0110)
+    }
+
+    pub fn software(&self) -> Option<&str> {
+        self.text(0x0131)
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.text(0x013B)
+    }
+
+    pub fn copyright(&self) -> Option<&str> {
+        self.text(0x8298)
+    }
+
+    /// Stars from the Microsoft Rating tag some cameras write to IFD0
+    pub fn rating(&self) -> Option<u16> {
+        self.short(0x4746)
+    }
+
+    pub fn lens_make(&self) -> Option<&str> {
+        self.text(0xA433)
+    }
+
+    pub fn lens_model(&self) -> Option<&str> {
+        self.text(0xA434)
+    }
+
+    /// LensSpecification: shortest and longest focal length in mm, then the
+    /// widest f-number at each; entries the camera does not know are 0
+    pub fn lens_specification(&self) -> Option<[f64; 4]> {
+        let value = self.value(0xA432)?;
+        let spec: [f64; 4] = std::array::from_fn(|i| value.f64_at(i).unwrap_or(0.0));
+        (spec[0] > 0.0).then_some(spec)
+    }
+
+    pub fn body_serial_number(&self) -> Option<&str> {
+        self.text(0xA431)
+    }
+
+    pub fn lens_serial_number(&self) -> Option<&str> {
+        self.text(0xA435)
+    }
+
+    /// When the shot was taken (DateTimeOriginal with its sub-seconds and offset)
+    pub fn date_time_original(&self) -> Option<DateTime> {
+        self.date_time(0x9003, 0x9291, 0x9011)
+    }
+
+    /// When the image was digitized (CreateDate)
+    pub fn create_date(&self) -> Option<DateTime> {
+        self.date_time(0x9004, 0x9292, 0x9012)
+    }
+
+    /// When the file was last changed (IFD0 DateTime)
+    pub fn modify_date(&self) -> Option<DateTime> {
+        self.date_time(0x0132, 0x9290, 0x9010)
+    }
+
+    fn date_time(&self, id: u16, subsec: u16, offset: u16) -> Option<DateTime> {
+        Some(DateTime::parse(self.text(id)?)?.with_subsec(self.text(subsec)).with_offset(self.text(offset)))
+    }
+
+    pub fn exposure_time(&self) -> Option<Rational> {
+        self.value(0x829A)?.as_rational()
+    }
+
+    pub fn f_number(&self) -> Option<Rational> {
+        self.value(0x829D)?.as_rational()
+    }
+
+    /// PhotographicSensitivity (ISOSpeedRatings)
+    pub fn iso(&self) -> Option<u32> {
+        self.value(0x8827)?.as_u32()
+    }
+
+    pub fn exposure_program(&self) -> Option<ExposureProgram> {
+        self.short(0x8822).map(ExposureProgram::from)
+    }
+
+    /// Exposure bias in EV
+    pub fn exposure_bias(&self) -> Option<f64> {
+        self.value(0x9204)?.as_f64()
+    }
+
+    pub fn metering_mode(&self) -> Option<MeteringMode> {
+        self.short(0x9207).map(MeteringMode::from)
+    }
+
+    pub fn flash(&self) -> Option<Flash> {
+        self.short(0x9209).map(Flash)
+    }
+
+    /// Focal length in mm
+    pub fn focal_length(&self) -> Option<f64> {
+        self.value(0x920A)?.as_f64()
+    }
+
+    pub fn focal_length_35mm(&self) -> Option<u16> {
+        self.short(0xA405).filter(|&mm| mm != 0)
+    }
+
+    pub fn gps_position(&self) -> Option<GpsPosition> {
+        let gps = |id| self.get(Directory::Gps, id).map(|tag| &tag.value);
+        let degrees = |id| {
+            let value = gps(id)?;
+            Some(value.f64_at(0)? + value.f64_at(1).unwrap_or(0.0) / 60.0 + value.f64_at(2).unwrap_or(0.0) / 3600.0)
+        };
+        let latitude = degrees(0x0002)?;
+        let longitude = degrees(0x0004)?;
+        let south = gps(0x0001).and_then(ExifValue::as_str) == Some("S");
+        let west = gps(0x0003).and_then(ExifValue::as_str) == Some("W");
+        // AltitudeRef 1 is below sea level
+        let below = gps(0x0005).and_then(ExifValue::as_u32) == Some(1);
+        Some(GpsPosition {
+            latitude: if south { -latitude } else { latitude },
+            longitude: if west { -longitude } else { longitude },
+            altitude: gps(0x0006).and_then(ExifValue::as_f64).map(|alt| if below { -alt } else { alt }),
+        })
+    }
+
+    /// The UTC time of the GPS fix
+    pub fn gps_time(&self) -> Option<DateTime> {
+        let date = self.get(Directory::Gps, 0x001D)?.value.as_str()?;
+        let time = &self.get(Directory::Gps, 0x0007)?.value;
+        let seconds = time.f64_at(2)?;
+        let mut stamp = DateTime::parse(&format!("{} 00:00:00", date))?;
+        stamp.hour = time.f64_at(0)? as u8;
+        stamp.minute = time.f64_at(1)? as u8;
+        stamp.second = seconds as u8;
+        stamp.nanosecond = (seconds.fract() * 1e9).round() as u32;
+        stamp.offset_minutes = Some(0);
+        Some(stamp)
+    }
+}
+
+/// Finds the TIFF structure holding a file's EXIF data
+fn locate(data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(b"FUJIFILMCCD-RAW") {
+        let offset = u32::from_be_bytes(data.get(0x54..0x58)?.try_into().ok()?) as usize;
+        let length = u32::from_be_bytes(data.get(0x58..0x5C)?.try_into().ok()?) as usize;
+        return jpeg_exif(data.get(offset..offset.checked_add(length)?.min(data.len()))?);
+    }
+    if data.starts_with(&[0xFF, 0xD8]) {
+        return jpeg_exif(data);
+    }
+    if data.starts_with(b"\0MRM") {
+        // Blocks of a four-byte name and a big-endian length
+        let end = (u32::from_be_bytes(data.get(4..8)?.try_into().ok()?) as usize + 8).min(data.len());
+        let mut pos = 8;
+        while pos + 8 <= end {
+            let length = u32::from_be_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+            let block = data.get(pos + 8..(pos + 8).checked_add(length)?)?;
+            if &data[pos..pos + 4] == b"\0TTW" {
+                return Some(block);
+            }
+            pos += 8 + length;
+        }
+        return None;
+    }
+    Some(data)
+}
+
+/// Where the MakerNote of a file's EXIF IFD lies
+pub(crate) struct MakernoteLocation<'a> {
+    /// The TIFF structure holding it, which many vendors' offsets count from
+    pub tiff: &'a [u8],
+    pub big_endian: bool,
+    /// Byte range of the MakerNote value within `tiff`
+    pub start: usize,
+    pub len: usize,
+}
+
+impl<'a> MakernoteLocation<'a> {
+    pub fn bytes(&self) -> &'a [u8] {
+        &self.tiff[self.start..self.start + self.len]
+    }
+}
+
+pub(crate) fn makernote_location(data: &[u8]) -> Option<MakernoteLocation<'_>> {
+    let tiff = locate(data).and_then(Tiff::open)?;
+    let exif = tiff.find(tiff.u32(4)? as usize, TAG_EXIF_IFD).and_then(|entry| tiff.u32(entry + 8))?;
+    let entry = tiff.find(exif as usize, TAG_MAKERNOTE)?;
+    let len = tiff.u32(entry + 4)? as usize;
+    let start = if len <= 4 { entry + 8 } else { tiff.u32(entry + 8)? as usize };
+    (start.checked_add(len)? <= tiff.data.len()).then_some(MakernoteLocation {
+        tiff: tiff.data,
+        big_endian: tiff.big_endian,
+        start,
+        len,
+    })
+}
+
+/// The TIFF structure in the APP1 segment of a JPEG
+fn jpeg_exif(jpeg: &[u8]) -> Option<&[u8]> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= jpeg.len() && jpeg[pos] == 0xFF {
+        let marker = jpeg[pos + 1];
+        let length = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+        if marker == 0xDA || length < 2 {
+            return None;
+        }
+        let body = &jpeg[pos + 4..(pos + 2 + length).min(jpeg.len())];
+        if marker == 0xE1 && body.starts_with(b"Exif\0\0") {
+            return Some(&body[6..]);
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+/// A TIFF structure; offsets count from its header
+pub(crate) struct Tiff<'a> {
+    pub data: &'a [u8],
+    pub big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// Entries of a directory whose offsets count from the start of `data`,
+    /// which need not begin with a TIFF header (makernotes)
+    pub fn with_order(data: &'a [u8], big_endian: bool) -> Self {
+        Tiff { data, big_endian }
+    }
+
+    /// Accepts plain TIFF and the ORF and RW2 variants of its magic number
+    pub fn open(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..2)? {
+            b"II" => false,
+            b"MM" => true,
+            _ => return None,
+        };
+        let tiff = Tiff { data, big_endian };
+        matches!(tiff.u16(2)?, 42 | 0x4F52 | 0x5352 | 0x55).then_some(tiff)
+    }
+
+    fn bytes<const N: usize>(&self, at: usize) -> Option<[u8; N]> {
+        let mut bytes: [u8; N] = self.data.get(at..at.checked_add(N)?)?.try_into().ok()?;
+        if !self.big_endian {
+            bytes.reverse();
+        }
+        Some(bytes)
+    }
+
+    pub fn u16(&self, at: usize) -> Option<u16> {
+        self.bytes(at).map(u16::from_be_bytes)
+    }
+
+    pub fn u32(&self, at: usize) -> Option<u32> {
+        self.bytes(at).map(u32::from_be_bytes)
+    }
+
+    /// `value` in the structure's byte order
+    pub fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    pub fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    /// Position of the entry for `id` in the directory at `ifd`
+    pub fn find(&self, ifd: usize, id: u16) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        (0..count).map(|i| ifd + 2 + i * 12).find(|&pos| self.u16(pos) == Some(id))
+    }
+
+    /// Decodes the values of the entry at `entry`; None for unknown types
+    /// and values that run past the data
+    pub fn value(&self, entry: usize, tiff_type: u16, count: usize) -> Option<ExifValue> {
+        let size = match tiff_type {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let length = count.checked_mul(size)?;
+        let start = if length <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+        let raw = self.data.get(start..start.checked_add(length)?)?;
+
+        let at = |i: usize| start + i * size;
+        let u16s = || (0..count).map(|i| self.u16(at(i))).collect::<Option<Vec<_>>>();
+        let u32s = || (0..count).map(|i| self.u32(at(i))).collect::<Option<Vec<_>>>();
+        let pairs = || (0..count).map(|i| Some((self.u32(at(i))?, self.u32(at(i) + 4)?))).collect::<Option<Vec<_>>>();
+        Some(match tiff_type {
+            1 => ExifValue::Byte(raw.to_vec()),
+            2 => {
+                let text = raw.split(|&b| b == 0).next().unwrap_or_default();
+                ExifValue::Ascii(String::from_utf8_lossy(text).trim_end_matches(' ').to_string())
+            }
+            3 => ExifValue::Short(u16s()?),
+            4 | 13 => ExifValue::Long(u32s()?),
+            5 => ExifValue::Rational(pairs()?.into_iter().map(|(num, den)| Rational { num, den }).collect()),
+            6 => ExifValue::SByte(raw.iter().map(|&b| b as i8).collect()),
+            7 => ExifValue::Undefined(raw.to_vec()),
+            8 => ExifValue::SShort(u16s()?.into_iter().map(|v| v as i16).collect()),
+            9 => ExifValue::SLong(u32s()?.into_iter().map(|v| v as i32).collect()),
+            10 => ExifValue::SRational(
+                pairs()?.into_iter().map(|(num, den)| SRational { num: num as i32, den: den as i32 }).collect(),
+            ),
+            11 => ExifValue::Float(u32s()?.into_iter().map(f32::from_bits).collect()),
+            _ => ExifValue::Double(
+                (0..count)
+                    .map(|i| self.bytes::<8>(at(i)).map(|b| f64::from_bits(u64::from_be_bytes(b))))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+        })
+    }
+}
+
+/// Names of the TIFF 6.0 and EXIF 3.0 tags of IFD0 and the EXIF IFD
+const TAGS: &[(u16, &str)] = &[
+    (0x000B, "ProcessingSoftware"),
+    (0x00FE, "SubfileType"),
+    (0x0100, "ImageWidth"),
+    (0x0101, "ImageLength"),
+    (0x0102, "BitsPerSample"),
+    (0x0103, "Compression"),
+    (0x0106, "PhotometricInterpretation"),
+    (0x010E, "ImageDescription"),
+    (0x010F, "Make"),
+    (0x0110, "Model"),
+    (0x0111, "StripOffsets"),
+    (0x0112, "Orientation"),
+    (0x0115, "SamplesPerPixel"),
+    (0x0116, "RowsPerStrip"),
+    (0x0117, "StripByteCounts"),
+    (0x011A, "XResolution"),
+    (0x011B, "YResolution"),
+    (0x011C, "PlanarConfiguration"),
+    (0x0128, "ResolutionUnit"),
+    (0x0131, "Software"),
+    (0x0132, "DateTime"),
+    (0x013B, "Artist"),
+    (0x0142, "TileWidth"),
+    (0x0143, "TileLength"),
+    (0x0144, "TileOffsets"),
+    (0x0145, "TileByteCounts"),
+    (0x014A, "SubIFDs"),
+    (0x0201, "JPEGInterchangeFormat"),
+    (0x0202, "JPEGInterchangeFormatLength"),
+    (0x0213, "YCbCrPositioning"),
+    (0x02BC, "XMLPacket"),
+    (0x4746, "Rating"),
+    (0x828D, "CFARepeatPatternDim"),
+    (0x828E, "CFAPattern"),
+    (0x8298, "Copyright"),
+    (0x829A, "ExposureTime"),
+    (0x829D, "FNumber"),
+    (0x83BB, "IPTC-NAA"),
+    (0x8769, "ExifIFDPointer"),
+    (0x8773, "InterColorProfile"),
+    (0x8822, "ExposureProgram"),
+    (0x8824, "SpectralSensitivity"),
+    (0x8825, "GPSInfoIFDPointer"),
+    (0x8827, "PhotographicSensitivity"),
+    (0x8828, "OECF"),
+    (0x8830, "SensitivityType"),
+    (0x8831, "StandardOutputSensitivity"),
+    (0x8832, "RecommendedExposureIndex"),
+    (0x8833, "ISOSpeed"),
+    (0x9000, "ExifVersion"),
+    (0x9003, "DateTimeOriginal"),
+    (0x9004, "DateTimeDigitized"),
+    (0x9010, "OffsetTime"),
+    (0x9011, "OffsetTimeOriginal"),
+    (0x9012, "OffsetTimeDigitized"),
+    (0x9101, "ComponentsConfiguration"),
+    (0x9102, "CompressedBitsPerPixel"),
+    (0x9201, "ShutterSpeedValue"),
+    (0x9202, "ApertureValue"),
+    (0x9203, "BrightnessValue"),
+    (0x9204, "ExposureBiasValue"),
+    (0x9205, "MaxApertureValue"),
+    (0x9206, "SubjectDistance"),
+    (0x9207, "MeteringMode"),
+    (0x9208, "LightSource"),
+    (0x9209, "Flash"),
+    (0x920A, "FocalLength"),
+    (0x9214, "SubjectArea"),
+    (0x927C, "MakerNote"),
+    (0x9286, "UserComment"),
+    (0x9290, "SubSecTime"),
+    (0x9291, "SubSecTimeOriginal"),
+    (0x9292, "SubSecTimeDigitized"),
+    (0x9400, "Temperature"),
+    (0x9401, "Humidity"),
+    (0x9402, "Pressure"),
+    (0x9403, "WaterDepth"),
+    (0x9404, "Acceleration"),
+    (0x9405, "CameraElevationAngle"),
+    (0xA000, "FlashpixVersion"),
+    (0xA001, "ColorSpace"),
+    (0xA002, "PixelXDimension"),
+    (0xA003, "PixelYDimension"),
+    (0xA004, "RelatedSoundFile"),
+    (0xA005, "InteroperabilityIFDPointer"),
+    (0xA20B, "FlashEnergy"),
+    (0xA20E, "FocalPlaneXResolution"),
+    (0xA20F, "FocalPlaneYResolution"),
+    (0xA210, "FocalPlaneResolutionUnit"),
+    (0xA214, "SubjectLocation"),
+    (0xA215, "ExposureIndex"),
+    (0xA217, "SensingMethod"),
+    (0xA300, "FileSource"),
+    (0xA301, "SceneType"),
+    (0xA302, "CFAPattern"),
+    (0xA401, "CustomRendered"),
+    (0xA402, "ExposureMode"),
+    (0xA403, "WhiteBalance"),
+    (0xA404, "DigitalZoomRatio"),
+    (0xA405, "FocalLengthIn35mmFilm"),
+    (0xA406, "SceneCaptureType"),
+    (0xA407, "GainControl"),
+    (0xA408, "Contrast"),
+    (0xA409, "Saturation"),
+    (0xA40A, "Sharpness"),
+    (0xA40B, "DeviceSettingDescription"),
+    (0xA40C, "SubjectDistanceRange"),
+    (0xA420, "ImageUniqueID"),
+    (0xA430, "CameraOwnerName"),
+    (0xA431, "BodySerialNumber"),
+    (0xA432, "LensSpecification"),
+    (0xA433, "LensMake"),
+    (0xA434, "LensModel"),
+    (0xA435, "LensSerialNumber"),
+    (0xA436, "ImageTitle"),
+    (0xA437, "Photographer"),
+    (0xA438, "ImageEditor"),
+    (0xA460, "CompositeImage"),
+    (0xA500, "Gamma"),
+    (0xC612, "DNGVersion"),
+    (0xC614, "UniqueCameraModel"),
+];
+
+const GPS_TAGS: &[(u16, &str)] = &[
+    (0x0000, "GPSVersionID"),
+    (0x0001, "GPSLatitudeRef"),
+    (0x0002, "GPSLatitude"),
+    (0x0003, "GPSLongitudeRef"),
+    (0x0004, "GPSLongitude"),
+    (0x0005, "GPSAltitudeRef"),
+    (0x0006, "GPSAltitude"),
+    (0x0007, "GPSTimeStamp"),
+    (0x0008, "GPSSatellites"),
+    (0x0009, "GPSStatus"),
+    (0x000A, "GPSMeasureMode"),
+    (0x000B, "GPSDOP"),
+    (0x000C, "GPSSpeedRef"),
+    (0x000D, "GPSSpeed"),
+    (0x000E, "GPSTrackRef"),
+    (0x000F, "GPSTrack"),
+    (0x0010, "GPSImgDirectionRef"),
+    (0x0011, "GPSImgDirection"),
+    (0x0012, "GPSMapDatum"),
+    (0x0013, "GPSDestLatitudeRef"),
+    (0x0014, "GPSDestLatitude"),
+    (0x0015, "GPSDestLongitudeRef"),
+    (0x0016, "GPSDestLongitude"),
+    (0x0017, "GPSDestBearingRef"),
+    (0x0018, "GPSDestBearing"),
+    (0x0019, "GPSDestDistanceRef"),
+    (0x001A, "GPSDestDistance"),
+    (0x001B, "GPSProcessingMethod"),
+    (0x001C, "GPSAreaInformation"),
+    (0x001D, "GPSDateStamp"),
+    (0x001E, "GPSDifferential"),
+    (0x001F, "GPSHPositioningError"),
+];
+
+const INTEROP_TAGS: &[(u16, &str)] = &[
+    (0x0001, "InteroperabilityIndex"),
+    (0x0002, "InteroperabilityVersion"),
+    (0x1000, "RelatedImageFileFormat"),
+    (0x1001, "RelatedImageWidth"),
+    (0x1002, "RelatedImageLength"),
+];
+
+/// The standard name of a tag, None for tags the specifications don't define
+pub fn tag_name(directory: Directory, id: u16) -> Option<&'static str> {
+    let table = match directory {
+        Directory::Ifd0 | Directory::Exif => TAGS,
+        Directory::Gps => GPS_TAGS,
+        Directory::Interop => INTEROP_TAGS,
+    };
+    table.iter().find(|&&(tag, _)| tag == id).map(|&(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Entry, TiffBuilder, Value};
+
+    /// A little-endian TIFF with a tag in each of the four directories
+    fn sample() -> Vec<u8> {
+        TiffBuilder::new(false).build(vec![
+            Entry(0x010F, Value::Ascii("FUJIFILM")),
+            Entry(0x0110, Value::Ascii("X-T5")),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x829A, Value::Rational(vec![(1, 500)])),
+                Entry(0x9003, Value::Ascii("2024:06:01 07:45:00")),
+                Entry(0x9011, Value::Ascii("-03:00")),
+                Entry(0x9204, Value::SRational(vec![(-2, 3)])),
+                Entry(0xA005, Value::Ifd(vec![Entry(0x0001, Value::Ascii("R98"))])),
+            ])),
+            Entry(0x8825, Value::Ifd(vec![
+                Entry(0x0001, Value::Ascii("S")),
+                Entry(0x0002, Value::Rational(vec![(33, 1), (52, 1), (12, 1)])),
+                Entry(0x0003, Value::Ascii("E")),
+                Entry(0x0004, Value::Rational(vec![(151, 1), (12, 1), (36, 1)])),
+            ])),
+        ])
+    }
+
+    #[test]
+    fn test_reads_every_directory() {
+        let exif = Exif::read(&sample());
+
+        let make = exif.get(Directory::Ifd0, 0x010F).unwrap();
+        assert_eq!((make.name, make.tiff_type), (Some("Make"), 2));
+        assert_eq!(exif.make(), Some("FUJIFILM"));
+
+        assert_eq!(exif.get(Directory::Exif, 0x829A).unwrap().name, Some("ExposureTime"));
+        assert_eq!(exif.exposure_time(), Some(Rational::new(1, 500)));
+        assert_eq!(exif.date_time_original().unwrap().to_string(), "2024-06-01T07:45:00-03:00");
+
+        let latitude = exif.get(Directory::Gps, 0x0002).unwrap();
+        assert_eq!(latitude.name, Some("GPSLatitude"));
+        let position = exif.gps_position().unwrap();
+        assert!((position.latitude + (33.0 + 52.0 / 60.0 + 12.0 / 3600.0)).abs() < 1e-9);
+        assert!((position.longitude - (151.0 + 12.0 / 60.0 + 36.0 / 3600.0)).abs() < 1e-9);
+
+        let index = exif.get(Directory::Interop, 0x0001).unwrap();
+        assert_eq!(index.name, Some("InteroperabilityIndex"));
+        assert_eq!(index.value.as_str(), Some("R98"));
+        // GPS tag 1 and Interop tag 1 are not confused
+        assert_eq!(exif.get(Directory::Gps, 0x0001).unwrap().value.as_str(), Some("S"));
+    }
+
+    #[test]
+    fn test_srational() {
+        let exif = Exif::read(&sample());
+        let bias = exif.get(Directory::Exif, 0x9204).unwrap();
+        assert_eq!(bias.tiff_type, 10);
+        assert_eq!(bias.value, ExifValue::SRational(vec![SRational { num: -2, den: 3 }]));
+        assert!((exif.exposure_bias().unwrap() + 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(SRational { num: 1, den: 0 }.to_f64(), None);
+    }
+
+    #[test]
+    fn test_truncated_ifd() {
+        let tiff = TiffBuilder::new(false).build(vec![
+            Entry(0x0100, Value::Long(vec![16])),
+            Entry(0x0101, Value::Long(vec![12])),
+            Entry(0x0112, Value::Short(vec![6])),
+            Entry(0x8769, Value::Ifd(vec![Entry(0x8827, Value::Short(vec![400]))])),
+        ]);
+        // IFD0 starts at 8; cut it in the middle of its third entry
+        let exif = Exif::read(&tiff[..8 + 2 + 2 * 12 + 6]);
+        let ids: Vec<_> = exif.tags().iter().map(|tag| (tag.directory, tag.id)).collect();
+        assert_eq!(ids, [(Directory::Ifd0, 0x0100), (Directory::Ifd0, 0x0101)]);
+        assert_eq!(exif.iso(), None);
+
+        assert_eq!(Exif::read(&tiff).iso(), Some(400));
+        for len in 0..tiff.len() {
+            let _ = Exif::read(&tiff[..len]);
+        }
+    }
+
+    #[test]
+    fn test_date_time_offset() {
+        let date = DateTime::parse("2024:06:01 07:45:00").unwrap();
+        assert_eq!(date.with_offset(Some("+05:30")).offset_minutes, Some(330));
+        assert_eq!(date.with_offset(Some("-03:00")).offset_minutes, Some(-180));
+        assert_eq!(date.with_offset(Some("+0530")).offset_minutes, None);
+        // Six bytes, but 'é' spans the hour and the colon
+        assert_eq!(date.with_offset(Some("+0é:0")).offset_minutes, None);
+        assert_eq!(date.with_offset(Some("+05:é")).offset_minutes, None);
+    }
+}
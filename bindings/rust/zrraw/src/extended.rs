@@ -0,0 +1,193 @@
+// bindings/rust/zrraw/src/extended.rs - Versioned key/value metadata beyond ZrRawMetadata
+use crate::color::{Calibration, ColorProfile, Illuminant};
+use crate::exif::DateTime;
+use crate::{CfaPattern, ZrRawError};
+use zrraw_sys::*;
+
+const MAGIC: &[u8; 4] = b"ZRMD";
+
+/// Default crop within the decoded image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Metadata that does not fit the fixed native struct; every field is
+/// `None` when the file does not record it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtendedMetadata {
+    /// Lens model as the file names it; `RawMetadata::lens` is filled from it
+    pub lens: Option<String>,
+    /// When the shutter fired, with sub-second digits and UTC offset where
+    /// recorded. `RawMetadata::extended` takes it from `Exif::date_time_original`.
+    pub date_time_original: Option<DateTime>,
+    pub body_serial_number: Option<String>,
+    pub lens_serial_number: Option<String>,
+    pub crop: Option<CropRect>,
+    pub cfa_pattern: Option<CfaPattern>,
+    pub bits_per_sample: Option<u32>,
+    /// TIFF compression value, or the format's own code for non-TIFF formats
+    pub compression: Option<u32>,
+    /// Degrees Celsius
+    pub sensor_temperature: Option<f32>,
+    /// Make and model as the camera database names them ("Nikon", "D850")
+    pub canonical_make: Option<String>,
+    pub canonical_model: Option<String>,
+    /// Colour calibration for each illuminant the file or camera database has
+    pub color: Option<ColorProfile>,
+}
+
+/// One entry of the blob, its value still encoded
+struct Entry<'a> {
+    key: u32,
+    value_type: u32,
+    value: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    fn text(&self) -> Option<&'a str> {
+        if self.value_type != ZRRAW_META_TYPE_STRING {
+            return None;
+        }
+        std::str::from_utf8(self.value).ok().filter(|text| !text.is_empty())
+    }
+
+    fn u32s(&self) -> Option<impl Iterator<Item = u32> + 'a> {
+        (self.value_type == ZRRAW_META_TYPE_U32).then(|| {
+            self.value.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        })
+    }
+
+    fn f32s(&self) -> Option<impl Iterator<Item = f32> + 'a> {
+        (self.value_type == ZRRAW_META_TYPE_F32).then(|| {
+            self.value.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        })
+    }
+
+    fn f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+        self.f32s()?.collect::<Vec<_>>().try_into().ok()
+    }
+}
+
+/// Colour entries, gathered until the blob is read
+#[derive(Default)]
+struct ColorEntries {
+    illuminants: Vec<u32>,
+    color_matrices: [Option<[f32; 9]>; 3],
+    camera_calibrations: [Option<[f32; 9]>; 3],
+    forward_matrices: [Option<[f32; 9]>; 3],
+    analog_balance: Option<[f32; 3]>,
+    as_shot_neutral: Option<[f32; 3]>,
+    as_shot_white_xy: Option<[f32; 2]>,
+}
+
+impl ColorEntries {
+    /// A profile needs a colour matrix for every illuminant
+    fn profile(self) -> Option<ColorProfile> {
+        let calibrations = self
+            .illuminants
+            .iter()
+            .take(3)
+            .enumerate()
+            .map(|(i, &illuminant)| {
+                Some(Calibration {
+                    illuminant: Illuminant(u16::try_from(illuminant).ok()?),
+                    color_matrix: self.color_matrices[i]?,
+                    camera_calibration: self.camera_calibrations[i],
+                    forward_matrix: self.forward_matrices[i],
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        (!calibrations.is_empty()).then(|| ColorProfile {
+            calibrations,
+            analog_balance: self.analog_balance.unwrap_or([1.0; 3]),
+            as_shot_neutral: self.as_shot_neutral,
+            as_shot_white_xy: self.as_shot_white_xy,
+        })
+    }
+}
+
+impl ExtendedMetadata {
+    /// Decodes the blob `zrraw_extract_extended_metadata` returns. Keys and
+    /// value types this version does not know are skipped.
+    pub fn decode(blob: &[u8]) -> Result<Self, ZrRawError> {
+        if blob.len() < 8 || &blob[..4] != MAGIC {
+            return Err(ZrRawError::CorruptedData);
+        }
+        let version = u16::from_le_bytes([blob[4], blob[5]]);
+        if u32::from(version) != ZRRAW_EXTENDED_VERSION {
+            return Err(ZrRawError::ParseError(format!("extended metadata version {}", version)));
+        }
+
+        let mut metadata = ExtendedMetadata::default();
+        let (mut date, mut subsec, mut offset) = (None, None, None);
+        let mut color = ColorEntries::default();
+        let mut pos = 8;
+        while pos < blob.len() {
+            let header = blob.get(pos..pos + 8).ok_or(ZrRawError::CorruptedData)?;
+            let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+            let value = blob.get(pos + 8..pos + 8 + len).ok_or(ZrRawError::CorruptedData)?;
+            pos += 8 + len;
+            let entry = Entry {
+                key: u32::from(u16::from_le_bytes([header[0], header[1]])),
+                value_type: u32::from(u16::from_le_bytes([header[2], header[3]])),
+                value,
+            };
+
+            match entry.key {
+                ZRRAW_META_LENS => metadata.lens = entry.text().map(str::to_owned),
+                ZRRAW_META_DATE_TIME_ORIGINAL => date = entry.text(),
+                ZRRAW_META_SUB_SEC_TIME_ORIGINAL => subsec = entry.text(),
+                ZRRAW_META_OFFSET_TIME_ORIGINAL => offset = entry.text(),
+                ZRRAW_META_BODY_SERIAL_NUMBER => metadata.body_serial_number = entry.text().map(str::to_owned),
+                ZRRAW_META_LENS_SERIAL_NUMBER => metadata.lens_serial_number = entry.text().map(str::to_owned),
+                ZRRAW_META_CROP => {
+                    let values: Vec<u32> = entry.u32s().into_iter().flatten().collect();
+                    if let [x, y, width, height] = values[..] {
+                        metadata.crop = Some(CropRect { x, y, width, height });
+                    }
+                }
+                ZRRAW_META_CFA_PATTERN if entry.value_type == ZRRAW_META_TYPE_BYTES => {
+                    if let [width, height, colors @ ..] = entry.value {
+                        let sites = usize::from(*width) * usize::from(*height);
+                        if sites > 0 && colors.len() == sites {
+                            metadata.cfa_pattern = Some(CfaPattern {
+                                width: u32::from(*width),
+                                height: u32::from(*height),
+                                colors: colors.to_vec(),
+                            });
+                        }
+                    }
+                }
+                ZRRAW_META_BITS_PER_SAMPLE => metadata.bits_per_sample = entry.u32s().and_then(|mut v| v.next()),
+                ZRRAW_META_COMPRESSION => metadata.compression = entry.u32s().and_then(|mut v| v.next()),
+                ZRRAW_META_SENSOR_TEMPERATURE => {
+                    metadata.sensor_temperature = entry.f32s().and_then(|mut v| v.next());
+                }
+                ZRRAW_META_CANONICAL_MAKE => metadata.canonical_make = entry.text().map(str::to_owned),
+                ZRRAW_META_CANONICAL_MODEL => metadata.canonical_model = entry.text().map(str::to_owned),
+                ZRRAW_META_CALIBRATION_ILLUMINANTS => color.illuminants = entry.u32s().into_iter().flatten().collect(),
+                key @ ZRRAW_META_COLOR_MATRIX_1..=ZRRAW_META_COLOR_MATRIX_3 => {
+                    color.color_matrices[(key - ZRRAW_META_COLOR_MATRIX_1) as usize] = entry.f32_array();
+                }
+                key @ ZRRAW_META_CAMERA_CALIBRATION_1..=ZRRAW_META_CAMERA_CALIBRATION_3 => {
+                    color.camera_calibrations[(key - ZRRAW_META_CAMERA_CALIBRATION_1) as usize] = entry.f32_array();
+                }
+                key @ ZRRAW_META_FORWARD_MATRIX_1..=ZRRAW_META_FORWARD_MATRIX_3 => {
+                    color.forward_matrices[(key - ZRRAW_META_FORWARD_MATRIX_1) as usize] = entry.f32_array();
+                }
+                ZRRAW_META_ANALOG_BALANCE => color.analog_balance = entry.f32_array(),
+                ZRRAW_META_AS_SHOT_NEUTRAL => color.as_shot_neutral = entry.f32_array(),
+                ZRRAW_META_AS_SHOT_WHITE_XY => color.as_shot_white_xy = entry.f32_array(),
+                _ => {}
+            }
+        }
+
+        metadata.color = color.profile();
+        metadata.date_time_original = date.and_then(DateTime::parse).map(|d| d.with_subsec(subsec).with_offset(offset));
+        Ok(metadata)
+    }
+}
@@ -0,0 +1,931 @@
+// bindings/rust/zrraw/src/lens.rs - Lens names for the numeric IDs makernotes record
+use crate::exif::Exif;
+use crate::makernote::Makernote;
+
+/// Whose numbering a lens ID follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LensTable {
+    /// Canon LensType, EF and EF-S mounts
+    Canon,
+    /// Nikon LensIDNumber; the focal range and apertures of LensData
+    /// complete the key
+    Nikon,
+    /// Sony and Minolta A-mount LensType
+    Minolta,
+    /// Pentax LensType series and number, as 0xSSnn
+    Pentax,
+    /// Olympus LensType make, model and sub-model, as 0xMMmmss
+    Olympus,
+}
+
+/// How far `RawMetadata::lens` trusts its answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LensConfidence {
+    /// Several lenses share the ID and the file does not tell them apart;
+    /// `name` is the first of them
+    Ambiguous,
+    /// The file names the lens itself
+    Reported,
+    /// Several lenses share the ID; the focal range and aperture single this one out
+    Matched,
+    /// The ID belongs to one lens only
+    Exact,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LensIdentification {
+    pub name: String,
+    pub confidence: LensConfidence,
+    /// The other lenses the ID could stand for, when ambiguous
+    pub alternatives: Vec<&'static str>,
+}
+
+/// What the file says about the lens, which tells lenses sharing an ID apart
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LensHints {
+    /// Shortest and longest focal length in millimetres
+    pub focal_range: Option<(f64, f64)>,
+    /// Widest f-number at the shortest focal length
+    pub max_aperture: Option<f64>,
+    /// Focal length of the shot in millimetres
+    pub focal_length: Option<f64>,
+}
+
+/// Looks `id` up in `table`; None when the ID is unknown or none of its
+/// lenses fits `hints`
+pub fn identify(table: LensTable, id: u32, hints: &LensHints) -> Option<LensIdentification> {
+    let rows = match table {
+        LensTable::Canon => CANON,
+        LensTable::Nikon => NIKON,
+        LensTable::Minolta => MINOLTA,
+        LensTable::Pentax => PENTAX,
+        LensTable::Olympus => OLYMPUS,
+    };
+    let candidates: Vec<&'static str> = rows.iter().filter(|row| row.0 == id).map(|row| row.1).collect();
+    let fitting: Vec<&'static str> = candidates.iter().copied().filter(|name| fits(name, hints)).collect();
+    let (&name, others) = fitting.split_first()?;
+    let confidence = match (candidates.len(), fitting.len()) {
+        (1, _) => LensConfidence::Exact,
+        (_, 1) => LensConfidence::Matched,
+        _ => LensConfidence::Ambiguous,
+    };
+    Some(LensIdentification { name: name.to_string(), confidence, alternatives: others.to_vec() })
+}
+
+/// Identifies the lens of a file from its makernote's lens ID, falling back
+/// to the lens name the file reports
+pub(crate) fn identify_file(makernote: Option<&Makernote>, exif: &Exif, reported: Option<&str>) -> Option<LensIdentification> {
+    let named = match makernote {
+        Some(Makernote::Canon(canon)) => canon.lens_model.as_deref(),
+        Some(Makernote::Olympus(olympus)) => olympus.lens_model.as_deref(),
+        Some(Makernote::Panasonic(panasonic)) => panasonic.lens_type.as_deref(),
+        _ => None,
+    };
+    let reported = exif
+        .lens_model()
+        .or(named)
+        .or(reported)
+        .filter(|name| !name.is_empty())
+        .map(|name| LensIdentification { name: name.to_string(), confidence: LensConfidence::Reported, alternatives: Vec::new() });
+    let looked_up = makernote.and_then(|makernote| {
+        let (table, id, hints) = lookup_key(makernote, exif)?;
+        identify(table, id, &hints)
+    });
+    match (looked_up, reported) {
+        (Some(found), reported) if found.confidence > LensConfidence::Reported || reported.is_none() => Some(found),
+        (_, reported) => reported,
+    }
+}
+
+/// The table, ID and hints a makernote supplies, with the EXIF
+/// LensSpecification and focal length filling gaps
+fn lookup_key(makernote: &Makernote, exif: &Exif) -> Option<(LensTable, u32, LensHints)> {
+    let spec = exif.lens_specification();
+    let mut hints = LensHints {
+        focal_range: spec.map(|spec| (spec[0], spec[1].max(spec[0]))),
+        max_aperture: spec.map(|spec| spec[2]).filter(|&f| f > 0.0),
+        focal_length: exif.focal_length().filter(|&mm| mm > 0.0),
+    };
+    let (table, id) = match makernote {
+        Makernote::Canon(canon) => {
+            let settings = canon.camera_settings.as_ref()?;
+            hints.focal_range = settings.focal_range.or(hints.focal_range);
+            hints.max_aperture = settings.aperture_range.map(|range| range.0).or(hints.max_aperture);
+            (LensTable::Canon, u32::from(settings.lens_type?))
+        }
+        Makernote::Nikon(nikon) => {
+            let data = nikon.lens_data.as_ref()?;
+            if let (Some(min), Some(max)) = (data.min_focal_length, data.max_focal_length) {
+                hints.focal_range = Some((min, max));
+            } else if let Some(lens) = nikon.lens {
+                hints.focal_range = Some((lens.min_focal_length, lens.max_focal_length));
+            }
+            hints.max_aperture = data.max_aperture_at_min_focal.or(hints.max_aperture);
+            (LensTable::Nikon, u32::from(data.lens_id_number?))
+        }
+        // E-mount bodies write 65535 and name the lens in the EXIF instead
+        Makernote::Sony(sony) => (LensTable::Minolta, sony.lens_type.filter(|&id| id != 0xFFFF)?),
+        Makernote::Pentax(pentax) => (LensTable::Pentax, u32::from(pentax.lens_type.filter(|&id| id > 0x00FF)?)),
+        Makernote::Olympus(olympus) => (LensTable::Olympus, olympus.lens_type.filter(|&id| id != 0)?),
+        Makernote::Fujifilm(_) | Makernote::Panasonic(_) => return None,
+    };
+    Some((table, id, hints))
+}
+
+/// Focal range and apertures as the lens name states them, teleconverter
+/// included
+#[derive(Debug, PartialEq)]
+struct NamedSpec {
+    focal: (f64, f64),
+    aperture: Option<f64>,
+}
+
+fn named_spec(name: &str) -> Option<NamedSpec> {
+    let mm = name.find("mm")?;
+    let start = name[..mm].rfind(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')).map_or(0, |i| i + 1);
+    let range = |text: &str| -> Option<(f64, f64)> {
+        let mut parts = text.split('-').map(str::parse::<f64>);
+        let low = parts.next()?.ok()?;
+        let high = parts.next().and_then(Result::ok).unwrap_or(low);
+        Some((low, high))
+    };
+    let focal = range(&name[start..mm])?;
+    // "f/2.8L", "F3.5-5.6", ...
+    let rest = &name[mm..];
+    let aperture = rest
+        .find("f/")
+        .map(|i| i + 2)
+        .or_else(|| rest.match_indices('F').map(|(i, _)| i + 1).find(|&i| rest[i..].starts_with(|c: char| c.is_ascii_digit())))
+        .and_then(|i| {
+            let digits: String = rest[i..].chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+            digits.parse::<f64>().ok()
+        });
+    let converter = name
+        .rsplit_once("+ ")
+        .and_then(|(_, tail)| tail.strip_suffix('x'))
+        .and_then(|factor| factor.parse::<f64>().ok())
+        .unwrap_or(1.0);
+    Some(NamedSpec {
+        focal: (focal.0 * converter, focal.1 * converter),
+        aperture: aperture.map(|f| f * converter),
+    })
+}
+
+/// Whether the lens `name` agrees with what the file records. Names that
+/// state no focal length agree with anything.
+fn fits(name: &str, hints: &LensHints) -> bool {
+    let Some(spec) = named_spec(name) else { return true };
+    // Nikon's logarithmic encoding and Canon's rounding both stay within 6%
+    let near = |a: f64, b: f64| (a - b).abs() <= b * 0.06;
+    let range_fits = hints.focal_range.is_none_or(|(min, max)| near(min, spec.focal.0) && near(max, spec.focal.1));
+    let aperture_fits = match (hints.max_aperture, spec.aperture) {
+        (Some(hint), Some(named)) => near(hint, named),
+        _ => true,
+    };
+    let focal_fits = hints
+        .focal_length
+        .is_none_or(|mm| mm >= spec.focal.0 * 0.94 && mm <= spec.focal.1 * 1.06);
+    range_fits && aperture_fits && focal_fits
+}
+
+const CANON: &[(u32, &str)] = &[
+    (1, "Canon EF 50mm f/1.8"),
+    (2, "Canon EF 28mm f/2.8"),
+    (2, "Sigma 24mm f/2.8 Super Wide II"),
+    (3, "Canon EF 135mm f/2.8 Soft"),
+    (4, "Canon EF 35-105mm f/3.5-4.5"),
+    (4, "Sigma UC Zoom 35-135mm f/4-5.6"),
+    (5, "Canon EF 35-70mm f/3.5-4.5"),
+    (6, "Canon EF 28-70mm f/3.5-4.5"),
+    (6, "Sigma 18-50mm f/3.5-5.6 DC"),
+    (6, "Sigma 18-125mm f/3.5-5.6 DC IF ASP"),
+    (6, "Tokina AF 193-2 19-35mm f/3.5-4.5"),
+    (7, "Canon EF 100-300mm f/5.6L"),
+    (8, "Canon EF 100-300mm f/5.6"),
+    (8, "Sigma 70-300mm f/4-5.6 DG Macro"),
+    (8, "Tokina AT-X 242 AF 24-200mm f/3.5-5.6"),
+    (9, "Canon EF 70-210mm f/4"),
+    (9, "Sigma 55-200mm f/4-5.6 DC"),
+    (10, "Canon EF 50mm f/2.5 Macro"),
+    (10, "Sigma 50mm f/2.8 EX"),
+    (10, "Sigma 28mm f/1.8"),
+    (10, "Sigma 105mm f/2.8 Macro EX"),
+    (10, "Sigma 70mm f/2.8 EX DG Macro EF"),
+    (11, "Canon EF 35mm f/2"),
+    (13, "Canon EF 15mm f/2.8 Fisheye"),
+    (14, "Canon EF 50-200mm f/3.5-4.5L"),
+    (15, "Canon EF 50-200mm f/3.5-4.5"),
+    (16, "Canon EF 35-135mm f/3.5-4.5"),
+    (17, "Canon EF 35-70mm f/3.5-4.5A"),
+    (18, "Canon EF 28-70mm f/3.5-4.5"),
+    (20, "Canon EF 100-200mm f/4.5A"),
+    (21, "Canon EF 80-200mm f/2.8L"),
+    (22, "Canon EF 20-35mm f/2.8L"),
+    (22, "Tokina AT-X 280 AF Pro 28-80mm f/2.8 Aspherical"),
+    (23, "Canon EF 35-105mm f/3.5-4.5"),
+    (24, "Canon EF 35-80mm f/4-5.6 Power Zoom"),
+    (25, "Canon EF 35-80mm f/4-5.6 Power Zoom"),
+    (26, "Canon EF 100mm f/2.8 Macro"),
+    (26, "Cosina 100mm f/3.5 Macro AF"),
+    (26, "Tamron SP AF 90mm f/2.8 Di Macro"),
+    (26, "Tamron SP AF 180mm f/3.5 Di Macro"),
+    (26, "Carl Zeiss Planar T* 50mm f/1.4"),
+    (27, "Canon EF 35-80mm f/4-5.6"),
+    (28, "Canon EF 80-200mm f/4.5-5.6"),
+    (28, "Tamron SP AF 28-105mm f/2.8 LD Aspherical IF"),
+    (28, "Tamron SP AF 28-75mm f/2.8 XR Di LD Aspherical [IF] Macro"),
+    (28, "Tamron AF 70-300mm f/4-5.6 Di LD 1:2 Macro"),
+    (28, "Tamron AF Aspherical 28-200mm f/3.8-5.6"),
+    (29, "Canon EF 50mm f/1.8 II"),
+    (30, "Canon EF 35-105mm f/4.5-5.6"),
+    (31, "Canon EF 75-300mm f/4-5.6"),
+    (31, "Tamron SP AF 300mm f/2.8 LD IF"),
+    (32, "Canon EF 24mm f/2.8"),
+    (32, "Sigma 15mm f/2.8 EX Fisheye"),
+    (35, "Canon EF 35-80mm f/4-5.6"),
+    (36, "Canon EF 38-76mm f/4.5-5.6"),
+    (37, "Canon EF 35-80mm f/4-5.6"),
+    (37, "Tamron 70-200mm f/2.8 Di LD IF Macro"),
+    (37, "Tamron SP AF 17-50mm f/2.8 XR Di II VC LD Aspherical [IF]"),
+    (38, "Canon EF 80-200mm f/4.5-5.6 II"),
+    (39, "Canon EF 75-300mm f/4-5.6"),
+    (40, "Canon EF 28-80mm f/3.5-5.6"),
+    (41, "Canon EF 28-90mm f/4-5.6"),
+    (42, "Canon EF 28-200mm f/3.5-5.6"),
+    (42, "Tamron AF 28-300mm f/3.5-6.3 XR Di VC LD Aspherical [IF] Macro"),
+    (43, "Canon EF 28-105mm f/4-5.6"),
+    (44, "Canon EF 90-300mm f/4.5-5.6"),
+    (45, "Canon EF-S 18-55mm f/3.5-5.6 [II]"),
+    (46, "Canon EF 28-90mm f/4-5.6"),
+    (48, "Canon EF-S 18-55mm f/3.5-5.6 IS"),
+    (49, "Canon EF-S 55-250mm f/4-5.6 IS"),
+    (50, "Canon EF-S 18-200mm f/3.5-5.6 IS"),
+    (51, "Canon EF-S 18-135mm f/3.5-5.6 IS"),
+    (52, "Canon EF-S 18-55mm f/3.5-5.6 IS II"),
+    (53, "Canon EF-S 18-55mm f/3.5-5.6 III"),
+    (54, "Canon EF-S 55-250mm f/4-5.6 IS II"),
+    (80, "Canon TS-E 50mm f/2.8L Macro"),
+    (81, "Canon TS-E 90mm f/2.8L Macro"),
+    (82, "Canon TS-E 135mm f/4L Macro"),
+    (94, "Canon TS-E 17mm f/4L"),
+    (95, "Canon TS-E 24mm f/3.5L II"),
+    (124, "Canon MP-E 65mm f/2.8 1-5x Macro Photo"),
+    (125, "Canon TS-E 24mm f/3.5L"),
+    (126, "Canon TS-E 45mm f/2.8"),
+    (127, "Canon TS-E 90mm f/2.8"),
+    (129, "Canon EF 300mm f/2.8L USM"),
+    (130, "Canon EF 50mm f/1.0L USM"),
+    (131, "Canon EF 28-80mm f/2.8-4L USM"),
+    (131, "Sigma 8mm f/3.5 EX DG Circular Fisheye"),
+    (131, "Sigma 17-35mm f/2.8-4 EX DG Aspherical HSM"),
+    (131, "Sigma 17-70mm f/2.8-4.5 DC Macro"),
+    (131, "Sigma APO 50-150mm f/2.8 EX DC HSM"),
+    (131, "Sigma APO 120-300mm f/2.8 EX DG HSM"),
+    (131, "Sigma 4.5mm f/2.8 EX DC HSM Circular Fisheye"),
+    (131, "Sigma 70-200mm f/2.8 APO EX HSM"),
+    (132, "Canon EF 1200mm f/5.6L USM"),
+    (134, "Canon EF 600mm f/4L IS USM"),
+    (135, "Canon EF 200mm f/1.8L USM"),
+    (136, "Canon EF 300mm f/2.8L USM"),
+    (137, "Canon EF 85mm f/1.2L USM"),
+    (137, "Sigma 18-50mm f/2.8-4.5 DC OS HSM"),
+    (137, "Sigma 50-200mm f/4-5.6 DC OS HSM"),
+    (137, "Sigma 18-250mm f/3.5-6.3 DC OS HSM"),
+    (137, "Sigma 24-70mm f/2.8 IF EX DG HSM"),
+    (137, "Sigma 18-125mm f/3.8-5.6 DC OS HSM"),
+    (137, "Sigma 17-70mm f/2.8-4 DC Macro OS HSM"),
+    (137, "Sigma 17-50mm f/2.8 OS HSM"),
+    (137, "Sigma 18-200mm f/3.5-6.3 DC OS HSM"),
+    (137, "Tamron AF 18-270mm f/3.5-6.3 Di II VC PZD"),
+    (137, "Sigma 8-16mm f/4.5-5.6 DC HSM"),
+    (137, "Tamron SP 60mm f/2 Macro Di II"),
+    (137, "Sigma 10-20mm f/3.5 EX DC HSM"),
+    (137, "Tamron SP 24-70mm f/2.8 Di VC USD"),
+    (137, "Sigma 18-35mm f/1.8 DC HSM"),
+    (137, "Sigma 12-24mm f/4.5-5.6 DG HSM II"),
+    (138, "Canon EF 28-80mm f/2.8-4L"),
+    (139, "Canon EF 400mm f/2.8L USM"),
+    (140, "Canon EF 500mm f/4.5L USM"),
+    (141, "Canon EF 500mm f/4.5L USM"),
+    (142, "Canon EF 300mm f/2.8L IS USM"),
+    (143, "Canon EF 500mm f/4L IS USM"),
+    (144, "Canon EF 35-135mm f/4-5.6 USM"),
+    (145, "Canon EF 100-300mm f/4.5-5.6 USM"),
+    (146, "Canon EF 70-210mm f/3.5-4.5 USM"),
+    (147, "Canon EF 35-135mm f/4-5.6 USM"),
+    (148, "Canon EF 28-80mm f/3.5-5.6 USM"),
+    (149, "Canon EF 100mm f/2 USM"),
+    (150, "Canon EF 14mm f/2.8L USM"),
+    (150, "Sigma 20mm EX f/1.8"),
+    (150, "Sigma 30mm f/1.4 DC HSM"),
+    (150, "Sigma 24mm f/1.8 DG Macro EX"),
+    (150, "Sigma 28mm f/1.8 DG Macro EX"),
+    (151, "Canon EF 200mm f/2.8L USM"),
+    (152, "Canon EF 300mm f/4L IS USM"),
+    (152, "Sigma 12-24mm f/4.5-5.6 EX DG ASPHERICAL HSM"),
+    (152, "Sigma 14mm f/2.8 EX Aspherical HSM"),
+    (152, "Sigma 10-20mm f/4-5.6"),
+    (152, "Sigma 100-300mm f/4"),
+    (153, "Canon EF 35-350mm f/3.5-5.6L USM"),
+    (153, "Sigma 50-500mm f/4-6.3 APO HSM EX"),
+    (153, "Tamron AF 28-300mm f/3.5-6.3 XR LD Aspherical [IF] Macro"),
+    (153, "Tamron AF 18-200mm f/3.5-6.3 XR Di II LD Aspherical [IF] Macro"),
+    (153, "Tamron 18-250mm f/3.5-6.3 Di II LD Aspherical [IF] Macro"),
+    (154, "Canon EF 20mm f/2.8 USM"),
+    (155, "Canon EF 85mm f/1.8 USM"),
+    (155, "Sigma 14mm f/1.8 DG HSM | A"),
+    (156, "Canon EF 28-105mm f/3.5-4.5 USM"),
+    (156, "Tamron SP 70-300mm f/4-5.6 Di VC USD"),
+    (156, "Tamron SP AF 28-105mm f/2.8 LD Aspherical IF"),
+    (160, "Canon EF 20-35mm f/3.5-4.5 USM"),
+    (160, "Tamron AF 19-35mm f/3.5-4.5"),
+    (160, "Tokina AT-X 124 AF Pro DX 12-24mm f/4"),
+    (160, "Tokina AT-X 107 AF DX 10-17mm f/3.5-4.5 Fisheye"),
+    (160, "Tokina AT-X 116 AF Pro DX 11-16mm f/2.8"),
+    (161, "Canon EF 28-70mm f/2.8L USM"),
+    (161, "Sigma 24-70mm f/2.8 EX"),
+    (161, "Sigma 28-70mm f/2.8 EX"),
+    (161, "Sigma 24-60mm f/2.8 EX DG"),
+    (161, "Tamron AF 17-50mm f/2.8 Di-II LD Aspherical"),
+    (161, "Tamron 90mm f/2.8"),
+    (161, "Tamron SP AF 17-35mm f/2.8-4 Di LD Aspherical IF"),
+    (161, "Tamron SP AF 28-75mm f/2.8 XR Di LD Aspherical [IF] Macro"),
+    (161, "Tokina AT-X 24-70mm f/2.8 PRO FX (IF)"),
+    (162, "Canon EF 200mm f/2.8L USM"),
+    (163, "Canon EF 300mm f/4L"),
+    (164, "Canon EF 400mm f/5.6L"),
+    (165, "Canon EF 70-200mm f/2.8L USM"),
+    (166, "Canon EF 70-200mm f/2.8L USM + 1.4x"),
+    (167, "Canon EF 70-200mm f/2.8L USM + 2x"),
+    (168, "Canon EF 28mm f/1.8 USM"),
+    (168, "Sigma 50-100mm f/1.8 DC HSM | A"),
+    (169, "Canon EF 17-35mm f/2.8L USM"),
+    (169, "Sigma 18-200mm f/3.5-6.3 DC OS"),
+    (169, "Sigma 15-30mm f/3.5-4.5 EX DG Aspherical"),
+    (169, "Sigma 18-50mm f/2.8 Macro"),
+    (169, "Sigma 50mm f/1.4 EX DG HSM"),
+    (169, "Sigma 85mm f/1.4 EX DG HSM"),
+    (169, "Sigma 30mm f/1.4 EX DC HSM"),
+    (169, "Sigma 35mm f/1.4 DG HSM"),
+    (170, "Canon EF 200mm f/2.8L II USM"),
+    (170, "Sigma 300mm f/2.8 APO EX DG HSM"),
+    (170, "Sigma 800mm f/5.6 APO EX DG HSM"),
+    (171, "Canon EF 300mm f/4L USM"),
+    (172, "Canon EF 400mm f/5.6L USM"),
+    (172, "Sigma 150-600mm f/5-6.3 DG OS HSM | S"),
+    (172, "Sigma 500mm f/4.5 APO EX DG HSM"),
+    (173, "Canon EF 180mm Macro f/3.5L USM"),
+    (173, "Sigma 180mm EX HSM Macro f/3.5"),
+    (173, "Sigma APO Macro 150mm f/2.8 EX DG HSM"),
+    (173, "Sigma 10mm f/2.8 EX DC Fisheye"),
+    (173, "Sigma 15mm f/2.8 EX DG Diagonal Fisheye"),
+    (174, "Canon EF 135mm f/2L USM"),
+    (174, "Sigma 70-200mm f/2.8 EX DG APO OS HSM"),
+    (174, "Sigma 50-500mm f/4.5-6.3 APO DG OS HSM"),
+    (174, "Sigma 150-500mm f/5-6.3 APO DG OS HSM"),
+    (174, "Zeiss Milvus 100mm f/2 Makro"),
+    (174, "Sigma APO 50-150mm f/2.8 EX DC OS HSM"),
+    (174, "Sigma APO 120-300mm f/2.8 EX DG OS HSM"),
+    (174, "Sigma 120-400mm f/4.5-5.6 APO DG OS HSM"),
+    (175, "Canon EF 400mm f/2.8L USM"),
+    (176, "Canon EF 24-85mm f/3.5-4.5 USM"),
+    (177, "Canon EF 300mm f/4L IS USM"),
+    (178, "Canon EF 28-135mm f/3.5-5.6 IS"),
+    (179, "Canon EF 24mm f/1.4L USM"),
+    (180, "Canon EF 35mm f/1.4L USM"),
+    (180, "Sigma 50mm f/1.4 DG HSM | A"),
+    (180, "Sigma 24mm f/1.4 DG HSM | A"),
+    (180, "Zeiss Milvus 50mm f/1.4"),
+    (180, "Zeiss Milvus 85mm f/1.4"),
+    (180, "Zeiss Otus 28mm f/1.4"),
+    (180, "Tokina Opera 50mm f/1.4 FF"),
+    (180, "Sigma 20mm f/1.4 DG HSM | A"),
+    (181, "Canon EF 100-400mm f/4.5-5.6L IS USM + 1.4x"),
+    (181, "Sigma 150-600mm f/5-6.3 DG OS HSM | S + 1.4x"),
+    (182, "Canon EF 100-400mm f/4.5-5.6L IS USM + 2x"),
+    (182, "Sigma 150-600mm f/5-6.3 DG OS HSM | S + 2x"),
+    (183, "Canon EF 100-400mm f/4.5-5.6L IS USM"),
+    (183, "Sigma 150mm f/2.8 EX DG OS HSM APO Macro"),
+    (183, "Sigma 105mm f/2.8 EX DG OS HSM Macro"),
+    (183, "Sigma 180mm f/2.8 EX DG OS HSM APO Macro"),
+    (183, "Sigma 150-600mm f/5-6.3 DG OS HSM | C"),
+    (183, "Sigma 100-400mm f/5-6.3 DG OS HSM"),
+    (184, "Canon EF 400mm f/2.8L USM + 2x"),
+    (185, "Canon EF 600mm f/4L IS USM"),
+    (186, "Canon EF 70-200mm f/4L USM"),
+    (187, "Canon EF 70-200mm f/4L USM + 1.4x"),
+    (188, "Canon EF 70-200mm f/4L USM + 2x"),
+    (189, "Canon EF 70-200mm f/4L USM + 2.8x"),
+    (190, "Canon EF 100mm f/2.8 Macro USM"),
+    (191, "Canon EF 400mm f/4 DO IS"),
+    (191, "Sigma 500mm f/4 DG OS HSM"),
+    (193, "Canon EF 35-80mm f/4-5.6 USM"),
+    (194, "Canon EF 80-200mm f/4.5-5.6 USM"),
+    (195, "Canon EF 35-105mm f/4.5-5.6 USM"),
+    (196, "Canon EF 75-300mm f/4-5.6 USM"),
+    (197, "Canon EF 75-300mm f/4-5.6 IS USM"),
+    (197, "Sigma 18-300mm f/3.5-6.3 DC Macro OS HS"),
+    (198, "Canon EF 50mm f/1.4 USM"),
+    (198, "Zeiss Otus 55mm f/1.4 ZE"),
+    (198, "Zeiss Otus 85mm f/1.4 ZE"),
+    (198, "Zeiss Milvus 25mm f/1.4"),
+    (198, "Zeiss Otus 100mm f/1.4"),
+    (198, "Zeiss Milvus 35mm f/1.4 ZE"),
+    (198, "Yongnuo YN 35mm f/2"),
+    (199, "Canon EF 28-80mm f/3.5-5.6 USM"),
+    (200, "Canon EF 75-300mm f/4-5.6 USM"),
+    (201, "Canon EF 28-80mm f/3.5-5.6 USM"),
+    (202, "Canon EF 28-80mm f/3.5-5.6 USM IV"),
+    (208, "Canon EF 22-55mm f/4-5.6 USM"),
+    (209, "Canon EF 55-200mm f/4.5-5.6"),
+    (210, "Canon EF 28-90mm f/4-5.6 USM"),
+    (211, "Canon EF 28-200mm f/3.5-5.6 USM"),
+    (212, "Canon EF 28-105mm f/4-5.6 USM"),
+    (213, "Canon EF 90-300mm f/4.5-5.6 USM"),
+    (213, "Tamron SP 150-600mm f/5-6.3 Di VC USD"),
+    (213, "Tamron 16-300mm f/3.5-6.3 Di II VC PZD Macro"),
+    (213, "Tamron SP 35mm f/1.8 Di VC USD"),
+    (213, "Tamron SP 45mm f/1.8 Di VC USD"),
+    (214, "Canon EF-S 18-55mm f/3.5-5.6 USM"),
+    (215, "Canon EF 55-200mm f/4.5-5.6 II USM"),
+    (217, "Tamron AF 18-270mm f/3.5-6.3 Di II VC PZD"),
+    (224, "Canon EF 70-200mm f/2.8L IS USM"),
+    (225, "Canon EF 70-200mm f/2.8L IS USM + 1.4x"),
+    (226, "Canon EF 70-200mm f/2.8L IS USM + 2x"),
+    (227, "Canon EF 70-200mm f/2.8L IS USM + 2.8x"),
+    (228, "Canon EF 28-105mm f/3.5-4.5 USM"),
+    (229, "Canon EF 16-35mm f/2.8L USM"),
+    (230, "Canon EF 24-70mm f/2.8L USM"),
+    (231, "Canon EF 17-40mm f/4L USM"),
+    (231, "Sigma 12-24mm f/4 DG HSM A016"),
+    (232, "Canon EF 70-300mm f/4.5-5.6 DO IS USM"),
+    (233, "Canon EF 28-300mm f/3.5-5.6L IS USM"),
+    (234, "Canon EF-S 17-85mm f/4-5.6 IS USM"),
+    (234, "Tokina AT-X 12-28 PRO DX 12-28mm f/4"),
+    (235, "Canon EF-S 10-22mm f/3.5-4.5 USM"),
+    (236, "Canon EF-S 60mm f/2.8 Macro USM"),
+    (237, "Canon EF 24-105mm f/4L IS USM"),
+    (238, "Canon EF 70-300mm f/4-5.6 IS USM"),
+    (239, "Canon EF 85mm f/1.2L II USM"),
+    (239, "Rokinon SP 85mm f/1.2"),
+    (240, "Canon EF-S 17-55mm f/2.8 IS USM"),
+    (240, "Sigma 17-50mm f/2.8 EX DC OS HSM"),
+    (241, "Canon EF 50mm f/1.2L USM"),
+    (242, "Canon EF 70-200mm f/4L IS USM"),
+    (243, "Canon EF 70-200mm f/4L IS USM + 1.4x"),
+    (244, "Canon EF 70-200mm f/4L IS USM + 2x"),
+    (245, "Canon EF 70-200mm f/4L IS USM + 2.8x"),
+    (246, "Canon EF 16-35mm f/2.8L II USM"),
+    (247, "Canon EF 14mm f/2.8L II USM"),
+    (248, "Canon EF 200mm f/2L IS USM"),
+    (248, "Sigma 24-35mm f/2 DG HSM | A"),
+    (248, "Sigma 135mm f/1.8 DG HSM A017"),
+    (248, "Sigma 105mm f/1.4 DG HSM | A"),
+    (248, "Sigma 14-24mm f/2.8 DG HSM | A"),
+    (249, "Canon EF 800mm f/5.6L IS USM"),
+    (250, "Canon EF 24mm f/1.4L II USM"),
+    (250, "Sigma 20mm f/1.4 DG HSM | A"),
+    (250, "Tokina Opera 16-28mm f/2.8 FF"),
+    (250, "Sigma 85mm f/1.4 DG HSM | A"),
+    (251, "Canon EF 70-200mm f/2.8L IS II USM"),
+    (251, "Canon EF 70-200mm f/2.8L IS III USM"),
+    (252, "Canon EF 70-200mm f/2.8L IS II USM + 1.4x"),
+    (253, "Canon EF 70-200mm f/2.8L IS II USM + 2x"),
+    (254, "Canon EF 100mm f/2.8L Macro IS USM"),
+    (254, "Tamron SP 90mm f/2.8 Di VC USD Macro 1:1"),
+    (255, "Sigma 24-105mm f/4 DG OS HSM | A"),
+    (255, "Sigma 180mm f/2.8 EX DG OS HSM APO Macro"),
+    (488, "Canon EF-S 15-85mm f/3.5-5.6 IS USM"),
+    (489, "Canon EF 70-300mm f/4-5.6L IS USM"),
+    (490, "Canon EF 8-15mm f/4L Fisheye USM"),
+    (491, "Canon EF 300mm f/2.8L IS II USM"),
+    (491, "Tamron SP 70-200mm f/2.8 Di VC USD G2 (A025)"),
+    (491, "Tamron 18-400mm f/3.5-6.3 Di II VC HLD (B028)"),
+    (491, "Tamron 100-400mm f/4.5-6.3 Di VC USD (A035)"),
+    (491, "Tamron 70-210mm f/4 Di VC USD (A034)"),
+    (491, "Tamron SP 24-70mm f/2.8 Di VC USD G2 (A032)"),
+    (492, "Canon EF 400mm f/2.8L IS II USM"),
+    (493, "Canon EF 500mm f/4L IS II USM"),
+    (493, "Canon EF 24-105mm f/4L IS USM"),
+    (494, "Canon EF 600mm f/4L IS II USM"),
+    (495, "Canon EF 24-70mm f/2.8L II USM"),
+    (495, "Sigma 24-70mm f/2.8 DG OS HSM | A"),
+    (496, "Canon EF 200-400mm f/4L IS USM"),
+    (499, "Canon EF 200-400mm f/4L IS USM + 1.4x"),
+    (502, "Canon EF 28mm f/2.8 IS USM"),
+    (502, "Tamron 35mm f/1.8 Di VC USD (F012)"),
+    (503, "Canon EF 24mm f/2.8 IS USM"),
+    (504, "Canon EF 24-70mm f/4L IS USM"),
+    (505, "Canon EF 35mm f/2 IS USM"),
+    (506, "Canon EF 400mm f/4 DO IS II USM"),
+    (507, "Canon EF 16-35mm f/4L IS USM"),
+    (508, "Canon EF 11-24mm f/4L USM"),
+    (508, "Tamron 10-24mm f/3.5-4.5 Di II VC HLD (B023)"),
+    (747, "Canon EF 100-400mm f/4.5-5.6L IS II USM"),
+    (747, "Tamron SP 150-600mm f/5-6.3 Di VC USD G2"),
+    (748, "Canon EF 100-400mm f/4.5-5.6L IS II USM + 1.4x"),
+    (749, "Canon EF 100-400mm f/4.5-5.6L IS II USM + 2x"),
+    (750, "Canon EF 35mm f/1.4L II USM"),
+    (750, "Tamron SP 85mm f/1.8 Di VC USD (F016)"),
+    (751, "Canon EF 16-35mm f/2.8L III USM"),
+    (752, "Canon EF 24-105mm f/4L IS II USM"),
+    (753, "Canon EF 85mm f/1.4L IS USM"),
+    (754, "Canon EF 70-200mm f/4L IS II USM"),
+    (757, "Canon EF 400mm f/2.8L IS III USM"),
+    (758, "Canon EF 600mm f/4L IS III USM"),
+    (4142, "Canon EF-S 18-135mm f/3.5-5.6 IS STM"),
+    (4143, "Canon EF-M 18-55mm f/3.5-5.6 IS STM"),
+    (4143, "Tamron 18-200mm f/3.5-6.3 Di III VC"),
+    (4144, "Canon EF 40mm f/2.8 STM"),
+    (4145, "Canon EF-M 22mm f/2 STM"),
+    (4146, "Canon EF-S 18-55mm f/3.5-5.6 IS STM"),
+    (4147, "Canon EF-M 11-22mm f/4-5.6 IS STM"),
+    (4148, "Canon EF-S 55-250mm f/4-5.6 IS STM"),
+    (4149, "Canon EF-M 55-200mm f/4.5-6.3 IS STM"),
+    (4150, "Canon EF-S 10-18mm f/4.5-5.6 IS STM"),
+    (4152, "Canon EF 24-105mm f/3.5-5.6 IS STM"),
+    (4153, "Canon EF-M 15-45mm f/3.5-6.3 IS STM"),
+    (4154, "Canon EF-S 24mm f/2.8 STM"),
+    (4155, "Canon EF-M 28mm f/3.5 Macro IS STM"),
+    (4156, "Canon EF 50mm f/1.8 STM"),
+    (4157, "Canon EF-M 18-150mm f/3.5-6.3 IS STM"),
+    (4158, "Canon EF-S 18-55mm f/4-5.6 IS STM"),
+    (4159, "Canon EF-M 32mm f/1.4 STM"),
+    (4160, "Canon EF-S 35mm f/2.8 Macro IS STM"),
+    (36910, "Canon EF 70-300mm f/4-5.6 IS II USM"),
+    (36912, "Canon EF-S 18-135mm f/3.5-5.6 IS USM"),
+];
+
+const NIKON: &[(u32, &str)] = &[
+    (0x01, "AF Nikkor 50mm f/1.8"),
+    (0x02, "AF Zoom-Nikkor 35-70mm f/3.3-4.5"),
+    (0x03, "AF Zoom-Nikkor 80-200mm f/2.8 ED"),
+    (0x04, "AF Nikkor 28mm f/2.8"),
+    (0x05, "AF Nikkor 50mm f/1.4"),
+    (0x06, "AF Micro-Nikkor 55mm f/2.8"),
+    (0x07, "AF Zoom-Nikkor 28-85mm f/3.5-4.5"),
+    (0x08, "AF Zoom-Nikkor 35-105mm f/3.5-4.5"),
+    (0x09, "AF Nikkor 24mm f/2.8"),
+    (0x0A, "AF Nikkor 300mm f/2.8 IF-ED"),
+    (0x0B, "AF Nikkor 180mm f/2.8 IF-ED"),
+    (0x0D, "AF Zoom-Nikkor 35-135mm f/3.5-4.5"),
+    (0x0E, "AF Zoom-Nikkor 80-200mm f/2.8 ED"),
+    (0x0F, "AF Nikkor 50mm f/1.8 N"),
+    (0x10, "AF Nikkor 300mm f/4 IF-ED"),
+    (0x11, "AF Zoom-Nikkor 35-70mm f/2.8"),
+    (0x12, "AF Nikkor 70-210mm f/4-5.6"),
+    (0x13, "AF Zoom-Nikkor 24-50mm f/3.3-4.5"),
+    (0x14, "AF Zoom-Nikkor 80-200mm f/2.8 ED"),
+    (0x15, "AF Nikkor 85mm f/1.8"),
+    (0x1A, "AF Nikkor 35mm f/2"),
+    (0x1C, "AF Nikkor 20mm f/2.8"),
+    (0x1D, "AF Zoom-Nikkor 35-70mm f/3.3-4.5 N"),
+    (0x1E, "AF Micro-Nikkor 60mm f/2.8"),
+    (0x1F, "AF Micro-Nikkor 105mm f/2.8"),
+    (0x22, "AF DC-Nikkor 135mm f/2"),
+    (0x24, "AF Zoom-Nikkor 80-200mm f/2.8D ED"),
+    (0x25, "AF Zoom-Nikkor 35-70mm f/2.8D"),
+    (0x26, "AF Zoom-Nikkor 28-70mm f/3.5-4.5D"),
+    (0x2F, "AF Zoom-Nikkor 20-35mm f/2.8D IF"),
+    (0x31, "AF Micro-Nikkor 60mm f/2.8D"),
+    (0x32, "AF Micro-Nikkor 105mm f/2.8D"),
+    (0x33, "AF Nikkor 18mm f/2.8D"),
+    (0x36, "AF Nikkor 24mm f/2.8D"),
+    (0x37, "AF Nikkor 20mm f/2.8D"),
+    (0x38, "AF Nikkor 85mm f/1.8D"),
+    (0x3B, "AF Zoom-Nikkor 35-70mm f/2.8D N"),
+    (0x3D, "AF Zoom-Nikkor 35-80mm f/4-5.6D"),
+    (0x3E, "AF Nikkor 28mm f/2.8D"),
+    (0x41, "AF Nikkor 180mm f/2.8D IF-ED"),
+    (0x42, "AF Nikkor 35mm f/2D"),
+    (0x43, "AF Nikkor 50mm f/1.4D"),
+    (0x45, "AF Zoom-Nikkor 28-80mm f/3.5-5.6D"),
+    (0x46, "AF Zoom-Nikkor 35-80mm f/4-5.6D N"),
+    (0x47, "AF Zoom-Nikkor 24-50mm f/3.3-4.5D"),
+    (0x48, "AF-S Nikkor 300mm f/2.8D IF-ED"),
+    (0x49, "AF-S Nikkor 600mm f/4D IF-ED"),
+    (0x4A, "AF Nikkor 85mm f/1.4D IF"),
+    (0x4C, "AF Zoom-Nikkor 24-120mm f/3.5-5.6D IF"),
+    (0x4D, "AF Zoom-Nikkor 28-200mm f/3.5-5.6D IF"),
+    (0x4E, "AF DC-Nikkor 135mm f/2D"),
+    (0x53, "AF Zoom-Nikkor 80-200mm f/2.8D ED"),
+    (0x54, "AF Zoom-Micro Nikkor 70-180mm f/4.5-5.6D ED"),
+    (0x56, "AF Zoom-Nikkor 70-300mm f/4-5.6D ED"),
+    (0x59, "AF-S Nikkor 400mm f/2.8D IF-ED"),
+    (0x5D, "AF-S Zoom-Nikkor 28-70mm f/2.8D IF-ED"),
+    (0x5E, "AF-S Zoom-Nikkor 80-200mm f/2.8D IF-ED"),
+    (0x5F, "AF Zoom-Nikkor 28-105mm f/3.5-4.5D IF"),
+    (0x63, "AF-S Nikkor 17-35mm f/2.8D IF-ED"),
+    (0x65, "AF VR Zoom-Nikkor 80-400mm f/4.5-5.6D ED"),
+    (0x66, "AF Zoom-Nikkor 18-35mm f/3.5-4.5D IF-ED"),
+    (0x6A, "AF-S Nikkor 300mm f/4D IF-ED"),
+    (0x6D, "AF-S VR Nikkor 300mm f/2.8D IF-ED"),
+    (0x6E, "AF-S VR Nikkor 400mm f/2.8D IF-ED"),
+    (0x6F, "AF-S VR Nikkor 500mm f/4D IF-ED"),
+    (0x70, "AF-S VR Nikkor 600mm f/4D IF-ED"),
+    (0x72, "Nikkor 45mm f/2.8 P"),
+    (0x74, "AF-S Zoom-Nikkor 24-85mm f/3.5-4.5G IF-ED"),
+    (0x75, "AF Zoom-Nikkor 28-100mm f/3.5-5.6G"),
+    (0x76, "AF Nikkor 50mm f/1.8D"),
+    (0x77, "AF-S VR Zoom-Nikkor 70-200mm f/2.8G IF-ED"),
+    (0x78, "AF-S VR Zoom-Nikkor 24-120mm f/3.5-5.6G IF-ED"),
+    (0x79, "AF Zoom-Nikkor 28-200mm f/3.5-5.6G IF-ED"),
+    (0x7A, "AF-S DX Zoom-Nikkor 12-24mm f/4G IF-ED"),
+    (0x7B, "AF-S VR Zoom-Nikkor 200-400mm f/4G IF-ED"),
+    (0x7D, "AF-S DX Zoom-Nikkor 17-55mm f/2.8G IF-ED"),
+    (0x7F, "AF-S DX Zoom-Nikkor 18-70mm f/3.5-4.5G IF-ED"),
+    (0x80, "AF DX Fisheye-Nikkor 10.5mm f/2.8G ED"),
+    (0x81, "AF-S VR Nikkor 200mm f/2G IF-ED"),
+    (0x82, "AF-S VR Nikkor 300mm f/2.8G IF-ED"),
+    (0x89, "AF-S DX Zoom-Nikkor 55-200mm f/4-5.6G ED"),
+    (0x8A, "AF-S VR Micro-Nikkor 105mm f/2.8G IF-ED"),
+    (0x8B, "AF-S DX VR Zoom-Nikkor 18-200mm f/3.5-5.6G IF-ED"),
+    (0x8C, "AF-S DX Zoom-Nikkor 18-55mm f/3.5-5.6G ED"),
+    (0x8D, "AF-S VR Zoom-Nikkor 70-300mm f/4.5-5.6G IF-ED"),
+    (0x8F, "AF-S DX Zoom-Nikkor 18-135mm f/3.5-5.6G IF-ED"),
+    (0x90, "AF-S DX VR Zoom-Nikkor 55-200mm f/4-5.6G IF-ED"),
+    (0x92, "AF-S Zoom-Nikkor 14-24mm f/2.8G ED"),
+    (0x93, "AF-S Zoom-Nikkor 24-70mm f/2.8G ED"),
+    (0x94, "AF-S DX Zoom-Nikkor 18-55mm f/3.5-5.6G ED II"),
+    (0x95, "PC-E Nikkor 24mm f/3.5D ED"),
+    (0x96, "AF-S VR Nikkor 400mm f/2.8G ED"),
+    (0x97, "AF-S VR Nikkor 500mm f/4G ED"),
+    (0x98, "AF-S VR Nikkor 600mm f/4G ED"),
+    (0x99, "AF-S DX VR Zoom-Nikkor 16-85mm f/3.5-5.6G ED"),
+    (0x9A, "AF-S DX VR Zoom-Nikkor 18-55mm f/3.5-5.6G"),
+    (0x9B, "PC-E Micro Nikkor 45mm f/2.8D ED"),
+    (0x9C, "AF-S Micro Nikkor 60mm f/2.8G ED"),
+    (0x9D, "PC-E Micro Nikkor 85mm f/2.8D"),
+    (0x9E, "AF-S DX VR Zoom-Nikkor 18-105mm f/3.5-5.6G ED"),
+    (0x9F, "AF-S DX Nikkor 35mm f/1.8G"),
+    // From 0xA0 on Nikon reuses numbers; the rest of LensData tells them apart
+    (0xA0, "AF-S Nikkor 50mm f/1.4G"),
+    (0xA0, "AF-S DX Nikkor 18-140mm f/3.5-5.6G ED VR"),
+    (0xA0, "AF-P DX Nikkor 18-55mm f/3.5-5.6G"),
+    (0xA1, "AF-S DX Nikkor 10-24mm f/3.5-4.5G ED"),
+    (0xA1, "AF-S Nikkor 58mm f/1.4G"),
+    (0xA2, "AF-S Nikkor 70-200mm f/2.8G ED VR II"),
+    (0xA2, "AF-S DX VR Nikkor 18-55mm f/3.5-5.6G II"),
+    (0xA3, "AF-S Nikkor 16-35mm f/4G ED VR"),
+    (0xA3, "AF-P DX Nikkor 70-300mm f/4.5-6.3G ED VR"),
+    (0xA4, "AF-S Nikkor 24mm f/1.4G ED"),
+    (0xA4, "AF-S DX Nikkor 18-300mm f/3.5-6.3G ED VR"),
+    (0xA5, "AF-S Nikkor 28-300mm f/3.5-5.6G ED VR"),
+    (0xA5, "AF-S Nikkor 35mm f/1.8G ED"),
+    (0xA6, "AF-S Nikkor 300mm f/2.8G IF-ED VR II"),
+    (0xA6, "AF-S Nikkor 400mm f/2.8E FL ED VR"),
+    (0xA7, "AF-S DX Micro Nikkor 85mm f/3.5G ED VR"),
+    (0xA7, "AF-S DX Nikkor 55-200mm f/4-5.6G ED VR II"),
+    (0xA8, "AF-S Zoom-Nikkor 200-400mm f/4G IF-ED VR II"),
+    (0xA8, "AF-S Nikkor 300mm f/4E PF ED VR"),
+    (0xA9, "AF-S Nikkor 200mm f/2G ED VR II"),
+    (0xA9, "AF-S Nikkor 20mm f/1.8G ED"),
+    (0xAA, "AF-S Nikkor 24-120mm f/4G ED VR"),
+    (0xAA, "AF-S Nikkor 24-70mm f/2.8E ED VR"),
+    (0xAB, "AF-S Nikkor 500mm f/4E FL ED VR"),
+    (0xAC, "AF-S DX VR Nikkor 55-300mm f/4.5-5.6G ED"),
+    (0xAC, "AF-S Nikkor 600mm f/4E FL ED VR"),
+    (0xAD, "AF-S DX Nikkor 18-300mm f/3.5-5.6G ED VR"),
+    (0xAD, "AF-S DX Nikkor 16-80mm f/2.8-4E ED VR"),
+    (0xAE, "AF-S Nikkor 85mm f/1.4G"),
+    (0xAE, "AF-S Nikkor 200-500mm f/5.6E ED VR"),
+    (0xAF, "AF-S Nikkor 35mm f/1.4G"),
+    (0xAF, "AF-S Nikkor 24mm f/1.8G ED"),
+    (0xB0, "AF-S Nikkor 50mm f/1.8G"),
+    (0xB1, "AF-S DX Micro Nikkor 40mm f/2.8G"),
+    (0xB2, "AF-S Nikkor 70-200mm f/4G ED VR"),
+    (0xB3, "AF-S Nikkor 85mm f/1.8G"),
+    (0xB4, "AF-S Zoom-Nikkor 24-85mm f/3.5-4.5G IF-ED VR"),
+    (0xB5, "AF-S Nikkor 28mm f/1.8G"),
+    (0xB7, "AF-S Nikkor 80-400mm f/4.5-5.6G ED VR"),
+    (0xB8, "AF-S Nikkor 18-35mm f/3.5-4.5G ED"),
+];
+
+const MINOLTA: &[(u32, &str)] = &[
+    (0, "Minolta AF 28-85mm F3.5-4.5 New"),
+    (1, "Minolta AF 80-200mm F2.8 HS-APO G"),
+    (2, "Minolta AF 28-70mm F2.8 G"),
+    (3, "Minolta AF 28-80mm F4-5.6"),
+    (4, "Minolta AF 85mm F1.4G"),
+    (5, "Minolta AF 35-70mm F3.5-4.5 [II]"),
+    (6, "Minolta AF 24-85mm F3.5-4.5 [New]"),
+    (7, "Minolta AF 100-300mm F4.5-5.6 APO [New]"),
+    (7, "Minolta AF 100-400mm F4.5-6.7 APO"),
+    (8, "Minolta AF 70-210mm F4.5-5.6 [II]"),
+    (9, "Minolta AF 50mm F3.5 Macro"),
+    (10, "Minolta AF 28-105mm F3.5-4.5 [New]"),
+    (11, "Minolta AF 300mm F4 HS-APO G"),
+    (12, "Minolta AF 100mm F2.8 Soft Focus"),
+    (13, "Minolta AF 75-300mm F4.5-5.6 (New or II)"),
+    (14, "Minolta AF 100-400mm F4.5-6.7 APO"),
+    (15, "Minolta AF 400mm F4.5 HS-APO G"),
+    (16, "Minolta AF 17-35mm F3.5 G"),
+    (17, "Minolta AF 20-35mm F3.5-4.5"),
+    (18, "Minolta AF 28-80mm F3.5-5.6 II"),
+    (19, "Minolta AF 35mm F1.4 G"),
+    (20, "Minolta/Sony 135mm F2.8 [T4.5] STF"),
+    (22, "Minolta AF 35-80mm F4-5.6 II"),
+    (23, "Minolta AF 200mm F4 Macro APO G"),
+    (24, "Minolta/Sony AF 24-105mm F3.5-4.5 (D)"),
+    (24, "Sigma 18-50mm F2.8"),
+    (24, "Sigma 17-70mm F2.8-4.5 DC Macro"),
+    (25, "Minolta AF 100-300mm F4.5-5.6 APO (D)"),
+    (25, "Sigma 70-300mm F4-5.6 DG Macro"),
+    (27, "Minolta AF 85mm F1.4 G (D)"),
+    (28, "Minolta/Sony AF 100mm F2.8 Macro (D)"),
+    (28, "Tamron SP AF 90mm F2.8 Di Macro"),
+    (29, "Minolta/Sony AF 75-300mm F4.5-5.6 (D)"),
+    (30, "Minolta AF 28-80mm F3.5-5.6 (D)"),
+    (30, "Sigma 10-20mm F4-5.6 EX DC"),
+    (31, "Minolta/Sony AF 50mm F2.8 Macro (D)"),
+    (31, "Minolta/Sony AF 50mm F3.5 Macro"),
+    (32, "Minolta/Sony AF 300mm F2.8 G APO (D) SSM"),
+    (33, "Minolta/Sony AF 70-200mm F2.8 G"),
+    (35, "Minolta AF 85mm F1.4 G (D) Limited"),
+    (36, "Minolta AF 28-100mm F3.5-5.6 (D)"),
+    (38, "Minolta AF 17-35mm F2.8-4 (D)"),
+    (39, "Minolta AF 28-75mm F2.8 (D)"),
+    (40, "Minolta/Sony AF DT 18-70mm F3.5-5.6 (D)"),
+    (40, "Sony AF DT 18-200mm F3.5-6.3"),
+    (41, "Minolta/Sony AF DT 11-18mm F4.5-5.6 (D)"),
+    (41, "Tamron SP AF 11-18mm F4.5-5.6 Di II LD Aspherical IF"),
+    (42, "Minolta/Sony AF DT 18-200mm F3.5-6.3 (D)"),
+    (43, "Sony 35mm F1.4 G (SAL35F14G)"),
+    (44, "Sony 50mm F1.4 (SAL50F14)"),
+    (45, "Carl Zeiss Planar T* 85mm F1.4 ZA (SAL85F14Z)"),
+    (46, "Carl Zeiss Vario-Sonnar T* DT 16-80mm F3.5-4.5 ZA (SAL1680Z)"),
+    (47, "Carl Zeiss Sonnar T* 135mm F1.8 ZA (SAL135F18Z)"),
+    (48, "Carl Zeiss Vario-Sonnar T* 24-70mm F2.8 ZA SSM (SAL2470Z)"),
+    (48, "Carl Zeiss Vario-Sonnar T* 24-70mm F2.8 ZA SSM II (SAL2470Z2)"),
+    (49, "Sony DT 55-200mm F4-5.6 (SAL55200)"),
+    (50, "Sony DT 18-250mm F3.5-6.3 (SAL18250)"),
+    (51, "Sony DT 16-105mm F3.5-5.6 (SAL16105)"),
+    (52, "Sony 70-300mm F4.5-5.6 G SSM (SAL70300G)"),
+    (52, "Sony 70-300mm F4.5-5.6 G SSM II (SAL70300G2)"),
+    (53, "Sony 70-400mm F4-5.6 G SSM (SAL70400G)"),
+    (54, "Carl Zeiss Vario-Sonnar T* 16-35mm F2.8 ZA SSM (SAL1635Z)"),
+    (54, "Carl Zeiss Vario-Sonnar T* 16-35mm F2.8 ZA SSM II (SAL1635Z2)"),
+    (55, "Sony DT 18-55mm F3.5-5.6 SAM (SAL1855)"),
+    (56, "Sony DT 55-200mm F4-5.6 SAM (SAL55200-2)"),
+    (57, "Sony DT 50mm F1.8 SAM (SAL50F18)"),
+    (57, "Tamron SP AF 60mm F2 Di II LD [IF] Macro 1:1"),
+    (58, "Sony DT 30mm F2.8 Macro SAM (SAL30M28)"),
+    (59, "Sony 28-75mm F2.8 SAM (SAL2875)"),
+    (60, "Carl Zeiss Distagon T* 24mm F2 ZA SSM (SAL24F20Z)"),
+    (61, "Sony 85mm F2.8 SAM (SAL85F28)"),
+    (62, "Sony DT 35mm F1.8 SAM (SAL35F18)"),
+    (63, "Sony DT 16-50mm F2.8 SSM (SAL1650)"),
+    (64, "Sony 500mm F4 G SSM (SAL500F40G)"),
+    (65, "Sony DT 18-135mm F3.5-5.6 SAM (SAL18135)"),
+    (66, "Sony 300mm F2.8 G SSM II (SAL300F28G2)"),
+    (67, "Sony 70-200mm F2.8 G SSM II (SAL70200G2)"),
+    (68, "Sony DT 55-300mm F4.5-5.6 SAM (SAL55300)"),
+    (69, "Sony 70-400mm F4-5.6 G SSM II (SAL70400G2)"),
+    (70, "Carl Zeiss Planar T* 50mm F1.4 ZA SSM (SAL50F14Z)"),
+    (25501, "Minolta AF 50mm F1.7"),
+    (25511, "Minolta AF 35-70mm F4"),
+    (25521, "Minolta AF 28-85mm F3.5-4.5"),
+    (25531, "Minolta AF 28-135mm F4-4.5"),
+    (25541, "Minolta AF 35-105mm F3.5-4.5"),
+    (25551, "Minolta AF 70-210mm F4 Macro"),
+    (25561, "Minolta AF 135mm F2.8"),
+    (25571, "Minolta/Sony AF 28mm F2.8"),
+    (25581, "Minolta AF 24-50mm F4"),
+    (25601, "Minolta AF 100-200mm F4.5"),
+    (25611, "Minolta AF 75-300mm F4.5-5.6"),
+    (25621, "Minolta AF 50mm F1.4 [New]"),
+    (25631, "Minolta AF 300mm F2.8 APO"),
+    (25641, "Minolta AF 50mm F2.8 Macro"),
+    (25651, "Minolta AF 600mm F4 APO"),
+    (25661, "Minolta AF 24mm F2.8"),
+    (25721, "Minolta/Sony AF 500mm F8 Reflex"),
+    (25781, "Minolta/Sony AF 16mm F2.8 Fisheye"),
+    (25791, "Minolta/Sony AF 20mm F2.8"),
+    (25811, "Minolta AF 100mm F2.8 Macro [New]"),
+    (25858, "Minolta AF 35-105mm F3.5-4.5 New"),
+    (25881, "Minolta AF 70-210mm F3.5-4.5"),
+    (25891, "Minolta AF 80-200mm F2.8 APO"),
+    (25921, "Minolta AF 85mm F1.4G"),
+];
+
+const PENTAX: &[(u32, &str)] = &[
+    (0x0311, "smc PENTAX-FA SOFT 85mm F2.8"),
+    (0x0313, "smc PENTAX-F 24-50mm F4"),
+    (0x0314, "smc PENTAX-F 35-80mm F4-5.6"),
+    (0x0315, "smc PENTAX-F 80-200mm F4.7-5.6"),
+    (0x0316, "smc PENTAX-F FISH-EYE 17-28mm F3.5-4.5"),
+    (0x0317, "smc PENTAX-F 100-300mm F4.5-5.6"),
+    (0x0318, "smc PENTAX-F 35-135mm F3.5-4.5"),
+    (0x0319, "smc PENTAX-F 35-105mm F4-5.6"),
+    (0x0401, "smc PENTAX-FA SOFT 28mm F2.8"),
+    (0x0402, "smc PENTAX-FA 80-320mm F4.5-5.6"),
+    (0x0403, "smc PENTAX-FA 43mm F1.9 Limited"),
+    (0x0406, "smc PENTAX-FA 35-80mm F4-5.6"),
+    (0x040C, "smc PENTAX-FA 50mm F1.4"),
+    (0x040F, "smc PENTAX-FA 28-105mm F4-5.6 [IF]"),
+    (0x0414, "smc PENTAX-FA 28-80mm F3.5-5.6"),
+    (0x0417, "smc PENTAX-FA 20-35mm F4 AL"),
+    (0x07C9, "smc PENTAX-DA L 50-200mm F4-5.6 ED WR"),
+    (0x07CA, "smc PENTAX-DA L 18-55mm F3.5-5.6 AL WR"),
+    (0x07CB, "HD PENTAX-DA 55-300mm F4-5.8 ED WR"),
+    (0x07CC, "HD PENTAX-DA 15mm F4 ED AL Limited"),
+    (0x07CD, "HD PENTAX-DA 35mm F2.8 Macro Limited"),
+    (0x07CE, "HD PENTAX-DA 70mm F2.4 Limited"),
+    (0x07CF, "HD PENTAX-DA 21mm F3.2 ED AL Limited"),
+    (0x07D0, "HD PENTAX-DA 40mm F2.8 Limited"),
+    (0x07D4, "smc PENTAX-DA 50mm F1.8"),
+    (0x07D5, "smc PENTAX-DA 40mm F2.8 XS"),
+    (0x07D6, "smc PENTAX-DA 35mm F2.4 AL"),
+    (0x07D8, "smc PENTAX-DA L 55-300mm F4-5.8 ED"),
+    (0x07D9, "smc PENTAX-DA 50-200mm F4-5.6 ED WR"),
+    (0x07DA, "smc PENTAX-DA 18-55mm F3.5-5.6 AL WR"),
+    (0x07DC, "Tamron SP AF 10-24mm F3.5-4.5 Di II LD Aspherical [IF]"),
+    (0x07DD, "smc PENTAX-DA L 50-200mm F4-5.6 ED"),
+    (0x07DE, "smc PENTAX-DA L 18-55mm F3.5-5.6"),
+    (0x07DF, "Samsung/Schneider D-XENON 18-55mm F3.5-5.6 II"),
+    (0x07E0, "smc PENTAX-DA 15mm F4 ED AL Limited"),
+    (0x07E1, "Samsung/Schneider D-XENON 18-250mm F3.5-6.3"),
+    (0x07E2, "smc PENTAX-DA* 55mm F1.4 SDM (SDM unused)"),
+    (0x07E3, "smc PENTAX-DA* 60-250mm F4 [IF] SDM (SDM unused)"),
+    (0x07E5, "smc PENTAX-DA 17-70mm F4 AL [IF] SDM (SDM unused)"),
+    (0x07E6, "Tamron AF 17-50mm F2.8 XR Di-II LD (Model A16)"),
+    (0x07E7, "smc PENTAX-DA 18-250mm F3.5-6.3 ED AL [IF]"),
+    (0x07E9, "smc PENTAX-DA 35mm F2.8 Macro Limited"),
+    (0x07EA, "smc PENTAX-DA* 300mm F4 ED [IF] SDM (SDM unused)"),
+    (0x07EB, "smc PENTAX-DA* 200mm F2.8 ED [IF] SDM (SDM unused)"),
+    (0x07EC, "smc PENTAX-DA 55-300mm F4-5.8 ED"),
+    (0x07EE, "Tamron AF 18-250mm F3.5-6.3 Di II LD Aspherical [IF] Macro"),
+    (0x07F1, "smc PENTAX-DA* 50-135mm F2.8 ED [IF] SDM (SDM unused)"),
+    (0x07F2, "smc PENTAX-DA* 16-50mm F2.8 ED AL [IF] SDM (SDM unused)"),
+    (0x07F3, "smc PENTAX-DA 70mm F2.4 Limited"),
+    (0x07F4, "smc PENTAX-DA 21mm F3.2 AL Limited"),
+    (0x07F5, "Schneider D-XENON 50-200mm F4-5.6"),
+    (0x07F6, "Schneider D-XENON 18-55mm F3.5-5.6"),
+    (0x07F7, "smc PENTAX-DA FISH-EYE 10-17mm F3.5-4.5 ED [IF]"),
+    (0x07F8, "smc PENTAX-DA 12-24mm F4 ED AL [IF]"),
+    (0x07F9, "Tamron XR DiII 18-200mm F3.5-6.3 (A14)"),
+    (0x07FA, "smc PENTAX-DA 50-200mm F4-5.6 ED"),
+    (0x07FB, "smc PENTAX-DA 40mm F2.8 Limited"),
+    (0x07FC, "smc PENTAX-DA 18-55mm F3.5-5.6 AL"),
+    (0x07FD, "smc PENTAX-DA 14mm F2.8 ED[IF]"),
+    (0x07FE, "smc PENTAX-DA 16-45mm F4 ED AL"),
+    (0x08D2, "smc PENTAX-DA 18-270mm F3.5-6.3 ED SDM"),
+    (0x08D7, "smc PENTAX-DA 18-135mm F3.5-5.6 ED AL [IF] DC WR"),
+    (0x08E2, "smc PENTAX-DA* 55mm F1.4 SDM"),
+    (0x08E3, "smc PENTAX-DA* 60-250mm F4 [IF] SDM"),
+    (0x08E8, "smc PENTAX-DA 17-70mm F4 AL [IF] SDM"),
+    (0x08EA, "smc PENTAX-DA* 300mm F4 ED [IF] SDM"),
+    (0x08EB, "smc PENTAX-DA* 200mm F2.8 ED [IF] SDM"),
+    (0x08F1, "smc PENTAX-DA* 50-135mm F2.8 ED [IF] SDM"),
+    (0x08F2, "smc PENTAX-DA* 16-50mm F2.8 ED AL [IF] SDM"),
+];
+
+const OLYMPUS: &[(u32, &str)] = &[
+    (0x000100, "Olympus Zuiko Digital ED 50mm F2.0 Macro"),
+    (0x000101, "Olympus Zuiko Digital 40-150mm F3.5-4.5"),
+    (0x000110, "Olympus M.Zuiko Digital ED 14-42mm F3.5-5.6"),
+    (0x000200, "Olympus Zuiko Digital ED 150mm F2.0"),
+    (0x000210, "Olympus M.Zuiko Digital 17mm F2.8 Pancake"),
+    (0x000300, "Olympus Zuiko Digital ED 300mm F2.8"),
+    (0x000310, "Olympus M.Zuiko Digital ED 14-150mm F4.0-5.6 [II]"),
+    (0x000410, "Olympus M.Zuiko Digital ED 9-18mm F4.0-5.6"),
+    (0x000500, "Olympus Zuiko Digital 14-54mm F2.8-3.5"),
+    (0x000501, "Olympus Zuiko Digital Pro ED 90-250mm F2.8"),
+    (0x000510, "Olympus M.Zuiko Digital ED 14-42mm F3.5-5.6 L"),
+    (0x000600, "Olympus Zuiko Digital ED 50-200mm F2.8-3.5"),
+    (0x000601, "Olympus Zuiko Digital ED 8mm F3.5 Fisheye"),
+    (0x000610, "Olympus M.Zuiko Digital ED 40-150mm F4.0-5.6"),
+    (0x000700, "Olympus Zuiko Digital 11-22mm F2.8-3.5"),
+    (0x000701, "Olympus Zuiko Digital 18-180mm F3.5-6.3"),
+    (0x000710, "Olympus M.Zuiko Digital ED 12mm F2.0"),
+    (0x000801, "Olympus Zuiko Digital 70-300mm F4.0-5.6"),
+    (0x000810, "Olympus M.Zuiko Digital ED 75-300mm F4.8-6.7"),
+    (0x000910, "Olympus M.Zuiko Digital 14-42mm F3.5-5.6 II"),
+    (0x001010, "Olympus M.Zuiko Digital ED 12-50mm F3.5-6.3 EZ"),
+    (0x001110, "Olympus M.Zuiko Digital 45mm F1.8"),
+    (0x001210, "Olympus M.Zuiko Digital ED 60mm F2.8 Macro"),
+    (0x001310, "Olympus M.Zuiko Digital 14-42mm F3.5-5.6 II R"),
+    (0x001410, "Olympus M.Zuiko Digital ED 40-150mm F4.0-5.6 R"),
+    (0x001510, "Olympus M.Zuiko Digital ED 75mm F1.8"),
+    (0x001610, "Olympus M.Zuiko Digital 17mm F1.8"),
+    (0x001810, "Olympus M.Zuiko Digital ED 75-300mm F4.8-6.7 II"),
+    (0x001910, "Olympus M.Zuiko Digital ED 12-40mm F2.8 Pro"),
+    (0x002010, "Olympus M.Zuiko Digital ED 40-150mm F2.8 Pro"),
+    (0x002110, "Olympus M.Zuiko Digital ED 14-42mm F3.5-5.6 EZ"),
+    (0x002210, "Olympus M.Zuiko Digital 25mm F1.8"),
+    (0x002310, "Olympus M.Zuiko Digital ED 7-14mm F2.8 Pro"),
+    (0x002410, "Olympus M.Zuiko Digital ED 300mm F4.0 IS Pro"),
+    (0x002510, "Olympus M.Zuiko Digital ED 8mm F1.8 Fisheye Pro"),
+    (0x002610, "Olympus M.Zuiko Digital ED 12-100mm F4.0 IS Pro"),
+    (0x002710, "Olympus M.Zuiko Digital ED 30mm F3.5 Macro"),
+    (0x002810, "Olympus M.Zuiko Digital ED 25mm F1.2 Pro"),
+    (0x002910, "Olympus M.Zuiko Digital ED 17mm F1.2 Pro"),
+    (0x003010, "Olympus M.Zuiko Digital ED 45mm F1.2 Pro"),
+    (0x003210, "Olympus M.Zuiko Digital ED 12-200mm F3.5-6.3"),
+    (0x003310, "Olympus M.Zuiko Digital 150-400mm F4.5 TC1.25x IS Pro"),
+    (0x003410, "Olympus M.Zuiko Digital ED 12-45mm F4.0 Pro"),
+    (0x003510, "Olympus M.Zuiko 100-400mm F5.0-6.3"),
+    (0x003610, "Olympus M.Zuiko Digital ED 8-25mm F4 Pro"),
+    (0x003710, "Olympus M.Zuiko Digital ED 40-150mm F4.0 Pro"),
+    (0x003910, "Olympus M.Zuiko Digital ED 90mm F3.5 Macro IS Pro"),
+];
@@ -1,10 +1,21 @@
 // bindings/rust/zrraw/src/lib.rs - CORRECTED VERSION
-use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, RgbImage, Rgba};
 use std::ffi::CStr;
 use thiserror::Error;
 use zrraw_sys::*;
 use libloading::Library; 
 
+pub mod color;
+pub mod dng;
+pub mod edit;
+pub mod exif;
+pub mod extended;
+pub mod lens;
+pub mod ljpeg;
+pub mod makernote;
+pub mod sequence;
+pub mod xmp;
+
 #[derive(Error, Debug)]
 pub enum ZrRawError {
     #[error("Invalid input data")]
@@ -19,6 +30,10 @@ pub enum ZrRawError {
     IoError,
     #[error("Corrupted data")]
     CorruptedData,
+    /// The image data uses a compression zrraw does not decode, such as DNG
+    /// 1.7 JPEG XL (52546); the metadata of such files is still readable
+    #[error("Unsupported compression {0}")]
+    UnsupportedCompression(u32),
     #[error("Unknown error: {0}")]
     Unknown(i32),
 }
@@ -46,10 +61,22 @@ pub enum RawFormat {
     Adobe(AdobeFormat),
     Fujifilm(FujifilmFormat),
     Olympus(OlympusFormat),
+    Panasonic(PanasonicFormat),
+    Pentax(PentaxFormat),
+    Sigma(SigmaFormat),
+    PhaseOne(PhaseOneFormat),
+    Hasselblad(HasselbladFormat),
+    Leaf(LeafFormat),
+    Minolta(MinoltaFormat),
+    Kodak(KodakFormat),
+    Samsung(SamsungFormat),
+    Epson(EpsonFormat),
+    /// Frames of a Magic Lantern video; only produced by `sequence::RawSequence`
+    MagicLantern(MagicLanternFormat),
 }
 
 #[derive(Debug, Clone, Copy)]
-pub enum CanonFormat { CR2 }
+pub enum CanonFormat { CR2, CRW }
 #[derive(Debug, Clone, Copy)]
 pub enum NikonFormat { NEF }
 #[derive(Debug, Clone, Copy)]
@@ -60,6 +87,28 @@ pub enum AdobeFormat { DNG }
 pub enum FujifilmFormat { RAF }
 #[derive(Debug, Clone, Copy)]
 pub enum OlympusFormat { ORF }
+#[derive(Debug, Clone, Copy)]
+pub enum PanasonicFormat { RW2 }
+#[derive(Debug, Clone, Copy)]
+pub enum PentaxFormat { PEF }
+#[derive(Debug, Clone, Copy)]
+pub enum SigmaFormat { X3F }
+#[derive(Debug, Clone, Copy)]
+pub enum PhaseOneFormat { IIQ }
+#[derive(Debug, Clone, Copy)]
+pub enum HasselbladFormat { ThreeFR, FFF }
+#[derive(Debug, Clone, Copy)]
+pub enum LeafFormat { MOS }
+#[derive(Debug, Clone, Copy)]
+pub enum MinoltaFormat { MRW }
+#[derive(Debug, Clone, Copy)]
+pub enum KodakFormat { DCR, KDC }
+#[derive(Debug, Clone, Copy)]
+pub enum SamsungFormat { SRW }
+#[derive(Debug, Clone, Copy)]
+pub enum EpsonFormat { ERF }
+#[derive(Debug, Clone, Copy)]
+pub enum MagicLanternFormat { MLV }
 
 impl From<ZrRawFormat> for RawFormat {
     fn from(format: ZrRawFormat) -> Self {
@@ -70,6 +119,19 @@ impl From<ZrRawFormat> for RawFormat {
             4 => RawFormat::Adobe(AdobeFormat::DNG),
             5 => RawFormat::Fujifilm(FujifilmFormat::RAF),
             6 => RawFormat::Olympus(OlympusFormat::ORF),
+            7 => RawFormat::Panasonic(PanasonicFormat::RW2),
+            8 => RawFormat::Pentax(PentaxFormat::PEF),
+            9 => RawFormat::Sigma(SigmaFormat::X3F),
+            10 => RawFormat::PhaseOne(PhaseOneFormat::IIQ),
+            11 => RawFormat::Hasselblad(HasselbladFormat::ThreeFR),
+            12 => RawFormat::Hasselblad(HasselbladFormat::FFF),
+            13 => RawFormat::Leaf(LeafFormat::MOS),
+            14 => RawFormat::Canon(CanonFormat::CRW),
+            15 => RawFormat::Minolta(MinoltaFormat::MRW),
+            16 => RawFormat::Kodak(KodakFormat::DCR),
+            17 => RawFormat::Kodak(KodakFormat::KDC),
+            18 => RawFormat::Samsung(SamsungFormat::SRW),
+            19 => RawFormat::Epson(EpsonFormat::ERF),
             _ => RawFormat::Unknown,
         }
     }
@@ -80,53 +142,390 @@ pub struct RawMetadata {
     pub format: RawFormat,
     pub width: u32,
     pub height: u32,
-    pub orientation: u8,
+    pub orientation: Orientation,
     pub make: String,
     pub model: String,
+    /// Lens model from the makernote, empty if the file does not name one
+    pub lens: String,
     pub iso: u32,
-    pub shutter_speed: f32,
-    pub aperture: f32,
+    pub shutter_speed: ExposureTime,
+    pub aperture: FNumber,
     pub focal_length: f32,
     pub color_matrix: [f32; 9],
     pub white_balance: [f32; 3],
     pub black_level: [f32; 4],
     pub white_level: [u32; 4],
+    /// Stops the file asks renderers to add (DNG BaselineExposure); applied by `process_file`
+    pub baseline_exposure: f32,
+    /// Frames per second of a video frame, 0 for stills
+    pub frame_rate: f32,
+    pub timecode: Option<Timecode>,
+    /// Lens, capture time, serial numbers, crop, CFA layout and more, as far
+    /// as the file records them
+    pub extended: extended::ExtendedMetadata,
+    exif: exif::Exif,
+    makernote: Option<makernote::Makernote>,
+    xmp: Option<xmp::Xmp>,
+}
+
+impl RawMetadata {
+    /// Every tag of IFD0 and the EXIF, GPS and Interop directories
+    pub fn exif(&self) -> &exif::Exif {
+        &self.exif
+    }
+
+    /// The vendor makernote, for the makes `makernote` supports
+    pub fn makernote(&self) -> Option<&makernote::Makernote> {
+        self.makernote.as_ref()
+    }
+
+    /// The XMP packet embedded in the file
+    pub fn xmp(&self) -> Option<&xmp::Xmp> {
+        self.xmp.as_ref()
+    }
+
+    /// The rating given in camera: XMP xmp:Rating, else the IFD0 Rating
+    /// tag. 1 to 5 stars, 0 for none, -1 for rejected
+    pub fn rating(&self) -> Option<i32> {
+        self.xmp.as_ref().and_then(xmp::Xmp::rating).or_else(|| self.exif.rating().map(i32::from))
+    }
+
+    /// The lens, named by the file or looked up from the makernote's lens ID
+    pub fn lens(&self) -> Option<lens::LensIdentification> {
+        let reported = self.extended.lens.as_deref().unwrap_or(&self.lens);
+        lens::identify_file(self.makernote.as_ref(), &self.exif, Some(reported))
+    }
+
+    /// Takes the extended metadata, after `read_exif`. The capture date
+    /// comes from the EXIF map, so `extended` and `exif()` always agree.
+    fn set_extended(&mut self, mut extended: extended::ExtendedMetadata) {
+        self.lens = extended.lens.clone().unwrap_or_default();
+        extended.date_time_original = self.exif.date_time_original();
+        self.extended = extended;
+    }
+
+    /// Reads the EXIF tags, the makernote and the XMP packet
+    fn read_exif(&mut self, data: &[u8]) {
+        self.exif = exif::Exif::read(data);
+        self.makernote = makernote::Makernote::read(data, &self.exif);
+        self.xmp = xmp::Xmp::from_file(data, &self.exif);
+    }
+}
+
+/// SMPTE timecode of a video frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub drop_frame: bool,
+}
+
+/// How the stored image must be turned to display upright, as in EXIF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Orientation {
+    #[default]
+    Normal = 1,
+    FlipH = 2,
+    Rotate180 = 3,
+    FlipV = 4,
+    Transpose = 5,
+    /// Turn 90 degrees clockwise
+    Rotate90 = 6,
+    Transverse = 7,
+    Rotate270 = 8,
+}
+
+impl Orientation {
+    /// Values outside 1-8 are treated as upright
+    pub fn from_exif(value: u32) -> Self {
+        match value {
+            2 => Orientation::FlipH,
+            3 => Orientation::Rotate180,
+            4 => Orientation::FlipV,
+            5 => Orientation::Transpose,
+            6 => Orientation::Rotate90,
+            7 => Orientation::Transverse,
+            8 => Orientation::Rotate270,
+            _ => Orientation::Normal,
+        }
+    }
+
+    pub fn to_exif(self) -> u16 {
+        self as u16
+    }
+
+    /// True if the displayed image has width and height swapped
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(self, Orientation::Transpose | Orientation::Rotate90 | Orientation::Transverse | Orientation::Rotate270)
+    }
+
+    /// Turns a stored image upright
+    pub fn apply(self, mut image: DynamicImage) -> DynamicImage {
+        if let Some(orientation) = image::metadata::Orientation::from_exif(self as u8) {
+            image.apply_orientation(orientation);
+        }
+        image
+    }
+}
+
+/// Exposure time in seconds, as the fraction the file records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ExposureTime(pub exif::Rational);
+
+impl ExposureTime {
+    pub fn new(num: u32, den: u32) -> Self {
+        ExposureTime(exif::Rational::new(num, den))
+    }
+
+    /// Times under a second become 1/n where that is exact enough
+    pub fn from_seconds(seconds: f64) -> Self {
+        if seconds.is_nan() || seconds <= 0.0 {
+            return ExposureTime::default();
+        }
+        let inverse = 1.0 / seconds;
+        if seconds < 1.0 && (inverse - inverse.round()).abs() < 1e-3 {
+            ExposureTime::new(1, inverse.round() as u32)
+        } else {
+            ExposureTime::new((seconds * 10000.0).round() as u32, 10000)
+        }
+    }
+
+    /// From an APEX time value, Tv = -log2(t)
+    pub fn from_apex(tv: f64) -> Self {
+        ExposureTime::from_seconds(2f64.powf(-tv))
+    }
+
+    /// Seconds, 0 if the file does not record the time
+    pub fn seconds(self) -> f64 {
+        self.0.to_f64().unwrap_or(0.0)
+    }
+
+    pub fn is_known(self) -> bool {
+        self.0.num > 0 && self.0.den > 0
+    }
+
+    pub fn to_apex(self) -> Option<f64> {
+        self.is_known().then(|| -self.seconds().log2())
+    }
+}
+
+impl std::fmt::Display for ExposureTime {
+    /// "1/250" below a second, decimal seconds otherwise
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let exif::Rational { num, den } = self.0.reduced();
+        if num == 1 && den > 1 {
+            write!(f, "1/{}", den)
+        } else {
+            write!(f, "{}", trim_decimal(self.seconds(), 4))
+        }
+    }
+}
+
+/// Aperture as an f-number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FNumber(pub exif::Rational);
+
+impl FNumber {
+    pub fn new(num: u32, den: u32) -> Self {
+        FNumber(exif::Rational::new(num, den))
+    }
+
+    /// Keeps two decimals, enough for every marked aperture
+    pub fn from_value(value: f64) -> Self {
+        if value.is_nan() || value <= 0.0 {
+            return FNumber::default();
+        }
+        FNumber(exif::Rational::new((value * 100.0).round() as u32, 100).reduced())
+    }
+
+    /// From an APEX aperture value, Av = 2 log2(N)
+    pub fn from_apex(av: f64) -> Self {
+        FNumber::from_value(2f64.powf(av / 2.0))
+    }
+
+    /// The f-number, 0 if the file does not record it
+    pub fn value(self) -> f64 {
+        self.0.to_f64().unwrap_or(0.0)
+    }
+
+    pub fn is_known(self) -> bool {
+        self.0.num > 0 && self.0.den > 0
+    }
+
+    pub fn to_apex(self) -> Option<f64> {
+        self.is_known().then(|| 2.0 * self.value().log2())
+    }
+}
+
+impl std::fmt::Display for FNumber {
+    /// "f/2.8", "f/11"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "f/{}", trim_decimal(self.value(), 1))
+    }
+}
+
+/// Formats with at most `places` decimals and no trailing zeros
+fn trim_decimal(value: f64, places: usize) -> String {
+    let text = format!("{:.*}", places, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+/// Reads a fixed-size, NUL-terminated C string field
+fn c_array_to_string(chars: &[std::os::raw::c_char]) -> String {
+    let bytes: Vec<u8> = chars.iter().map(|&c| c as u8).collect();
+    match CStr::from_bytes_until_nul(&bytes) {
+        Ok(s) => s.to_string_lossy().into_owned(),
+        Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    }
 }
 
 impl From<ZrRawMetadata> for RawMetadata {
     fn from(meta: ZrRawMetadata) -> Self {
-        let make = unsafe {
-            CStr::from_ptr(meta.make.as_ptr() as *const i8)
-                .to_string_lossy()
-                .into_owned()
-        };
-        let model = unsafe {
-            CStr::from_ptr(meta.model.as_ptr() as *const i8)
-                .to_string_lossy()
-                .into_owned()
-        };
-        let shutter_speed = if meta.shutter_speed_den > 0 {
-            meta.shutter_speed_num as f32 / meta.shutter_speed_den as f32
-        } else { 0.0 };
-        let aperture = if meta.aperture_den > 0 {
-            meta.aperture_num as f32 / meta.aperture_den as f32
-        } else { 0.0 };
-
+        let make = c_array_to_string(&meta.make);
+        let model = c_array_to_string(&meta.model);
         RawMetadata {
             format: meta.format.into(),
             width: meta.width,
             height: meta.height,
-            orientation: meta.orientation as u8,
+            orientation: Orientation::from_exif(meta.orientation as u32),
             make,
             model,
+            // Carried by the extended metadata
+            lens: String::new(),
             iso: meta.iso,
-            shutter_speed,
-            aperture,
+            shutter_speed: ExposureTime::new(meta.shutter_speed_num, meta.shutter_speed_den),
+            aperture: FNumber::new(meta.aperture_num, meta.aperture_den),
             focal_length: meta.focal_length,
             color_matrix: meta.color_matrix,
             white_balance: meta.white_balance,
             black_level: meta.black_level,
             white_level: meta.white_level,
+            baseline_exposure: meta.baseline_exposure,
+            frame_rate: meta.frame_rate,
+            timecode: (meta.timecode_flags & ZRRAW_TIMECODE_PRESENT != 0).then(|| Timecode {
+                hours: meta.timecode[0],
+                minutes: meta.timecode[1],
+                seconds: meta.timecode[2],
+                frames: meta.timecode[3],
+                drop_frame: meta.timecode_flags & ZRRAW_TIMECODE_DROP_FRAME != 0,
+            }),
+            extended: extended::ExtendedMetadata::default(),
+            exif: exif::Exif::default(),
+            makernote: None,
+            xmp: None,
+        }
+    }
+}
+
+/// Colour filter array layout; colours are 0 = red, 1 = green, 2 = blue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfaPattern {
+    pub width: u32,
+    pub height: u32,
+    /// One colour per site, row-major
+    pub colors: Vec<u8>,
+}
+
+/// Undemosaiced sensor samples covering the active area
+#[derive(Debug, Clone)]
+pub struct SensorData {
+    pub width: u32,
+    pub height: u32,
+    /// 1 for CFA data, 3 for linear (demosaiced) raw data
+    pub samples_per_pixel: u32,
+    pub bits_per_sample: u32,
+    pub cfa: CfaPattern,
+    /// Row-major samples, interleaved per pixel
+    pub data: Vec<u16>,
+}
+
+/// Semantic mask of a DNG, e.g. sky or skin coverage of an Apple ProRAW
+#[derive(Debug, Clone)]
+pub struct SemanticMask {
+    pub name: String,
+    pub instance_id: String,
+    /// Coverage from 0 to 255
+    pub image: GrayImage,
+}
+
+/// Encoding of an embedded preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewFormat {
+    Jpeg,
+    /// Uncompressed 8-bit RGB, row-major
+    Rgb8,
+}
+
+/// An embedded preview or thumbnail of a raw file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: PreviewFormat,
+    /// Position of the encoded image in the file
+    pub offset: u64,
+    pub length: u64,
+    /// Orientation of the file, which previews are stored without
+    pub orientation: Orientation,
+}
+
+impl PreviewInfo {
+    /// The preview's bytes within the file
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset as usize..(self.offset + self.length) as usize
+    }
+}
+
+/// Which preview `ZrRaw::extract_preview` picks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewSelector {
+    Largest,
+    Smallest,
+    /// The largest whose longer side is at most this many pixels, or the
+    /// smallest if none is
+    LargestUnder(u32),
+    /// By position in the list `ZrRaw::previews` returns
+    Index(usize),
+}
+
+impl PreviewSelector {
+    pub fn select<'a>(&self, previews: &'a [PreviewInfo]) -> Option<&'a PreviewInfo> {
+        let pixels = |info: &&PreviewInfo| info.width as u64 * info.height as u64;
+        match *self {
+            PreviewSelector::Largest => previews.iter().max_by_key(pixels),
+            PreviewSelector::Smallest => previews.iter().min_by_key(pixels),
+            PreviewSelector::LargestUnder(limit) => previews
+                .iter()
+                .filter(|info| info.width.max(info.height) <= limit)
+                .max_by_key(pixels)
+                .or_else(|| previews.iter().min_by_key(pixels)),
+            PreviewSelector::Index(index) => previews.get(index),
+        }
+    }
+}
+
+/// An embedded preview, borrowing its bytes from the raw file
+#[derive(Debug, Clone)]
+pub struct Preview<'a> {
+    pub info: PreviewInfo,
+    /// The JPEG stream or RGB pixels as stored
+    pub bytes: &'a [u8],
+}
+
+impl Preview<'_> {
+    pub fn decode(&self) -> Result<DynamicImage, ZrRawError> {
+        match self.info.format {
+            PreviewFormat::Jpeg => image::load_from_memory_with_format(self.bytes, image::ImageFormat::Jpeg)
+                .map_err(|_| ZrRawError::CorruptedData),
+            PreviewFormat::Rgb8 => RgbImage::from_raw(self.info.width, self.info.height, self.bytes.to_vec())
+                .map(DynamicImage::ImageRgb8)
+                .ok_or(ZrRawError::CorruptedData),
         }
     }
 }
@@ -141,6 +540,8 @@ pub struct ProcessingParams {
     pub exposure_compensation: f32,
     pub output_gamma: f32,
     pub output_16bit: bool,
+    /// Rotate or flip the output upright according to the file's orientation
+    pub apply_orientation: bool,
 }
 
 #[derive(Default)]
@@ -149,6 +550,8 @@ pub enum DemosaicAlgorithm {
     #[default]
     Quality,
     Best,
+    /// Directional X-Trans interpolation; Bayer sensors get `Best`
+    Markesteijn,
 }
 
 impl From<ProcessingParams> for ZrRawProcessParams {
@@ -158,6 +561,7 @@ impl From<ProcessingParams> for ZrRawProcessParams {
                 DemosaicAlgorithm::Fast => 0,
                 DemosaicAlgorithm::Quality => 1,
                 DemosaicAlgorithm::Best => 2,
+                DemosaicAlgorithm::Markesteijn => 3,
             },
             wb_temperature: val.wb_temperature,
             wb_tint: val.wb_tint,
@@ -183,6 +587,21 @@ type ProcessFileFunc = unsafe extern "C" fn(
     *const u8, usize, *const ZrRawProcessParams, *mut ZrRawImage, *mut ZrRawMetadata
 ) -> i32;
 type FreeImageFunc = unsafe extern "C" fn(*mut ZrRawImage);
+type ExtractSensorDataFunc = unsafe extern "C" fn(*const u8, usize, *mut ZrRawSensorData) -> i32;
+type FreeSensorDataFunc = unsafe extern "C" fn(*mut ZrRawSensorData);
+type SemanticMaskCountFunc = unsafe extern "C" fn(*const u8, usize, *mut u32) -> i32;
+type ExtractSemanticMaskFunc = unsafe extern "C" fn(*const u8, usize, u32, *mut ZrRawMask) -> i32;
+type FreeSemanticMaskFunc = unsafe extern "C" fn(*mut ZrRawMask);
+type ExtractExtendedMetadataFunc = unsafe extern "C" fn(*const u8, usize, *mut ZrRawExtendedMetadata) -> i32;
+type FreeExtendedMetadataFunc = unsafe extern "C" fn(*mut ZrRawExtendedMetadata);
+type PreviewCountFunc = unsafe extern "C" fn(*const u8, usize, *mut u32) -> i32;
+type PreviewInfoFunc = unsafe extern "C" fn(*const u8, usize, u32, *mut ZrRawPreview) -> i32;
+type LjpegDecodeFunc = unsafe extern "C" fn(*const u8, usize, *mut ZrRawLjpegImage) -> i32;
+type FreeLjpegImageFunc = unsafe extern "C" fn(*mut ZrRawLjpegImage);
+type UnsupportedCompressionFunc = unsafe extern "C" fn() -> u32;
+type LoadCameraDatabaseFunc = unsafe extern "C" fn(*const u8, usize) -> i32;
+type ColorMatricesFunc =
+    unsafe extern "C" fn(*const u8, usize, *const f32, f32, f32, *mut ZrRawColorMatrices) -> i32;
 type VersionFunc = unsafe extern "C" fn() -> *const i8;
 
 
@@ -194,6 +613,20 @@ pub struct ZrRaw {
     zrraw_extract_metadata: ExtractMetadataFunc,
     zrraw_process_file: ProcessFileFunc,
     zrraw_free_image: FreeImageFunc,
+    zrraw_extract_sensor_data: ExtractSensorDataFunc,
+    zrraw_free_sensor_data: FreeSensorDataFunc,
+    zrraw_semantic_mask_count: SemanticMaskCountFunc,
+    zrraw_extract_semantic_mask: ExtractSemanticMaskFunc,
+    zrraw_free_semantic_mask: FreeSemanticMaskFunc,
+    zrraw_extract_extended_metadata: ExtractExtendedMetadataFunc,
+    zrraw_free_extended_metadata: FreeExtendedMetadataFunc,
+    zrraw_preview_count: PreviewCountFunc,
+    zrraw_preview_info: PreviewInfoFunc,
+    zrraw_ljpeg_decode: LjpegDecodeFunc,
+    zrraw_free_ljpeg_image: FreeLjpegImageFunc,
+    zrraw_unsupported_compression: UnsupportedCompressionFunc,
+    zrraw_load_camera_database: LoadCameraDatabaseFunc,
+    zrraw_color_matrices: ColorMatricesFunc,
     zrraw_version: VersionFunc,
 }
 impl ZrRaw {
@@ -287,6 +720,25 @@ impl ZrRaw {
             let zrraw_extract_metadata = *lib.get::<ExtractMetadataFunc>(b"zrraw_extract_metadata")?;
             let zrraw_process_file = *lib.get::<ProcessFileFunc>(b"zrraw_process_image")?;
             let zrraw_free_image = *lib.get::<FreeImageFunc>(b"zrraw_free_image")?;
+            let zrraw_extract_sensor_data = *lib.get::<ExtractSensorDataFunc>(b"zrraw_extract_sensor_data")?;
+            let zrraw_free_sensor_data = *lib.get::<FreeSensorDataFunc>(b"zrraw_free_sensor_data")?;
+            let zrraw_semantic_mask_count = *lib.get::<SemanticMaskCountFunc>(b"zrraw_semantic_mask_count")?;
+            let zrraw_extract_semantic_mask =
+                *lib.get::<ExtractSemanticMaskFunc>(b"zrraw_extract_semantic_mask")?;
+            let zrraw_free_semantic_mask = *lib.get::<FreeSemanticMaskFunc>(b"zrraw_free_semantic_mask")?;
+            let zrraw_extract_extended_metadata =
+                *lib.get::<ExtractExtendedMetadataFunc>(b"zrraw_extract_extended_metadata")?;
+            let zrraw_free_extended_metadata =
+                *lib.get::<FreeExtendedMetadataFunc>(b"zrraw_free_extended_metadata")?;
+            let zrraw_preview_count = *lib.get::<PreviewCountFunc>(b"zrraw_preview_count")?;
+            let zrraw_preview_info = *lib.get::<PreviewInfoFunc>(b"zrraw_preview_info")?;
+            let zrraw_ljpeg_decode = *lib.get::<LjpegDecodeFunc>(b"zrraw_ljpeg_decode")?;
+            let zrraw_free_ljpeg_image = *lib.get::<FreeLjpegImageFunc>(b"zrraw_free_ljpeg_image")?;
+            let zrraw_unsupported_compression =
+                *lib.get::<UnsupportedCompressionFunc>(b"zrraw_unsupported_compression")?;
+            let zrraw_load_camera_database =
+                *lib.get::<LoadCameraDatabaseFunc>(b"zrraw_load_camera_database")?;
+            let zrraw_color_matrices = *lib.get::<ColorMatricesFunc>(b"zrraw_color_matrices")?;
             let zrraw_version = *lib.get::<VersionFunc>(b"zrraw_version")?;
 
             // The dangerous `transmute` is no longer needed!
@@ -296,15 +748,38 @@ impl ZrRaw {
                 zrraw_extract_metadata,
                 zrraw_process_file,
                 zrraw_free_image,
+                zrraw_extract_sensor_data,
+                zrraw_free_sensor_data,
+                zrraw_semantic_mask_count,
+                zrraw_extract_semantic_mask,
+                zrraw_free_semantic_mask,
+                zrraw_extract_extended_metadata,
+                zrraw_free_extended_metadata,
+                zrraw_preview_count,
+                zrraw_preview_info,
+                zrraw_ljpeg_decode,
+                zrraw_free_ljpeg_image,
+                zrraw_unsupported_compression,
+                zrraw_load_camera_database,
+                zrraw_color_matrices,
                 zrraw_version,
             })
         }
     }
+    /// Maps a failed call's status code, fetching the compression value
+    /// behind an unsupported compression
+    fn error(&self, code: i32) -> ZrRawError {
+        match code {
+            -7 => ZrRawError::UnsupportedCompression(unsafe { (self.zrraw_unsupported_compression)() }),
+            other => ZrRawError::from(other),
+        }
+    }
+
     /// Detect the format of a RAW file
     pub fn detect_format(&self, data: &[u8]) -> Result<RawFormat, ZrRawError> {
         let mut format = 0;
         let result = unsafe { (self.zrraw_detect_format)(data.as_ptr(), data.len(), &mut format) };
-        if result != 0 { return Err(ZrRawError::from(result)); }
+        if result != 0 { return Err(self.error(result)); }
         Ok(format.into())
     }
 
@@ -312,8 +787,54 @@ impl ZrRaw {
     pub fn extract_metadata(&self, data: &[u8]) -> Result<RawMetadata, ZrRawError> {
         let mut metadata = unsafe { std::mem::zeroed::<ZrRawMetadata>() };
         let result = unsafe { (self.zrraw_extract_metadata)(data.as_ptr(), data.len(), &mut metadata) };
-        if result != 0 { return Err(ZrRawError::from(result)); }
-        Ok(metadata.into())
+        if result != 0 { return Err(self.error(result)); }
+        let mut metadata: RawMetadata = metadata.into();
+        metadata.read_exif(data);
+        metadata.set_extended(self.extract_extended_metadata(data)?);
+        Ok(metadata)
+    }
+
+    /// Loads a TOML camera database for bodies the built-in one lacks. Its
+    /// entries take precedence, and stay in force for every `ZrRaw` in the
+    /// process until another database replaces them; an empty file removes it.
+    pub fn load_camera_database(&self, path: impl AsRef<std::path::Path>) -> Result<(), ZrRawError> {
+        let text = std::fs::read(path).map_err(|_| ZrRawError::IoError)?;
+        let result = unsafe { (self.zrraw_load_camera_database)(text.as_ptr(), text.len()) };
+        if result != 0 { return Err(self.error(result)); }
+        Ok(())
+    }
+
+    /// The camera to XYZ and sRGB matrices for rendering `white` as neutral,
+    /// interpolated between the file's calibration illuminants. Fails with
+    /// `InvalidInput` if the file has no colour calibration.
+    pub fn color_matrices(&self, data: &[u8], white: color::WhitePoint) -> Result<color::ColorMatrices, ZrRawError> {
+        let (neutral, kelvin, tint) = match white {
+            color::WhitePoint::AsShot => (None, 0.0, 0.0),
+            color::WhitePoint::Neutral(neutral) => (Some(neutral), 0.0, 0.0),
+            color::WhitePoint::Temperature { kelvin, tint } => (None, kelvin, tint),
+        };
+        let neutral_ptr = neutral.as_ref().map_or(std::ptr::null(), |n| n.as_ptr());
+        let mut matrices = unsafe { std::mem::zeroed::<ZrRawColorMatrices>() };
+        let result = unsafe {
+            (self.zrraw_color_matrices)(data.as_ptr(), data.len(), neutral_ptr, kelvin, tint, &mut matrices)
+        };
+        if result != 0 { return Err(self.error(result)); }
+        Ok(matrices.into())
+    }
+
+    /// Extract the metadata that does not fit the fixed native struct
+    pub fn extract_extended_metadata(&self, data: &[u8]) -> Result<extended::ExtendedMetadata, ZrRawError> {
+        let mut blob = unsafe { std::mem::zeroed::<ZrRawExtendedMetadata>() };
+        let result = unsafe { (self.zrraw_extract_extended_metadata)(data.as_ptr(), data.len(), &mut blob) };
+        if result != 0 { return Err(self.error(result)); }
+
+        let decoded = if blob.data.is_null() {
+            Err(ZrRawError::CorruptedData)
+        } else {
+            extended::ExtendedMetadata::decode(unsafe { std::slice::from_raw_parts(blob.data, blob.data_len) })
+        };
+        unsafe { (self.zrraw_free_extended_metadata)(&mut blob) };
+        decoded
     }
 
     pub fn process_file(
@@ -323,6 +844,7 @@ impl ZrRaw {
     ) -> Result<ProcessedRawFile, ZrRawError> {
         let mut raw_image = unsafe { std::mem::zeroed::<ZrRawImage>() };
         let mut raw_metadata = unsafe { std::mem::zeroed::<ZrRawMetadata>() };
+        let apply_orientation = params.apply_orientation;
         let ffi_params: ZrRawProcessParams = params.into();
 
         let result = unsafe {
@@ -331,16 +853,138 @@ impl ZrRaw {
             )
         };
 
-        if result != 0 { return Err(ZrRawError::from(result)); }
+        if result != 0 { return Err(self.error(result)); }
 
-        let dynamic_image = Self::convert_to_dynamic_image(&raw_image)?;
-        let metadata: RawMetadata = raw_metadata.into();
+        let mut dynamic_image = Self::convert_to_dynamic_image(&raw_image)?;
+        let mut metadata: RawMetadata = raw_metadata.into();
+        metadata.read_exif(data);
+        if apply_orientation {
+            dynamic_image = metadata.orientation.apply(dynamic_image);
+        }
 
         unsafe { (self.zrraw_free_image)(&mut raw_image) };
+        metadata.set_extended(self.extract_extended_metadata(data)?);
 
         Ok(ProcessedRawFile { image: dynamic_image, metadata })
     }
 
+    /// Decode the sensor data without demosaicing or colour processing
+    pub fn extract_sensor_data(&self, data: &[u8]) -> Result<SensorData, ZrRawError> {
+        let mut sensor = unsafe { std::mem::zeroed::<ZrRawSensorData>() };
+        let result = unsafe { (self.zrraw_extract_sensor_data)(data.as_ptr(), data.len(), &mut sensor) };
+        if result != 0 { return Err(self.error(result)); }
+
+        let samples = if sensor.data.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(sensor.data, sensor.data_len) }.to_vec()
+        };
+        let sites = (sensor.cfa_width * sensor.cfa_height).min(36) as usize;
+        let sensor_data = SensorData {
+            width: sensor.width,
+            height: sensor.height,
+            samples_per_pixel: sensor.samples_per_pixel,
+            bits_per_sample: sensor.bits_per_sample,
+            cfa: CfaPattern {
+                width: sensor.cfa_width,
+                height: sensor.cfa_height,
+                colors: sensor.cfa[..sites].to_vec(),
+            },
+            data: samples,
+        };
+
+        unsafe { (self.zrraw_free_sensor_data)(&mut sensor) };
+        Ok(sensor_data)
+    }
+
+    /// Extract the semantic masks of a DNG; other formats have none
+    pub fn semantic_masks(&self, data: &[u8]) -> Result<Vec<SemanticMask>, ZrRawError> {
+        let mut count = 0;
+        let result = unsafe { (self.zrraw_semantic_mask_count)(data.as_ptr(), data.len(), &mut count) };
+        if result != 0 { return Err(self.error(result)); }
+
+        let mut masks = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            let mut mask = unsafe { std::mem::zeroed::<ZrRawMask>() };
+            let result =
+                unsafe { (self.zrraw_extract_semantic_mask)(data.as_ptr(), data.len(), index, &mut mask) };
+            if result != 0 { return Err(self.error(result)); }
+
+            let pixels = if mask.data.is_null() {
+                Vec::new()
+            } else {
+                unsafe { std::slice::from_raw_parts(mask.data, mask.data_len) }.to_vec()
+            };
+            let name = c_array_to_string(&mask.name);
+            let instance_id = c_array_to_string(&mask.instance_id);
+            let image = GrayImage::from_raw(mask.width, mask.height, pixels);
+            unsafe { (self.zrraw_free_semantic_mask)(&mut mask) };
+
+            let image = image.ok_or(ZrRawError::CorruptedData)?;
+            masks.push(SemanticMask { name, instance_id, image });
+        }
+        Ok(masks)
+    }
+
+    /// List the embedded previews and thumbnails, in file order
+    pub fn previews(&self, data: &[u8]) -> Result<Vec<PreviewInfo>, ZrRawError> {
+        let mut count = 0;
+        let result = unsafe { (self.zrraw_preview_count)(data.as_ptr(), data.len(), &mut count) };
+        if result != 0 { return Err(self.error(result)); }
+
+        let mut previews = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            let mut preview = unsafe { std::mem::zeroed::<ZrRawPreview>() };
+            let result = unsafe { (self.zrraw_preview_info)(data.as_ptr(), data.len(), index, &mut preview) };
+            if result != 0 { return Err(self.error(result)); }
+            previews.push(PreviewInfo {
+                width: preview.width,
+                height: preview.height,
+                format: if preview.format == 1 { PreviewFormat::Rgb8 } else { PreviewFormat::Jpeg },
+                offset: preview.offset,
+                length: preview.length,
+                orientation: Orientation::from_exif(preview.orientation as u32),
+            });
+        }
+        Ok(previews)
+    }
+
+    /// Pick an embedded preview without decoding the raw data. None if the
+    /// file has no previews.
+    pub fn extract_preview<'a>(
+        &self,
+        data: &'a [u8],
+        selector: PreviewSelector,
+    ) -> Result<Option<Preview<'a>>, ZrRawError> {
+        let previews = self.previews(data)?;
+        let Some(info) = selector.select(&previews) else { return Ok(None) };
+        let bytes = data.get(info.range()).ok_or(ZrRawError::CorruptedData)?;
+        Ok(Some(Preview { info: info.clone(), bytes }))
+    }
+
+    /// Decode the first scan of a lossless JPEG stream with the native
+    /// decoder. Any predictor, point transform and restart interval is
+    /// accepted; see [`ljpeg`] for the Canon and DNG layouts.
+    pub fn decode_ljpeg(&self, data: &[u8]) -> Result<ljpeg::Image, ZrRawError> {
+        let mut image = unsafe { std::mem::zeroed::<ZrRawLjpegImage>() };
+        let result = unsafe { (self.zrraw_ljpeg_decode)(data.as_ptr(), data.len(), &mut image) };
+        if result != 0 { return Err(self.error(result)); }
+
+        let samples = if image.data.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(image.data, image.data_len) }.to_vec()
+        };
+        let frame = ljpeg::Frame {
+            width: image.width,
+            height: image.height,
+            components: image.components,
+            precision: image.precision,
+        };
+        unsafe { (self.zrraw_free_ljpeg_image)(&mut image) };
+        Ok(ljpeg::Image { frame, samples })
+    }
+
     pub fn version(&self) -> String {
         unsafe { CStr::from_ptr((self.zrraw_version)()).to_string_lossy().into_owned() }
     }
@@ -369,15 +1013,571 @@ impl ZrRaw {
                 .map(DynamicImage::ImageRgba8)
                 .ok_or(ZrRawError::CorruptedData)
             }
+            (3, 16) => {
+                let samples: Vec<u16> = data_slice
+                    .chunks_exact(2)
+                    .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+                    .collect();
+                ImageBuffer::<Rgb<u16>, _>::from_raw(raw_image.width, raw_image.height, samples)
+                    .map(DynamicImage::ImageRgb16)
+                    .ok_or(ZrRawError::CorruptedData)
+            }
             _ => Err(ZrRawError::UnsupportedFormat),
         }
     }
 
 }
 
+#[cfg(test)]
+mod test_support;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{
+        camf_matrix, camf_properties, camf_section, ciff_file, fuji_compressed_zeros, ifd_block, iiq_calibration,
+        iiq_file, iiq_file_with_ifd0, jpeg_with_exif, leaf_packets, ljpeg_header, mlv_file, mrw_file, pack_msb,
+        raf_file, sony_crypt, tiff_relative_makernote, with_makernote, x3f_file, x3f_properties, Entry, IiqValue,
+        TiffBuilder, Value,
+    };
+
+    /// A 4x4 uncompressed 12-bit NEF with a type-3 Nikon makernote
+    fn synthetic_nef() -> Vec<u8> {
+        let samples: Vec<u16> = (0..16).map(|i| 256 + i * 100).collect();
+        let makernote_tiff = TiffBuilder::new(true).build(vec![
+            Entry(0x000C, Value::Rational(vec![(2, 1), (3, 2), (1, 1), (1, 1)])),
+            Entry(0x003D, Value::Short(vec![600; 4])),
+        ]);
+        let mut makernote = b"Nikon\0\x02\x10\0\0".to_vec();
+        makernote.extend(makernote_tiff);
+
+        let raw_ifd = vec![
+            Entry(0x00FE, Value::Long(vec![0])),
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![4])),
+            Entry(0x0102, Value::Short(vec![12])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x0111, Value::Offset(pack_msb(&samples, 12))),
+            Entry(0x0115, Value::Short(vec![1])),
+            Entry(0x0116, Value::Long(vec![4])),
+            Entry(0x0117, Value::Long(vec![24])),
+            Entry(0x828D, Value::Short(vec![2, 2])),
+            Entry(0x828E, Value::Byte(vec![0, 1, 1, 2])),
+        ];
+        TiffBuilder::new(true).build(vec![
+            Entry(0x00FE, Value::Long(vec![1])),
+            Entry(0x010F, Value::Ascii("NIKON CORPORATION")),
+            Entry(0x0110, Value::Ascii("NIKON D810")),
+            Entry(0x014A, Value::Ifds(vec![raw_ifd])),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x829A, Value::Rational(vec![(1, 250)])),
+                Entry(0x829D, Value::Rational(vec![(28, 10)])),
+                Entry(0x8827, Value::Short(vec![400])),
+                Entry(0x927C, Value::Undefined(makernote)),
+            ])),
+        ])
+    }
+
+    /// A 32x2 cRAW ARW with an encrypted SR2SubIFD holding WB and levels
+    fn synthetic_arw() -> Vec<u8> {
+        const KEY: u32 = 0x1234_5678;
+        let sr2 = |base| {
+            ifd_block(false, base, vec![
+                Entry(0x7310, Value::Short(vec![128; 4])),
+                Entry(0x7313, Value::Short(vec![2048, 1024, 1024, 1536])),
+                Entry(0x787F, Value::Short(vec![16300; 3])),
+            ])
+        };
+        let len = sr2(0).len();
+
+        // Every block codes max = min = 256 (imax 0, imin 1) with zero deltas
+        let header: u32 = 256 | 256 << 11 | 1 << 26;
+        let mut block = [0u8; 16];
+        block[..4].copy_from_slice(&header.to_le_bytes());
+
+        let raw_ifd = vec![
+            Entry(0x00FE, Value::Long(vec![0])),
+            Entry(0x0100, Value::Long(vec![32])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![8])),
+            Entry(0x0103, Value::Short(vec![32767])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x0111, Value::Offset(block.repeat(4))),
+            Entry(0x0117, Value::Long(vec![64])),
+            Entry(0x7010, Value::Short(vec![8000, 10400, 12900, 14100])),
+            Entry(0x828D, Value::Short(vec![2, 2])),
+            Entry(0x828E, Value::Byte(vec![0, 1, 1, 2])),
+        ];
+        let mut file = TiffBuilder::new(false).build(vec![
+            Entry(0x010F, Value::Ascii("SONY")),
+            Entry(0x0110, Value::Ascii("ILCE-7M3")),
+            Entry(0x014A, Value::Ifds(vec![raw_ifd])),
+            Entry(0xC634, Value::Ifd(vec![
+                Entry(0x7200, Value::Offset(vec![0xA5; len])),
+                Entry(0x7201, Value::Long(vec![len as u32])),
+                Entry(0x7221, Value::Long(vec![KEY])),
+            ])),
+        ]);
+
+        // Replace the placeholder with the SR2SubIFD laid out at its final offset
+        let base = file.windows(len).position(|w| w.iter().all(|&b| b == 0xA5)).unwrap();
+        let mut encrypted = sr2(base as u32);
+        sony_crypt(&mut encrypted, KEY);
+        file[base..base + len].copy_from_slice(&encrypted);
+        file
+    }
+
+    /// A 10x6 tiled CFA DNG with an 8x4 active area, a black level pattern
+    /// with column deltas, a linearization table and a 6x2 default crop
+    fn synthetic_dng() -> Vec<u8> {
+        // 8x4 tiles of stored value 2, which the table maps to 2000
+        let tile: Vec<u8> = [2u16; 32].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let raw_ifd = vec![
+            Entry(0x00FE, Value::Long(vec![0])),
+            Entry(0x0100, Value::Long(vec![10])),
+            Entry(0x0101, Value::Long(vec![6])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x0142, Value::Long(vec![8])),
+            Entry(0x0143, Value::Long(vec![4])),
+            Entry(0x0144, Value::Offsets(vec![tile.clone(); 4])),
+            Entry(0x0145, Value::Long(vec![64; 4])),
+            Entry(0x828D, Value::Short(vec![2, 2])),
+            Entry(0x828E, Value::Byte(vec![0, 1, 1, 2])),
+            Entry(0xC618, Value::Short(vec![0, 1000, 2000, 3000])),
+            Entry(0xC619, Value::Short(vec![2, 2])),
+            Entry(0xC61A, Value::Short(vec![256; 4])),
+            Entry(0xC61B, Value::Rational(vec![(0, 1), (10, 1), (0, 1), (10, 1), (0, 1), (10, 1), (0, 1), (10, 1)])),
+            Entry(0xC61D, Value::Short(vec![4000])),
+            Entry(0xC61F, Value::Long(vec![1, 1])),
+            Entry(0xC620, Value::Long(vec![6, 2])),
+            Entry(0xC68D, Value::Long(vec![1, 2, 5, 10])),
+        ];
+        let identity = vec![(1, 1), (0, 1), (0, 1), (0, 1), (1, 1), (0, 1), (0, 1), (0, 1), (1, 1)];
+        TiffBuilder::new(false).build(vec![
+            Entry(0x00FE, Value::Long(vec![1])),
+            Entry(0x014A, Value::Ifds(vec![raw_ifd])),
+            Entry(0xC612, Value::Byte(vec![1, 4, 0, 0])),
+            Entry(0xC614, Value::Ascii("Synthetic DNG")),
+            Entry(0xC621, Value::Rational(identity.clone())),
+            Entry(0xC622, Value::Rational(identity)),
+            Entry(0xC628, Value::Rational(vec![(1, 2), (1, 1), (2, 3)])),
+            Entry(0xC65A, Value::Short(vec![17])),
+            Entry(0xC65B, Value::Short(vec![21])),
+        ])
+    }
+
+    /// A 4x2 CFA DNG whose 16-byte strip uses `compression`, with `extra`
+    /// tags in its IFD
+    fn synthetic_compressed_dng(compression: u16, extra: Vec<Entry>) -> Vec<u8> {
+        let mut ifd0 = vec![
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![compression])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x0117, Value::Long(vec![16])),
+            Entry(0x0111, Value::Offset(vec![0; 16])),
+            Entry(0x828D, Value::Short(vec![2, 2])),
+            Entry(0x828E, Value::Byte(vec![0, 1, 1, 2])),
+            Entry(0xC612, Value::Byte(vec![1, 7, 0, 0])),
+        ];
+        ifd0.extend(extra);
+        TiffBuilder::new(false).build(ifd0)
+    }
+
+    /// A 4x2 three-sample linear DNG stored as Deflate-compressed 32-bit
+    /// floats with the floating point predictor
+    fn synthetic_linear_dng() -> Vec<u8> {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in 0..2 {
+            let samples: Vec<[u8; 4]> = (0..12).map(|i| (0.25 + 0.05 * (row * 12 + i) as f32).to_be_bytes()).collect();
+            // Byte planes, most significant first, then byte differences with a stride of 3
+            let mut bytes: Vec<u8> = (0..4).flat_map(|b| samples.iter().map(move |s| s[b])).collect();
+            for i in (3..bytes.len()).rev() {
+                bytes[i] = bytes[i].wrapping_sub(bytes[i - 3]);
+            }
+            encoder.write_all(&bytes).unwrap();
+        }
+        let strip = encoder.finish().unwrap();
+
+        TiffBuilder::new(true).build(vec![
+            Entry(0x00FE, Value::Long(vec![0])),
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![32; 3])),
+            Entry(0x0103, Value::Short(vec![8])),
+            Entry(0x0106, Value::Short(vec![34892])),
+            Entry(0x0110, Value::Ascii("Linear Camera")),
+            Entry(0x0111, Value::Offset(strip.clone())),
+            Entry(0x0115, Value::Short(vec![3])),
+            Entry(0x0116, Value::Long(vec![2])),
+            Entry(0x0117, Value::Long(vec![strip.len() as u32])),
+            Entry(0x013D, Value::Short(vec![34894])),
+            Entry(0x0153, Value::Short(vec![3; 3])),
+            Entry(0xC612, Value::Byte(vec![1, 4, 0, 0])),
+            Entry(0xC61D, Value::Long(vec![1])),
+        ])
+    }
+
+    /// X-Trans layout of the synthetic RAFs, row-major
+    const XTRANS: [u8; 36] = [
+        1, 1, 0, 1, 1, 2,
+        1, 1, 2, 1, 1, 0,
+        2, 0, 1, 0, 2, 1,
+        1, 1, 2, 1, 1, 0,
+        1, 1, 0, 1, 1, 2,
+        0, 2, 1, 2, 0, 1,
+    ];
+
+    /// A 14-bit X-Trans RAF holding `data` in its TIFF-like CFA section, with
+    /// a crop two pixels in from each edge and as-shot WB of (2, 1, 1.5)
+    fn synthetic_raf(width: u32, height: u32, data: Vec<u8>) -> Vec<u8> {
+        let exif = TiffBuilder::new(false).build(vec![
+            Entry(0x010F, Value::Ascii("FUJIFILM")),
+            Entry(0x0110, Value::Ascii("X-T5")),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x829A, Value::Rational(vec![(1, 125)])),
+                Entry(0x8827, Value::Short(vec![320])),
+            ])),
+        ]);
+        let section = TiffBuilder::new(false).build(vec![Entry(0xF000, Value::Ifd(vec![
+            Entry(0xF001, Value::Long(vec![width])),
+            Entry(0xF002, Value::Long(vec![height])),
+            Entry(0xF003, Value::Long(vec![14])),
+            Entry(0xF007, Value::Offset(data.clone())),
+            Entry(0xF008, Value::Long(vec![data.len() as u32])),
+            Entry(0xF00A, Value::Long(vec![1024; 36])),
+        ]))]);
+
+        let be = |values: &[u16]| values.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>();
+        let (w, h) = (width as u16, height as u16);
+        let records = vec![
+            (0x0100, be(&[h, w])),
+            (0x0110, be(&[2, 2])),
+            (0x0111, be(&[h - 4, w - 4])),
+            (0x0131, XTRANS.iter().rev().copied().collect()),
+            (0x2FF0, be(&[256, 512, 256, 384])),
+        ];
+        raf_file("X-T5", &jpeg_with_exif(&exif), &records, &section)
+    }
+
+    /// 12x12 uncompressed little-endian samples
+    fn synthetic_uncompressed_raf() -> Vec<u8> {
+        let data = (0..144u16).flat_map(|i| (1024 + i * 50).to_le_bytes()).collect();
+        synthetic_raf(12, 12, data)
+    }
+
+    /// A 12x12 ORF whose "OLYMPUS" makernote names the lens and sets WB
+    /// (2, 1, 1.5), per-colour black levels and a crop two pixels in from
+    /// each edge. `data` is the sensor strip in IFD0.
+    fn synthetic_orf(data: Vec<u8>) -> Vec<u8> {
+        let mut makernote = b"OLYMPUS\0II\x03\0".to_vec();
+        makernote.extend(ifd_block(false, 12, vec![
+            Entry(0x2010, Value::Ifd(vec![Entry(0x0203, Value::Ascii("M.Zuiko 12-40mm F2.8"))])),
+            Entry(0x2040, Value::Ifd(vec![
+                Entry(0x0100, Value::Short(vec![512, 384])),
+                Entry(0x0200, Value::Short(vec![256, 0, 0, 0, 256, 0, 0, 0, 256])),
+                Entry(0x0600, Value::Short(vec![250, 254, 256, 260])),
+                Entry(0x0611, Value::Short(vec![12, 12])),
+                Entry(0x0612, Value::Short(vec![2, 2])),
+                Entry(0x0613, Value::Short(vec![2, 2])),
+                Entry(0x0614, Value::Long(vec![8])),
+                Entry(0x0615, Value::Long(vec![8])),
+            ])),
+        ]));
+
+        TiffBuilder::with_magic(false, 0x4F52).build(vec![
+            Entry(0x0100, Value::Long(vec![12])),
+            Entry(0x0101, Value::Long(vec![12])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x010F, Value::Ascii("OM Digital Solutions")),
+            Entry(0x0110, Value::Ascii("OM-1")),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x829A, Value::Rational(vec![(1, 60)])),
+                Entry(0x8827, Value::Short(vec![800])),
+                Entry(0x927C, Value::Undefined(makernote)),
+            ])),
+        ])
+    }
+
+    /// An 8x4 Pentax-compressed PEF whose makernote table gives symbols 0
+    /// and 1 the one-bit codes 0 and 1
+    fn synthetic_pef(data: Vec<u8>) -> Vec<u8> {
+        let mut table = vec![0, 6];
+        table.extend([0u8; 12]);
+        table.extend([0x00, 0x00, 0x08, 0x00, 1, 1]);
+        let mut makernote = b"PENTAX \0MM".to_vec();
+        makernote.extend(ifd_block(true, 10, vec![
+            Entry(0x0200, Value::Short(vec![64, 64, 64, 64])),
+            Entry(0x0201, Value::Short(vec![16384, 8192, 8192, 12288])),
+            Entry(0x0220, Value::Undefined(table)),
+        ]));
+
+        TiffBuilder::new(true).build(vec![
+            Entry(0x0100, Value::Long(vec![8])),
+            Entry(0x0101, Value::Long(vec![4])),
+            Entry(0x0102, Value::Short(vec![12])),
+            Entry(0x0103, Value::Short(vec![65535])),
+            Entry(0x010F, Value::Ascii("RICOH IMAGING COMPANY, LTD.")),
+            Entry(0x0110, Value::Ascii("PENTAX K-3 Mark III")),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x8827, Value::Short(vec![400])),
+                Entry(0x927C, Value::Undefined(makernote)),
+            ])),
+        ])
+    }
+
+    /// An 8x8 TRUE-coded X3F. Symbol 0 (no difference) is coded 0 and symbol
+    /// 1 is coded 1; only the first pixel of the first plane steps up by one.
+    fn synthetic_x3f() -> Vec<u8> {
+        let mut image = b"SECi".to_vec();
+        for v in [0x0002_0000u32, 3, 30, 8, 8, 0] {
+            image.extend(v.to_le_bytes());
+        }
+        for v in [100u16, 200, 300, 0] {
+            image.extend(v.to_le_bytes());
+        }
+        image.extend([1, 0x00, 1, 0x80, 0, 0]);
+        for _ in 0..3 {
+            image.extend(8u32.to_le_bytes());
+        }
+        let mut first = vec![0b1100_0000];
+        first.extend([0u8; 15]);
+        image.extend(first);
+        image.extend([0u8; 32]);
+
+        let camf = camf_section(&[
+            camf_properties("WhiteBalanceGains", &[("Sunlight", "SunlightGains")]),
+            camf_matrix("SunlightGains", 3, &[3], &[2.0f32.to_bits(), 1.0f32.to_bits(), 1.5f32.to_bits()]),
+            camf_properties("WhiteBalanceColorCorrections", &[("Sunlight", "SunlightCC")]),
+            camf_matrix("SunlightCC", 3, &[3, 3], &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0].map(f32::to_bits)),
+            camf_matrix("SaturationLevel", 2, &[3], &[4000, 4000, 4000]),
+            camf_matrix("ActiveImageArea", 2, &[4], &[1, 1, 6, 6]),
+        ], 0x1234);
+
+        x3f_file(8, 8, "Sunlight", &[
+            (b"IMA2", image),
+            (b"PROP", x3f_properties(&[("CAMMANUF", "SIGMA"), ("CAMMODEL", "SIGMA DP2 Merrill"), ("ISO", "200")])),
+            (b"CAMF", camf),
+        ])
+    }
+
+    /// A 4x2 compressed IIQ. Its rows are too short for coded groups, so
+    /// every sample is stored whole (100). Black is 10 less a per-row
+    /// amount that differs left and right of column 2, and a per-column
+    /// amount (only column 3) that differs above and below row 1. A flat
+    /// field then halves every sample.
+    fn synthetic_iiq() -> Vec<u8> {
+        let le = |values: &[u32]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let shorts = |values: &[i16]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let floats = |values: &[f32]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let row = le(&[(100 << 16) | 100, (100 << 16) | 100]);
+
+        let mut flat_field = Vec::new();
+        for v in [0u16, 0, 6, 4, 2, 2, 0, 0] {
+            flat_field.extend(v.to_le_bytes());
+        }
+        for _ in 0..6 {
+            flat_field.extend(16384u16.to_le_bytes());
+        }
+
+        iiq_file(vec![
+            (0x0107, IiqValue::Data(floats(&[2.0, 1.0, 1.5]))),
+            (0x0108, IiqValue::Int(4)),
+            (0x0109, IiqValue::Int(2)),
+            (0x010E, IiqValue::Int(3)),
+            (0x010F, IiqValue::Data(row.repeat(2))),
+            (0x0110, IiqValue::Data(iiq_calibration(&[(0x0410, flat_field)]))),
+            (0x021C, IiqValue::Data(le(&[0, 8]))),
+            (0x021D, IiqValue::Int(10)),
+            (0x0222, IiqValue::Int(2)),
+            (0x0223, IiqValue::Data(shorts(&[1, 2, 3, 4]))),
+            (0x0224, IiqValue::Int(1)),
+            (0x0225, IiqValue::Data(shorts(&[0, 0, 0, 0, 0, 0, 5, 6]))),
+            (0x0301, IiqValue::Data(b"IQ4 150MP camera\0".to_vec())),
+        ])
+    }
+
+    /// A 4x1 Hasselblad-coded 3FR. Length 0 is coded 0 and length 2 is
+    /// coded 1; only the first pixel pair has a difference (+3, 0).
+    fn synthetic_3fr(software: &'static str) -> Vec<u8> {
+        let mut counts = [0u8; 16];
+        counts[0] = 2;
+        let mut data = ljpeg_header(4, 1, counts, &[0, 2]);
+        data.extend((0b1011_0000u32 << 24).to_le_bytes());
+
+        TiffBuilder::new(false).build(vec![
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![1])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![7])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x010F, Value::Ascii("Hasselblad")),
+            Entry(0x0110, Value::Ascii("X2D 100C")),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x0131, Value::Ascii(software)),
+            Entry(0xC628, Value::Rational(vec![(1, 2), (1, 1), (2, 3)])),
+        ])
+    }
+
+    /// A 4x2 uncompressed Leaf MOS with no Photometric tag, whose PKTS
+    /// metadata gives the neutrals and a GRBG mosaic
+    fn synthetic_mos() -> Vec<u8> {
+        let data: Vec<u8> = (1..=8u16).flat_map(|v| (v * 100).to_be_bytes()).collect();
+        TiffBuilder::new(true).build(vec![
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x010F, Value::Ascii("Leaf")),
+            Entry(0x0110, Value::Ascii("Aptus-II 12")),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x8606, Value::Undefined(leaf_packets(&[
+                ("NeutObj_neutrals", "1 4096 8192 16384"),
+                ("CaptProf_mosaic_pattern", "0 1 0 0"),
+            ]))),
+        ])
+    }
+
+    /// A 64x1 CRW with the low-bit plane. The first block codes a difference
+    /// of 5 for the first pixel and zeros after it.
+    fn synthetic_crw() -> Vec<u8> {
+        let mut raw_data = vec![0b11_10_01_00];
+        raw_data.resize(514 + 16, 0);
+        raw_data.extend([0x57, 0xF6]);
+        raw_data.extend([0u8; 32]);
+
+        let le = |values: &[u16]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+        let mut image_info: Vec<u8> = [64u32, 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+        image_info.extend(1.0f32.to_le_bytes());
+        image_info.resize(28, 0);
+        ciff_file(&[
+            (0x2005, raw_data),
+            (0x080A, b"Canon\0Canon PowerShot Pro1\0".to_vec()),
+            // ISO 100, f/4, 1/8 s, auto white balance
+            (0x102A, le(&[0, 0, 160, 0, 128, 96, 0, 0])),
+            (0x10A9, le(&[0, 1024, 512, 512, 768])),
+            (0x1810, image_info),
+            (0x1835, 0u32.to_le_bytes().to_vec()),
+        ])
+    }
+
+    /// A 4x2 packed 12-bit GBRG MRW with white balance and an EXIF block
+    fn synthetic_mrw() -> Vec<u8> {
+        let mut picture = b"21810002".to_vec();
+        picture.extend([2u16, 4, 2, 4].iter().flat_map(|v| v.to_be_bytes()));
+        picture.extend([12, 12, 0x52, 0, 0, 0, 0, 4]);
+        let mut white_balance = vec![0u8; 4];
+        white_balance.extend([256u16, 384, 512, 256].iter().flat_map(|v| v.to_be_bytes()));
+        let exif = TiffBuilder::new(true).build(vec![
+            Entry(0x010F, Value::Ascii("Konica Minolta")),
+            Entry(0x0110, Value::Ascii("DYNAX 7D")),
+            Entry(0x8769, Value::Ifd(vec![Entry(0x8827, Value::Short(vec![200]))])),
+        ]);
+        let samples: Vec<u16> = (1..=8).map(|v| v * 100).collect();
+        mrw_file(&[(b"\0PRD", picture), (b"\0WBG", white_balance), (b"\0TTW", exif)], &pack_msb(&samples, 12))
+    }
+
+    /// A 4x2 uncompressed DCR or KDC whose Kodak IFD gives ISO and white
+    /// balance (2, 1, 0.5)
+    fn synthetic_kodak(model: &'static str) -> Vec<u8> {
+        let data: Vec<u8> = (1..=8u16).flat_map(|v| (v * 100).to_be_bytes()).collect();
+        let mut white_balance = vec![0u8; 72];
+        for (i, v) in [1024u16, 2048, 4096].iter().enumerate() {
+            white_balance[40 + i * 2..42 + i * 2].copy_from_slice(&v.to_be_bytes());
+        }
+        TiffBuilder::new(true).build(vec![
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x010F, Value::Ascii("Kodak")),
+            Entry(0x0110, Value::Ascii(model)),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x828D, Value::Short(vec![2, 2])),
+            Entry(0x828E, Value::Byte(vec![1, 0, 2, 1])),
+            Entry(0x8290, Value::Ifd(vec![
+                Entry(0x03FD, Value::Undefined(white_balance)),
+                Entry(0x1784, Value::Long(vec![320])),
+            ])),
+        ])
+    }
+
+    /// A 4x2 uncompressed SRW whose makernote gives black levels of 128 and
+    /// white balance levels that include them
+    fn synthetic_srw() -> Vec<u8> {
+        let data: Vec<u8> = (1..=8u16).flat_map(|v| (v * 100).to_le_bytes()).collect();
+        let makernote = ifd_block(false, 0, vec![
+            Entry(0xA021, Value::Long(vec![1152, 640, 640, 896])),
+            Entry(0xA028, Value::Long(vec![128; 4])),
+        ]);
+        TiffBuilder::new(false).build(vec![
+            Entry(0x0100, Value::Long(vec![4])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![16])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x010F, Value::Ascii("SAMSUNG")),
+            Entry(0x0110, Value::Ascii("NX1")),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x8827, Value::Short(vec![400])),
+                Entry(0x927C, Value::Undefined(makernote)),
+            ])),
+        ])
+    }
+
+    /// A 10x2 ERF with Epson's control-byte packing and white balance in
+    /// the makernote's image settings
+    fn synthetic_erf() -> Vec<u8> {
+        let makernote = |base: u32| {
+            let mut settings = vec![0u8; 256];
+            settings[48..50].copy_from_slice(&240u16.to_le_bytes());
+            settings[50..52].copy_from_slice(&300u16.to_le_bytes());
+            let mut note = b"EPSON\0\x01\0".to_vec();
+            note.extend(ifd_block(false, base + 8, vec![Entry(0x0E80, Value::Undefined(settings))]));
+            note
+        };
+        let len = makernote(0).len();
+
+        let samples: Vec<u16> = (0..10).map(|v| 100 * v + 50).collect();
+        let mut row = pack_msb(&samples, 12);
+        row.push(0);
+        let data = row.repeat(2);
+        let mut file = TiffBuilder::new(false).build(vec![
+            Entry(0x0100, Value::Long(vec![10])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![12])),
+            Entry(0x0103, Value::Short(vec![32769])),
+            Entry(0x0106, Value::Short(vec![32803])),
+            Entry(0x010F, Value::Ascii("SEIKO EPSON CORP.")),
+            Entry(0x0110, Value::Ascii("R-D1")),
+            Entry(0x0117, Value::Long(vec![data.len() as u32])),
+            Entry(0x0111, Value::Offset(data)),
+            Entry(0x8769, Value::Ifd(vec![Entry(0x927C, Value::Undefined(vec![0xA5; len]))])),
+        ]);
+
+        // Replace the placeholder with the makernote laid out at its final offset
+        let base = file.windows(len).position(|w| w.iter().all(|&b| b == 0xA5)).unwrap();
+        file[base..base + len].copy_from_slice(&makernote(base as u32));
+        file
+    }
 
     #[test]
     fn test_version() {
@@ -401,6 +1601,14 @@ mod tests {
         assert!(matches!(format, RawFormat::Unknown));
     }
 
+    #[test]
+    fn test_format_codes() {
+        assert!(matches!(RawFormat::from(6), RawFormat::Olympus(OlympusFormat::ORF)));
+        assert!(matches!(RawFormat::from(7), RawFormat::Panasonic(PanasonicFormat::RW2)));
+        assert!(matches!(RawFormat::from(8), RawFormat::Pentax(PentaxFormat::PEF)));
+        assert!(matches!(RawFormat::from(0), RawFormat::Unknown));
+    }
+
     #[test]
     fn test_process_file_stub() {
         // 1. Create an instance
@@ -419,4 +1627,1835 @@ mod tests {
         // Check metadata from stub
         assert_eq!(result.metadata.make, "Unknown Make");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_nef_metadata() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let nef = synthetic_nef();
+
+        let format = zrraw_lib.detect_format(&nef).unwrap();
+        assert!(matches!(format, RawFormat::Nikon(NikonFormat::NEF)));
+
+        let metadata = zrraw_lib.extract_metadata(&nef).unwrap();
+        assert_eq!(metadata.make, "NIKON CORPORATION");
+        assert_eq!(metadata.model, "NIKON D810");
+        assert_eq!((metadata.width, metadata.height), (4, 4));
+        assert_eq!(metadata.iso, 400);
+        assert_eq!(metadata.shutter_speed.to_string(), "1/250");
+        assert_eq!(metadata.aperture.to_string(), "f/2.8");
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.black_level, [150.0; 4]);
+        assert_eq!(metadata.white_level, [4095; 4]);
+    }
+
+    #[test]
+    fn test_nef_process() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let params = ProcessingParams { output_16bit: true, ..Default::default() };
+        let result = zrraw_lib.process_file(&synthetic_nef(), params).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (4, 4));
+        assert!(matches!(result.image, DynamicImage::ImageRgb16(_)));
+    }
+
+    #[test]
+    fn test_arw_sr2_metadata() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let arw = synthetic_arw();
+
+        let format = zrraw_lib.detect_format(&arw).unwrap();
+        assert!(matches!(format, RawFormat::Sony(SonyFormat::ARW)));
+
+        let metadata = zrraw_lib.extract_metadata(&arw).unwrap();
+        assert_eq!(metadata.model, "ILCE-7M3");
+        assert_eq!((metadata.width, metadata.height), (32, 2));
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.black_level, [128.0; 4]);
+        assert_eq!(metadata.white_level, [16300; 4]);
+    }
+
+    #[test]
+    fn test_arw_craw_process() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let result = zrraw_lib.process_file(&synthetic_arw(), ProcessingParams::default()).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (32, 2));
+    }
+
+    #[test]
+    fn test_dng_metadata() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let dng = synthetic_dng();
+
+        let format = zrraw_lib.detect_format(&dng).unwrap();
+        assert!(matches!(format, RawFormat::Adobe(AdobeFormat::DNG)));
+
+        let metadata = zrraw_lib.extract_metadata(&dng).unwrap();
+        assert_eq!(metadata.model, "Synthetic DNG");
+        assert_eq!((metadata.width, metadata.height), (8, 4));
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.black_level, [256.0; 4]);
+        assert_eq!(metadata.white_level, [4000; 4]);
+        assert_eq!(metadata.color_matrix, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_dual_illuminant_color() {
+        use crate::color::{Illuminant, WhitePoint};
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let matrix = |m: [i32; 9]| Value::SRational(m.iter().map(|&v| (v, 10000)).collect());
+        let dng = synthetic_compressed_dng(1, vec![
+            Entry(0xC621, matrix([8000, -2000, -1000, -4000, 13000, 2000, -500, 2000, 5000])),
+            Entry(0xC622, matrix([7000, -1500, -1000, -4500, 12500, 2500, -1000, 2500, 6500])),
+            Entry(0xC628, Value::Rational(vec![(4192, 10000), (1, 1), (7136, 10000)])),
+            Entry(0xC65A, Value::Short(vec![17])),
+            Entry(0xC65B, Value::Short(vec![21])),
+        ]);
+
+        let color = zrraw_lib.extract_metadata(&dng).unwrap().extended.color.unwrap();
+        let illuminants: Vec<_> = color.calibrations.iter().map(|c| c.illuminant).collect();
+        assert_eq!(illuminants, [Illuminant::STANDARD_LIGHT_A, Illuminant::D65]);
+        assert_eq!(color.calibrations[1].color_matrix[0], 0.7);
+
+        // The as-shot neutral sits between the two illuminants
+        let as_shot = zrraw_lib.color_matrices(&dng, WhitePoint::AsShot).unwrap();
+        assert!((as_shot.temperature - 5500.0).abs() < 2.0);
+        for (i, c) in as_shot.neutral.iter().enumerate() {
+            assert!((c - [0.4192, 1.0, 0.7136][i]).abs() < 1e-4);
+        }
+        for row in as_shot.camera_to_srgb.chunks(3) {
+            let white: f32 = row.iter().zip(as_shot.neutral).map(|(m, n)| m * n).sum();
+            assert!((white - 1.0).abs() < 1e-3);
+        }
+
+        // At standard light A only its matrix is used
+        let tungsten = WhitePoint::Temperature { kelvin: 2850.0, tint: 0.0 };
+        let warm = zrraw_lib.color_matrices(&dng, tungsten).unwrap();
+        for (i, c) in warm.neutral.iter().enumerate() {
+            assert!((c - [0.69153, 1.0, 0.34608][i]).abs() < 1e-4);
+        }
+        let back = zrraw_lib.color_matrices(&dng, WhitePoint::Neutral(warm.neutral)).unwrap();
+        assert!((back.temperature - 2850.0).abs() < 1.0);
+
+        // Without calibration there is nothing to interpolate
+        let plain = synthetic_compressed_dng(1, Vec::new());
+        assert!(matches!(zrraw_lib.color_matrices(&plain, WhitePoint::AsShot), Err(ZrRawError::InvalidInput)));
+    }
+
+    #[test]
+    fn test_xmp_sidecar_round_trip() {
+        use crate::xmp::{sidecar_path, Crop, Xmp, NS_CRS};
+        let sidecar = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="Adobe XMP Core 7.0">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/"
+    xmlns:crs="http://ns.adobe.com/camera-raw-settings/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:lr="http://ns.adobe.com/lightroom/1.0/"
+    xmp:Rating="3"
+    xmp:Label="Red"
+    photoshop:City="Gen&#xE8;ve &amp; co"
+    crs:ProcessVersion="11.0"
+    crs:Temperature="5500"
+    crs:Tint="+10"
+    crs:Exposure2012="+0.35"
+    crs:HasCrop="True"
+    crs:CropTop="0.1"
+    crs:CropLeft="0.2"
+    crs:CropBottom="0.9"
+    crs:CropRight="0.8"
+    crs:CropAngle="0">
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">Lake</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>water</rdf:li>
+     <rdf:li>alps</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <lr:hierarchicalSubject>
+    <rdf:Bag>
+     <rdf:li>places|alps</rdf:li>
+    </rdf:Bag>
+   </lr:hierarchicalSubject>
+   <!-- kept -->
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+        let xmp = Xmp::parse(sidecar.as_bytes()).unwrap();
+        // Character references are decoded, so compare parsed packets
+        assert_eq!(Xmp::parse(&xmp.to_bytes()).unwrap(), xmp);
+        let mut metadata = xmp.metadata();
+        assert_eq!(metadata.rating, Some(3));
+        assert_eq!(metadata.label.as_deref(), Some("Red"));
+        assert_eq!(metadata.keywords, ["water", "alps"]);
+        assert_eq!(metadata.title.as_deref(), Some("Lake"));
+        assert_eq!(metadata.develop.temperature, Some(5500));
+        assert_eq!(metadata.develop.tint, Some(10));
+        assert_eq!(metadata.develop.exposure, Some(0.35));
+        assert_eq!(metadata.develop.contrast, None);
+        let crop = Crop { top: 0.1, left: 0.2, bottom: 0.9, right: 0.8, angle: 0.0 };
+        assert_eq!(metadata.develop.crop, Some(crop));
+
+        metadata.rating = Some(5);
+        metadata.label = None;
+        metadata.keywords.push("dusk".to_string());
+        metadata.title = None;
+        metadata.description = Some("Evening <light>".to_string());
+        metadata.develop.contrast = Some(-15);
+        metadata.develop.crop = None;
+        let mut edited = xmp.clone();
+        edited.apply(&metadata);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = sidecar_path(dir.path().join("IMG_0001.CR2"));
+        assert_eq!(path.file_name().unwrap(), "IMG_0001.xmp");
+        edited.write_sidecar(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let reread = Xmp::read_sidecar(&path).unwrap();
+        assert_eq!(reread.metadata(), metadata);
+        assert_eq!(reread.text(NS_CRS, "Contrast2012").as_deref(), Some("-15"));
+        assert!(text.contains("xmp:Rating=\"5\""));
+        assert!(!text.contains("Label") && !text.contains("dc:title") && !text.contains("CropTop"));
+        // Unknown namespaces, comments and layout survive the edit
+        assert!(text.contains("photoshop:City=\"Genève &amp; co\""));
+        assert!(text.contains("   <lr:hierarchicalSubject>\n    <rdf:Bag>\n     <rdf:li>places|alps</rdf:li>"));
+        assert!(text.contains("<!-- kept -->"));
+        assert!(text.contains("\n    crs:ProcessVersion=\"11.0\""));
+        assert!(text.contains("<rdf:li xml:lang=\"x-default\">Evening &lt;light&gt;</rdf:li>"));
+
+        // A new packet declares the namespaces it uses
+        let mut fresh = Xmp::new();
+        fresh.set_text(NS_CRS, "WhiteBalance", "As Shot");
+        let fresh = Xmp::parse(&fresh.to_bytes()).unwrap();
+        assert_eq!(fresh.metadata().develop.white_balance.as_deref(), Some("As Shot"));
+        assert!(Xmp::parse(b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>").is_err());
+    }
+
+    #[test]
+    fn test_xmp_nesting_limit() {
+        use crate::xmp::Xmp;
+
+        let nested = |levels: usize| {
+            let open = concat!(
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+                "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            );
+            let close = "</rdf:RDF></x:xmpmeta>";
+            format!("{open}{}{}{close}", "<a>".repeat(levels), "</a>".repeat(levels))
+        };
+        // x:xmpmeta and rdf:RDF count too
+        assert!(Xmp::parse(nested(254).as_bytes()).is_ok());
+        let error = Xmp::parse(nested(255).as_bytes()).unwrap_err();
+        assert!(matches!(error, ZrRawError::ParseError(ref message) if message.contains("nested too deeply")));
+        // Deep enough to exhaust the stack without the limit
+        assert!(matches!(Xmp::parse(nested(1_000_000).as_bytes()), Err(ZrRawError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_xmp_embedded_packets() {
+        use crate::xmp::Xmp;
+        let packet = "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+            <x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+            <rdf:Description rdf:about=\"\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\
+            <xmp:Rating>4</xmp:Rating></rdf:Description></rdf:RDF></x:xmpmeta>\
+            <?xpacket end=\"w\"?>";
+
+        // TIFF tag 700, padded with NULs as some cameras write it
+        let mut bytes = packet.as_bytes().to_vec();
+        bytes.extend([0; 7]);
+        let tiff = TiffBuilder::new(false).build(vec![
+            Entry(0x010F, Value::Ascii("NIKON CORPORATION")),
+            Entry(0x02BC, Value::Byte(bytes)),
+        ]);
+        let xmp = Xmp::embedded(&tiff).unwrap();
+        assert_eq!(xmp.rating(), Some(4));
+        assert_eq!(xmp.to_bytes(), packet.as_bytes());
+
+        // CR3: a top-level uuid box after ftyp and moov
+        let boxed = |kind: &[u8; 4], payload: &[u8]| {
+            let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+            b.extend(kind);
+            b.extend(payload);
+            b
+        };
+        let mut uuid = vec![
+            0xBE, 0x7A, 0xCF, 0xCB, 0x97, 0xA9, 0x42, 0xE8, 0x9C, 0x71, 0x99, 0x94, 0x91, 0xE3, 0xAF, 0xAC,
+        ];
+        uuid.extend(packet.as_bytes());
+        let mut cr3 = boxed(b"ftyp", b"crx \0\0\0\x01crx isom");
+        cr3.extend(boxed(b"moov", &[0; 16]));
+        cr3.extend(boxed(b"uuid", &uuid));
+        assert_eq!(Xmp::embedded(&cr3).unwrap().rating(), Some(4));
+        assert!(Xmp::embedded(&cr3[..cr3.len() - 20]).is_none());
+
+        // Cameras without XMP may still set the IFD0 Rating tag
+        let rated = TiffBuilder::new(false).build(vec![Entry(0x4746, Value::Short(vec![2]))]);
+        assert!(Xmp::embedded(&rated).is_none());
+        assert_eq!(exif::Exif::read(&rated).rating(), Some(2));
+    }
+
+    #[test]
+    fn test_metadata_edit_in_place() {
+        use crate::edit::{image_data_ranges, MetadataEdit};
+        use crate::exif::{Directory, Exif, ExifValue, GpsPosition};
+        use crate::makernote::Makernote;
+        use crate::xmp::{Xmp, NS_DC};
+
+        // A CR2-like file whose makernote offsets count from the TIFF header
+        let samples: Vec<u16> = (0..16).map(|i| 1024 + i * 37).collect();
+        let note_entries = || {
+            vec![Entry(0x0006, Value::Ascii("Canon EOS 5D Mark IV")), Entry(0x000C, Value::Long(vec![4242]))]
+        };
+        let build = |note: Vec<u8>| {
+            TiffBuilder::new(false).build(vec![
+                Entry(0x010F, Value::Ascii("Canon")),
+                Entry(0x0110, Value::Ascii("Canon EOS 5D Mark IV")),
+                Entry(0x0132, Value::Ascii("2024:12:31 23:30:00")),
+                Entry(0x014A, Value::Ifds(vec![vec![
+                    Entry(0x0100, Value::Long(vec![4])),
+                    Entry(0x0101, Value::Long(vec![4])),
+                    Entry(0x0111, Value::Offset(pack_msb(&samples, 16))),
+                    Entry(0x0117, Value::Long(vec![32])),
+                ]])),
+                Entry(0x8769, Value::Ifd(vec![
+                    Entry(0x9003, Value::Ascii("2024:12:31 23:30:00")),
+                    Entry(0x9004, Value::Ascii("2024:12:31 23:30:00")),
+                    Entry(0x927C, Value::Undefined(note)),
+                ])),
+            ])
+        };
+        let len = ifd_block(false, 0, note_entries()).len();
+        let mut raw = build(vec![0; len]);
+        let start = crate::exif::makernote_location(&raw).unwrap().start;
+        raw[start..start + len].copy_from_slice(&ifd_block(false, start as u32, note_entries()));
+        let makernote = Makernote::read(&raw, &Exif::read(&raw));
+        assert!(makernote.is_some());
+        let image = image_data_ranges(&raw);
+        assert_eq!(image.len(), 1);
+        assert_eq!(image[0].len(), 32);
+
+        // Same-size dates are rewritten where they are
+        let clock = MetadataEdit::new().shift_dates(45 * 60).plan(&raw).unwrap();
+        assert!(clock.changes().iter().all(|change| !change.before.is_empty()));
+        let mut fixed = raw.clone();
+        clock.apply(&mut fixed).unwrap();
+        assert_eq!(fixed.len(), raw.len());
+        let exif = Exif::read(&fixed);
+        assert_eq!(exif.date_time_original().unwrap().to_string(), "2025-01-01T00:15:00");
+        assert_eq!(exif.modify_date().unwrap().to_string(), "2025-01-01T00:15:00");
+        let differing = raw.iter().zip(&fixed).filter(|(a, b)| a != b).count();
+        assert_eq!(differing, clock.changes().iter().map(|change| change.after.len()).sum::<usize>());
+
+        // New and larger values move their directories to the end
+        let geneva = GpsPosition { latitude: 46.2044, longitude: -6.1432, altitude: Some(375.5) };
+        let edit = MetadataEdit::new().artist("Ada Example").copyright("(c) 2025 Ada Example").rating(4).gps(geneva);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("IMG_0001.CR2");
+        std::fs::write(&path, &raw).unwrap();
+        let dry = edit.apply_to_file(&path, true).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), raw);
+        let plan = edit.apply_to_file(&path, false).unwrap();
+        assert_eq!(plan, dry);
+        let edited = std::fs::read(&path).unwrap();
+        let mut expected = raw.clone();
+        plan.apply(&mut expected).unwrap();
+        assert_eq!(edited, expected);
+
+        // The dry run names every byte that differs: the relinked IFD0
+        // pointer in place, the rest appended
+        let appended = plan.changes().last().unwrap();
+        assert_eq!(appended.offset as usize, raw.len());
+        assert_eq!(edited.len(), raw.len() + appended.after.len());
+        let in_place: Vec<_> = plan.changes().iter().filter(|change| !change.before.is_empty()).collect();
+        assert!(in_place.iter().all(|change| change.offset >= 4 && change.offset + (change.after.len() as u64) <= 8));
+        for (i, (a, b)) in raw.iter().zip(&edited).enumerate() {
+            let reported = in_place.iter().any(|c| (c.offset as usize..c.offset as usize + c.after.len()).contains(&i));
+            assert_eq!(a != b, reported);
+        }
+
+        let exif = Exif::read(&edited);
+        assert_eq!(exif.artist(), Some("Ada Example"));
+        assert_eq!(exif.copyright(), Some("(c) 2025 Ada Example"));
+        assert_eq!(exif.rating(), Some(4));
+        let position = exif.gps_position().unwrap();
+        assert!((position.latitude - 46.2044).abs() < 1e-6 && (position.longitude + 6.1432).abs() < 1e-6);
+        assert_eq!(position.altitude, Some(375.5));
+        let xmp = Xmp::embedded(&edited).unwrap();
+        assert_eq!(xmp.rating(), Some(4));
+        assert_eq!(xmp.metadata().rating, Some(4));
+        assert_eq!(xmp.default_text(NS_DC, "rights").as_deref(), Some("(c) 2025 Ada Example"));
+
+        // Makernote and sensor data did not move
+        assert_eq!(crate::exif::makernote_location(&edited).unwrap().start, start);
+        assert_eq!(Makernote::read(&edited, &exif), makernote);
+        assert_eq!(image_data_ranges(&edited), image);
+        assert_eq!(edited[image[0].clone()], raw[image[0].clone()]);
+
+        // Later edits keep the packet in step with IFD0
+        let again = MetadataEdit::new().artist("Grace Example").plan(&edited).unwrap();
+        let mut twice = edited.clone();
+        again.apply(&mut twice).unwrap();
+        let xmp = Xmp::embedded(&twice).unwrap();
+        assert_eq!(xmp.get(NS_DC, "creator"), Some(crate::xmp::XmpValue::Array(vec!["Grace Example".to_string()])));
+        assert_eq!(xmp.rating(), Some(4));
+
+        let strips = MetadataEdit::new().set(Directory::Ifd0, 0x0111, ExifValue::Long(vec![0]));
+        assert!(matches!(strips.plan(&raw), Err(ZrRawError::InvalidInput)));
+        assert!(matches!(edit.plan(&jpeg_with_exif(&raw)), Err(ZrRawError::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn test_dng_unknown_compression_is_named() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        // A JPEG XL compressed ProRAW, which zrraw does not decode
+        let dng = synthetic_compressed_dng(52546, vec![
+            Entry(0x010F, Value::Ascii("Apple")),
+            Entry(0x0110, Value::Ascii("iPhone 16 Pro")),
+        ]);
+
+        let metadata = zrraw_lib.extract_metadata(&dng).unwrap();
+        assert_eq!(metadata.model, "iPhone 16 Pro");
+        let result = zrraw_lib.extract_sensor_data(&dng);
+        assert!(matches!(result, Err(ZrRawError::UnsupportedCompression(52546))));
+    }
+
+    #[test]
+    fn test_dng_tiled_process_applies_default_crop() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let result = zrraw_lib.process_file(&synthetic_dng(), ProcessingParams::default()).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (6, 2));
+    }
+
+    #[test]
+    fn test_linear_dng_deflate_float() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let dng = synthetic_linear_dng();
+        assert!(matches!(zrraw_lib.detect_format(&dng).unwrap(), RawFormat::Adobe(AdobeFormat::DNG)));
+
+        let params = ProcessingParams { output_16bit: true, ..Default::default() };
+        let result = zrraw_lib.process_file(&dng, params).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (4, 2));
+        assert!(matches!(result.image, DynamicImage::ImageRgb16(_)));
+    }
+
+    #[test]
+    fn test_proraw_gain_table_masks_and_baseline_exposure() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+
+        // One grid point with a single-entry table: a constant gain of 0.5
+        let mut gain_table = Vec::new();
+        for v in [1u32, 1] { gain_table.extend(v.to_be_bytes()); }
+        for v in [1.0f64, 1.0, 0.0, 0.0] { gain_table.extend(v.to_be_bytes()); }
+        gain_table.extend(1u32.to_be_bytes());
+        for v in [0.0f32, 0.0, 0.0, 0.0, 1.0, 0.5] { gain_table.extend(v.to_be_bytes()); }
+
+        let pixels: Vec<u8> = [16384u16; 12].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let dng = TiffBuilder::new(false).build(vec![
+            Entry(0x00FE, Value::Long(vec![0])),
+            Entry(0x0100, Value::Long(vec![2])),
+            Entry(0x0101, Value::Long(vec![2])),
+            Entry(0x0102, Value::Short(vec![16; 3])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x0106, Value::Short(vec![34892])),
+            Entry(0x010F, Value::Ascii("Apple")),
+            Entry(0x0110, Value::Ascii("iPhone 15 Pro")),
+            Entry(0x0111, Value::Offset(pixels)),
+            Entry(0x0115, Value::Short(vec![3])),
+            Entry(0x0116, Value::Long(vec![2])),
+            Entry(0x0117, Value::Long(vec![24])),
+            Entry(0x014A, Value::Ifd(vec![
+                Entry(0x00FE, Value::Long(vec![0x10004])),
+                Entry(0x0100, Value::Long(vec![2])),
+                Entry(0x0101, Value::Long(vec![1])),
+                Entry(0x0102, Value::Short(vec![8])),
+                Entry(0x0103, Value::Short(vec![1])),
+                Entry(0x0106, Value::Short(vec![4])),
+                Entry(0x0111, Value::Offset(vec![0, 255])),
+                Entry(0x0116, Value::Long(vec![1])),
+                Entry(0x0117, Value::Long(vec![2])),
+                Entry(0xCD2E, Value::Ascii("Sky")),
+            ])),
+            Entry(0xC612, Value::Byte(vec![1, 6, 0, 0])),
+            Entry(0xC61D, Value::Long(vec![65535])),
+            Entry(0xC62A, Value::Rational(vec![(1, 1)])),
+            Entry(0xC7A5, Value::Rational(vec![(1, 2)])),
+            Entry(0xCD2D, Value::Undefined(gain_table)),
+        ]);
+
+        let metadata = zrraw_lib.extract_metadata(&dng).unwrap();
+        assert_eq!(metadata.baseline_exposure, 1.5);
+
+        let masks = zrraw_lib.semantic_masks(&dng).unwrap();
+        assert_eq!(masks.len(), 1);
+        assert_eq!(masks[0].name, "Sky");
+        assert_eq!(masks[0].instance_id, "");
+        assert_eq!(masks[0].image.as_raw(), &vec![0, 255]);
+        assert!(zrraw_lib.semantic_masks(&synthetic_nef()).unwrap().is_empty());
+
+        // 0.25 halved by the gain table, then pushed up 1.5 stops
+        let result = zrraw_lib.process_file(&dng, ProcessingParams::default()).unwrap();
+        let rgb = result.image.to_rgb8();
+        assert!(rgb.pixels().all(|p| p.0 == [159, 159, 159]));
+    }
+
+    #[test]
+    fn test_embedded_previews() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let encode = |width: u32, height: u32| {
+            let pixels = vec![128u8; (width * height * 3) as usize];
+            let mut jpeg = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+                .encode(&pixels, width, height, image::ExtendedColorType::Rgb8)
+                .unwrap();
+            jpeg
+        };
+        let full = encode(64, 48);
+        let medium = encode(32, 24);
+        let thumbnail: Vec<u8> = (0..16 * 12 * 3).map(|i| i as u8).collect();
+
+        // The medium JPEG hangs off the makernote's PreviewIFD
+        let makernote_tiff = TiffBuilder::new(true).build(vec![
+            Entry(0x0011, Value::Ifd(vec![
+                Entry(0x0201, Value::Offset(medium.clone())),
+                Entry(0x0202, Value::Long(vec![medium.len() as u32])),
+            ])),
+        ]);
+        let mut makernote = b"Nikon\0\x02\x10\0\0".to_vec();
+        makernote.extend(makernote_tiff);
+
+        let nef = TiffBuilder::new(true).build(vec![
+            Entry(0x00FE, Value::Long(vec![1])),
+            Entry(0x0100, Value::Long(vec![16])),
+            Entry(0x0101, Value::Long(vec![12])),
+            Entry(0x0102, Value::Short(vec![8, 8, 8])),
+            Entry(0x0103, Value::Short(vec![1])),
+            Entry(0x0106, Value::Short(vec![2])),
+            Entry(0x010F, Value::Ascii("NIKON CORPORATION")),
+            Entry(0x0110, Value::Ascii("NIKON D810")),
+            Entry(0x0111, Value::Offset(thumbnail.clone())),
+            Entry(0x0112, Value::Short(vec![6])),
+            Entry(0x0115, Value::Short(vec![3])),
+            Entry(0x0117, Value::Long(vec![thumbnail.len() as u32])),
+            Entry(0x014A, Value::Ifd(vec![
+                Entry(0x0201, Value::Offset(full.clone())),
+                Entry(0x0202, Value::Long(vec![full.len() as u32])),
+            ])),
+            Entry(0x8769, Value::Ifd(vec![Entry(0x927C, Value::Undefined(makernote))])),
+        ]);
+
+        let previews = zrraw_lib.previews(&nef).unwrap();
+        let found: Vec<_> = previews.iter().map(|p| (p.format, p.width, p.height)).collect();
+        assert_eq!(found, vec![
+            (PreviewFormat::Rgb8, 16, 12),
+            (PreviewFormat::Jpeg, 64, 48),
+            (PreviewFormat::Jpeg, 32, 24),
+        ]);
+        assert!(previews.iter().all(|p| p.orientation == Orientation::Rotate90));
+        assert_eq!(&nef[previews[1].range()], &full[..]);
+
+        let preview = zrraw_lib.extract_preview(&nef, PreviewSelector::LargestUnder(40)).unwrap().unwrap();
+        assert_eq!(preview.bytes, &medium[..]);
+        let decoded = preview.decode().unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (32, 24));
+
+        let smallest = zrraw_lib.extract_preview(&nef, PreviewSelector::Smallest).unwrap().unwrap();
+        assert_eq!(smallest.decode().unwrap().to_rgb8().into_raw(), thumbnail);
+        // Nothing fits, so the smallest is the closest
+        let fallback = zrraw_lib.extract_preview(&nef, PreviewSelector::LargestUnder(8)).unwrap().unwrap();
+        assert_eq!(fallback.info.width, 16);
+        assert!(zrraw_lib.extract_preview(&nef, PreviewSelector::Index(3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mrw_previews() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let encode = |width: u32, height: u32| {
+            let mut jpeg = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+                .encode(&vec![96u8; (width * height * 3) as usize], width, height, image::ExtendedColorType::Rgb8)
+                .unwrap();
+            jpeg
+        };
+        let thumbnail = encode(16, 12);
+        let preview = encode(64, 48);
+
+        // The Minolta makernote holds the thumbnail and points at the preview
+        let ttw = tiff_relative_makernote("Minolta", b"", || vec![
+            Entry(0x0081, Value::Undefined(thumbnail.clone())),
+            Entry(0x0088, Value::Offset(preview.clone())),
+            Entry(0x0089, Value::Long(vec![preview.len() as u32])),
+        ]);
+        let mrw = mrw_file(&[(b"\0TTW", ttw)], &[]);
+
+        let previews = zrraw_lib.previews(&mrw).unwrap();
+        let found: Vec<_> = previews.iter().map(|p| (p.format, p.width, p.height)).collect();
+        assert_eq!(found, vec![(PreviewFormat::Jpeg, 16, 12), (PreviewFormat::Jpeg, 64, 48)]);
+        assert_eq!(&mrw[previews[1].range()], &preview[..]);
+    }
+
+    #[test]
+    fn test_iiq_previews() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let mut preview = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut preview)
+            .encode(&[64u8; 64 * 48 * 3], 64, 48, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        let thumbnail: Vec<u8> = (0..16 * 12 * 3).map(|i| i as u8).collect();
+
+        // The TIFF structure after the Phase One block holds a strip
+        // thumbnail and, in a SubIFD, the JPEG preview
+        let iiq = iiq_file_with_ifd0(
+            vec![(0x0108, IiqValue::Int(4)), (0x0109, IiqValue::Int(2))],
+            vec![
+                Entry(0x0100, Value::Long(vec![16])),
+                Entry(0x0101, Value::Long(vec![12])),
+                Entry(0x0102, Value::Short(vec![8, 8, 8])),
+                Entry(0x0103, Value::Short(vec![1])),
+                Entry(0x0106, Value::Short(vec![2])),
+                Entry(0x010F, Value::Ascii("Phase One")),
+                Entry(0x0111, Value::Offset(thumbnail.clone())),
+                Entry(0x0112, Value::Short(vec![8])),
+                Entry(0x0115, Value::Short(vec![3])),
+                Entry(0x0117, Value::Long(vec![thumbnail.len() as u32])),
+                Entry(0x014A, Value::Ifd(vec![
+                    Entry(0x0201, Value::Offset(preview.clone())),
+                    Entry(0x0202, Value::Long(vec![preview.len() as u32])),
+                ])),
+            ],
+        );
+        assert!(matches!(zrraw_lib.detect_format(&iiq).unwrap(), RawFormat::PhaseOne(PhaseOneFormat::IIQ)));
+
+        let previews = zrraw_lib.previews(&iiq).unwrap();
+        let found: Vec<_> = previews.iter().map(|p| (p.format, p.width, p.height)).collect();
+        assert_eq!(found, vec![(PreviewFormat::Rgb8, 16, 12), (PreviewFormat::Jpeg, 64, 48)]);
+        assert!(previews.iter().all(|p| p.orientation == Orientation::Rotate270));
+        assert_eq!(&iiq[previews[0].range()], &thumbnail[..]);
+        assert_eq!(&iiq[previews[1].range()], &preview[..]);
+    }
+
+    #[test]
+    fn test_exif_tag_map() {
+        use crate::exif::{Directory, Exif, ExifValue, ExposureProgram, MeteringMode, Rational};
+
+        let tiff = TiffBuilder::new(false).build(vec![
+            Entry(0x010F, Value::Ascii("NIKON CORPORATION")),
+            Entry(0x0110, Value::Ascii("NIKON Z 8")),
+            Entry(0x0132, Value::Ascii("2024:05:02 09:00:00")),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x829A, Value::Rational(vec![(1, 250)])),
+                Entry(0x8822, Value::Short(vec![3])),
+                Entry(0x9003, Value::Ascii("2024:05:01 18:30:15")),
+                Entry(0x9011, Value::Ascii("+02:00")),
+                Entry(0x9204, Value::SRational(vec![(-2, 3)])),
+                Entry(0x9207, Value::Short(vec![5])),
+                Entry(0x9209, Value::Short(vec![0x19])),
+                Entry(0x9291, Value::Ascii("25")),
+                Entry(0xA005, Value::Ifd(vec![Entry(0x0001, Value::Ascii("R98"))])),
+                Entry(0xA431, Value::Ascii("3012345")),
+                Entry(0xA434, Value::Ascii("NIKKOR Z 24-120mm f/4 S")),
+                Entry(0xC000, Value::Long(vec![7])),
+            ])),
+            Entry(0x8825, Value::Ifd(vec![
+                Entry(0x0001, Value::Ascii("N")),
+                Entry(0x0002, Value::Rational(vec![(48, 1), (51, 1), (2412, 100)])),
+                Entry(0x0003, Value::Ascii("W")),
+                Entry(0x0004, Value::Rational(vec![(2, 1), (21, 1), (0, 1)])),
+                Entry(0x0006, Value::Rational(vec![(35, 1)])),
+                Entry(0x0007, Value::Rational(vec![(16, 1), (30, 1), (155, 10)])),
+                Entry(0x001D, Value::Ascii("2024:05:01")),
+            ])),
+        ]);
+
+        let exif = Exif::read(&tiff);
+        assert_eq!(exif.model(), Some("NIKON Z 8"));
+        assert_eq!(exif.lens_model(), Some("NIKKOR Z 24-120mm f/4 S"));
+        assert_eq!(exif.body_serial_number(), Some("3012345"));
+        assert_eq!(exif.exposure_time(), Some(Rational::new(1, 250)));
+        assert_eq!(exif.exposure_program(), Some(ExposureProgram::AperturePriority));
+        assert_eq!(exif.metering_mode(), Some(MeteringMode::Pattern));
+        assert!(exif.flash().unwrap().fired());
+        assert_eq!(exif.flash().unwrap().mode(), 3);
+        assert!((exif.exposure_bias().unwrap() + 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(exif.date_time_original().unwrap().to_string(), "2024-05-01T18:30:15.25+02:00");
+        assert_eq!(exif.modify_date().unwrap().to_string(), "2024-05-02T09:00:00");
+        assert_eq!(exif.gps_time().unwrap().to_string(), "2024-05-01T16:30:15.5+00:00");
+
+        let position = exif.gps_position().unwrap();
+        assert!((position.latitude - 48.8567).abs() < 1e-4);
+        assert!((position.longitude + 2.35).abs() < 1e-9);
+        assert_eq!(position.altitude, Some(35.0));
+
+        let interop = exif.get(Directory::Interop, 0x0001).unwrap();
+        assert_eq!(interop.name, Some("InteroperabilityIndex"));
+        assert_eq!(interop.value, ExifValue::Ascii("R98".to_string()));
+        let private = exif.get(Directory::Exif, 0xC000).unwrap();
+        assert_eq!((private.name, private.tiff_type), (None, 4));
+        assert_eq!(exif.get(Directory::Gps, 0x0002).unwrap().name, Some("GPSLatitude"));
+
+        // RAFs keep theirs in the embedded JPEG
+        let raf = raf_file("X-T5", &jpeg_with_exif(&tiff), &[], &[0; 16]);
+        assert_eq!(Exif::read(&raf).lens_model(), Some("NIKKOR Z 24-120mm f/4 S"));
+        assert!(Exif::read(b"not a raw file").is_empty());
+    }
+
+    #[test]
+    fn test_capture_date_has_one_source() {
+        let tiff = TiffBuilder::new(false).build(vec![
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x9003, Value::Ascii("2024:06:01 07:45:00")),
+                Entry(0x9011, Value::Ascii("+09:00")),
+            ])),
+        ]);
+        let mut metadata = writer_metadata(4, 4);
+        metadata.read_exif(&tiff);
+        // Whatever the native blob says, the EXIF map's date wins
+        let blob_date = exif::DateTime::parse("2024:06:01 07:45:00").map(|d| d.with_offset(Some("+02:00")));
+        metadata.set_extended(extended::ExtendedMetadata { date_time_original: blob_date, ..Default::default() });
+        assert_eq!(metadata.extended.date_time_original, metadata.exif().date_time_original());
+        assert_eq!(metadata.extended.date_time_original.unwrap().offset_minutes, Some(540));
+    }
+
+    #[test]
+    fn test_exposure_fractions_and_orientation() {
+        assert_eq!(ExposureTime::new(1, 3).to_string(), "1/3");
+        assert_eq!(ExposureTime::new(10, 2500).to_string(), "1/250");
+        assert_eq!(ExposureTime::new(25, 10).to_string(), "2.5");
+        assert_eq!(ExposureTime::new(3, 10).to_string(), "0.3");
+        assert_eq!(ExposureTime::from_apex(8.0), ExposureTime::new(1, 256));
+        assert!((ExposureTime::new(1, 250).to_apex().unwrap() - 7.966).abs() < 1e-3);
+        assert_eq!(ExposureTime::default().to_apex(), None);
+
+        assert_eq!(FNumber::new(28, 10).to_string(), "f/2.8");
+        assert_eq!(FNumber::new(11, 1).to_string(), "f/11");
+        assert_eq!(FNumber::from_apex(5.0).to_string(), "f/5.7");
+        assert!((FNumber::new(4, 1).to_apex().unwrap() - 4.0).abs() < 1e-9);
+
+        // Stored sideways: the top row has to end up in the right-hand column
+        let stored = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 0]));
+        let upright = Orientation::from_exif(6).apply(DynamicImage::ImageRgb8(stored)).to_rgb8();
+        assert_eq!(upright.dimensions(), (2, 3));
+        assert_eq!(upright.get_pixel(1, 0).0, [0, 0, 0]);
+        assert_eq!(upright.get_pixel(0, 2).0, [2, 1, 0]);
+        assert!(Orientation::Rotate270.swaps_dimensions());
+        assert_eq!(Orientation::from_exif(9), Orientation::Normal);
+    }
+
+    #[test]
+    fn test_extended_metadata_decode() {
+        let entry = |key: u16, value_type: u16, value: &[u8]| {
+            let mut bytes = [key.to_le_bytes(), value_type.to_le_bytes()].concat();
+            bytes.extend((value.len() as u32).to_le_bytes());
+            bytes.extend(value);
+            bytes
+        };
+        let u32s = |values: &[u32]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let mut blob = b"ZRMD\x01\x00\x00\x00".to_vec();
+        blob.extend(entry(1, 1, b"XF23mmF1.4 R LM WR"));
+        blob.extend(entry(2, 1, b"2024:06:01 18:30:05"));
+        blob.extend(entry(3, 1, b"25"));
+        blob.extend(entry(4, 1, b"+02:00"));
+        blob.extend(entry(5, 1, b"4CA12345"));
+        // Keys and value types from later versions are skipped
+        blob.extend(entry(900, 1, b"future"));
+        blob.extend(entry(6, 77, b"??"));
+        blob.extend(entry(7, 2, &u32s(&[8, 4, 6000, 4000])));
+        blob.extend(entry(8, 4, &[2, 2, 1, 2, 0, 1]));
+        blob.extend(entry(9, 2, &u32s(&[14])));
+        blob.extend(entry(10, 2, &u32s(&[7])));
+        blob.extend(entry(11, 3, &(-4.5f32).to_le_bytes()));
+        blob.extend(entry(12, 1, b"Fujifilm"));
+        let f32s = |values: &[f32]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let standard_a = [0.8, -0.2, -0.1, -0.4, 1.3, 0.2, -0.05, 0.2, 0.5];
+        let d65 = [0.7, -0.15, -0.1, -0.45, 1.25, 0.25, -0.1, 0.25, 0.65];
+        blob.extend(entry(14, 2, &u32s(&[17, 21])));
+        blob.extend(entry(15, 3, &f32s(&standard_a)));
+        blob.extend(entry(16, 3, &f32s(&d65)));
+        blob.extend(entry(22, 3, &f32s(&[0.6, 0.3, 0.05, 0.25, 0.7, 0.05, 0.0, 0.05, 0.78])));
+        blob.extend(entry(25, 3, &f32s(&[0.5, 1.0, 0.7])));
+
+        let decoded = extended::ExtendedMetadata::decode(&blob).unwrap();
+        assert_eq!(decoded.lens.as_deref(), Some("XF23mmF1.4 R LM WR"));
+        assert_eq!(
+            decoded.date_time_original.unwrap().to_string(),
+            "2024-06-01T18:30:05.25+02:00"
+        );
+        assert_eq!(decoded.body_serial_number.as_deref(), Some("4CA12345"));
+        assert_eq!(decoded.lens_serial_number, None);
+        assert_eq!(decoded.crop, Some(extended::CropRect { x: 8, y: 4, width: 6000, height: 4000 }));
+        assert_eq!(decoded.cfa_pattern.unwrap().colors, vec![1, 2, 0, 1]);
+        assert_eq!(decoded.bits_per_sample, Some(14));
+        assert_eq!(decoded.compression, Some(7));
+        assert_eq!(decoded.sensor_temperature, Some(-4.5));
+        assert_eq!(decoded.canonical_make.as_deref(), Some("Fujifilm"));
+        assert_eq!(decoded.canonical_model, None);
+        let color = decoded.color.unwrap();
+        assert_eq!(color.calibrations.len(), 2);
+        assert_eq!(color.calibrations[0].color_matrix, standard_a);
+        assert_eq!(color.calibrations[0].forward_matrix, None);
+        let daylight = color.calibration(color::Illuminant::D65).unwrap();
+        assert_eq!(daylight.color_matrix, d65);
+        assert_eq!(daylight.forward_matrix.unwrap()[1], 0.3);
+        assert_eq!(color.calibrations[0].illuminant.temperature(), Some(2850.0));
+        assert_eq!(color.analog_balance, [1.0; 3]);
+        assert_eq!(color.as_shot_neutral, Some([0.5, 1.0, 0.7]));
+
+        // A truncated entry is corrupt, an unknown framing version unsupported
+        assert!(extended::ExtendedMetadata::decode(&blob[..blob.len() - 1]).is_err());
+        blob[4] = 2;
+        assert!(matches!(extended::ExtendedMetadata::decode(&blob), Err(ZrRawError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_vendor_makernotes() {
+        use crate::exif::Exif;
+        use crate::makernote::{canon::WhiteBalancePreset, nikon, sony, DriveMode, FocusMode, Makernote};
+
+        let read = |tiff: &[u8]| Makernote::read(tiff, &Exif::read(tiff));
+
+        let canon = tiff_relative_makernote("Canon", b"", || {
+            let mut settings = vec![0u16; 46];
+            settings[0] = 92;
+            settings[5] = 1;
+            settings[7] = 1;
+            settings[22] = 61182;
+            (settings[23], settings[24], settings[25]) = (105, 24, 1);
+            // f/4 to f/22: 4 and 8.92 stops in Canon EV
+            (settings[26], settings[27]) = (0x80, 0x11d);
+            let mut shot_info = vec![0u16; 34];
+            shot_info[6] = 0xfff4;
+            shot_info[12] = 151;
+            let mut color_data = vec![0u16; 1227];
+            color_data[0x3f..0x44].copy_from_slice(&[2000, 1024, 1024, 1500, 5200]);
+            color_data[0x8f..0x94].copy_from_slice(&[2100, 1024, 1024, 1400, 5500]);
+            let af_info2 = vec![
+                36, 2, 2, 2, 6000, 4000, 6000, 4000, 80, 80, 90, 90, (-100i16) as u16, 100, 0, 50, 0b10, 0b01,
+            ];
+            vec![
+                Entry(0x0001, Value::Short(settings)),
+                Entry(0x0004, Value::Short(shot_info)),
+                Entry(0x000C, Value::Long(vec![12345678])),
+                Entry(0x0026, Value::Short(af_info2)),
+                Entry(0x0095, Value::Ascii("RF24-105mm F4 L IS USM")),
+                Entry(0x4001, Value::Short(color_data)),
+            ]
+        });
+        let makernote = read(&canon).unwrap();
+        assert_eq!(makernote.drive_mode(), Some(DriveMode::Continuous));
+        assert_eq!(makernote.focus_mode(), Some(FocusMode::Continuous));
+        assert_eq!(makernote.lens_id(), Some(61182));
+        let Makernote::Canon(canon) = makernote else { panic!("not a Canon makernote") };
+        assert_eq!(canon.serial_number, Some(12345678));
+        assert_eq!(canon.lens_model.as_deref(), Some("RF24-105mm F4 L IS USM"));
+        let settings = canon.camera_settings.unwrap();
+        assert_eq!(settings.focal_range, Some((24.0, 105.0)));
+        let (widest, narrowest) = settings.aperture_range.unwrap();
+        assert!((widest - 4.0).abs() < 1e-9 && (narrowest - 22.0).abs() < 0.1);
+        let shot_info = canon.shot_info.unwrap();
+        assert_eq!(shot_info.camera_temperature, Some(23));
+        assert!((shot_info.exposure_compensation.unwrap() + 1.0 / 3.0).abs() < 1e-9);
+        let color_data = canon.color_data.unwrap();
+        assert_eq!(color_data.version, 4);
+        let as_shot = color_data.as_shot.unwrap();
+        assert_eq!(as_shot.color_temperature, Some(5200));
+        assert_eq!(as_shot.multipliers().unwrap()[0], 2000.0 / 1024.0);
+        assert_eq!(color_data.presets.len(), 1);
+        assert_eq!(color_data.presets[0].0, WhiteBalancePreset::Daylight);
+        assert_eq!(color_data.presets[0].1.rggb, [2100, 1024, 1024, 1400]);
+        let af = canon.af_info.unwrap();
+        assert_eq!((af.image_width, af.image_height, af.points.len()), (6000, 4000, 2));
+        assert_eq!((af.points[0].x, af.points[0].in_focus, af.points[0].selected), (-100, false, true));
+        assert_eq!((af.points[1].y, af.points[1].in_focus, af.points[1].selected), (50, true, false));
+
+        // Nikon's LensData is encrypted with the serial number and shutter count
+        let mut lens_data = b"0204".to_vec();
+        lens_data.resize(0x21, 0);
+        lens_data[0x0c..0x13].copy_from_slice(&[0xa5, 0x48, 0x36, 0x5b, 0x30, 0x30, 0x02]);
+        nikon::crypt(&mut lens_data[4..], 3012345, 20341);
+        let mut nikon_note = b"Nikon\0\x02\x10\0\0".to_vec();
+        nikon_note.extend(TiffBuilder::new(true).build(vec![
+            Entry(0x0001, Value::Undefined(b"0211".to_vec())),
+            Entry(0x0002, Value::Short(vec![0, 400])),
+            Entry(0x0007, Value::Ascii("AF-C  ")),
+            Entry(0x000C, Value::Rational(vec![(21, 10), (15, 10), (1, 1), (1, 1)])),
+            Entry(0x001D, Value::Ascii("3012345")),
+            Entry(0x0084, Value::Rational(vec![(24, 1), (120, 1), (4, 1), (4, 1)])),
+            Entry(0x0089, Value::Short(vec![0x01])),
+            Entry(0x0098, Value::Undefined(lens_data)),
+            Entry(0x00A7, Value::Long(vec![20341])),
+        ]));
+        let nef = TiffBuilder::new(false).build(vec![
+            Entry(0x010F, Value::Ascii("NIKON CORPORATION")),
+            Entry(0x0110, Value::Ascii("NIKON D850")),
+            Entry(0x8769, Value::Ifd(vec![Entry(0x927C, Value::Undefined(nikon_note))])),
+        ]);
+        let makernote = read(&nef).unwrap();
+        assert_eq!(makernote.shutter_count(), Some(20341));
+        assert_eq!(makernote.drive_mode(), Some(DriveMode::Continuous));
+        assert_eq!(makernote.focus_mode(), Some(FocusMode::Continuous));
+        assert_eq!(makernote.lens_id(), Some(0xa5));
+        let Makernote::Nikon(nikon) = makernote else { panic!("not a Nikon makernote") };
+        assert_eq!(nikon.version.as_deref(), Some("0211"));
+        assert_eq!(nikon.iso, Some(400));
+        assert_eq!(nikon.white_balance_rb, Some([2.1, 1.5]));
+        assert_eq!(nikon.lens.unwrap().max_focal_length, 120.0);
+        let lens_data = nikon.lens_data.unwrap();
+        assert_eq!(lens_data.version, "0204");
+        assert_eq!(lens_data.lens_f_stops, Some(6.0));
+        assert!((lens_data.min_focal_length.unwrap() - 23.8).abs() < 0.1);
+        assert!((lens_data.max_focal_length.unwrap() - 69.6).abs() < 0.5);
+        assert_eq!(lens_data.max_aperture_at_min_focal, Some(4.0));
+        assert_eq!(lens_data.mcu_version, Some(2));
+
+        // Sony's 0x9400 block selects the layout of the 0x9050 block
+        let sony = tiff_relative_makernote("SONY", b"SONY DSC \0\0\0", || {
+            let mut tag9400 = vec![0u8; 0x40];
+            tag9400[0] = 0x23;
+            tag9400[0x0a..0x0e].copy_from_slice(&17u32.to_le_bytes());
+            tag9400[0x12..0x16].copy_from_slice(&4u32.to_le_bytes());
+            tag9400[0x1a..0x1e].copy_from_slice(&4u32.to_le_bytes());
+            let mut tag9050 = vec![0u8; 0x100];
+            tag9050[0x3a..0x3e].copy_from_slice(&0xAB00_3039u32.to_le_bytes());
+            vec![
+                Entry(0x201B, Value::Byte(vec![3])),
+                Entry(0x9050, Value::Undefined(sony::encipher(&tag9050))),
+                Entry(0x9400, Value::Undefined(sony::encipher(&tag9400))),
+                Entry(0xB020, Value::Ascii("Standard")),
+                Entry(0xB027, Value::Long(vec![65535])),
+                Entry(0xB049, Value::Short(vec![2])),
+            ]
+        });
+        let makernote = read(&sony).unwrap();
+        assert_eq!(makernote.shutter_count(), Some(12345));
+        assert_eq!(makernote.drive_mode(), Some(DriveMode::Continuous));
+        assert_eq!(makernote.focus_mode(), Some(FocusMode::Continuous));
+        let Makernote::Sony(sony) = makernote else { panic!("not a Sony makernote") };
+        assert_eq!(sony.creative_style.as_deref(), Some("Standard"));
+        let tag9400 = sony.tag9400.unwrap();
+        assert_eq!(tag9400.sequence_image_number, Some(5));
+        assert_eq!(tag9400.shot_number_since_power_up, Some(17));
+
+        let leica = tiff_relative_makernote("Leica Camera AG", b"", || vec![Entry(0x0001, Value::Long(vec![1]))]);
+        assert_eq!(read(&leica), None);
+    }
+
+    #[test]
+    fn test_nikon_makernote_types_and_key() {
+        use crate::exif::{Exif, ExifValue, Rational};
+        use crate::makernote::{nikon, Makernote};
+
+        let read = |tiff: &[u8]| Makernote::read(tiff, &Exif::read(tiff));
+
+        // Type 1: the IFD follows the signature and its offsets count from
+        // the file's TIFF header. 0x0007 is the white balance here, not the
+        // focus mode of later types.
+        let coolpix = tiff_relative_makernote("NIKON", b"Nikon\0\x01\0", || vec![
+            Entry(0x0003, Value::Short(vec![2])),
+            Entry(0x0007, Value::Short(vec![1])),
+            Entry(0x000A, Value::Rational(vec![(0, 100)])),
+        ]);
+        let Some(Makernote::Nikon(nikon)) = read(&coolpix) else { panic!("not a Nikon makernote") };
+        let ids: Vec<_> = nikon.tags.iter().map(|tag| tag.id).collect();
+        assert_eq!(ids, [0x0003, 0x0007, 0x000A]);
+        assert_eq!(nikon.tags[2].value, ExifValue::Rational(vec![Rational::new(0, 100)]));
+        assert_eq!((nikon.focus_mode(), nikon.version), (None, None));
+
+        // LensData decrypts only with the serial number and shutter count it was written with
+        let nef = |serial: &'static str, shutter_count: u32| {
+            let mut lens_data = b"0204".to_vec();
+            lens_data.resize(0x21, 0);
+            lens_data[0x0c..0x13].copy_from_slice(&[0xa5, 0x48, 0x36, 0x5b, 0x30, 0x30, 0x02]);
+            nikon::crypt(&mut lens_data[4..], 3012345, 20341);
+            let mut note = b"Nikon\0\x02\x10\0\0".to_vec();
+            note.extend(TiffBuilder::new(true).build(vec![
+                Entry(0x001D, Value::Ascii(serial)),
+                Entry(0x0098, Value::Undefined(lens_data)),
+                Entry(0x00A7, Value::Long(vec![shutter_count])),
+            ]));
+            TiffBuilder::new(false).build(vec![
+                Entry(0x010F, Value::Ascii("NIKON CORPORATION")),
+                Entry(0x0110, Value::Ascii("NIKON D850")),
+                Entry(0x8769, Value::Ifd(vec![Entry(0x927C, Value::Undefined(note))])),
+            ])
+        };
+        let lens_id = |tiff: &[u8]| read(tiff).unwrap().lens_id();
+        assert_eq!(lens_id(&nef("3012345", 20341)), Some(0xa5));
+        assert_ne!(lens_id(&nef("3012346", 20341)), Some(0xa5));
+        assert_ne!(lens_id(&nef("3012345", 20342)), Some(0xa5));
+    }
+
+    #[test]
+    fn test_sony_9050_layouts() {
+        use crate::exif::Exif;
+        use crate::makernote::{sony, Makernote};
+
+        // (0x9400 version, ShutterCount position in 0x9050, fields of 0x9400
+        // as sequence image, sequence file and shots since power-up)
+        let layouts = [
+            (0x0a, Some(0x32), [0x08, 0x0c, 0x1a]),
+            (0x0c, Some(0x32), [0x08, 0x0c, 0]),
+            (0x23, Some(0x3a), [0x12, 0x1a, 0x0a]),
+            (0x01, None, [0, 0, 0]),
+        ];
+        for (version, count_at, fields) in layouts {
+            let tiff = tiff_relative_makernote("SONY", b"SONY DSC \0\0\0", || {
+                let mut tag9400 = vec![0u8; 0x40];
+                tag9400[0] = version;
+                for (field, value) in fields.iter().zip([4u32, 6, 17]) {
+                    if *field != 0 {
+                        tag9400[*field..*field + 4].copy_from_slice(&value.to_le_bytes());
+                    }
+                }
+                // The other layout's position holds a decoy
+                let mut tag9050 = vec![0u8; 0x100];
+                tag9050[0x32..0x36].copy_from_slice(&777u32.to_le_bytes());
+                tag9050[0x3a..0x3e].copy_from_slice(&777u32.to_le_bytes());
+                if let Some(at) = count_at {
+                    tag9050[at..at + 4].copy_from_slice(&0xAB00_3039u32.to_le_bytes());
+                }
+                vec![
+                    Entry(0x9050, Value::Undefined(sony::encipher(&tag9050))),
+                    Entry(0x9400, Value::Undefined(sony::encipher(&tag9400))),
+                ]
+            });
+            let makernote = Makernote::read(&tiff, &Exif::read(&tiff)).unwrap();
+            let expected = count_at.map(|_| 12345);
+            assert_eq!(makernote.shutter_count(), expected, "0x9400 version {version:#04x}");
+            let Makernote::Sony(sony) = makernote else { panic!("not a Sony makernote") };
+            let tag9400 = sony.tag9400.unwrap();
+            assert_eq!(tag9400.version, version);
+            let present = |at: usize, value: u32| (at != 0).then_some(value);
+            assert_eq!(tag9400.sequence_image_number, present(fields[0], 5));
+            assert_eq!(tag9400.sequence_file_number, present(fields[1], 7));
+            assert_eq!(tag9400.shot_number_since_power_up, present(fields[2], 17));
+            assert_eq!(sony.tag9050.unwrap().data[0x32..0x36], if count_at == Some(0x32) {
+                0xAB00_3039u32.to_le_bytes()
+            } else {
+                777u32.to_le_bytes()
+            });
+        }
+    }
+
+    #[test]
+    fn test_more_vendor_makernotes() {
+        use crate::exif::Exif;
+        use crate::makernote::fujifilm::FilmSimulation;
+        use crate::makernote::{DriveMode, FocusMode, Makernote, Stabilization};
+
+        let read = |tiff: &[u8]| Makernote::read(tiff, &Exif::read(tiff)).unwrap();
+        // Makernotes that count offsets from their own first byte
+        let note = |header: &[u8], big_endian: bool, entries: Vec<Entry>| {
+            let mut note = header.to_vec();
+            note.extend(ifd_block(big_endian, header.len() as u32, entries));
+            note
+        };
+
+        let mut fuji_header = b"FUJIFILM".to_vec();
+        fuji_header.extend(12u32.to_le_bytes());
+        let fuji = read(&with_makernote("FUJIFILM", note(&fuji_header, false, vec![
+            Entry(0x0010, Value::Ascii("FPX20123456")),
+            Entry(0x102D, Value::Long(vec![0x2000])),
+            Entry(0x1103, Value::Long(vec![8 << 24 | 2])),
+            Entry(0x1401, Value::Short(vec![0x600])),
+            Entry(0x1402, Value::Short(vec![0x100])),
+            Entry(0x1403, Value::Short(vec![200])),
+            Entry(0x1422, Value::Short(vec![2, 1, 0])),
+        ])));
+        assert_eq!(fuji.focus_mode(), Some(FocusMode::Continuous));
+        assert_eq!(fuji.drive_mode(), Some(DriveMode::ContinuousHigh));
+        assert_eq!(fuji.stabilization(), Some(Stabilization::Body));
+        let Makernote::Fujifilm(fuji) = fuji else { panic!("not a Fujifilm makernote") };
+        assert_eq!(fuji.film_simulation, Some(FilmSimulation::ClassicChrome));
+        assert_eq!((fuji.dynamic_range, fuji.dynamic_range_auto, fuji.drive_speed), (Some(200), Some(false), Some(8)));
+        assert_eq!(fuji.internal_serial_number.as_deref(), Some("FPX20123456"));
+        // Black and white simulations are a saturation setting
+        let acros = read(&with_makernote("FUJIFILM", note(&fuji_header, false, vec![
+            Entry(0x1003, Value::Short(vec![0x501])),
+            Entry(0x1401, Value::Short(vec![0])),
+        ])));
+        assert!(matches!(acros, Makernote::Fujifilm(f) if f.film_simulation == Some(FilmSimulation::Acros)));
+
+        let panasonic = read(&tiff_relative_makernote("Panasonic", b"Panasonic\0\0\0", || {
+            vec![
+                Entry(0x0007, Value::Short(vec![7])),
+                Entry(0x001A, Value::Short(vec![6])),
+                Entry(0x002A, Value::Short(vec![0])),
+                Entry(0x002E, Value::Short(vec![3])),
+                Entry(0x0051, Value::Ascii("LUMIX G VARIO 12-60/F3.5-5.6")),
+                Entry(0x0079, Value::Short(vec![2])),
+            ]
+        }));
+        assert_eq!(panasonic.focus_mode(), Some(FocusMode::Continuous));
+        assert_eq!(panasonic.drive_mode(), Some(DriveMode::SelfTimer));
+        assert_eq!(panasonic.stabilization(), Some(Stabilization::Body));
+        let Makernote::Panasonic(panasonic) = panasonic else { panic!("not a Panasonic makernote") };
+        assert_eq!(panasonic.lens_type.as_deref(), Some("LUMIX G VARIO 12-60/F3.5-5.6"));
+        assert_eq!(panasonic.intelligent_d_range, Some(2));
+
+        // A big-endian note inside a little-endian file
+        let olympus = read(&with_makernote("OM Digital Solutions", note(b"OLYMPUS\0MM\x03\0", true, vec![
+            Entry(0x0000, Value::Undefined(b"0100".to_vec())),
+            Entry(0x2010, Value::Ifd(vec![
+                Entry(0x0101, Value::Ascii("BHR123456")),
+                Entry(0x0201, Value::Byte(vec![0, 0, 0x21, 0x10, 0, 0])),
+                Entry(0x0203, Value::Ascii("M.Zuiko Digital ED 12-40mm F2.8 Pro")),
+            ])),
+            Entry(0x2020, Value::Ifd(vec![
+                Entry(0x0301, Value::Short(vec![2, 0])),
+                Entry(0x0520, Value::Short(vec![2])),
+                Entry(0x0600, Value::Short(vec![1, 0, 0])),
+                Entry(0x0604, Value::Long(vec![1])),
+            ])),
+        ])));
+        assert_eq!(olympus.lens_id(), Some(0x002110));
+        assert_eq!(olympus.focus_mode(), Some(FocusMode::Continuous));
+        assert_eq!(olympus.drive_mode(), Some(DriveMode::Continuous));
+        assert_eq!(olympus.stabilization(), Some(Stabilization::Body));
+        let Makernote::Olympus(olympus) = olympus else { panic!("not an Olympus makernote") };
+        assert_eq!(olympus.serial_number.as_deref(), Some("BHR123456"));
+        assert_eq!(olympus.lens_model.as_deref(), Some("M.Zuiko Digital ED 12-40mm F2.8 Pro"));
+        assert_eq!(olympus.picture_mode, Some(2));
+
+        // "AOC\0" notes count from the TIFF header, "PENTAX \0" ones from their own start
+        let pentax = read(&tiff_relative_makernote("PENTAX", b"AOC\0II", || {
+            vec![
+                Entry(0x000D, Value::Short(vec![16])),
+                Entry(0x0034, Value::Byte(vec![0, 2, 0, 0])),
+                Entry(0x003F, Value::Byte(vec![8, 0x12])),
+                Entry(0x0047, Value::Byte(vec![0xfb])),
+                Entry(0x005C, Value::Byte(vec![0, 1, 0, 0])),
+                Entry(0x0069, Value::Byte(vec![1, 0, 0, 0])),
+            ]
+        }));
+        assert_eq!(pentax.lens_id(), Some(0x0812));
+        assert_eq!(pentax.focus_mode(), Some(FocusMode::Single));
+        assert_eq!(pentax.drive_mode(), Some(DriveMode::SelfTimer));
+        assert_eq!(pentax.stabilization(), Some(Stabilization::Body));
+        let Makernote::Pentax(pentax) = pentax else { panic!("not a Pentax makernote") };
+        assert_eq!((pentax.camera_temperature, pentax.dynamic_range_expansion), (Some(-5), Some(true)));
+        let ricoh = read(&with_makernote("RICOH IMAGING COMPANY, LTD.", note(b"PENTAX \0MM", true, vec![
+            Entry(0x0005, Value::Long(vec![0x13010])),
+            Entry(0x000D, Value::Short(vec![3])),
+        ])));
+        assert_eq!(ricoh.focus_mode(), Some(FocusMode::Manual));
+        assert!(matches!(ricoh, Makernote::Pentax(p) if p.model_id == Some(0x13010)));
+    }
+
+    #[test]
+    fn test_lens_identification() {
+        use crate::exif::Exif;
+        use crate::lens::{identify, identify_file, LensConfidence, LensHints, LensTable};
+        use crate::makernote::Makernote;
+
+        // Canon shares 137 between its 85mm f/1.2L and a dozen third-party zooms
+        let portrait = LensHints { focal_range: Some((85.0, 85.0)), max_aperture: Some(1.2), ..Default::default() };
+        let found = identify(LensTable::Canon, 137, &portrait).unwrap();
+        assert_eq!((found.name.as_str(), found.confidence), ("Canon EF 85mm f/1.2L USM", LensConfidence::Matched));
+        let zoom = LensHints { focal_range: Some((24.0, 70.0)), max_aperture: Some(2.8), ..Default::default() };
+        assert_eq!(identify(LensTable::Canon, 137, &zoom).unwrap().name, "Sigma 24-70mm f/2.8 IF EX DG HSM");
+        let unknown = identify(LensTable::Canon, 137, &LensHints::default()).unwrap();
+        assert_eq!(unknown.confidence, LensConfidence::Ambiguous);
+        assert_eq!(unknown.alternatives.len(), 15);
+        assert!(identify(LensTable::Canon, 137, &LensHints { focal_range: Some((300.0, 300.0)), ..Default::default() }).is_none());
+        let exact = identify(LensTable::Canon, 237, &LensHints::default()).unwrap();
+        assert_eq!((exact.name.as_str(), exact.confidence), ("Canon EF 24-105mm f/4L IS USM", LensConfidence::Exact));
+        // Bodies report the focal range through a teleconverter
+        let extended = LensHints { focal_range: Some((140.0, 560.0)), ..Default::default() };
+        assert_eq!(identify(LensTable::Canon, 181, &extended).unwrap().name, "Canon EF 100-400mm f/4.5-5.6L IS USM + 1.4x");
+        let pentax = identify(LensTable::Pentax, 0x07F2, &LensHints { focal_length: Some(35.0), ..Default::default() }).unwrap();
+        assert_eq!(pentax.name, "smc PENTAX-DA* 16-50mm F2.8 ED AL [IF] SDM (SDM unused)");
+
+        // An ID wins over the name only when it settles the lens
+        let canon = |lens_type: u16, model: Option<&'static str>| {
+            tiff_relative_makernote("Canon", b"", move || {
+                let mut settings = vec![0u16; 46];
+                (settings[22], settings[23], settings[24], settings[25]) = (lens_type, 85, 85, 1);
+                settings[26] = 0x11;
+                let mut entries = vec![Entry(0x0001, Value::Short(settings))];
+                entries.extend(model.map(|model| Entry(0x0095, Value::Ascii(model))));
+                entries
+            })
+        };
+        let lens = |tiff: &[u8]| {
+            let exif = Exif::read(tiff);
+            identify_file(Makernote::read(tiff, &exif).as_ref(), &exif, None)
+        };
+        let matched = lens(&canon(137, Some("EF85mm f/1.2L USM"))).unwrap();
+        assert_eq!((matched.name.as_str(), matched.confidence), ("Canon EF 85mm f/1.2L USM", LensConfidence::Matched));
+        let reported = lens(&canon(61182, Some("RF85mm F1.2 L USM"))).unwrap();
+        assert_eq!((reported.name.as_str(), reported.confidence), ("RF85mm F1.2 L USM", LensConfidence::Reported));
+        assert_eq!(lens(&canon(61182, None)), None);
+    }
+
+    #[test]
+    fn test_cinema_dng_sequence() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let dir = tempfile::tempdir().unwrap();
+        // 01:02:03:04 and 01:02:03:05 in SMPTE BCD, drop-frame flagged
+        for (name, frames) in [("clip_000001.dng", 0x45), ("clip_000000.dng", 0x44)] {
+            let dng = synthetic_compressed_dng(1, vec![
+                Entry(0xC763, Value::Byte(vec![frames, 0x03, 0x02, 0x01, 0, 0, 0, 0])),
+                Entry(0xC764, Value::Rational(vec![(30000, 1001)])),
+            ]);
+            std::fs::write(dir.path().join(name), dng).unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), b"not a frame").unwrap();
+
+        let mut sequence = sequence::RawSequence::open(&zrraw_lib, dir.path()).unwrap();
+        assert_eq!(sequence.len(), 2);
+        let frames: Vec<_> = sequence.frames().map(Result::unwrap).collect();
+        let tc = |frames| Timecode { hours: 1, minutes: 2, seconds: 3, frames, drop_frame: true };
+        assert_eq!(frames[0].metadata.timecode, Some(tc(4)));
+        assert_eq!(frames[1].metadata.timecode, Some(tc(5)));
+        assert!((frames[0].metadata.frame_rate - 29.97).abs() < 1e-3);
+        assert_eq!((frames[1].sensor.width, frames[1].sensor.height), (4, 2));
+
+        let result = frames[0].process(&zrraw_lib, ProcessingParams::default()).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (4, 2));
+        assert!(zrraw_lib.extract_metadata(&synthetic_nef()).unwrap().timecode.is_none());
+    }
+
+    #[test]
+    fn test_mlv_sequence() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let frame_samples = |frame: u16| -> Vec<u16> { (0..8).map(|i| 2048 + (i + frame * 8) * 700).collect() };
+        let vidf = |number: u32| {
+            let mut body = number.to_le_bytes().to_vec();
+            body.extend([0; 8]); // crop and pan positions
+            body.extend(4u32.to_le_bytes()); // frame space
+            body.extend([0; 4]);
+            // Magic Lantern stores the bit stream as little-endian words
+            let mut packed = pack_msb(&frame_samples(number as u16), 14);
+            packed.chunks_exact_mut(2).for_each(|w| w.swap(0, 1));
+            body.extend(packed);
+            body
+        };
+
+        let mut raw_info = vec![0u8; 160];
+        raw_info[24..28].copy_from_slice(&14u32.to_le_bytes());
+        raw_info[28..32].copy_from_slice(&2048u32.to_le_bytes());
+        raw_info[32..36].copy_from_slice(&16383u32.to_le_bytes());
+        raw_info[76..80].copy_from_slice(&0x02010100u32.to_le_bytes());
+        for i in 0..9 {
+            let num: u32 = if i % 4 == 0 { 10000 } else { 0 };
+            raw_info[84 + i * 8..88 + i * 8].copy_from_slice(&num.to_le_bytes());
+            raw_info[88 + i * 8..92 + i * 8].copy_from_slice(&10000u32.to_le_bytes());
+        }
+        let mut rawi = [4u16.to_le_bytes(), 2u16.to_le_bytes()].concat();
+        rawi.extend(raw_info);
+
+        let mut idnt = vec![0u8; 68];
+        idnt[..21].copy_from_slice(b"Canon EOS 5D Mark III");
+        let mut lens = vec![0u8; 80];
+        lens[0..2].copy_from_slice(&50u16.to_le_bytes());
+        lens[4..6].copy_from_slice(&280u16.to_le_bytes());
+        lens[16..32].copy_from_slice(b"EF50mm f/1.8 STM");
+        let mut expo = vec![0u8; 24];
+        expo[4..8].copy_from_slice(&800u32.to_le_bytes());
+        expo[16..24].copy_from_slice(&20000u64.to_le_bytes());
+        let rtci: Vec<u8> = [30u16, 20, 10].iter().flat_map(|v| v.to_le_bytes()).chain([0; 22]).collect();
+
+        let mlv = mlv_file(2, (25, 1), &[
+            (b"RAWI", rawi),
+            (b"IDNT", idnt),
+            (b"LENS", lens),
+            (b"RTCI", rtci),
+            (b"EXPO", expo),
+            (b"VIDF", vidf(1)),
+            (b"NULL", vec![0; 8]),
+            (b"VIDF", vidf(0)),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("M18-1234.MLV");
+        std::fs::write(&path, mlv).unwrap();
+
+        let mut sequence = sequence::RawSequence::open(&zrraw_lib, &path).unwrap();
+        assert_eq!(sequence.len(), 2);
+        for (index, frame) in sequence.frames().enumerate() {
+            let frame = frame.unwrap();
+            assert_eq!(frame.sensor.data, frame_samples(index as u16));
+            assert_eq!(frame.sensor.cfa.colors, vec![0, 1, 1, 2]);
+            let tc = Timecode { hours: 10, minutes: 20, seconds: 30, frames: index as u8, drop_frame: false };
+            assert_eq!(frame.metadata.timecode, Some(tc));
+        }
+
+        let frame = sequence.frame(1).unwrap();
+        let metadata = &frame.metadata;
+        assert!(matches!(metadata.format, RawFormat::MagicLantern(MagicLanternFormat::MLV)));
+        assert_eq!((metadata.make.as_str(), metadata.model.as_str()), ("Canon", "Canon EOS 5D Mark III"));
+        assert_eq!(metadata.lens, "EF50mm f/1.8 STM");
+        assert_eq!(metadata.iso, 800);
+        assert_eq!((metadata.shutter_speed, metadata.aperture), (ExposureTime::new(1, 50), FNumber::new(280, 100)));
+        assert_eq!((metadata.black_level[0], metadata.white_level[0]), (2048.0, 16383));
+        assert_eq!(metadata.frame_rate, 25.0);
+        assert_eq!(metadata.color_matrix, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+
+        let result = frame.process(&zrraw_lib, ProcessingParams::default()).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (4, 2));
+        assert!(matches!(result.metadata.format, RawFormat::MagicLantern(_)));
+        assert!(sequence.frame(2).is_err());
+    }
+
+    #[test]
+    fn test_dng_writer_round_trip() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let nef = synthetic_nef();
+        let sensor = zrraw_lib.extract_sensor_data(&nef).unwrap();
+        let metadata = zrraw_lib.extract_metadata(&nef).unwrap();
+
+        let dng = dng::DngWriter::new(&sensor, &metadata).to_bytes().unwrap();
+        assert!(matches!(zrraw_lib.detect_format(&dng).unwrap(), RawFormat::Adobe(AdobeFormat::DNG)));
+
+        let read_back = zrraw_lib.extract_sensor_data(&dng).unwrap();
+        assert_eq!((read_back.width, read_back.height), (sensor.width, sensor.height));
+        assert_eq!(read_back.cfa, sensor.cfa);
+        assert_eq!(read_back.data, sensor.data);
+
+        let written = zrraw_lib.extract_metadata(&dng).unwrap();
+        assert_eq!(written.make, metadata.make);
+        assert_eq!(written.model, metadata.model);
+        assert_eq!(written.iso, metadata.iso);
+        assert_eq!(written.shutter_speed, metadata.shutter_speed);
+        assert_eq!(written.black_level, metadata.black_level);
+        assert_eq!(written.white_level, metadata.white_level);
+        for (a, b) in written.white_balance.iter().zip(metadata.white_balance) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    /// Metadata for sensor data the DNG writer tests make up
+    fn writer_metadata(width: u32, height: u32) -> RawMetadata {
+        RawMetadata {
+            format: RawFormat::Unknown,
+            width,
+            height,
+            orientation: Orientation::Normal,
+            make: "Test".to_string(),
+            model: "Sensor".to_string(),
+            lens: String::new(),
+            iso: 200,
+            shutter_speed: ExposureTime::new(1, 100),
+            aperture: FNumber::new(28, 10),
+            focal_length: 35.0,
+            color_matrix: [0.0; 9],
+            white_balance: [2.0, 1.0, 1.25],
+            black_level: [512.0; 4],
+            white_level: [16383; 4],
+            baseline_exposure: 0.0,
+            frame_rate: 0.0,
+            timecode: None,
+            extended: Default::default(),
+            exif: Default::default(),
+            makernote: None,
+            xmp: None,
+        }
+    }
+
+    #[test]
+    fn test_dng_writer_carries_exif() {
+        use crate::exif::{Directory, Exif, ExifValue, Rational, SRational};
+        let source = TiffBuilder::new(true).build(vec![
+            Entry(0x010F, Value::Ascii("Test")),
+            Entry(0x8769, Value::Ifd(vec![
+                Entry(0x829A, Value::Rational(vec![(1, 8)])),
+                Entry(0x9003, Value::Ascii("2024:05:06 07:08:09")),
+                Entry(0x9011, Value::Ascii("+02:00")),
+                Entry(0x9204, Value::SRational(vec![(-2, 3)])),
+                Entry(0x927C, Value::Undefined(b"private offsets".to_vec())),
+                Entry(0xA005, Value::Ifd(vec![Entry(0x0001, Value::Ascii("R98"))])),
+                Entry(0xA431, Value::Ascii("123456")),
+                Entry(0xA434, Value::Ascii("50mm F1.8")),
+            ])),
+            Entry(0x8825, Value::Ifd(vec![
+                Entry(0x0001, Value::Ascii("N")),
+                Entry(0x0002, Value::Rational(vec![(48, 1), (8, 1), (30, 1)])),
+                Entry(0x0003, Value::Ascii("E")),
+                Entry(0x0004, Value::Rational(vec![(11, 1), (34, 1), (0, 1)])),
+            ])),
+        ]);
+        let sensor = SensorData {
+            width: 4,
+            height: 4,
+            samples_per_pixel: 1,
+            bits_per_sample: 12,
+            cfa: CfaPattern { width: 2, height: 2, colors: vec![0, 1, 1, 2] },
+            data: vec![1000; 16],
+        };
+        let mut metadata = writer_metadata(4, 4);
+        metadata.exif = Exif::read(&source);
+
+        let dng = dng::DngWriter::new(&sensor, &metadata).to_bytes().unwrap();
+        let written = Exif::read(&dng);
+        let date = written.date_time_original().unwrap();
+        assert_eq!(Some(date), metadata.exif().date_time_original());
+        assert_eq!(date.offset_minutes, Some(120));
+        assert_eq!(written.lens_model(), Some("50mm F1.8"));
+        assert_eq!(written.body_serial_number(), Some("123456"));
+        let bias = &written.get(Directory::Exif, 0x9204).unwrap().value;
+        assert_eq!(bias, &ExifValue::SRational(vec![SRational { num: -2, den: 3 }]));
+        assert!(written.gps_position().is_some());
+        assert_eq!(written.gps_position(), metadata.exif().gps_position());
+        // The decoded settings replace the file's, and offsets that would
+        // break are not copied
+        assert_eq!(written.exposure_time(), Some(Rational::new(1, 100)));
+        assert!(written.get(Directory::Exif, 0x927C).is_none());
+        assert!(written.get(Directory::Interop, 0x0001).is_none());
+    }
+
+    #[test]
+    fn test_dng_writer_lossless_jpeg_round_trip() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+
+        // Noisy 14-bit data spanning several partial tiles
+        let (width, height) = (300, 260);
+        let mut seed = 0x2545_F491u32;
+        let data = (0..width * height)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 18) as u16
+            })
+            .collect();
+        let sensor = SensorData {
+            width,
+            height,
+            samples_per_pixel: 1,
+            bits_per_sample: 14,
+            cfa: CfaPattern { width: 2, height: 2, colors: vec![1, 0, 2, 1] },
+            data,
+        };
+        let metadata = writer_metadata(width, height);
+        let preview = DynamicImage::new_rgb8(width, height);
+
+        let dng = dng::DngWriter::new(&sensor, &metadata)
+            .compression(dng::DngCompression::LosslessJpeg)
+            .preview(&preview)
+            .to_bytes()
+            .unwrap();
+        let digest = dng::new_raw_image_digest(&sensor);
+        assert!(dng.windows(16).any(|w| w == digest));
+
+        let read_back = zrraw_lib.extract_sensor_data(&dng).unwrap();
+        assert_eq!((read_back.width, read_back.height), (width, height));
+        assert_eq!(read_back.cfa, sensor.cfa);
+        assert!(read_back.data == sensor.data);
+
+        let written = zrraw_lib.extract_metadata(&dng).unwrap();
+        assert_eq!(written.model, "Sensor");
+        assert_eq!(written.black_level, [512.0; 4]);
+        assert_eq!(written.white_level, [16383; 4]);
+    }
+
+    #[test]
+    fn test_raf_metadata() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let raf = synthetic_uncompressed_raf();
+
+        let format = zrraw_lib.detect_format(&raf).unwrap();
+        assert!(matches!(format, RawFormat::Fujifilm(FujifilmFormat::RAF)));
+
+        let metadata = zrraw_lib.extract_metadata(&raf).unwrap();
+        assert_eq!(metadata.make, "FUJIFILM");
+        assert_eq!(metadata.model, "X-T5");
+        assert_eq!((metadata.width, metadata.height), (12, 12));
+        assert_eq!(metadata.iso, 320);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.black_level, [1024.0; 4]);
+        assert_eq!(metadata.white_level, [16383; 4]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&raf).unwrap();
+        assert_eq!(sensor.cfa, CfaPattern { width: 6, height: 6, colors: XTRANS.to_vec() });
+        assert_eq!(sensor.data[13], 1024 + 13 * 50);
+    }
+
+    #[test]
+    fn test_raf_markesteijn_process_applies_crop() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let params = ProcessingParams { demosaic_algorithm: DemosaicAlgorithm::Markesteijn, ..Default::default() };
+        let result = zrraw_lib.process_file(&synthetic_uncompressed_raf(), params).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (8, 8));
+    }
+
+    #[test]
+    fn test_raf_lossless_compressed() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let raf = synthetic_raf(768, 12, fuji_compressed_zeros(768, 12, true));
+
+        let sensor = zrraw_lib.extract_sensor_data(&raf).unwrap();
+        assert_eq!((sensor.width, sensor.height), (768, 12));
+        assert_eq!(sensor.data.len(), 768 * 12);
+        assert!(sensor.data.iter().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn test_orf_metadata() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let samples: Vec<u16> = (0..144).map(|i| 300 + i * 20).collect();
+        let orf = synthetic_orf(pack_msb(&samples, 12));
+
+        let format = zrraw_lib.detect_format(&orf).unwrap();
+        assert!(matches!(format, RawFormat::Olympus(OlympusFormat::ORF)));
+
+        let metadata = zrraw_lib.extract_metadata(&orf).unwrap();
+        assert_eq!(metadata.make, "OM Digital Solutions");
+        assert_eq!(metadata.model, "OM-1");
+        assert_eq!(metadata.lens, "M.Zuiko 12-40mm F2.8");
+        assert_eq!(metadata.iso, 800);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.black_level, [250.0, 254.0, 256.0, 260.0]);
+        assert_eq!(metadata.white_level, [4095; 4]);
+        // An identity camera to sRGB matrix comes back as the sRGB to XYZ matrix
+        assert!((metadata.color_matrix[0] - 0.4124564).abs() < 1e-6);
+        assert!((metadata.color_matrix[4] - 0.7151522).abs() < 1e-6);
+
+        let sensor = zrraw_lib.extract_sensor_data(&orf).unwrap();
+        assert_eq!(sensor.data, samples);
+    }
+
+    #[test]
+    fn test_orf_process_applies_crop() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let samples: Vec<u16> = (0..144).map(|i| 300 + i * 20).collect();
+        let orf = synthetic_orf(pack_msb(&samples, 12));
+        let result = zrraw_lib.process_file(&orf, ProcessingParams::default()).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (8, 8));
+    }
+
+    #[test]
+    fn test_orf_compressed() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        // After the 7-byte header, the first two pixels code a residual of 13
+        // and every later one a residual of 0, so predictions carry 13 down
+        // the even rows and 0 down the odd ones
+        let mut data = vec![0u8; 7];
+        data.extend([0x33, 0x33]);
+        data.extend([0u8; 64]);
+        let orf = synthetic_orf(data);
+
+        let sensor = zrraw_lib.extract_sensor_data(&orf).unwrap();
+        assert_eq!((sensor.width, sensor.height), (12, 12));
+        for (i, &v) in sensor.data.iter().enumerate() {
+            assert_eq!(v, if (i / 12) % 2 == 0 { 13 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn test_pef_compressed() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        // The first two pixels of the stream step up by one and every later
+        // difference is 0, so even rows hold 1 and odd rows 0
+        let pef = synthetic_pef(vec![0b1111_0000, 0, 0, 0]);
+
+        let format = zrraw_lib.detect_format(&pef).unwrap();
+        assert!(matches!(format, RawFormat::Pentax(PentaxFormat::PEF)));
+
+        let metadata = zrraw_lib.extract_metadata(&pef).unwrap();
+        assert_eq!(metadata.model, "PENTAX K-3 Mark III");
+        assert_eq!(metadata.iso, 400);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.black_level, [64.0; 4]);
+        assert_eq!(metadata.white_level, [4095; 4]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&pef).unwrap();
+        assert_eq!((sensor.width, sensor.height), (8, 4));
+        for (i, &v) in sensor.data.iter().enumerate() {
+            assert_eq!(v, if (i / 8) % 2 == 0 { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn test_x3f_metadata() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let x3f = synthetic_x3f();
+
+        let format = zrraw_lib.detect_format(&x3f).unwrap();
+        assert!(matches!(format, RawFormat::Sigma(SigmaFormat::X3F)));
+
+        let metadata = zrraw_lib.extract_metadata(&x3f).unwrap();
+        assert_eq!(metadata.make, "SIGMA");
+        assert_eq!(metadata.model, "SIGMA DP2 Merrill");
+        assert_eq!(metadata.iso, 200);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.white_level, [4000; 4]);
+        // An identity colour correction comes back as the sRGB to XYZ matrix
+        assert!((metadata.color_matrix[0] - 0.4124564).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_x3f_planes_are_interleaved() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let sensor = zrraw_lib.extract_sensor_data(&synthetic_x3f()).unwrap();
+        assert_eq!((sensor.width, sensor.height, sensor.samples_per_pixel), (8, 8, 3));
+        assert_eq!(sensor.data.len(), 8 * 8 * 3);
+        // Even columns of even rows continue from the first pixel
+        assert_eq!(sensor.data[0..6], [101, 200, 300, 100, 200, 300]);
+        assert_eq!(sensor.data[(2 * 8 + 2) * 3], 101);
+        assert_eq!(sensor.data[8 * 3], 100);
+
+        let result = zrraw_lib.process_file(&synthetic_x3f(), ProcessingParams::default()).unwrap();
+        assert_eq!((result.image.width(), result.image.height()), (6, 6));
+    }
+
+    #[test]
+    fn test_iiq_black_and_flat_field() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let iiq = synthetic_iiq();
+
+        let format = zrraw_lib.detect_format(&iiq).unwrap();
+        assert!(matches!(format, RawFormat::PhaseOne(PhaseOneFormat::IIQ)));
+
+        let metadata = zrraw_lib.extract_metadata(&iiq).unwrap();
+        assert_eq!(metadata.make, "Phase One");
+        assert_eq!(metadata.model, "IQ4 150MP");
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        // Black is already removed from compressed data
+        assert_eq!(metadata.black_level, [0.0; 4]);
+        assert_eq!(metadata.white_level, [0xFFFC - 10; 4]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&iiq).unwrap();
+        assert_eq!((sensor.width, sensor.height), (4, 2));
+        assert_eq!(sensor.cfa, CfaPattern { width: 2, height: 2, colors: vec![1, 2, 0, 1] });
+        // (100 << 2) - 10 + row and column black, then halved
+        assert_eq!(sensor.data, [195, 195, 196, 198, 196, 196, 197, 200]);
+    }
+
+    #[test]
+    fn test_3fr_and_fff() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let three_fr = synthetic_3fr("X2D 100C 1.0.0");
+
+        let format = zrraw_lib.detect_format(&three_fr).unwrap();
+        assert!(matches!(format, RawFormat::Hasselblad(HasselbladFormat::ThreeFR)));
+        let format = zrraw_lib.detect_format(&synthetic_3fr("Phocus 3.8")).unwrap();
+        assert!(matches!(format, RawFormat::Hasselblad(HasselbladFormat::FFF)));
+
+        let metadata = zrraw_lib.extract_metadata(&three_fr).unwrap();
+        assert_eq!(metadata.model, "X2D 100C");
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&three_fr).unwrap();
+        assert_eq!(sensor.data, [0x8003, 0x8000, 0x8003, 0x8000]);
+    }
+
+    #[test]
+    fn test_mos_packets() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let mos = synthetic_mos();
+
+        let format = zrraw_lib.detect_format(&mos).unwrap();
+        assert!(matches!(format, RawFormat::Leaf(LeafFormat::MOS)));
+
+        let metadata = zrraw_lib.extract_metadata(&mos).unwrap();
+        assert_eq!(metadata.model, "Aptus-II 12");
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 0.5]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&mos).unwrap();
+        assert_eq!(sensor.cfa, CfaPattern { width: 2, height: 2, colors: vec![1, 0, 2, 1] });
+        assert_eq!(sensor.data, [100, 200, 300, 400, 500, 600, 700, 800]);
+    }
+
+    #[test]
+    fn test_crw_low_bits() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let crw = synthetic_crw();
+
+        let format = zrraw_lib.detect_format(&crw).unwrap();
+        assert!(matches!(format, RawFormat::Canon(CanonFormat::CRW)));
+
+        let metadata = zrraw_lib.extract_metadata(&crw).unwrap();
+        assert_eq!(metadata.model, "Canon PowerShot Pro1");
+        assert_eq!(metadata.iso, 100);
+        assert!((metadata.aperture.value() - 4.0).abs() < 1e-4);
+        assert_eq!(metadata.shutter_speed.to_string(), "1/8");
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+        assert_eq!(metadata.white_level, [4095; 4]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&crw).unwrap();
+        assert_eq!(sensor.width, 64);
+        assert_eq!(sensor.data[..5], [2068, 2049, 2070, 2051, 2068]);
+    }
+
+    #[test]
+    fn test_mrw_packed() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let mrw = synthetic_mrw();
+
+        let format = zrraw_lib.detect_format(&mrw).unwrap();
+        assert!(matches!(format, RawFormat::Minolta(MinoltaFormat::MRW)));
+
+        let metadata = zrraw_lib.extract_metadata(&mrw).unwrap();
+        assert_eq!(metadata.model, "DYNAX 7D");
+        assert_eq!(metadata.iso, 200);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&mrw).unwrap();
+        assert_eq!(sensor.cfa, CfaPattern { width: 2, height: 2, colors: vec![1, 2, 0, 1] });
+        assert_eq!(sensor.data, [100, 200, 300, 400, 500, 600, 700, 800]);
+    }
+
+    #[test]
+    fn test_kodak_dcr_and_kdc() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let dcr = synthetic_kodak("DCS Pro 14N");
+
+        let format = zrraw_lib.detect_format(&dcr).unwrap();
+        assert!(matches!(format, RawFormat::Kodak(KodakFormat::DCR)));
+        let format = zrraw_lib.detect_format(&synthetic_kodak("EasyShare Z980")).unwrap();
+        assert!(matches!(format, RawFormat::Kodak(KodakFormat::KDC)));
+
+        let metadata = zrraw_lib.extract_metadata(&dcr).unwrap();
+        assert_eq!(metadata.iso, 320);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 0.5]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&dcr).unwrap();
+        assert_eq!(sensor.cfa, CfaPattern { width: 2, height: 2, colors: vec![1, 0, 2, 1] });
+        assert_eq!(sensor.data, [100, 200, 300, 400, 500, 600, 700, 800]);
+    }
+
+    #[test]
+    fn test_srw_makernote_levels() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let srw = synthetic_srw();
+
+        let format = zrraw_lib.detect_format(&srw).unwrap();
+        assert!(matches!(format, RawFormat::Samsung(SamsungFormat::SRW)));
+
+        let metadata = zrraw_lib.extract_metadata(&srw).unwrap();
+        assert_eq!(metadata.black_level, [128.0; 4]);
+        assert_eq!(metadata.white_balance, [2.0, 1.0, 1.5]);
+
+        let sensor = zrraw_lib.extract_sensor_data(&srw).unwrap();
+        assert_eq!(sensor.data, [100, 200, 300, 400, 500, 600, 700, 800]);
+    }
+
+    #[test]
+    fn test_erf_control_bytes() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let erf = synthetic_erf();
+
+        let format = zrraw_lib.detect_format(&erf).unwrap();
+        assert!(matches!(format, RawFormat::Epson(EpsonFormat::ERF)));
+
+        let metadata = zrraw_lib.extract_metadata(&erf).unwrap();
+        assert_eq!(metadata.model, "R-D1");
+        assert!((metadata.white_balance[0] - 240.0 * 508.0 * 1.078 / 65536.0).abs() < 1e-4);
+        assert!((metadata.white_balance[2] - 300.0 * 382.0 * 1.173 / 65536.0).abs() < 1e-4);
+
+        let sensor = zrraw_lib.extract_sensor_data(&erf).unwrap();
+        let row: Vec<u16> = (0..10).map(|v| 100 * v + 50).collect();
+        assert_eq!(sensor.data, row.repeat(2));
+    }
+
+    /// Deterministic pseudo-random samples below 2^precision
+    fn noise(len: usize, precision: u32, seed: u32) -> Vec<u16> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                // A gradient plus noise, so every predictor sees real structure
+                ((i as u32 * 37 + (state & 0xFF)) & ((1 << precision) - 1)) as u16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ljpeg_round_trip() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        for components in 1..=4u32 {
+            for precision in [2, 8, 12, 14, 16] {
+                for predictor in 1..=7u8 {
+                    for restart_interval in [0, 3, 7] {
+                        let frame = ljpeg::Frame { width: 7, height: 5, components, precision };
+                        let samples = noise(7 * 5 * components as usize, precision, predictor as u32 * 31 + precision);
+                        let stream = ljpeg::Encoder::new(&samples, frame)
+                            .predictor(predictor)
+                            .restart_interval(restart_interval)
+                            .encode()
+                            .unwrap();
+                        let image = zrraw_lib.decode_ljpeg(&stream).unwrap();
+                        assert_eq!(image.frame, frame);
+                        assert!(image.samples == samples, "{components} {precision} {predictor} {restart_interval}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ljpeg_encoder_rejects_bad_input() {
+        let frame = ljpeg::Frame { width: 2, height: 1, components: 1, precision: 8 };
+        assert!(ljpeg::Encoder::new(&[1, 256], frame).encode().is_err());
+        assert!(ljpeg::Encoder::new(&[1, 2], frame).predictor(8).encode().is_err());
+    }
+
+    #[test]
+    fn test_ljpeg_decodes_hand_made_stream() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        // 2x2, two 8-bit components, point transform 1, one table where
+        // category 0 is '0' and category 1 is '10'
+        let stream = [
+            0xFF, 0xD8,
+            0xFF, 0xC4, 0x00, 0x15, 0x00, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x01,
+            0xFF, 0xC3, 0x00, 0x0E, 0x08, 0x00, 0x02, 0x00, 0x02, 0x02, 0x01, 0x11, 0x00, 0x02, 0x11, 0x00,
+            0xFF, 0xDA, 0x00, 0x0A, 0x02, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01,
+            0xA0, 0x00, 0xFF, 0xD9,
+        ];
+        let image = zrraw_lib.decode_ljpeg(&stream).unwrap();
+        assert_eq!(image.samples, [130, 128, 130, 128, 130, 128, 130, 128]);
+    }
+
+    #[test]
+    fn test_ljpeg_rejects_damaged_streams() {
+        let zrraw_lib = ZrRaw::new().expect("Failed to load zrraw dynamic library");
+        let frame = ljpeg::Frame { width: 16, height: 8, components: 2, precision: 12 };
+        let samples = noise(16 * 8 * 2, 12, 7);
+        let stream = ljpeg::Encoder::new(&samples, frame).predictor(6).restart_interval(5).encode().unwrap();
+
+        for len in 0..stream.len() {
+            let _ = zrraw_lib.decode_ljpeg(&stream[..len]);
+        }
+        let mut state = 0x9E37_79B9u32;
+        for _ in 0..5000 {
+            let mut damaged = stream.clone();
+            for _ in 0..1 + state % 4 {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let i = state as usize % damaged.len();
+                damaged[i] = (state >> 24) as u8;
+            }
+            let _ = zrraw_lib.decode_ljpeg(&damaged);
+        }
+
+        // A huge frame over a few bytes of data is refused before allocating
+        let mut huge = stream.clone();
+        let sof = huge.windows(2).position(|w| w == [0xFF, 0xC3]).unwrap();
+        huge[sof + 5..sof + 9].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(zrraw_lib.decode_ljpeg(&huge).is_err());
+    }
+
+    #[test]
+    fn test_ljpeg_canon_and_dng_layouts() {
+        // Two slices of 2 columns and a last one of 1, over 2 rows
+        let image = ljpeg::Image {
+            frame: ljpeg::Frame { width: 5, height: 2, components: 1, precision: 8 },
+            samples: (0..10).collect(),
+        };
+        assert_eq!(ljpeg::cr2_unslice(&image, [2, 2, 1]).unwrap(), [0, 1, 4, 5, 8, 2, 3, 6, 7, 9]);
+        assert!(ljpeg::cr2_unslice(&image, [2, 2, 2]).is_err());
+
+        // A 4x2 CFA tile coded as two components of half the width, placed
+        // at the right edge of a 6x3 image
+        let tile = ljpeg::Image {
+            frame: ljpeg::Frame { width: 2, height: 2, components: 2, precision: 8 },
+            samples: (1..=8).collect(),
+        };
+        let mut dest = vec![0u16; 18];
+        ljpeg::place_dng_tile(&tile, 4, 1, &mut dest, 6, 4, 1).unwrap();
+        assert_eq!(dest, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 5, 6]);
+    }
+}
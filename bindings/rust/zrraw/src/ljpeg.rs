@@ -0,0 +1,434 @@
+// bindings/rust/zrraw/src/ljpeg.rs - Lossless JPEG (ITU T.81 process 14) encoder and layouts
+//!
+//! Encodes the single-scan lossless JPEG streams that CR2, DNG, NEF, 3FR,
+//! lossless ARW and others store their sensor data in, and holds the Canon
+//! and DNG conventions for turning decoded samples into an image. Decoding
+//! is done by the native library, through [`crate::ZrRaw::decode_ljpeg`].
+use crate::ZrRawError;
+
+// Markers (the byte following 0xFF)
+const SOI: u8 = 0xD8;
+const EOI: u8 = 0xD9;
+const SOF3: u8 = 0xC3;
+const DHT: u8 = 0xC4;
+const SOS: u8 = 0xDA;
+const DRI: u8 = 0xDD;
+const RST0: u8 = 0xD0;
+
+const MAX_COMPONENTS: usize = 4;
+/// Symbols are difference categories 0..=16
+const SYMBOLS: usize = 17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    /// Components interleaved per pixel, 1 to 4
+    pub components: u32,
+    /// Bits per sample, 2 to 16
+    pub precision: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub frame: Frame,
+    /// Row-major samples, components interleaved (width * height * components)
+    pub samples: Vec<u16>,
+}
+
+/// Lays out a decoded CR2 strip. Canon cuts the sensor into vertical slices
+/// (CR2Slice: `[count, width, last width]`) and codes them one after the
+/// other, each top to bottom; the result is row-major, `count * width +
+/// last width` samples wide.
+pub fn cr2_unslice(image: &Image, slices: [u16; 3]) -> Result<Vec<u16>, ZrRawError> {
+    let [count, width, last] = slices.map(|v| v as usize);
+    let samples = &image.samples;
+    if count == 0 {
+        return Ok(samples.clone());
+    }
+    let raw_width = count * width + last;
+    if raw_width == 0 || width == 0 || !samples.len().is_multiple_of(raw_width) {
+        return Err(ZrRawError::CorruptedData);
+    }
+    let height = samples.len() / raw_width;
+
+    let mut out = vec![0u16; samples.len()];
+    let mut src = samples.iter();
+    for slice in 0..=count {
+        let (x, slice_width) = (slice * width, if slice < count { width } else { last });
+        for row in 0..height {
+            for dest in &mut out[row * raw_width + x..][..slice_width] {
+                *dest = *src.next().ok_or(ZrRawError::CorruptedData)?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Copies a decoded DNG tile to (`x`, `y`) of `dest`, an image `dest_width`
+/// pixels wide with `samples_per_pixel` interleaved samples. The JPEG frame
+/// need not match the tile's shape (CFA tiles are often coded as two
+/// half-width components), so samples are taken in stream order, rows of
+/// `tile_width` pixels. Padding beyond the image edges is dropped.
+pub fn place_dng_tile(
+    image: &Image,
+    tile_width: u32,
+    samples_per_pixel: u32,
+    dest: &mut [u16],
+    dest_width: u32,
+    x: u32,
+    y: u32,
+) -> Result<(), ZrRawError> {
+    let spp = samples_per_pixel as usize;
+    let tile_row = tile_width as usize * spp;
+    let dest_row = dest_width as usize * spp;
+    if tile_row == 0 || dest_row == 0 || !dest.len().is_multiple_of(dest_row) {
+        return Err(ZrRawError::InvalidInput);
+    }
+    let dest_height = dest.len() / dest_row;
+    let (x, y) = (x as usize, y as usize);
+    if x >= dest_width as usize || y >= dest_height {
+        return Ok(());
+    }
+
+    let len = tile_row.min(dest_row - x * spp);
+    for (row, line) in image.samples.chunks_exact(tile_row).enumerate().take(dest_height - y) {
+        dest[(y + row) * dest_row + x * spp..][..len].copy_from_slice(&line[..len]);
+    }
+    Ok(())
+}
+
+/// Lossless JPEG encoder. One Huffman table, optimised for the data, codes
+/// every component.
+pub struct Encoder<'a> {
+    samples: &'a [u16],
+    frame: Frame,
+    predictor: u8,
+    restart_interval: u16,
+}
+
+impl<'a> Encoder<'a> {
+    /// `samples` are row-major with the frame's components interleaved
+    pub fn new(samples: &'a [u16], frame: Frame) -> Self {
+        Encoder { samples, frame, predictor: 1, restart_interval: 0 }
+    }
+
+    /// Predictor 1 to 7 (T.81 table H.1); 1 by default
+    pub fn predictor(mut self, predictor: u8) -> Self {
+        self.predictor = predictor;
+        self
+    }
+
+    /// Pixels between restart markers, 0 (the default) for none
+    pub fn restart_interval(mut self, pixels: u16) -> Self {
+        self.restart_interval = pixels;
+        self
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ZrRawError> {
+        let frame = self.frame;
+        let valid = (1..=MAX_COMPONENTS as u32).contains(&frame.components)
+            && (2..=16).contains(&frame.precision)
+            && (1..=u16::MAX as u32).contains(&frame.width)
+            && (1..=u16::MAX as u32).contains(&frame.height)
+            && (1..=7).contains(&self.predictor)
+            && self.samples.len() == frame.width as usize * frame.height as usize * frame.components as usize
+            && self.samples.iter().all(|&s| (s as u32) < 1 << frame.precision);
+        if !valid {
+            return Err(ZrRawError::InvalidInput);
+        }
+
+        let diffs = self.differences();
+        let mut freq = [0u32; SYMBOLS];
+        for &d in &diffs {
+            freq[category(d) as usize] += 1;
+        }
+        let (bits, values) = huffman_table(&freq);
+        let codes = canonical_codes(&bits, &values);
+
+        let mut out = vec![0xFF, SOI];
+
+        let components = frame.components as u8;
+        let mut header = vec![frame.precision as u8];
+        header.extend_from_slice(&(frame.height as u16).to_be_bytes());
+        header.extend_from_slice(&(frame.width as u16).to_be_bytes());
+        header.push(components);
+        for c in 0..components {
+            header.extend_from_slice(&[c, 0x11, 0]);
+        }
+        segment(&mut out, SOF3, &header);
+
+        let mut table = vec![0x00];
+        table.extend_from_slice(&bits);
+        table.extend_from_slice(&values);
+        segment(&mut out, DHT, &table);
+
+        if self.restart_interval > 0 {
+            segment(&mut out, DRI, &self.restart_interval.to_be_bytes());
+        }
+
+        let mut scan = vec![components];
+        for c in 0..components {
+            scan.extend_from_slice(&[c, 0x00]);
+        }
+        // No point transform
+        scan.extend_from_slice(&[self.predictor, 0, 0]);
+        segment(&mut out, SOS, &scan);
+
+        let mut writer = BitWriter::new(out);
+        let interval = self.restart_interval as usize;
+        for (mcu, pixel) in diffs.chunks(components as usize).enumerate() {
+            if interval > 0 && mcu > 0 && mcu % interval == 0 {
+                writer.restart(RST0 + ((mcu / interval - 1) % 8) as u8);
+            }
+            for &d in pixel {
+                let ssss = category(d);
+                let (code, len) = codes[ssss as usize];
+                writer.put(code as u32, len);
+                // 32768 (category 16) carries no additional bits
+                if ssss > 0 && ssss < 16 {
+                    let extra = if d < 0 { d - 1 } else { d };
+                    writer.put(extra as u32 & ((1 << ssss) - 1), ssss);
+                }
+            }
+        }
+        let mut out = writer.finish();
+        out.extend_from_slice(&[0xFF, EOI]);
+        Ok(out)
+    }
+
+    /// Prediction differences in coding order, modulo 2^16
+    fn differences(&self) -> Vec<i32> {
+        let frame = self.frame;
+        let comps = frame.components as usize;
+        let row_len = frame.width as usize * comps;
+        let mut context = Context::new(self.restart_interval as u32, frame.precision);
+        let mut diffs = Vec::with_capacity(self.samples.len());
+        for row in 0..frame.height as usize {
+            for col in 0..frame.width as usize {
+                context.start_pixel(row, col);
+                for c in 0..comps {
+                    let pred = context.predict(self.samples, row_len, comps, self.predictor, row, col, c);
+                    let x = self.samples[row * row_len + col * comps + c] as i32;
+                    diffs.push((x - pred) as u16 as i16 as i32);
+                }
+                context.end_pixel();
+            }
+        }
+        diffs
+    }
+}
+
+fn segment(out: &mut Vec<u8>, marker: u8, body: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((body.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(body);
+}
+
+/// Tracks where prediction starts over, as the native decoder does. The
+/// first pixel of the scan and of each restart interval predicts from the
+/// midpoint, the rest of its line from the left and the first column from
+/// above.
+struct Context {
+    restart_interval: u32,
+    pixels_left: u32,
+    first_line: usize,
+    fresh: bool,
+    initial: i32,
+}
+
+impl Context {
+    fn new(restart_interval: u32, precision: u32) -> Self {
+        Context { restart_interval, pixels_left: restart_interval, first_line: 0, fresh: false, initial: 1 << (precision - 1) }
+    }
+
+    fn start_pixel(&mut self, row: usize, col: usize) {
+        if self.restart_interval == 0 {
+            return;
+        }
+        if self.pixels_left == 0 {
+            self.pixels_left = self.restart_interval;
+            self.fresh = true;
+            if col == 0 {
+                self.first_line = row;
+            }
+        }
+        self.pixels_left -= 1;
+    }
+
+    fn end_pixel(&mut self) {
+        self.fresh = false;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn predict(&self, samples: &[u16], row_len: usize, comps: usize, predictor: u8, row: usize, col: usize, c: usize) -> i32 {
+        let i = row * row_len + col * comps + c;
+        if self.fresh || (row == self.first_line && col == 0) {
+            self.initial
+        } else if row == self.first_line {
+            samples[i - comps] as i32
+        } else if col == 0 {
+            samples[i - row_len] as i32
+        } else {
+            let a = samples[i - comps] as i32;
+            let b = samples[i - row_len] as i32;
+            let c = samples[i - row_len - comps] as i32;
+            match predictor {
+                1 => a,
+                2 => b,
+                3 => c,
+                4 => a + b - c,
+                5 => a + ((b - c) >> 1),
+                6 => b + ((a - c) >> 1),
+                _ => (a + b) >> 1,
+            }
+        }
+    }
+}
+
+fn category(d: i32) -> u32 {
+    if d == -32768 { 16 } else { 32 - d.unsigned_abs().leading_zeros() }
+}
+
+/// Builds BITS and HUFFVAL from symbol frequencies (T.81 Annex K.2 and K.3),
+/// limiting code lengths to 16 bits
+fn huffman_table(freq: &[u32; SYMBOLS]) -> ([u8; 16], Vec<u8>) {
+    // One extra symbol with frequency 1 keeps any code from being all ones
+    let mut freq: Vec<u64> = freq.iter().map(|&f| f as u64).chain([1]).collect();
+    let n = freq.len();
+    let mut code_size = vec![0usize; n];
+    let mut others: Vec<Option<usize>> = vec![None; n];
+
+    loop {
+        // Least frequent symbol, ties going to the larger symbol, then the next least
+        let mut v1: Option<usize> = None;
+        for i in 0..n {
+            if freq[i] > 0 && v1.is_none_or(|v| freq[i] <= freq[v]) {
+                v1 = Some(i);
+            }
+        }
+        let mut v2: Option<usize> = None;
+        for i in 0..n {
+            if freq[i] > 0 && Some(i) != v1 && v2.is_none_or(|v| freq[i] <= freq[v]) {
+                v2 = Some(i);
+            }
+        }
+        let (Some(mut a), Some(mut b)) = (v1, v2) else { break };
+
+        freq[a] += freq[b];
+        freq[b] = 0;
+        code_size[a] += 1;
+        while let Some(next) = others[a] {
+            a = next;
+            code_size[a] += 1;
+        }
+        others[a] = Some(b);
+        code_size[b] += 1;
+        while let Some(next) = others[b] {
+            b = next;
+            code_size[b] += 1;
+        }
+    }
+
+    let mut counts = [0u32; 33];
+    for &size in &code_size {
+        if size > 0 {
+            counts[size] += 1;
+        }
+    }
+    for i in (17..=32).rev() {
+        while counts[i] > 0 {
+            let mut j = i - 2;
+            while counts[j] == 0 {
+                j -= 1;
+            }
+            counts[i] -= 2;
+            counts[i - 1] += 1;
+            counts[j + 1] += 2;
+            counts[j] -= 1;
+        }
+    }
+    // Drop the reserved symbol from the longest codes
+    let mut longest = 16;
+    while counts[longest] == 0 {
+        longest -= 1;
+    }
+    counts[longest] -= 1;
+
+    let mut bits = [0u8; 16];
+    for (i, b) in bits.iter_mut().enumerate() {
+        *b = counts[i + 1] as u8;
+    }
+    let mut values = Vec::new();
+    for size in 1..=32 {
+        for (symbol, &s) in code_size.iter().enumerate().take(SYMBOLS) {
+            if s == size {
+                values.push(symbol as u8);
+            }
+        }
+    }
+    (bits, values)
+}
+
+/// (code, length) per symbol
+fn canonical_codes(bits: &[u8; 16], values: &[u8]) -> [(u16, u32); SYMBOLS] {
+    let mut codes = [(0u16, 0u32); SYMBOLS];
+    let mut code = 0u16;
+    let mut k = 0;
+    for (i, &count) in bits.iter().enumerate() {
+        for _ in 0..count {
+            codes[values[k] as usize] = (code, i as u32 + 1);
+            code = code.wrapping_add(1);
+            k += 1;
+        }
+        code = code.wrapping_shl(1);
+    }
+    codes
+}
+
+/// MSB-first bit writer with JPEG byte stuffing
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> Self {
+        BitWriter { out, acc: 0, count: 0 }
+    }
+
+    fn put(&mut self, value: u32, len: u32) {
+        self.acc = (self.acc << len) | (value as u64 & ((1 << len) - 1));
+        self.count += len;
+        while self.count >= 8 {
+            self.count -= 8;
+            self.push((self.acc >> self.count) as u8);
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.out.push(byte);
+        if byte == 0xFF {
+            self.out.push(0x00);
+        }
+    }
+
+    /// Pads the last byte with one bits
+    fn flush(&mut self) {
+        if self.count > 0 {
+            let pad = 8 - self.count;
+            self.put((1 << pad) - 1, pad);
+        }
+    }
+
+    fn restart(&mut self, marker: u8) {
+        self.flush();
+        self.out.extend_from_slice(&[0xFF, marker]);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        self.out
+    }
+}
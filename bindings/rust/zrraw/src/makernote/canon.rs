@@ -0,0 +1,366 @@
+// bindings/rust/zrraw/src/makernote/canon.rs - Canon makernotes
+use super::{number, read_directory, shorts, text, DriveMode, FocusMode, MakernoteTag, Stabilization};
+use crate::exif::{MakernoteLocation, Tiff};
+
+const CAMERA_SETTINGS: u16 = 0x0001;
+const SHOT_INFO: u16 = 0x0004;
+const IMAGE_TYPE: u16 = 0x0006;
+const FIRMWARE_VERSION: u16 = 0x0007;
+const OWNER_NAME: u16 = 0x0009;
+const SERIAL_NUMBER: u16 = 0x000C;
+const MODEL_ID: u16 = 0x0010;
+const AF_INFO: u16 = 0x0012;
+const AF_INFO2: u16 = 0x0026;
+const LENS_MODEL: u16 = 0x0095;
+const COLOR_DATA: u16 = 0x4001;
+
+/// Canon's makernote: a bare IFD whose offsets count from the TIFF header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canon {
+    pub camera_settings: Option<CameraSettings>,
+    pub shot_info: Option<ShotInfo>,
+    pub color_data: Option<ColorData>,
+    /// From AFInfo2, or the older AFInfo
+    pub af_info: Option<AfInfo>,
+    pub image_type: Option<String>,
+    pub firmware_version: Option<String>,
+    pub owner_name: Option<String>,
+    pub serial_number: Option<u32>,
+    pub model_id: Option<u32>,
+    pub lens_model: Option<String>,
+    pub tags: Vec<MakernoteTag>,
+}
+
+/// The CameraSettings array (0x0001)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraSettings {
+    pub macro_mode: Option<u16>,
+    /// Self-timer delay in tenths of a second, 0 when off
+    pub self_timer: Option<u16>,
+    pub quality: Option<u16>,
+    pub flash_mode: Option<u16>,
+    pub drive_mode: Option<DriveMode>,
+    pub focus_mode: Option<FocusMode>,
+    pub metering_mode: Option<u16>,
+    pub af_point: Option<u16>,
+    pub exposure_mode: Option<u16>,
+    /// Canon's lens ID
+    pub lens_type: Option<u16>,
+    /// Shortest and longest focal length of the lens in millimetres
+    pub focal_range: Option<(f64, f64)>,
+    /// Widest and narrowest f-number of the lens
+    pub aperture_range: Option<(f64, f64)>,
+    pub image_stabilization: Option<u16>,
+}
+
+/// The ShotInfo array (0x0004)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShotInfo {
+    /// Auto-ISO adjustment in percent
+    pub auto_iso: Option<f64>,
+    pub base_iso: Option<f64>,
+    /// Stops
+    pub exposure_compensation: Option<f64>,
+    pub white_balance: Option<u16>,
+    /// Position within a burst or bracket
+    pub sequence_number: Option<u16>,
+    /// Degrees Celsius
+    pub camera_temperature: Option<i16>,
+    pub af_points_in_focus: Option<u16>,
+}
+
+/// White balance multipliers of one ColorData entry, green counted twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhiteBalanceLevels {
+    pub rggb: [u16; 4],
+    /// Kelvin
+    pub color_temperature: Option<u16>,
+}
+
+impl WhiteBalanceLevels {
+    /// Red, green and blue multipliers relative to green
+    pub fn multipliers(&self) -> Option<[f32; 3]> {
+        let [r, g1, g2, b] = self.rggb.map(f32::from);
+        let green = (g1 + g2) / 2.0;
+        (green > 0.0 && r > 0.0 && b > 0.0).then(|| [r / green, 1.0, b / green])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhiteBalancePreset {
+    Auto,
+    Measured,
+    Daylight,
+    Shade,
+    Cloudy,
+    Tungsten,
+    Fluorescent,
+    Kelvin,
+    Flash,
+}
+
+/// ColorData (0x4001), whose layout depends on the body generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorData {
+    /// Layout number as ExifTool counts them, 1 (20D, 350D) to 11 (R5, R6)
+    pub version: u8,
+    pub as_shot: Option<WhiteBalanceLevels>,
+    /// The in-camera presets, for layouts whose preset table is known
+    pub presets: Vec<(WhiteBalancePreset, WhiteBalanceLevels)>,
+}
+
+/// Autofocus points and which of them were used
+#[derive(Debug, Clone, PartialEq)]
+pub struct AfInfo {
+    pub area_mode: Option<u16>,
+    /// Size of the image the point coordinates refer to
+    pub image_width: u16,
+    pub image_height: u16,
+    pub points: Vec<AfPoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AfPoint {
+    /// Centre of the point relative to the image centre, y pointing up
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub in_focus: bool,
+    pub selected: bool,
+}
+
+impl CameraSettings {
+    /// Canon stabilises in the lens; 256 and up repeat the modes of bodies
+    /// that report the setting twice
+    pub(crate) fn stabilization(&self) -> Option<Stabilization> {
+        Some(match self.image_stabilization? & 0xff {
+            0 => Stabilization::Off,
+            1..=4 => Stabilization::Lens,
+            other => Stabilization::Other(u32::from(other)),
+        })
+    }
+}
+
+impl Canon {
+    pub(crate) fn read(location: &MakernoteLocation) -> Option<Canon> {
+        // Editors that move the makernote record where it used to start;
+        // its offsets still count from there
+        let shift = original_start(location).and_then(|start| location.start.checked_sub(start)).unwrap_or(0);
+        let tiff = Tiff::with_order(&location.tiff[shift..], location.big_endian);
+        let tags = read_directory(&tiff, location.start - shift);
+        if tags.is_empty() {
+            return None;
+        }
+
+        Some(Canon {
+            camera_settings: shorts(&tags, CAMERA_SETTINGS).map(|v| camera_settings(&v)),
+            shot_info: shorts(&tags, SHOT_INFO).map(|v| shot_info(&v)),
+            color_data: shorts(&tags, COLOR_DATA).and_then(|v| color_data(&v)),
+            af_info: shorts(&tags, AF_INFO2)
+                .and_then(|v| af_info2(&v))
+                .or_else(|| shorts(&tags, AF_INFO).and_then(|v| af_info(&v))),
+            image_type: text(&tags, IMAGE_TYPE),
+            firmware_version: text(&tags, FIRMWARE_VERSION),
+            owner_name: text(&tags, OWNER_NAME),
+            serial_number: number(&tags, SERIAL_NUMBER),
+            model_id: number(&tags, MODEL_ID),
+            lens_model: text(&tags, LENS_MODEL),
+            tags,
+        })
+    }
+}
+
+/// The start recorded in the "II*\0" trailer some editors append
+fn original_start(location: &MakernoteLocation) -> Option<usize> {
+    let bytes = location.bytes();
+    let trailer = bytes.get(bytes.len().checked_sub(8)?..)?;
+    let offset = [trailer[4], trailer[5], trailer[6], trailer[7]];
+    match &trailer[..4] {
+        b"II*\0" => Some(u32::from_le_bytes(offset) as usize),
+        b"MM\0*" => Some(u32::from_be_bytes(offset) as usize),
+        _ => None,
+    }
+}
+
+/// Entry `index` of a settings array; Canon writes -1 for "not applicable"
+fn setting(values: &[u16], index: usize) -> Option<u16> {
+    values.get(index).copied().filter(|&v| v != 0xFFFF)
+}
+
+/// Canon's EV encoding: 1/32 stops, with 0x0c and 0x14 meaning 1/3 and 2/3
+fn canon_ev(raw: u16) -> f64 {
+    let value = i32::from(raw as i16);
+    let magnitude = value.abs();
+    let fraction = match magnitude & 0x1f {
+        0x0c => 32.0 / 3.0,
+        0x14 => 64.0 / 3.0,
+        other => f64::from(other),
+    };
+    f64::from(value.signum()) * (f64::from(magnitude & !0x1f) + fraction) / 32.0
+}
+
+fn aperture(raw: u16) -> f64 {
+    2f64.powf(canon_ev(raw) / 2.0)
+}
+
+fn camera_settings(v: &[u16]) -> CameraSettings {
+    let self_timer = setting(v, 2);
+    let drive_mode = setting(v, 5).map(|drive| match drive {
+        _ if self_timer.is_some_and(|delay| delay != 0) => DriveMode::SelfTimer,
+        0 | 6 | 9 => DriveMode::Single,
+        1 | 3 | 10 => DriveMode::Continuous,
+        4 => DriveMode::ContinuousLow,
+        5 => DriveMode::ContinuousHigh,
+        other => DriveMode::Other(u32::from(other)),
+    });
+    let focus_mode = setting(v, 7).map(|focus| match focus {
+        0 | 4 => FocusMode::Single,
+        1 | 5 => FocusMode::Continuous,
+        2 => FocusMode::Automatic,
+        3 | 6 => FocusMode::Manual,
+        other => FocusMode::Other(u32::from(other)),
+    });
+    let units = f64::from(setting(v, 25).filter(|&u| u != 0).unwrap_or(1));
+    let focal_range = match (setting(v, 24), setting(v, 23)) {
+        (Some(short), Some(long)) if short > 0 && long >= short => {
+            Some((f64::from(short) / units, f64::from(long) / units))
+        }
+        _ => None,
+    };
+    let aperture_range = match (setting(v, 26), setting(v, 27)) {
+        (Some(widest), Some(narrowest)) if widest > 0 && narrowest >= widest => {
+            Some((aperture(widest), aperture(narrowest)))
+        }
+        _ => None,
+    };
+
+    CameraSettings {
+        macro_mode: setting(v, 1),
+        self_timer,
+        quality: setting(v, 3),
+        flash_mode: setting(v, 4),
+        drive_mode,
+        focus_mode,
+        metering_mode: setting(v, 17),
+        af_point: setting(v, 19),
+        exposure_mode: setting(v, 20),
+        lens_type: setting(v, 22),
+        focal_range,
+        aperture_range,
+        image_stabilization: setting(v, 34),
+    }
+}
+
+fn shot_info(v: &[u16]) -> ShotInfo {
+    // ISO values are stored as 1/32 stops
+    let stops = |raw: u16| 2f64.powf(f64::from(raw as i16) / 32.0);
+    ShotInfo {
+        auto_iso: setting(v, 1).map(|raw| stops(raw) * 100.0),
+        base_iso: setting(v, 2).filter(|&raw| raw != 0).map(|raw| stops(raw) * 100.0 / 32.0),
+        exposure_compensation: setting(v, 6).map(canon_ev),
+        white_balance: setting(v, 7),
+        sequence_number: setting(v, 9),
+        camera_temperature: setting(v, 12).filter(|&t| t != 0).map(|t| t as i16 - 128),
+        af_points_in_focus: setting(v, 14),
+    }
+}
+
+fn color_data(v: &[u16]) -> Option<ColorData> {
+    let (version, as_shot) = match v.len() {
+        582 => (1, 0x19),
+        653 => (2, 0x18),
+        796 => (3, 0x3f),
+        674 | 692 | 702 | 1227 | 1250 | 1251 | 1337 | 1338 | 1346 => (4, 0x3f),
+        5120 => (5, 0x47),
+        1273 | 1275 => (6, 0x3f),
+        1312 | 1313 | 1316 | 1506 => (7, 0x3f),
+        1560 | 1592 | 1353 | 1602 => (8, 0x3f),
+        1816 | 1820 | 1824 => (9, 0x47),
+        2024 | 3656 => (10, 0x55),
+        3973 | 3778 => (11, 0x69),
+        _ => return None,
+    };
+    // From the 1D Mark II N on, a colour temperature follows each set of levels
+    let with_temperature = !matches!(version, 1 | 2 | 5);
+    let levels = |at: usize| {
+        let rggb: [u16; 4] = v.get(at..at + 4)?.try_into().ok()?;
+        let color_temperature = if with_temperature { v.get(at + 4).copied().filter(|&k| k != 0) } else { None };
+        (rggb != [0; 4]).then_some(WhiteBalanceLevels { rggb, color_temperature })
+    };
+
+    use WhiteBalancePreset::*;
+    let table: &[(WhiteBalancePreset, usize)] = match version {
+        3 => &[
+            (Auto, 0x44),
+            (Measured, 0x49),
+            (Daylight, 0x4e),
+            (Shade, 0x53),
+            (Cloudy, 0x58),
+            (Tungsten, 0x5d),
+            (Fluorescent, 0x62),
+            (Kelvin, 0x67),
+            (Flash, 0x6c),
+        ],
+        4 => &[
+            (Auto, 0x44),
+            (Measured, 0x49),
+            (Daylight, 0x8f),
+            (Shade, 0x94),
+            (Cloudy, 0x99),
+            (Tungsten, 0x9e),
+            (Fluorescent, 0xa3),
+            (Kelvin, 0xa8),
+            (Flash, 0xad),
+        ],
+        6..=8 => &[(Auto, 0x44), (Measured, 0x49)],
+        _ => &[],
+    };
+    Some(ColorData {
+        version,
+        as_shot: levels(as_shot),
+        presets: table.iter().filter_map(|&(preset, at)| Some((preset, levels(at)?))).collect(),
+    })
+}
+
+/// AFInfo2 (0x0026): size, area mode, point count, valid points, two image
+/// sizes, then per-point widths, heights, x and y, and focus bit masks
+fn af_info2(v: &[u16]) -> Option<AfInfo> {
+    let count = usize::from(*v.get(2)?);
+    let words = count.div_ceil(16);
+    let in_focus = v.get(8 + 4 * count..8 + 4 * count + words)?;
+    let selected = v.get(8 + 4 * count + words..8 + 4 * count + 2 * words);
+    let points = (0..count)
+        .map(|i| AfPoint {
+            width: v[8 + i],
+            height: v[8 + count + i],
+            x: v[8 + 2 * count + i] as i16,
+            y: v[8 + 3 * count + i] as i16,
+            in_focus: bit(in_focus, i),
+            selected: selected.is_some_and(|mask| bit(mask, i)),
+        })
+        .collect();
+    Some(AfInfo { area_mode: setting(v, 1), image_width: v[6], image_height: v[7], points })
+}
+
+/// AFInfo (0x0012): point count, valid points, two image sizes, one point
+/// size for all, x and y, then the in-focus bit mask
+fn af_info(v: &[u16]) -> Option<AfInfo> {
+    let count = usize::from(*v.first()?);
+    let in_focus = v.get(8 + 2 * count..8 + 2 * count + count.div_ceil(16))?;
+    let points = (0..count)
+        .map(|i| AfPoint {
+            width: v[6],
+            height: v[7],
+            x: v[8 + i] as i16,
+            y: v[8 + count + i] as i16,
+            in_focus: bit(in_focus, i),
+            selected: false,
+        })
+        .collect();
+    Some(AfInfo { area_mode: None, image_width: v[4], image_height: v[5], points })
+}
+
+fn bit(mask: &[u16], index: usize) -> bool {
+    mask[index / 16] & (1 << (index % 16)) != 0
+}
@@ -0,0 +1,148 @@
+// bindings/rust/zrraw/src/makernote/fujifilm.rs - Fujifilm makernotes
+use super::{note_relative, number, read_directory, shorts, text, DriveMode, FocusMode, MakernoteTag, Stabilization};
+use crate::exif::MakernoteLocation;
+
+const INTERNAL_SERIAL_NUMBER: u16 = 0x0010;
+const QUALITY: u16 = 0x1000;
+const SATURATION: u16 = 0x1003;
+const FOCUS_MODE: u16 = 0x1021;
+const FOCUS_SETTINGS: u16 = 0x102D;
+const AUTO_BRACKETING: u16 = 0x1100;
+const SEQUENCE_NUMBER: u16 = 0x1101;
+const DRIVE_SETTINGS: u16 = 0x1103;
+const FILM_MODE: u16 = 0x1401;
+const DYNAMIC_RANGE_SETTING: u16 = 0x1402;
+const DEVELOPMENT_DYNAMIC_RANGE: u16 = 0x1403;
+const IMAGE_STABILIZATION: u16 = 0x1422;
+
+/// Fujifilm's makernote: "FUJIFILM", then the little-endian offset of an
+/// IFD whose offsets count from the makernote's first byte, whatever the
+/// byte order of the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fujifilm {
+    pub internal_serial_number: Option<String>,
+    /// "NORMAL", "FINE", ...
+    pub quality: Option<String>,
+    pub film_simulation: Option<FilmSimulation>,
+    /// DR100, DR200 or DR400, as a percentage
+    pub dynamic_range: Option<u16>,
+    /// Whether the camera chose the dynamic range
+    pub dynamic_range_auto: Option<bool>,
+    pub focus_mode: Option<FocusMode>,
+    pub drive_mode: Option<DriveMode>,
+    /// Frames per second of continuous drive
+    pub drive_speed: Option<u8>,
+    /// Position within a burst, 0 outside one
+    pub sequence_number: Option<u16>,
+    pub stabilization: Option<Stabilization>,
+    pub tags: Vec<MakernoteTag>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilmSimulation {
+    Provia,
+    Velvia,
+    Astia,
+    ClassicChrome,
+    ProNegHi,
+    ProNegStd,
+    Eterna,
+    EternaBleachBypass,
+    ClassicNeg,
+    NostalgicNeg,
+    RealaAce,
+    /// Black and white, plain or through a simulated colour filter
+    Monochrome,
+    Acros,
+    Sepia,
+    /// The FilmMode value of the older portrait and studio modes
+    Other(u16),
+}
+
+impl Fujifilm {
+    pub(crate) fn read(location: &MakernoteLocation) -> Option<Fujifilm> {
+        let note = location.bytes();
+        if !note.starts_with(b"FUJIFILM") {
+            return None;
+        }
+        let tiff = note_relative(location, false);
+        let tags = read_directory(&tiff, tiff.u32(8)? as usize);
+        if tags.is_empty() {
+            return None;
+        }
+
+        let focus_mode = match number(&tags, FOCUS_SETTINGS) {
+            // FocusMode2 of the X-series FocusSettings
+            Some(settings) => match (settings >> 12) & 0xf {
+                0 => Some(FocusMode::Manual),
+                1 => Some(FocusMode::Single),
+                2 => Some(FocusMode::Continuous),
+                other => Some(FocusMode::Other(other)),
+            },
+            None => match number(&tags, FOCUS_MODE) {
+                Some(1) => Some(FocusMode::Manual),
+                Some(0xFFFF) | None => None,
+                Some(other) => Some(FocusMode::Other(other)),
+            },
+        };
+        let drive_settings = number(&tags, DRIVE_SETTINGS);
+        let drive_mode = match drive_settings.map(|settings| settings & 0xff) {
+            Some(0) => Some(DriveMode::Single),
+            Some(1) => Some(DriveMode::ContinuousLow),
+            Some(2) => Some(DriveMode::ContinuousHigh),
+            Some(other) => Some(DriveMode::Other(other)),
+            // Older bodies only tell whether they bracketed
+            None => number(&tags, AUTO_BRACKETING).filter(|&bracketing| bracketing == 1).map(|_| DriveMode::Bracketing),
+        };
+        // The type of stabiliser, then whether and when it runs
+        let stabilization = shorts(&tags, IMAGE_STABILIZATION).and_then(|v| match (v.first()?, v.get(1)?) {
+            (_, 0) => Some(Stabilization::Off),
+            (1 | 3, _) => Some(Stabilization::Lens),
+            (2, _) => Some(Stabilization::Body),
+            (0x102 | 0x103, _) => Some(Stabilization::Dual),
+            (&other, _) => Some(Stabilization::Other(u32::from(other))),
+        });
+
+        Some(Fujifilm {
+            internal_serial_number: text(&tags, INTERNAL_SERIAL_NUMBER),
+            quality: text(&tags, QUALITY),
+            film_simulation: film_simulation(number(&tags, SATURATION), number(&tags, FILM_MODE)),
+            dynamic_range: number(&tags, DEVELOPMENT_DYNAMIC_RANGE).map(|dr| dr as u16).filter(|&dr| dr != 0),
+            dynamic_range_auto: number(&tags, DYNAMIC_RANGE_SETTING)
+                .filter(|&setting| setting != 0x8000)
+                .map(|setting| setting == 0),
+            focus_mode,
+            drive_mode,
+            drive_speed: drive_settings.map(|settings| (settings >> 24) as u8).filter(|&fps| fps != 0),
+            sequence_number: number(&tags, SEQUENCE_NUMBER).map(|n| n as u16),
+            stabilization,
+            tags,
+        })
+    }
+}
+
+/// Black and white simulations are a Saturation setting, colour ones a
+/// FilmMode
+fn film_simulation(saturation: Option<u32>, film_mode: Option<u32>) -> Option<FilmSimulation> {
+    use FilmSimulation::*;
+    match saturation {
+        Some(0x300..=0x30f) => return Some(Monochrome),
+        Some(0x310) => return Some(Sepia),
+        Some(0x500..=0x50f) => return Some(Acros),
+        _ => {}
+    }
+    Some(match film_mode? {
+        0x000 => Provia,
+        0x120 => Astia,
+        0x200 | 0x400 => Velvia,
+        0x500 => ProNegStd,
+        0x501 => ProNegHi,
+        0x600 => ClassicChrome,
+        0x700 => Eterna,
+        0x800 => ClassicNeg,
+        0x900 => EternaBleachBypass,
+        0xa00 => NostalgicNeg,
+        0xb00 => RealaAce,
+        other => Other(other as u16),
+    })
+}
@@ -0,0 +1,246 @@
+// bindings/rust/zrraw/src/makernote/mod.rs - Vendor makernotes behind the EXIF MakerNote tag
+use crate::exif::{self, ExifValue, MakernoteLocation, Tiff};
+
+pub mod canon;
+pub mod fujifilm;
+pub mod nikon;
+pub mod olympus;
+pub mod panasonic;
+pub mod pentax;
+pub mod sony;
+
+/// One entry of a makernote directory, its value decoded by TIFF type
+#[derive(Debug, Clone, PartialEq)]
+pub struct MakernoteTag {
+    pub id: u16,
+    /// TIFF field type as stored
+    pub tiff_type: u16,
+    pub value: ExifValue,
+}
+
+/// Shooting mode of the shutter release, as far as vendors agree on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveMode {
+    Single,
+    Continuous,
+    ContinuousLow,
+    ContinuousHigh,
+    SelfTimer,
+    Bracketing,
+    /// The vendor's own code
+    Other(u32),
+}
+
+/// Focus mode, as far as vendors agree on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusMode {
+    Manual,
+    /// Focus locks once acquired (One-Shot, AF-S)
+    Single,
+    /// Focus tracks the subject (AI Servo, AF-C)
+    Continuous,
+    /// The camera picks single or continuous (AI Focus, AF-A)
+    Automatic,
+    /// The vendor's own code
+    Other(u32),
+}
+
+/// Image stabilisation, as far as vendors agree on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stabilization {
+    Off,
+    /// Sensor shift in the body
+    Body,
+    /// An optical group in the lens
+    Lens,
+    /// Body and lens together
+    Dual,
+    /// On, by a mechanism the makernote does not name
+    On,
+    /// The vendor's own code
+    Other(u32),
+}
+
+/// A decoded makernote. `Makernote::tags` lists every entry of the main
+/// directory; the vendor structs decode the ones worth naming.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Makernote {
+    Canon(canon::Canon),
+    Nikon(nikon::Nikon),
+    Sony(sony::Sony),
+    Fujifilm(fujifilm::Fujifilm),
+    Panasonic(panasonic::Panasonic),
+    Olympus(olympus::Olympus),
+    Pentax(pentax::Pentax),
+}
+
+impl Makernote {
+    /// Reads the makernote of a raw file whose EXIF tags are `exif`; None
+    /// when there is none or its vendor is not supported
+    pub fn read(data: &[u8], exif: &exif::Exif) -> Option<Makernote> {
+        let location = exif::makernote_location(data)?;
+        let make = exif.make()?.to_ascii_uppercase();
+        if make.starts_with("CANON") {
+            canon::Canon::read(&location).map(Makernote::Canon)
+        } else if make.starts_with("NIKON") {
+            nikon::Nikon::read(&location, exif.model().unwrap_or_default()).map(Makernote::Nikon)
+        } else if make.starts_with("SONY") {
+            sony::Sony::read(&location).map(Makernote::Sony)
+        } else if make.starts_with("FUJIFILM") {
+            fujifilm::Fujifilm::read(&location).map(Makernote::Fujifilm)
+        } else if make.starts_with("PANASONIC") {
+            panasonic::Panasonic::read(&location).map(Makernote::Panasonic)
+        } else if make.starts_with("OLYMPUS") || make.starts_with("OM DIGITAL") {
+            olympus::Olympus::read(&location).map(Makernote::Olympus)
+        } else if make.starts_with("PENTAX") || make.starts_with("RICOH IMAGING") {
+            pentax::Pentax::read(&location).map(Makernote::Pentax)
+        } else {
+            None
+        }
+    }
+
+    pub fn tags(&self) -> &[MakernoteTag] {
+        match self {
+            Makernote::Canon(canon) => &canon.tags,
+            Makernote::Nikon(nikon) => &nikon.tags,
+            Makernote::Sony(sony) => &sony.tags,
+            Makernote::Fujifilm(fujifilm) => &fujifilm.tags,
+            Makernote::Panasonic(panasonic) => &panasonic.tags,
+            Makernote::Olympus(olympus) => &olympus.tags,
+            Makernote::Pentax(pentax) => &pentax.tags,
+        }
+    }
+
+    /// Shutter actuations, for bodies that count them
+    pub fn shutter_count(&self) -> Option<u32> {
+        match self {
+            Makernote::Nikon(nikon) => nikon.shutter_count,
+            Makernote::Sony(sony) => sony.tag9050.as_ref()?.shutter_count,
+            _ => None,
+        }
+    }
+
+    /// The vendor's lens identifier, to look up in its own lens table
+    pub fn lens_id(&self) -> Option<u32> {
+        match self {
+            Makernote::Canon(canon) => canon.camera_settings.as_ref()?.lens_type.map(u32::from),
+            Makernote::Nikon(nikon) => nikon.lens_data.as_ref()?.lens_id_number.map(u32::from),
+            Makernote::Sony(sony) => sony.lens_type,
+            Makernote::Olympus(olympus) => olympus.lens_type,
+            Makernote::Pentax(pentax) => pentax.lens_type.map(u32::from),
+            // Fujifilm and Panasonic name their lenses instead
+            Makernote::Fujifilm(_) | Makernote::Panasonic(_) => None,
+        }
+    }
+
+    pub fn drive_mode(&self) -> Option<DriveMode> {
+        match self {
+            Makernote::Canon(canon) => canon.camera_settings.as_ref()?.drive_mode,
+            Makernote::Nikon(nikon) => nikon.drive_mode(),
+            Makernote::Sony(sony) => sony.drive_mode,
+            Makernote::Fujifilm(fujifilm) => fujifilm.drive_mode,
+            Makernote::Panasonic(panasonic) => panasonic.drive_mode,
+            Makernote::Olympus(olympus) => olympus.drive_mode,
+            Makernote::Pentax(pentax) => pentax.drive_mode,
+        }
+    }
+
+    pub fn focus_mode(&self) -> Option<FocusMode> {
+        match self {
+            Makernote::Canon(canon) => canon.camera_settings.as_ref()?.focus_mode,
+            Makernote::Nikon(nikon) => nikon.focus_mode(),
+            Makernote::Sony(sony) => sony.focus_mode,
+            Makernote::Fujifilm(fujifilm) => fujifilm.focus_mode,
+            Makernote::Panasonic(panasonic) => panasonic.focus_mode,
+            Makernote::Olympus(olympus) => olympus.focus_mode,
+            Makernote::Pentax(pentax) => pentax.focus_mode,
+        }
+    }
+
+    pub fn stabilization(&self) -> Option<Stabilization> {
+        match self {
+            Makernote::Canon(canon) => canon.camera_settings.as_ref()?.stabilization(),
+            Makernote::Nikon(_) => None,
+            Makernote::Sony(sony) => sony.stabilization,
+            Makernote::Fujifilm(fujifilm) => fujifilm.stabilization,
+            Makernote::Panasonic(panasonic) => panasonic.stabilization,
+            Makernote::Olympus(olympus) => olympus.stabilization,
+            Makernote::Pentax(pentax) => pentax.stabilization,
+        }
+    }
+}
+
+/// Reads the directory at `offset` of `tiff`. A count that runs past the
+/// data means the offset base is wrong, which gives no entries.
+pub(crate) fn read_directory(tiff: &Tiff, offset: usize) -> Vec<MakernoteTag> {
+    let Some(count) = tiff.u16(offset).map(usize::from) else { return Vec::new() };
+    if count == 0 || offset + 2 + count * 12 > tiff.data.len() {
+        return Vec::new();
+    }
+    (0..count)
+        .filter_map(|i| {
+            let pos = offset + 2 + i * 12;
+            let (id, tiff_type, n) = (tiff.u16(pos)?, tiff.u16(pos + 2)?, tiff.u32(pos + 4)?);
+            Some(MakernoteTag { id, tiff_type, value: tiff.value(pos, tiff_type, n as usize)? })
+        })
+        .collect()
+}
+
+/// A makernote directory whose offsets count from the TIFF header, as
+/// Canon, Sony and most TIFF-based vendors write them. `header` bytes of
+/// signature precede the directory.
+pub(crate) fn tiff_relative(location: &MakernoteLocation, header: usize) -> Vec<MakernoteTag> {
+    let tiff = Tiff::with_order(location.tiff, location.big_endian);
+    read_directory(&tiff, location.start + header)
+}
+
+/// The makernote as a structure of its own, for vendors whose offsets count
+/// from its first byte
+pub(crate) fn note_relative<'a>(location: &MakernoteLocation<'a>, big_endian: bool) -> Tiff<'a> {
+    Tiff::with_order(location.bytes(), big_endian)
+}
+
+/// The byte order a two-byte "II" or "MM" marker at `at` names
+pub(crate) fn byte_order(note: &[u8], at: usize) -> Option<bool> {
+    match note.get(at..at + 2)? {
+        b"II" => Some(false),
+        b"MM" => Some(true),
+        _ => None,
+    }
+}
+
+pub(crate) fn value(tags: &[MakernoteTag], id: u16) -> Option<&ExifValue> {
+    tags.iter().find(|tag| tag.id == id).map(|tag| &tag.value)
+}
+
+pub(crate) fn text(tags: &[MakernoteTag], id: u16) -> Option<String> {
+    let text = match value(tags, id)? {
+        ExifValue::Ascii(text) => text.trim().to_string(),
+        // Some vendors store strings as UNDEFINED
+        ExifValue::Undefined(bytes) => {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+        }
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+pub(crate) fn number(tags: &[MakernoteTag], id: u16) -> Option<u32> {
+    value(tags, id)?.as_u32()
+}
+
+pub(crate) fn shorts(tags: &[MakernoteTag], id: u16) -> Option<Vec<u16>> {
+    match value(tags, id)? {
+        ExifValue::Short(values) => Some(values.clone()),
+        ExifValue::SShort(values) => Some(values.iter().map(|&v| v as u16).collect()),
+        _ => None,
+    }
+}
+
+pub(crate) fn bytes(tags: &[MakernoteTag], id: u16) -> Option<&[u8]> {
+    match value(tags, id)? {
+        ExifValue::Undefined(bytes) | ExifValue::Byte(bytes) => Some(bytes),
+        _ => None,
+    }
+}
@@ -0,0 +1,323 @@
+// bindings/rust/zrraw/src/makernote/nikon.rs - Nikon makernotes, including the encrypted blocks
+use super::{bytes, number, read_directory, text, value, DriveMode, FocusMode, MakernoteTag};
+use crate::exif::{ExifValue, MakernoteLocation, Rational, Tiff};
+
+const VERSION: u16 = 0x0001;
+const ISO: u16 = 0x0002;
+const QUALITY: u16 = 0x0004;
+const WHITE_BALANCE: u16 = 0x0005;
+const FOCUS_MODE: u16 = 0x0007;
+const WB_RB_LEVELS: u16 = 0x000C;
+const SERIAL_NUMBER: u16 = 0x001D;
+const LENS_TYPE: u16 = 0x0083;
+const LENS: u16 = 0x0084;
+const SHOOTING_MODE: u16 = 0x0089;
+const SHOT_INFO: u16 = 0x0091;
+const LENS_DATA: u16 = 0x0098;
+const SHUTTER_COUNT: u16 = 0x00A7;
+
+/// Nikon's makernote. Current bodies write "Nikon\0", a version and a TIFF
+/// header the offsets count from; early Coolpix bodies "Nikon\0\x01\0" and an
+/// IFD with file-relative offsets; D1-era bodies a bare IFD.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Nikon {
+    /// MakerNoteVersion, e.g. "0211"
+    pub version: Option<String>,
+    pub iso: Option<u32>,
+    pub quality: Option<String>,
+    pub white_balance: Option<String>,
+    /// "AF-S", "AF-C", "AF-A" or "MANUAL"
+    pub focus_mode_setting: Option<String>,
+    /// As-shot red and blue multipliers relative to green
+    pub white_balance_rb: Option<[f64; 2]>,
+    pub serial_number: Option<String>,
+    pub shutter_count: Option<u32>,
+    /// LensType bit field: MF, D, G, VR, ...
+    pub lens_type: Option<u8>,
+    /// Focal range in millimetres and the widest f-number at either end
+    pub lens: Option<LensSpec>,
+    /// ShootingMode bit field: continuous, delay, self-timer, bracketing, ...
+    pub shooting_mode: Option<u16>,
+    pub shot_info: Option<ShotInfo>,
+    pub lens_data: Option<LensData>,
+    pub tags: Vec<MakernoteTag>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensSpec {
+    pub min_focal_length: f64,
+    pub max_focal_length: f64,
+    pub max_aperture_at_min_focal: f64,
+    pub max_aperture_at_max_focal: f64,
+}
+
+/// ShotInfo (0x0091), whose layout differs for nearly every body; the
+/// bytes are decrypted but otherwise left as stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShotInfo {
+    pub version: String,
+    pub data: Vec<u8>,
+}
+
+/// LensData (0x0098) of F-mount bodies, decrypted where needed
+#[derive(Debug, Clone, PartialEq)]
+pub struct LensData {
+    pub version: String,
+    /// Together with the other fields, the key of Nikon lens tables
+    pub lens_id_number: Option<u8>,
+    /// Stops the aperture ring covers
+    pub lens_f_stops: Option<f64>,
+    pub min_focal_length: Option<f64>,
+    pub max_focal_length: Option<f64>,
+    pub max_aperture_at_min_focal: Option<f64>,
+    pub max_aperture_at_max_focal: Option<f64>,
+    pub mcu_version: Option<u8>,
+    /// Metres, for versions that record it
+    pub focus_distance: Option<f64>,
+    /// Millimetres, for versions that record it
+    pub focal_length: Option<f64>,
+}
+
+impl Nikon {
+    pub(crate) fn read(location: &MakernoteLocation, model: &str) -> Option<Nikon> {
+        let note = location.bytes();
+        if note.starts_with(b"Nikon\0\x01\0") {
+            // Type 1 (early Coolpix): no TIFF header of its own, the IFD
+            // follows the 8-byte signature. Its tags are numbered apart from
+            // the later types' (0x0007 is the white balance), so only the
+            // tags themselves are kept.
+            let tiff = Tiff::with_order(location.tiff, location.big_endian);
+            let tags = read_directory(&tiff, location.start + 8);
+            return (!tags.is_empty()).then(|| Nikon { tags, ..Default::default() });
+        }
+        let tags = if note.starts_with(b"Nikon\0") {
+            // Type 3: the version, then a TIFF header the offsets count from
+            let header = note.get(10..).filter(|h| h.starts_with(b"II") || h.starts_with(b"MM"))?;
+            let tiff = Tiff::with_order(header, header.starts_with(b"MM"));
+            read_directory(&tiff, tiff.u32(4)? as usize)
+        } else {
+            // Bare IFDs have file-relative offsets
+            let tiff = Tiff::with_order(location.tiff, location.big_endian);
+            read_directory(&tiff, location.start)
+        };
+        if tags.is_empty() {
+            return None;
+        }
+
+        let serial_number = text(&tags, SERIAL_NUMBER);
+        let shutter_count = number(&tags, SHUTTER_COUNT);
+        let cipher = Cipher::new(serial_key(serial_number.as_deref(), model), shutter_count.unwrap_or(0));
+        let white_balance_rb = match value(&tags, WB_RB_LEVELS) {
+            Some(ExifValue::Rational(levels)) if levels.len() >= 2 => levels[0]
+                .to_f64()
+                .zip(levels[1].to_f64())
+                .map(|(red, blue)| [red, blue])
+                .filter(|rb| rb[0] > 0.0 && rb[1] > 0.0),
+            _ => None,
+        };
+        let lens = match value(&tags, LENS) {
+            Some(ExifValue::Rational(spec)) => lens_spec(spec),
+            _ => None,
+        };
+
+        Some(Nikon {
+            version: text(&tags, VERSION),
+            iso: match value(&tags, ISO) {
+                Some(ExifValue::Short(iso)) => iso.get(1).map(|&v| u32::from(v)).filter(|&v| v != 0),
+                _ => None,
+            },
+            quality: text(&tags, QUALITY),
+            white_balance: text(&tags, WHITE_BALANCE),
+            focus_mode_setting: text(&tags, FOCUS_MODE),
+            white_balance_rb,
+            shutter_count,
+            lens_type: number(&tags, LENS_TYPE).map(|v| v as u8),
+            lens,
+            shooting_mode: number(&tags, SHOOTING_MODE).map(|v| v as u16),
+            shot_info: bytes(&tags, SHOT_INFO).and_then(|block| shot_info(block, cipher)),
+            lens_data: bytes(&tags, LENS_DATA).and_then(|block| lens_data(block, cipher)),
+            serial_number,
+            tags,
+        })
+    }
+
+    pub(crate) fn drive_mode(&self) -> Option<DriveMode> {
+        let mode = self.shooting_mode?;
+        Some(if mode & 0x08 != 0 {
+            DriveMode::SelfTimer
+        } else if mode & 0x10 != 0 {
+            DriveMode::Bracketing
+        } else if mode & 0x01 != 0 {
+            DriveMode::Continuous
+        } else {
+            DriveMode::Single
+        })
+    }
+
+    pub(crate) fn focus_mode(&self) -> Option<FocusMode> {
+        let setting = self.focus_mode_setting.as_deref()?;
+        Some(match setting {
+            "AF-S" => FocusMode::Single,
+            "AF-C" => FocusMode::Continuous,
+            "AF-A" => FocusMode::Automatic,
+            _ if setting.starts_with("MANUAL") || setting == "MF" => FocusMode::Manual,
+            _ => FocusMode::Other(0),
+        })
+    }
+}
+
+fn lens_spec(spec: &[Rational]) -> Option<LensSpec> {
+    let [min_focal, max_focal, min_aperture, max_aperture] = spec else { return None };
+    Some(LensSpec {
+        min_focal_length: min_focal.to_f64()?,
+        max_focal_length: max_focal.to_f64()?,
+        max_aperture_at_min_focal: min_aperture.to_f64()?,
+        max_aperture_at_max_focal: max_aperture.to_f64()?,
+    })
+}
+
+/// The serial number as the cipher uses it. Bodies with letters in theirs
+/// use a fixed key instead.
+fn serial_key(serial: Option<&str>, model: &str) -> u32 {
+    match serial.and_then(|s| s.parse().ok()) {
+        Some(serial) => serial,
+        None if model.ends_with("D50") => 0x22,
+        None => 0x60,
+    }
+}
+
+/// The stream cipher keyed on serial number and shutter count that
+/// protects ShotInfo, ColorBalance and LensData
+#[derive(Clone, Copy)]
+struct Cipher {
+    ci: u8,
+    cj: u8,
+}
+
+impl Cipher {
+    fn new(serial: u32, shutter_count: u32) -> Self {
+        let key = shutter_count.to_le_bytes().iter().fold(0u8, |k, &b| k ^ b);
+        Cipher { ci: XLAT[0][(serial & 0xff) as usize], cj: XLAT[1][key as usize] }
+    }
+
+    /// Encryption and decryption are the same operation
+    fn apply(self, data: &mut [u8]) {
+        let (ci, mut cj, mut ck) = (self.ci, self.cj, 0x60u8);
+        for byte in data {
+            cj = cj.wrapping_add(ci.wrapping_mul(ck));
+            ck = ck.wrapping_add(1);
+            *byte ^= cj;
+        }
+    }
+}
+
+fn version(block: &[u8]) -> Option<String> {
+    let version = block.get(..4)?;
+    version.iter().all(u8::is_ascii_digit).then(|| String::from_utf8_lossy(version).into_owned())
+}
+
+fn shot_info(block: &[u8], cipher: Cipher) -> Option<ShotInfo> {
+    let version = version(block)?;
+    let mut data = block.to_vec();
+    // The D100's "0100" layout is stored in the clear
+    if version.starts_with("02") || version.starts_with("03") || version.starts_with("08") {
+        cipher.apply(&mut data[4..]);
+    }
+    Some(ShotInfo { version, data })
+}
+
+fn lens_data(block: &[u8], cipher: Cipher) -> Option<LensData> {
+    let version = version(block)?;
+    let mut data = block.to_vec();
+    // Field positions of the lens ID number, the distance and focal length
+    let (id, distance, focal) = match version.as_str() {
+        "0100" => (0x06, None, None),
+        "0101" => (0x0b, Some(0x09), Some(0x0a)),
+        "0201" | "0202" | "0203" => {
+            cipher.apply(&mut data[4..]);
+            (0x0b, Some(0x09), Some(0x0a))
+        }
+        "0204" => {
+            cipher.apply(&mut data[4..]);
+            (0x0c, Some(0x0a), Some(0x0b))
+        }
+        // Z-mount bodies ("0800" and later) use a different layout
+        _ => return Some(LensData { version, ..LensData::empty() }),
+    };
+    let byte = |at: usize| data.get(at).copied().filter(|&v| v != 0);
+    // Focal lengths and apertures are logarithmic, in 1/24 stops
+    let focal_length = |v: u8| 5.0 * 2f64.powf(f64::from(v) / 24.0);
+    let aperture = |v: u8| 2f64.powf(f64::from(v) / 24.0);
+    Some(LensData {
+        lens_id_number: byte(id),
+        lens_f_stops: byte(id + 1).map(|v| f64::from(v) / 12.0),
+        min_focal_length: byte(id + 2).map(focal_length),
+        max_focal_length: byte(id + 3).map(focal_length),
+        max_aperture_at_min_focal: byte(id + 4).map(aperture),
+        max_aperture_at_max_focal: byte(id + 5).map(aperture),
+        mcu_version: byte(id + 6),
+        focus_distance: distance.and_then(byte).map(|v| 0.01 * 10f64.powf(f64::from(v) / 40.0)),
+        focal_length: focal.and_then(byte).map(focal_length),
+        version,
+    })
+}
+
+impl LensData {
+    fn empty() -> Self {
+        LensData {
+            version: String::new(),
+            lens_id_number: None,
+            lens_f_stops: None,
+            min_focal_length: None,
+            max_focal_length: None,
+            max_aperture_at_min_focal: None,
+            max_aperture_at_max_focal: None,
+            mcu_version: None,
+            focus_distance: None,
+            focal_length: None,
+        }
+    }
+}
+
+/// Key tables of the cipher, indexed by serial number and shutter count
+const XLAT: [[u8; 256]; 2] = [
+    [
+        0xc1, 0xbf, 0x6d, 0x0d, 0x59, 0xc5, 0x13, 0x9d, 0x83, 0x61, 0x6b, 0x4f, 0xc7, 0x7f, 0x3d, 0x3d, 0x53, 0x59, 0xe3,
+        0xc7, 0xe9, 0x2f, 0x95, 0xa7, 0x95, 0x1f, 0xdf, 0x7f, 0x2b, 0x29, 0xc7, 0x0d, 0xdf, 0x07, 0xef, 0x71, 0x89, 0x3d,
+        0x13, 0x3d, 0x3b, 0x13, 0xfb, 0x0d, 0x89, 0xc1, 0x65, 0x1f, 0xb3, 0x0d, 0x6b, 0x29, 0xe3, 0xfb, 0xef, 0xa3, 0x6b,
+        0x47, 0x7f, 0x95, 0x35, 0xa7, 0x47, 0x4f, 0xc7, 0xf1, 0x59, 0x95, 0x35, 0x11, 0x29, 0x61, 0xf1, 0x3d, 0xb3, 0x2b,
+        0x0d, 0x43, 0x89, 0xc1, 0x9d, 0x9d, 0x89, 0x65, 0xf1, 0xe9, 0xdf, 0xbf, 0x3d, 0x7f, 0x53, 0x97, 0xe5, 0xe9, 0x95,
+        0x17, 0x1d, 0x3d, 0x8b, 0xfb, 0xc7, 0xe3, 0x67, 0xa7, 0x07, 0xf1, 0x71, 0xa7, 0x53, 0xb5, 0x29, 0x89, 0xe5, 0x2b,
+        0xa7, 0x17, 0x29, 0xe9, 0x4f, 0xc5, 0x65, 0x6d, 0x6b, 0xef, 0x0d, 0x89, 0x49, 0x2f, 0xb3, 0x43, 0x53, 0x65, 0x1d,
+        0x49, 0xa3, 0x13, 0x89, 0x59, 0xef, 0x6b, 0xef, 0x65, 0x1d, 0x0b, 0x59, 0x13, 0xe3, 0x4f, 0x9d, 0xb3, 0x29, 0x43,
+        0x2b, 0x07, 0x1d, 0x95, 0x59, 0x59, 0x47, 0xfb, 0xe5, 0xe9, 0x61, 0x47, 0x2f, 0x35, 0x7f, 0x17, 0x7f, 0xef, 0x7f,
+        0x95, 0x95, 0x71, 0xd3, 0xa3, 0x0b, 0x71, 0xa3, 0xad, 0x0b, 0x3b, 0xb5, 0xfb, 0xa3, 0xbf, 0x4f, 0x83, 0x1d, 0xad,
+        0xe9, 0x2f, 0x71, 0x65, 0xa3, 0xe5, 0x07, 0x35, 0x3d, 0x0d, 0xb5, 0xe9, 0xe5, 0x47, 0x3b, 0x9d, 0xef, 0x35, 0xa3,
+        0xbf, 0xb3, 0xdf, 0x53, 0xd3, 0x97, 0x53, 0x49, 0x71, 0x07, 0x35, 0x61, 0x71, 0x2f, 0x43, 0x2f, 0x11, 0xdf, 0x17,
+        0x97, 0xfb, 0x95, 0x3b, 0x7f, 0x6b, 0xd3, 0x25, 0xbf, 0xad, 0xc7, 0xc5, 0xc5, 0xb5, 0x8b, 0xef, 0x2f, 0xd3, 0x07,
+        0x6b, 0x25, 0x49, 0x95, 0x25, 0x49, 0x6d, 0x71, 0xc7,
+    ],
+    [
+        0xa7, 0xbc, 0xc9, 0xad, 0x91, 0xdf, 0x85, 0xe5, 0xd4, 0x78, 0xd5, 0x17, 0x46, 0x7c, 0x29, 0x4c, 0x4d, 0x03, 0xe9,
+        0x25, 0x68, 0x11, 0x86, 0xb3, 0xbd, 0xf7, 0x6f, 0x61, 0x22, 0xa2, 0x26, 0x34, 0x2a, 0xbe, 0x1e, 0x46, 0x14, 0x68,
+        0x9d, 0x44, 0x18, 0xc2, 0x40, 0xf4, 0x7e, 0x5f, 0x1b, 0xad, 0x0b, 0x94, 0xb6, 0x67, 0xb4, 0x0b, 0xe1, 0xea, 0x95,
+        0x9c, 0x66, 0xdc, 0xe7, 0x5d, 0x6c, 0x05, 0xda, 0xd5, 0xdf, 0x7a, 0xef, 0xf6, 0xdb, 0x1f, 0x82, 0x4c, 0xc0, 0x68,
+        0x47, 0xa1, 0xbd, 0xee, 0x39, 0x50, 0x56, 0x4a, 0xdd, 0xdf, 0xa5, 0xf8, 0xc6, 0xda, 0xca, 0x90, 0xca, 0x01, 0x42,
+        0x9d, 0x8b, 0x0c, 0x73, 0x43, 0x75, 0x05, 0x94, 0xde, 0x24, 0xb3, 0x80, 0x34, 0xe5, 0x2c, 0xdc, 0x9b, 0x3f, 0xca,
+        0x33, 0x45, 0xd0, 0xdb, 0x5f, 0xf5, 0x52, 0xc3, 0x21, 0xda, 0xe2, 0x22, 0x72, 0x6b, 0x3e, 0xd0, 0x5b, 0xa8, 0x87,
+        0x8c, 0x06, 0x5d, 0x0f, 0xdd, 0x09, 0x19, 0x93, 0xd0, 0xb9, 0xfc, 0x8b, 0x0f, 0x84, 0x60, 0x33, 0x1c, 0x9b, 0x45,
+        0xf1, 0xf0, 0xa3, 0x94, 0x3a, 0x12, 0x77, 0x33, 0x4d, 0x44, 0x78, 0x28, 0x3c, 0x9e, 0xfd, 0x65, 0x57, 0x16, 0x94,
+        0x6b, 0xfb, 0x59, 0xd0, 0xc8, 0x22, 0x36, 0xdb, 0xd2, 0x63, 0x98, 0x43, 0xa1, 0x04, 0x87, 0x86, 0xf7, 0xa6, 0x26,
+        0xbb, 0xd6, 0x59, 0x4d, 0xbf, 0x6a, 0x2e, 0xaa, 0x2b, 0xef, 0xe6, 0x78, 0xb6, 0x4e, 0xe0, 0x2f, 0xdc, 0x7c, 0xbe,
+        0x57, 0x19, 0x32, 0x7e, 0x2a, 0xd0, 0xb8, 0xba, 0x29, 0x00, 0x3c, 0x52, 0x7d, 0xa8, 0x49, 0x3b, 0x2d, 0xeb, 0x25,
+        0x49, 0xfa, 0xa3, 0xaa, 0x39, 0xa7, 0xc5, 0xa7, 0x50, 0x11, 0x36, 0xfb, 0xc6, 0x67, 0x4a, 0xf5, 0xa5, 0x12, 0x65,
+        0x7e, 0xb0, 0xdf, 0xaf, 0x4e, 0xb3, 0x61, 0x7f, 0x2f,
+    ],
+];
+
+/// Encrypts or decrypts `data` as a body with this serial number and
+/// shutter count would
+#[cfg(test)]
+pub(crate) fn crypt(data: &mut [u8], serial: u32, shutter_count: u32) {
+    Cipher::new(serial, shutter_count).apply(data);
+}
@@ -0,0 +1,104 @@
+// bindings/rust/zrraw/src/makernote/olympus.rs - Olympus and OM System makernotes
+use super::{byte_order, bytes, note_relative, number, read_directory, shorts, text, DriveMode, FocusMode, MakernoteTag, Stabilization};
+use crate::exif::{MakernoteLocation, Tiff};
+
+const EQUIPMENT: u16 = 0x2010;
+const CAMERA_SETTINGS: u16 = 0x2020;
+
+// Equipment
+const SERIAL_NUMBER: u16 = 0x0101;
+const LENS_TYPE: u16 = 0x0201;
+const LENS_SERIAL_NUMBER: u16 = 0x0202;
+const LENS_MODEL: u16 = 0x0203;
+
+// CameraSettings
+const FOCUS_MODE: u16 = 0x0301;
+const PICTURE_MODE: u16 = 0x0520;
+const DRIVE_MODE: u16 = 0x0600;
+const IMAGE_STABILIZATION: u16 = 0x0604;
+
+/// Olympus' makernote. "OLYMPUS\0" and "OM SYSTEM" notes carry their own
+/// byte order and count offsets from their first byte; older "OLYMP\0"
+/// notes count them from the TIFF header. Most settings live in the
+/// Equipment and CameraSettings sub-directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Olympus {
+    pub serial_number: Option<String>,
+    /// Make, model and sub-model bytes of LensType, as 0xMMmmss
+    pub lens_type: Option<u32>,
+    pub lens_model: Option<String>,
+    pub lens_serial_number: Option<String>,
+    pub focus_mode: Option<FocusMode>,
+    pub drive_mode: Option<DriveMode>,
+    pub stabilization: Option<Stabilization>,
+    /// 1 vivid, 2 natural, 3 muted, 4 portrait, 5 i-Enhance, 256 monotone, ...
+    pub picture_mode: Option<u16>,
+    pub tags: Vec<MakernoteTag>,
+    pub equipment: Vec<MakernoteTag>,
+    pub camera_settings: Vec<MakernoteTag>,
+}
+
+impl Olympus {
+    pub(crate) fn read(location: &MakernoteLocation) -> Option<Olympus> {
+        let note = location.bytes();
+        let (tiff, ifd) = if note.starts_with(b"OLYMPUS\0") {
+            (note_relative(location, byte_order(note, 8)?), 12)
+        } else if note.starts_with(b"OM SYSTEM\0") {
+            (note_relative(location, byte_order(note, 12)?), 16)
+        } else if note.starts_with(b"OLYMP\0") {
+            (Tiff::with_order(location.tiff, location.big_endian), location.start + 8)
+        } else {
+            return None;
+        };
+        let tags = read_directory(&tiff, ifd);
+        if tags.is_empty() {
+            return None;
+        }
+        // Sub-directories are IFD or UNDEFINED entries, their offset in the
+        // value field either way
+        let directory = |id: u16| {
+            let offset = tiff.find(ifd, id).and_then(|entry| tiff.u32(entry + 8));
+            offset.map(|offset| read_directory(&tiff, offset as usize)).unwrap_or_default()
+        };
+        let equipment = directory(EQUIPMENT);
+        let camera_settings = directory(CAMERA_SETTINGS);
+
+        let focus_mode = number(&camera_settings, FOCUS_MODE).map(|mode| match mode {
+            0 => FocusMode::Single,
+            // Sequential shooting AF refocuses between frames
+            1 | 2 => FocusMode::Continuous,
+            10 => FocusMode::Manual,
+            other => FocusMode::Other(other),
+        });
+        let drive_mode = number(&camera_settings, DRIVE_MODE).map(|mode| match mode {
+            0 => DriveMode::Single,
+            1 => DriveMode::Continuous,
+            // Exposure, white balance or both
+            2..=4 => DriveMode::Bracketing,
+            other => DriveMode::Other(other),
+        });
+        let stabilization = number(&camera_settings, IMAGE_STABILIZATION).map(|mode| match mode {
+            0 => Stabilization::Off,
+            // Modes 1 to 4: all axes, vertical, horizontal, automatic
+            1..=4 => Stabilization::Body,
+            other => Stabilization::Other(other),
+        });
+        let lens_type = bytes(&equipment, LENS_TYPE)
+            .filter(|lens| lens.len() >= 4)
+            .map(|lens| u32::from(lens[0]) << 16 | u32::from(lens[2]) << 8 | u32::from(lens[3]));
+
+        Some(Olympus {
+            serial_number: text(&equipment, SERIAL_NUMBER),
+            lens_type,
+            lens_model: text(&equipment, LENS_MODEL),
+            lens_serial_number: text(&equipment, LENS_SERIAL_NUMBER),
+            focus_mode,
+            drive_mode,
+            stabilization,
+            picture_mode: shorts(&camera_settings, PICTURE_MODE).and_then(|mode| mode.first().copied()),
+            tags,
+            equipment,
+            camera_settings,
+        })
+    }
+}
@@ -0,0 +1,87 @@
+// bindings/rust/zrraw/src/makernote/panasonic.rs - Panasonic makernotes
+use super::{number, text, tiff_relative, DriveMode, FocusMode, MakernoteTag, Stabilization};
+use crate::exif::MakernoteLocation;
+
+const IMAGE_QUALITY: u16 = 0x0001;
+const FOCUS_MODE: u16 = 0x0007;
+const IMAGE_STABILIZATION: u16 = 0x001A;
+const INTERNAL_SERIAL_NUMBER: u16 = 0x0025;
+const BURST_MODE: u16 = 0x002A;
+const SEQUENCE_NUMBER: u16 = 0x002B;
+const SELF_TIMER: u16 = 0x002E;
+const LENS_TYPE: u16 = 0x0051;
+const LENS_SERIAL_NUMBER: u16 = 0x0052;
+const INTELLIGENT_D_RANGE: u16 = 0x0079;
+const PHOTO_STYLE: u16 = 0x0089;
+
+/// Panasonic's makernote: "Panasonic\0\0\0" and an IFD whose offsets count
+/// from the TIFF header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Panasonic {
+    pub image_quality: Option<u16>,
+    pub internal_serial_number: Option<String>,
+    /// The lens name, as Panasonic records no lens ID
+    pub lens_type: Option<String>,
+    pub lens_serial_number: Option<String>,
+    pub focus_mode: Option<FocusMode>,
+    pub drive_mode: Option<DriveMode>,
+    /// Position within a burst, 0 outside one
+    pub sequence_number: Option<u32>,
+    pub stabilization: Option<Stabilization>,
+    /// Intelligent D-Range: 0 off, 1 low, 2 standard, 3 high
+    pub intelligent_d_range: Option<u16>,
+    /// Photo Style: 1 standard, 2 vivid, 3 natural, 4 monochrome, ...
+    pub photo_style: Option<u16>,
+    pub tags: Vec<MakernoteTag>,
+}
+
+impl Panasonic {
+    pub(crate) fn read(location: &MakernoteLocation) -> Option<Panasonic> {
+        if !location.bytes().starts_with(b"Panasonic\0") {
+            return None;
+        }
+        let tags = tiff_relative(location, 12);
+        if tags.is_empty() {
+            return None;
+        }
+
+        let focus_mode = number(&tags, FOCUS_MODE).map(|mode| match mode {
+            2 => FocusMode::Manual,
+            6 => FocusMode::Single,
+            5 | 7 => FocusMode::Continuous,
+            // Auto and AF-F, which switches to tracking on its own
+            1 | 8 => FocusMode::Automatic,
+            other => FocusMode::Other(other),
+        });
+        let self_timer = number(&tags, SELF_TIMER).is_some_and(|timer| (2..=4).contains(&timer));
+        let drive_mode = number(&tags, BURST_MODE).map(|mode| match mode {
+            0 if self_timer => DriveMode::SelfTimer,
+            0 => DriveMode::Single,
+            1 | 4 => DriveMode::Continuous,
+            // Exposure, focus, white balance and aperture bracketing
+            2 | 3 | 8 | 18 => DriveMode::Bracketing,
+            other => DriveMode::Other(other),
+        });
+        let stabilization = number(&tags, IMAGE_STABILIZATION).map(|mode| match mode {
+            3 => Stabilization::Off,
+            2 | 4 | 5 => Stabilization::Lens,
+            6 | 7 => Stabilization::Body,
+            9..=12 => Stabilization::Dual,
+            other => Stabilization::Other(other),
+        });
+
+        Some(Panasonic {
+            image_quality: number(&tags, IMAGE_QUALITY).map(|q| q as u16),
+            internal_serial_number: text(&tags, INTERNAL_SERIAL_NUMBER),
+            lens_type: text(&tags, LENS_TYPE),
+            lens_serial_number: text(&tags, LENS_SERIAL_NUMBER),
+            focus_mode,
+            drive_mode,
+            sequence_number: number(&tags, SEQUENCE_NUMBER),
+            stabilization,
+            intelligent_d_range: number(&tags, INTELLIGENT_D_RANGE).map(|v| v as u16),
+            photo_style: number(&tags, PHOTO_STYLE).map(|v| v as u16),
+            tags,
+        })
+    }
+}
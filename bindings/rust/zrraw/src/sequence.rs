@@ -0,0 +1,439 @@
+// bindings/rust/zrraw/src/sequence.rs - CinemaDNG and Magic Lantern MLV sequences
+use crate::{
+    dng, extended::ExtendedMetadata, CfaPattern, ExposureTime, FNumber, MagicLanternFormat, Orientation, ProcessedRawFile, ProcessingParams,
+    RawFormat, RawMetadata, SensorData, Timecode, ZrRaw, ZrRawError,
+};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// MLV video classes; the high bits flag compressed frames
+const MLV_VIDEO_CLASS_RAW: u16 = 0x01;
+const MLV_VIDEO_CLASS_FLAGS: u16 = 0xF0;
+
+const MLV_HEADER_SIZE: usize = 52;
+const MLV_BLOCK_HEADER_SIZE: usize = 16;
+const VIDF_HEADER_SIZE: u64 = 32;
+const RAWI_SIZE: usize = 180;
+/// .M00 to .M99 continue a clip split across files
+const MAX_MLV_CHUNKS: usize = 101;
+
+/// One frame of a sequence, decoded to sensor data
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Position in the sequence, from 0
+    pub index: usize,
+    pub metadata: RawMetadata,
+    pub sensor: SensorData,
+    /// The frame's own file for CinemaDNG
+    dng: Option<Vec<u8>>,
+}
+
+impl Frame {
+    /// Renders the frame like `ZrRaw::process_file`. MLV frames go through
+    /// the pipeline wrapped in a DNG; the result keeps their MLV metadata.
+    pub fn process(&self, zrraw: &ZrRaw, params: ProcessingParams) -> Result<ProcessedRawFile, ZrRawError> {
+        match &self.dng {
+            Some(data) => zrraw.process_file(data, params),
+            None => {
+                let data = dng::DngWriter::new(&self.sensor, &self.metadata).to_bytes()?;
+                let mut result = zrraw.process_file(&data, params)?;
+                result.metadata = self.metadata.clone();
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// Frames of a CinemaDNG directory (one DNG per frame) or a Magic Lantern
+/// MLV clip. Opening only lists the files or reads the MLV block headers;
+/// each frame is read from disk when it is requested.
+pub struct RawSequence<'a> {
+    zrraw: &'a ZrRaw,
+    source: Source,
+}
+
+enum Source {
+    CinemaDng(Vec<PathBuf>),
+    Mlv(Box<Mlv>),
+}
+
+impl<'a> RawSequence<'a> {
+    /// Opens a directory as CinemaDNG, anything else as an MLV file
+    pub fn open(zrraw: &'a ZrRaw, path: impl AsRef<Path>) -> Result<Self, ZrRawError> {
+        let path = path.as_ref();
+        let source = if path.is_dir() {
+            Source::CinemaDng(dng_files(path)?)
+        } else {
+            Source::Mlv(Box::new(Mlv::open(path)?))
+        };
+        Ok(RawSequence { zrraw, source })
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.source {
+            Source::CinemaDng(files) => files.len(),
+            Source::Mlv(mlv) => mlv.frames.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads and decodes one frame
+    pub fn frame(&mut self, index: usize) -> Result<Frame, ZrRawError> {
+        if index >= self.len() {
+            return Err(ZrRawError::InvalidInput);
+        }
+        match &mut self.source {
+            Source::CinemaDng(files) => {
+                let data = std::fs::read(&files[index]).map_err(|_| ZrRawError::IoError)?;
+                let metadata = self.zrraw.extract_metadata(&data)?;
+                let sensor = self.zrraw.extract_sensor_data(&data)?;
+                Ok(Frame { index, metadata, sensor, dng: Some(data) })
+            }
+            Source::Mlv(mlv) => {
+                let (metadata, sensor) = mlv.frame(index)?;
+                Ok(Frame { index, metadata, sensor, dng: None })
+            }
+        }
+    }
+
+    /// Iterates the frames in order, decoding each as it is reached
+    pub fn frames(&mut self) -> Frames<'_, 'a> {
+        Frames { sequence: self, next: 0 }
+    }
+}
+
+pub struct Frames<'s, 'a> {
+    sequence: &'s mut RawSequence<'a>,
+    next: usize,
+}
+
+impl Iterator for Frames<'_, '_> {
+    type Item = Result<Frame, ZrRawError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.sequence.len() {
+            return None;
+        }
+        let frame = self.sequence.frame(self.next);
+        self.next += 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.sequence.len().saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Frames<'_, '_> {}
+
+/// The directory's .dng files in name order, which is frame order for
+/// every CinemaDNG writer we know of
+fn dng_files(dir: &Path) -> Result<Vec<PathBuf>, ZrRawError> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|_| ZrRawError::IoError)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("dng")))
+        .collect();
+    if files.is_empty() {
+        return Err(ZrRawError::InvalidInput);
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Where a VIDF block's frame lives, with the exposure in effect for it
+struct MlvFrame {
+    chunk: usize,
+    offset: u64,
+    size: u64,
+    number: u32,
+    exposure: Exposure,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Exposure {
+    iso: u32,
+    shutter_us: u64,
+}
+
+/// Clip-wide settings from the MLVI, RAWI, IDNT, LENS and RTCI blocks
+#[derive(Default)]
+struct MlvInfo {
+    fps_num: u32,
+    fps_den: u32,
+    width: u32,
+    height: u32,
+    bits: u32,
+    black: u32,
+    white: u32,
+    cfa: [u8; 4],
+    /// ColorMatrix1: XYZ to camera
+    xyz_to_cam: Option<[f64; 9]>,
+    camera: String,
+    lens: String,
+    focal_length: f32,
+    aperture: FNumber,
+    /// Recording start as seconds into the day
+    start: u32,
+}
+
+struct Mlv {
+    chunks: Vec<BufReader<File>>,
+    frames: Vec<MlvFrame>,
+    info: MlvInfo,
+}
+
+impl Mlv {
+    fn open(path: &Path) -> Result<Mlv, ZrRawError> {
+        let mut mlv = Mlv { chunks: Vec::new(), frames: Vec::new(), info: MlvInfo::default() };
+        let mut exposure = Exposure::default();
+        let mut raw_info = false;
+
+        for chunk in 0..MAX_MLV_CHUNKS {
+            let chunk_path = if chunk == 0 { path.to_path_buf() } else { path.with_extension(format!("M{:02}", chunk - 1)) };
+            let file = match File::open(&chunk_path) {
+                Ok(file) => file,
+                Err(_) if chunk > 0 => break,
+                Err(_) => return Err(ZrRawError::IoError),
+            };
+            let mut reader = BufReader::new(file);
+            raw_info |= mlv.scan(&mut reader, chunk, &mut exposure)?;
+            mlv.chunks.push(reader);
+        }
+
+        if !raw_info {
+            return Err(ZrRawError::CorruptedData);
+        }
+        mlv.frames.sort_by_key(|f| f.number);
+        Ok(mlv)
+    }
+
+    /// Indexes one file's blocks; returns whether it held a RAWI block
+    fn scan(&mut self, reader: &mut BufReader<File>, chunk: usize, exposure: &mut Exposure) -> Result<bool, ZrRawError> {
+        let mut header = [0u8; MLV_HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(|_| ZrRawError::UnsupportedFormat)?;
+        if &header[0..4] != b"MLVI" {
+            return Err(ZrRawError::UnsupportedFormat);
+        }
+        let video_class = u16_at(&header, 32);
+        if video_class & MLV_VIDEO_CLASS_FLAGS != 0 {
+            return Err(ZrRawError::UnsupportedCompression(video_class as u32));
+        }
+        if video_class & !MLV_VIDEO_CLASS_FLAGS != MLV_VIDEO_CLASS_RAW {
+            return Err(ZrRawError::UnsupportedFormat);
+        }
+        if chunk == 0 {
+            self.info.fps_num = u32_at(&header, 44);
+            self.info.fps_den = u32_at(&header, 48);
+        }
+
+        let mut raw_info = false;
+        let mut position = u32_at(&header, 4) as u64;
+        let file_len = reader.get_ref().metadata().map_err(|_| ZrRawError::IoError)?.len();
+        while position + MLV_BLOCK_HEADER_SIZE as u64 <= file_len {
+            reader.seek(SeekFrom::Start(position)).map_err(|_| ZrRawError::IoError)?;
+            let mut block = [0u8; MLV_BLOCK_HEADER_SIZE];
+            reader.read_exact(&mut block).map_err(|_| ZrRawError::IoError)?;
+            let size = u32_at(&block, 4) as u64;
+            if size < MLV_BLOCK_HEADER_SIZE as u64 || position + size > file_len {
+                return Err(ZrRawError::CorruptedData);
+            }
+
+            match &block[0..4] {
+                b"VIDF" => {
+                    let mut vidf = [0u8; 16];
+                    reader.read_exact(&mut vidf).map_err(|_| ZrRawError::IoError)?;
+                    let skip = VIDF_HEADER_SIZE + u32_at(&vidf, 12) as u64;
+                    if skip > size {
+                        return Err(ZrRawError::CorruptedData);
+                    }
+                    self.frames.push(MlvFrame {
+                        chunk,
+                        offset: position + skip,
+                        size: size - skip,
+                        number: u32_at(&vidf, 0),
+                        exposure: *exposure,
+                    });
+                }
+                tag @ (b"RAWI" | b"EXPO" | b"IDNT" | b"LENS" | b"RTCI") => {
+                    let mut body = vec![0u8; size as usize];
+                    body[..MLV_BLOCK_HEADER_SIZE].copy_from_slice(&block);
+                    reader.read_exact(&mut body[MLV_BLOCK_HEADER_SIZE..]).map_err(|_| ZrRawError::IoError)?;
+                    match tag {
+                        b"RAWI" => {
+                            self.info.read_rawi(&body)?;
+                            raw_info = true;
+                        }
+                        b"EXPO" if body.len() >= 40 => {
+                            *exposure = Exposure { iso: u32_at(&body, 20), shutter_us: u64_at(&body, 32) };
+                        }
+                        b"IDNT" if body.len() >= 48 => self.info.camera = c_string(&body[16..48]),
+                        b"LENS" if body.len() >= 64 => {
+                            self.info.focal_length = u16_at(&body, 16) as f32;
+                            self.info.aperture = FNumber::new(u16_at(&body, 20) as u32, 100);
+                            self.info.lens = c_string(&body[32..64]);
+                        }
+                        b"RTCI" if body.len() >= 22 => {
+                            let (sec, min, hour) = (u16_at(&body, 16), u16_at(&body, 18), u16_at(&body, 20));
+                            self.info.start = hour as u32 * 3600 + min as u32 * 60 + sec as u32;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+            position += size;
+        }
+        Ok(raw_info)
+    }
+
+    fn frame(&mut self, index: usize) -> Result<(RawMetadata, SensorData), ZrRawError> {
+        let info = &self.info;
+        let frame = &self.frames[index];
+        let pixels = info.width as usize * info.height as usize;
+        let packed = (pixels * info.bits as usize).div_ceil(8);
+        if frame.size < packed as u64 {
+            return Err(ZrRawError::CorruptedData);
+        }
+
+        let reader = &mut self.chunks[frame.chunk];
+        reader.seek(SeekFrom::Start(frame.offset)).map_err(|_| ZrRawError::IoError)?;
+        // Rounded up to whole words
+        let mut bytes = vec![0u8; packed.div_ceil(2) * 2];
+        reader.read_exact(&mut bytes[..packed]).map_err(|_| ZrRawError::IoError)?;
+
+        let sensor = SensorData {
+            width: info.width,
+            height: info.height,
+            samples_per_pixel: 1,
+            bits_per_sample: info.bits,
+            cfa: CfaPattern { width: 2, height: 2, colors: info.cfa.to_vec() },
+            data: unpack(&bytes, info.bits, pixels),
+        };
+        let first = self.frames[0].number;
+        Ok((info.metadata(frame, frame.number.wrapping_sub(first)), sensor))
+    }
+}
+
+impl MlvInfo {
+    fn read_rawi(&mut self, body: &[u8]) -> Result<(), ZrRawError> {
+        if body.len() < RAWI_SIZE {
+            return Err(ZrRawError::CorruptedData);
+        }
+        // xRes and yRes, then Magic Lantern's raw_info structure
+        self.width = u16_at(body, 16) as u32;
+        self.height = u16_at(body, 18) as u32;
+        self.bits = u32_at(body, 44);
+        self.black = u32_at(body, 48);
+        self.white = u32_at(body, 52);
+        if self.width == 0 || self.height == 0 || !(8..=16).contains(&self.bits) {
+            return Err(ZrRawError::CorruptedData);
+        }
+
+        // DNG CFAPattern bytes, little-endian
+        let cfa = u32_at(body, 96).to_le_bytes();
+        self.cfa = if cfa.iter().all(|&c| c <= 2) && cfa != [0; 4] { cfa } else { [0, 1, 1, 2] };
+
+        let mut matrix = [0.0f64; 9];
+        for (i, m) in matrix.iter_mut().enumerate() {
+            let num = u32_at(body, 104 + i * 8) as i32;
+            let den = u32_at(body, 108 + i * 8) as i32;
+            *m = if den != 0 { num as f64 / den as f64 } else { 0.0 };
+        }
+        self.xyz_to_cam = matrix.iter().any(|&v| v != 0.0).then_some(matrix);
+        Ok(())
+    }
+
+    fn metadata(&self, frame: &MlvFrame, elapsed: u32) -> RawMetadata {
+        let frame_rate = if self.fps_den > 0 { self.fps_num as f32 / self.fps_den as f32 } else { 0.0 };
+        let color_matrix = self
+            .xyz_to_cam
+            .and_then(dng::invert_3x3)
+            .map(|m| m.map(|v| v as f32))
+            .unwrap_or([0.0; 9]);
+
+        RawMetadata {
+            format: RawFormat::MagicLantern(MagicLanternFormat::MLV),
+            width: self.width,
+            height: self.height,
+            orientation: Orientation::Normal,
+            make: "Canon".to_string(),
+            model: self.camera.clone(),
+            lens: self.lens.clone(),
+            iso: frame.exposure.iso,
+            shutter_speed: ExposureTime::from_seconds(frame.exposure.shutter_us as f64 / 1_000_000.0),
+            aperture: self.aperture,
+            focal_length: self.focal_length,
+            color_matrix,
+            white_balance: [1.0; 3],
+            black_level: [self.black as f32; 4],
+            white_level: [self.white; 4],
+            baseline_exposure: 0.0,
+            frame_rate,
+            timecode: (frame_rate > 0.0).then(|| timecode(self.start, elapsed, frame_rate)),
+            extended: ExtendedMetadata {
+                lens: (!self.lens.is_empty()).then(|| self.lens.clone()),
+                cfa_pattern: Some(CfaPattern { width: 2, height: 2, colors: self.cfa.to_vec() }),
+                bits_per_sample: Some(self.bits),
+                ..Default::default()
+            },
+            exif: Default::default(),
+            makernote: None,
+            xmp: None,
+        }
+    }
+}
+
+/// Time of day `elapsed` frames after the recording started. MLV has no
+/// drop-frame counting, so NTSC rates count whole frames at the rounded rate.
+fn timecode(start: u32, elapsed: u32, frame_rate: f32) -> Timecode {
+    let fps = (frame_rate.round() as u64).max(1);
+    let total = start as u64 * fps + elapsed as u64;
+    let seconds = total / fps;
+    Timecode {
+        hours: ((seconds / 3600) % 24) as u8,
+        minutes: ((seconds / 60) % 60) as u8,
+        seconds: (seconds % 60) as u8,
+        frames: (total % fps) as u8,
+        drop_frame: false,
+    }
+}
+
+/// Magic Lantern packs samples most-significant-bit first into a stream of
+/// little-endian 16-bit words
+fn unpack(bytes: &[u8], bits: u32, count: usize) -> Vec<u16> {
+    let words: Vec<u16> = bytes.chunks_exact(2).map(|w| u16::from_le_bytes([w[0], w[1]])).collect();
+    let mask = (1u32 << bits) - 1;
+    (0..count)
+        .map(|i| {
+            let bit = i * bits as usize;
+            let word = bit / 16;
+            let high = words[word] as u32;
+            let low = words.get(word + 1).copied().unwrap_or(0) as u32;
+            let shift = 32 - bits - (bit % 16) as u32;
+            (((high << 16 | low) >> shift) & mask) as u16
+        })
+        .collect()
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
@@ -0,0 +1,634 @@
+// bindings/rust/zrraw/src/test_support.rs - Builders for synthetic raw files used by the tests
+
+/// A TIFF field value. Pointer-like variants are laid out by the builder.
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(&'static str),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SRational(Vec<(i32, i32)>),
+    Undefined(Vec<u8>),
+    /// A LONG pointing at a nested IFD (EXIF IFD, SubIFDs with one entry, ...)
+    Ifd(Vec<Entry>),
+    /// LONG offsets to several nested IFDs (SubIFDs)
+    Ifds(Vec<Vec<Entry>>),
+    /// A LONG pointing at an opaque blob, e.g. strip data
+    Offset(Vec<u8>),
+    /// LONG offsets to several blobs (tiles, multiple strips)
+    Offsets(Vec<Vec<u8>>),
+}
+
+pub struct Entry(pub u16, pub Value);
+
+pub struct TiffBuilder {
+    big_endian: bool,
+    magic: u16,
+    buf: Vec<u8>,
+}
+
+impl TiffBuilder {
+    pub fn new(big_endian: bool) -> Self {
+        Self::with_magic(big_endian, 42)
+    }
+
+    pub fn with_magic(big_endian: bool, magic: u16) -> Self {
+        TiffBuilder { big_endian, magic, buf: Vec::new() }
+    }
+
+    /// Serialises a TIFF file whose first IFD holds `ifd0`
+    pub fn build(mut self, ifd0: Vec<Entry>) -> Vec<u8> {
+        self.buf.extend_from_slice(if self.big_endian { b"MM" } else { b"II" });
+        let magic = self.u16_bytes(self.magic);
+        self.buf.extend_from_slice(&magic);
+        self.buf.extend_from_slice(&[0; 4]);
+        let offset = self.write_ifd(ifd0);
+        let bytes = self.u32_bytes(offset);
+        self.buf[4..8].copy_from_slice(&bytes);
+        self.buf
+    }
+
+    fn u16_bytes(&self, v: u16) -> [u8; 2] {
+        if self.big_endian { v.to_be_bytes() } else { v.to_le_bytes() }
+    }
+
+    fn u32_bytes(&self, v: u32) -> [u8; 4] {
+        if self.big_endian { v.to_be_bytes() } else { v.to_le_bytes() }
+    }
+
+    fn align(&mut self) {
+        if self.buf.len() % 2 == 1 {
+            self.buf.push(0);
+        }
+    }
+
+    fn write_ifd(&mut self, mut entries: Vec<Entry>) -> u32 {
+        entries.sort_by_key(|e| e.0);
+        self.align();
+        let start = self.buf.len();
+        let count = self.u16_bytes(entries.len() as u16);
+        self.buf.extend_from_slice(&count);
+        self.buf.resize(start + 2 + entries.len() * 12 + 4, 0);
+
+        for (i, Entry(tag, value)) in entries.into_iter().enumerate() {
+            let (ty, count, payload) = self.encode(value);
+            let pos = start + 2 + i * 12;
+            let tag_bytes = self.u16_bytes(tag);
+            let ty_bytes = self.u16_bytes(ty);
+            let count_bytes = self.u32_bytes(count);
+            self.buf[pos..pos + 2].copy_from_slice(&tag_bytes);
+            self.buf[pos + 2..pos + 4].copy_from_slice(&ty_bytes);
+            self.buf[pos + 4..pos + 8].copy_from_slice(&count_bytes);
+            if payload.len() <= 4 {
+                self.buf[pos + 8..pos + 8 + payload.len()].copy_from_slice(&payload);
+            } else {
+                self.align();
+                let offset = self.u32_bytes(self.buf.len() as u32);
+                self.buf.extend_from_slice(&payload);
+                self.buf[pos + 8..pos + 12].copy_from_slice(&offset);
+            }
+        }
+        start as u32
+    }
+
+    /// Returns (type, count, value bytes), writing nested data first
+    fn encode(&mut self, value: Value) -> (u16, u32, Vec<u8>) {
+        match value {
+            Value::Byte(v) => (1, v.len() as u32, v),
+            Value::Ascii(s) => {
+                let mut v = s.as_bytes().to_vec();
+                v.push(0);
+                (2, v.len() as u32, v)
+            }
+            Value::Short(v) => (3, v.len() as u32, v.iter().flat_map(|x| self.u16_bytes(*x)).collect()),
+            Value::Long(v) => (4, v.len() as u32, v.iter().flat_map(|x| self.u32_bytes(*x)).collect()),
+            Value::Rational(v) => (
+                5,
+                v.len() as u32,
+                v.iter().flat_map(|(n, d)| [self.u32_bytes(*n), self.u32_bytes(*d)].concat()).collect(),
+            ),
+            Value::SRational(v) => (
+                10,
+                v.len() as u32,
+                v.iter().flat_map(|(n, d)| [self.u32_bytes(*n as u32), self.u32_bytes(*d as u32)].concat()).collect(),
+            ),
+            Value::Undefined(v) => (7, v.len() as u32, v),
+            Value::Ifd(entries) => {
+                let offset = self.write_ifd(entries);
+                (4, 1, self.u32_bytes(offset).to_vec())
+            }
+            Value::Ifds(ifds) => {
+                let offsets: Vec<u32> = ifds.into_iter().map(|e| self.write_ifd(e)).collect();
+                (4, offsets.len() as u32, offsets.iter().flat_map(|x| self.u32_bytes(*x)).collect())
+            }
+            Value::Offset(blob) => {
+                self.align();
+                let offset = self.buf.len() as u32;
+                self.buf.extend_from_slice(&blob);
+                (4, 1, self.u32_bytes(offset).to_vec())
+            }
+            Value::Offsets(blobs) => {
+                let mut offsets = Vec::new();
+                for blob in blobs {
+                    self.align();
+                    offsets.push(self.buf.len() as u32);
+                    self.buf.extend_from_slice(&blob);
+                }
+                (4, offsets.len() as u32, offsets.iter().flat_map(|x| self.u32_bytes(*x)).collect())
+            }
+        }
+    }
+}
+
+/// Serialises a lone IFD as if it started at file offset `base`
+pub fn ifd_block(big_endian: bool, base: u32, entries: Vec<Entry>) -> Vec<u8> {
+    let mut builder = TiffBuilder { big_endian, magic: 42, buf: vec![0; base as usize] };
+    builder.write_ifd(entries);
+    builder.buf.split_off(base as usize)
+}
+
+/// A TIFF from `make` whose EXIF IFD holds `makernote`
+pub fn with_makernote(make: &'static str, makernote: Vec<u8>) -> Vec<u8> {
+    TiffBuilder::new(false).build(vec![
+        Entry(0x010F, Value::Ascii(make)),
+        Entry(0x8769, Value::Ifd(vec![Entry(0x927C, Value::Undefined(makernote))])),
+    ])
+}
+
+/// A TIFF from `make` whose makernote is `header` and an IFD whose offsets
+/// count from the TIFF header, laid out once the makernote's own position
+/// is known
+pub fn tiff_relative_makernote(make: &'static str, header: &[u8], entries: impl Fn() -> Vec<Entry>) -> Vec<u8> {
+    let len = header.len() + ifd_block(false, 0, entries()).len();
+    let mut tiff = with_makernote(make, vec![0; len]);
+    let start = crate::exif::makernote_location(&tiff).unwrap().start;
+    let mut note = header.to_vec();
+    note.extend(ifd_block(false, (start + header.len()) as u32, entries()));
+    tiff[start..start + len].copy_from_slice(&note);
+    tiff
+}
+
+/// Sony's SR2 pad cipher; applying it twice restores the input
+pub fn sony_crypt(block: &mut [u8], key: u32) {
+    let mut pad = [0u32; 128];
+    let mut seed = key;
+    for p in pad.iter_mut().take(4) {
+        seed = seed.wrapping_mul(48828125).wrapping_add(1);
+        *p = seed;
+    }
+    pad[3] = pad[3] << 1 | (pad[0] ^ pad[2]) >> 31;
+    for p in 4..127 {
+        pad[p] = (pad[p - 4] ^ pad[p - 2]) << 1 | (pad[p - 3] ^ pad[p - 1]) >> 31;
+    }
+    let mut p = 127;
+    for word in block.chunks_exact_mut(4) {
+        p += 1;
+        pad[(p - 1) & 127] = pad[p & 127] ^ pad[(p + 64) & 127];
+        let value = u32::from_be_bytes([word[0], word[1], word[2], word[3]]) ^ pad[(p - 1) & 127];
+        word.copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Packs samples most-significant-bit first
+pub fn pack_msb(samples: &[u16], bits: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut acc: u64 = 0;
+    let mut n = 0;
+    for &s in samples {
+        acc = (acc << bits) | (s as u64 & ((1 << bits) - 1));
+        n += bits;
+        while n >= 8 {
+            n -= 8;
+            out.push((acc >> n) as u8);
+        }
+    }
+    if n > 0 {
+        out.push((acc << (8 - n)) as u8);
+    }
+    out
+}
+
+/// Wraps a TIFF structure in the APP1 segment of an otherwise empty JPEG
+pub fn jpeg_with_exif(tiff: &[u8]) -> Vec<u8> {
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend_from_slice(&(tiff.len() as u16 + 8).to_be_bytes());
+    jpeg.extend_from_slice(b"Exif\0\0");
+    jpeg.extend_from_slice(tiff);
+    jpeg.extend_from_slice(&[0xFF, 0xD9]);
+    jpeg
+}
+
+/// Lays out a RAF: the big-endian header, then the embedded JPEG, the
+/// directory records and the CFA section
+pub fn raf_file(model: &str, jpeg: &[u8], records: &[(u16, Vec<u8>)], section: &[u8]) -> Vec<u8> {
+    let mut directory = (records.len() as u32).to_be_bytes().to_vec();
+    for (tag, payload) in records {
+        directory.extend_from_slice(&tag.to_be_bytes());
+        directory.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        directory.extend_from_slice(payload);
+    }
+
+    let mut file = vec![0u8; 0x94];
+    file[..16].copy_from_slice(b"FUJIFILMCCD-RAW ");
+    file[16..20].copy_from_slice(b"0201");
+    file[0x1C..0x1C + model.len()].copy_from_slice(model.as_bytes());
+    let mut offset = file.len();
+    for (slot, block) in [jpeg, &directory, section].into_iter().enumerate() {
+        let at = 0x54 + slot * 8;
+        file[at..at + 4].copy_from_slice(&(offset as u32).to_be_bytes());
+        file[at + 4..at + 8].copy_from_slice(&(block.len() as u32).to_be_bytes());
+        offset += block.len();
+    }
+    file.extend_from_slice(jpeg);
+    file.extend_from_slice(&directory);
+    file.extend_from_slice(section);
+    file
+}
+
+/// Fuji lossless compressed data (14-bit) for an all-zero image. Every
+/// residual is zero, so only the adaptive code lengths of the even and odd
+/// contexts of each pass need tracking.
+pub fn fuji_compressed_zeros(width: u16, height: u16, xtrans: bool) -> Vec<u8> {
+    const BLOCK: u16 = 0x300;
+    let blocks = width.div_ceil(BLOCK);
+    let line_width = if xtrans { BLOCK as usize * 2 / 3 } else { BLOCK as usize / 2 };
+    // Even positions (mod 4) at which each pass interpolates its X-Trans red or blue line
+    let interpolated: [&[usize]; 6] = [&[0, 2], &[0, 2], &[0], &[2], &[2], &[0]];
+
+    let mut strips = Vec::new();
+    for _ in 0..blocks {
+        let mut bits = BitSink::default();
+        let mut even = [(256, 1); 3];
+        let mut odd = [(256, 1); 3];
+        for _ in 0..height / 6 {
+            for (pass, rule) in interpolated.iter().enumerate() {
+                let (mut e, mut o) = (0, 1);
+                while e < line_width || o < line_width {
+                    if e < line_width {
+                        let decoded = if xtrans && rule.contains(&(e % 4)) { 1 } else { 2 };
+                        for _ in 0..decoded {
+                            bits.zero_residual(&mut even[pass % 3]);
+                        }
+                        e += 2;
+                    }
+                    if e > 8 {
+                        bits.zero_residual(&mut odd[pass % 3]);
+                        bits.zero_residual(&mut odd[pass % 3]);
+                        o += 2;
+                    }
+                }
+            }
+        }
+        strips.push(bits.finish());
+    }
+
+    let mut out = vec![0x49, 0x53, 1, if xtrans { 16 } else { 0 }, 14];
+    for value in [height, blocks * BLOCK, width, BLOCK] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    out.push(blocks as u8);
+    out.extend_from_slice(&(height / 6).to_be_bytes());
+    for strip in &strips {
+        out.extend_from_slice(&(strip.len() as u32).to_be_bytes());
+    }
+    while out.len() % 16 != 0 {
+        out.push(0);
+    }
+    for strip in strips {
+        out.extend(strip);
+    }
+    out
+}
+
+#[derive(Default)]
+struct BitSink {
+    out: Vec<u8>,
+    acc: u8,
+    count: u32,
+}
+
+impl BitSink {
+    fn push(&mut self, bit: bool) {
+        self.acc = self.acc << 1 | bit as u8;
+        self.count += 1;
+        if self.count == 8 {
+            self.out.push(self.acc);
+            self.acc = 0;
+            self.count = 0;
+        }
+    }
+
+    /// A one bit ending the (empty) unary prefix, then the context's
+    /// current number of low bits, all zero
+    fn zero_residual(&mut self, context: &mut (i32, i32)) {
+        let (value1, value2) = *context;
+        let mut low_bits = 0;
+        if value2 < value1 {
+            while low_bits <= 14 {
+                low_bits += 1;
+                if value2 << low_bits >= value1 {
+                    break;
+                }
+            }
+        }
+        self.push(true);
+        for _ in 0..low_bits {
+            self.push(false);
+        }
+        if context.1 == 0x40 {
+            context.0 >>= 1;
+            context.1 >>= 1;
+        }
+        context.1 += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        while self.count != 0 {
+            self.push(false);
+        }
+        self.out
+    }
+}
+
+/// Lays out an X3F: the header, the given sections, their directory and
+/// the directory offset as the last word of the file
+pub fn x3f_file(columns: u32, rows: u32, white_balance: &str, sections: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut file = b"FOVb".to_vec();
+    file.extend(0x0002_0002u32.to_le_bytes());
+    file.extend([0u8; 16]);
+    for v in [0, columns, rows, 0] {
+        file.extend(v.to_le_bytes());
+    }
+    let mut wb = [0u8; 32];
+    wb[..white_balance.len()].copy_from_slice(white_balance.as_bytes());
+    file.extend(wb);
+
+    let mut entries = Vec::new();
+    for (kind, section) in sections {
+        entries.push((file.len() as u32, section.len() as u32, **kind));
+        file.extend(section);
+    }
+    let directory = file.len() as u32;
+    file.extend(b"SECd");
+    file.extend(0x0002_0000u32.to_le_bytes());
+    file.extend((entries.len() as u32).to_le_bytes());
+    for (offset, length, kind) in entries {
+        file.extend(offset.to_le_bytes());
+        file.extend(length.to_le_bytes());
+        file.extend(kind);
+    }
+    file.extend(directory.to_le_bytes());
+    file
+}
+
+/// A PROP section with UTF-16 names and values
+pub fn x3f_properties(properties: &[(&str, &str)]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut strings: Vec<u16> = Vec::new();
+    for (name, value) in properties {
+        index.extend((strings.len() as u32).to_le_bytes());
+        strings.extend(name.encode_utf16().chain([0]));
+        index.extend((strings.len() as u32).to_le_bytes());
+        strings.extend(value.encode_utf16().chain([0]));
+    }
+    let mut section = b"SECp".to_vec();
+    for v in [0x0002_0000, properties.len() as u32, 0, 0, strings.len() as u32] {
+        section.extend(v.to_le_bytes());
+    }
+    section.extend(index);
+    section.extend(strings.iter().flat_map(|c| c.to_le_bytes()));
+    section
+}
+
+fn camf_entry(kind: u8, name: &str, value: impl FnOnce(u32) -> Vec<u8>) -> Vec<u8> {
+    let mut name = name.as_bytes().to_vec();
+    name.push(0);
+    while !name.len().is_multiple_of(4) {
+        name.push(0);
+    }
+    let value_offset = 20 + name.len() as u32;
+    let value = value(value_offset);
+    let mut entry = b"CMb".to_vec();
+    entry.push(kind);
+    for v in [0, value_offset + value.len() as u32, 20, value_offset] {
+        entry.extend(v.to_le_bytes());
+    }
+    entry.extend(name);
+    entry.extend(value);
+    entry
+}
+
+/// A CAMF matrix entry of 32-bit elements (`element_type` 2 = unsigned,
+/// 3 = float)
+pub fn camf_matrix(name: &str, element_type: u32, dims: &[u32], data: &[u32]) -> Vec<u8> {
+    camf_entry(b'M', name, |value_offset| {
+        let data_offset = value_offset + 12 + dims.len() as u32 * 12;
+        let mut value = Vec::new();
+        for v in [element_type, dims.len() as u32, data_offset] {
+            value.extend(v.to_le_bytes());
+        }
+        for (i, &size) in dims.iter().enumerate() {
+            for v in [size, 0, i as u32] {
+                value.extend(v.to_le_bytes());
+            }
+        }
+        value.extend(data.iter().flat_map(|v| v.to_le_bytes()));
+        value
+    })
+}
+
+/// A CAMF property list entry
+pub fn camf_properties(name: &str, pairs: &[(&str, &str)]) -> Vec<u8> {
+    camf_entry(b'P', name, |value_offset| {
+        let base = value_offset + 8 + pairs.len() as u32 * 8;
+        let mut index = Vec::new();
+        let mut strings = Vec::new();
+        for (key, value) in pairs {
+            for s in [key, value] {
+                index.extend((strings.len() as u32).to_le_bytes());
+                strings.extend(s.as_bytes());
+                strings.push(0);
+            }
+        }
+        let mut value = (pairs.len() as u32).to_le_bytes().to_vec();
+        value.extend(base.to_le_bytes());
+        value.extend(index);
+        value.extend(strings);
+        value
+    })
+}
+
+/// A type 2 CAMF section: the entries XORed with the key's byte stream
+pub fn camf_section(entries: &[Vec<u8>], key: u32) -> Vec<u8> {
+    let mut section = b"SECc".to_vec();
+    for v in [0x0002_0000, 2, 0, 0, 0, key] {
+        section.extend(v.to_le_bytes());
+    }
+    let mut key = key;
+    for byte in entries.concat() {
+        key = (key.wrapping_mul(1597).wrapping_add(51749)) % 244944;
+        let tmp = ((key as u64 * 301593171) >> 24) as u32;
+        section.push(byte ^ ((((key << 8).wrapping_sub(tmp) >> 1).wrapping_add(tmp)) >> 17) as u8);
+    }
+    section
+}
+
+/// A Phase One directory value: a number kept in the entry itself, or bytes
+/// the entry points at
+pub enum IiqValue {
+    Int(u32),
+    Data(Vec<u8>),
+}
+
+/// Lays out an IIQ: a TIFF header, the Phase One block with the entries'
+/// data and then its directory, and an empty IFD0. Offsets in the directory
+/// count from the start of the block.
+pub fn iiq_file(entries: Vec<(u32, IiqValue)>) -> Vec<u8> {
+    iiq_file_with_ifd0(entries, Vec::new())
+}
+
+/// An IIQ as [`iiq_file`] lays it out, with `ifd0` as its TIFF IFD0
+pub fn iiq_file_with_ifd0(entries: Vec<(u32, IiqValue)>, ifd0: Vec<Entry>) -> Vec<u8> {
+    let mut block = b"IIII".to_vec();
+    block.extend(0x5261_7700u32.to_le_bytes());
+    block.extend([0u8; 4]);
+    let mut directory = Vec::new();
+    for (tag, value) in &entries {
+        let (len, data) = match value {
+            IiqValue::Int(v) => (4, *v),
+            IiqValue::Data(bytes) => {
+                let at = block.len() as u32;
+                block.extend(bytes);
+                (bytes.len() as u32, at)
+            }
+        };
+        directory.extend([*tag, 0, len, data].iter().flat_map(|v| v.to_le_bytes()));
+    }
+    while !block.len().is_multiple_of(4) {
+        block.push(0);
+    }
+    let directory_offset = block.len() as u32;
+    block[8..12].copy_from_slice(&directory_offset.to_le_bytes());
+    block.extend((entries.len() as u32).to_le_bytes());
+    block.extend(0u32.to_le_bytes());
+    block.extend(directory);
+
+    let mut file = b"II*\0".to_vec();
+    file.extend((8 + block.len() as u32).to_le_bytes());
+    file.extend(block);
+    let ifd0 = ifd_block(false, file.len() as u32, ifd0);
+    file.extend(ifd0);
+    file
+}
+
+/// An IIQ calibration block holding the given (tag, data) entries
+pub fn iiq_calibration(entries: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut block = b"II".to_vec();
+    block.extend([0u8; 10]);
+    let mut directory = Vec::new();
+    for (tag, data) in entries {
+        directory.extend([*tag, data.len() as u32, block.len() as u32].iter().flat_map(|v| v.to_le_bytes()));
+        block.extend(data);
+    }
+    let directory_offset = block.len() as u32;
+    block[8..12].copy_from_slice(&directory_offset.to_le_bytes());
+    block.extend((entries.len() as u32).to_le_bytes());
+    block.extend(0u32.to_le_bytes());
+    block.extend(directory);
+    block
+}
+
+/// The markers of a one-component lossless JPEG up to the start of the scan,
+/// with a single Huffman table
+pub fn ljpeg_header(width: u16, height: u16, counts: [u8; 16], symbols: &[u8]) -> Vec<u8> {
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xC4];
+    jpeg.extend((19 + symbols.len() as u16).to_be_bytes());
+    jpeg.push(0x00);
+    jpeg.extend(counts);
+    jpeg.extend(symbols);
+    jpeg.extend([0xFF, 0xC3, 0, 11, 16]);
+    jpeg.extend(height.to_be_bytes());
+    jpeg.extend(width.to_be_bytes());
+    jpeg.extend([1, 1, 0x11, 0]);
+    jpeg.extend([0xFF, 0xDA, 0, 8, 1, 1, 0x00, 1, 0, 0]);
+    jpeg
+}
+
+/// Leaf PKTS packets, all nested inside one outer packet
+pub fn leaf_packets(packets: &[(&str, &str)]) -> Vec<u8> {
+    fn packet(name: &str, value: &[u8]) -> Vec<u8> {
+        let mut out = b"PKTS".to_vec();
+        out.extend([0u8; 4]);
+        let mut padded = [0u8; 40];
+        padded[..name.len()].copy_from_slice(name.as_bytes());
+        out.extend(padded);
+        out.extend((value.len() as u32).to_be_bytes());
+        out.extend(value);
+        out
+    }
+    let inner: Vec<u8> = packets.iter().flat_map(|(name, value)| packet(name, value.as_bytes())).collect();
+    packet("CaptProf", &inner)
+}
+
+/// A little-endian CIFF file: the header, then one heap holding the records'
+/// data followed by its record table
+pub fn ciff_file(records: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut file = b"II".to_vec();
+    file.extend(26u32.to_le_bytes());
+    file.extend(b"HEAPCCDR");
+    file.extend(0x0001_0002u32.to_le_bytes());
+    file.resize(26, 0);
+
+    let mut heap = Vec::new();
+    let mut table = (records.len() as u16).to_le_bytes().to_vec();
+    for (kind, data) in records {
+        table.extend(kind.to_le_bytes());
+        table.extend((data.len() as u32).to_le_bytes());
+        table.extend((heap.len() as u32).to_le_bytes());
+        heap.extend(data);
+    }
+    let table_offset = heap.len() as u32;
+    heap.extend(table);
+    heap.extend(table_offset.to_le_bytes());
+    file.extend(heap);
+    file
+}
+
+/// An MRW file: the header and the given blocks, then the image data
+pub fn mrw_file(blocks: &[(&[u8; 4], Vec<u8>)], data: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    for (tag, block) in blocks {
+        header.extend(*tag);
+        header.extend((block.len() as u32).to_be_bytes());
+        header.extend(block);
+    }
+    let mut file = b"\0MRM".to_vec();
+    file.extend((header.len() as u32).to_be_bytes());
+    file.extend(header);
+    file.extend(data);
+    file
+}
+
+/// An MLV clip of uncompressed raw video: the MLVI header, then each block
+/// behind its type, size and timestamp
+pub fn mlv_file(frame_count: u32, fps: (u32, u32), blocks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut file = b"MLVI".to_vec();
+    file.extend(52u32.to_le_bytes());
+    file.extend(b"v2.0\0\0\0\0");
+    file.extend([0; 8]); // file GUID
+    file.extend([0, 0, 1, 0]); // file number and count
+    file.extend([0; 4]); // file flags
+    file.extend(1u16.to_le_bytes()); // video class: raw
+    file.extend(0u16.to_le_bytes());
+    file.extend(frame_count.to_le_bytes());
+    file.extend(0u32.to_le_bytes());
+    file.extend(fps.0.to_le_bytes());
+    file.extend(fps.1.to_le_bytes());
+    for (i, (tag, body)) in blocks.iter().enumerate() {
+        file.extend(*tag);
+        file.extend((body.len() as u32 + 16).to_le_bytes());
+        file.extend((i as u64 * 1000).to_le_bytes());
+        file.extend(body);
+    }
+    file
+}
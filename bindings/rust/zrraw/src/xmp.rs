@@ -0,0 +1,819 @@
+// bindings/rust/zrraw/src/xmp.rs - XMP packets embedded in raws and `.xmp` sidecars
+use crate::exif::{Directory, Exif, ExifValue};
+use crate::ZrRawError;
+use std::path::{Path, PathBuf};
+
+pub const NS_RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+pub const NS_XMP: &str = "http://ns.adobe.com/xap/1.0/";
+pub const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+pub const NS_CRS: &str = "http://ns.adobe.com/camera-raw-settings/1.0/";
+const NS_XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+const TAG_XML_PACKET: u16 = 0x02BC;
+/// Elements nested deeper are refused; real packets stay under ten levels,
+/// and the parser recurses once per level
+const MAX_DEPTH: usize = 256;
+/// Type of the CR3 `uuid` box holding the XMP packet
+const XMP_UUID: [u8; 16] = [
+    0xBE, 0x7A, 0xCF, 0xCB, 0x97, 0xA9, 0x42, 0xE8, 0x9C, 0x71, 0x99, 0x94, 0x91, 0xE3, 0xAF, 0xAC,
+];
+
+const EMPTY_PACKET: &str = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"zrraw\">
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+  <rdf:Description rdf:about=\"\"/>
+ </rdf:RDF>
+</x:xmpmeta>
+";
+
+/// The value of a top-level property
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmpValue {
+    Text(String),
+    /// Items of an rdf:Bag or rdf:Seq
+    Array(Vec<String>),
+    /// Items of an rdf:Alt as (xml:lang, text)
+    LangAlt(Vec<(String, String)>),
+    /// Structures and other shapes, kept in the packet but not decoded
+    Other,
+}
+
+/// Common fields of a packet, as culling and cataloguing tools write them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XmpMetadata {
+    /// xmp:Rating: 1 to 5 stars, 0 for unrated, -1 for rejected
+    pub rating: Option<i32>,
+    /// xmp:Label, the colour label's name such as "Red"
+    pub label: Option<String>,
+    /// dc:subject
+    pub keywords: Vec<String>,
+    /// dc:title in the default language
+    pub title: Option<String>,
+    /// dc:description in the default language
+    pub description: Option<String>,
+    pub develop: DevelopSettings,
+}
+
+/// Camera Raw (`crs:`) develop settings, in process 2012 and later units
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DevelopSettings {
+    pub process_version: Option<String>,
+    /// "As Shot", "Auto", "Custom" or a preset name
+    pub white_balance: Option<String>,
+    /// Kelvin
+    pub temperature: Option<i32>,
+    pub tint: Option<i32>,
+    /// Stops
+    pub exposure: Option<f32>,
+    pub contrast: Option<i32>,
+    pub highlights: Option<i32>,
+    pub shadows: Option<i32>,
+    pub whites: Option<i32>,
+    pub blacks: Option<i32>,
+    pub clarity: Option<i32>,
+    pub vibrance: Option<i32>,
+    pub saturation: Option<i32>,
+    pub crop: Option<Crop>,
+}
+
+/// Crop rectangle as fractions of the unrotated image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub top: f32,
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32,
+    /// Degrees
+    pub angle: f32,
+}
+
+/// An XMP packet. Everything the typed accessors do not touch, including
+/// unknown namespaces, comments and the xpacket wrapper, is written back
+/// as it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Xmp {
+    nodes: Vec<Node>,
+}
+
+impl Default for Xmp {
+    fn default() -> Self {
+        Xmp::new()
+    }
+}
+
+impl Xmp {
+    /// An empty packet with a single rdf:Description
+    pub fn new() -> Xmp {
+        Xmp::parse(EMPTY_PACKET.as_bytes()).expect("empty packet parses")
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Xmp, ZrRawError> {
+        let text = std::str::from_utf8(data).map_err(|_| ZrRawError::ParseError("XMP is not UTF-8".to_string()))?;
+        let text = text.trim_start_matches('\u{feff}').trim_end_matches('\0');
+        let mut parser = Parser { text, pos: 0, depth: 0 };
+        let xmp = Xmp { nodes: parser.nodes(None)? };
+        if xmp.rdf().is_none() {
+            return Err(ZrRawError::ParseError("XMP without rdf:RDF".to_string()));
+        }
+        Ok(xmp)
+    }
+
+    /// The packet a raw file embeds: TIFF tag 700 in IFD0, or the XMP
+    /// `uuid` box of a CR3
+    pub fn embedded(data: &[u8]) -> Option<Xmp> {
+        Xmp::from_file(data, &Exif::read(data))
+    }
+
+    pub(crate) fn from_file(data: &[u8], exif: &Exif) -> Option<Xmp> {
+        let packet = match exif.get(Directory::Ifd0, TAG_XML_PACKET).map(|tag| &tag.value) {
+            Some(ExifValue::Byte(bytes) | ExifValue::Undefined(bytes)) => bytes.as_slice(),
+            Some(ExifValue::Ascii(text)) => text.as_bytes(),
+            _ => bmff_packet(data)?,
+        };
+        Xmp::parse(packet).ok()
+    }
+
+    pub fn read_sidecar(path: impl AsRef<Path>) -> Result<Xmp, ZrRawError> {
+        let data = std::fs::read(path).map_err(|_| ZrRawError::IoError)?;
+        Xmp::parse(&data)
+    }
+
+    /// Writes through a temporary file, so a failed write leaves any
+    /// existing sidecar as it was
+    pub fn write_sidecar(&self, path: impl AsRef<Path>) -> Result<(), ZrRawError> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, self.to_bytes()).map_err(|_| ZrRawError::IoError)?;
+        std::fs::rename(&temp, path).map_err(|_| ZrRawError::IoError)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        write_nodes(&self.nodes, &mut out);
+        out.into_bytes()
+    }
+
+    /// A top-level property, from any rdf:Description, in attribute or
+    /// element form
+    pub fn get(&self, namespace: &str, name: &str) -> Option<XmpValue> {
+        for (path, scope) in self.descriptions() {
+            let description = element(&self.nodes, &path);
+            for attribute in &description.attributes {
+                if scope.is(&attribute.name, namespace, name) {
+                    return Some(XmpValue::Text(attribute.value.clone()));
+                }
+            }
+            for child in description.elements() {
+                let inner = scope.enter(child);
+                if inner.is(&child.name, namespace, name) {
+                    return Some(property_value(child, &inner));
+                }
+            }
+        }
+        None
+    }
+
+    /// A simple text property
+    pub fn text(&self, namespace: &str, name: &str) -> Option<String> {
+        match self.get(namespace, name)? {
+            XmpValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// A language alternative's default text, or a simple property's text
+    pub fn default_text(&self, namespace: &str, name: &str) -> Option<String> {
+        match self.get(namespace, name)? {
+            XmpValue::Text(text) => Some(text),
+            XmpValue::LangAlt(items) => {
+                let default = items.iter().position(|(lang, _)| lang == "x-default").unwrap_or(0);
+                items.into_iter().nth(default).map(|(_, text)| text)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set_text(&mut self, namespace: &str, name: &str, value: &str) {
+        self.remove(namespace, name);
+        let (path, prefix) = self.target(namespace);
+        let description = element_mut(&mut self.nodes, &path);
+        let lead = description.attributes.last().map_or(" ".to_string(), |a| a.lead.clone());
+        description.attributes.push(Attribute { lead, name: format!("{prefix}:{name}"), value: value.to_string() });
+    }
+
+    /// Replaces the property with an rdf:Bag, or an rdf:Seq when `ordered`
+    pub fn set_array(&mut self, namespace: &str, name: &str, items: &[String], ordered: bool) {
+        let container = if ordered { "Seq" } else { "Bag" };
+        let items = items.iter().map(|item| (Vec::new(), item.as_str())).collect::<Vec<_>>();
+        self.set_container(namespace, name, container, &items);
+    }
+
+    /// Replaces the property with an rdf:Alt holding `text` as x-default
+    pub fn set_default_text(&mut self, namespace: &str, name: &str, text: &str) {
+        let lang = Attribute { lead: " ".to_string(), name: "xml:lang".to_string(), value: "x-default".to_string() };
+        let lang = vec![lang];
+        self.set_container(namespace, name, "Alt", &[(lang, text)]);
+    }
+
+    /// Removes the property from every rdf:Description
+    pub fn remove(&mut self, namespace: &str, name: &str) {
+        for (path, scope) in self.descriptions() {
+            let description = element_mut(&mut self.nodes, &path);
+            description.attributes.retain(|attribute| !scope.is(&attribute.name, namespace, name));
+            let mut i = 0;
+            while i < description.children.len() {
+                let matched = matches!(&description.children[i],
+                    Node::Element(child) if scope.enter(child).is(&child.name, namespace, name));
+                if !matched {
+                    i += 1;
+                    continue;
+                }
+                description.children.remove(i);
+                if i > 0 && matches!(&description.children[i - 1], Node::Text(text) if text.trim().is_empty()) {
+                    description.children.remove(i - 1);
+                    i -= 1;
+                }
+            }
+        }
+    }
+
+    pub fn rating(&self) -> Option<i32> {
+        let rating = self.text(NS_XMP, "Rating")?.trim().parse::<f64>().ok()?;
+        Some(rating.round() as i32)
+    }
+
+    pub fn metadata(&self) -> XmpMetadata {
+        let keywords = match self.get(NS_DC, "subject") {
+            Some(XmpValue::Array(items)) => items,
+            Some(XmpValue::Text(text)) if !text.is_empty() => vec![text],
+            _ => Vec::new(),
+        };
+        XmpMetadata {
+            rating: self.rating(),
+            label: self.text(NS_XMP, "Label"),
+            keywords,
+            title: self.default_text(NS_DC, "title"),
+            description: self.default_text(NS_DC, "description"),
+            develop: self.develop_settings(),
+        }
+    }
+
+    /// Writes the typed fields; `None` and empty fields are removed, so
+    /// edit a value read with `metadata` rather than a fresh one
+    pub fn apply(&mut self, metadata: &XmpMetadata) {
+        self.set_or_remove(NS_XMP, "Rating", metadata.rating.map(|rating| rating.to_string()));
+        self.set_or_remove(NS_XMP, "Label", metadata.label.clone());
+        if metadata.keywords.is_empty() {
+            self.remove(NS_DC, "subject");
+        } else {
+            self.set_array(NS_DC, "subject", &metadata.keywords, false);
+        }
+        for (name, text) in [("title", &metadata.title), ("description", &metadata.description)] {
+            match text {
+                Some(text) => self.set_default_text(NS_DC, name, text),
+                None => self.remove(NS_DC, name),
+            }
+        }
+        self.apply_develop_settings(&metadata.develop);
+    }
+
+    fn develop_settings(&self) -> DevelopSettings {
+        let int = |name: &str| self.text(NS_CRS, name)?.trim().trim_start_matches('+').parse::<i32>().ok();
+        let real = |name: &str| self.text(NS_CRS, name)?.trim().trim_start_matches('+').parse::<f32>().ok();
+        let crop = (self.text(NS_CRS, "HasCrop").as_deref() == Some("True")).then(|| Crop {
+            top: real("CropTop").unwrap_or(0.0),
+            left: real("CropLeft").unwrap_or(0.0),
+            bottom: real("CropBottom").unwrap_or(1.0),
+            right: real("CropRight").unwrap_or(1.0),
+            angle: real("CropAngle").unwrap_or(0.0),
+        });
+        DevelopSettings {
+            process_version: self.text(NS_CRS, "ProcessVersion"),
+            white_balance: self.text(NS_CRS, "WhiteBalance"),
+            temperature: int("Temperature"),
+            tint: int("Tint"),
+            exposure: real("Exposure2012"),
+            contrast: int("Contrast2012"),
+            highlights: int("Highlights2012"),
+            shadows: int("Shadows2012"),
+            whites: int("Whites2012"),
+            blacks: int("Blacks2012"),
+            clarity: int("Clarity2012"),
+            vibrance: int("Vibrance"),
+            saturation: int("Saturation"),
+            crop,
+        }
+    }
+
+    fn apply_develop_settings(&mut self, develop: &DevelopSettings) {
+        // Camera Raw writes slider values with an explicit sign
+        let signed = |value: Option<i32>| value.map(|v| if v == 0 { "0".to_string() } else { format!("{v:+}") });
+        self.set_or_remove(NS_CRS, "ProcessVersion", develop.process_version.clone());
+        self.set_or_remove(NS_CRS, "WhiteBalance", develop.white_balance.clone());
+        self.set_or_remove(NS_CRS, "Temperature", develop.temperature.map(|t| t.to_string()));
+        self.set_or_remove(NS_CRS, "Tint", signed(develop.tint));
+        let exposure = develop.exposure.map(|e| if e == 0.0 { "0.00".to_string() } else { format!("{e:+.2}") });
+        self.set_or_remove(NS_CRS, "Exposure2012", exposure);
+        self.set_or_remove(NS_CRS, "Contrast2012", signed(develop.contrast));
+        self.set_or_remove(NS_CRS, "Highlights2012", signed(develop.highlights));
+        self.set_or_remove(NS_CRS, "Shadows2012", signed(develop.shadows));
+        self.set_or_remove(NS_CRS, "Whites2012", signed(develop.whites));
+        self.set_or_remove(NS_CRS, "Blacks2012", signed(develop.blacks));
+        self.set_or_remove(NS_CRS, "Clarity2012", signed(develop.clarity));
+        self.set_or_remove(NS_CRS, "Vibrance", signed(develop.vibrance));
+        self.set_or_remove(NS_CRS, "Saturation", signed(develop.saturation));
+        let crop = develop.crop;
+        self.set_or_remove(NS_CRS, "HasCrop", crop.map(|_| "True".to_string()));
+        self.set_or_remove(NS_CRS, "CropTop", crop.map(|c| c.top.to_string()));
+        self.set_or_remove(NS_CRS, "CropLeft", crop.map(|c| c.left.to_string()));
+        self.set_or_remove(NS_CRS, "CropBottom", crop.map(|c| c.bottom.to_string()));
+        self.set_or_remove(NS_CRS, "CropRight", crop.map(|c| c.right.to_string()));
+        self.set_or_remove(NS_CRS, "CropAngle", crop.map(|c| c.angle.to_string()));
+    }
+
+    fn set_or_remove(&mut self, namespace: &str, name: &str, value: Option<String>) {
+        match value {
+            Some(value) => self.set_text(namespace, name, &value),
+            None => self.remove(namespace, name),
+        }
+    }
+
+    fn set_container(&mut self, namespace: &str, name: &str, container: &str, items: &[(Vec<Attribute>, &str)]) {
+        self.remove(namespace, name);
+        let (path, prefix) = self.target(namespace);
+        let rdf = self.scope_of(&path).prefix(NS_RDF).unwrap_or("rdf").to_string();
+        // Indent one space per level, as Adobe's XMP toolkit does
+        let indent = |depth: usize| Node::Text(format!("\n{}", " ".repeat(depth)));
+        let depth = path.len();
+        let mut list = Element::new(format!("{rdf}:{container}"));
+        for (attributes, text) in items {
+            let mut item = Element::new(format!("{rdf}:li"));
+            item.attributes = attributes.clone();
+            item.children.push(Node::Text(text.to_string()));
+            list.children.extend([indent(depth + 2), Node::Element(item)]);
+        }
+        list.children.push(indent(depth + 1));
+        let mut property = Element::new(format!("{prefix}:{name}"));
+        property.children = vec![indent(depth + 1), Node::Element(list), indent(depth)];
+
+        let description = element_mut(&mut self.nodes, &path);
+        let children = &mut description.children;
+        let end = match children.last() {
+            Some(Node::Text(text)) if text.trim().is_empty() => children.len() - 1,
+            Some(_) => children.len(),
+            None => {
+                children.push(indent(depth - 1));
+                0
+            }
+        };
+        children.splice(end..end, [indent(depth), Node::Element(property)]);
+    }
+
+    /// The first rdf:Description, created if the packet has none, and
+    /// the prefix `namespace` has there, declared if needed
+    fn target(&mut self, namespace: &str) -> (Vec<usize>, String) {
+        let path = match self.descriptions().into_iter().next() {
+            Some((path, _)) => path,
+            None => {
+                let rdf_path = self.rdf().expect("packets have rdf:RDF");
+                let rdf = self.scope_of(&rdf_path).prefix(NS_RDF).unwrap_or("rdf").to_string();
+                let mut description = Element::new(format!("{rdf}:Description"));
+                description.attributes.push(Attribute {
+                    lead: " ".to_string(),
+                    name: format!("{rdf}:about"),
+                    value: String::new(),
+                });
+                let parent = element_mut(&mut self.nodes, &rdf_path);
+                parent.children.push(Node::Element(description));
+                let mut path = rdf_path;
+                path.push(parent.children.len() - 1);
+                path
+            }
+        };
+        let scope = self.scope_of(&path);
+        if let Some(prefix) = scope.prefix(namespace) {
+            return (path, prefix.to_string());
+        }
+        let preferred = match namespace {
+            NS_XMP => "xmp",
+            NS_DC => "dc",
+            NS_CRS => "crs",
+            _ => "ns",
+        };
+        let prefix = std::iter::once(preferred.to_string())
+            .chain((1..).map(|n| format!("{preferred}{n}")))
+            .find(|prefix| scope.uri(prefix).is_none())
+            .expect("unbound prefix");
+        let description = element_mut(&mut self.nodes, &path);
+        let lead = description.attributes.last().map_or(" ".to_string(), |a| a.lead.clone());
+        description.attributes.push(Attribute { lead, name: format!("xmlns:{prefix}"), value: namespace.to_string() });
+        (path, prefix)
+    }
+
+    fn rdf(&self) -> Option<Vec<usize>> {
+        fn find(nodes: &[Node], scope: &Scope, path: &mut Vec<usize>) -> bool {
+            for (i, node) in nodes.iter().enumerate() {
+                let Node::Element(element) = node else { continue };
+                let inner = scope.enter(element);
+                path.push(i);
+                if inner.is(&element.name, NS_RDF, "RDF") || find(&element.children, &inner, path) {
+                    return true;
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = Vec::new();
+        find(&self.nodes, &Scope::default(), &mut path).then_some(path)
+    }
+
+    /// Top-level rdf:Description elements with the namespaces in scope on them
+    fn descriptions(&self) -> Vec<(Vec<usize>, Scope)> {
+        fn walk(nodes: &[Node], scope: &Scope, path: &mut Vec<usize>, out: &mut Vec<(Vec<usize>, Scope)>) {
+            for (i, node) in nodes.iter().enumerate() {
+                let Node::Element(element) = node else { continue };
+                let inner = scope.enter(element);
+                path.push(i);
+                if inner.is(&element.name, NS_RDF, "Description") {
+                    out.push((path.clone(), inner));
+                } else {
+                    walk(&element.children, &inner, path, out);
+                }
+                path.pop();
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.nodes, &Scope::default(), &mut Vec::new(), &mut out);
+        out
+    }
+
+    fn scope_of(&self, path: &[usize]) -> Scope {
+        let mut scope = Scope::default();
+        let mut nodes = &self.nodes;
+        for &i in path {
+            let Node::Element(element) = &nodes[i] else { break };
+            scope = scope.enter(element);
+            nodes = &element.children;
+        }
+        scope
+    }
+}
+
+/// `IMG_0001.CR2` to `IMG_0001.xmp`, the name Lightroom and most culling
+/// tools look for
+pub fn sidecar_path(raw: impl AsRef<Path>) -> PathBuf {
+    raw.as_ref().with_extension("xmp")
+}
+
+/// Payload of the top-level XMP `uuid` box of an ISO base media file
+fn bmff_packet(data: &[u8]) -> Option<&[u8]> {
+    if data.get(4..8)? != b"ftyp" {
+        return None;
+    }
+    let mut pos = 0usize;
+    while pos + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as u64;
+        let (header, size) = match size {
+            0 => (8, (data.len() - pos) as u64),
+            1 => (16, u64::from_be_bytes(data.get(pos + 8..pos + 16)?.try_into().ok()?)),
+            size => (8, size),
+        };
+        if size < header as u64 {
+            return None;
+        }
+        let end = pos.saturating_add(usize::try_from(size).ok()?).min(data.len());
+        if &data[pos + 4..pos + 8] == b"uuid" && data.get(pos + header..pos + header + 16)? == XMP_UUID {
+            return data.get(pos + header + 16..end);
+        }
+        pos = end;
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Element(Element),
+    Text(String),
+    /// Comments, processing instructions and declarations, kept verbatim
+    Markup(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Attribute {
+    /// Whitespace before the name, so rewritten tags keep their layout
+    lead: String,
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    attributes: Vec<Attribute>,
+    /// Whitespace before `>` or `/>`
+    tail: String,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn new(name: String) -> Element {
+        Element { name, attributes: Vec::new(), tail: String::new(), children: Vec::new() }
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn element<'a>(nodes: &'a [Node], path: &[usize]) -> &'a Element {
+    let mut nodes = nodes;
+    let mut found = None;
+    for &i in path {
+        let Node::Element(element) = &nodes[i] else { unreachable!("paths lead through elements") };
+        nodes = &element.children;
+        found = Some(element);
+    }
+    found.expect("non-empty path")
+}
+
+fn element_mut<'a>(nodes: &'a mut [Node], path: &[usize]) -> &'a mut Element {
+    let (&first, rest) = path.split_first().expect("non-empty path");
+    let Node::Element(first) = &mut nodes[first] else { unreachable!("paths lead through elements") };
+    let mut element = first;
+    for &i in rest {
+        let Node::Element(child) = &mut element.children[i] else { unreachable!("paths lead through elements") };
+        element = child;
+    }
+    element
+}
+
+fn property_value(property: &Element, scope: &Scope) -> XmpValue {
+    if let Some(resource) = property.attributes.iter().find(|a| scope.is(&a.name, NS_RDF, "resource")) {
+        return XmpValue::Text(resource.value.clone());
+    }
+    let Some(container) = property.elements().next() else { return XmpValue::Text(property.text()) };
+    let inner = scope.enter(container);
+    let items = container.elements().filter(|item| inner.is(&item.name, NS_RDF, "li"));
+    if inner.is(&container.name, NS_RDF, "Bag") || inner.is(&container.name, NS_RDF, "Seq") {
+        XmpValue::Array(items.map(Element::text).collect())
+    } else if inner.is(&container.name, NS_RDF, "Alt") {
+        let lang = |item: &Element| {
+            let attribute = item.attributes.iter().find(|a| inner.is(&a.name, NS_XML, "lang"));
+            attribute.map_or(String::new(), |a| a.value.clone())
+        };
+        XmpValue::LangAlt(items.map(|item| (lang(item), item.text())).collect())
+    } else {
+        XmpValue::Other
+    }
+}
+
+/// Prefix to namespace bindings in scope, innermost last
+#[derive(Debug, Clone, Default)]
+struct Scope(Vec<(String, String)>);
+
+impl Scope {
+    fn enter(&self, element: &Element) -> Scope {
+        let mut scope = self.clone();
+        for attribute in &element.attributes {
+            if let Some(prefix) = attribute.name.strip_prefix("xmlns:") {
+                scope.0.push((prefix.to_string(), attribute.value.clone()));
+            }
+        }
+        scope
+    }
+
+    fn uri(&self, prefix: &str) -> Option<&str> {
+        if prefix == "xml" {
+            return Some(NS_XML);
+        }
+        self.0.iter().rev().find(|(p, _)| p == prefix).map(|(_, uri)| uri.as_str())
+    }
+
+    fn prefix(&self, uri: &str) -> Option<&str> {
+        let (prefix, _) = self.0.iter().rev().find(|(_, u)| u == uri)?;
+        // A closer binding may have reused the prefix for another namespace
+        (self.uri(prefix) == Some(uri)).then_some(prefix.as_str())
+    }
+
+    /// Whether the qualified name `qname` is `local` in `namespace`
+    fn is(&self, qname: &str, namespace: &str, local: &str) -> bool {
+        match qname.split_once(':') {
+            Some((prefix, name)) => name == local && self.uri(prefix) == Some(namespace),
+            None => false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Elements open at `pos`
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn error(&self, what: &str) -> ZrRawError {
+        ZrRawError::ParseError(format!("XMP: {what} at byte {}", self.pos))
+    }
+
+    /// Consumes up to and including `end`, returning the consumed text
+    fn through(&mut self, end: &str) -> Result<&'a str, ZrRawError> {
+        let length = self.rest().find(end).ok_or_else(|| self.error("unterminated markup"))? + end.len();
+        let start = self.pos;
+        self.pos += length;
+        Ok(&self.text[start..self.pos])
+    }
+
+    fn whitespace(&mut self) -> String {
+        let length = self.rest().len() - self.rest().trim_start().len();
+        let start = self.pos;
+        self.pos += length;
+        self.text[start..self.pos].to_string()
+    }
+
+    fn name(&mut self) -> Result<String, ZrRawError> {
+        let rest = self.rest();
+        let length = rest.find(|c: char| c.is_whitespace() || "/>=".contains(c)).unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += length;
+        Ok(self.text[self.pos - length..self.pos].to_string())
+    }
+
+    /// Nodes up to the end of input, or up to the closing tag of `open`
+    fn nodes(&mut self, open: Option<&str>) -> Result<Vec<Node>, ZrRawError> {
+        let mut nodes = Vec::new();
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return match open {
+                    Some(_) => Err(self.error("unclosed element")),
+                    None => Ok(nodes),
+                };
+            }
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                self.whitespace();
+                if open != Some(name.as_str()) || !self.rest().starts_with('>') {
+                    return Err(self.error("mismatched closing tag"));
+                }
+                self.pos += 1;
+                return Ok(nodes);
+            }
+            let node = if rest.starts_with("<!--") {
+                Node::Markup(self.through("-->")?.to_string())
+            } else if rest.starts_with("<![CDATA[") {
+                let section = self.through("]]>")?;
+                Node::Text(section["<![CDATA[".len()..section.len() - 3].to_string())
+            } else if rest.starts_with("<?") {
+                Node::Markup(self.through("?>")?.to_string())
+            } else if rest.starts_with("<!") {
+                Node::Markup(self.through(">")?.to_string())
+            } else if rest.starts_with('<') {
+                self.pos += 1;
+                Node::Element(self.element()?)
+            } else {
+                let length = rest.find('<').unwrap_or(rest.len());
+                self.pos += length;
+                Node::Text(unescape(&rest[..length]))
+            };
+            nodes.push(node);
+        }
+    }
+
+    fn element(&mut self) -> Result<Element, ZrRawError> {
+        let mut element = Element::new(self.name()?);
+        loop {
+            let lead = self.whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                element.tail = lead;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                element.tail = lead;
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("elements nested too deeply"));
+                }
+                self.depth += 1;
+                element.children = self.nodes(Some(&element.name))?;
+                self.depth -= 1;
+                return Ok(element);
+            }
+            if lead.is_empty() {
+                return Err(self.error("expected whitespace before an attribute"));
+            }
+            let name = self.name()?;
+            self.whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error("expected '='"));
+            }
+            self.pos += 1;
+            self.whitespace();
+            let quote = self.rest().chars().next().filter(|&c| c == '"' || c == '\'');
+            let quote = quote.ok_or_else(|| self.error("expected a quoted value"))?;
+            self.pos += 1;
+            let length = self.rest().find(quote).ok_or_else(|| self.error("unterminated value"))?;
+            let value = unescape(&self.rest()[..length]);
+            self.pos += length + 1;
+            element.attributes.push(Attribute { lead, name, value });
+        }
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                entity => {
+                    let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        // Unknown entities stay as written
+        let (c, length) = decoded.unwrap_or(('&', 1));
+        out.push(c);
+        rest = &rest[length..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(text: &str, attribute: bool, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\n' if attribute => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            '\t' if attribute => out.push_str("&#x9;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn write_nodes(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => escape(text, false, out),
+            Node::Markup(markup) => out.push_str(markup),
+            Node::Element(element) => {
+                out.push('<');
+                out.push_str(&element.name);
+                for attribute in &element.attributes {
+                    out.push_str(&attribute.lead);
+                    out.push_str(&attribute.name);
+                    out.push_str("=\"");
+                    escape(&attribute.value, true, out);
+                    out.push('"');
+                }
+                out.push_str(&element.tail);
+                if element.children.is_empty() {
+                    out.push_str("/>");
+                } else {
+                    out.push('>');
+                    write_nodes(&element.children, out);
+                    out.push_str("</");
+                    out.push_str(&element.name);
+                    out.push('>');
+                }
+            }
+        }
+    }
+}
@@ -58,6 +58,7 @@ fn generateHeader(b: *std.Build) void {
         \\    ZRRAW_OUT_OF_MEMORY = -4,
         \\    ZRRAW_IO_ERROR = -5,
         \\    ZRRAW_CORRUPTED_DATA = -6,
+        \\    ZRRAW_UNSUPPORTED_COMPRESSION = -7,
         \\} ZrRawError;
         \\
         \\// Raw formats
@@ -72,6 +73,16 @@ fn generateHeader(b: *std.Build) void {
         \\    ZRRAW_FORMAT_RW2 = 7,
         \\    ZRRAW_FORMAT_PEF = 8,
         \\    ZRRAW_FORMAT_X3F = 9,
+        \\    ZRRAW_FORMAT_IIQ = 10,
+        \\    ZRRAW_FORMAT_3FR = 11,
+        \\    ZRRAW_FORMAT_FFF = 12,
+        \\    ZRRAW_FORMAT_MOS = 13,
+        \\    ZRRAW_FORMAT_CRW = 14,
+        \\    ZRRAW_FORMAT_MRW = 15,
+        \\    ZRRAW_FORMAT_DCR = 16,
+        \\    ZRRAW_FORMAT_KDC = 17,
+        \\    ZRRAW_FORMAT_SRW = 18,
+        \\    ZRRAW_FORMAT_ERF = 19,
         \\} ZrRawFormat;
         \\
         \\// Orientation values
@@ -91,6 +102,7 @@ fn generateHeader(b: *std.Build) void {
         \\    ZRRAW_DEMOSAIC_FAST = 0,
         \\    ZRRAW_DEMOSAIC_QUALITY = 1,
         \\    ZRRAW_DEMOSAIC_BEST = 2,
+        \\    ZRRAW_DEMOSAIC_MARKESTEIJN = 3,
         \\} ZrRawDemosaic;
         \\
         \\// Processing parameters
@@ -105,6 +117,10 @@ fn generateHeader(b: *std.Build) void {
         \\    bool output_16bit;
         \\} ZrRawProcessParams;
         \\
+        \\// Timecode flags
+        \\#define ZRRAW_TIMECODE_PRESENT 1
+        \\#define ZRRAW_TIMECODE_DROP_FRAME 2
+        \\
         \\// Metadata structure
         \\typedef struct {
         \\    ZrRawFormat format;
@@ -123,7 +139,11 @@ fn generateHeader(b: *std.Build) void {
         \\    float white_balance[3];
         \\    float black_level[4];
         \\    uint32_t white_level[4];
-        \\    uint8_t _reserved[32];
+        \\    float baseline_exposure;
+        \\    float frame_rate;
+        \\    uint8_t timecode[4];
+        \\    uint32_t timecode_flags;
+        \\    uint8_t _reserved[16];
         \\} ZrRawMetadata;
         \\
         \\// Image data structure
@@ -138,11 +158,135 @@ fn generateHeader(b: *std.Build) void {
         \\    uint8_t _reserved[16];
         \\} ZrRawImage;
         \\
+        \\// Sensor data structure
+        \\typedef struct {
+        \\    uint32_t width;
+        \\    uint32_t height;
+        \\    uint32_t samples_per_pixel;
+        \\    uint32_t bits_per_sample;
+        \\    uint32_t cfa_width;
+        \\    uint32_t cfa_height;
+        \\    uint8_t cfa[36];
+        \\    uint16_t* data;
+        \\    size_t data_len;
+        \\    void* _allocator;
+        \\    uint8_t _reserved[16];
+        \\} ZrRawSensorData;
+        \\
+        \\// Semantic mask structure
+        \\typedef struct {
+        \\    uint32_t width;
+        \\    uint32_t height;
+        \\    char name[64];
+        \\    char instance_id[64];
+        \\    uint8_t* data;
+        \\    size_t data_len;
+        \\    void* _allocator;
+        \\    uint8_t _reserved[16];
+        \\} ZrRawMask;
+        \\
+        \\// Extended metadata: "ZRMD", a u16 version and two bytes of padding, then
+        \\// entries of u16 key, u16 type, u32 length and the value (little-endian).
+        \\// Unknown keys and types are to be skipped.
+        \\#define ZRRAW_EXTENDED_VERSION 1
+        \\#define ZRRAW_META_TYPE_STRING 1
+        \\#define ZRRAW_META_TYPE_U32 2
+        \\#define ZRRAW_META_TYPE_F32 3
+        \\#define ZRRAW_META_TYPE_BYTES 4
+        \\#define ZRRAW_META_LENS 1
+        \\#define ZRRAW_META_DATE_TIME_ORIGINAL 2
+        \\#define ZRRAW_META_SUB_SEC_TIME_ORIGINAL 3
+        \\#define ZRRAW_META_OFFSET_TIME_ORIGINAL 4
+        \\#define ZRRAW_META_BODY_SERIAL_NUMBER 5
+        \\#define ZRRAW_META_LENS_SERIAL_NUMBER 6
+        \\#define ZRRAW_META_CROP 7
+        \\#define ZRRAW_META_CFA_PATTERN 8
+        \\#define ZRRAW_META_BITS_PER_SAMPLE 9
+        \\#define ZRRAW_META_COMPRESSION 10
+        \\#define ZRRAW_META_SENSOR_TEMPERATURE 11
+        \\#define ZRRAW_META_CANONICAL_MAKE 12
+        \\#define ZRRAW_META_CANONICAL_MODEL 13
+        \\#define ZRRAW_META_CALIBRATION_ILLUMINANTS 14
+        \\#define ZRRAW_META_COLOR_MATRIX_1 15
+        \\#define ZRRAW_META_COLOR_MATRIX_2 16
+        \\#define ZRRAW_META_COLOR_MATRIX_3 17
+        \\#define ZRRAW_META_CAMERA_CALIBRATION_1 18
+        \\#define ZRRAW_META_CAMERA_CALIBRATION_2 19
+        \\#define ZRRAW_META_CAMERA_CALIBRATION_3 20
+        \\#define ZRRAW_META_FORWARD_MATRIX_1 21
+        \\#define ZRRAW_META_FORWARD_MATRIX_2 22
+        \\#define ZRRAW_META_FORWARD_MATRIX_3 23
+        \\#define ZRRAW_META_ANALOG_BALANCE 24
+        \\#define ZRRAW_META_AS_SHOT_NEUTRAL 25
+        \\#define ZRRAW_META_AS_SHOT_WHITE_XY 26
+        \\
+        \\typedef struct {
+        \\    uint32_t version;
+        \\    uint8_t* data;
+        \\    size_t data_len;
+        \\    void* _allocator;
+        \\    uint8_t _reserved[16];
+        \\} ZrRawExtendedMetadata;
+        \\
+        \\// Embedded preview formats
+        \\typedef enum {
+        \\    ZRRAW_PREVIEW_JPEG = 0,
+        \\    ZRRAW_PREVIEW_RGB8 = 1,
+        \\} ZrRawPreviewFormat;
+        \\
+        \\// Embedded preview or thumbnail; the bytes stay in the caller's buffer
+        \\typedef struct {
+        \\    uint64_t offset;
+        \\    uint64_t length;
+        \\    uint32_t width;
+        \\    uint32_t height;
+        \\    ZrRawPreviewFormat format;
+        \\    ZrRawOrientation orientation;
+        \\    uint8_t _reserved[16];
+        \\} ZrRawPreview;
+        \\
+        \\// Decoded lossless JPEG stream
+        \\typedef struct {
+        \\    uint32_t width;
+        \\    uint32_t height;
+        \\    uint32_t components;
+        \\    uint32_t precision;
+        \\    uint16_t* data;
+        \\    size_t data_len;
+        \\    void* _allocator;
+        \\    uint8_t _reserved[16];
+        \\} ZrRawLjpegImage;
+        \\
+        \\// Colour matrices interpolated for a white point
+        \\typedef struct {
+        \\    float camera_to_xyz[9];
+        \\    float camera_to_srgb[9];
+        \\    float neutral[3];
+        \\    float white_xy[2];
+        \\    float temperature;
+        \\    float tint;
+        \\    uint8_t _reserved[16];
+        \\} ZrRawColorMatrices;
+        \\
         \\// Function declarations
         \\ZrRawError zrraw_detect_format(const uint8_t* data, size_t data_len, ZrRawFormat* format);
         \\ZrRawError zrraw_extract_metadata(const uint8_t* data, size_t data_len, ZrRawMetadata* metadata);
         \\ZrRawError zrraw_process_image(const uint8_t* data, size_t data_len, const ZrRawProcessParams* params, ZrRawImage* result_image, ZrRawMetadata* result_metadata);        
         \\void zrraw_free_image(ZrRawImage* image);
+        \\ZrRawError zrraw_extract_sensor_data(const uint8_t* data, size_t data_len, ZrRawSensorData* sensor);
+        \\void zrraw_free_sensor_data(ZrRawSensorData* sensor);
+        \\ZrRawError zrraw_semantic_mask_count(const uint8_t* data, size_t data_len, uint32_t* count);
+        \\ZrRawError zrraw_extract_semantic_mask(const uint8_t* data, size_t data_len, uint32_t index, ZrRawMask* mask);
+        \\void zrraw_free_semantic_mask(ZrRawMask* mask);
+        \\ZrRawError zrraw_extract_extended_metadata(const uint8_t* data, size_t data_len, ZrRawExtendedMetadata* extended);
+        \\void zrraw_free_extended_metadata(ZrRawExtendedMetadata* extended);
+        \\ZrRawError zrraw_preview_count(const uint8_t* data, size_t data_len, uint32_t* count);
+        \\ZrRawError zrraw_preview_info(const uint8_t* data, size_t data_len, uint32_t index, ZrRawPreview* preview);
+        \\ZrRawError zrraw_ljpeg_decode(const uint8_t* data, size_t data_len, ZrRawLjpegImage* image);
+        \\void zrraw_free_ljpeg_image(ZrRawLjpegImage* image);
+        \\uint32_t zrraw_unsupported_compression(void);
+        \\ZrRawError zrraw_load_camera_database(const uint8_t* data, size_t data_len);
+        \\ZrRawError zrraw_color_matrices(const uint8_t* data, size_t data_len, const float* neutral, float temperature, float tint, ZrRawColorMatrices* matrices);
         \\const char* zrraw_version(void);
         \\uint32_t zrraw_supported_formats(void);
         \\
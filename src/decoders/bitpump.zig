@@ -0,0 +1,211 @@
+// src/decoders/bitpump.zig - Bit readers for packed and entropy-coded sensor data
+const std = @import("std");
+
+/// Reads bits most-significant first. With `jpeg_stuffing` enabled, 0xFF 0x00
+/// pairs are collapsed and a marker ends the stream (further reads yield zeros).
+pub const BitPumpMsb = struct {
+    data: []const u8,
+    pos: usize = 0,
+    buffer: u64 = 0,
+    bits: u32 = 0,
+    jpeg_stuffing: bool = false,
+    marker_hit: bool = false,
+
+    pub fn init(data: []const u8) BitPumpMsb {
+        return BitPumpMsb{ .data = data };
+    }
+
+    pub fn init_jpeg(data: []const u8) BitPumpMsb {
+        return BitPumpMsb{ .data = data, .jpeg_stuffing = true };
+    }
+
+    fn next_byte(self: *BitPumpMsb) u8 {
+        if (self.marker_hit or self.pos >= self.data.len) return 0;
+        const byte = self.data[self.pos];
+        self.pos += 1;
+        if (self.jpeg_stuffing and byte == 0xFF) {
+            if (self.pos < self.data.len and self.data[self.pos] == 0x00) {
+                self.pos += 1;
+            } else {
+                // Leave the marker in place for the caller
+                self.pos -= 1;
+                self.marker_hit = true;
+                return 0;
+            }
+        }
+        return byte;
+    }
+
+    fn fill(self: *BitPumpMsb) void {
+        while (self.bits <= 56) {
+            const byte: u64 = self.next_byte();
+            self.buffer |= byte << @as(u6, @intCast(56 - self.bits));
+            self.bits += 8;
+        }
+    }
+
+    /// Returns the next `n` (at most 32) bits without consuming them
+    pub fn peek_bits(self: *BitPumpMsb, n: u32) u32 {
+        if (n == 0) return 0;
+        if (self.bits < n) self.fill();
+        return @intCast(self.buffer >> @as(u6, @intCast(64 - n)));
+    }
+
+    pub fn skip_bits(self: *BitPumpMsb, n: u32) void {
+        if (n == 0) return;
+        if (self.bits < n) self.fill();
+        self.buffer <<= @as(u6, @intCast(n));
+        self.bits -= n;
+    }
+
+    pub fn get_bits(self: *BitPumpMsb, n: u32) u32 {
+        const value = self.peek_bits(n);
+        self.skip_bits(n);
+        return value;
+    }
+
+    /// Drops buffered bits and continues reading at `offset`
+    pub fn reset(self: *BitPumpMsb, offset: usize) void {
+        self.pos = offset;
+        self.buffer = 0;
+        self.bits = 0;
+        self.marker_hit = false;
+    }
+
+    /// Skips the rest of the current entropy-coded segment and continues after
+    /// the RSTn marker that ends it. Returns false if the marker is not RSTn.
+    pub fn restart(self: *BitPumpMsb) bool {
+        while (!self.marker_hit and self.pos < self.data.len) _ = self.next_byte();
+        if (!self.marker_hit) return false;
+        var marker_pos = self.pos + 1;
+        while (marker_pos < self.data.len and self.data[marker_pos] == 0xFF) marker_pos += 1;
+        if (marker_pos >= self.data.len) return false;
+        const marker = self.data[marker_pos];
+        if (marker < 0xD0 or marker > 0xD7) return false;
+        self.reset(marker_pos + 1);
+        return true;
+    }
+};
+
+/// Reads bits least-significant first, as used by little-endian packers
+pub const BitPumpLsb = struct {
+    data: []const u8,
+    pos: usize = 0,
+    buffer: u64 = 0,
+    bits: u32 = 0,
+
+    pub fn init(data: []const u8) BitPumpLsb {
+        return BitPumpLsb{ .data = data };
+    }
+
+    fn fill(self: *BitPumpLsb) void {
+        while (self.bits <= 56) {
+            var byte: u64 = 0;
+            if (self.pos < self.data.len) {
+                byte = self.data[self.pos];
+                self.pos += 1;
+            }
+            self.buffer |= byte << @as(u6, @intCast(self.bits));
+            self.bits += 8;
+        }
+    }
+
+    pub fn peek_bits(self: *BitPumpLsb, n: u32) u32 {
+        if (n == 0) return 0;
+        if (self.bits < n) self.fill();
+        const mask = (@as(u64, 1) << @as(u6, @intCast(n))) - 1;
+        return @intCast(self.buffer & mask);
+    }
+
+    pub fn skip_bits(self: *BitPumpLsb, n: u32) void {
+        if (n == 0) return;
+        if (self.bits < n) self.fill();
+        self.buffer >>= @as(u6, @intCast(n));
+        self.bits -= n;
+    }
+
+    pub fn get_bits(self: *BitPumpLsb, n: u32) u32 {
+        const value = self.peek_bits(n);
+        self.skip_bits(n);
+        return value;
+    }
+};
+
+/// Reads bits most-significant first from little-endian 32-bit words, the
+/// layout of Phase One and Hasselblad entropy-coded data
+pub const BitPumpMsb32 = struct {
+    data: []const u8,
+    pos: usize = 0,
+    buffer: u64 = 0,
+    bits: u32 = 0,
+
+    pub fn init(data: []const u8) BitPumpMsb32 {
+        return BitPumpMsb32{ .data = data };
+    }
+
+    fn fill(self: *BitPumpMsb32) void {
+        while (self.bits <= 32) {
+            var word: [4]u8 = .{ 0, 0, 0, 0 };
+            const end = @min(self.data.len, self.pos + 4);
+            if (self.pos < end) @memcpy(word[0 .. end - self.pos], self.data[self.pos..end]);
+            self.pos += 4;
+            const value: u64 = std.mem.readInt(u32, &word, .little);
+            self.buffer |= value << @as(u6, @intCast(32 - self.bits));
+            self.bits += 32;
+        }
+    }
+
+    pub fn peek_bits(self: *BitPumpMsb32, n: u32) u32 {
+        if (n == 0) return 0;
+        if (self.bits < n) self.fill();
+        return @intCast(self.buffer >> @as(u6, @intCast(64 - n)));
+    }
+
+    pub fn skip_bits(self: *BitPumpMsb32, n: u32) void {
+        if (n == 0) return;
+        if (self.bits < n) self.fill();
+        self.buffer <<= @as(u6, @intCast(n));
+        self.bits -= n;
+    }
+
+    pub fn get_bits(self: *BitPumpMsb32, n: u32) u32 {
+        const value = self.peek_bits(n);
+        self.skip_bits(n);
+        return value;
+    }
+
+    /// Bytes consumed so far, counting a partly read word as whole
+    pub fn bytes_consumed(self: BitPumpMsb32) usize {
+        const consumed = self.pos * 8 - self.bits;
+        return (consumed + 31) / 32 * 4;
+    }
+};
+
+test "msb pump reads across byte boundaries" {
+    var pump = BitPumpMsb.init(&[_]u8{ 0xAB, 0xCD, 0xEF });
+    try std.testing.expectEqual(@as(u32, 0xABC), pump.get_bits(12));
+    try std.testing.expectEqual(@as(u32, 0xDE), pump.get_bits(8));
+    try std.testing.expectEqual(@as(u32, 0xF), pump.get_bits(4));
+}
+
+test "jpeg pump skips stuffed zero bytes" {
+    var pump = BitPumpMsb.init_jpeg(&[_]u8{ 0xFF, 0x00, 0x12, 0xFF, 0xD9 });
+    try std.testing.expectEqual(@as(u32, 0xFF12), pump.get_bits(16));
+    try std.testing.expectEqual(@as(u32, 0), pump.get_bits(8));
+    try std.testing.expect(pump.marker_hit);
+}
+
+test "lsb pump reads low bits first" {
+    var pump = BitPumpLsb.init(&[_]u8{ 0x21, 0x43 });
+    try std.testing.expectEqual(@as(u32, 0x1), pump.get_bits(4));
+    try std.testing.expectEqual(@as(u32, 0x432), pump.get_bits(12));
+}
+
+test "msb32 pump reads each word from its top bit" {
+    var pump = BitPumpMsb32.init(&[_]u8{ 0x78, 0x56, 0x34, 0x12, 0xCD, 0xAB });
+    try std.testing.expectEqual(@as(u32, 0x123), pump.get_bits(12));
+    try std.testing.expectEqual(@as(u32, 0x45678), pump.get_bits(20));
+    // A short last word is padded with zero bytes at its top
+    try std.testing.expectEqual(@as(u32, 0), pump.get_bits(16));
+    try std.testing.expectEqual(@as(u32, 0xABCD), pump.get_bits(16));
+}
@@ -0,0 +1,171 @@
+// src/decoders/canon.zig - Canon CRW compressed sensor data
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+const huffman = @import("huffman.zig");
+
+/// Codes for the first difference of each block, one per CRW decoder
+/// table: 16 code-length counts followed by the symbols in code order
+const FIRST_TREES = [3][29]u8{
+    .{
+        0, 1, 4, 2, 3, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0x04, 0x03, 0x05, 0x06, 0x02, 0x07, 0x01, 0x08, 0x09, 0x00, 0x0A, 0x0B,
+        0xFF,
+    },
+    .{
+        0, 2, 2, 3, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 0,
+        0x03, 0x02, 0x04, 0x01, 0x05, 0x00, 0x06, 0x07, 0x09, 0x08, 0x0A, 0x0B,
+        0xFF,
+    },
+    .{
+        0, 0, 6, 3, 1, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0x06, 0x05, 0x07, 0x04, 0x08, 0x03, 0x09, 0x02, 0x00, 0x0A, 0x01, 0x0B,
+        0xFF,
+    },
+};
+
+/// Codes for the remaining differences: a zero run in the high nibble and
+/// the difference length in the low nibble
+const SECOND_TREES = [3][180]u8{
+    .{
+        0, 2, 2, 2, 1, 4, 2, 1, 2, 5, 1, 1, 0, 0, 0, 139,
+        0x03, 0x04, 0x02, 0x05, 0x01, 0x06, 0x07, 0x08, 0x12, 0x13, 0x11, 0x14,
+        0x09, 0x15, 0x22, 0x00, 0x21, 0x16, 0x0A, 0xF0, 0x23, 0x17, 0x24, 0x31,
+        0x32, 0x18, 0x19, 0x33, 0x25, 0x41, 0x34, 0x42, 0x35, 0x51, 0x36, 0x37,
+        0x38, 0x29, 0x79, 0x26, 0x1A, 0x39, 0x56, 0x57, 0x28, 0x27, 0x52, 0x55,
+        0x58, 0x43, 0x76, 0x59, 0x77, 0x54, 0x61, 0xF9, 0x71, 0x78, 0x75, 0x96,
+        0x97, 0x49, 0xB7, 0x53, 0xD7, 0x74, 0xB6, 0x98, 0x47, 0x48, 0x95, 0x69,
+        0x99, 0x91, 0xFA, 0xB8, 0x68, 0xB5, 0xB9, 0xD6, 0xF7, 0xD8, 0x67, 0x46,
+        0x45, 0x94, 0x89, 0xF8, 0x81, 0xD5, 0xF6, 0xB4, 0x88, 0xB1, 0x2A, 0x44,
+        0x72, 0xD9, 0x87, 0x66, 0xD4, 0xF5, 0x3A, 0xA7, 0x73, 0xA9, 0xA8, 0x86,
+        0x62, 0xC7, 0x65, 0xC8, 0xC9, 0xA1, 0xF4, 0xD1, 0xE9, 0x5A, 0x92, 0x85,
+        0xA6, 0xE7, 0x93, 0xE8, 0xC1, 0xC6, 0x7A, 0x64, 0xE1, 0x4A, 0x6A, 0xE6,
+        0xB3, 0xF1, 0xD3, 0xA5, 0x8A, 0xB2, 0x9A, 0xBA, 0x84, 0xA4, 0x63, 0xE5,
+        0xC5, 0xF3, 0xD2, 0xC4, 0x82, 0xAA, 0xDA, 0xE4, 0xF2, 0xCA, 0x83, 0xA3,
+        0xA2, 0xC3, 0xEA, 0xC2, 0xE2, 0xE3, 0xFF, 0xFF,
+    },
+    .{
+        0, 2, 2, 1, 4, 1, 4, 1, 3, 3, 1, 0, 0, 0, 0, 140,
+        0x02, 0x03, 0x01, 0x04, 0x05, 0x12, 0x11, 0x06, 0x13, 0x07, 0x08, 0x14,
+        0x22, 0x09, 0x21, 0x00, 0x23, 0x15, 0x31, 0x32, 0x0A, 0x16, 0xF0, 0x24,
+        0x33, 0x41, 0x42, 0x19, 0x17, 0x25, 0x18, 0x51, 0x34, 0x43, 0x52, 0x29,
+        0x35, 0x61, 0x39, 0x71, 0x62, 0x36, 0x53, 0x26, 0x38, 0x1A, 0x37, 0x81,
+        0x27, 0x91, 0x79, 0x55, 0x45, 0x28, 0x72, 0x59, 0xA1, 0xB1, 0x44, 0x69,
+        0x54, 0x58, 0xD1, 0xFA, 0x57, 0xE1, 0xF1, 0xB9, 0x49, 0x47, 0x63, 0x6A,
+        0xF9, 0x56, 0x46, 0xA8, 0x2A, 0x4A, 0x78, 0x99, 0x3A, 0x75, 0x74, 0x86,
+        0x65, 0xC1, 0x76, 0xB6, 0x96, 0xD6, 0x89, 0x85, 0xC9, 0xF5, 0x95, 0xB4,
+        0xC7, 0xF7, 0x8A, 0x97, 0xB8, 0x73, 0xB7, 0xD8, 0xD9, 0x87, 0xA7, 0x7A,
+        0x48, 0x82, 0x84, 0xEA, 0xF4, 0xA6, 0xC5, 0x5A, 0x94, 0xA4, 0xC6, 0x92,
+        0xC3, 0x68, 0xB5, 0xC8, 0xE4, 0xE5, 0xE6, 0xE9, 0xA2, 0xA3, 0xE3, 0xC2,
+        0x66, 0x67, 0x93, 0xAA, 0xD4, 0xD5, 0xE7, 0xF8, 0x88, 0x9A, 0xD7, 0x77,
+        0xC4, 0x64, 0xE2, 0x98, 0xA5, 0xCA, 0xDA, 0xE8, 0xF3, 0xF6, 0xA9, 0xB2,
+        0xB3, 0xF2, 0xD2, 0x83, 0xBA, 0xD3, 0xFF, 0xFF,
+    },
+    .{
+        0, 0, 6, 2, 1, 3, 3, 2, 5, 1, 2, 2, 8, 10, 0, 117,
+        0x04, 0x05, 0x03, 0x06, 0x02, 0x07, 0x01, 0x08, 0x09, 0x12, 0x13, 0x14,
+        0x11, 0x15, 0x0A, 0x16, 0x17, 0xF0, 0x00, 0x22, 0x21, 0x18, 0x23, 0x19,
+        0x24, 0x32, 0x31, 0x25, 0x33, 0x38, 0x37, 0x34, 0x35, 0x36, 0x39, 0x79,
+        0x57, 0x58, 0x59, 0x28, 0x56, 0x78, 0x27, 0x41, 0x29, 0x77, 0x26, 0x42,
+        0x76, 0x99, 0x1A, 0x55, 0x98, 0x97, 0xF9, 0x48, 0x54, 0x96, 0x89, 0x47,
+        0xB7, 0x49, 0xFA, 0x75, 0x68, 0xB6, 0x67, 0x69, 0xB9, 0xB8, 0xD8, 0x52,
+        0xD7, 0x88, 0xB5, 0x74, 0x51, 0x46, 0xD9, 0xF8, 0x3A, 0xD6, 0x87, 0x45,
+        0x7A, 0x95, 0xD5, 0xF6, 0x86, 0xB4, 0xA9, 0x94, 0x53, 0x2A, 0xA8, 0x43,
+        0xF5, 0xF7, 0xD4, 0x66, 0xA7, 0x5A, 0x44, 0x8A, 0xC9, 0xE8, 0xC8, 0xE7,
+        0x9A, 0x6A, 0x73, 0x4A, 0x61, 0xC7, 0xF4, 0xC6, 0x65, 0xE9, 0x72, 0xE6,
+        0x71, 0x91, 0x93, 0xA6, 0xDA, 0x92, 0x85, 0x62, 0xF3, 0xC5, 0xB2, 0xA4,
+        0x84, 0xBA, 0x64, 0xA5, 0xB3, 0xD2, 0x81, 0xE5, 0xD3, 0xAA, 0xC4, 0xCA,
+        0xF2, 0xB1, 0xE4, 0xD1, 0x83, 0x63, 0xEA, 0xC3, 0xE2, 0x82, 0xF1, 0xA3,
+        0xC2, 0xA1, 0xC1, 0xE3, 0xA2, 0xE1, 0xFF, 0xFF,
+    },
+};
+
+/// Samples per coded block
+const BLOCK = 64;
+
+pub const Tables = struct {
+    first: huffman.HuffmanTable,
+    second: huffman.HuffmanTable,
+
+    /// Files name one of three table pairs; higher numbers use the last
+    pub fn init(index: u32) !Tables {
+        const i = @min(index, 2);
+        return .{
+            .first = try huffman.HuffmanTable.init(FIRST_TREES[i][0..16], FIRST_TREES[i][16..]),
+            .second = try huffman.HuffmanTable.init(SECOND_TREES[i][0..16], SECOND_TREES[i][16..]),
+        };
+    }
+};
+
+/// Decodes 10-bit samples coded in blocks of 64, JPEG-like: a first
+/// difference carried over from block to block, then run/length coded
+/// differences. Samples predict from the previous one of the same column
+/// parity, restarting at 512 on every row.
+///
+/// With `low_bits`, the samples gain two more bits from a separate plane
+/// that packs four samples into each byte, lowest bits first.
+pub fn decode(input: []const u8, low_bits: ?[]const u8, out: []u16, width: u32, height: u32, tables: *const Tables) !void {
+    const total = @as(usize, width) * height;
+    if (out.len < total) return error.CorruptedData;
+    var pump = bitpump.BitPumpMsb.init_jpeg(input);
+    var carry: i32 = 0;
+    var base = [2]i32{ 512, 512 };
+
+    var row: usize = 0;
+    while (row < height) : (row += 8) {
+        const first = row * width;
+        const blocks = @min(8, height - row) * width / BLOCK;
+        for (0..blocks) |block| {
+            var diffs = [_]i32{0} ** BLOCK;
+            var i: usize = 0;
+            while (i < BLOCK) : (i += 1) {
+                const leaf = try (if (i > 0) &tables.second else &tables.first).decode(&pump);
+                if (leaf == 0 and i > 0) break;
+                if (leaf == 0xFF) continue;
+                i += leaf >> 4;
+                const len: u5 = @intCast(leaf & 15);
+                if (len == 0) continue;
+                var diff: i32 = @intCast(pump.get_bits(len));
+                if (diff & (@as(i32, 1) << (len - 1)) == 0) diff -= (@as(i32, 1) << len) - 1;
+                if (i < BLOCK) diffs[i] = diff;
+            }
+            diffs[0] += carry;
+            carry = diffs[0];
+
+            for (diffs, 0..) |diff, k| {
+                const pixel = first + block * BLOCK + k;
+                if (pixel % width == 0) base = .{ 512, 512 };
+                base[k & 1] += diff;
+                if (base[k & 1] >> 10 != 0) return error.CorruptedData;
+                out[pixel] = @intCast(base[k & 1]);
+            }
+        }
+
+        if (low_bits) |plane| {
+            const end = @min(total, first + @as(usize, width) * 8);
+            for (first..end) |pixel| {
+                if (pixel / 4 >= plane.len) return error.TruncatedFile;
+                const shift: u3 = @intCast((pixel % 4) * 2);
+                var value = (out[pixel] << 2) + ((plane[pixel / 4] >> shift) & 3);
+                // Matches the camera's own conversion on 2672-wide sensors
+                if (width == 2672 and value < 512) value += 2;
+                out[pixel] = value;
+            }
+        }
+    }
+}
+
+test "blocks carry their first difference and restart rows at 512" {
+    const tables = try Tables.init(0);
+    // First tree: 010 (length 3), then 101 (+5); second tree: 111111011 ends the block
+    const input = [_]u8{ 0x57, 0xF6 };
+    var out: [64]u16 = undefined;
+    try decode(&input, null, &out, 64, 1, &tables);
+    try std.testing.expectEqual(@as(u16, 517), out[0]);
+    try std.testing.expectEqual(@as(u16, 512), out[1]);
+    try std.testing.expectEqual(@as(u16, 517), out[62]);
+
+    var low = [_]u8{0} ** 16;
+    low[0] = 0b11_10_01_00;
+    try decode(&input, &low, &out, 64, 1, &tables);
+    try std.testing.expectEqualSlices(u16, &[_]u16{ 2068, 2049, 2070, 2051, 2068 }, out[0..5]);
+}
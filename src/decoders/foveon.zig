@@ -0,0 +1,102 @@
+// src/decoders/foveon.zig - Sigma TRUE-engine Huffman coding (X3F image planes and CAMF)
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+
+const LOOKUP_BITS = 8;
+
+/// Difference-length codes of up to 8 bits. Symbol i is the number of
+/// difference bits that follow the code.
+pub const Table = struct {
+    /// (code length << 8) | symbol, indexed by the next 8 bits; 0 = miss
+    lookup: [1 << LOOKUP_BITS]u16 = [_]u16{0} ** (1 << LOOKUP_BITS),
+
+    /// Reads (length, left-aligned code) byte pairs up to and including the
+    /// pair with length 0. Returns the table and the bytes it took.
+    pub fn read(bytes: []const u8) !struct { table: Table, size: usize } {
+        var table = Table{};
+        var pos: usize = 0;
+        var symbol: usize = 0;
+        while (true) : (symbol += 1) {
+            if (pos + 2 > bytes.len or symbol > 0xff) return error.CorruptedData;
+            const len = bytes[pos];
+            const code = bytes[pos + 1];
+            pos += 2;
+            if (len == 0) break;
+            if (len > LOOKUP_BITS) return error.CorruptedData;
+
+            const first = code & ~((@as(u16, 1) << @as(u4, @intCast(LOOKUP_BITS - len))) - 1);
+            const span = @as(usize, 1) << @as(u4, @intCast(LOOKUP_BITS - len));
+            for (first..first + span) |i| table.lookup[i] = (@as(u16, len) << 8) | @as(u16, @intCast(symbol));
+        }
+        return .{ .table = table, .size = pos };
+    }
+
+    pub fn decode_diff(self: *const Table, pump: *bitpump.BitPumpMsb) !i32 {
+        const entry = self.lookup[pump.peek_bits(LOOKUP_BITS)];
+        if (entry == 0) return error.CorruptedData;
+        pump.skip_bits(entry >> 8);
+
+        const bits: u32 = entry & 0xff;
+        if (bits == 0) return 0;
+        if (bits > 16) return error.CorruptedData;
+        var diff: i32 = @intCast(pump.get_bits(bits));
+        const shift: u5 = @intCast(bits);
+        if ((diff >> (shift - 1)) == 0) diff -= (@as(i32, 1) << shift) - 1;
+        return diff;
+    }
+};
+
+/// Decodes one colour plane into every `channels`-th sample of `out`,
+/// starting at `channel`. Each value adds its difference to the previous
+/// value of the same column parity; the first two columns continue from
+/// the row two above, starting at `seed`.
+pub fn decode_plane(
+    input: []const u8,
+    table: *const Table,
+    out: []u16,
+    width: u32,
+    height: u32,
+    channels: u32,
+    channel: u32,
+    seed: u16,
+) !void {
+    if (out.len < @as(usize, width) * height * channels) return error.CorruptedData;
+    var pump = bitpump.BitPumpMsb.init(input);
+    var row_start = [2][2]i32{ .{ seed, seed }, .{ seed, seed } };
+
+    for (0..height) |row| {
+        var acc: [2]i32 = .{ 0, 0 };
+        for (0..width) |col| {
+            const diff = try table.decode_diff(&pump);
+            const prev = if (col < 2) row_start[row & 1][col & 1] else acc[col & 1];
+            const value = prev +% diff;
+            acc[col & 1] = value;
+            if (col < 2) row_start[row & 1][col & 1] = value;
+            out[(row * width + col) * channels + channel] = @intCast(std.math.clamp(value, 0, 0xffff));
+        }
+    }
+}
+
+/// Fills `out` with the low bytes of a single running sum that starts at
+/// `bias`, as CAMF type 5 blocks are coded
+pub fn decode_bytes(input: []const u8, table: *const Table, out: []u8, bias: i32) !void {
+    var pump = bitpump.BitPumpMsb.init(input);
+    var acc = bias;
+    for (out) |*byte| {
+        acc +%= try table.decode_diff(&pump);
+        byte.* = @truncate(@as(u32, @bitCast(acc)));
+    }
+}
+
+test "plane values accumulate per column parity" {
+    // Symbol 0 (no difference) is 0, symbol 2 is 10, symbol 1 is 11
+    const table_bytes = [_]u8{ 1, 0x00, 2, 0xC0, 2, 0x80, 0, 0 };
+    const parsed = try Table.read(&table_bytes);
+    try std.testing.expectEqual(@as(usize, 8), parsed.size);
+
+    // 10 11 (+3), 0, 11 1 (+1), 0
+    const input = [_]u8{ 0b10110111, 0b00000000 };
+    var out: [4]u16 = undefined;
+    try decode_plane(&input, &parsed.table, &out, 4, 1, 1, 0, 100);
+    try std.testing.expectEqualSlices(u16, &[_]u16{ 103, 100, 104, 100 }, &out);
+}
@@ -0,0 +1,446 @@
+// src/decoders/fuji_compressed.zig - Fujifilm compressed RAF decoder
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+
+const SIGNATURE = 0x4953;
+const BLOCK_SIZE = 0x300;
+const HEADER_SIZE = 16;
+
+/// Raw type values in the header
+const RAW_TYPE_BAYER = 0;
+const RAW_TYPE_XTRANS = 16;
+
+/// Adaptive contexts halve their statistics after this many samples
+const MIN_VALUE = 0x40;
+
+// Line buffers: five red, eight green and five blue lines. Lines 0 and 1 of
+// each colour hold the previous group's last lines for prediction.
+const R0 = 0;
+const R1 = 1;
+const R2 = 2;
+const R3 = 3;
+const R4 = 4;
+const G0 = 5;
+const G1 = 6;
+const G2 = 7;
+const G3 = 8;
+const G4 = 9;
+const G5 = 10;
+const G6 = 11;
+const G7 = 12;
+const B0 = 13;
+const B1 = 14;
+const B2 = 15;
+const B3 = 16;
+const B4 = 17;
+const LINES = 18;
+
+pub const Header = struct {
+    lossless: bool,
+    xtrans: bool,
+    bits: u32,
+    height: u32,
+    width: u32,
+    /// Number of vertical strips, each BLOCK_SIZE columns wide and coded independently
+    blocks: u32,
+    /// Number of six-row line groups
+    lines: u32,
+};
+
+/// Parses and validates the 16-byte header at the start of the raw data
+pub fn read_header(data: []const u8) ?Header {
+    if (data.len < HEADER_SIZE) return null;
+    const signature = std.mem.readInt(u16, data[0..2], .big);
+    const lossless = data[2];
+    const raw_type = data[3];
+    const bits = data[4];
+    const height = std.mem.readInt(u16, data[5..7], .big);
+    const rounded_width = std.mem.readInt(u16, data[7..9], .big);
+    const width = std.mem.readInt(u16, data[9..11], .big);
+    const block_size = std.mem.readInt(u16, data[11..13], .big);
+    const blocks = data[13];
+    const lines = std.mem.readInt(u16, data[14..16], .big);
+
+    if (signature != SIGNATURE or lossless > 1) return null;
+    if (raw_type != RAW_TYPE_BAYER and raw_type != RAW_TYPE_XTRANS) return null;
+    if (bits != 12 and bits != 14 and bits != 16) return null;
+    if (height < 6 or height > 0x3000 or height % 6 != 0) return null;
+    if (width < BLOCK_SIZE or width > 0x3000 or width % 24 != 0) return null;
+    if (block_size != BLOCK_SIZE or rounded_width > 0x3000 or rounded_width % block_size != 0) return null;
+    if (rounded_width < width or rounded_width - width >= block_size) return null;
+    if (blocks == 0 or blocks > 0x10 or blocks != rounded_width / block_size) return null;
+    if (lines == 0 or lines > 0x800 or lines != height / 6) return null;
+
+    return Header{
+        .lossless = lossless == 1,
+        .xtrans = raw_type == RAW_TYPE_XTRANS,
+        .bits = bits,
+        .height = height,
+        .width = width,
+        .blocks = blocks,
+        .lines = lines,
+    };
+}
+
+/// Quantisation and coding parameters shared by all strips
+const Params = struct {
+    /// Gradient quantisation, indexed by difference + max_value
+    q_table: []i8,
+    max_value: i32,
+    total_values: i32,
+    raw_bits: u5,
+    max_bits: u32,
+    max_diff: i32,
+    /// Samples per colour line: half the strip for Bayer, two thirds for X-Trans
+    line_width: usize,
+
+    fn init(allocator: std.mem.Allocator, header: Header) !Params {
+        const max_value = (@as(i32, 1) << @as(u5, @intCast(header.bits))) - 1;
+        const q_table = try allocator.alloc(i8, @intCast(2 * max_value + 1));
+        // Thresholds between the quantised gradient levels
+        const q1 = 0x12;
+        const q2 = 0x43;
+        const q3 = 0x114;
+        for (q_table, 0..) |*q, i| {
+            const value = @as(i32, @intCast(i)) - max_value;
+            const level: i8 = if (@abs(value) >= q3) 4 else if (@abs(value) >= q2) 3 else if (@abs(value) >= q1) 2 else if (value != 0) 1 else 0;
+            q.* = if (value < 0) -level else level;
+        }
+
+        const total_values = max_value + 1;
+        return Params{
+            .q_table = q_table,
+            .max_value = max_value,
+            .total_values = total_values,
+            .raw_bits = @intCast(header.bits),
+            .max_bits = 4 * header.bits,
+            .max_diff = @max(2, (total_values + 0x20) >> 6),
+            .line_width = if (header.xtrans) BLOCK_SIZE * 2 / 3 else BLOCK_SIZE / 2,
+        };
+    }
+
+    fn quantise(self: Params, diff: i32) i32 {
+        return self.q_table[@intCast(diff + self.max_value)];
+    }
+};
+
+const Grad = struct {
+    value1: i32,
+    value2: i32,
+};
+
+const Grads = [41]Grad;
+
+/// How an even position of a non-green X-Trans line is filled
+const EvenRule = enum {
+    decode,
+    interpolate,
+    /// Interpolated where position % 4 == 0, decoded otherwise
+    interpolate_at_0,
+    /// Interpolated where position % 4 == 2, decoded otherwise
+    interpolate_at_2,
+
+    fn interpolates(self: EvenRule, pos: usize) bool {
+        return switch (self) {
+            .decode => false,
+            .interpolate => true,
+            .interpolate_at_0 => (pos & 3) == 0,
+            .interpolate_at_2 => (pos & 3) == 2,
+        };
+    }
+};
+
+/// One of the six passes that decode a line group: two lines coded
+/// interleaved, sharing one set of adaptive contexts
+const Pass = struct {
+    first: usize,
+    second: usize,
+    grads: usize,
+    /// X-Trans handling of the non-green line's even positions
+    rule: EvenRule,
+    first_is_green: bool,
+};
+
+const passes = [6]Pass{
+    .{ .first = R2, .second = G2, .grads = 0, .rule = .interpolate, .first_is_green = false },
+    .{ .first = G3, .second = B2, .grads = 1, .rule = .interpolate, .first_is_green = true },
+    .{ .first = R3, .second = G4, .grads = 2, .rule = .interpolate_at_0, .first_is_green = false },
+    .{ .first = G5, .second = B3, .grads = 0, .rule = .interpolate_at_2, .first_is_green = true },
+    .{ .first = R4, .second = G6, .grads = 1, .rule = .interpolate_at_2, .first_is_green = false },
+    .{ .first = G7, .second = B4, .grads = 2, .rule = .interpolate_at_0, .first_is_green = true },
+};
+
+/// Decoder state for one vertical strip
+const Strip = struct {
+    params: *const Params,
+    xtrans: bool,
+    pump: bitpump.BitPumpMsb,
+    /// LINES buffers of line_width samples, each padded by one on either side
+    lines: []u16,
+    stride: usize,
+    even: [3]Grads,
+    odd: [3]Grads,
+
+    fn init(allocator: std.mem.Allocator, params: *const Params, xtrans: bool, data: []const u8) !Strip {
+        const stride = params.line_width + 2;
+        const lines = try allocator.alloc(u16, LINES * stride);
+        @memset(lines, 0);
+        const grads: Grads = @splat(Grad{ .value1 = params.max_diff, .value2 = 1 });
+        return Strip{
+            .params = params,
+            .xtrans = xtrans,
+            .pump = bitpump.BitPumpMsb.init(data),
+            .lines = lines,
+            .stride = stride,
+            .even = .{ grads, grads, grads },
+            .odd = .{ grads, grads, grads },
+        };
+    }
+
+    fn at(self: *Strip, line: usize, pos: usize) usize {
+        return line * self.stride + 1 + pos;
+    }
+
+    /// Counts zero bits up to and including the next one bit
+    fn zero_bits(self: *Strip) !u32 {
+        var count: u32 = 0;
+        while (true) {
+            const window = self.pump.peek_bits(32);
+            if (window != 0) {
+                const zeros: u32 = @clz(window);
+                self.pump.skip_bits(zeros + 1);
+                return count + zeros;
+            }
+            self.pump.skip_bits(32);
+            count += 32;
+            // Valid streams never run longer than the escape length
+            if (count > self.params.max_bits) return error.CorruptedData;
+        }
+    }
+
+    /// Reads one residual, coded as a unary prefix and a number of raw bits
+    /// adapted to the average magnitude seen in this gradient context
+    fn read_residual(self: *Strip, grad: *Grad) !i32 {
+        const p = self.params;
+        const sample = try self.zero_bits();
+        var code: i32 = undefined;
+        if (sample < p.max_bits - p.raw_bits - 1) {
+            const dec_bits = bit_diff(grad.value1, grad.value2);
+            code = @as(i32, @intCast(self.pump.get_bits(dec_bits))) + (@as(i32, @intCast(sample)) << dec_bits);
+        } else {
+            code = @as(i32, @intCast(self.pump.get_bits(p.raw_bits))) + 1;
+        }
+        if (code < 0 or code >= p.total_values) return error.CorruptedData;
+
+        code = if ((code & 1) != 0) -1 - @divTrunc(code, 2) else @divTrunc(code, 2);
+        grad.value1 += @intCast(@abs(code));
+        if (grad.value2 == MIN_VALUE) {
+            grad.value1 >>= 1;
+            grad.value2 >>= 1;
+        }
+        grad.value2 += 1;
+        return code;
+    }
+
+    fn store(self: *Strip, index: usize, prediction: i32, grad: i32, code: i32) void {
+        const p = self.params;
+        var value = if (grad < 0) prediction - code else prediction + code;
+        if (value < 0) {
+            value += p.total_values;
+        } else if (value > p.max_value) {
+            value -= p.total_values;
+        }
+        self.lines[index] = if (value >= 0) @intCast(@min(value, p.max_value)) else 0;
+    }
+
+    /// Prediction for even positions from the two lines above
+    fn predict_even(self: *Strip, index: usize) struct { value: i32, rb: i32, rc: i32, rf: i32 } {
+        const s = self.stride;
+        const rb: i32 = self.lines[index - s];
+        const rc: i32 = self.lines[index - s - 1];
+        const rd: i32 = self.lines[index - s + 1];
+        const rf: i32 = self.lines[index - 2 * s];
+        const diff_rc_rb = @abs(rc - rb);
+        const diff_rf_rb = @abs(rf - rb);
+        const diff_rd_rb = @abs(rd - rb);
+
+        var value: i32 = undefined;
+        if ((diff_rc_rb > diff_rf_rb and diff_rc_rb > diff_rd_rb) or (diff_rf_rb == 0 and diff_rc_rb == 0 and diff_rd_rb == 0)) {
+            value = rf + rd + 2 * rb;
+        } else if (diff_rd_rb > diff_rc_rb and diff_rd_rb > diff_rf_rb) {
+            value = rf + rc + 2 * rb;
+        } else {
+            value = rd + rc + 2 * rb;
+        }
+        return .{ .value = value >> 2, .rb = rb, .rc = rc, .rf = rf };
+    }
+
+    fn interpolate_even(self: *Strip, line: usize, pos: usize) void {
+        const index = self.at(line, pos);
+        self.lines[index] = @intCast(self.predict_even(index).value);
+    }
+
+    fn decode_even(self: *Strip, line: usize, pos: usize, grads: *Grads) !void {
+        const index = self.at(line, pos);
+        const prediction = self.predict_even(index);
+        const grad = self.params.quantise(prediction.rb - prediction.rf) * 9 + self.params.quantise(prediction.rc - prediction.rb);
+        const code = try self.read_residual(&grads[@abs(grad)]);
+        self.store(index, prediction.value, grad, code);
+    }
+
+    fn decode_odd(self: *Strip, line: usize, pos: usize, grads: *Grads) !void {
+        const s = self.stride;
+        const index = self.at(line, pos);
+        const ra: i32 = self.lines[index - 1];
+        const rb: i32 = self.lines[index - s];
+        const rc: i32 = self.lines[index - s - 1];
+        const rd: i32 = self.lines[index - s + 1];
+        const rg: i32 = self.lines[index + 1];
+
+        const grad = self.params.quantise(rb - rc) * 9 + self.params.quantise(rc - ra);
+        const prediction = if ((rb > rc and rb > rd) or (rb < rc and rb < rd)) (rg + ra + 2 * rb) >> 2 else (ra + rg) >> 1;
+        const code = try self.read_residual(&grads[@abs(grad)]);
+        self.store(index, prediction, grad, code);
+    }
+
+    fn fill_even(self: *Strip, line: usize, pos: usize, rule: EvenRule, grads: *Grads) !void {
+        if (self.xtrans and rule.interpolates(pos)) {
+            self.interpolate_even(line, pos);
+        } else {
+            try self.decode_even(line, pos, grads);
+        }
+    }
+
+    /// Decodes one group of six rows. Odd positions trail the even ones so
+    /// that their right-hand neighbour is already known.
+    fn decode_group(self: *Strip) !void {
+        const width = self.params.line_width;
+        for (passes) |pass| {
+            const first_rule: EvenRule = if (pass.first_is_green) .decode else pass.rule;
+            const second_rule: EvenRule = if (pass.first_is_green) pass.rule else .decode;
+            var even: usize = 0;
+            var odd: usize = 1;
+            while (even < width or odd < width) {
+                if (even < width) {
+                    try self.fill_even(pass.first, even, first_rule, &self.even[pass.grads]);
+                    try self.fill_even(pass.second, even, second_rule, &self.even[pass.grads]);
+                    even += 2;
+                }
+                if (even > 8) {
+                    try self.decode_odd(pass.first, odd, &self.odd[pass.grads]);
+                    try self.decode_odd(pass.second, odd, &self.odd[pass.grads]);
+                    odd += 2;
+                }
+            }
+            self.extend(pass.first);
+            self.extend(pass.second);
+        }
+    }
+
+    /// Copies the padding of the lines decoded so far from the line above
+    fn extend(self: *Strip, line: usize) void {
+        const range: [2]usize = switch (line) {
+            R2...R4 => .{ R2, R4 },
+            G2...G7 => .{ G2, G7 },
+            else => .{ B2, B4 },
+        };
+        const s = self.stride;
+        const width = self.params.line_width;
+        for (range[0]..range[1] + 1) |l| {
+            self.lines[l * s] = self.lines[(l - 1) * s + 1];
+            self.lines[l * s + width + 1] = self.lines[(l - 1) * s + width];
+        }
+    }
+
+    /// Keeps the last decoded lines as context and clears the rest
+    fn advance(self: *Strip) void {
+        const s = self.stride;
+        const width = self.params.line_width;
+        const keep = [_][2]usize{ .{ R0, R3 }, .{ R1, R4 }, .{ G0, G6 }, .{ G1, G7 }, .{ B0, B3 }, .{ B1, B4 } };
+        for (keep) |pair| {
+            @memcpy(self.lines[pair[0] * s ..][0..s], self.lines[pair[1] * s ..][0..s]);
+        }
+        const clear = [_][2]usize{ .{ R2, 3 }, .{ G2, 6 }, .{ B2, 3 } };
+        for (clear) |range| {
+            const first = range[0];
+            @memset(self.lines[first * s ..][0 .. range[1] * s], 0);
+            self.lines[first * s] = self.lines[(first - 1) * s + 1];
+            self.lines[first * s + width + 1] = self.lines[(first - 1) * s + width];
+        }
+    }
+
+    /// Scatters the group's colour lines into six rows of the output strip
+    fn copy_group(self: *Strip, colors: *const [6][6]u8, out: []u16, out_width: usize, x0: usize, strip_width: usize) void {
+        for (0..6) |row| {
+            const dst = out[row * out_width + x0 ..][0..strip_width];
+            for (dst, 0..) |*px, col| {
+                const line = switch (colors[row][col % 6]) {
+                    0 => R2 + row / 2,
+                    2 => B2 + row / 2,
+                    else => G2 + row,
+                };
+                // X-Trans lines interleave pairs of same-coloured sites from each third
+                const pos = if (self.xtrans)
+                    (((col * 2 / 3) & ~@as(usize, 1)) | ((col % 3) & 1)) + (col % 3) / 2
+                else
+                    col / 2;
+                px.* = self.lines[self.at(line, pos)];
+            }
+        }
+    }
+};
+
+fn bit_diff(value1: i32, value2: i32) u5 {
+    var bits: u5 = 0;
+    if (value2 < value1) {
+        while (bits <= 14) {
+            bits += 1;
+            if ((value2 << bits) >= value1) break;
+        }
+    }
+    return bits;
+}
+
+/// Decodes lossless compressed data (header included) into `out`, which
+/// holds header.width x header.height samples. `colors` is the CFA layout at
+/// the sensor origin; Bayer layouts repeat their 2x2 pattern.
+pub fn decode(allocator: std.mem.Allocator, data: []const u8, header: Header, colors: *const [6][6]u8, out: []u16) !void {
+    // Lossy files carry per-line quantisation bases this decoder does not apply
+    if (!header.lossless) return error.UnsupportedFormat;
+    if (out.len < @as(usize, header.width) * header.height) return error.CorruptedData;
+
+    var offset: usize = HEADER_SIZE + 4 * @as(usize, header.blocks);
+    if ((offset & 0xC) != 0) offset += 0x10 - (offset & 0xC);
+    if (data.len < offset) return error.TruncatedFile;
+
+    var params = try Params.init(allocator, header);
+    defer allocator.free(params.q_table);
+
+    for (0..header.blocks) |block| {
+        const size = std.mem.readInt(u32, data[HEADER_SIZE + block * 4 ..][0..4], .big);
+        if (offset >= data.len) return error.TruncatedFile;
+        const strip_data = data[offset..@min(data.len, offset + size)];
+        offset += size;
+
+        var strip = try Strip.init(allocator, &params, header.xtrans, strip_data);
+        defer allocator.free(strip.lines);
+
+        const x0 = block * BLOCK_SIZE;
+        const strip_width = @min(BLOCK_SIZE, header.width - x0);
+        for (0..header.lines) |line| {
+            try strip.decode_group();
+            const rows = out[line * 6 * @as(usize, header.width) ..];
+            strip.copy_group(colors, rows, header.width, x0, strip_width);
+            strip.advance();
+        }
+    }
+}
+
+test "header rejects a wrong block size" {
+    var header = [_]u8{ 0x49, 0x53, 1, 16, 14, 0x00, 0x0C, 0x03, 0x00, 0x03, 0x00, 0x03, 0x00, 1, 0x00, 0x02 };
+    const parsed = read_header(&header) orelse return error.TestUnexpectedResult;
+    try std.testing.expect(parsed.xtrans and parsed.lossless);
+    try std.testing.expectEqual(@as(u32, 768), parsed.width);
+    header[12] = 0x01;
+    try std.testing.expect(read_header(&header) == null);
+}
@@ -0,0 +1,86 @@
+// src/decoders/huffman.zig - Canonical Huffman tables (JPEG DHT layout)
+const std = @import("std");
+
+const LOOKUP_BITS = 9;
+
+pub const HuffmanTable = struct {
+    max_code: [17]i32 = [_]i32{-1} ** 17,
+    min_code: [17]i32 = [_]i32{0} ** 17,
+    val_ptr: [17]i32 = [_]i32{0} ** 17,
+    symbols: [256]u8 = [_]u8{0} ** 256,
+    /// (code length << 8) | symbol for every code of up to LOOKUP_BITS bits, 0 = miss
+    lookup: [1 << LOOKUP_BITS]u16 = [_]u16{0} ** (1 << LOOKUP_BITS),
+
+    /// Builds a table from 16 code-length counts followed by the symbols in code order
+    pub fn init(counts: []const u8, symbols: []const u8) !HuffmanTable {
+        if (counts.len < 16) return error.CorruptedData;
+        var table = HuffmanTable{};
+
+        var total: usize = 0;
+        for (counts[0..16]) |count| total += count;
+        if (total > table.symbols.len or total > symbols.len) return error.CorruptedData;
+        @memcpy(table.symbols[0..total], symbols[0..total]);
+
+        var code: i32 = 0;
+        var index: i32 = 0;
+        for (1..17) |len| {
+            const count: i32 = counts[len - 1];
+            if (count > 0) {
+                table.val_ptr[len] = index;
+                table.min_code[len] = code;
+                table.max_code[len] = code + count - 1;
+                if (code + count > (@as(i32, 1) << @as(u5, @intCast(len)))) return error.CorruptedData;
+
+                if (len <= LOOKUP_BITS) {
+                    const shift: u6 = @intCast(LOOKUP_BITS - len);
+                    var c = code;
+                    while (c < code + count) : (c += 1) {
+                        const symbol = table.symbols[@intCast(index + c - code)];
+                        const first = @as(usize, @intCast(c)) << shift;
+                        const span = @as(usize, 1) << shift;
+                        for (first..first + span) |i| {
+                            table.lookup[i] = (@as(u16, @intCast(len)) << 8) | symbol;
+                        }
+                    }
+                }
+                code += count;
+                index += count;
+            }
+            code <<= 1;
+        }
+        return table;
+    }
+
+    /// Decodes one symbol from a bit pump (anything with peek/skip/get_bits)
+    pub fn decode(self: *const HuffmanTable, pump: anytype) !u8 {
+        const entry = self.lookup[pump.peek_bits(LOOKUP_BITS)];
+        if (entry != 0) {
+            pump.skip_bits(entry >> 8);
+            return @truncate(entry);
+        }
+
+        var code: i32 = 0;
+        for (1..17) |len| {
+            code = (code << 1) | @as(i32, @intCast(pump.get_bits(1)));
+            if (self.max_code[len] >= 0 and code >= self.min_code[len] and code <= self.max_code[len]) {
+                return self.symbols[@intCast(self.val_ptr[len] + code - self.min_code[len])];
+            }
+        }
+        return error.CorruptedData;
+    }
+};
+
+test "canonical codes decode in order" {
+    const bitpump = @import("bitpump.zig");
+    // Two 2-bit codes (00, 01) and two 3-bit codes (100, 101)
+    const counts = [_]u8{ 0, 2, 2 } ++ [_]u8{0} ** 13;
+    const symbols = [_]u8{ 7, 3, 9, 1 };
+    const table = try HuffmanTable.init(&counts, &symbols);
+
+    // 01 101 00 100 -> 3, 1, 7, 9
+    var pump = bitpump.BitPumpMsb.init(&[_]u8{ 0b01101001, 0b00000000 });
+    try std.testing.expectEqual(@as(u8, 3), try table.decode(&pump));
+    try std.testing.expectEqual(@as(u8, 1), try table.decode(&pump));
+    try std.testing.expectEqual(@as(u8, 7), try table.decode(&pump));
+    try std.testing.expectEqual(@as(u8, 9), try table.decode(&pump));
+}
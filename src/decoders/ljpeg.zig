@@ -0,0 +1,304 @@
+// src/decoders/ljpeg.zig - Lossless JPEG (ITU T.81 process 14) decoder
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+const huffman = @import("huffman.zig");
+
+// Markers (the byte following 0xFF)
+const SOI = 0xD8;
+const EOI = 0xD9;
+const SOF3 = 0xC3;
+const DHT = 0xC4;
+const SOS = 0xDA;
+const DRI = 0xDD;
+const RST0 = 0xD0;
+const RST7 = 0xD7;
+
+const MAX_COMPONENTS = 4;
+
+pub const Frame = struct {
+    width: u32,
+    height: u32,
+    components: u32,
+    precision: u32,
+};
+
+pub const Image = struct {
+    frame: Frame,
+    /// Row-major samples, components interleaved (width * height * components)
+    samples: []u16,
+    allocator: std.mem.Allocator,
+
+    pub fn deinit(self: *Image) void {
+        self.allocator.free(self.samples);
+    }
+};
+
+const Component = struct {
+    id: u8,
+};
+
+pub const Scan = struct {
+    predictor: u8,
+    point_transform: u4,
+    tables: [MAX_COMPONENTS]u8 = .{ 0, 0, 0, 0 },
+};
+
+/// Everything up to the first scan's entropy-coded data
+pub const Header = struct {
+    frame: Frame,
+    scan: Scan,
+    tables: [4]?huffman.HuffmanTable,
+    restart_interval: u32,
+    /// Offset of the entropy-coded data in the stream
+    data_start: usize,
+
+    /// The Huffman table the scan uses for `component`
+    pub fn table(self: *const Header, component: usize) ?*const huffman.HuffmanTable {
+        return if (self.tables[self.scan.tables[component]]) |*t| t else null;
+    }
+};
+
+/// Decodes the first lossless scan of a JPEG stream. Only interleaved scans
+/// with 1x1 sampling are supported; vendors tile or reshape these samples.
+pub fn decode(allocator: std.mem.Allocator, data: []const u8) !Image {
+    const header = try read_header(data);
+    return decode_scan(allocator, data[header.data_start..], header.frame, header.scan, &header.tables, header.restart_interval);
+}
+
+/// Parses the markers up to the first SOS. Vendors with their own
+/// entropy coding (Hasselblad) keep only these tables from the format.
+pub fn read_header(data: []const u8) !Header {
+    if (data.len < 4 or data[0] != 0xFF or data[1] != SOI) return error.CorruptedData;
+
+    var tables: [4]?huffman.HuffmanTable = .{ null, null, null, null };
+    var components: [MAX_COMPONENTS]Component = undefined;
+    var frame: ?Frame = null;
+    var restart_interval: u32 = 0;
+
+    var pos: usize = 2;
+    while (true) {
+        // Skip anything up to the next marker, including fill bytes
+        while (pos < data.len and data[pos] != 0xFF) pos += 1;
+        while (pos < data.len and data[pos] == 0xFF) pos += 1;
+        if (pos >= data.len) return error.TruncatedFile;
+        const marker = data[pos];
+        pos += 1;
+
+        if (marker == EOI) return error.CorruptedData;
+        if (marker == SOI or (marker >= RST0 and marker <= RST7)) continue;
+
+        if (pos + 2 > data.len) return error.TruncatedFile;
+        const length = std.mem.readInt(u16, data[pos..][0..2], .big);
+        if (length < 2 or pos + length > data.len) return error.TruncatedFile;
+        const segment = data[pos + 2 .. pos + length];
+        pos += length;
+
+        switch (marker) {
+            SOF3 => frame = try parse_frame(segment, &components),
+            // Baseline, progressive and arithmetic-coded frames
+            0xC0...0xC2, 0xC5...0xC7, 0xC9...0xCB, 0xCD...0xCF => return error.UnsupportedFormat,
+            DHT => try parse_huffman_tables(segment, &tables),
+            DRI => {
+                if (segment.len < 2) return error.CorruptedData;
+                restart_interval = std.mem.readInt(u16, segment[0..2], .big);
+            },
+            SOS => {
+                const f = frame orelse return error.CorruptedData;
+                return Header{
+                    .frame = f,
+                    .scan = try parse_scan(segment, f, &components),
+                    .tables = tables,
+                    .restart_interval = restart_interval,
+                    .data_start = pos,
+                };
+            },
+            else => {},
+        }
+    }
+}
+
+fn parse_frame(segment: []const u8, components: *[MAX_COMPONENTS]Component) !Frame {
+    if (segment.len < 6) return error.CorruptedData;
+    const precision = segment[0];
+    const count = segment[5];
+    if (precision < 2 or precision > 16) return error.CorruptedData;
+    if (count == 0 or count > MAX_COMPONENTS) return error.UnsupportedFormat;
+    if (segment.len < 6 + @as(usize, count) * 3) return error.CorruptedData;
+
+    for (0..count) |i| {
+        const spec = segment[6 + i * 3 ..][0..3];
+        // Subsampled components (Canon sRAW) are not handled here
+        if (spec[1] != 0x11) return error.UnsupportedFormat;
+        components[i] = .{ .id = spec[0] };
+    }
+
+    return Frame{
+        .precision = precision,
+        .height = std.mem.readInt(u16, segment[1..3], .big),
+        .width = std.mem.readInt(u16, segment[3..5], .big),
+        .components = count,
+    };
+}
+
+fn parse_huffman_tables(segment: []const u8, tables: *[4]?huffman.HuffmanTable) !void {
+    var pos: usize = 0;
+    while (pos < segment.len) {
+        if (pos + 17 > segment.len) return error.CorruptedData;
+        const index = segment[pos] & 0x0F;
+        if (index > 3) return error.CorruptedData;
+        const counts = segment[pos + 1 .. pos + 17];
+        var total: usize = 0;
+        for (counts) |count| total += count;
+        if (pos + 17 + total > segment.len) return error.CorruptedData;
+        tables[index] = try huffman.HuffmanTable.init(counts, segment[pos + 17 .. pos + 17 + total]);
+        pos += 17 + total;
+    }
+}
+
+fn parse_scan(segment: []const u8, frame: Frame, components: *const [MAX_COMPONENTS]Component) !Scan {
+    if (segment.len < 1) return error.CorruptedData;
+    const count = segment[0];
+    if (count != frame.components) return error.UnsupportedFormat;
+    if (segment.len < 1 + @as(usize, count) * 2 + 3) return error.CorruptedData;
+
+    var scan = Scan{ .predictor = 0, .point_transform = 0 };
+    for (0..count) |i| {
+        const id = segment[1 + i * 2];
+        const selector = segment[2 + i * 2] >> 4;
+        if (selector > 3) return error.CorruptedData;
+        // Scan components may be listed in a different order than the frame's
+        const index = for (components[0..count], 0..) |c, j| {
+            if (c.id == id) break j;
+        } else i;
+        scan.tables[index] = selector;
+    }
+
+    const params = segment[1 + @as(usize, count) * 2 ..];
+    scan.predictor = params[0];
+    if (scan.predictor < 1 or scan.predictor > 7) return error.UnsupportedFormat;
+    const point_transform = params[2] & 0x0F;
+    if (point_transform >= frame.precision) return error.CorruptedData;
+    scan.point_transform = @intCast(point_transform);
+    return scan;
+}
+
+fn decode_scan(
+    allocator: std.mem.Allocator,
+    ecs: []const u8,
+    frame: Frame,
+    scan: Scan,
+    tables: *const [4]?huffman.HuffmanTable,
+    restart_interval: u32,
+) !Image {
+    const comps: usize = frame.components;
+    const row_len = @as(usize, frame.width) * comps;
+    // Every sample takes at least one bit, which bounds what a hostile
+    // header can make us allocate
+    if (row_len * frame.height > ecs.len *| 8) return error.CorruptedData;
+    const samples = try allocator.alloc(u16, row_len * frame.height);
+    errdefer allocator.free(samples);
+
+    var huff: [MAX_COMPONENTS]*const huffman.HuffmanTable = undefined;
+    for (0..comps) |c| {
+        huff[c] = if (tables[scan.tables[c]]) |*t| t else return error.CorruptedData;
+    }
+
+    const shift: u5 = @intCast(frame.precision - scan.point_transform - 1);
+    const initial: i32 = @as(i32, 1) << shift;
+    var pump = bitpump.BitPumpMsb.init_jpeg(ecs);
+    var mcus_left = restart_interval;
+    // The first line of the scan and of each restart interval predicts from the left only
+    var first_line: usize = 0;
+    var fresh = false;
+
+    for (0..frame.height) |row| {
+        const line = samples[row * row_len ..][0..row_len];
+        const above = if (row > 0) samples[(row - 1) * row_len ..][0..row_len] else line;
+        for (0..frame.width) |col| {
+            if (restart_interval != 0) {
+                if (mcus_left == 0) {
+                    if (!pump.restart()) return error.CorruptedData;
+                    mcus_left = restart_interval;
+                    fresh = true;
+                    if (col == 0) first_line = row;
+                }
+                mcus_left -= 1;
+            }
+            for (0..comps) |c| {
+                const i = col * comps + c;
+                var pred: i32 = undefined;
+                if (fresh or (row == first_line and col == 0)) {
+                    pred = initial;
+                } else if (row == first_line) {
+                    pred = line[i - comps];
+                } else if (col == 0) {
+                    pred = above[i];
+                } else {
+                    pred = predict(scan.predictor, line[i - comps], above[i], above[i - comps]);
+                }
+                const diff = try decode_diff(huff[c], &pump);
+                line[i] = @truncate(@as(u32, @bitCast(pred + diff)));
+            }
+            fresh = false;
+        }
+    }
+
+    if (scan.point_transform > 0) {
+        for (samples) |*s| s.* <<= scan.point_transform;
+    }
+
+    return Image{ .frame = frame, .samples = samples, .allocator = allocator };
+}
+
+fn predict(predictor: u8, a: i32, b: i32, c: i32) i32 {
+    return switch (predictor) {
+        1 => a,
+        2 => b,
+        3 => c,
+        4 => a + b - c,
+        5 => a + ((b - c) >> 1),
+        6 => b + ((a - c) >> 1),
+        else => (a + b) >> 1,
+    };
+}
+
+fn decode_diff(table: *const huffman.HuffmanTable, pump: *bitpump.BitPumpMsb) !i32 {
+    const len = try table.decode(pump);
+    if (len == 0) return 0;
+    // 32768 is coded without additional bits
+    if (len == 16) return -32768;
+    if (len > 16) return error.CorruptedData;
+    const bits: u5 = @intCast(len);
+    var diff: i32 = @intCast(pump.get_bits(len));
+    if (diff < (@as(i32, 1) << (bits - 1))) diff -= (@as(i32, 1) << bits) - 1;
+    return diff;
+}
+
+test "decodes a two-component predictor 1 stream" {
+    // 2x2 image, two components, 8-bit, one DC table where every diff is
+    // coded as category 0 (code '0') or category 1 (code '10')
+    const stream = [_]u8{
+        0xFF, 0xD8,
+        0xFF, 0xC4, 0x00, 0x15, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0xFF, 0xC3, 0x00, 0x0E, 0x08, 0x00, 0x02, 0x00, 0x02, 0x02, 0x01, 0x11, 0x00, 0x02, 0x11, 0x00,
+        0xFF, 0xDA, 0x00, 0x0A, 0x02, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00,
+        // diffs: +1 then zeros -> bits 10 1 0 0 0 0 0 0 0 (then padding)
+        0xA0, 0x00, 0xFF, 0xD9,
+    };
+    var image = try decode(std.testing.allocator, &stream);
+    defer image.deinit();
+    try std.testing.expectEqual(@as(u32, 2), image.frame.components);
+    try std.testing.expectEqualSlices(u16, &.{ 129, 128, 129, 128, 129, 128, 129, 128 }, image.samples);
+}
+
+test "a frame larger than its data is refused" {
+    // 65535x65535 over two bytes of entropy-coded data
+    const stream = [_]u8{
+        0xFF, 0xD8,
+        0xFF, 0xC4, 0x00, 0x15, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0xFF, 0xC3, 0x00, 0x0B, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x01, 0x11, 0x00,
+        0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0xA0, 0x00, 0xFF, 0xD9,
+    };
+    try std.testing.expectError(error.CorruptedData, decode(std.testing.allocator, &stream));
+}
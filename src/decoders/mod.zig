@@ -0,0 +1,25 @@
+// src/decoders/mod.zig - Bitstream and entropy decoders shared by the format modules
+const std = @import("std");
+
+pub const bitpump = @import("bitpump.zig");
+pub const canon = @import("canon.zig");
+pub const foveon = @import("foveon.zig");
+pub const fuji_compressed = @import("fuji_compressed.zig");
+pub const hasselblad = @import("hasselblad.zig");
+pub const huffman = @import("huffman.zig");
+pub const kodak = @import("kodak.zig");
+pub const ljpeg = @import("ljpeg.zig");
+pub const olympus = @import("olympus.zig");
+pub const pentax = @import("pentax.zig");
+pub const phase_one = @import("phase_one.zig");
+pub const samsung = @import("samsung.zig");
+pub const unpack = @import("unpack.zig");
+
+pub const BitPumpMsb = bitpump.BitPumpMsb;
+pub const BitPumpLsb = bitpump.BitPumpLsb;
+pub const BitPumpMsb32 = bitpump.BitPumpMsb32;
+pub const HuffmanTable = huffman.HuffmanTable;
+
+test {
+    std.testing.refAllDecls(@This());
+}
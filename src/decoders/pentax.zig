@@ -0,0 +1,104 @@
+// src/decoders/pentax.zig - Pentax Huffman-compressed sensor data
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+
+const LOOKUP_BITS = 12;
+
+/// Code lengths and symbols used by bodies whose makernote carries no table
+const default_tree = [_]u8{ 0, 2, 3, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 3, 4, 2, 5, 1, 6, 0, 7, 8, 9, 10, 11, 12 };
+
+/// Difference-length codes of up to 12 bits, looked up in one step
+pub const Table = struct {
+    /// (code length << 8) | symbol, indexed by the next 12 bits; 0 = miss
+    lookup: [1 << LOOKUP_BITS]u16 = [_]u16{0} ** (1 << LOOKUP_BITS),
+
+    /// Canonical codes for the JPEG-style count/symbol list in `default_tree`
+    pub fn default() Table {
+        var table = Table{};
+        const counts = default_tree[0..16];
+        const symbols = default_tree[16..];
+        var code: u32 = 0;
+        var index: usize = 0;
+        for (1..17) |len| {
+            for (0..counts[len - 1]) |_| {
+                table.add(code << @as(u5, @intCast(LOOKUP_BITS - len)), @intCast(len), symbols[index]);
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+        return table;
+    }
+
+    /// Reads makernote tag 0x0220: a depth word, 12 unused bytes, then one
+    /// 12-bit left-aligned code word and one code length per symbol
+    pub fn from_makernote(bytes: []const u8, order: std.builtin.Endian) !Table {
+        if (bytes.len < 14) return error.CorruptedData;
+        const depth: usize = (std.mem.readInt(u16, bytes[0..2], order) +% 12) & 15;
+        if (bytes.len < 14 + depth * 3) return error.CorruptedData;
+
+        var table = Table{};
+        for (0..depth) |symbol| {
+            const code = std.mem.readInt(u16, bytes[14 + symbol * 2 ..][0..2], order);
+            const len = bytes[14 + depth * 2 + symbol];
+            if (len == 0 or len > LOOKUP_BITS) return error.CorruptedData;
+            table.add(code & 0xfff, len, @intCast(symbol));
+        }
+        return table;
+    }
+
+    fn add(self: *Table, first: u32, len: u8, symbol: u8) void {
+        const span = @as(u32, 1) << @as(u5, @intCast(LOOKUP_BITS - len));
+        var i = first;
+        while (i < first + span and i < self.lookup.len) : (i += 1) {
+            self.lookup[i] = (@as(u16, len) << 8) | symbol;
+        }
+    }
+
+    fn decode_diff(self: *const Table, pump: *bitpump.BitPumpMsb) !i32 {
+        const entry = self.lookup[pump.peek_bits(LOOKUP_BITS)];
+        if (entry == 0) return error.CorruptedData;
+        pump.skip_bits(entry >> 8);
+
+        const len: u5 = @intCast(entry & 0xff);
+        if (len == 0) return 0;
+        var diff: i32 = @intCast(pump.get_bits(len));
+        if ((diff & (@as(i32, 1) << (len - 1))) == 0) diff -= (@as(i32, 1) << len) - 1;
+        return diff;
+    }
+};
+
+/// Decodes `height` rows of `width` samples. Each sample adds its difference
+/// to the previous one of the same colour in the row; the first two of a
+/// row continue from the first two of the row two above.
+pub fn decode(input: []const u8, out: []u16, width: u32, height: u32, table: *const Table) !void {
+    if (out.len < @as(usize, width) * height) return error.CorruptedData;
+    var pump = bitpump.BitPumpMsb.init(input);
+    var vpred = [2][2]u16{ .{ 0, 0 }, .{ 0, 0 } };
+    var hpred: [2]u16 = .{ 0, 0 };
+
+    for (0..height) |row| {
+        for (0..width) |col| {
+            const diff: u16 = @truncate(@as(u32, @bitCast(try table.decode_diff(&pump))));
+            if (col < 2) {
+                vpred[row & 1][col] +%= diff;
+                hpred[col] = vpred[row & 1][col];
+            } else {
+                hpred[col & 1] +%= diff;
+            }
+            out[row * width + col] = hpred[col & 1];
+        }
+    }
+}
+
+test "makernote table drives the difference decoding" {
+    // Symbols 0 and 1 get the 1-bit codes 0 and 1
+    const bytes = [_]u8{ 0, 0xF6 } ++ [_]u8{0} ** 12 ++ [_]u8{ 0x00, 0x00, 0x08, 0x00, 1, 1 };
+    const table = try Table.from_makernote(&bytes, .big);
+
+    // 1 1 (diff +1), 0 (diff 0), 1 0 (diff -1), 1 1 (diff +1)
+    const input = [_]u8{0b11010110};
+    var out: [4]u16 = undefined;
+    try decode(&input, &out, 4, 1, &table);
+    try std.testing.expectEqualSlices(u16, &[_]u16{ 1, 0, 0, 1 }, &out);
+}
@@ -0,0 +1,53 @@
+// src/decoders/phase_one.zig - Phase One IIQ compressed sensor data
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+
+/// Bit lengths selected by the 1-to-5 bit prefix codes
+const LENGTHS = [_]u32{ 8, 7, 6, 9, 11, 10, 5, 12, 14, 13 };
+
+/// Samples past the last whole group of 8 are stored as raw 16-bit values
+const RAW_LENGTH = 14;
+
+/// Decodes one row. Every 8 columns each column parity may pick a new bit
+/// length, which carries over between rows; samples are differences to the
+/// previous one of the same parity in the row, except at length 14, which
+/// stores the sample itself.
+pub fn decode_row(input: []const u8, out: []u16, lens: *[2]u32) !void {
+    var pump = bitpump.BitPumpMsb32.init(input);
+    var pred = [2]i32{ 0, 0 };
+    const grouped = out.len & ~@as(usize, 7);
+
+    for (out, 0..) |*sample, col| {
+        if (col >= grouped) {
+            lens.* = .{ RAW_LENGTH, RAW_LENGTH };
+        } else if (col % 8 == 0) {
+            for (lens) |*len| {
+                // Up to five zeros, then one more bit; a leading 1 keeps the length
+                var zeros: u32 = 0;
+                while (zeros < 5 and pump.get_bits(1) == 0) zeros += 1;
+                if (zeros > 0) len.* = LENGTHS[(zeros - 1) * 2 + pump.get_bits(1)];
+            }
+        }
+
+        const len = lens[col & 1];
+        if (len == 0) return error.CorruptedData;
+        if (len == RAW_LENGTH) {
+            pred[col & 1] = @intCast(pump.get_bits(16));
+        } else {
+            const shift: u5 = @intCast(len - 1);
+            pred[col & 1] += @as(i32, @intCast(pump.get_bits(len))) + 1 - (@as(i32, 1) << shift);
+        }
+        if ((pred[col & 1] >> 16) != 0) return error.CorruptedData;
+        sample.* = @intCast(pred[col & 1]);
+    }
+}
+
+test "length codes select the difference width per column parity" {
+    // 010 011: lengths 8 and 7, then +2, +1 and zero differences
+    const input = [_]u8{ 0xFB, 0x03, 0x06, 0x4E, 0xEF, 0xEF, 0xF7, 0xF7, 0x00, 0x00, 0x00, 0xC0 };
+    var out: [8]u16 = undefined;
+    var lens = [2]u32{ 0, 0 };
+    try decode_row(&input, &out, &lens);
+    try std.testing.expectEqualSlices(u16, &[_]u16{ 2, 1, 2, 1, 2, 1, 2, 1 }, &out);
+    try std.testing.expectEqual([2]u32{ 8, 7 }, lens);
+}
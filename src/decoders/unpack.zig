@@ -0,0 +1,81 @@
+// src/decoders/unpack.zig - Uncompressed sensor data layouts
+const std = @import("std");
+const bitpump = @import("bitpump.zig");
+
+pub const Packing = enum {
+    /// One sample per 16-bit little-endian word
+    u16_little,
+    /// One sample per 16-bit big-endian word
+    u16_big,
+    /// Tightly packed samples, most significant bit first
+    msb,
+    /// Tightly packed samples, least significant bit first
+    lsb,
+    /// 12-bit samples packed most significant bit first, with a control
+    /// byte after every ten samples (Epson)
+    msb_control,
+};
+
+/// Bytes needed for one row of `width` samples in the given packing
+pub fn row_bytes(width: u32, bits: u32, packing: Packing) usize {
+    return switch (packing) {
+        .u16_little, .u16_big => @as(usize, width) * 2,
+        .msb, .lsb => (@as(usize, width) * bits + 7) / 8,
+        .msb_control => @as(usize, width) * bits / 8 * 16 / 15,
+    };
+}
+
+/// Unpacks `height` rows of `width` samples, each row starting `row_stride`
+/// bytes after the previous one.
+pub fn unpack(input: []const u8, out: []u16, width: u32, height: u32, bits: u32, packing: Packing, row_stride: usize) !void {
+    if (bits == 0 or bits > 16) return error.CorruptedData;
+    if (packing == .msb_control and bits != 12) return error.CorruptedData;
+    if (out.len < @as(usize, width) * height) return error.CorruptedData;
+    if (height == 0) return;
+    const needed = row_stride * (height - 1) + row_bytes(width, bits, packing);
+    if (input.len < needed) return error.TruncatedFile;
+
+    for (0..height) |row| {
+        const line = input[row * row_stride ..];
+        const dst = out[row * width ..][0..width];
+        switch (packing) {
+            .u16_little => for (dst, 0..) |*px, col| {
+                px.* = std.mem.readInt(u16, line[col * 2 ..][0..2], .little);
+            },
+            .u16_big => for (dst, 0..) |*px, col| {
+                px.* = std.mem.readInt(u16, line[col * 2 ..][0..2], .big);
+            },
+            .msb => {
+                var pump = bitpump.BitPumpMsb.init(line);
+                for (dst) |*px| px.* = @truncate(pump.get_bits(bits));
+            },
+            .lsb => {
+                var pump = bitpump.BitPumpLsb.init(line);
+                for (dst) |*px| px.* = @truncate(pump.get_bits(bits));
+            },
+            .msb_control => {
+                var pump = bitpump.BitPumpMsb.init(line);
+                for (dst, 0..) |*px, col| {
+                    px.* = @truncate(pump.get_bits(bits));
+                    if (col % 10 == 9) pump.skip_bits(8);
+                }
+            },
+        }
+    }
+}
+
+test "12-bit msb packing" {
+    var out: [2]u16 = undefined;
+    try unpack(&[_]u8{ 0x12, 0x34, 0x56 }, &out, 2, 1, 12, .msb, 3);
+    try std.testing.expectEqualSlices(u16, &[_]u16{ 0x123, 0x456 }, &out);
+}
+
+test "control bytes follow every ten samples" {
+    var input = [_]u8{0} ** 17;
+    input[15] = 0xFF;
+    input[16] = 0xAB;
+    var out: [11]u16 = undefined;
+    try unpack(&input, &out, 11, 1, 12, .msb_control, 17);
+    try std.testing.expectEqual(@as(u16, 0), out[9]);
+    try std.testing.expectEqual(@as(u16, 0xAB0), out[10]);
+}
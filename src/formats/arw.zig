@@ -1,10 +1,400 @@
+// src/formats/arw.zig - Sony ARW/SR2 RAW format support
 const std = @import("std");
-const mod = @import("mod.zig");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
 
-pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !mod.RawMetadata {
-    return mod.parse_metadata(data, allocator);
+// IFD0 pointer to the SR2Private IFD
+const SONY_SR2_PRIVATE = 0xC634;
+// Raw IFD tone curve knots for compressed (cRAW) data
+const SONY_TONE_CURVE = 0x7010;
+
+// SR2Private tags describing the encrypted SR2SubIFD
+const SR2_SUBIFD_OFFSET = 0x7200;
+const SR2_SUBIFD_LENGTH = 0x7201;
+const SR2_SUBIFD_KEY = 0x7221;
+
+// SR2SubIFD tags
+const SR2_BLACK_LEVEL = 0x7300;
+const SR2_WB_GRBG_LEVELS = 0x7303;
+const SR2_BLACK_LEVEL_2 = 0x7310;
+const SR2_WB_RGGB_LEVELS = 0x7313;
+const SR2_WHITE_LEVEL = 0x787F;
+
+// Compression values
+const COMPRESSION_NONE = 1;
+const COMPRESSION_LJPEG = 7;
+const SONY_ARW_COMPRESSED = 32767;
+
+/// How the sensor data is stored, which also decides the value range
+const Encoding = enum {
+    /// 12/14-bit samples, packed or in 16-bit containers
+    uncompressed,
+    /// ARW 2.x: 8 bits per pixel on average, expanded through the tone curve
+    craw,
+    /// ARW 4.0 lossless: LJPEG tiles holding 2x2 blocks as four components
+    lossless,
+};
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+};
+
+/// The decrypted SR2SubIFD. `block` is owned by the caller.
+const Sr2 = struct {
+    block: []u8,
+    parser: tiff.IfdParser,
+    ifd: tiff.Ifd,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return false;
+    return std.mem.startsWith(u8, make, "SONY");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse return formats.FormatError.UnsupportedFormat;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = tiff.read_exif_info(ifd0),
+    };
+}
+
+fn classify(raw: tiff.Ifd, width: u32, height: u32) !Encoding {
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE;
+    const pixels = @as(u64, width) * height;
+    return switch (compression) {
+        COMPRESSION_NONE => .uncompressed,
+        COMPRESSION_LJPEG => .lossless,
+        SONY_ARW_COMPRESSED => {
+            // The same tag value covers cRAW and some uncompressed bodies
+            const size = raw.strip_bytes();
+            if (size == pixels) return .craw;
+            if (size == pixels * 2) return .uncompressed;
+            // ARW 1.0 (DSLR-A100) column-wise Huffman data
+            return formats.FormatError.UnsupportedFormat;
+        },
+        else => formats.FormatError.UnsupportedFormat,
+    };
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const encoding = try classify(raw, width, height);
+
+    // cRAW stores 8 bits per pixel that the tone curve expands to 14 bits
+    var bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 14;
+    var white: u32 = undefined;
+    switch (encoding) {
+        .craw => {
+            const curve = tone_curve(layout);
+            bits = 14;
+            white = curve[0xffe];
+        },
+        .uncompressed, .lossless => {
+            if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+            white = (@as(u32, 1) << @as(u5, @intCast(bits))) - 1;
+        },
+    }
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "SONY");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Sony Camera");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .ARW,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        // Sony does not store a colour matrix in the file
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{white} ** 4,
+        .raw_data_offset = first_data_offset(raw),
+        .raw_data_size = @intCast(@min(data_size(raw), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE,
+        .allocator = allocator,
+    };
+
+    if (try read_sr2(data, layout.ifd0, allocator)) |sr2| {
+        defer allocator.free(sr2.block);
+        apply_sr2(&metadata, sr2);
+    }
+
+    return metadata;
+}
+
+fn first_data_offset(raw: tiff.Ifd) u32 {
+    if (raw.get_u32(tiff.TAG_STRIP_OFFSETS)) |offset| return offset;
+    return raw.get_u32(tiff.TAG_TILE_OFFSETS) orelse 0;
+}
+
+fn data_size(raw: tiff.Ifd) u64 {
+    const entry = raw.find(tiff.TAG_TILE_BYTE_COUNTS) orelse return raw.strip_bytes();
+    var total: u64 = 0;
+    for (0..entry.count) |i| total += entry.u32_at(raw.parser, i) orelse 0;
+    return total;
+}
+
+/// Locates and decrypts the SR2SubIFD. Returns null if the file has none.
+fn read_sr2(data: []const u8, ifd0: tiff.Ifd, allocator: std.mem.Allocator) !?Sr2 {
+    // The pointer is stored as four bytes in the file's byte order
+    const pointer = ifd0.get_bytes(SONY_SR2_PRIVATE) orelse return null;
+    if (pointer.len < 4) return null;
+    const order = ifd0.parser.byte_order;
+    const private_offset = tiff.IfdParser.init(pointer, order).read_u32(0);
+    const private = ifd0.parser.parse_ifd(private_offset) catch return null;
+
+    const offset = private.get_u32(SR2_SUBIFD_OFFSET) orelse return null;
+    const length = private.get_u32(SR2_SUBIFD_LENGTH) orelse return null;
+    const key = private.get_u32(SR2_SUBIFD_KEY) orelse return null;
+    if (length < 6 or !ifd0.parser.in_bounds(offset, length)) return null;
+
+    const block = try allocator.dupe(u8, data[offset..][0..length]);
+    sony_decrypt(block, key);
+
+    // Entries inside the block still hold file offsets
+    const parser = tiff.IfdParser.init_with_base(block, order, offset);
+    const ifd = parser.parse_ifd(0) catch {
+        allocator.free(block);
+        return null;
+    };
+    return Sr2{ .block = block, .parser = parser, .ifd = ifd };
 }
 
-pub fn extract_raw_data(data: []const u8, metadata: mod.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
-    return mod.extract_raw_data(data, metadata, allocator);
-}
+/// Sony's SR2 cipher: XOR with a lagged pad stream seeded from `key`.
+/// Operates on whole big-endian words; a trailing partial word is left as is.
+fn sony_decrypt(block: []u8, key: u32) void {
+    var pad: [128]u32 = undefined;
+    var seed = key;
+    for (0..4) |n| {
+        seed = seed *% 48828125 +% 1;
+        pad[n] = seed;
+    }
+    pad[3] = pad[3] << 1 | (pad[0] ^ pad[2]) >> 31;
+    for (4..127) |n| {
+        pad[n] = (pad[n - 4] ^ pad[n - 2]) << 1 | (pad[n - 3] ^ pad[n - 1]) >> 31;
+    }
+
+    var p: usize = 127;
+    var i: usize = 0;
+    while (i + 4 <= block.len) : (i += 4) {
+        p += 1;
+        pad[(p - 1) & 127] = pad[p & 127] ^ pad[(p + 64) & 127];
+        const word = block[i..][0..4];
+        std.mem.writeInt(u32, word, std.mem.readInt(u32, word, .big) ^ pad[(p - 1) & 127], .big);
+    }
+}
+
+fn apply_sr2(metadata: *formats.RawMetadata, sr2: Sr2) void {
+    // As-shot levels; older bodies only have the GRBG-ordered variant
+    var levels: ?[4]u32 = null;
+    if (read_levels(sr2, SR2_WB_RGGB_LEVELS)) |v| {
+        levels = v;
+    } else if (read_levels(sr2, SR2_WB_GRBG_LEVELS)) |v| {
+        levels = .{ v[1], v[0], v[3], v[2] };
+    }
+    if (levels) |rggb| {
+        if (rggb[0] > 0 and rggb[1] > 0 and rggb[3] > 0) {
+            const green: f32 = @floatFromInt(rggb[1]);
+            metadata.white_balance = .{
+                @as(f32, @floatFromInt(rggb[0])) / green,
+                1.0,
+                @as(f32, @floatFromInt(rggb[3])) / green,
+            };
+        }
+    }
+
+    if (read_levels(sr2, SR2_BLACK_LEVEL_2) orelse read_levels(sr2, SR2_BLACK_LEVEL)) |black| {
+        metadata.set_black_levels_rggb(black);
+    }
+
+    if (sr2.ifd.get_u32(SR2_WHITE_LEVEL)) |white| {
+        if (white > 0) metadata.white_level = [_]u32{white} ** 4;
+    }
+}
+
+fn read_levels(sr2: Sr2, tag: u16) ?[4]u32 {
+    const entry = sr2.ifd.find(tag) orelse return null;
+    var levels: [4]u32 = undefined;
+    for (&levels, 0..) |*level, i| level.* = entry.u32_at(sr2.parser, i) orelse return null;
+    return levels;
+}
+
+/// Expands the four tone curve knots into a 12-bit to 14-bit lookup table.
+/// Segment i between consecutive knots has a slope of 2^i.
+fn tone_curve(layout: Layout) [0x1000]u16 {
+    var knots = [6]u32{ 0, 0, 0, 0, 0, 4095 };
+    const source = if (layout.raw_ifd.find(SONY_TONE_CURVE) != null) layout.raw_ifd else layout.ifd0;
+    if (source.find(SONY_TONE_CURVE)) |entry| {
+        for (0..4) |i| knots[i + 1] = ((entry.u32_at(source.parser, i) orelse 0) >> 2) & 0xfff;
+    }
+
+    var curve: [0x1000]u16 = undefined;
+    for (&curve, 0..) |*v, i| v.* = @intCast(i);
+    for (0..5) |i| {
+        var j = knots[i] + 1;
+        while (j <= knots[i + 1] and j < curve.len) : (j += 1) {
+            curve[j] = curve[j - 1] +% (@as(u16, 1) << @as(u4, @intCast(i)));
+        }
+    }
+    return curve;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+    const encoding = try classify(layout.raw_ifd, width, height);
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    if (encoding == .lossless) {
+        @memset(raw, 0);
+        try decode_lossless(allocator, data, layout.raw_ifd, raw, width, height);
+        return raw;
+    }
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    switch (encoding) {
+        .craw => {
+            const curve = tone_curve(layout);
+            try decode_craw(input, raw, width, height, &curve);
+        },
+        .uncompressed => try decode_uncompressed(input, raw, width, height, metadata.bits_per_sample),
+        .lossless => unreachable,
+    }
+    return raw;
+}
+
+fn decode_uncompressed(input: []const u8, raw: []u16, width: u32, height: u32, bits: u32) !void {
+    if (height == 0) return;
+    const unpack = decoders.unpack;
+    const stride = input.len / height;
+
+    if (stride >= unpack.row_bytes(width, bits, .u16_little)) {
+        try unpack.unpack(input, raw, width, height, 16, .u16_little, stride);
+    } else {
+        try unpack.unpack(input, raw, width, height, bits, .lsb, stride);
+    }
+}
+
+/// ARW 2.x: each 16-byte block codes 16 same-coloured pixels spaced two
+/// columns apart. A block holds an 11-bit max and min, their positions, and
+/// 7-bit deltas from the min scaled by the block's range.
+fn decode_craw(input: []const u8, raw: []u16, width: u32, height: u32, curve: *const [0x1000]u16) !void {
+    if (input.len < @as(usize, width) * height) return formats.FormatError.TruncatedFile;
+    if (width < 32) return formats.FormatError.CorruptedData;
+
+    for (0..height) |row| {
+        const line = input[row * width ..][0..width];
+        const out = raw[row * width ..][0..width];
+        var col: usize = 0;
+        var block: usize = 0;
+        while (col + 30 < width and block + 16 <= line.len) : (block += 16) {
+            const header = std.mem.readInt(u32, line[block..][0..4], .little);
+            const max: u32 = header & 0x7ff;
+            const min: u32 = (header >> 11) & 0x7ff;
+            const imax = (header >> 22) & 0x0f;
+            const imin = (header >> 26) & 0x0f;
+
+            var sh: u5 = 0;
+            while (sh < 4 and max > min and (@as(u32, 0x80) << sh) <= max - min) sh += 1;
+
+            var pix: [16]u32 = undefined;
+            var bit: usize = 30;
+            for (&pix, 0..) |*p, i| {
+                if (i == imax) {
+                    p.* = max;
+                } else if (i == imin) {
+                    p.* = min;
+                } else {
+                    // The last delta may straddle into the next block's first byte
+                    const at = block + (bit >> 3);
+                    const lo: u32 = line[at];
+                    const hi: u32 = if (at + 1 < line.len) line[at + 1] else 0;
+                    const delta = ((lo | hi << 8) >> @as(u5, @intCast(bit & 7))) & 0x7f;
+                    p.* = @min((delta << sh) + min, 0x7ff);
+                    bit += 7;
+                }
+            }
+
+            for (pix) |p| {
+                if (col < width) out[col] = curve[p << 1];
+                col += 2;
+            }
+            col -= if ((col & 1) == 1) 1 else 31;
+        }
+    }
+}
+
+/// ARW 4.0 lossless: one LJPEG stream per tile, each sample of the four
+/// components filling one site of a 2x2 block.
+fn decode_lossless(allocator: std.mem.Allocator, data: []const u8, ifd: tiff.Ifd, raw: []u16, width: u32, height: u32) !void {
+    const tile_width = ifd.get_u32(tiff.TAG_TILE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const tile_length = ifd.get_u32(tiff.TAG_TILE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const offsets = ifd.find(tiff.TAG_TILE_OFFSETS) orelse return formats.FormatError.CorruptedData;
+    const counts = ifd.find(tiff.TAG_TILE_BYTE_COUNTS) orelse return formats.FormatError.CorruptedData;
+    if (tile_width == 0 or tile_length == 0) return formats.FormatError.CorruptedData;
+    const tiles_across = (width + tile_width - 1) / tile_width;
+
+    for (0..offsets.count) |tile| {
+        const offset = offsets.u32_at(ifd.parser, tile) orelse return formats.FormatError.CorruptedData;
+        const size = counts.u32_at(ifd.parser, tile) orelse return formats.FormatError.CorruptedData;
+        if (!ifd.parser.in_bounds(offset, size)) return formats.FormatError.TruncatedFile;
+
+        var image = try decoders.ljpeg.decode(allocator, data[offset..][0..size]);
+        defer image.deinit();
+        if (image.frame.components != 4) return formats.FormatError.UnsupportedFormat;
+
+        const x0 = (tile % tiles_across) * tile_width;
+        const y0 = (tile / tiles_across) * tile_length;
+        for (0..image.frame.height) |jrow| {
+            for (0..image.frame.width) |jcol| {
+                const block = image.samples[(jrow * image.frame.width + jcol) * 4 ..][0..4];
+                for (block, 0..) |sample, k| {
+                    const row = y0 + jrow * 2 + k / 2;
+                    const col = x0 + jcol * 2 + k % 2;
+                    if (row < height and col < width) raw[row * width + col] = sample;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+// src/formats/cameras.zig - Per-model calibration for files that leave it out
+const std = @import("std");
+const formats = @import("mod.zig");
+const dng = @import("dng/mod.zig");
+
+/// What zrraw knows about a body, or about every body of a make when
+/// `model` is empty
+pub const Camera = struct {
+    /// Matched case-insensitively against the start of the file's Make
+    make: []const u8,
+    /// Matched case-insensitively against the whole Model, trailing blanks aside
+    model: []const u8 = "",
+    /// Readout the entry is for, as the format names it ("12bit", "14bit");
+    /// empty for any
+    mode: []const u8 = "",
+    canonical_make: ?[]const u8 = null,
+    canonical_model: ?[]const u8 = null,
+    /// XYZ (D65) to camera, scaled by 10000 as in Adobe's tables
+    color_matrix: ?[9]f32 = null,
+    black: ?u32 = null,
+    white: ?u32 = null,
+    /// Image area within the decoded sensor data
+    crop: ?formats.Rect = null,
+    /// Colours of the 2x2 CFA, row-major: 0 = red, 1 = green, 2 = blue
+    cfa: ?[4]u8 = null,
+};
+
+/// Camera list read from an override file
+pub const Database = struct {
+    arena: std.heap.ArenaAllocator,
+    cameras: []const Camera,
+
+    pub fn deinit(self: *Database) void {
+        self.arena.deinit();
+    }
+};
+
+var overrides: ?Database = null;
+var overrides_lock: std.Thread.Mutex = .{};
+
+/// Replaces the override database, whose entries take precedence over the
+/// built-in ones; empty text removes it
+pub fn load_overrides(text: []const u8) !void {
+    var database = try parse_toml(std.heap.c_allocator, text);
+    overrides_lock.lock();
+    defer overrides_lock.unlock();
+    if (overrides) |*old| old.deinit();
+    overrides = null;
+    if (database.cameras.len == 0) database.deinit() else overrides = database;
+}
+
+/// Fills in what the file did not record: the colour matrix when it has
+/// none, black levels when all are zero, the white level when the format
+/// fell back to the full range of its bit depth, and the crop. CFA
+/// overrides always apply, and canonical names are always set.
+pub fn apply(metadata: *formats.RawMetadata) !void {
+    overrides_lock.lock();
+    defer overrides_lock.unlock();
+    const overridden: []const Camera = if (overrides) |database| database.cameras else &.{};
+    const model = trim(metadata.model);
+    const camera = resolve(&.{ overridden, &known_cameras }, metadata.make, model, metadata.mode);
+
+    if (camera.color_matrix) |matrix| {
+        if (std.mem.allEqual(f32, &metadata.color_matrix, 0.0)) {
+            if (camera_to_xyz(matrix)) |inverse| metadata.color_matrix = inverse;
+        }
+    }
+    if (camera.black) |black| {
+        if (std.mem.allEqual(f32, &metadata.black_level, 0.0)) metadata.black_level = [_]f32{@floatFromInt(black)} ** 4;
+    }
+    if (camera.white) |white| {
+        const bits = metadata.bits_per_sample;
+        const full_range = if (bits > 0 and bits < 32) (@as(u32, 1) << @intCast(bits)) - 1 else 0;
+        if (std.mem.allEqual(u32, &metadata.white_level, full_range)) metadata.white_level = [_]u32{white} ** 4;
+    }
+    if (metadata.crop == null) metadata.crop = camera.crop;
+    if (camera.cfa) |colors| metadata.cfa = formats.CfaPattern.from_2x2(colors);
+
+    const make = camera.canonical_make orelse trim(metadata.make);
+    metadata.canonical_make = try metadata.allocator.dupe(u8, make);
+    const canonical_model = camera.canonical_model orelse
+        without_make(without_make(model, make), metadata.make);
+    metadata.canonical_model = try metadata.allocator.dupe(u8, canonical_model);
+}
+
+/// Merges the entries for a body: model entries before make-wide ones,
+/// earlier sources first, and entries for `mode` before those for any mode
+fn resolve(sources: []const []const Camera, make: []const u8, model: []const u8, mode: []const u8) Camera {
+    var camera = Camera{ .make = make, .model = model, .mode = mode };
+    for ([_][]const u8{ model, "" }) |wanted_model| {
+        for (sources) |cameras| {
+            for ([_][]const u8{ mode, "" }) |wanted_mode| {
+                const found = find(cameras, make, wanted_model, wanted_mode) orelse continue;
+                inline for (.{ "canonical_make", "canonical_model", "color_matrix", "black", "white", "crop", "cfa" }) |field| {
+                    if (@field(camera, field) == null) @field(camera, field) = @field(found, field);
+                }
+            }
+        }
+    }
+    return camera;
+}
+
+fn find(cameras: []const Camera, make: []const u8, model: []const u8, mode: []const u8) ?Camera {
+    for (cameras) |camera| {
+        if (std.ascii.startsWithIgnoreCase(make, camera.make) and
+            std.ascii.eqlIgnoreCase(camera.model, model) and
+            std.mem.eql(u8, camera.mode, mode)) return camera;
+    }
+    return null;
+}
+
+fn camera_to_xyz(xyz_to_camera: [9]f32) ?[9]f32 {
+    var m: [9]f64 = undefined;
+    for (&m, xyz_to_camera) |*out, v| out.* = v / 10000.0;
+    const inverse = dng.invert3(m) orelse return null;
+    var result: [9]f32 = undefined;
+    for (&result, inverse) |*out, v| out.* = @floatCast(v);
+    return result;
+}
+
+fn trim(text: []const u8) []const u8 {
+    return std.mem.trim(u8, text, " \x00");
+}
+
+/// Drops the make's first word from the front of a model ("NIKON D850")
+fn without_make(model: []const u8, make: []const u8) []const u8 {
+    const word = make[0 .. std.mem.indexOfAny(u8, make, " ,") orelse make.len];
+    if (word.len == 0 or model.len <= word.len + 1) return model;
+    if (!std.ascii.startsWithIgnoreCase(model, word) or model[word.len] != ' ') return model;
+    return model[word.len + 1 ..];
+}
+
+/// Reads a camera list in TOML, one `[[camera]]` table per entry:
+///
+///     [[camera]]
+///     make = "Panasonic"
+///     model = "DC-G9M2"
+///     mode = "14bit"
+///     canonical_model = "DC-G9 II"
+///     color_matrix = [ 8325, -3456, -623, -4330, 12089, 2528, -860, 2646, 5984 ]
+///     black = 512
+///     white = 16383
+///     crop = [ 8, 4, 5776, 4336 ]
+///     cfa = "RGGB"
+///
+/// Only these keys, strings without escapes, numbers and one-level arrays
+/// are understood; anything else is rejected.
+pub fn parse_toml(allocator: std.mem.Allocator, text: []const u8) !Database {
+    var arena = std.heap.ArenaAllocator.init(allocator);
+    errdefer arena.deinit();
+    const owned = arena.allocator();
+    var cameras = std.ArrayList(Camera).init(owned);
+
+    var lines = std.mem.splitScalar(u8, text, '\n');
+    while (lines.next()) |line| {
+        const content = std.mem.trim(u8, without_comment(line), " \t\r");
+        if (content.len == 0) continue;
+        if (std.mem.eql(u8, content, "[[camera]]")) {
+            try cameras.append(.{ .make = "" });
+            continue;
+        }
+        if (cameras.items.len == 0) return error.InvalidInput;
+        const equals = std.mem.indexOfScalar(u8, content, '=') orelse return error.InvalidInput;
+        const key = std.mem.trim(u8, content[0..equals], " \t");
+        var value = std.mem.trim(u8, content[equals + 1 ..], " \t");
+
+        // Arrays may run over several lines
+        if (std.mem.startsWith(u8, value, "[") and std.mem.indexOfScalar(u8, value, ']') == null) {
+            var joined = std.ArrayList(u8).init(owned);
+            try joined.appendSlice(value);
+            while (std.mem.indexOfScalar(u8, joined.items, ']') == null) {
+                const next = lines.next() orelse return error.InvalidInput;
+                try joined.append(' ');
+                try joined.appendSlice(std.mem.trim(u8, without_comment(next), " \t\r"));
+            }
+            value = joined.items;
+        }
+        try set_field(owned, &cameras.items[cameras.items.len - 1], key, value);
+    }
+    for (cameras.items) |camera| {
+        if (camera.make.len == 0) return error.InvalidInput;
+    }
+    return Database{ .arena = arena, .cameras = cameras.items };
+}
+
+fn without_comment(line: []const u8) []const u8 {
+    var quoted = false;
+    for (line, 0..) |c, i| {
+        if (c == '"') quoted = !quoted;
+        if (c == '#' and !quoted) return line[0..i];
+    }
+    return line;
+}
+
+fn set_field(allocator: std.mem.Allocator, camera: *Camera, key: []const u8, value: []const u8) !void {
+    const eql = std.mem.eql;
+    if (eql(u8, key, "make")) {
+        camera.make = try string(allocator, value);
+    } else if (eql(u8, key, "model")) {
+        camera.model = try string(allocator, value);
+    } else if (eql(u8, key, "mode")) {
+        camera.mode = try string(allocator, value);
+    } else if (eql(u8, key, "canonical_make")) {
+        camera.canonical_make = try string(allocator, value);
+    } else if (eql(u8, key, "canonical_model")) {
+        camera.canonical_model = try string(allocator, value);
+    } else if (eql(u8, key, "color_matrix")) {
+        camera.color_matrix = try numbers(9, value);
+    } else if (eql(u8, key, "black")) {
+        camera.black = std.fmt.parseInt(u32, value, 0) catch return error.InvalidInput;
+    } else if (eql(u8, key, "white")) {
+        camera.white = std.fmt.parseInt(u32, value, 0) catch return error.InvalidInput;
+    } else if (eql(u8, key, "crop")) {
+        var rect: [4]u32 = undefined;
+        for (&rect, try numbers(4, value)) |*out, v| {
+            if (v < 0 or v != @floor(v) or v > std.math.maxInt(u32)) return error.InvalidInput;
+            out.* = @intFromFloat(v);
+        }
+        camera.crop = .{ .x = rect[0], .y = rect[1], .width = rect[2], .height = rect[3] };
+    } else if (eql(u8, key, "cfa")) {
+        const letters = try string(allocator, value);
+        if (letters.len != 4) return error.InvalidInput;
+        var colors: [4]u8 = undefined;
+        for (&colors, letters) |*color, letter| color.* = switch (std.ascii.toUpper(letter)) {
+            'R' => 0,
+            'G' => 1,
+            'B' => 2,
+            else => return error.InvalidInput,
+        };
+        camera.cfa = colors;
+    } else return error.InvalidInput;
+}
+
+fn string(allocator: std.mem.Allocator, value: []const u8) ![]const u8 {
+    if (value.len < 2 or value[0] != '"' or value[value.len - 1] != '"') return error.InvalidInput;
+    const inner = value[1 .. value.len - 1];
+    if (std.mem.indexOfAny(u8, inner, "\"\\") != null) return error.InvalidInput;
+    return allocator.dupe(u8, inner);
+}
+
+fn numbers(comptime count: usize, value: []const u8) ![count]f32 {
+    if (value.len < 2 or value[0] != '[' or value[value.len - 1] != ']') return error.InvalidInput;
+    var result: [count]f32 = undefined;
+    var found: usize = 0;
+    var items = std.mem.splitScalar(u8, value[1 .. value.len - 1], ',');
+    while (items.next()) |item| {
+        const number = std.mem.trim(u8, item, " \t");
+        // A trailing comma is allowed
+        if (number.len == 0 and items.peek() == null) break;
+        if (found == count) return error.InvalidInput;
+        result[found] = std.fmt.parseFloat(f32, number) catch return error.InvalidInput;
+        found += 1;
+    }
+    if (found != count) return error.InvalidInput;
+    return result;
+}
+
+/// Matrices follow Adobe's published tables
+const known_cameras = [_]Camera{
+    // Canonical makes
+    .{ .make = "NIKON", .canonical_make = "Nikon" },
+    .{ .make = "OLYMPUS", .canonical_make = "Olympus" },
+    .{ .make = "OM Digital Solutions", .canonical_make = "OM System" },
+    .{ .make = "SONY", .canonical_make = "Sony" },
+    .{ .make = "FUJIFILM", .canonical_make = "Fujifilm" },
+    .{ .make = "PENTAX", .canonical_make = "Pentax" },
+    .{ .make = "RICOH IMAGING", .canonical_make = "Ricoh" },
+    .{ .make = "SAMSUNG", .canonical_make = "Samsung" },
+    .{ .make = "LEICA", .canonical_make = "Leica" },
+    .{ .make = "SIGMA", .canonical_make = "Sigma" },
+    .{ .make = "EASTMAN KODAK", .canonical_make = "Kodak" },
+    .{ .make = "KODAK", .canonical_make = "Kodak" },
+    .{ .make = "KONICA MINOLTA", .canonical_make = "Konica Minolta" },
+    .{ .make = "Minolta", .canonical_make = "Minolta" },
+    .{ .make = "SEIKO EPSON", .canonical_make = "Epson" },
+    .{ .make = "Phase One", .canonical_make = "Phase One" },
+    .{ .make = "Leaf", .canonical_make = "Leaf" },
+
+    // Panasonic RW2 files carry no colour matrix
+    .{ .make = "Panasonic", .canonical_make = "Panasonic" },
+    .{ .make = "Panasonic", .model = "DMC-G1", .color_matrix = .{ 8199, -2065, -1056, -8124, 16156, 2033, -2458, 3022, 7220 } },
+    .{ .make = "Panasonic", .model = "DMC-GH2", .color_matrix = .{ 7780, -2410, -806, -3913, 11724, 2484, -1018, 2390, 5298 } },
+    .{ .make = "Panasonic", .model = "DMC-GX1", .color_matrix = .{ 6763, -1919, -863, -3868, 11515, 2684, -1216, 2387, 5879 } },
+    .{ .make = "Panasonic", .model = "DMC-GH3", .color_matrix = .{ 6559, -1752, -491, -3672, 11407, 2586, -962, 1875, 5130 } },
+    .{ .make = "Panasonic", .model = "DMC-GM1", .color_matrix = .{ 6770, -1895, -744, -5232, 13145, 2303, -1664, 2691, 5703 } },
+    .{ .make = "Panasonic", .model = "DMC-GX7", .color_matrix = .{ 7610, -2780, -576, -4614, 12195, 2733, -1375, 2393, 6490 } },
+    .{ .make = "Panasonic", .model = "DMC-GH4", .color_matrix = .{ 7122, -2108, -512, -3155, 11201, 2231, -541, 1423, 5045 } },
+    .{ .make = "Panasonic", .model = "DMC-G7", .color_matrix = .{ 7610, -2780, -576, -4614, 12195, 2733, -1375, 2393, 6490 } },
+    .{ .make = "Panasonic", .model = "DMC-GX8", .color_matrix = .{ 7564, -2263, -606, -3148, 11239, 2177, -540, 1435, 4853 } },
+    .{ .make = "Panasonic", .model = "DMC-G85", .color_matrix = .{ 7610, -2780, -576, -4614, 12195, 2733, -1375, 2393, 6490 } },
+    .{ .make = "Panasonic", .model = "DMC-G80", .canonical_model = "DMC-G85", .color_matrix = .{ 7610, -2780, -576, -4614, 12195, 2733, -1375, 2393, 6490 } },
+    .{ .make = "Panasonic", .model = "DMC-GX85", .color_matrix = .{ 7771, -3020, -629, -4029, 11950, 2345, -821, 1977, 6119 } },
+    .{ .make = "Panasonic", .model = "DMC-GX80", .canonical_model = "DMC-GX85", .color_matrix = .{ 7771, -3020, -629, -4029, 11950, 2345, -821, 1977, 6119 } },
+    .{ .make = "Panasonic", .model = "DMC-FZ200", .color_matrix = .{ 8112, -2563, -740, -3730, 11784, 2197, -941, 2075, 4933 } },
+    .{ .make = "Panasonic", .model = "DMC-FZ1000", .color_matrix = .{ 7830, -2696, -763, -3325, 11667, 1866, -641, 1712, 4824 } },
+    .{ .make = "Panasonic", .model = "DMC-LX7", .color_matrix = .{ 10148, -3743, -991, -2837, 11366, 1659, -701, 1893, 4899 } },
+    .{ .make = "Panasonic", .model = "DMC-LX100", .color_matrix = .{ 8844, -3538, -768, -3709, 11762, 2200, -698, 1792, 5220 } },
+    .{ .make = "Panasonic", .model = "DC-GH5", .color_matrix = .{ 7641, -2336, -605, -3218, 11299, 2187, -485, 1338, 5121 } },
+    .{ .make = "Panasonic", .model = "DC-G9", .color_matrix = .{ 7685, -2375, -634, -3687, 11700, 2249, -748, 1546, 5111 } },
+    .{ .make = "Panasonic", .model = "DC-S1", .color_matrix = .{ 9744, -3905, -779, -4899, 12807, 2324, -798, 1630, 5827 } },
+
+    // Nikon keeps its matrices out of NEF files
+    .{ .make = "NIKON", .model = "NIKON D5300", .color_matrix = .{ 6988, -1384, -714, -5631, 13410, 2447, -1485, 2204, 7318 } },
+    .{ .make = "NIKON", .model = "NIKON D7500", .color_matrix = .{ 8813, -3210, -1036, -4703, 12868, 2021, -1054, 1940, 6129 } },
+    .{ .make = "NIKON", .model = "NIKON D750", .color_matrix = .{ 9020, -2890, -715, -4535, 12436, 2348, -934, 1919, 7086 } },
+    .{ .make = "NIKON", .model = "NIKON D850", .color_matrix = .{ 10405, -3755, -1270, -5461, 13787, 1793, -1040, 2015, 6785 } },
+    .{ .make = "NIKON", .model = "NIKON Z 6", .color_matrix = .{ 8210, -2534, -683, -5355, 13338, 2212, -1143, 1929, 6464 } },
+
+    // Sony ARW files carry none either
+    .{ .make = "SONY", .model = "ILCE-6000", .color_matrix = .{ 5991, -1456, -455, -4764, 12135, 2980, -707, 1425, 6701 } },
+    .{ .make = "SONY", .model = "ILCE-7M3", .color_matrix = .{ 7374, -2389, -551, -5435, 13162, 2519, -1006, 1795, 6552 } },
+    .{ .make = "SONY", .model = "ILCE-7RM3", .color_matrix = .{ 6640, -1847, -503, -5238, 13010, 2474, -993, 1673, 6527 } },
+
+    .{ .make = "PENTAX", .model = "PENTAX K-3", .color_matrix = .{ 7415, -2052, -721, -5186, 12788, 2682, -1446, 2157, 6773 } },
+    .{ .make = "RICOH IMAGING", .model = "PENTAX K-1", .canonical_make = "Pentax", .color_matrix = .{ 8596, -2981, -639, -4202, 12046, 2431, -685, 1424, 6122 } },
+};
+
+test "override entries take precedence and fill only what is missing" {
+    var database = try parse_toml(std.testing.allocator,
+        \\# A body newer than the built-in table
+        \\[[camera]]
+        \\make = "Panasonic"
+        \\model = "DC-G9M2"
+        \\canonical_model = "DC-G9 II"
+        \\color_matrix = [ 10000, 0, 0,
+        \\                 0, 5000, 0,
+        \\                 0, 0, 2500 ]
+        \\white = 16383
+        \\
+        \\[[camera]]
+        \\make = "Panasonic"
+        \\model = "DC-G9M2"
+        \\mode = "12bit"
+        \\white = 4095
+        \\cfa = "GBRG"  # read out one row down
+    );
+    defer database.deinit();
+    try std.testing.expectEqual(@as(usize, 2), database.cameras.len);
+    try std.testing.expectError(error.InvalidInput, parse_toml(std.testing.allocator, "[[camera]]\nmake = \"X\"\ncolour = 1\n"));
+
+    const camera = resolve(&.{ database.cameras, &known_cameras }, "Panasonic", "DC-G9M2", "14bit");
+    try std.testing.expectEqual(@as(?u32, 16383), camera.white);
+    try std.testing.expectEqualStrings("Panasonic", camera.canonical_make.?);
+    const twelve = resolve(&.{ database.cameras, &known_cameras }, "Panasonic", "DC-G9M2", "12bit");
+    try std.testing.expectEqual(@as(?u32, 4095), twelve.white);
+    try std.testing.expectEqual([4]u8{ 1, 2, 0, 1 }, twelve.cfa.?);
+
+    var metadata = std.mem.zeroInit(formats.RawMetadata, .{
+        .format = .NEF,
+        .make = "NIKON CORPORATION",
+        .model = "NIKON D850 ",
+        .color_matrix = [_]f32{0} ** 9,
+        .white_balance = [_]f32{1} ** 3,
+        .black_level = [_]f32{ 600, 600, 600, 600 },
+        .white_level = [_]u32{16383} ** 4,
+        .bits_per_sample = 14,
+        .allocator = std.testing.allocator,
+    });
+    try apply(&metadata);
+    defer std.testing.allocator.free(metadata.canonical_make);
+    defer std.testing.allocator.free(metadata.canonical_model);
+    try std.testing.expectEqualStrings("Nikon", metadata.canonical_make);
+    try std.testing.expectEqualStrings("D850", metadata.canonical_model);
+    try std.testing.expect(metadata.color_matrix[0] != 0.0);
+    try std.testing.expectEqual(@as(f32, 600), metadata.black_level[0]);
+}
@@ -0,0 +1,499 @@
+// src/formats/color.zig - Colour calibration and white point interpolation
+//
+// Follows "Mapping Camera Color Space to CIE XYZ Space" in the DNG
+// specification: the XYZ to camera matrices of the calibration illuminants
+// are blended by inverse colour temperature of the white point, which for an
+// as-shot neutral is found iteratively.
+const std = @import("std");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const dng = @import("dng/mod.zig");
+
+/// Row-major 3x3
+pub const Matrix = [9]f64;
+
+const identity = Matrix{ 1, 0, 0, 0, 1, 0, 0, 0, 1 };
+
+/// EXIF LightSource value of D65, which single-matrix calibrations assume
+pub const ILLUMINANT_D65 = 21;
+
+/// The DNG profile connection space white
+pub const d50_xy = [2]f64{ 0.3457, 0.3585 };
+
+// XYZ D50 to linear sRGB, Bradford-adapted to D65
+const xyz_d50_to_srgb = Matrix{
+    3.1338561,  -1.6168667, -0.4906146,
+    -0.9787684, 1.9161415,  0.0334540,
+    0.0719453,  -0.2289914, 1.4052427,
+};
+
+const bradford = Matrix{
+    0.8951,  0.2664,  -0.1614,
+    -0.7502, 1.7135,  0.0367,
+    0.0389,  -0.0685, 1.0296,
+};
+
+const bradford_inverse = Matrix{
+    0.9869929,  -0.1470543, 0.1599627,
+    0.4323053,  0.5183603,  0.0492912,
+    -0.0085287, 0.0400428,  0.9684867,
+};
+
+pub const Calibration = struct {
+    /// EXIF LightSource value of the CalibrationIlluminant tag
+    illuminant: u16,
+    /// XYZ to reference camera space
+    color_matrix: Matrix,
+    /// Reference camera space to this body's own response
+    camera_calibration: ?Matrix = null,
+    /// White-balanced camera space to XYZ D50
+    forward_matrix: ?Matrix = null,
+};
+
+/// DNG colour tags, or the single matrix other formats carry
+pub const Profile = struct {
+    /// Up to three illuminants (DNG 1.6), in tag order
+    calibrations: [3]Calibration = undefined,
+    count: usize = 0,
+    analog_balance: [3]f64 = .{ 1.0, 1.0, 1.0 },
+    as_shot_neutral: ?[3]f64 = null,
+    as_shot_white_xy: ?[2]f64 = null,
+
+    pub fn items(self: *const Profile) []const Calibration {
+        return self.calibrations[0..self.count];
+    }
+
+    /// The camera to XYZ and sRGB matrices for rendering `white` as
+    /// neutral, or null if the calibration cannot be inverted
+    pub fn matrices(self: Profile, white: White) ?Matrices {
+        if (self.count == 0) return null;
+        var neutral: [3]f64 = undefined;
+        var white_xy: [2]f64 = undefined;
+        switch (white) {
+            .neutral => |camera_neutral| {
+                neutral = camera_neutral;
+                white_xy = self.neutral_to_xy(camera_neutral) orelse return null;
+            },
+            .xy => |xy| {
+                white_xy = xy;
+                neutral = self.xy_to_neutral(xy) orelse return null;
+            },
+            .temperature => |temperature| {
+                white_xy = temperature_to_xy(temperature);
+                neutral = self.xy_to_neutral(white_xy) orelse return null;
+            },
+        }
+        neutral = normalized(neutral) orelse return null;
+
+        const blended = self.interpolate(white_xy);
+        var camera_to_xyz: Matrix = undefined;
+        if (blended.forward_matrix) |forward| {
+            const to_reference = dng.invert3(blended.reference) orelse return null;
+            const reference_neutral = apply(to_reference, neutral);
+            var scale: [3]f64 = undefined;
+            for (&scale, reference_neutral) |*s, n| {
+                if (!(n > 0.0)) return null;
+                s.* = 1.0 / n;
+            }
+            camera_to_xyz = mul(forward, mul(diagonal(scale), to_reference));
+        } else {
+            const to_xyz = dng.invert3(blended.xyz_to_camera) orelse return null;
+            camera_to_xyz = mul(adaptation(xy_to_xyz(white_xy), xy_to_xyz(d50_xy)), to_xyz);
+        }
+
+        // The neutral maps to Y = 1
+        const y = apply(camera_to_xyz, neutral)[1];
+        if (!(y > 0.0)) return null;
+        for (&camera_to_xyz) |*v| v.* /= y;
+
+        return .{
+            .camera_to_xyz = camera_to_xyz,
+            .camera_to_srgb = mul(xyz_d50_to_srgb, camera_to_xyz),
+            .neutral = neutral,
+            .white_xy = white_xy,
+            .temperature = xy_to_temperature(white_xy),
+        };
+    }
+
+    /// Iterates from D50 until the white the neutral implies stops moving
+    fn neutral_to_xy(self: Profile, neutral: [3]f64) ?[2]f64 {
+        const max_passes = 30;
+        var last = d50_xy;
+        for (0..max_passes) |pass| {
+            const to_xyz = dng.invert3(self.interpolate(last).xyz_to_camera) orelse return null;
+            var next = xyz_to_xy(apply(to_xyz, neutral)) orelse return null;
+            if (@abs(next[0] - last[0]) + @abs(next[1] - last[1]) < 1e-7) return next;
+            // Settle between the last two guesses in case they oscillate
+            if (pass == max_passes - 1) next = .{ (last[0] + next[0]) / 2.0, (last[1] + next[1]) / 2.0 };
+            last = next;
+        }
+        return last;
+    }
+
+    fn xy_to_neutral(self: Profile, xy: [2]f64) ?[3]f64 {
+        return normalized(apply(self.interpolate(xy).xyz_to_camera, xy_to_xyz(xy)));
+    }
+
+    const Blended = struct {
+        /// AnalogBalance * CameraCalibration * ColorMatrix
+        xyz_to_camera: Matrix,
+        /// AnalogBalance * CameraCalibration
+        reference: Matrix,
+        forward_matrix: ?Matrix,
+    };
+
+    fn interpolate(self: Profile, xy: [2]f64) Blended {
+        const pair = self.bracket(xy_to_temperature(xy).kelvin);
+        const first = self.calibrations[pair.first];
+        const second = self.calibrations[pair.second];
+        const calibration = mix(
+            first.camera_calibration orelse identity,
+            second.camera_calibration orelse identity,
+            pair.weight,
+        );
+        const forward: ?Matrix = if (first.forward_matrix != null and second.forward_matrix != null)
+            mix(first.forward_matrix.?, second.forward_matrix.?, pair.weight)
+        else
+            null;
+        const reference = mul(diagonal(self.analog_balance), calibration);
+        return .{
+            .xyz_to_camera = mul(reference, mix(first.color_matrix, second.color_matrix, pair.weight)),
+            .reference = reference,
+            .forward_matrix = forward,
+        };
+    }
+
+    const Pair = struct { first: usize, second: usize, weight: f64 };
+
+    /// The calibrations whose illuminants bracket `kelvin` and the weight of
+    /// the first. With three illuminants only the bracketing two are blended;
+    /// without two known temperatures the first calibration is used alone.
+    fn bracket(self: Profile, kelvin: f64) Pair {
+        var order: [3]usize = undefined;
+        var temperatures: [3]f64 = undefined;
+        var known: usize = 0;
+        for (self.items(), 0..) |calibration, i| {
+            const t = illuminant_temperature(calibration.illuminant);
+            if (t <= 0.0) continue;
+            var j = known;
+            while (j > 0 and temperatures[j - 1] > t) : (j -= 1) {
+                temperatures[j] = temperatures[j - 1];
+                order[j] = order[j - 1];
+            }
+            temperatures[j] = t;
+            order[j] = i;
+            known += 1;
+        }
+        if (known < 2 or !(kelvin > 0.0)) return .{ .first = 0, .second = 0, .weight = 1.0 };
+        if (kelvin <= temperatures[0]) return .{ .first = order[0], .second = order[0], .weight = 1.0 };
+        for (1..known) |k| {
+            if (kelvin < temperatures[k]) {
+                const weight = (1.0 / kelvin - 1.0 / temperatures[k]) /
+                    (1.0 / temperatures[k - 1] - 1.0 / temperatures[k]);
+                return .{ .first = order[k - 1], .second = order[k], .weight = weight };
+            }
+        }
+        return .{ .first = order[known - 1], .second = order[known - 1], .weight = 1.0 };
+    }
+};
+
+pub const Temperature = struct {
+    kelvin: f64,
+    /// Offset from the Planckian locus, positive towards magenta, in the
+    /// DNG SDK's units
+    tint: f64 = 0.0,
+};
+
+/// What to render as neutral
+pub const White = union(enum) {
+    /// Camera response to the white, as in AsShotNeutral
+    neutral: [3]f64,
+    /// CIE 1931 chromaticity
+    xy: [2]f64,
+    temperature: Temperature,
+};
+
+pub const Matrices = struct {
+    /// Camera space, before white balance, to XYZ D50; the neutral maps to
+    /// the D50 white with Y = 1
+    camera_to_xyz: Matrix,
+    /// Camera space, before white balance, to linear sRGB
+    camera_to_srgb: Matrix,
+    /// Camera response to the white, largest component 1
+    neutral: [3]f64,
+    white_xy: [2]f64,
+    temperature: Temperature,
+};
+
+const calibration_tags = [3][4]u16{
+    .{ dng.TAG_CALIBRATION_ILLUMINANT_1, dng.TAG_COLOR_MATRIX_1, dng.TAG_CAMERA_CALIBRATION_1, dng.TAG_FORWARD_MATRIX_1 },
+    .{ dng.TAG_CALIBRATION_ILLUMINANT_2, dng.TAG_COLOR_MATRIX_2, dng.TAG_CAMERA_CALIBRATION_2, dng.TAG_FORWARD_MATRIX_2 },
+    .{ dng.TAG_CALIBRATION_ILLUMINANT_3, dng.TAG_COLOR_MATRIX_3, dng.TAG_CAMERA_CALIBRATION_3, dng.TAG_FORWARD_MATRIX_3 },
+};
+
+/// Reads the DNG colour tags of IFD0. Only three-colour calibrations are
+/// handled; IlluminantData for illuminant 255 is not read.
+pub fn from_ifd(ifd0: tiff.Ifd) ?Profile {
+    var profile = Profile{};
+    for (calibration_tags) |tags| {
+        const color_matrix = read_matrix(ifd0, tags[1]) orelse continue;
+        profile.calibrations[profile.count] = .{
+            .illuminant = @intCast(@min(ifd0.get_u32(tags[0]) orelse 0, std.math.maxInt(u16))),
+            .color_matrix = color_matrix,
+            .camera_calibration = read_matrix(ifd0, tags[2]),
+            .forward_matrix = read_matrix(ifd0, tags[3]),
+        };
+        profile.count += 1;
+    }
+    if (profile.count == 0) return null;
+    if (read_positive(3, ifd0, dng.TAG_ANALOG_BALANCE)) |balance| profile.analog_balance = balance;
+    profile.as_shot_neutral = read_positive(3, ifd0, dng.TAG_AS_SHOT_NEUTRAL);
+    profile.as_shot_white_xy = read_positive(2, ifd0, dng.TAG_AS_SHOT_WHITE_XY);
+    return profile;
+}
+
+/// The file's profile, or a D65 one built from `RawMetadata.color_matrix`
+/// for formats that carry a single matrix
+pub fn of(metadata: formats.RawMetadata) ?Profile {
+    if (metadata.color_profile) |profile| return profile;
+    if (std.mem.allEqual(f32, &metadata.color_matrix, 0.0)) return null;
+    var camera_to_xyz: Matrix = undefined;
+    for (&camera_to_xyz, metadata.color_matrix) |*out, v| out.* = v;
+    var profile = Profile{ .count = 1 };
+    profile.calibrations[0] = .{
+        .illuminant = ILLUMINANT_D65,
+        .color_matrix = dng.invert3(camera_to_xyz) orelse return null,
+    };
+    return profile;
+}
+
+fn read_matrix(ifd: tiff.Ifd, tag: u16) ?Matrix {
+    const entry = ifd.find(tag) orelse return null;
+    if (entry.count != 9) return null;
+    var m: Matrix = undefined;
+    for (&m, 0..) |*v, i| v.* = entry.f64_at(ifd.parser, i) orelse return null;
+    return m;
+}
+
+fn read_positive(comptime n: usize, ifd: tiff.Ifd, tag: u16) ?[n]f64 {
+    const entry = ifd.find(tag) orelse return null;
+    if (entry.count < n) return null;
+    var values: [n]f64 = undefined;
+    for (&values, 0..) |*v, i| {
+        v.* = entry.f64_at(ifd.parser, i) orelse return null;
+        if (!(v.* > 0.0)) return null;
+    }
+    return values;
+}
+
+/// Temperatures the DNG SDK assigns to the EXIF light sources; 0 when unknown
+pub fn illuminant_temperature(illuminant: u16) f64 {
+    return switch (illuminant) {
+        3, 17 => 2850.0, // Tungsten, standard light A
+        24 => 3200.0, // ISO studio tungsten
+        23 => 5000.0, // D50
+        1, 4, 9, 18, 20 => 5500.0, // Daylight, flash, fine weather, standard light B, D55
+        10, 19, 21 => 6500.0, // Cloudy, standard light C, D65
+        11, 22 => 7500.0, // Shade, D75
+        12 => 6400.0, // Daylight fluorescent
+        13 => 5000.0, // Day white fluorescent
+        2, 14 => 4150.0, // Fluorescent, cool white fluorescent
+        15 => 3450.0, // White fluorescent
+        16 => 2925.0, // Warm white fluorescent
+        else => 0.0,
+    };
+}
+
+// Robertson's isotemperature lines: reciprocal megakelvin, CIE 1960 u and v
+// of the locus point, and the slope of the line
+const Isotherm = struct { mired: f64, u: f64, v: f64, slope: f64 };
+
+const isotherms = [_]Isotherm{
+    .{ .mired = 0, .u = 0.18006, .v = 0.26352, .slope = -0.24341 },
+    .{ .mired = 10, .u = 0.18066, .v = 0.26589, .slope = -0.25479 },
+    .{ .mired = 20, .u = 0.18133, .v = 0.26846, .slope = -0.26876 },
+    .{ .mired = 30, .u = 0.18208, .v = 0.27119, .slope = -0.28539 },
+    .{ .mired = 40, .u = 0.18293, .v = 0.27407, .slope = -0.30470 },
+    .{ .mired = 50, .u = 0.18388, .v = 0.27709, .slope = -0.32675 },
+    .{ .mired = 60, .u = 0.18494, .v = 0.28021, .slope = -0.35156 },
+    .{ .mired = 70, .u = 0.18611, .v = 0.28342, .slope = -0.37915 },
+    .{ .mired = 80, .u = 0.18740, .v = 0.28668, .slope = -0.40955 },
+    .{ .mired = 90, .u = 0.18880, .v = 0.28997, .slope = -0.44278 },
+    .{ .mired = 100, .u = 0.19032, .v = 0.29326, .slope = -0.47888 },
+    .{ .mired = 125, .u = 0.19462, .v = 0.30141, .slope = -0.58204 },
+    .{ .mired = 150, .u = 0.19962, .v = 0.30921, .slope = -0.70471 },
+    .{ .mired = 175, .u = 0.20525, .v = 0.31647, .slope = -0.84901 },
+    .{ .mired = 200, .u = 0.21142, .v = 0.32312, .slope = -1.0182 },
+    .{ .mired = 225, .u = 0.21807, .v = 0.32909, .slope = -1.2168 },
+    .{ .mired = 250, .u = 0.22511, .v = 0.33439, .slope = -1.4512 },
+    .{ .mired = 275, .u = 0.23247, .v = 0.33904, .slope = -1.7298 },
+    .{ .mired = 300, .u = 0.24010, .v = 0.34308, .slope = -2.0637 },
+    .{ .mired = 325, .u = 0.24792, .v = 0.34655, .slope = -2.4681 },
+    .{ .mired = 350, .u = 0.25591, .v = 0.34951, .slope = -2.9641 },
+    .{ .mired = 375, .u = 0.26400, .v = 0.35200, .slope = -3.5814 },
+    .{ .mired = 400, .u = 0.27218, .v = 0.35407, .slope = -4.3633 },
+    .{ .mired = 425, .u = 0.28039, .v = 0.35577, .slope = -5.3762 },
+    .{ .mired = 450, .u = 0.28863, .v = 0.35714, .slope = -6.7262 },
+    .{ .mired = 475, .u = 0.29685, .v = 0.35823, .slope = -8.5955 },
+    .{ .mired = 500, .u = 0.30505, .v = 0.35907, .slope = -11.324 },
+    .{ .mired = 525, .u = 0.31320, .v = 0.35968, .slope = -15.628 },
+    .{ .mired = 550, .u = 0.32129, .v = 0.36011, .slope = -23.325 },
+    .{ .mired = 575, .u = 0.32931, .v = 0.36038, .slope = -40.770 },
+    .{ .mired = 600, .u = 0.33724, .v = 0.36051, .slope = -116.45 },
+};
+
+const tint_scale = -3000.0;
+
+pub fn xy_to_temperature(xy: [2]f64) Temperature {
+    const denom = 1.5 - xy[0] + 6.0 * xy[1];
+    const u = 2.0 * xy[0] / denom;
+    const v = 3.0 * xy[1] / denom;
+
+    var last_dt: f64 = 0.0;
+    var last_du: f64 = 0.0;
+    var last_dv: f64 = 0.0;
+    for (1..isotherms.len) |i| {
+        const line = isotherms[i];
+        const prev = isotherms[i - 1];
+        const len = @sqrt(1.0 + line.slope * line.slope);
+        const du = 1.0 / len;
+        const dv = line.slope / len;
+        // Signed distance from this isotherm; the white lies between the
+        // first line it is not above and the one before
+        var dt = -(u - line.u) * dv + (v - line.v) * du;
+        if (dt <= 0.0 or i == isotherms.len - 1) {
+            dt = -@min(dt, 0.0);
+            const f: f64 = if (i == 1) 0.0 else dt / (last_dt + dt);
+            var tu = du * (1.0 - f) + last_du * f;
+            var tv = dv * (1.0 - f) + last_dv * f;
+            const tlen = @sqrt(tu * tu + tv * tv);
+            tu /= tlen;
+            tv /= tlen;
+            const off_u = u - (prev.u * f + line.u * (1.0 - f));
+            const off_v = v - (prev.v * f + line.v * (1.0 - f));
+            return .{
+                .kelvin = 1.0e6 / (prev.mired * f + line.mired * (1.0 - f)),
+                .tint = (off_u * tu + off_v * tv) * tint_scale,
+            };
+        }
+        last_dt = dt;
+        last_du = du;
+        last_dv = dv;
+    }
+    unreachable;
+}
+
+pub fn temperature_to_xy(temperature: Temperature) [2]f64 {
+    const mired = 1.0e6 / temperature.kelvin;
+    var u: f64 = 0.0;
+    var v: f64 = 0.0;
+    for (0..isotherms.len - 1) |i| {
+        const a = isotherms[i];
+        const b = isotherms[i + 1];
+        if (mired < b.mired or i == isotherms.len - 2) {
+            const f = (b.mired - mired) / (b.mired - a.mired);
+            u = a.u * f + b.u * (1.0 - f);
+            v = a.v * f + b.v * (1.0 - f);
+            const len_a = @sqrt(1.0 + a.slope * a.slope);
+            const len_b = @sqrt(1.0 + b.slope * b.slope);
+            var du = f / len_a + (1.0 - f) / len_b;
+            var dv = a.slope / len_a * f + b.slope / len_b * (1.0 - f);
+            const len = @sqrt(du * du + dv * dv);
+            du /= len;
+            dv /= len;
+            u += du * temperature.tint / tint_scale;
+            v += dv * temperature.tint / tint_scale;
+            break;
+        }
+    }
+    const denom = u - 4.0 * v + 2.0;
+    return .{ 1.5 * u / denom, v / denom };
+}
+
+fn xy_to_xyz(xy: [2]f64) [3]f64 {
+    return .{ xy[0] / xy[1], 1.0, (1.0 - xy[0] - xy[1]) / xy[1] };
+}
+
+fn xyz_to_xy(xyz: [3]f64) ?[2]f64 {
+    const sum = xyz[0] + xyz[1] + xyz[2];
+    if (!(sum > 0.0)) return null;
+    return .{ xyz[0] / sum, xyz[1] / sum };
+}
+
+/// Bradford chromatic adaptation between two white XYZs
+fn adaptation(from: [3]f64, to: [3]f64) Matrix {
+    const from_cone = apply(bradford, from);
+    const to_cone = apply(bradford, to);
+    var scale: [3]f64 = undefined;
+    for (&scale, from_cone, to_cone) |*s, a, b| s.* = if (a > 0.0) std.math.clamp(b / a, 0.1, 10.0) else 1.0;
+    return mul(bradford_inverse, mul(diagonal(scale), bradford));
+}
+
+/// Scales to a largest component of 1; null unless every component is positive
+fn normalized(v: [3]f64) ?[3]f64 {
+    const max = @max(v[0], @max(v[1], v[2]));
+    if (!(v[0] > 0.0 and v[1] > 0.0 and v[2] > 0.0)) return null;
+    return .{ v[0] / max, v[1] / max, v[2] / max };
+}
+
+pub fn mul(a: Matrix, b: Matrix) Matrix {
+    var m: Matrix = undefined;
+    for (0..3) |i| {
+        for (0..3) |j| {
+            var sum: f64 = 0.0;
+            for (0..3) |k| sum += a[i * 3 + k] * b[k * 3 + j];
+            m[i * 3 + j] = sum;
+        }
+    }
+    return m;
+}
+
+pub fn apply(m: Matrix, v: [3]f64) [3]f64 {
+    var out: [3]f64 = undefined;
+    for (0..3) |i| out[i] = m[i * 3] * v[0] + m[i * 3 + 1] * v[1] + m[i * 3 + 2] * v[2];
+    return out;
+}
+
+pub fn diagonal(v: [3]f64) Matrix {
+    return .{ v[0], 0, 0, 0, v[1], 0, 0, 0, v[2] };
+}
+
+fn mix(a: Matrix, b: Matrix, weight: f64) Matrix {
+    var m: Matrix = undefined;
+    for (&m, a, b) |*out, x, y| out.* = x * weight + y * (1.0 - weight);
+    return m;
+}
+
+test "as-shot neutrals interpolate between the calibration illuminants" {
+    var profile = Profile{ .count = 2 };
+    profile.calibrations[0] = .{
+        .illuminant = 17,
+        .color_matrix = .{ 0.8, -0.2, -0.1, -0.4, 1.3, 0.2, -0.05, 0.2, 0.5 },
+    };
+    profile.calibrations[1] = .{
+        .illuminant = ILLUMINANT_D65,
+        .color_matrix = .{ 0.7, -0.15, -0.1, -0.45, 1.25, 0.25, -0.1, 0.25, 0.65 },
+    };
+
+    // Round trip through the temperature, tint included
+    const warm = xy_to_temperature(temperature_to_xy(.{ .kelvin = 5000.0, .tint = 10.0 }));
+    try std.testing.expectApproxEqAbs(@as(f64, 5000.0), warm.kelvin, 1e-6);
+    try std.testing.expectApproxEqAbs(@as(f64, 10.0), warm.tint, 1e-6);
+
+    // Between the illuminants the matrices blend by inverse temperature
+    const pair = profile.bracket(5500.0);
+    try std.testing.expectEqual(@as(usize, 0), pair.first);
+    try std.testing.expectApproxEqAbs(@as(f64, 0.14197), pair.weight, 1e-5);
+
+    // The neutral a temperature implies leads back to that temperature
+    const by_temperature = profile.matrices(.{ .temperature = .{ .kelvin = 5500.0 } }).?;
+    const by_neutral = profile.matrices(.{ .neutral = by_temperature.neutral }).?;
+    try std.testing.expectApproxEqAbs(by_temperature.white_xy[0], by_neutral.white_xy[0], 1e-6);
+    try std.testing.expectApproxEqAbs(by_temperature.white_xy[1], by_neutral.white_xy[1], 1e-6);
+    try std.testing.expectApproxEqAbs(@as(f64, 5500.0), by_neutral.temperature.kelvin, 1.0);
+
+    // and renders as sRGB white
+    for (apply(by_neutral.camera_to_srgb, by_neutral.neutral)) |c| {
+        try std.testing.expectApproxEqAbs(@as(f64, 1.0), c, 1e-3);
+    }
+}
@@ -0,0 +1,297 @@
+// src/formats/crw.zig - Canon CRW (CIFF) RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+const SIGNATURE = "HEAPCCDR";
+
+// CIFF record types
+const CIFF_MAKE_MODEL = 0x080A;
+const CIFF_SHOT_INFO = 0x102A;
+const CIFF_SENSOR_INFO = 0x1031;
+const CIFF_COLOR_BALANCE = 0x10A9;
+const CIFF_IMAGE_INFO = 0x1810;
+const CIFF_DECODER_TABLE = 0x1835;
+const CIFF_RAW_DATA = 0x2005;
+const CIFF_JPG_FROM_RAW = 0x2007;
+const CIFF_THUMBNAIL = 0x2008;
+const CIFF_FOCAL_LENGTH = 0x5029;
+
+/// Storage bits of a record type marking data held in the record itself
+const STORAGE_MASK = 0xC000;
+const STORAGE_INLINE = 0x4000;
+const RECORD_SIZE = 10;
+/// Heaps nest inside records whose type has one of these high bytes
+const SUBHEAP_KINDS = [_]u16{ 0x28, 0x30 };
+const MAX_HEAP_DEPTH = 8;
+
+/// The compressed data follows a header of this size in the raw data record
+const RAW_HEADER_SIZE = 514;
+/// End of the region, counted from the raw data, that shows whether the
+/// optional low-bit plane is present
+const LOW_BITS_PROBE_END = 0x4000 - 26;
+
+/// ColorBalance records longer than this list ten presets, in this order
+/// relative to the ShotInfo white balance index
+const LONG_COLOR_BALANCE = 66;
+const COLOR_BALANCE_PRESETS = "0134567028";
+
+/// The records this reader uses
+const Ciff = struct {
+    make: []const u8 = "Canon",
+    model: []const u8 = "",
+    iso: u32 = 0,
+    aperture: f32 = 0.0,
+    shutter_speed: f32 = 0.0,
+    focal_length: f32 = 0.0,
+    white_balance_index: u16 = 0,
+    color_balance: ?[]const u8 = null,
+    /// Sensor size and the borders of its exposed area
+    sensor: ?[9]u16 = null,
+    image_width: u32 = 0,
+    image_height: u32 = 0,
+    rotation: i32 = 0,
+    decoder_table: u32 = 0,
+    raw_data: ?[]const u8 = null,
+    raw_offset: usize = 0,
+    jpeg: ?[]const u8 = null,
+    thumbnail: ?[]const u8 = null,
+};
+
+pub fn detect(data: []const u8) bool {
+    if (data.len < 14) return false;
+    if (!std.mem.eql(u8, data[0..2], "II") and !std.mem.eql(u8, data[0..2], "MM")) return false;
+    return std.mem.eql(u8, data[6..14], SIGNATURE);
+}
+
+fn byte_order(data: []const u8) std.builtin.Endian {
+    return if (data[0] == 'I') .little else .big;
+}
+
+fn locate(data: []const u8) !Ciff {
+    if (!detect(data)) return formats.FormatError.InvalidHeader;
+    const order = byte_order(data);
+    const header_size = std.mem.readInt(u32, data[2..6], order);
+    if (header_size >= data.len) return formats.FormatError.CorruptedData;
+
+    var ciff = Ciff{};
+    read_heap(data, order, header_size, data.len - header_size, &ciff, 0);
+    return ciff;
+}
+
+/// Reads the records of the heap at `offset`; its record table sits where
+/// the last four bytes of the heap point
+fn read_heap(data: []const u8, order: std.builtin.Endian, offset: usize, length: usize, ciff: *Ciff, depth: u32) void {
+    if (depth >= MAX_HEAP_DEPTH or length < 4) return;
+    const heap = data[offset..][0..length];
+    const table: usize = std.mem.readInt(u32, heap[length - 4 ..][0..4], order);
+    if (table + 2 > length) return;
+    const count = std.mem.readInt(u16, heap[table..][0..2], order);
+
+    for (0..count) |i| {
+        const pos = table + 2 + i * RECORD_SIZE;
+        if (pos + RECORD_SIZE > length) return;
+        const kind = std.mem.readInt(u16, heap[pos..][0..2], order);
+        const size = std.mem.readInt(u32, heap[pos + 2 ..][0..4], order);
+        const start = std.mem.readInt(u32, heap[pos + 6 ..][0..4], order);
+
+        if (kind & STORAGE_MASK == STORAGE_INLINE) {
+            // Focal type in the low half, then the length in mm or 1/32 mm
+            if (kind == CIFF_FOCAL_LENGTH) {
+                const focal: f32 = @floatFromInt(size >> 16);
+                ciff.focal_length = if (size & 0xFFFF == 2) focal / 32.0 else focal;
+            }
+            continue;
+        }
+        if (start > length or size > length - start) continue;
+        const value = heap[start..][0..size];
+
+        if (std.mem.indexOfScalar(u16, &SUBHEAP_KINDS, kind >> 8) != null) {
+            read_heap(data, order, offset + start, size, ciff, depth + 1);
+            continue;
+        }
+        read_record(kind, value, order, ciff);
+        if (kind == CIFF_RAW_DATA) ciff.raw_offset = offset + start;
+    }
+}
+
+fn read_record(kind: u16, value: []const u8, order: std.builtin.Endian, ciff: *Ciff) void {
+    switch (kind) {
+        CIFF_MAKE_MODEL => {
+            ciff.make = std.mem.sliceTo(value, 0);
+            if (ciff.make.len < value.len) ciff.model = std.mem.sliceTo(value[ciff.make.len + 1 ..], 0);
+        },
+        CIFF_SHOT_INFO => {
+            if (value.len < 16) return;
+            // APEX-style values in 1/32 and 1/64 steps
+            const iso: f32 = @floatFromInt(std.mem.readInt(u16, value[4..6], order));
+            const aperture: f32 = @floatFromInt(std.mem.readInt(i16, value[8..10], order));
+            const shutter: f32 = @floatFromInt(std.mem.readInt(i16, value[10..12], order));
+            ciff.iso = @intFromFloat(@round(50.0 * std.math.pow(f32, 2.0, iso / 32.0 - 4.0)));
+            ciff.aperture = std.math.pow(f32, 2.0, aperture / 64.0);
+            ciff.shutter_speed = std.math.pow(f32, 2.0, -shutter / 32.0);
+            const index = std.mem.readInt(u16, value[14..16], order);
+            ciff.white_balance_index = if (index > 17) 0 else index;
+        },
+        CIFF_SENSOR_INFO => {
+            if (value.len < 18) return;
+            var sensor: [9]u16 = undefined;
+            for (&sensor, 0..) |*v, i| v.* = std.mem.readInt(u16, value[i * 2 ..][0..2], order);
+            ciff.sensor = sensor;
+        },
+        CIFF_COLOR_BALANCE => ciff.color_balance = value,
+        CIFF_IMAGE_INFO => {
+            if (value.len < 16) return;
+            ciff.image_width = std.mem.readInt(u32, value[0..4], order);
+            ciff.image_height = std.mem.readInt(u32, value[4..8], order);
+            ciff.rotation = std.mem.readInt(i32, value[12..16], order);
+        },
+        CIFF_DECODER_TABLE => {
+            if (value.len >= 4) ciff.decoder_table = std.mem.readInt(u32, value[0..4], order);
+        },
+        CIFF_RAW_DATA => ciff.raw_data = value,
+        CIFF_JPG_FROM_RAW => ciff.jpeg = value,
+        CIFF_THUMBNAIL => ciff.thumbnail = value,
+        else => {},
+    }
+}
+
+/// The white balance levels of the shot, in R, G, G, B order
+fn white_balance_levels(ciff: Ciff, order: std.builtin.Endian) ?[4]u16 {
+    const record = ciff.color_balance orelse return null;
+    var index: usize = ciff.white_balance_index;
+    if (record.len > LONG_COLOR_BALANCE) {
+        if (index >= COLOR_BALANCE_PRESETS.len) return null;
+        index = COLOR_BALANCE_PRESETS[index] - '0';
+    }
+    const pos = 2 + index * 8;
+    if (pos + 8 > record.len) return null;
+    var levels: [4]u16 = undefined;
+    for (&levels, 0..) |*level, i| level.* = std.mem.readInt(u16, record[pos + i * 2 ..][0..2], order);
+    return levels;
+}
+
+/// Later bodies store two more bits per sample in a plane before the
+/// compressed data. Without it, the entropy-coded data starts right after
+/// the header, and shows byte stuffing (0xFF 0x00) early on.
+fn has_low_bits(raw_data: []const u8) bool {
+    if (raw_data.len <= RAW_HEADER_SIZE) return true;
+    const probe = raw_data[RAW_HEADER_SIZE..@min(raw_data.len, LOW_BITS_PROBE_END)];
+    var stuffed = false;
+    for (probe[0 .. probe.len -| 1], 0..) |byte, i| {
+        if (byte != 0xFF) continue;
+        if (probe[i + 1] != 0) return true;
+        stuffed = true;
+    }
+    return !stuffed;
+}
+
+fn orientation(ciff: Ciff) root.ZrRawOrientation {
+    return switch (@mod(ciff.rotation, 360)) {
+        90 => .Rotate90,
+        180 => .Rotate180,
+        270 => .Rotate270,
+        else => .Normal,
+    };
+}
+
+/// Adds the full-size JPEG and the thumbnail records
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    const ciff = locate(data) catch return;
+    found.orientation = orientation(ciff);
+    if (ciff.jpeg) |jpeg| found.add_jpeg(jpeg);
+    if (ciff.thumbnail) |thumbnail| found.add_jpeg(thumbnail);
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const ciff = try locate(data);
+    const order = byte_order(data);
+    const raw_data = ciff.raw_data orelse return formats.FormatError.CorruptedData;
+    const width: u32 = if (ciff.sensor) |s| s[1] else ciff.image_width;
+    const height: u32 = if (ciff.sensor) |s| s[2] else ciff.image_height;
+    if (width == 0 or height == 0) return formats.FormatError.CorruptedData;
+
+    const make = try allocator.dupe(u8, ciff.make);
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, if (ciff.model.len > 0) ciff.model else "Unknown Canon Camera");
+    errdefer allocator.free(model);
+
+    const low_bits = has_low_bits(raw_data);
+    var metadata = formats.RawMetadata{
+        .format = .CRW,
+        .width = width,
+        .height = height,
+        .orientation = orientation(ciff),
+        .make = make,
+        .model = model,
+        .iso = ciff.iso,
+        .shutter_speed = ciff.shutter_speed,
+        .aperture = ciff.aperture,
+        .focal_length = ciff.focal_length,
+        // CIFF has no colour matrix
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{if (low_bits) 4095 else 1023} ** 4,
+        .raw_data_offset = @intCast(ciff.raw_offset),
+        .raw_data_size = @intCast(raw_data.len),
+        .bits_per_sample = if (low_bits) 12 else 10,
+        // CIFF has no compression field; this is the decoder table the data uses
+        .compression = ciff.decoder_table,
+        .allocator = allocator,
+    };
+
+    if (white_balance_levels(ciff, order)) |wb| {
+        if (wb[0] > 0 and wb[1] > 0 and wb[3] > 0) {
+            const green: f32 = @floatFromInt(wb[1]);
+            metadata.white_balance = .{ @as(f32, @floatFromInt(wb[0])) / green, 1.0, @as(f32, @floatFromInt(wb[3])) / green };
+        }
+    }
+
+    // Borders are inclusive; black is measured in the covered columns on the left
+    if (ciff.sensor) |s| {
+        const left = s[5];
+        const top = s[6];
+        const right = s[7];
+        const bottom = s[8];
+        if (right > left and bottom > top and right < width and bottom < height) {
+            metadata.crop = .{ .x = left, .y = top, .width = right - left + 1, .height = bottom - top + 1 };
+            if (left > 0) metadata.masked_area = .{ .x = 0, .y = top, .width = left, .height = bottom - top + 1 };
+        }
+    }
+    return metadata;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const raw_data = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    const tables = try decoders.canon.Tables.init(metadata.compression);
+    const low_bits = metadata.bits_per_sample == 12;
+    const start = RAW_HEADER_SIZE + if (low_bits) @as(usize, width) * height / 4 else 0;
+    if (start >= raw_data.len) return formats.FormatError.TruncatedFile;
+    try decoders.canon.decode(raw_data[start..], if (low_bits) raw_data else null, raw, width, height, &tables);
+    return raw;
+}
+
+test "low bits show as unstuffed 0xFF bytes" {
+    var raw_data = [_]u8{0} ** (RAW_HEADER_SIZE + 4);
+    try std.testing.expect(has_low_bits(&raw_data));
+    raw_data[RAW_HEADER_SIZE] = 0xFF;
+    try std.testing.expect(!has_low_bits(&raw_data));
+    raw_data[RAW_HEADER_SIZE + 2] = 0xFF;
+    raw_data[RAW_HEADER_SIZE + 3] = 0xD8;
+    try std.testing.expect(has_low_bits(&raw_data));
+}
@@ -0,0 +1,100 @@
+// src/formats/dng/gain_table.zig - ProfileGainTableMap (DNG 1.6)
+const std = @import("std");
+const formats = @import("../mod.zig");
+const opcodes = @import("opcodes.zig");
+
+/// Number of MapInputWeights: red, green, blue, min and max of the three
+const INPUT_WEIGHTS = 5;
+
+/// A grid of gain tables over the image. Each table maps a weighted mix of
+/// the pixel's channels, in [0, 1], to a gain applied to all three.
+const GainTableMap = struct {
+    grid: opcodes.GainGrid,
+    spacing_v: f64,
+    spacing_h: f64,
+    origin_v: f64,
+    origin_h: f64,
+    weights: [INPUT_WEIGHTS]f32,
+
+    fn read(data: []const u8) !GainTableMap {
+        var r = opcodes.Reader{ .data = data };
+        const points_v = try r.read_u32();
+        const points_h = try r.read_u32();
+        var map = GainTableMap{
+            .grid = undefined,
+            .spacing_v = try r.read_f64(),
+            .spacing_h = try r.read_f64(),
+            .origin_v = try r.read_f64(),
+            .origin_h = try r.read_f64(),
+            .weights = undefined,
+        };
+        const points_n = try r.read_u32();
+        for (&map.weights) |*w| w.* = @bitCast(try r.read_u32());
+        if (points_v == 0 or points_h == 0 or points_n == 0) return error.CorruptedData;
+        if (!(map.spacing_v > 0.0) or !(map.spacing_h > 0.0)) return error.CorruptedData;
+
+        map.grid = try opcodes.GainGrid.take(&r, points_v, points_h, points_n);
+        return map;
+    }
+
+    /// Gain at grid coordinates (v, h) for table input `x`, interpolated
+    /// between the two nearest table entries
+    fn gain(self: GainTableMap, v: f64, h: f64, x: f32) f32 {
+        const last: f32 = @floatFromInt(self.grid.planes - 1);
+        const position = std.math.clamp(x, 0.0, 1.0) * last;
+        const n0: usize = @intFromFloat(@floor(position));
+        const n1 = @min(n0 + 1, self.grid.planes - 1);
+        const t = position - @floor(position);
+        return self.grid.sample(v, h, n0) * (1.0 - t) + self.grid.sample(v, h, n1) * t;
+    }
+};
+
+/// Scales each RGB pixel of `image`, white balanced and normalised to
+/// [0, 1], by its gain. Map coordinates are relative to the whole image.
+pub fn apply(table: []const u8, image: *formats.LinearImage) !void {
+    if (image.planes < 3) return error.CorruptedData;
+    const map = try GainTableMap.read(table);
+
+    const height: f64 = @floatFromInt(image.height);
+    const width: f64 = @floatFromInt(image.width);
+    for (0..image.height) |row| {
+        const v = ((@as(f64, @floatFromInt(row)) / height) - map.origin_v) / map.spacing_v;
+        for (0..image.width) |col| {
+            const h = ((@as(f64, @floatFromInt(col)) / width) - map.origin_h) / map.spacing_h;
+            const px = image.data[image.index(row, col, 0)..][0..3];
+            const inputs = [INPUT_WEIGHTS]f32{ px[0], px[1], px[2], @min(px[0], px[1], px[2]), @max(px[0], px[1], px[2]) };
+            var x: f32 = 0.0;
+            for (inputs, map.weights) |input, weight| x += input * weight;
+            const g = map.gain(v, h, x);
+            for (px) |*value| value.* *= g;
+        }
+    }
+}
+
+test "gain table interpolates on the weighted input" {
+    const allocator = std.testing.allocator;
+    var image = try formats.LinearImage.init(allocator, 1, 1, 3);
+    defer image.deinit();
+    image.data[0] = 0.2;
+    image.data[1] = 0.5;
+    image.data[2] = 0.8;
+
+    // A single grid point whose two-entry table rises from 1 to 3, fed
+    // by the maximum channel
+    var table: [8 + 32 + 4 + 20 + 8]u8 = undefined;
+    std.mem.writeInt(u32, table[0..4], 1, .big);
+    std.mem.writeInt(u32, table[4..8], 1, .big);
+    for ([_]f64{ 1.0, 1.0, 0.0, 0.0 }, 0..) |v, i| std.mem.writeInt(u64, table[8 + i * 8 ..][0..8], @bitCast(v), .big);
+    std.mem.writeInt(u32, table[40..44], 2, .big);
+    for ([_]f32{ 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 3.0 }, 0..) |v, i| std.mem.writeInt(u32, table[44 + i * 4 ..][0..4], @bitCast(v), .big);
+
+    try apply(&table, &image);
+    // max = 0.8 gives a gain of 2.6
+    try std.testing.expectApproxEqAbs(@as(f32, 0.52), image.data[0], 1e-5);
+    try std.testing.expectApproxEqAbs(@as(f32, 1.3), image.data[1], 1e-5);
+    try std.testing.expectApproxEqAbs(@as(f32, 2.08), image.data[2], 1e-5);
+
+    // A grid whose byte count overflows is corrupt, not a panic
+    for ([_]usize{ 0, 4, 40 }) |offset| std.mem.writeInt(u32, table[offset..][0..4], std.math.maxInt(u32), .big);
+    try std.testing.expectError(error.CorruptedData, apply(&table, &image));
+}
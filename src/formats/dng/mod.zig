@@ -0,0 +1,921 @@
+// src/formats/dng/mod.zig - Adobe DNG support
+const std = @import("std");
+const root = @import("../../root.zig");
+const formats = @import("../mod.zig");
+const tiff = @import("../tiff/mod.zig");
+const decoders = @import("../../decoders/mod.zig");
+const pef = @import("../pef.zig");
+pub const opcodes = @import("opcodes.zig");
+const gain_table = @import("gain_table.zig");
+
+// DNG tags
+pub const TAG_DNG_VERSION = 0xC612;
+pub const TAG_UNIQUE_CAMERA_MODEL = 0xC614;
+pub const TAG_CFA_PLANE_COLOR = 0xC616;
+pub const TAG_LINEARIZATION_TABLE = 0xC618;
+pub const TAG_BLACK_LEVEL_REPEAT_DIM = 0xC619;
+pub const TAG_BLACK_LEVEL = 0xC61A;
+pub const TAG_BLACK_LEVEL_DELTA_H = 0xC61B;
+pub const TAG_BLACK_LEVEL_DELTA_V = 0xC61C;
+pub const TAG_WHITE_LEVEL = 0xC61D;
+pub const TAG_DEFAULT_SCALE = 0xC61E;
+pub const TAG_DEFAULT_CROP_ORIGIN = 0xC61F;
+pub const TAG_DEFAULT_CROP_SIZE = 0xC620;
+pub const TAG_COLOR_MATRIX_1 = 0xC621;
+pub const TAG_COLOR_MATRIX_2 = 0xC622;
+pub const TAG_CAMERA_CALIBRATION_1 = 0xC623;
+pub const TAG_CAMERA_CALIBRATION_2 = 0xC624;
+pub const TAG_ANALOG_BALANCE = 0xC627;
+pub const TAG_AS_SHOT_NEUTRAL = 0xC628;
+pub const TAG_AS_SHOT_WHITE_XY = 0xC629;
+pub const TAG_BASELINE_EXPOSURE = 0xC62A;
+pub const TAG_DNG_PRIVATE_DATA = 0xC634;
+pub const TAG_CALIBRATION_ILLUMINANT_1 = 0xC65A;
+pub const TAG_CALIBRATION_ILLUMINANT_2 = 0xC65B;
+pub const TAG_ACTIVE_AREA = 0xC68D;
+pub const TAG_FORWARD_MATRIX_1 = 0xC714;
+pub const TAG_FORWARD_MATRIX_2 = 0xC715;
+pub const TAG_OPCODE_LIST_1 = 0xC740;
+pub const TAG_OPCODE_LIST_2 = 0xC741;
+pub const TAG_OPCODE_LIST_3 = 0xC74E;
+pub const TAG_TIME_CODES = 0xC763;
+pub const TAG_FRAME_RATE = 0xC764;
+pub const TAG_BASELINE_EXPOSURE_OFFSET = 0xC7A5;
+pub const TAG_PROFILE_GAIN_TABLE_MAP = 0xCD2D;
+pub const TAG_SEMANTIC_NAME = 0xCD2E;
+pub const TAG_SEMANTIC_INSTANCE_ID = 0xCD30;
+pub const TAG_CALIBRATION_ILLUMINANT_3 = 0xCD31;
+pub const TAG_CAMERA_CALIBRATION_3 = 0xCD32;
+pub const TAG_COLOR_MATRIX_3 = 0xCD33;
+pub const TAG_FORWARD_MATRIX_3 = 0xCD34;
+
+// Compression values
+const COMPRESSION_NONE = 1;
+const COMPRESSION_LJPEG = 7;
+const COMPRESSION_DEFLATE = 8;
+const COMPRESSION_ADOBE_DEFLATE = 32946;
+const COMPRESSION_PENTAX = pef.PENTAX_COMPRESSED;
+
+// Predictor values (TIFF and DNG 1.4 extensions)
+const PREDICTOR_NONE = 1;
+const PREDICTOR_HORIZONTAL = 2;
+const PREDICTOR_FLOAT = 3;
+const PREDICTOR_HORIZONTAL_X2 = 34892;
+const PREDICTOR_HORIZONTAL_X4 = 34893;
+const PREDICTOR_FLOAT_X1 = 34894;
+const PREDICTOR_FLOAT_X2 = 34895;
+const PREDICTOR_FLOAT_X4 = 34896;
+
+const SAMPLE_FORMAT_FLOAT = 3;
+/// NewSubFileType of the semantic masks added in DNG 1.6
+const SUBFILE_SEMANTIC_MASK = 0x10004;
+const ILLUMINANT_D65 = 21;
+
+// Largest BlackLevelRepeatDim we accept in either direction
+const MAX_BLACK_REPEAT = 8;
+const MAX_SAMPLES = 4;
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+};
+
+/// Encoding and storage geometry of the main image. Strips are treated as
+/// tiles that span the full width.
+const Source = struct {
+    width: u32,
+    height: u32,
+    samples: u32,
+    bits: u32,
+    float: bool,
+    compression: u32,
+    predictor: u32,
+    tiled: bool,
+    chunk_width: u32,
+    chunk_length: u32,
+    offsets: tiff.IfdEntry,
+    counts: tiff.IfdEntry,
+
+    fn row_bytes(self: Source, cols: u32) usize {
+        return (@as(usize, cols) * self.samples * self.bits + 7) / 8;
+    }
+};
+
+/// One strip or tile; `rows` excludes rows past the end of a final strip
+const Chunk = struct {
+    x: u32,
+    y: u32,
+    width: u32,
+    rows: u32,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    return ifd0.find(TAG_DNG_VERSION) != null;
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = find_main_image(ifd0) orelse return formats.FormatError.UnsupportedFormat;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = tiff.read_exif_info(ifd0),
+    };
+}
+
+/// The main image is the full-resolution (NewSubFileType 0) CFA or linear
+/// IFD, usually in IFD0's SubIFDs behind a preview
+fn find_main_image(ifd0: tiff.Ifd) ?tiff.Ifd {
+    if (is_main_image(ifd0)) return ifd0;
+    if (ifd0.find(tiff.TAG_SUB_IFDS)) |entry| {
+        for (0..@min(entry.count, 16)) |i| {
+            const offset = entry.u32_at(ifd0.parser, i) orelse continue;
+            if (offset == 0) continue;
+            const sub = ifd0.parser.parse_ifd(offset) catch continue;
+            if (is_main_image(sub)) return sub;
+        }
+    }
+    return tiff.find_raw_ifd(ifd0);
+}
+
+fn is_main_image(ifd: tiff.Ifd) bool {
+    if ((ifd.get_u32(tiff.TAG_NEW_SUBFILE_TYPE) orelse 0) != 0) return false;
+    const photometric = ifd.get_u32(tiff.TAG_PHOTOMETRIC) orelse return false;
+    return photometric == tiff.PHOTOMETRIC_CFA or photometric == tiff.PHOTOMETRIC_LINEAR_RAW;
+}
+
+fn read_source(ifd: tiff.Ifd) !Source {
+    const width = ifd.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = ifd.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const samples = ifd.get_u32(tiff.TAG_SAMPLES_PER_PIXEL) orelse 1;
+    const bits = ifd.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 16;
+    const float = (ifd.get_u32(tiff.TAG_SAMPLE_FORMAT) orelse 1) == SAMPLE_FORMAT_FLOAT;
+    if (width == 0 or height == 0 or samples == 0 or samples > MAX_SAMPLES) return formats.FormatError.CorruptedData;
+    if (float and bits != 16 and bits != 24 and bits != 32) return formats.FormatError.UnsupportedFormat;
+    if (!float and (bits == 0 or (bits > 16 and bits != 32))) return formats.FormatError.UnsupportedFormat;
+    // Separate sample planes are not used by any DNG writer we know of
+    if ((ifd.get_u32(tiff.TAG_PLANAR_CONFIGURATION) orelse 1) != 1) return formats.FormatError.UnsupportedFormat;
+
+    var source = Source{
+        .width = width,
+        .height = height,
+        .samples = samples,
+        .bits = bits,
+        .float = float,
+        .compression = ifd.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE,
+        .predictor = ifd.get_u32(tiff.TAG_PREDICTOR) orelse PREDICTOR_NONE,
+        .tiled = ifd.find(tiff.TAG_TILE_OFFSETS) != null,
+        .chunk_width = width,
+        .chunk_length = height,
+        .offsets = undefined,
+        .counts = undefined,
+    };
+    if (source.tiled) {
+        source.chunk_width = ifd.get_u32(tiff.TAG_TILE_WIDTH) orelse return formats.FormatError.CorruptedData;
+        source.chunk_length = ifd.get_u32(tiff.TAG_TILE_LENGTH) orelse return formats.FormatError.CorruptedData;
+        source.offsets = ifd.find(tiff.TAG_TILE_OFFSETS).?;
+        source.counts = ifd.find(tiff.TAG_TILE_BYTE_COUNTS) orelse return formats.FormatError.CorruptedData;
+    } else {
+        source.chunk_length = @min(ifd.get_u32(tiff.TAG_ROWS_PER_STRIP) orelse height, height);
+        source.offsets = ifd.find(tiff.TAG_STRIP_OFFSETS) orelse return formats.FormatError.CorruptedData;
+        source.counts = ifd.find(tiff.TAG_STRIP_BYTE_COUNTS) orelse return formats.FormatError.CorruptedData;
+    }
+    if (source.chunk_width == 0 or source.chunk_length == 0) return formats.FormatError.CorruptedData;
+    if (source.counts.count < source.offsets.count) return formats.FormatError.CorruptedData;
+    return source;
+}
+
+fn active_area(ifd: tiff.Ifd, source: Source) !formats.Rect {
+    const entry = ifd.find(TAG_ACTIVE_AREA) orelse return .{ .width = source.width, .height = source.height };
+    const top = entry.u32_at(ifd.parser, 0) orelse return formats.FormatError.CorruptedData;
+    const left = entry.u32_at(ifd.parser, 1) orelse return formats.FormatError.CorruptedData;
+    const bottom = entry.u32_at(ifd.parser, 2) orelse return formats.FormatError.CorruptedData;
+    const right = entry.u32_at(ifd.parser, 3) orelse return formats.FormatError.CorruptedData;
+    if (bottom > source.height or right > source.width or top >= bottom or left >= right) {
+        return formats.FormatError.CorruptedData;
+    }
+    return .{ .x = left, .y = top, .width = right - left, .height = bottom - top };
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+    const source = try read_source(raw);
+    const area = try active_area(raw, source);
+    const photometric = raw.get_u32(tiff.TAG_PHOTOMETRIC) orelse tiff.PHOTOMETRIC_CFA;
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "Unknown");
+    errdefer allocator.free(make);
+    const model_name = layout.exif.model orelse layout.ifd0.get_string(TAG_UNIQUE_CAMERA_MODEL) orelse "Unknown DNG Camera";
+    const model = try allocator.dupe(u8, model_name);
+    errdefer allocator.free(model);
+
+    const white = white_levels(raw, source);
+    const black = try BlackLevels.init(raw, source.samples);
+    var black_level: [4]f32 = undefined;
+    for (&black_level, 0..) |*level, site| level.* = black.pattern_at(site / 2, site % 2, 0);
+    const profile = formats.color.from_ifd(layout.ifd0);
+
+    return formats.RawMetadata{
+        .format = .DNG,
+        .width = area.width,
+        .height = area.height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = camera_to_xyz(layout.ifd0) orelse [_]f32{0.0} ** 9,
+        .color_profile = profile,
+        .white_balance = as_shot_white_balance(layout.ifd0) orelse
+            white_xy_balance(profile) orelse [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = black_level,
+        .white_level = [_]u32{@intFromFloat(std.math.clamp(@as(f64, white[0]), 0.0, 4294967295.0))} ** 4,
+        .raw_data_offset = source.offsets.u32_at(raw.parser, 0) orelse 0,
+        .raw_data_size = @intCast(@min(total_bytes(raw.parser, source.counts), std.math.maxInt(u32))),
+        .cfa = cfa_pattern(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = source.bits,
+        .compression = source.compression,
+        .samples_per_pixel = source.samples,
+        .linear = photometric == tiff.PHOTOMETRIC_LINEAR_RAW,
+        .crop = default_crop(raw, area),
+        .default_scale = default_scale(raw),
+        .baseline_exposure = baseline_exposure(layout.ifd0),
+        .frame_rate = @floatCast(layout.ifd0.get_f64(TAG_FRAME_RATE) orelse 0.0),
+        .timecode = timecode(layout.ifd0),
+        .allocator = allocator,
+    };
+}
+
+fn total_bytes(parser: tiff.IfdParser, counts: tiff.IfdEntry) u64 {
+    var total: u64 = 0;
+    for (0..counts.count) |i| total += counts.u32_at(parser, i) orelse 0;
+    return total;
+}
+
+/// CFAPattern holds indices into CFAPlaneColor rather than colours
+fn cfa_pattern(ifd: tiff.Ifd) ?formats.CfaPattern {
+    var pattern = formats.cfa_from_ifd(ifd) orelse return null;
+    if (ifd.get_bytes(TAG_CFA_PLANE_COLOR)) |plane_colors| {
+        const sites = @as(usize, pattern.width) * pattern.height;
+        for (pattern.colors[0..sites]) |*color| {
+            const mapped = if (color.* < plane_colors.len) plane_colors[color.*] else 1;
+            color.* = if (mapped <= 2) mapped else 1;
+        }
+    }
+    return pattern;
+}
+
+/// Inverts the ColorMatrix (XYZ to camera) closest to D65. Only three-colour
+/// cameras are handled; others fall back to no colour conversion.
+pub fn camera_to_xyz(ifd0: tiff.Ifd) ?[9]f32 {
+    const illuminant_1 = ifd0.get_u32(TAG_CALIBRATION_ILLUMINANT_1) orelse 0;
+    const tag: u16 = if (illuminant_1 != ILLUMINANT_D65 and ifd0.find(TAG_COLOR_MATRIX_2) != null)
+        TAG_COLOR_MATRIX_2
+    else
+        TAG_COLOR_MATRIX_1;
+    const entry = ifd0.find(tag) orelse return null;
+    if (entry.count != 9) return null;
+
+    var m: [9]f64 = undefined;
+    for (&m, 0..) |*v, i| v.* = entry.f64_at(ifd0.parser, i) orelse return null;
+    const inverse = invert3(m) orelse return null;
+    var result: [9]f32 = undefined;
+    for (&result, inverse) |*out, v| out.* = @floatCast(v);
+    return result;
+}
+
+pub fn invert3(m: [9]f64) ?[9]f64 {
+    const det = m[0] * (m[4] * m[8] - m[5] * m[7]) -
+        m[1] * (m[3] * m[8] - m[5] * m[6]) +
+        m[2] * (m[3] * m[7] - m[4] * m[6]);
+    if (@abs(det) < 1e-12) return null;
+    return .{
+        (m[4] * m[8] - m[5] * m[7]) / det, (m[2] * m[7] - m[1] * m[8]) / det, (m[1] * m[5] - m[2] * m[4]) / det,
+        (m[5] * m[6] - m[3] * m[8]) / det, (m[0] * m[8] - m[2] * m[6]) / det, (m[2] * m[3] - m[0] * m[5]) / det,
+        (m[3] * m[7] - m[4] * m[6]) / det, (m[1] * m[6] - m[0] * m[7]) / det, (m[0] * m[4] - m[1] * m[3]) / det,
+    };
+}
+
+/// AsShotNeutral is the camera response to white; multipliers are its inverse
+pub fn as_shot_white_balance(ifd0: tiff.Ifd) ?[3]f32 {
+    const entry = ifd0.find(TAG_AS_SHOT_NEUTRAL) orelse return null;
+    var neutral: [3]f64 = undefined;
+    for (&neutral, 0..) |*n, i| {
+        n.* = entry.f64_at(ifd0.parser, i) orelse return null;
+        if (!(n.* > 0.0)) return null;
+    }
+    return .{ @floatCast(neutral[1] / neutral[0]), 1.0, @floatCast(neutral[1] / neutral[2]) };
+}
+
+/// Multipliers for files that give the white as AsShotWhiteXY instead
+fn white_xy_balance(profile: ?formats.color.Profile) ?[3]f32 {
+    const p = profile orelse return null;
+    const xy = p.as_shot_white_xy orelse return null;
+    const neutral = (p.matrices(.{ .xy = xy }) orelse return null).neutral;
+    return .{ @floatCast(neutral[1] / neutral[0]), 1.0, @floatCast(neutral[1] / neutral[2]) };
+}
+
+/// DefaultCropOrigin/Size, relative to the active area
+fn default_crop(ifd: tiff.Ifd, area: formats.Rect) ?formats.Rect {
+    const origin = ifd.find(TAG_DEFAULT_CROP_ORIGIN);
+    const size = ifd.find(TAG_DEFAULT_CROP_SIZE);
+    if (origin == null and size == null) return null;
+
+    var x: u32 = 0;
+    var y: u32 = 0;
+    if (origin) |entry| {
+        x = to_extent(entry.f64_at(ifd.parser, 0) orelse 0.0, area.width);
+        y = to_extent(entry.f64_at(ifd.parser, 1) orelse 0.0, area.height);
+    }
+    var width = area.width - x;
+    var height = area.height - y;
+    if (size) |entry| {
+        width = to_extent(entry.f64_at(ifd.parser, 0) orelse 0.0, width);
+        height = to_extent(entry.f64_at(ifd.parser, 1) orelse 0.0, height);
+    }
+    if (width == 0 or height == 0) return null;
+    return .{ .x = x, .y = y, .width = width, .height = height };
+}
+
+fn to_extent(value: f64, max: u32) u32 {
+    return @intFromFloat(std.math.clamp(@round(value), 0.0, @as(f64, @floatFromInt(max))));
+}
+
+/// BaselineExposure plus the profile's BaselineExposureOffset, in stops
+fn baseline_exposure(ifd0: tiff.Ifd) f32 {
+    const base = ifd0.get_f64(TAG_BASELINE_EXPOSURE) orelse 0.0;
+    const offset = ifd0.get_f64(TAG_BASELINE_EXPOSURE_OFFSET) orelse 0.0;
+    return @floatCast(base + offset);
+}
+
+/// First TimeCodes entry: an SMPTE 12M time address with BCD digits,
+/// frames first
+fn timecode(ifd0: tiff.Ifd) ?formats.Timecode {
+    const bytes = ifd0.get_bytes(TAG_TIME_CODES) orelse return null;
+    if (bytes.len < 8) return null;
+    return formats.Timecode{
+        .hours = bcd(bytes[3] & 0x3F),
+        .minutes = bcd(bytes[2] & 0x7F),
+        .seconds = bcd(bytes[1] & 0x7F),
+        .frames = bcd(bytes[0] & 0x3F),
+        .drop_frame = bytes[0] & 0x40 != 0,
+    };
+}
+
+fn bcd(value: u8) u8 {
+    return (value >> 4) * 10 + (value & 0x0F);
+}
+
+fn default_scale(ifd: tiff.Ifd) [2]f32 {
+    const entry = ifd.find(TAG_DEFAULT_SCALE) orelse return .{ 1.0, 1.0 };
+    const h = entry.f64_at(ifd.parser, 0) orelse 1.0;
+    const v = entry.f64_at(ifd.parser, 1) orelse 1.0;
+    if (!(h > 0.0) or !(v > 0.0)) return .{ 1.0, 1.0 };
+    return .{ @floatCast(h), @floatCast(v) };
+}
+
+fn white_levels(ifd: tiff.Ifd, source: Source) [MAX_SAMPLES]f32 {
+    const default: f32 = if (source.float)
+        1.0
+    else
+        @floatFromInt((@as(u64, 1) << @as(u6, @intCast(source.bits))) - 1);
+    var levels = [_]f32{default} ** MAX_SAMPLES;
+    if (ifd.find(TAG_WHITE_LEVEL)) |entry| {
+        for (&levels, 0..) |*level, i| {
+            // A single value covers every sample
+            const value = entry.f64_at(ifd.parser, @min(i, entry.count -| 1)) orelse continue;
+            if (value > 0.0) level.* = @floatCast(value);
+        }
+    }
+    return levels;
+}
+
+/// Black level model: a repeating pattern per sample, plus optional
+/// per-column (DeltaH) and per-row (DeltaV) offsets within the active area
+const BlackLevels = struct {
+    repeat_rows: u32 = 1,
+    repeat_cols: u32 = 1,
+    samples: u32,
+    pattern: [MAX_BLACK_REPEAT * MAX_BLACK_REPEAT * MAX_SAMPLES]f32 = [_]f32{0.0} ** (MAX_BLACK_REPEAT * MAX_BLACK_REPEAT * MAX_SAMPLES),
+    delta_h: ?[]f32 = null,
+    delta_v: ?[]f32 = null,
+
+    fn init(ifd: tiff.Ifd, samples: u32) !BlackLevels {
+        var levels = BlackLevels{ .samples = samples };
+        if (ifd.find(TAG_BLACK_LEVEL_REPEAT_DIM)) |dim| {
+            levels.repeat_rows = dim.u32_at(ifd.parser, 0) orelse 1;
+            levels.repeat_cols = dim.u32_at(ifd.parser, 1) orelse 1;
+        }
+        if (levels.repeat_rows == 0 or levels.repeat_cols == 0 or
+            levels.repeat_rows > MAX_BLACK_REPEAT or levels.repeat_cols > MAX_BLACK_REPEAT)
+        {
+            return formats.FormatError.UnsupportedFormat;
+        }
+        if (ifd.find(TAG_BLACK_LEVEL)) |entry| {
+            const count = levels.repeat_rows * levels.repeat_cols * samples;
+            for (0..count) |i| {
+                // Some writers store a single value for the whole pattern
+                const value = entry.f64_at(ifd.parser, i) orelse entry.f64_at(ifd.parser, 0) orelse 0.0;
+                levels.pattern[i] = @floatCast(value);
+            }
+        }
+        return levels;
+    }
+
+    fn load_deltas(self: *BlackLevels, allocator: std.mem.Allocator, ifd: tiff.Ifd, area: formats.Rect) !void {
+        self.delta_h = try read_deltas(allocator, ifd, TAG_BLACK_LEVEL_DELTA_H, area.width);
+        self.delta_v = try read_deltas(allocator, ifd, TAG_BLACK_LEVEL_DELTA_V, area.height);
+    }
+
+    fn deinit(self: *BlackLevels, allocator: std.mem.Allocator) void {
+        if (self.delta_h) |d| allocator.free(d);
+        if (self.delta_v) |d| allocator.free(d);
+    }
+
+    fn pattern_at(self: BlackLevels, row: usize, col: usize, sample: usize) f32 {
+        const r = row % self.repeat_rows;
+        const c = col % self.repeat_cols;
+        return self.pattern[(r * self.repeat_cols + c) * self.samples + sample];
+    }
+
+    fn at(self: BlackLevels, row: usize, col: usize, sample: usize) f32 {
+        var black = self.pattern_at(row, col, sample);
+        if (self.delta_h) |d| black += d[col];
+        if (self.delta_v) |d| black += d[row];
+        return black;
+    }
+};
+
+fn read_deltas(allocator: std.mem.Allocator, ifd: tiff.Ifd, tag: u16, len: u32) !?[]f32 {
+    const entry = ifd.find(tag) orelse return null;
+    const deltas = try allocator.alloc(f32, len);
+    for (deltas, 0..) |*d, i| d.* = @floatCast(entry.f64_at(ifd.parser, i) orelse 0.0);
+    return deltas;
+}
+
+fn read_linearization_table(allocator: std.mem.Allocator, ifd: tiff.Ifd) !?[]f32 {
+    const entry = ifd.find(TAG_LINEARIZATION_TABLE) orelse return null;
+    if (entry.count == 0 or entry.count > 65536) return formats.FormatError.CorruptedData;
+    const table = try allocator.alloc(f32, entry.count);
+    errdefer allocator.free(table);
+    for (table, 0..) |*v, i| v.* = @floatFromInt(entry.u32_at(ifd.parser, i) orelse return formats.FormatError.CorruptedData);
+    return table;
+}
+
+fn linearize(table: ?[]const f32, value: f32) f32 {
+    const t = table orelse return value;
+    const index: usize = @intFromFloat(std.math.clamp(value, 0.0, @as(f32, @floatFromInt(t.len - 1))));
+    return t[index];
+}
+
+/// Stored sample values after OpcodeList1, covering the whole raw IFD
+fn decode_stage1(allocator: std.mem.Allocator, data: []const u8, layout: Layout, source: Source) !formats.LinearImage {
+    const pentax = if (source.compression == COMPRESSION_PENTAX) try pentax_table(layout.ifd0) else null;
+    var image = try decode_stored(allocator, data, layout.raw_ifd.parser, source, if (pentax) |*table| table else null);
+    errdefer image.deinit();
+    if (layout.raw_ifd.get_bytes(TAG_OPCODE_LIST_1)) |list| try opcodes.apply(allocator, list, &image, .raw);
+    return image;
+}
+
+/// Decodes, linearizes and normalises the active area, then runs OpcodeList2
+pub fn extract_linear(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) !formats.LinearImage {
+    _ = metadata;
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+    const source = try read_source(raw);
+    const area = try active_area(raw, source);
+
+    var stored = try decode_stage1(allocator, data, layout, source);
+    defer stored.deinit();
+
+    const table = try read_linearization_table(allocator, raw);
+    defer if (table) |t| allocator.free(t);
+    var black = try BlackLevels.init(raw, source.samples);
+    defer black.deinit(allocator);
+    try black.load_deltas(allocator, raw, area);
+    const white = white_levels(raw, source);
+
+    var image = try formats.LinearImage.init(allocator, area.width, area.height, source.samples);
+    errdefer image.deinit();
+    for (0..area.height) |row| {
+        for (0..area.width) |col| {
+            for (0..source.samples) |s| {
+                const value = linearize(table, stored.data[stored.index(area.y + row, area.x + col, s)]);
+                const level = black.at(row, col, s);
+                const range = @max(white[s] - level, std.math.floatEps(f32));
+                image.data[image.index(row, col, s)] = @max(value - level, 0.0) / range;
+            }
+        }
+    }
+
+    if (raw.get_bytes(TAG_OPCODE_LIST_2)) |list| try opcodes.apply(allocator, list, &image, .linear);
+    return image;
+}
+
+/// Linearized active-area samples, interleaved when there is more than one
+/// per pixel. Floating point data is scaled so that white maps to 65535.
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    _ = metadata;
+    const layout = try locate(data);
+    const raw_ifd = layout.raw_ifd;
+    const source = try read_source(raw_ifd);
+    const area = try active_area(raw_ifd, source);
+
+    var stored = try decode_stage1(allocator, data, layout, source);
+    defer stored.deinit();
+    const table = try read_linearization_table(allocator, raw_ifd);
+    defer if (table) |t| allocator.free(t);
+    const white = white_levels(raw_ifd, source);
+
+    const raw = try allocator.alloc(u16, @as(usize, area.width) * area.height * source.samples);
+    errdefer allocator.free(raw);
+    var i: usize = 0;
+    for (0..area.height) |row| {
+        for (0..area.width) |col| {
+            for (0..source.samples) |s| {
+                var value = linearize(table, stored.data[stored.index(area.y + row, area.x + col, s)]);
+                if (source.float) value = value / white[s] * 65535.0;
+                raw[i] = @intFromFloat(std.math.clamp(@round(value), 0.0, 65535.0));
+                i += 1;
+            }
+        }
+    }
+    return raw;
+}
+
+/// Applies OpcodeList3, then the profile's gain table map, to the
+/// demosaiced, white balanced image
+pub fn finish_rgb(data: []const u8, image: *formats.LinearImage) !void {
+    const layout = try locate(data);
+    if (layout.raw_ifd.get_bytes(TAG_OPCODE_LIST_3)) |list| try opcodes.apply(image.allocator, list, image, .linear);
+    if (layout.ifd0.get_bytes(TAG_PROFILE_GAIN_TABLE_MAP)) |table| try gain_table.apply(table, image);
+}
+
+/// A semantic mask (sky, skin, people, ...) decoded to 8 bits per pixel
+pub const SemanticMask = struct {
+    /// SemanticName and SemanticInstanceID; they point into the file data
+    name: []const u8,
+    instance_id: []const u8,
+    width: u32,
+    height: u32,
+    data: []u8,
+};
+
+/// Walks the semantic mask IFDs: IFD0's SubIFDs, then the IFDs chained
+/// after IFD0
+const SemanticMasks = struct {
+    ifd0: tiff.Ifd,
+    sub_index: usize = 0,
+    chained: ?tiff.Ifd,
+    chain_length: u32 = 0,
+
+    fn init(ifd0: tiff.Ifd) SemanticMasks {
+        return .{ .ifd0 = ifd0, .chained = ifd0.next() };
+    }
+
+    fn next(self: *SemanticMasks) ?tiff.Ifd {
+        if (self.ifd0.find(tiff.TAG_SUB_IFDS)) |entry| {
+            while (self.sub_index < @min(entry.count, 16)) {
+                const i = self.sub_index;
+                self.sub_index += 1;
+                const offset = entry.u32_at(self.ifd0.parser, i) orelse continue;
+                if (offset == 0) continue;
+                const sub = self.ifd0.parser.parse_ifd(offset) catch continue;
+                if (is_semantic_mask(sub)) return sub;
+            }
+        }
+        while (self.chained) |ifd| {
+            if (self.chain_length >= 16) break;
+            self.chain_length += 1;
+            self.chained = ifd.next();
+            if (is_semantic_mask(ifd)) return ifd;
+        }
+        return null;
+    }
+};
+
+fn is_semantic_mask(ifd: tiff.Ifd) bool {
+    return (ifd.get_u32(tiff.TAG_NEW_SUBFILE_TYPE) orelse 0) == SUBFILE_SEMANTIC_MASK;
+}
+
+pub fn semantic_mask_count(data: []const u8) !u32 {
+    const layout = try locate(data);
+    var masks = SemanticMasks.init(layout.ifd0);
+    var count: u32 = 0;
+    while (masks.next() != null) count += 1;
+    return count;
+}
+
+/// Decodes the `index`th semantic mask, scaling its samples to 8 bits
+pub fn extract_semantic_mask(data: []const u8, index: u32, allocator: std.mem.Allocator) !SemanticMask {
+    const layout = try locate(data);
+    var masks = SemanticMasks.init(layout.ifd0);
+    var ifd = masks.next() orelse return error.InvalidInput;
+    for (0..index) |_| ifd = masks.next() orelse return error.InvalidInput;
+
+    const source = try read_source(ifd);
+    if (source.samples != 1 or source.float) return formats.FormatError.UnsupportedFormat;
+    var stored = try decode_stored(allocator, data, ifd.parser, source, null);
+    defer stored.deinit();
+
+    const max: f32 = @floatFromInt((@as(u32, 1) << @as(u5, @intCast(@min(source.bits, 16)))) - 1);
+    const pixels = try allocator.alloc(u8, stored.data.len);
+    for (pixels, stored.data) |*px, value| px.* = @intFromFloat(std.math.clamp(@round(value / max * 255.0), 0.0, 255.0));
+    return SemanticMask{
+        .name = ifd.get_string(TAG_SEMANTIC_NAME) orelse "",
+        .instance_id = ifd.get_string(TAG_SEMANTIC_INSTANCE_ID) orelse "",
+        .width = source.width,
+        .height = source.height,
+        .data = pixels,
+    };
+}
+
+/// Pentax bodies may write DNGs with Pentax' own Huffman coding. The table
+/// is in the makernote copy in DNGPrivateData, which Adobe's converter
+/// wraps in an "Adobe\0MakN" header.
+fn pentax_table(ifd0: tiff.Ifd) !decoders.pentax.Table {
+    const entry = ifd0.find(TAG_DNG_PRIVATE_DATA) orelse return decoders.pentax.Table.default();
+    const bytes = entry.bytes(ifd0.parser) orelse return formats.FormatError.CorruptedData;
+    var start: usize = entry.value_offset;
+    if (std.mem.startsWith(u8, bytes, "Adobe\x00MakN")) start += 20;
+    const mn = pef.open_makernote(ifd0.parser, start) orelse return decoders.pentax.Table.default();
+    return mn.huffman_table();
+}
+
+fn decode_stored(
+    allocator: std.mem.Allocator,
+    data: []const u8,
+    parser: tiff.IfdParser,
+    source: Source,
+    pentax: ?*const decoders.pentax.Table,
+) !formats.LinearImage {
+    var image = try formats.LinearImage.init(allocator, source.width, source.height, source.samples);
+    errdefer image.deinit();
+    @memset(image.data, 0.0);
+
+    const row_buf = try allocator.alloc(f32, @as(usize, source.chunk_width) * source.samples);
+    defer allocator.free(row_buf);
+
+    const across = (source.width + source.chunk_width - 1) / source.chunk_width;
+    for (0..source.offsets.count) |i| {
+        const offset = source.offsets.u32_at(parser, i) orelse return formats.FormatError.CorruptedData;
+        const size = source.counts.u32_at(parser, i) orelse return formats.FormatError.CorruptedData;
+        if (!parser.in_bounds(offset, size)) return formats.FormatError.TruncatedFile;
+
+        const x: u32 = @intCast((i % across) * source.chunk_width);
+        const y: u32 = @intCast((i / across) * source.chunk_length);
+        if (y >= source.height) break;
+        // Tiles are padded to full size; the last strip is not
+        const rows = if (source.tiled) source.chunk_length else @min(source.chunk_length, source.height - y);
+        const chunk = Chunk{ .x = x, .y = y, .width = source.chunk_width, .rows = rows };
+        const bytes = data[offset..][0..size];
+
+        switch (source.compression) {
+            COMPRESSION_NONE => try decode_plain(bytes, parser.byte_order, source, chunk, &image, row_buf),
+            COMPRESSION_LJPEG => try decode_ljpeg(allocator, bytes, source, chunk, &image, row_buf),
+            COMPRESSION_DEFLATE, COMPRESSION_ADOBE_DEFLATE => try decode_deflate(allocator, bytes, parser.byte_order, source, chunk, &image, row_buf),
+            COMPRESSION_PENTAX => try decode_pentax(allocator, bytes, source, chunk, &image, row_buf, pentax.?),
+            // Such as JPEG XL (52546); the error names the compression
+            else => return formats.unsupported_compression(source.compression),
+        }
+    }
+    return image;
+}
+
+/// Copies one decoded chunk row into the image, dropping tile padding
+fn store_row(image: *formats.LinearImage, chunk: Chunk, row: usize, values: []const f32) void {
+    const y = chunk.y + row;
+    if (y >= image.height or chunk.x >= image.width) return;
+    const len = @as(usize, @min(chunk.width, image.width - chunk.x)) * image.planes;
+    @memcpy(image.data[image.index(y, chunk.x, 0)..][0..len], values[0..len]);
+}
+
+fn decode_plain(bytes: []const u8, order: tiff.ByteOrder, source: Source, chunk: Chunk, image: *formats.LinearImage, row_buf: []f32) !void {
+    const row_len = source.row_bytes(chunk.width);
+    for (0..chunk.rows) |row| {
+        if ((row + 1) * row_len > bytes.len) return formats.FormatError.TruncatedFile;
+        try read_samples(bytes[row * row_len ..][0..row_len], row_buf, source, order);
+        store_row(image, chunk, row, row_buf);
+    }
+}
+
+/// Each tile is a lossless JPEG whose rows, taken as width * components
+/// samples, are the tile rows
+fn decode_ljpeg(allocator: std.mem.Allocator, bytes: []const u8, source: Source, chunk: Chunk, image: *formats.LinearImage, row_buf: []f32) !void {
+    var jpeg = try decoders.ljpeg.decode(allocator, bytes);
+    defer jpeg.deinit();
+
+    const row_len = @as(usize, chunk.width) * source.samples;
+    const rows = @min(jpeg.samples.len / row_len, chunk.rows);
+    for (0..rows) |row| {
+        for (row_buf, jpeg.samples[row * row_len ..][0..row_len]) |*v, s| v.* = @floatFromInt(s);
+        store_row(image, chunk, row, row_buf);
+    }
+}
+
+/// Pentax Huffman data for one single-sample strip
+fn decode_pentax(
+    allocator: std.mem.Allocator,
+    bytes: []const u8,
+    source: Source,
+    chunk: Chunk,
+    image: *formats.LinearImage,
+    row_buf: []f32,
+    table: *const decoders.pentax.Table,
+) !void {
+    if (source.samples != 1) return formats.FormatError.UnsupportedFormat;
+    const samples = try allocator.alloc(u16, @as(usize, chunk.width) * chunk.rows);
+    defer allocator.free(samples);
+    try decoders.pentax.decode(bytes, samples, chunk.width, chunk.rows, table);
+    for (0..chunk.rows) |row| {
+        for (row_buf[0..chunk.width], samples[row * chunk.width ..][0..chunk.width]) |*v, s| v.* = @floatFromInt(s);
+        store_row(image, chunk, row, row_buf);
+    }
+}
+
+fn decode_deflate(
+    allocator: std.mem.Allocator,
+    bytes: []const u8,
+    order: tiff.ByteOrder,
+    source: Source,
+    chunk: Chunk,
+    image: *formats.LinearImage,
+    row_buf: []f32,
+) !void {
+    const row_len = source.row_bytes(chunk.width);
+    const inflated = try allocator.alloc(u8, row_len * chunk.rows);
+    defer allocator.free(inflated);
+
+    var input = std.io.fixedBufferStream(bytes);
+    var output = std.io.fixedBufferStream(inflated);
+    std.compress.zlib.decompress(input.reader(), output.writer()) catch |err| switch (err) {
+        // Anything past the expected size is padding
+        error.NoSpaceLeft => {},
+        else => return formats.FormatError.CorruptedData,
+    };
+    if (output.pos < inflated.len) return formats.FormatError.TruncatedFile;
+
+    const scratch = try allocator.alloc(u8, row_len);
+    defer allocator.free(scratch);
+    for (0..chunk.rows) |row| {
+        const bytes_row = inflated[row * row_len ..][0..row_len];
+        var sample_order = order;
+        switch (source.predictor) {
+            PREDICTOR_NONE => {},
+            PREDICTOR_HORIZONTAL => try undo_horizontal(bytes_row, source, order, source.samples),
+            PREDICTOR_HORIZONTAL_X2 => try undo_horizontal(bytes_row, source, order, source.samples * 2),
+            PREDICTOR_HORIZONTAL_X4 => try undo_horizontal(bytes_row, source, order, source.samples * 4),
+            PREDICTOR_FLOAT, PREDICTOR_FLOAT_X1, PREDICTOR_FLOAT_X2, PREDICTOR_FLOAT_X4 => {
+                const factor: u32 = switch (source.predictor) {
+                    PREDICTOR_FLOAT_X2 => 2,
+                    PREDICTOR_FLOAT_X4 => 4,
+                    else => 1,
+                };
+                undo_floating_point(bytes_row, scratch, source, source.samples * factor);
+                sample_order = .big;
+            },
+            else => return formats.FormatError.UnsupportedFormat,
+        }
+        try read_samples(bytes_row, row_buf, source, sample_order);
+        store_row(image, chunk, row, row_buf);
+    }
+}
+
+/// Integer horizontal differencing: each sample is stored as the difference
+/// to the sample `stride` positions earlier, modulo the sample width
+fn undo_horizontal(row: []u8, source: Source, order: tiff.ByteOrder, stride: usize) !void {
+    const endian: std.builtin.Endian = if (order == .little) .little else .big;
+    switch (source.bits) {
+        8 => {
+            for (stride..row.len) |i| row[i] +%= row[i - stride];
+        },
+        16 => {
+            for (stride..row.len / 2) |i| {
+                const prev = std.mem.readInt(u16, row[(i - stride) * 2 ..][0..2], endian);
+                const cur = std.mem.readInt(u16, row[i * 2 ..][0..2], endian);
+                std.mem.writeInt(u16, row[i * 2 ..][0..2], cur +% prev, endian);
+            }
+        },
+        32 => {
+            for (stride..row.len / 4) |i| {
+                const prev = std.mem.readInt(u32, row[(i - stride) * 4 ..][0..4], endian);
+                const cur = std.mem.readInt(u32, row[i * 4 ..][0..4], endian);
+                std.mem.writeInt(u32, row[i * 4 ..][0..4], cur +% prev, endian);
+            }
+        },
+        else => return formats.FormatError.UnsupportedFormat,
+    }
+}
+
+/// Floating point predictor: bytes are differenced, then split into planes
+/// from the most significant byte down. Leaves big-endian samples in `row`.
+fn undo_floating_point(row: []u8, scratch: []u8, source: Source, stride: usize) void {
+    for (stride..row.len) |i| row[i] +%= row[i - stride];
+    const width = source.bits / 8;
+    const count = row.len / width;
+    for (0..count) |i| {
+        for (0..width) |b| scratch[i * width + b] = row[b * count + i];
+    }
+    @memcpy(row[0 .. count * width], scratch[0 .. count * width]);
+}
+
+/// Converts one row of stored samples to f32. Multi-byte samples are in
+/// `order`; integer depths other than 8, 16 and 32 are packed MSB first.
+fn read_samples(bytes: []const u8, out: []f32, source: Source, order: tiff.ByteOrder) !void {
+    if (bytes.len < (out.len * source.bits + 7) / 8) return formats.FormatError.TruncatedFile;
+    const endian: std.builtin.Endian = if (order == .little) .little else .big;
+
+    if (source.float) {
+        for (out, 0..) |*v, i| {
+            v.* = switch (source.bits) {
+                16 => @as(f16, @bitCast(std.mem.readInt(u16, bytes[i * 2 ..][0..2], endian))),
+                24 => fp24_to_f32(std.mem.readInt(u24, bytes[i * 3 ..][0..3], endian)),
+                else => @bitCast(std.mem.readInt(u32, bytes[i * 4 ..][0..4], endian)),
+            };
+        }
+        return;
+    }
+
+    switch (source.bits) {
+        8 => for (out, bytes[0..out.len]) |*v, b| {
+            v.* = @floatFromInt(b);
+        },
+        16 => for (out, 0..) |*v, i| {
+            v.* = @floatFromInt(std.mem.readInt(u16, bytes[i * 2 ..][0..2], endian));
+        },
+        32 => for (out, 0..) |*v, i| {
+            v.* = @floatFromInt(std.mem.readInt(u32, bytes[i * 4 ..][0..4], endian));
+        },
+        else => {
+            var pump = decoders.BitPumpMsb.init(bytes);
+            for (out) |*v| v.* = @floatFromInt(pump.get_bits(source.bits));
+        },
+    }
+}
+
+/// DNG 24-bit float: sign, 7-bit exponent (bias 63) and 16-bit mantissa
+fn fp24_to_f32(value: u24) f32 {
+    const sign: u32 = value >> 23;
+    const exponent: u32 = (value >> 16) & 0x7F;
+    const mantissa: u32 = value & 0xFFFF;
+    const bits: u32 = switch (exponent) {
+        0 => {
+            const magnitude = std.math.ldexp(@as(f32, @floatFromInt(mantissa)), -78);
+            return if (sign == 1) -magnitude else magnitude;
+        },
+        0x7F => (sign << 31) | (0xFF << 23) | (mantissa << 7),
+        else => (sign << 31) | ((exponent + 64) << 23) | (mantissa << 7),
+    };
+    return @bitCast(bits);
+}
+
+test "floating point predictor restores big-endian samples" {
+    const source = Source{
+        .width = 2,
+        .height = 1,
+        .samples = 1,
+        .bits = 32,
+        .float = true,
+        .compression = COMPRESSION_DEFLATE,
+        .predictor = PREDICTOR_FLOAT,
+        .tiled = false,
+        .chunk_width = 2,
+        .chunk_length = 1,
+        .offsets = undefined,
+        .counts = undefined,
+    };
+    // 1.0 (3F800000) and 2.0 (40000000) split into byte planes, then differenced
+    const planes = [_]u8{ 0x3F, 0x40, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00 };
+    var row: [8]u8 = undefined;
+    row[0] = planes[0];
+    for (1..8) |i| row[i] = planes[i] -% planes[i - 1];
+
+    var scratch: [8]u8 = undefined;
+    undo_floating_point(&row, &scratch, source, 1);
+    var out: [2]f32 = undefined;
+    try read_samples(&row, &out, source, .big);
+    try std.testing.expectEqualSlices(f32, &.{ 1.0, 2.0 }, &out);
+}
+
+test "24-bit floats widen to f32" {
+    try std.testing.expectEqual(@as(f32, 1.0), fp24_to_f32(0x3F0000));
+    try std.testing.expectEqual(@as(f32, -2.0), fp24_to_f32(0xC00000));
+}
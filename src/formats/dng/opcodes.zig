@@ -0,0 +1,444 @@
+// src/formats/dng/opcodes.zig - DNG opcode lists (OpcodeList1-3)
+const std = @import("std");
+const formats = @import("../mod.zig");
+
+// Opcode IDs from the DNG specification
+const WARP_RECTILINEAR = 1;
+const FIX_VIGNETTE_RADIAL = 3;
+const FIX_BAD_PIXELS_CONSTANT = 4;
+const FIX_BAD_PIXELS_LIST = 5;
+const TRIM_BOUNDS = 6;
+const MAP_TABLE = 7;
+const MAP_POLYNOMIAL = 8;
+const GAIN_MAP = 9;
+const DELTA_PER_ROW = 10;
+const DELTA_PER_COLUMN = 11;
+const SCALE_PER_ROW = 12;
+const SCALE_PER_COLUMN = 13;
+
+// Opcodes flagged optional may be skipped by readers that do not implement them
+const FLAG_OPTIONAL = 1;
+
+/// Where in the pipeline a list runs, which decides the value range
+pub const Stage = enum {
+    /// OpcodeList1: stored sample values
+    raw,
+    /// OpcodeList2 and OpcodeList3: values normalised to [0, 1]
+    linear,
+
+    fn range(self: Stage) f32 {
+        return if (self == .raw) 65535.0 else 1.0;
+    }
+};
+
+/// Opcode lists and their parameters are always big-endian
+pub const Reader = struct {
+    data: []const u8,
+    pos: usize = 0,
+
+    pub fn take(self: *Reader, len: usize) ![]const u8 {
+        if (len > self.data.len - self.pos) return error.CorruptedData;
+        const bytes = self.data[self.pos..][0..len];
+        self.pos += len;
+        return bytes;
+    }
+
+    pub fn read_u32(self: *Reader) !u32 {
+        return std.mem.readInt(u32, (try self.take(4))[0..4], .big);
+    }
+
+    pub fn read_f64(self: *Reader) !f64 {
+        return @bitCast(std.mem.readInt(u64, (try self.take(8))[0..8], .big));
+    }
+};
+
+/// The rectangle, planes and pitch that area-based opcodes operate on
+const Area = struct {
+    top: u32,
+    left: u32,
+    bottom: u32,
+    right: u32,
+    plane: u32,
+    planes: u32,
+    row_pitch: u32,
+    col_pitch: u32,
+
+    fn read(r: *Reader) !Area {
+        var area: Area = undefined;
+        inline for (std.meta.fields(Area)) |field| @field(area, field.name) = try r.read_u32();
+        if (area.row_pitch == 0 or area.col_pitch == 0) return error.CorruptedData;
+        return area;
+    }
+
+    fn rows(self: Area, image: formats.LinearImage) Steps {
+        return .{ .pos = self.top, .end = @min(self.bottom, image.height), .step = self.row_pitch };
+    }
+
+    fn cols(self: Area, image: formats.LinearImage) Steps {
+        return .{ .pos = self.left, .end = @min(self.right, image.width), .step = self.col_pitch };
+    }
+
+    fn plane_end(self: Area, image: formats.LinearImage) u32 {
+        return @max(self.plane, @min(self.plane +| self.planes, image.planes));
+    }
+};
+
+const Steps = struct {
+    pos: u32,
+    end: u32,
+    step: u32,
+
+    fn next(self: *Steps) ?u32 {
+        if (self.pos >= self.end) return null;
+        const current = self.pos;
+        self.pos +|= self.step;
+        return current;
+    }
+};
+
+/// Runs every opcode of a serialised list against `image`
+pub fn apply(allocator: std.mem.Allocator, list: []const u8, image: *formats.LinearImage, stage: Stage) !void {
+    var reader = Reader{ .data = list };
+    const count = try reader.read_u32();
+    for (0..count) |_| {
+        const id = try reader.read_u32();
+        _ = try reader.read_u32(); // DNG version that introduced the opcode
+        const flags = try reader.read_u32();
+        const size = try reader.read_u32();
+        var params = Reader{ .data = try reader.take(size) };
+
+        run(allocator, id, &params, image, stage) catch |err| switch (err) {
+            error.UnsupportedFormat => if ((flags & FLAG_OPTIONAL) == 0) return err,
+            else => return err,
+        };
+    }
+}
+
+fn run(allocator: std.mem.Allocator, id: u32, r: *Reader, image: *formats.LinearImage, stage: Stage) !void {
+    switch (id) {
+        WARP_RECTILINEAR => try warp_rectilinear(allocator, r, image),
+        FIX_VIGNETTE_RADIAL => try fix_vignette_radial(r, image),
+        FIX_BAD_PIXELS_CONSTANT => try fix_bad_pixels_constant(allocator, r, image, stage),
+        FIX_BAD_PIXELS_LIST => try fix_bad_pixels_list(allocator, r, image),
+        // Only a hint for the final crop
+        TRIM_BOUNDS => {},
+        MAP_TABLE => try map_table(r, image, stage),
+        MAP_POLYNOMIAL => try map_polynomial(r, image, stage),
+        GAIN_MAP => try gain_map(r, image),
+        DELTA_PER_ROW, DELTA_PER_COLUMN, SCALE_PER_ROW, SCALE_PER_COLUMN => try per_line(id, r, image, stage),
+        else => return error.UnsupportedFormat,
+    }
+}
+
+fn gain_map(r: *Reader, image: *formats.LinearImage) !void {
+    const area = try Area.read(r);
+    const points_v = try r.read_u32();
+    const points_h = try r.read_u32();
+    const spacing_v = try r.read_f64();
+    const spacing_h = try r.read_f64();
+    const origin_v = try r.read_f64();
+    const origin_h = try r.read_f64();
+    const map_planes = try r.read_u32();
+    if (points_v == 0 or points_h == 0 or map_planes == 0) return error.CorruptedData;
+    if (!(spacing_v > 0.0) or !(spacing_h > 0.0)) return error.CorruptedData;
+    const map = try GainGrid.take(r, points_v, points_h, map_planes);
+    const height: f64 = @floatFromInt(image.height);
+    const width: f64 = @floatFromInt(image.width);
+    var rows = area.rows(image.*);
+    while (rows.next()) |row| {
+        // Map coordinates are relative to the whole image
+        const v = ((@as(f64, @floatFromInt(row)) / height) - origin_v) / spacing_v;
+        var cols = area.cols(image.*);
+        while (cols.next()) |col| {
+            const h = ((@as(f64, @floatFromInt(col)) / width) - origin_h) / spacing_h;
+            for (area.plane..area.plane_end(image.*)) |p| {
+                const map_plane = @min(p - area.plane, map_planes - 1);
+                image.data[image.index(row, col, p)] *= map.sample(v, h, map_plane);
+            }
+        }
+    }
+}
+
+pub const GainGrid = struct {
+    gains: []const u8,
+    points_v: u32,
+    points_h: u32,
+    planes: u32,
+
+    /// Takes the grid's FLOAT gains from `r`; sizes whose byte count
+    /// overflows are corrupt rather than a panic
+    pub fn take(r: *Reader, points_v: u32, points_h: u32, planes: u32) !GainGrid {
+        var size: usize = 4;
+        for ([_]u32{ points_v, points_h, planes }) |n| {
+            size = std.math.mul(usize, size, n) catch return error.CorruptedData;
+        }
+        return .{ .gains = try r.take(size), .points_v = points_v, .points_h = points_h, .planes = planes };
+    }
+
+    fn at(self: GainGrid, iv: usize, ih: usize, plane: usize) f32 {
+        const i = (iv * self.points_h + ih) * self.planes + plane;
+        return @bitCast(std.mem.readInt(u32, self.gains[i * 4 ..][0..4], .big));
+    }
+
+    /// Bilinear lookup at fractional grid coordinates, clamped to the grid
+    pub fn sample(self: GainGrid, v: f64, h: f64, plane: usize) f32 {
+        const fv = std.math.clamp(v, 0.0, @as(f64, @floatFromInt(self.points_v - 1)));
+        const fh = std.math.clamp(h, 0.0, @as(f64, @floatFromInt(self.points_h - 1)));
+        const v0: usize = @intFromFloat(@floor(fv));
+        const h0: usize = @intFromFloat(@floor(fh));
+        const v1 = @min(v0 + 1, self.points_v - 1);
+        const h1 = @min(h0 + 1, self.points_h - 1);
+        const tv: f32 = @floatCast(fv - @floor(fv));
+        const th: f32 = @floatCast(fh - @floor(fh));
+        const top = self.at(v0, h0, plane) * (1.0 - th) + self.at(v0, h1, plane) * th;
+        const bottom = self.at(v1, h0, plane) * (1.0 - th) + self.at(v1, h1, plane) * th;
+        return top * (1.0 - tv) + bottom * tv;
+    }
+};
+
+/// Optical centre in pixels and the distance from it to the farthest corner
+const Radial = struct {
+    cx: f64,
+    cy: f64,
+    max_dist: f64,
+
+    fn init(image: formats.LinearImage, center_h: f64, center_v: f64) Radial {
+        const w: f64 = @floatFromInt(image.width - 1);
+        const h: f64 = @floatFromInt(image.height - 1);
+        const cx = center_h * w;
+        const cy = center_v * h;
+        const dx = @max(cx, w - cx);
+        const dy = @max(cy, h - cy);
+        return .{ .cx = cx, .cy = cy, .max_dist = @max(@sqrt(dx * dx + dy * dy), 1e-9) };
+    }
+};
+
+fn warp_rectilinear(allocator: std.mem.Allocator, r: *Reader, image: *formats.LinearImage) !void {
+    const coefficient_sets = try r.read_u32();
+    if (coefficient_sets == 0 or coefficient_sets > 4) return error.CorruptedData;
+    var coefficients: [4][6]f64 = undefined;
+    for (0..coefficient_sets) |set| {
+        for (&coefficients[set]) |*k| k.* = try r.read_f64();
+    }
+    const geometry = Radial.init(image.*, try r.read_f64(), try r.read_f64());
+
+    const source = try allocator.dupe(f32, image.data);
+    defer allocator.free(source);
+    const max_x: f64 = @floatFromInt(image.width - 1);
+    const max_y: f64 = @floatFromInt(image.height - 1);
+
+    for (0..image.height) |row| {
+        for (0..image.width) |col| {
+            const dx = (@as(f64, @floatFromInt(col)) - geometry.cx) / geometry.max_dist;
+            const dy = (@as(f64, @floatFromInt(row)) - geometry.cy) / geometry.max_dist;
+            const r2 = dx * dx + dy * dy;
+            for (0..image.planes) |p| {
+                const k = coefficients[@min(p, coefficient_sets - 1)];
+                const ratio = k[0] + r2 * (k[1] + r2 * (k[2] + r2 * k[3]));
+                const tx = k[4] * 2.0 * dx * dy + k[5] * (r2 + 2.0 * dx * dx);
+                const ty = k[5] * 2.0 * dx * dy + k[4] * (r2 + 2.0 * dy * dy);
+                const sx = std.math.clamp(geometry.cx + geometry.max_dist * (dx * ratio + tx), 0.0, max_x);
+                const sy = std.math.clamp(geometry.cy + geometry.max_dist * (dy * ratio + ty), 0.0, max_y);
+                image.data[image.index(row, col, p)] = bilinear(source, image.*, sx, sy, p);
+            }
+        }
+    }
+}
+
+fn bilinear(source: []const f32, image: formats.LinearImage, x: f64, y: f64, plane: usize) f32 {
+    const x0: usize = @intFromFloat(@floor(x));
+    const y0: usize = @intFromFloat(@floor(y));
+    const x1 = @min(x0 + 1, image.width - 1);
+    const y1 = @min(y0 + 1, image.height - 1);
+    const tx: f32 = @floatCast(x - @floor(x));
+    const ty: f32 = @floatCast(y - @floor(y));
+    const top = source[image.index(y0, x0, plane)] * (1.0 - tx) + source[image.index(y0, x1, plane)] * tx;
+    const bottom = source[image.index(y1, x0, plane)] * (1.0 - tx) + source[image.index(y1, x1, plane)] * tx;
+    return top * (1.0 - ty) + bottom * ty;
+}
+
+fn fix_vignette_radial(r: *Reader, image: *formats.LinearImage) !void {
+    var k: [5]f64 = undefined;
+    for (&k) |*v| v.* = try r.read_f64();
+    const geometry = Radial.init(image.*, try r.read_f64(), try r.read_f64());
+
+    for (0..image.height) |row| {
+        for (0..image.width) |col| {
+            const dx = (@as(f64, @floatFromInt(col)) - geometry.cx) / geometry.max_dist;
+            const dy = (@as(f64, @floatFromInt(row)) - geometry.cy) / geometry.max_dist;
+            const r2 = dx * dx + dy * dy;
+            const gain: f32 = @floatCast(1.0 + r2 * (k[0] + r2 * (k[1] + r2 * (k[2] + r2 * (k[3] + r2 * k[4])))));
+            for (0..image.planes) |p| image.data[image.index(row, col, p)] *= gain;
+        }
+    }
+}
+
+fn fix_bad_pixels_constant(allocator: std.mem.Allocator, r: *Reader, image: *formats.LinearImage, stage: Stage) !void {
+    const constant: f32 = @floatFromInt(try r.read_u32());
+    _ = try r.read_u32(); // Bayer phase; same-colour neighbours are two sites away regardless
+
+    var bad = try std.DynamicBitSet.initEmpty(allocator, @as(usize, image.width) * image.height);
+    defer bad.deinit();
+    const scale = 65535.0 / stage.range();
+    for (0..image.height) |row| {
+        for (0..image.width) |col| {
+            if (@round(image.data[image.index(row, col, 0)] * scale) == constant) bad.set(row * image.width + col);
+        }
+    }
+    repair(image, &bad);
+}
+
+fn fix_bad_pixels_list(allocator: std.mem.Allocator, r: *Reader, image: *formats.LinearImage) !void {
+    _ = try r.read_u32(); // Bayer phase
+    const point_count = try r.read_u32();
+    const rect_count = try r.read_u32();
+
+    var bad = try std.DynamicBitSet.initEmpty(allocator, @as(usize, image.width) * image.height);
+    defer bad.deinit();
+    for (0..point_count) |_| {
+        const row = try r.read_u32();
+        const col = try r.read_u32();
+        if (row < image.height and col < image.width) bad.set(@as(usize, row) * image.width + col);
+    }
+    for (0..rect_count) |_| {
+        const top = try r.read_u32();
+        const left = try r.read_u32();
+        const bottom = @min(try r.read_u32(), image.height);
+        const right = @min(try r.read_u32(), image.width);
+        for (top..@max(top, bottom)) |row| {
+            for (left..@max(left, right)) |col| bad.set(row * image.width + col);
+        }
+    }
+    repair(image, &bad);
+}
+
+/// Replaces each flagged pixel with the mean of its good same-colour neighbours
+fn repair(image: *formats.LinearImage, bad: *const std.DynamicBitSet) void {
+    const step: isize = if (image.planes == 1) 2 else 1;
+    const width: isize = @intCast(image.width);
+    const height: isize = @intCast(image.height);
+    const offsets = [8][2]isize{ .{ -1, 0 }, .{ 1, 0 }, .{ 0, -1 }, .{ 0, 1 }, .{ -1, -1 }, .{ -1, 1 }, .{ 1, -1 }, .{ 1, 1 } };
+    var it = bad.iterator(.{});
+    while (it.next()) |i| {
+        const row: isize = @intCast(i / image.width);
+        const col: isize = @intCast(i % image.width);
+        for (0..image.planes) |p| {
+            var sum: f32 = 0.0;
+            var count: f32 = 0.0;
+            for (offsets) |o| {
+                const y = row + o[0] * step;
+                const x = col + o[1] * step;
+                if (y < 0 or x < 0 or y >= height or x >= width) continue;
+                const ny: usize = @intCast(y);
+                const nx: usize = @intCast(x);
+                if (bad.isSet(ny * image.width + nx)) continue;
+                sum += image.data[image.index(ny, nx, p)];
+                count += 1.0;
+            }
+            if (count > 0.0) image.data[image.index(@intCast(row), @intCast(col), p)] = sum / count;
+        }
+    }
+}
+
+fn map_table(r: *Reader, image: *formats.LinearImage, stage: Stage) !void {
+    const area = try Area.read(r);
+    const size = try r.read_u32();
+    if (size == 0) return error.CorruptedData;
+    const table = try r.take(@as(usize, size) * 2);
+    const range = stage.range();
+
+    var rows = area.rows(image.*);
+    while (rows.next()) |row| {
+        var cols = area.cols(image.*);
+        while (cols.next()) |col| {
+            for (area.plane..area.plane_end(image.*)) |p| {
+                const value = &image.data[image.index(row, col, p)];
+                const index: usize = @intFromFloat(std.math.clamp(@round(value.* / range * 65535.0), 0.0, @as(f32, @floatFromInt(size - 1))));
+                const mapped: f32 = @floatFromInt(std.mem.readInt(u16, table[index * 2 ..][0..2], .big));
+                value.* = mapped / 65535.0 * range;
+            }
+        }
+    }
+}
+
+fn map_polynomial(r: *Reader, image: *formats.LinearImage, stage: Stage) !void {
+    const area = try Area.read(r);
+    const degree = try r.read_u32();
+    if (degree > 8) return error.CorruptedData;
+    var coefficients: [9]f64 = undefined;
+    for (coefficients[0 .. degree + 1]) |*c| c.* = try r.read_f64();
+    const range = stage.range();
+
+    var rows = area.rows(image.*);
+    while (rows.next()) |row| {
+        var cols = area.cols(image.*);
+        while (cols.next()) |col| {
+            for (area.plane..area.plane_end(image.*)) |p| {
+                const value = &image.data[image.index(row, col, p)];
+                const x: f64 = value.* / range;
+                var y: f64 = 0.0;
+                var i = degree + 1;
+                while (i > 0) : (i -= 1) y = y * x + coefficients[i - 1];
+                value.* = @as(f32, @floatCast(std.math.clamp(y, 0.0, 1.0))) * range;
+            }
+        }
+    }
+}
+
+/// DeltaPerRow/Column and ScalePerRow/Column: one value per row or column step
+fn per_line(id: u32, r: *Reader, image: *formats.LinearImage, stage: Stage) !void {
+    const area = try Area.read(r);
+    const count = try r.read_u32();
+    const values = try r.take(@as(usize, count) * 4);
+    const per_row = id == DELTA_PER_ROW or id == SCALE_PER_ROW;
+    const is_delta = id == DELTA_PER_ROW or id == DELTA_PER_COLUMN;
+
+    var rows = area.rows(image.*);
+    while (rows.next()) |row| {
+        var cols = area.cols(image.*);
+        while (cols.next()) |col| {
+            const line = if (per_row) (row - area.top) / area.row_pitch else (col - area.left) / area.col_pitch;
+            if (line >= count) continue;
+            const v: f32 = @bitCast(std.mem.readInt(u32, values[line * 4 ..][0..4], .big));
+            for (area.plane..area.plane_end(image.*)) |p| {
+                const value = &image.data[image.index(row, col, p)];
+                if (is_delta) value.* += v * stage.range() else value.* *= v;
+            }
+        }
+    }
+}
+
+test "gain map scales the covered area" {
+    const allocator = std.testing.allocator;
+    var image = try formats.LinearImage.init(allocator, 4, 2, 1);
+    defer image.deinit();
+    @memset(image.data, 0.5);
+
+    // One opcode: a 1x1 map of gain 2.0 over the left half
+    var list: [4 + 16 + 32 + 8 + 32 + 4 + 4]u8 = undefined;
+    var pos: usize = 0;
+    const put = struct {
+        fn u32_(buf: []u8, at: *usize, v: u32) void {
+            std.mem.writeInt(u32, buf[at.*..][0..4], v, .big);
+            at.* += 4;
+        }
+        fn f64_(buf: []u8, at: *usize, v: f64) void {
+            std.mem.writeInt(u64, buf[at.*..][0..8], @bitCast(v), .big);
+            at.* += 8;
+        }
+    };
+    put.u32_(&list, &pos, 1);
+    for ([_]u32{ GAIN_MAP, 0x01030000, 0, list.len - 20 }) |v| put.u32_(&list, &pos, v);
+    for ([_]u32{ 0, 0, 2, 2, 0, 1, 1, 1, 1, 1 }) |v| put.u32_(&list, &pos, v);
+    for ([_]f64{ 1.0, 1.0, 0.0, 0.0 }) |v| put.f64_(&list, &pos, v);
+    put.u32_(&list, &pos, 1);
+    put.u32_(&list, &pos, @bitCast(@as(f32, 2.0)));
+
+    try apply(allocator, &list, &image, .linear);
+    try std.testing.expectEqualSlices(f32, &.{ 1.0, 1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5 }, image.data);
+
+    // A grid whose byte count overflows is corrupt, not a panic
+    var short = Reader{ .data = list[0..8] };
+    const max = std.math.maxInt(u32);
+    try std.testing.expectError(error.CorruptedData, GainGrid.take(&short, max, max, max));
+}
@@ -0,0 +1,144 @@
+// src/formats/erf.zig - Epson ERF RAW format support
+const std = @import("std");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+/// Epson makernotes are an IFD after this signature, with file-relative offsets
+const MAKERNOTE_SIGNATURE = "EPSON\x00";
+const MAKERNOTE_HEADER_SIZE = 8;
+
+/// Camera settings block holding the white balance
+const EPSON_IMAGE_SETTINGS = 0x0E80;
+const EPSON_IMAGE_SETTINGS_SIZE = 256;
+const EPSON_WB_OFFSET = 48;
+// The stored red and blue values scale to multipliers by these factors
+const EPSON_RED_SCALE = 508.0 * 1.078 / 65536.0;
+const EPSON_BLUE_SCALE = 382.0 * 1.173 / 65536.0;
+
+const COMPRESSION_NONE = 1;
+/// 12-bit samples with a control byte after every ten
+const COMPRESSION_EPSON = 32769;
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+    makernote: ?tiff.Ifd,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return false;
+    return std.mem.startsWith(u8, make, "SEIKO EPSON") or std.mem.startsWith(u8, make, "EPSON");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse return formats.FormatError.UnsupportedFormat;
+    const exif = tiff.read_exif_info(ifd0);
+
+    var makernote: ?tiff.Ifd = null;
+    if (exif.makernote) |entry| {
+        const start = entry.value_offset;
+        if (parser.in_bounds(start, MAKERNOTE_HEADER_SIZE) and std.mem.startsWith(u8, data[start..], MAKERNOTE_SIGNATURE)) {
+            makernote = parser.parse_ifd(start + MAKERNOTE_HEADER_SIZE) catch null;
+        }
+    }
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = exif,
+        .makernote = makernote,
+    };
+}
+
+fn white_balance(mn: tiff.Ifd) ?[3]f32 {
+    const entry = mn.find(EPSON_IMAGE_SETTINGS) orelse return null;
+    if (entry.data_type != tiff.TYPE_UNDEFINED or entry.count != EPSON_IMAGE_SETTINGS_SIZE) return null;
+    const bytes = entry.bytes(mn.parser) orelse return null;
+    const settings = tiff.IfdParser.init(bytes, mn.parser.byte_order);
+    const red = @as(f32, @floatFromInt(settings.read_u16(EPSON_WB_OFFSET))) * EPSON_RED_SCALE;
+    const blue = @as(f32, @floatFromInt(settings.read_u16(EPSON_WB_OFFSET + 2))) * EPSON_BLUE_SCALE;
+    if (red <= 0.0 or blue <= 0.0) return null;
+    return .{ red, 1.0, blue };
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 12;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE;
+    if (compression != COMPRESSION_NONE and compression != COMPRESSION_EPSON) return formats.FormatError.UnsupportedFormat;
+    if (compression == COMPRESSION_EPSON and bits != 12) return formats.FormatError.UnsupportedFormat;
+
+    const wb = if (layout.makernote) |mn| white_balance(mn) else null;
+    const make = try allocator.dupe(u8, layout.exif.make orelse "SEIKO EPSON");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Epson Camera");
+    errdefer allocator.free(model);
+
+    return formats.RawMetadata{
+        .format = .ERF,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = wb orelse [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(bits))) - 1} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = compression,
+        .allocator = allocator,
+    };
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    const unpack = decoders.unpack;
+    const bits = metadata.bits_per_sample;
+    if (metadata.compression == COMPRESSION_EPSON) {
+        try unpack.unpack(input, raw, width, height, bits, .msb_control, unpack.row_bytes(width, bits, .msb_control));
+    } else if (input.len >= @as(usize, width) * height * 2) {
+        const packing: unpack.Packing = if (layout.ifd0.parser.byte_order == .little) .u16_little else .u16_big;
+        try unpack.unpack(input, raw, width, height, 16, packing, @as(usize, width) * 2);
+    } else {
+        try unpack.unpack(input, raw, width, height, bits, .msb, unpack.row_bytes(width, bits, .msb));
+    }
+    return raw;
+}
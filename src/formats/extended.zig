@@ -0,0 +1,184 @@
+// src/formats/extended.zig - Versioned key/value metadata beyond ZrRawMetadata
+const std = @import("std");
+const formats = @import("mod.zig");
+
+/// Blobs start with the magic and a little-endian u16 version, then a u16
+/// of padding. Each entry follows as a u16 key, a u16 value type and a u32
+/// byte length, all little-endian, then the value. Readers skip keys and
+/// value types they do not know, so entries can be added without bumping
+/// the version; it changes only if the framing does.
+pub const MAGIC = "ZRMD";
+pub const VERSION: u16 = 1;
+
+pub const Key = enum(u16) {
+    /// Lens model, untruncated
+    lens = 1,
+    /// EXIF DateTimeOriginal, "YYYY:MM:DD HH:MM:SS"
+    date_time_original = 2,
+    /// EXIF SubSecTimeOriginal digits
+    sub_sec_time_original = 3,
+    /// EXIF OffsetTimeOriginal, "+HH:MM"
+    offset_time_original = 4,
+    body_serial_number = 5,
+    lens_serial_number = 6,
+    /// x, y, width, height of the default crop
+    crop = 7,
+    /// Width, height, then one colour per site (0 = R, 1 = G, 2 = B)
+    cfa_pattern = 8,
+    bits_per_sample = 9,
+    /// TIFF compression value, or the format's own code for non-TIFF formats
+    compression = 10,
+    /// Degrees Celsius
+    sensor_temperature = 11,
+    /// Make and model as the camera database names them
+    canonical_make = 12,
+    canonical_model = 13,
+    /// EXIF LightSource value of each calibration, in order; the matrices
+    /// below are numbered the same way
+    calibration_illuminants = 14,
+    /// XYZ to camera, row-major
+    color_matrix_1 = 15,
+    color_matrix_2 = 16,
+    color_matrix_3 = 17,
+    camera_calibration_1 = 18,
+    camera_calibration_2 = 19,
+    camera_calibration_3 = 20,
+    /// White-balanced camera to XYZ D50
+    forward_matrix_1 = 21,
+    forward_matrix_2 = 22,
+    forward_matrix_3 = 23,
+    analog_balance = 24,
+    as_shot_neutral = 25,
+    as_shot_white_xy = 26,
+};
+
+pub const ValueType = enum(u16) {
+    /// UTF-8 text without terminator
+    string = 1,
+    u32_array = 2,
+    f32_array = 3,
+    bytes = 4,
+};
+
+const Writer = struct {
+    list: std.ArrayList(u8),
+
+    fn entry(self: *Writer, key: Key, value_type: ValueType, value: []const u8) !void {
+        var header: [8]u8 = undefined;
+        std.mem.writeInt(u16, header[0..2], @intFromEnum(key), .little);
+        std.mem.writeInt(u16, header[2..4], @intFromEnum(value_type), .little);
+        std.mem.writeInt(u32, header[4..8], @intCast(value.len), .little);
+        try self.list.appendSlice(&header);
+        try self.list.appendSlice(value);
+    }
+
+    fn string(self: *Writer, key: Key, text: ?[]const u8) !void {
+        const value = text orelse return;
+        if (value.len == 0) return;
+        try self.entry(key, .string, value);
+    }
+
+    fn u32s(self: *Writer, key: Key, values: []const u32) !void {
+        var buf: [36]u8 = undefined;
+        for (values, 0..) |value, i| std.mem.writeInt(u32, buf[i * 4 ..][0..4], value, .little);
+        try self.entry(key, .u32_array, buf[0 .. values.len * 4]);
+    }
+
+    fn f32s(self: *Writer, key: Key, values: []const f32) !void {
+        var buf: [36]u8 = undefined;
+        for (values, 0..) |value, i| std.mem.writeInt(u32, buf[i * 4 ..][0..4], @bitCast(value), .little);
+        try self.entry(key, .f32_array, buf[0 .. values.len * 4]);
+    }
+
+    fn f64s(self: *Writer, key: Key, values: []const f64) !void {
+        var narrowed: [9]f32 = undefined;
+        for (values, 0..) |value, i| narrowed[i] = @floatCast(value);
+        try self.f32s(key, narrowed[0..values.len]);
+    }
+
+    fn profile(self: *Writer, color: formats.color.Profile) !void {
+        var illuminants: [3]u32 = undefined;
+        for (color.items(), 0..) |calibration, i| illuminants[i] = calibration.illuminant;
+        try self.u32s(.calibration_illuminants, illuminants[0..color.count]);
+        for (color.items(), 0..) |calibration, i| {
+            const offset: u16 = @intCast(i);
+            try self.f64s(@enumFromInt(@intFromEnum(Key.color_matrix_1) + offset), &calibration.color_matrix);
+            if (calibration.camera_calibration) |m| {
+                try self.f64s(@enumFromInt(@intFromEnum(Key.camera_calibration_1) + offset), &m);
+            }
+            if (calibration.forward_matrix) |m| {
+                try self.f64s(@enumFromInt(@intFromEnum(Key.forward_matrix_1) + offset), &m);
+            }
+        }
+        try self.f64s(.analog_balance, &color.analog_balance);
+        if (color.as_shot_neutral) |neutral| try self.f64s(.as_shot_neutral, &neutral);
+        if (color.as_shot_white_xy) |xy| try self.f64s(.as_shot_white_xy, &xy);
+    }
+};
+
+/// Encodes what `metadata` and the file's EXIF tags know. The caller owns
+/// the returned blob.
+pub fn encode(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u8 {
+    var writer = Writer{ .list = std.ArrayList(u8).init(allocator) };
+    errdefer writer.list.deinit();
+
+    var header: [8]u8 = undefined;
+    @memcpy(header[0..4], MAGIC);
+    std.mem.writeInt(u16, header[4..6], VERSION, .little);
+    std.mem.writeInt(u16, header[6..8], 0, .little);
+    try writer.list.appendSlice(&header);
+
+    try writer.string(.lens, metadata.lens);
+    const exif = formats.exif_info(data);
+    try writer.string(.date_time_original, exif.date_time_original);
+    try writer.string(.sub_sec_time_original, exif.sub_sec_time_original);
+    try writer.string(.offset_time_original, exif.offset_time_original);
+    try writer.string(.body_serial_number, exif.body_serial);
+    try writer.string(.lens_serial_number, exif.lens_serial);
+
+    if (metadata.crop) |crop| try writer.u32s(.crop, &.{ crop.x, crop.y, crop.width, crop.height });
+    if (!metadata.linear) {
+        const cfa = metadata.cfa;
+        var pattern: [38]u8 = undefined;
+        const sites = @as(usize, cfa.width) * cfa.height;
+        pattern[0] = cfa.width;
+        pattern[1] = cfa.height;
+        @memcpy(pattern[2..][0..sites], cfa.colors[0..sites]);
+        try writer.entry(.cfa_pattern, .bytes, pattern[0 .. 2 + sites]);
+    }
+    try writer.u32s(.bits_per_sample, &.{metadata.bits_per_sample});
+    try writer.u32s(.compression, &.{metadata.compression});
+    if (metadata.sensor_temperature) |celsius| try writer.f32s(.sensor_temperature, &.{celsius});
+    try writer.string(.canonical_make, metadata.canonical_make);
+    try writer.string(.canonical_model, metadata.canonical_model);
+    if (formats.color.of(metadata)) |profile| try writer.profile(profile);
+
+    return writer.list.toOwnedSlice();
+}
+
+test "entries follow the versioned header" {
+    var metadata = std.mem.zeroInit(formats.RawMetadata, .{
+        .format = .PEF,
+        .make = "",
+        .model = "",
+        .lens = "smc PENTAX-DA 35mm",
+        .color_matrix = [_]f32{0} ** 9,
+        .white_balance = [_]f32{1} ** 3,
+        .black_level = [_]f32{0} ** 4,
+        .white_level = [_]u32{0} ** 4,
+        .allocator = std.testing.allocator,
+    });
+    metadata.sensor_temperature = -4.0;
+
+    const blob = try encode("", metadata, std.testing.allocator);
+    defer std.testing.allocator.free(blob);
+    try std.testing.expectEqualSlices(u8, "ZRMD\x01\x00\x00\x00", blob[0..8]);
+    // The lens comes first
+    try std.testing.expectEqual(@as(u16, 1), std.mem.readInt(u16, blob[8..10], .little));
+    try std.testing.expectEqual(@as(u32, 18), std.mem.readInt(u32, blob[12..16], .little));
+    try std.testing.expectEqualStrings("smc PENTAX-DA 35mm", blob[16..34]);
+    // The temperature is last
+    const last = blob[blob.len - 12 ..];
+    try std.testing.expectEqual(@as(u16, 11), std.mem.readInt(u16, last[0..2], .little));
+    try std.testing.expectEqual(@as(f32, -4.0), @as(f32, @bitCast(std.mem.readInt(u32, last[8..12], .little))));
+}
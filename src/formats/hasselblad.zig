@@ -0,0 +1,131 @@
+// src/formats/hasselblad.zig - Hasselblad 3FR and FFF RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const dng = @import("dng/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+const TAG_SOFTWARE = 0x0131;
+
+const COMPRESSION_NONE = 1;
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+};
+
+pub fn detect(data: []const u8) bool {
+    return detect_format(data) != null;
+}
+
+/// Both formats share one layout, so detection also tells them apart
+pub fn detect_format(data: []const u8) ?root.ZrRawFormat {
+    const header = tiff.read_header(data) orelse return null;
+    if (header.magic != tiff.TIFF_MAGIC) return null;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return null;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return null;
+    if (!std.mem.startsWith(u8, make, "Hasselblad") and !std.mem.startsWith(u8, make, "Imacon")) return null;
+    return if (is_fff(ifd0)) .FFF else .@"3FR";
+}
+
+/// 3FR is what the camera writes; FFF is written by FlexColor and Phocus,
+/// and by the Imacon backs that preceded them
+fn is_fff(ifd0: tiff.Ifd) bool {
+    if (ifd0.get_string(tiff.EXIF_MAKE)) |make| {
+        if (std.mem.startsWith(u8, make, "Imacon")) return true;
+    }
+    const software = ifd0.get_string(TAG_SOFTWARE) orelse return false;
+    return std.mem.startsWith(u8, software, "FlexColor") or std.mem.startsWith(u8, software, "Phocus");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse tiff.find_sensor_ifd(ifd0) orelse
+        return formats.FormatError.UnsupportedFormat;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = tiff.read_exif_info(ifd0),
+    };
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 16;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "Hasselblad");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Hasselblad Camera");
+    errdefer allocator.free(model);
+
+    const white = raw.get_u32(dng.TAG_WHITE_LEVEL) orelse (@as(u32, 1) << @as(u5, @intCast(bits))) - 1;
+    const black = raw.get_u32(dng.TAG_BLACK_LEVEL) orelse layout.ifd0.get_u32(dng.TAG_BLACK_LEVEL) orelse 0;
+
+    return formats.RawMetadata{
+        .format = if (is_fff(layout.ifd0)) .FFF else .@"3FR",
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        // Recent bodies carry the DNG colour tags in IFD0
+        .color_matrix = dng.camera_to_xyz(layout.ifd0) orelse [_]f32{0.0} ** 9,
+        .color_profile = formats.color.from_ifd(layout.ifd0),
+        .white_balance = dng.as_shot_white_balance(layout.ifd0) orelse [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{@floatFromInt(black)} ** 4,
+        .white_level = [_]u32{white} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE,
+        .allocator = allocator,
+    };
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    if (metadata.compression == COMPRESSION_NONE) {
+        const packing: decoders.unpack.Packing = if (layout.ifd0.parser.byte_order == .little) .u16_little else .u16_big;
+        try decoders.unpack.unpack(input, raw, width, height, 16, packing, @as(usize, width) * 2);
+        return raw;
+    }
+
+    // Only the Huffman table is taken from the lossless JPEG header
+    const header = try decoders.ljpeg.read_header(input);
+    const table = header.table(0) orelse return formats.FormatError.CorruptedData;
+    try decoders.hasselblad.decode(input[header.data_start..], table, raw, width, height, 0);
+    return raw;
+}
@@ -0,0 +1,563 @@
+// src/formats/iiq.zig - Phase One IIQ RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+/// The Phase One block follows a TIFF header; "Raw" marks it after the
+/// byte order word
+const RAW_MARK = 0x526177;
+
+// Phase One directory entries
+const IIQ_ORIENTATION = 0x0100;
+const IIQ_ROMM_MATRIX = 0x0106;
+const IIQ_WB_LEVELS = 0x0107;
+const IIQ_RAW_WIDTH = 0x0108;
+const IIQ_RAW_HEIGHT = 0x0109;
+const IIQ_LEFT_MARGIN = 0x010A;
+const IIQ_TOP_MARGIN = 0x010B;
+const IIQ_WIDTH = 0x010C;
+const IIQ_HEIGHT = 0x010D;
+const IIQ_FORMAT = 0x010E;
+const IIQ_DATA_OFFSET = 0x010F;
+const IIQ_CALIBRATION = 0x0110;
+const IIQ_KEY = 0x0112;
+const IIQ_SENSOR_TEMPERATURE = 0x0210;
+const IIQ_STRIP_OFFSETS = 0x021C;
+const IIQ_BLACK = 0x021D;
+const IIQ_SPLIT_COLUMN = 0x0222;
+/// Black measured in the covered columns: a left/right pair per row
+const IIQ_ROW_BLACK = 0x0223;
+const IIQ_SPLIT_ROW = 0x0224;
+/// Black measured in the covered rows: a top/bottom pair per column
+const IIQ_COLUMN_BLACK = 0x0225;
+const IIQ_MODEL = 0x0301;
+
+// Calibration block entries
+const CAL_DEFECTS = 0x0400;
+const CAL_FLAT_FIELD_ALL_FLOAT = 0x0401;
+const CAL_FLAT_FIELD_RED_BLUE = 0x040B;
+const CAL_FLAT_FIELD = 0x0410;
+const CAL_FLAT_FIELD_ALT = 0x0416;
+const CAL_CURVE_RIGHT = 0x0419;
+const CAL_CURVE = 0x041A;
+
+// Defect types
+const DEFECT_PIXEL = 129;
+const DEFECT_COLUMN = 131;
+const DEFECT_COLUMN_ALT = 137;
+
+/// Formats below this are stored uncompressed
+const FORMAT_COMPRESSED = 3;
+/// Compressed small-size files map codes below 256 through a square curve
+const FORMAT_SMALL = 5;
+/// Compressed samples are stored at full 16-bit scale
+const FORMAT_FULL_SCALE = 8;
+
+const WHITE = 0xFFFC;
+
+/// The values of the Phase One directory this reader uses
+const Directory = struct {
+    orientation: u32 = 0,
+    romm_cam: ?[9]f32 = null,
+    wb: ?[3]f32 = null,
+    raw_width: u32 = 0,
+    raw_height: u32 = 0,
+    left: u32 = 0,
+    top: u32 = 0,
+    width: u32 = 0,
+    height: u32 = 0,
+    format: u32 = 0,
+    data_offset: usize = 0,
+    calibration: ?[]const u8 = null,
+    /// File position of the XOR key words of uncompressed formats
+    key_offset: ?usize = null,
+    sensor_temperature: ?f32 = null,
+    strip_offsets: usize = 0,
+    black: u32 = 0,
+    split_column: u32 = 0,
+    row_black: usize = 0,
+    split_row: u32 = 0,
+    column_black: usize = 0,
+    model: ?[]const u8 = null,
+};
+
+const Layout = struct {
+    order: std.builtin.Endian,
+    directory: Directory,
+    exif: tiff.ExifInfo,
+};
+
+fn find_base(data: []const u8) ?usize {
+    const head = data[0..@min(data.len, 32)];
+    return std.mem.indexOf(u8, head, "IIII") orelse std.mem.indexOf(u8, head, "MMMM");
+}
+
+fn byte_order(data: []const u8, base: usize) std.builtin.Endian {
+    return if (data[base] == 'I') .little else .big;
+}
+
+pub fn detect(data: []const u8) bool {
+    const base = find_base(data) orelse return false;
+    if (base + 12 > data.len) return false;
+    return std.mem.readInt(u32, data[base + 4 ..][0..4], byte_order(data, base)) >> 8 == RAW_MARK;
+}
+
+fn locate(data: []const u8) !Layout {
+    if (!detect(data)) return formats.FormatError.InvalidHeader;
+    const base = find_base(data).?;
+    const order = byte_order(data, base);
+    const start = base + try read_u32(data, base + 8, order);
+    const count = try read_u32(data, start, order);
+    var dir = Directory{};
+    for (0..count) |i| {
+        const entry = start + 8 + i * 16;
+        const tag = try read_u32(data, entry, order);
+        const len = try read_u32(data, entry + 8, order);
+        const value = try read_u32(data, entry + 12, order);
+        const at = base + value;
+
+        switch (tag) {
+            IIQ_ORIENTATION => dir.orientation = value & 3,
+            IIQ_ROMM_MATRIX => dir.romm_cam = read_floats(9, data, at, order),
+            IIQ_WB_LEVELS => dir.wb = read_floats(3, data, at, order),
+            IIQ_RAW_WIDTH => dir.raw_width = value,
+            IIQ_RAW_HEIGHT => dir.raw_height = value,
+            IIQ_LEFT_MARGIN => dir.left = value,
+            IIQ_TOP_MARGIN => dir.top = value,
+            IIQ_WIDTH => dir.width = value,
+            IIQ_HEIGHT => dir.height = value,
+            IIQ_FORMAT => dir.format = value,
+            IIQ_DATA_OFFSET => dir.data_offset = at,
+            IIQ_CALIBRATION => {
+                if (len > 0 and at + len <= data.len) dir.calibration = data[at..][0..len];
+            },
+            IIQ_KEY => dir.key_offset = entry + 12,
+            IIQ_SENSOR_TEMPERATURE => dir.sensor_temperature = @as(f32, @bitCast(value)),
+            IIQ_STRIP_OFFSETS => dir.strip_offsets = at,
+            IIQ_BLACK => dir.black = value,
+            IIQ_SPLIT_COLUMN => dir.split_column = value,
+            IIQ_ROW_BLACK => dir.row_black = at,
+            IIQ_SPLIT_ROW => dir.split_row = value,
+            IIQ_COLUMN_BLACK => dir.column_black = at,
+            IIQ_MODEL => {
+                if (at < data.len) {
+                    const text = std.mem.sliceTo(data[at..@min(data.len, at + 63)], 0);
+                    // Backs report e.g. "IQ180 camera"
+                    dir.model = text[0 .. std.mem.indexOf(u8, text, " camera") orelse text.len];
+                }
+            },
+            else => {},
+        }
+    }
+
+    // Capture settings are in the EXIF data of the enclosing TIFF
+    var exif = tiff.ExifInfo{};
+    if (tiff.read_header(data)) |header| {
+        const parser = tiff.IfdParser.init(data, header.byte_order);
+        if (parser.parse_ifd(header.first_ifd)) |ifd0| {
+            exif = tiff.read_exif_info(ifd0);
+        } else |_| {}
+    }
+    return Layout{ .order = order, .directory = dir, .exif = exif };
+}
+
+fn read_u32(bytes: []const u8, pos: usize, order: std.builtin.Endian) !u32 {
+    if (pos + 4 > bytes.len) return formats.FormatError.TruncatedFile;
+    return std.mem.readInt(u32, bytes[pos..][0..4], order);
+}
+
+fn read_floats(comptime n: usize, bytes: []const u8, at: usize, order: std.builtin.Endian) ?[n]f32 {
+    if (at + n * 4 > bytes.len) return null;
+    var values: [n]f32 = undefined;
+    for (&values, 0..) |*v, i| v.* = @bitCast(std.mem.readInt(u32, bytes[at + i * 4 ..][0..4], order));
+    return values;
+}
+
+/// Capture settings from the TIFF structure ahead of the Phase One directory
+pub fn exif_info(data: []const u8) tiff.ExifInfo {
+    const layout = locate(data) catch return .{};
+    return layout.exif;
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const dir = layout.directory;
+    if (dir.raw_width == 0 or dir.raw_height == 0 or dir.data_offset == 0) return formats.FormatError.CorruptedData;
+    if (dir.data_offset >= data.len) return formats.FormatError.TruncatedFile;
+
+    const make = try allocator.dupe(u8, "Phase One");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, dir.model orelse layout.exif.model orelse "Unknown Phase One Back");
+    errdefer allocator.free(model);
+
+    const compressed = dir.format >= FORMAT_COMPRESSED;
+    var metadata = formats.RawMetadata{
+        .format = .IIQ,
+        .width = dir.raw_width,
+        .height = dir.raw_height,
+        .orientation = switch (dir.orientation) {
+            1 => .Rotate90,
+            2 => .Rotate270,
+            3 => .Rotate180,
+            else => .Normal,
+        },
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        // Compressed data has its black removed while decoding
+        .black_level = [_]f32{if (compressed) 0.0 else @floatFromInt(dir.black)} ** 4,
+        .white_level = [_]u32{if (compressed) WHITE -| dir.black else 0xFFFF} ** 4,
+        .raw_data_offset = @intCast(dir.data_offset),
+        .raw_data_size = @intCast(@min(data.len - dir.data_offset, std.math.maxInt(u32))),
+        // Green-blue rows first, counted from the top of the stored data
+        .cfa = formats.CfaPattern.from_2x2(.{ 1, 2, 0, 1 }),
+        .bits_per_sample = 16,
+        .compression = dir.format,
+        .allocator = allocator,
+    };
+
+    if (dir.width > 0 and dir.height > 0 and dir.left + dir.width <= dir.raw_width and dir.top + dir.height <= dir.raw_height) {
+        metadata.crop = .{ .x = dir.left, .y = dir.top, .width = dir.width, .height = dir.height };
+    }
+    if (dir.wb) |wb| {
+        if (wb[0] > 0.0 and wb[1] > 0.0 and wb[2] > 0.0) metadata.white_balance = .{ wb[0] / wb[1], 1.0, wb[2] / wb[1] };
+    }
+    if (dir.romm_cam) |romm_cam| metadata.color_matrix = formats.xyz_from_romm_matrix(romm_cam);
+    metadata.sensor_temperature = dir.sensor_temperature;
+    return metadata;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const dir = layout.directory;
+    const width = metadata.width;
+    const height = metadata.height;
+    if (width != dir.raw_width or height != dir.raw_height) return formats.FormatError.CorruptedData;
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+    if (dir.format >= FORMAT_COMPRESSED) {
+        try decode_compressed(data, layout, raw, allocator);
+    } else {
+        try decode_uncompressed(data, layout, raw);
+    }
+
+    if (dir.calibration) |block| try apply_calibration(block, dir, metadata.cfa, raw, width, height, allocator);
+    return raw;
+}
+
+/// 16-bit words; formats 1 and 2 swap bits between the two samples of each
+/// pair after XORing them with the key words
+fn decode_uncompressed(data: []const u8, layout: Layout, raw: []u16) !void {
+    const dir = layout.directory;
+    if (dir.data_offset + raw.len * 2 > data.len) return formats.FormatError.TruncatedFile;
+    for (raw, 0..) |*sample, i| sample.* = std.mem.readInt(u16, data[dir.data_offset + i * 2 ..][0..2], layout.order);
+    if (dir.format == 0) return;
+
+    const key_at = dir.key_offset orelse return formats.FormatError.CorruptedData;
+    if (key_at + 4 > data.len) return formats.FormatError.TruncatedFile;
+    const a_key = std.mem.readInt(u16, data[key_at..][0..2], layout.order);
+    const b_key = std.mem.readInt(u16, data[key_at + 2 ..][0..2], layout.order);
+    const mask: u16 = if (dir.format == 1) 0x5555 else 0x1354;
+    var i: usize = 0;
+    while (i + 1 < raw.len) : (i += 2) {
+        const a = raw[i] ^ a_key;
+        const b = raw[i + 1] ^ b_key;
+        raw[i] = (a & mask) | (b & ~mask);
+        raw[i + 1] = (b & mask) | (a & ~mask);
+    }
+}
+
+fn decode_compressed(data: []const u8, layout: Layout, raw: []u16, allocator: std.mem.Allocator) !void {
+    const dir = layout.directory;
+    const width: usize = dir.raw_width;
+    const height: usize = dir.raw_height;
+    if (dir.strip_offsets == 0 or dir.strip_offsets + height * 4 > data.len) return formats.FormatError.CorruptedData;
+
+    // Per-row and per-column black, each split in two halves
+    const row_black = try read_black_pairs(allocator, data, dir.row_black, height, layout.order);
+    defer allocator.free(row_black);
+    const column_black = try read_black_pairs(allocator, data, dir.column_black, width, layout.order);
+    defer allocator.free(column_black);
+
+    const pixels = try allocator.alloc(u16, width);
+    defer allocator.free(pixels);
+    const shift: u5 = if (dir.format == FORMAT_FULL_SCALE) 0 else 2;
+    var lens = [2]u32{ 0, 0 };
+
+    for (0..height) |row| {
+        const offset = std.mem.readInt(u32, data[dir.strip_offsets + row * 4 ..][0..4], layout.order);
+        const start = dir.data_offset + offset;
+        if (start >= data.len) return formats.FormatError.TruncatedFile;
+        try decoders.phase_one.decode_row(data[start..], pixels, &lens);
+
+        for (pixels, 0..) |pixel, col| {
+            var value: i32 = pixel;
+            if (dir.format == FORMAT_SMALL and value < 256) {
+                value = @intFromFloat(@as(f32, @floatFromInt(value * value)) / 3.969 + 0.5);
+            }
+            value = (value << shift) - @as(i32, @intCast(dir.black)) +
+                row_black[row * 2 + @intFromBool(col >= dir.split_column)] +
+                column_black[col * 2 + @intFromBool(row >= dir.split_row)];
+            raw[row * width + col] = @intCast(std.math.clamp(value, 0, 0xFFFF));
+        }
+    }
+}
+
+fn read_black_pairs(allocator: std.mem.Allocator, data: []const u8, at: usize, count: usize, order: std.builtin.Endian) ![]i32 {
+    const pairs = try allocator.alloc(i32, count * 2);
+    @memset(pairs, 0);
+    if (at == 0 or at + count * 4 > data.len) return pairs;
+    for (pairs, 0..) |*value, i| value.* = std.mem.readInt(i16, data[at + i * 2 ..][0..2], order);
+    return pairs;
+}
+
+/// Sensor calibration stored by the back: defect maps, flat fields and
+/// linearisation curves, applied in the order they are listed
+fn apply_calibration(
+    block: []const u8,
+    dir: Directory,
+    cfa: formats.CfaPattern,
+    raw: []u16,
+    width: u32,
+    height: u32,
+    allocator: std.mem.Allocator,
+) !void {
+    if (block.len < 16) return;
+    const cal = Calibration{ .bytes = block, .order = if (block[0] == 'I') .little else .big };
+    const start = cal.u32_at(8) orelse return;
+    const count = cal.u32_at(start) orelse return;
+    const image = Image{ .data = raw, .width = width, .height = height, .cfa = cfa };
+
+    for (0..count) |i| {
+        const entry = start + 8 + i * 12;
+        const tag = cal.u32_at(entry) orelse return formats.FormatError.CorruptedData;
+        const len = cal.u32_at(entry + 4) orelse return formats.FormatError.CorruptedData;
+        const at: usize = cal.u32_at(entry + 8) orelse return formats.FormatError.CorruptedData;
+
+        switch (tag) {
+            CAL_DEFECTS => fix_defects(cal, at, len, image),
+            CAL_FLAT_FIELD_ALL_FLOAT => try flat_field(allocator, cal, at, image, true, 2),
+            CAL_FLAT_FIELD, CAL_FLAT_FIELD_ALT => try flat_field(allocator, cal, at, image, false, 2),
+            CAL_FLAT_FIELD_RED_BLUE => try flat_field(allocator, cal, at, image, false, 4),
+            CAL_CURVE_RIGHT => {
+                var poly: [8]f32 = undefined;
+                for (&poly, 0..) |*p, k| p.* = cal.f32_at(at + 4 + k * 4) orelse return formats.FormatError.CorruptedData;
+                // Shifted by the sensor temperature at capture
+                poly[3] += ((dir.sensor_temperature orelse 0.0) - poly[7]) * poly[6] + 1.0;
+                apply_curve(image, @min(dir.split_column, width), poly[1], poly[3], poly[5]);
+            },
+            CAL_CURVE => {
+                var poly: [4]f32 = undefined;
+                for (&poly, 0..) |*p, k| p.* = cal.f32_at(at + k * 4) orelse return formats.FormatError.CorruptedData;
+                for (image.data) |*sample| {
+                    const x: f32 = @floatFromInt(sample.*);
+                    const value = ((poly[3] * x + poly[2]) * x + poly[1]) * x + poly[0] + x;
+                    sample.* = @intFromFloat(std.math.clamp(value, 0.0, 65535.0));
+                }
+            },
+            else => {},
+        }
+    }
+}
+
+const Calibration = struct {
+    bytes: []const u8,
+    order: std.builtin.Endian,
+
+    fn u16_at(self: Calibration, pos: usize) ?u16 {
+        if (pos + 2 > self.bytes.len) return null;
+        return std.mem.readInt(u16, self.bytes[pos..][0..2], self.order);
+    }
+
+    fn u32_at(self: Calibration, pos: usize) ?u32 {
+        if (pos + 4 > self.bytes.len) return null;
+        return std.mem.readInt(u32, self.bytes[pos..][0..4], self.order);
+    }
+
+    fn f32_at(self: Calibration, pos: usize) ?f32 {
+        return @bitCast(self.u32_at(pos) orelse return null);
+    }
+};
+
+const Image = struct {
+    data: []u16,
+    width: u32,
+    height: u32,
+    cfa: formats.CfaPattern,
+
+    /// Samples outside the image read as 0
+    fn at(self: Image, row: i64, col: i64) i32 {
+        if (row < 0 or col < 0 or row >= self.height or col >= self.width) return 0;
+        return self.data[@intCast(row * self.width + col)];
+    }
+
+    fn set(self: Image, row: usize, col: usize, value: f32) void {
+        self.data[row * self.width + col] = @intFromFloat(std.math.clamp(value, 0.0, 65535.0));
+    }
+};
+
+/// Quadratic curve for the columns right of `from`
+fn apply_curve(image: Image, from: u32, c0: f32, c1: f32, c2: f32) void {
+    for (0..image.height) |row| {
+        for (from..image.width) |col| {
+            const x: f32 = @floatFromInt(image.data[row * image.width + col]);
+            image.set(row, col, (c2 * x + c1) * x + c0);
+        }
+    }
+}
+
+/// Neighbour offsets: the four diagonals, the four two away in a line, then
+/// the four two away diagonally
+const NEIGHBOURS = [12][2]i64{
+    .{ -1, -1 }, .{ -1, 1 }, .{ 1, -1 }, .{ 1, 1 },
+    .{ -2, 0 },  .{ 0, -2 }, .{ 0, 2 },  .{ 2, 0 },
+    .{ -2, -2 }, .{ -2, 2 }, .{ 2, -2 }, .{ 2, 2 },
+};
+
+fn neighbour_sum(image: Image, row: i64, col: i64, from: usize, to: usize) i32 {
+    var sum: i32 = 0;
+    for (NEIGHBOURS[from..to]) |d| sum += image.at(row + d[0], col + d[1]);
+    return sum;
+}
+
+/// Interpolates mapped bad pixels and columns from same-colour neighbours
+fn fix_defects(cal: Calibration, at: usize, len: u32, image: Image) void {
+    var pos = at;
+    while (pos + 8 <= at + len) : (pos += 8) {
+        const col: i64 = cal.u16_at(pos) orelse return;
+        const defect_row: i64 = cal.u16_at(pos + 2) orelse return;
+        const kind = cal.u16_at(pos + 4) orelse return;
+        if (col >= image.width) continue;
+
+        if (kind == DEFECT_COLUMN or kind == DEFECT_COLUMN_ALT) {
+            for (0..image.height) |r| {
+                const row: i64 = @intCast(r);
+                if (image.cfa.color_at(r, @intCast(col)) == 1) {
+                    // Green: the mean of the diagonals, leaving out the outlier
+                    var values: [4]i32 = undefined;
+                    var sum: i32 = 0;
+                    for (&values, NEIGHBOURS[0..4]) |*v, d| {
+                        v.* = image.at(row + d[0], col + d[1]);
+                        sum += v.*;
+                    }
+                    var worst: usize = 0;
+                    for (values, 0..) |v, k| {
+                        if (@abs(v * 256 - sum) > @abs(values[worst] * 256 - sum)) worst = k;
+                    }
+                    image.set(r, @intCast(col), @as(f32, @floatFromInt(sum - values[worst])) / 3.0 + 0.5);
+                } else {
+                    const diagonal: f32 = @floatFromInt(neighbour_sum(image, row, col, 8, 12));
+                    const beside: f32 = @floatFromInt(image.at(row, col - 2) + image.at(row, col + 2));
+                    image.set(r, @intCast(col), 0.5 + diagonal * 0.0732233 + beside * 0.3535534);
+                }
+            }
+        } else if (kind == DEFECT_PIXEL) {
+            if (defect_row >= image.height) continue;
+            // Greens use the diagonals and the lines, others the lines and far diagonals
+            const first: usize = if (image.cfa.color_at(@intCast(defect_row), @intCast(col)) != 1) 4 else 0;
+            const sum = neighbour_sum(image, defect_row, col, first, first + 8);
+            image.data[@intCast(defect_row * image.width + col)] = @intCast((sum + 4) >> 3);
+        }
+    }
+}
+
+/// Gains on a coarse grid, interpolated bilinearly. The header gives the
+/// grid's origin, extent and spacing; each node holds one gain, or a red
+/// and a blue gain when `planes` is 4 (greens are left alone).
+fn flat_field(allocator: std.mem.Allocator, cal: Calibration, at: usize, image: Image, is_float: bool, planes: usize) !void {
+    var head: [6]u32 = undefined;
+    for (&head, 0..) |*h, k| h.* = cal.u16_at(at + k * 2) orelse return formats.FormatError.CorruptedData;
+    if (head[2] == 0 or head[3] == 0 or head[4] == 0 or head[5] == 0) return;
+    const wide: usize = std.math.divCeil(u32, head[2], head[4]) catch unreachable;
+    const high: usize = std.math.divCeil(u32, head[3], head[5]) catch unreachable;
+    const step_x: f32 = @floatFromInt(head[4]);
+    const step_y: f32 = @floatFromInt(head[5]);
+
+    // Per plane pair: the gain along the current row, then its step per row
+    const mrow = try allocator.alloc(f32, planes * wide);
+    defer allocator.free(mrow);
+    @memset(mrow, 0.0);
+
+    var pos = at + 16;
+    for (0..high) |y| {
+        for (0..wide) |x| {
+            var c: usize = 0;
+            while (c < planes) : (c += 2) {
+                const num: f32 = if (is_float) blk: {
+                    const v = cal.f32_at(pos) orelse return formats.FormatError.CorruptedData;
+                    pos += 4;
+                    break :blk v;
+                } else blk: {
+                    const v = cal.u16_at(pos) orelse return formats.FormatError.CorruptedData;
+                    pos += 2;
+                    break :blk @as(f32, @floatFromInt(v)) / 32768.0;
+                };
+                if (y == 0) {
+                    mrow[c * wide + x] = num;
+                } else {
+                    mrow[(c + 1) * wide + x] = (num - mrow[c * wide + x]) / step_y;
+                }
+            }
+        }
+        if (y == 0) continue;
+
+        const row_end = head[1] + y * head[5];
+        var row: usize = row_end - head[5];
+        while (row < image.height and row < row_end and row < (head[1] + head[3]) -| head[5]) : (row += 1) {
+            for (1..wide) |x| {
+                var mult: [4]f32 = undefined;
+                var c: usize = 0;
+                while (c < planes) : (c += 2) {
+                    mult[c] = mrow[c * wide + x - 1];
+                    mult[c + 1] = (mrow[c * wide + x] - mult[c]) / step_x;
+                }
+                const col_end = head[0] + x * head[4];
+                var col: usize = col_end - head[4];
+                while (col < image.width and col < col_end and col < (head[0] + head[2]) -| head[4]) : (col += 1) {
+                    const color: usize = if (planes > 2) image.cfa.color_at(row, col) else 0;
+                    if (color & 1 == 0) {
+                        const value: f32 = @floatFromInt(image.data[row * image.width + col]);
+                        image.set(row, col, @trunc(value * mult[color]));
+                    }
+                    c = 0;
+                    while (c < planes) : (c += 2) mult[c] += mult[c + 1];
+                }
+            }
+            for (0..wide) |x| {
+                var c: usize = 0;
+                while (c < planes) : (c += 2) mrow[c * wide + x] += mrow[(c + 1) * wide + x];
+            }
+        }
+    }
+}
+
+test "flat field gains interpolate between grid rows" {
+    // A 2x2 grid over a 4x4 area with spacing 2: gain 1.0 on the first grid
+    // row and 2.0 on the second, as 1/32768 units
+    var block: [16 + 8]u8 = undefined;
+    const head = [_]u16{ 0, 0, 4, 4, 2, 2, 0, 0 };
+    for (head, 0..) |h, k| std.mem.writeInt(u16, block[k * 2 ..][0..2], h, .little);
+    const gains = [_]u16{ 32768, 32768, 65535, 65535 };
+    for (gains, 0..) |g, k| std.mem.writeInt(u16, block[16 + k * 2 ..][0..2], g, .little);
+
+    var samples = [_]u16{100} ** 16;
+    const image = Image{ .data = &samples, .width = 4, .height = 4, .cfa = formats.CfaPattern.rggb };
+    try flat_field(std.testing.allocator, .{ .bytes = &block, .order = .little }, 0, image, false, 2);
+    // Rows 0 and 1, columns 0 and 1, step from 1.0 towards 2.0
+    try std.testing.expectEqual(@as(u16, 100), samples[0]);
+    try std.testing.expectEqual(@as(u16, 149), samples[4]);
+}
@@ -0,0 +1,239 @@
+// src/formats/kodak.zig - Kodak DCR and KDC RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+/// Points to Kodak's private IFD, from IFD0 or the EXIF IFD
+const TAG_KODAK_IFD = 0x8290;
+const TAG_KODAK_IFD_ALT = 0xFE00;
+const EXIF_COMPRESSED_BITS_PER_PIXEL = 0x9102;
+
+// Kodak IFD entries
+const KODAK_WB_MULTIPLIERS = 0x03FD;
+const KODAK_LINEARIZATION = 0x090D;
+const KODAK_ISO = 0x1784;
+
+/// Length of the Kodak WB entry whose multipliers this reader knows
+const KODAK_WB_MULTIPLIERS_SIZE = 72;
+/// The multipliers are 2048 over the stored values
+const KODAK_WB_UNITY = 2048.0;
+
+const COMPRESSION_NONE = 1;
+const COMPRESSION_KODAK_65000 = 65000;
+
+// The DC40 and DC50 store radc data at a fixed place with a fixed size
+const RADC_WIDTH = 768;
+const RADC_HEIGHT = 512;
+const DC40_DATA_OFFSET = 1152;
+const DC50_DATA_OFFSET = 19712;
+const RADC_WHITE = 0x3FFF;
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: ?tiff.Ifd,
+    exif: tiff.ExifInfo,
+    kodak: ?tiff.Ifd,
+    /// Start of the radc data of the DC40 and DC50
+    radc_offset: ?u32,
+};
+
+pub fn detect(data: []const u8) bool {
+    return detect_format(data) != null;
+}
+
+/// DCR is the professional bodies' format (DCS, Pro Back); KDC covers the
+/// consumer cameras. Both are TIFF-based.
+pub fn detect_format(data: []const u8) ?root.ZrRawFormat {
+    const header = tiff.read_header(data) orelse return null;
+    if (header.magic != tiff.TIFF_MAGIC) return null;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return null;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return null;
+    if (!std.mem.startsWith(u8, make, "Kodak") and !std.mem.startsWith(u8, make, "KODAK") and
+        !std.mem.startsWith(u8, make, "EASTMAN KODAK")) return null;
+
+    const model = ifd0.get_string(tiff.EXIF_MODEL) orelse "";
+    if (std.mem.indexOf(u8, model, "DCS") != null or std.mem.indexOf(u8, model, "ProBack") != null) return .DCR;
+    return .KDC;
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const exif_ifd = ifd0.sub_ifd(tiff.TAG_EXIF_IFD);
+
+    var kodak: ?tiff.Ifd = ifd0.sub_ifd(TAG_KODAK_IFD) orelse ifd0.sub_ifd(TAG_KODAK_IFD_ALT);
+    if (kodak == null) {
+        if (exif_ifd) |exif| kodak = exif.sub_ifd(TAG_KODAK_IFD) orelse exif.sub_ifd(TAG_KODAK_IFD_ALT);
+    }
+
+    const model = ifd0.get_string(tiff.EXIF_MODEL) orelse "";
+    const radc_offset: ?u32 = if (std.mem.indexOf(u8, model, "DC40") != null)
+        DC40_DATA_OFFSET
+    else if (std.mem.indexOf(u8, model, "DC50") != null)
+        DC50_DATA_OFFSET
+    else
+        null;
+
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse tiff.find_sensor_ifd(ifd0);
+    if (raw_ifd == null and radc_offset == null) return formats.FormatError.UnsupportedFormat;
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = tiff.read_exif_info(ifd0),
+        .kodak = kodak,
+        .radc_offset = radc_offset,
+    };
+}
+
+/// The tone curve the data is stored on, from the Kodak IFD
+fn read_curve(layout: Layout, allocator: std.mem.Allocator) !?[]u16 {
+    const kodak = layout.kodak orelse return null;
+    const entry = kodak.find(KODAK_LINEARIZATION) orelse return null;
+    if (entry.count == 0 or entry.count > 0x10000) return null;
+    const curve = try allocator.alloc(u16, entry.count);
+    errdefer allocator.free(curve);
+    for (curve, 0..) |*v, i| {
+        const value = entry.u32_at(kodak.parser, i) orelse return formats.FormatError.CorruptedData;
+        v.* = @truncate(value);
+    }
+    return curve;
+}
+
+fn apply_kodak_ifd(metadata: *formats.RawMetadata, kodak: tiff.Ifd) void {
+    if (kodak.get_f64(KODAK_ISO)) |iso| {
+        if (iso > 0.0 and iso < 1e6) metadata.iso = @intFromFloat(@round(iso));
+    }
+    if (kodak.find(KODAK_WB_MULTIPLIERS)) |entry| {
+        const bytes = entry.bytes(kodak.parser) orelse return;
+        if (bytes.len != KODAK_WB_MULTIPLIERS_SIZE) return;
+        // R, G, B after 40 bytes of other settings
+        const wb = tiff.IfdParser.init(bytes, kodak.parser.byte_order);
+        var rgb: [3]f32 = undefined;
+        for (&rgb, 0..) |*v, i| {
+            const stored = wb.read_u16(40 + i * 2);
+            if (stored == 0) return;
+            v.* = KODAK_WB_UNITY / @as(f32, @floatFromInt(stored));
+        }
+        metadata.white_balance = .{ rgb[0] / rgb[1], 1.0, rgb[2] / rgb[1] };
+    }
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    const format = detect_format(data) orelse return formats.FormatError.UnsupportedFormat;
+    const layout = try locate(data);
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "Kodak");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Kodak Camera");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = format,
+        .width = RADC_WIDTH,
+        .height = RADC_HEIGHT,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{RADC_WHITE} ** 4,
+        .raw_data_offset = 0,
+        .raw_data_size = 0,
+        // radc output puts green on the diagonal, red beside it
+        .cfa = formats.CfaPattern.from_2x2(.{ 1, 0, 2, 1 }),
+        .bits_per_sample = 14,
+        .compression = 0,
+        .allocator = allocator,
+    };
+    if (layout.kodak) |kodak| apply_kodak_ifd(&metadata, kodak);
+
+    if (layout.radc_offset) |offset| {
+        if (offset >= data.len) return formats.FormatError.TruncatedFile;
+        metadata.raw_data_offset = offset;
+        metadata.raw_data_size = @intCast(data.len - offset);
+        return metadata;
+    }
+
+    const raw = layout.raw_ifd.?;
+    metadata.width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    metadata.height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 12;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE;
+    if (compression != COMPRESSION_NONE and compression != COMPRESSION_KODAK_65000) return formats.FormatError.UnsupportedFormat;
+
+    metadata.bits_per_sample = bits;
+    metadata.compression = compression;
+    metadata.raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0;
+    metadata.raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32)));
+    metadata.cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb;
+
+    // Compressed data is linearised on decoding, so white is the curve's top
+    var white = (@as(u32, 1) << @as(u5, @intCast(bits))) - 1;
+    if (compression == COMPRESSION_KODAK_65000) {
+        white = 0xFFF;
+        if (try read_curve(layout, allocator)) |curve| {
+            defer allocator.free(curve);
+            white = curve[curve.len - 1];
+        }
+    }
+    metadata.white_level = [_]u32{white} ** 4;
+    return metadata;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    if (layout.radc_offset != null) {
+        try decoders.kodak.decode_radc(input, raw, width, height, compressed_bits_per_pixel(layout));
+        return raw;
+    }
+
+    const order: std.builtin.Endian = if (layout.ifd0.parser.byte_order == .little) .little else .big;
+    if (metadata.compression == COMPRESSION_KODAK_65000) {
+        const curve = try read_curve(layout, allocator);
+        defer if (curve) |c| allocator.free(c);
+        try decoders.kodak.decode_65000(input, raw, width, height, curve, order);
+        return raw;
+    }
+
+    const unpack = decoders.unpack;
+    if (input.len >= @as(usize, width) * height * 2) {
+        try unpack.unpack(input, raw, width, height, 16, if (order == .little) .u16_little else .u16_big, @as(usize, width) * 2);
+    } else {
+        const bits = metadata.bits_per_sample;
+        try unpack.unpack(input, raw, width, height, bits, .msb, unpack.row_bytes(width, bits, .msb));
+    }
+    return raw;
+}
+
+/// The radc compression level, stored as EXIF CompressedBitsPerPixel
+fn compressed_bits_per_pixel(layout: Layout) u32 {
+    const exif = layout.ifd0.sub_ifd(tiff.TAG_EXIF_IFD);
+    for ([_]?tiff.Ifd{ exif, layout.ifd0 }) |candidate| {
+        const ifd = candidate orelse continue;
+        if (ifd.get_rational(EXIF_COMPRESSED_BITS_PER_PIXEL)) |r| return std.math.cast(u32, r.num) orelse 0;
+    }
+    return 0;
+}
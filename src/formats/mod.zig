@@ -2,16 +2,210 @@
 const std = @import("std");
 const root = @import("../root.zig");
 
+const tiff = @import("tiff/mod.zig");
+
 // Import format-specific modules
 const rw2 = @import("rw2.zig");
+const nef = @import("nef.zig");
+const arw = @import("arw.zig");
+const dng = @import("dng/mod.zig");
+const raf = @import("raf.zig");
+const orf = @import("orf.zig");
+const pef = @import("pef.zig");
+const x3f = @import("x3f.zig");
+const iiq = @import("iiq.zig");
+const hasselblad = @import("hasselblad.zig");
+const mos = @import("mos.zig");
+const crw = @import("crw.zig");
+const mrw = @import("mrw.zig");
+const kodak = @import("kodak.zig");
+const srw = @import("srw.zig");
+const erf = @import("erf.zig");
+pub const preview = @import("preview.zig");
+pub const extended = @import("extended.zig");
+pub const cameras = @import("cameras.zig");
+pub const color = @import("color.zig");
 
 pub const FormatError = error{
     UnsupportedFormat,
     InvalidHeader,
     CorruptedData,
     TruncatedFile,
+    UnsupportedCompression,
+};
+
+/// Compression value of the last `UnsupportedCompression` error on this thread
+pub threadlocal var last_unsupported_compression: u32 = 0;
+
+/// Records `compression` so callers can name it, then fails with `UnsupportedCompression`
+pub fn unsupported_compression(compression: u32) FormatError {
+    last_unsupported_compression = compression;
+    return FormatError.UnsupportedCompression;
+}
+
+/// Colour filter array layout, repeated over the sensor
+pub const CfaPattern = struct {
+    width: u8 = 2,
+    height: u8 = 2,
+    /// Colour per site, row-major: 0 = red, 1 = green, 2 = blue
+    colors: [36]u8 = [_]u8{0} ** 36,
+
+    pub const rggb = from_2x2(.{ 0, 1, 1, 2 });
+
+    pub fn from_2x2(colors: [4]u8) CfaPattern {
+        var pattern = CfaPattern{};
+        @memcpy(pattern.colors[0..4], &colors);
+        return pattern;
+    }
+
+    pub fn color_at(self: CfaPattern, row: usize, col: usize) u8 {
+        return self.colors[(row % self.height) * self.width + (col % self.width)];
+    }
+};
+
+/// Reads a TIFF/EP (0x828D/0x828E) or EXIF (0xA302) CFA pattern
+pub fn cfa_from_ifd(ifd: tiff.Ifd) ?CfaPattern {
+    var dims: [2]u32 = .{ 2, 2 };
+    var colors: []const u8 = undefined;
+    if (ifd.find(tiff.TAG_CFA_PATTERN)) |entry| {
+        colors = entry.bytes(ifd.parser) orelse return null;
+        if (ifd.find(tiff.TAG_CFA_REPEAT_PATTERN_DIM)) |dim| {
+            dims[0] = dim.u32_at(ifd.parser, 0) orelse return null;
+            dims[1] = dim.u32_at(ifd.parser, 1) orelse return null;
+        }
+    } else if (ifd.get_bytes(tiff.TAG_EXIF_CFA_PATTERN)) |exif| {
+        // Horizontal and vertical repeat in the file's byte order, then the colours
+        if (exif.len < 4) return null;
+        const parser = tiff.IfdParser.init(exif, ifd.parser.byte_order);
+        dims = .{ parser.read_u16(2), parser.read_u16(0) };
+        colors = exif[4..];
+    } else return null;
+
+    const rows = dims[0];
+    const cols = dims[1];
+    if (rows == 0 or cols == 0 or rows > 6 or cols > 6 or colors.len < rows * cols) return null;
+    var pattern = CfaPattern{ .width = @intCast(cols), .height = @intCast(rows) };
+    for (colors[0 .. rows * cols], 0..) |color, i| {
+        // Anything beyond RGB (CMY sensors) is treated as green
+        pattern.colors[i] = if (color <= 2) color else 1;
+    }
+    return pattern;
+}
+
+/// A rectangle in image coordinates
+pub const Rect = struct {
+    x: u32 = 0,
+    y: u32 = 0,
+    width: u32,
+    height: u32,
+};
+
+/// SMPTE timecode of a video frame
+pub const Timecode = struct {
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+    frames: u8,
+    drop_frame: bool = false,
+};
+
+/// Floating point sensor data, normalised so that black is 0.0 and white
+/// is 1.0 once it leaves `extract_linear`
+pub const LinearImage = struct {
+    width: u32,
+    height: u32,
+    /// Interleaved samples per pixel; 1 for CFA data
+    planes: u32,
+    data: []f32,
+    allocator: std.mem.Allocator,
+
+    pub fn init(allocator: std.mem.Allocator, width: u32, height: u32, planes: u32) !LinearImage {
+        const data = try allocator.alloc(f32, @as(usize, width) * height * planes);
+        return LinearImage{ .width = width, .height = height, .planes = planes, .data = data, .allocator = allocator };
+    }
+
+    pub fn deinit(self: *LinearImage) void {
+        self.allocator.free(self.data);
+    }
+
+    pub fn index(self: LinearImage, row: usize, col: usize, plane: usize) usize {
+        return (row * self.width + col) * self.planes + plane;
+    }
+};
+
+pub fn orientation_from_exif(value: u16) root.ZrRawOrientation {
+    return std.meta.intToEnum(root.ZrRawOrientation, value) catch .Normal;
+}
+
+// sRGB primaries to XYZ, D65 white
+const srgb_to_xyz = [9]f32{
+    0.4124564, 0.3575761, 0.1804375,
+    0.2126729, 0.7151522, 0.0721750,
+    0.0193339, 0.1191920, 0.9503041,
 };
 
+/// Turns a camera to sRGB matrix, as some makers store, into the camera to
+/// XYZ matrix `RawMetadata.color_matrix` holds
+pub fn xyz_from_srgb_matrix(rgb_cam: [9]f32) [9]f32 {
+    var m: [9]f32 = undefined;
+    for (0..3) |i| {
+        for (0..3) |j| {
+            var sum: f32 = 0.0;
+            for (0..3) |k| sum += srgb_to_xyz[i * 3 + k] * rgb_cam[k * 3 + j];
+            m[i * 3 + j] = sum;
+        }
+    }
+    return m;
+}
+
+// ROMM (ProPhoto) to linear sRGB
+const rgb_from_romm = [9]f32{
+    2.034193,  -0.727420, -0.306766,
+    -0.228811, 1.231729,  -0.002922,
+    -0.008565, -0.153273, 1.161839,
+};
+
+/// Turns a camera to ROMM RGB matrix, as medium format backs store, into
+/// the camera to XYZ matrix
+pub fn xyz_from_romm_matrix(romm_cam: [9]f32) [9]f32 {
+    var rgb_cam: [9]f32 = undefined;
+    for (0..3) |i| {
+        for (0..3) |j| {
+            var sum: f32 = 0.0;
+            for (0..3) |k| sum += rgb_from_romm[i * 3 + k] * romm_cam[k * 3 + j];
+            rgb_cam[i * 3 + j] = sum;
+        }
+    }
+    return xyz_from_srgb_matrix(rgb_cam);
+}
+
+/// The fraction the file recorded if it fits the FFI metadata struct,
+/// otherwise an approximation of `value`
+fn to_fraction(exact: ?tiff.Rational, value: f32, num: *u32, den: *u32) void {
+    if (exact) |r| {
+        const max = std.math.maxInt(u32);
+        if (r.num >= 0 and r.num <= max and r.den > 0 and r.den <= max) {
+            num.* = @intCast(r.num);
+            den.* = @intCast(r.den);
+            return;
+        }
+    }
+    if (!(value > 0.0)) {
+        num.* = 0;
+        den.* = 1;
+        return;
+    }
+    // 1/n where that is close, as for most shutter speeds, else tenths
+    const n = @round(1.0 / value);
+    if (value < 1.0 and @abs(1.0 / n - value) <= value * 0.01) {
+        num.* = 1;
+        den.* = std.math.lossyCast(u32, n);
+    } else {
+        num.* = std.math.lossyCast(u32, @round(value * 10.0));
+        den.* = 10;
+    }
+}
+
 pub const RawMetadata = struct {
     format: root.ZrRawFormat,
     width: u32,
@@ -19,21 +213,77 @@ pub const RawMetadata = struct {
     orientation: root.ZrRawOrientation = .Normal,
     make: []const u8,
     model: []const u8,
+    /// Lens model from the makernote, empty if unknown
+    lens: []const u8 = "",
+    /// Make and model as the camera database names them, set by `cameras.apply`
+    canonical_make: []const u8 = "",
+    canonical_model: []const u8 = "",
+    /// Readout the camera database distinguishes, such as "12bit"; not owned
+    mode: []const u8 = "",
     iso: u32,
     shutter_speed: f32,
     aperture: f32,
+    /// Exposure time and f-number as the file records them, which the FFI
+    /// fractions keep exact; null where only the floats are known
+    exposure_time: ?tiff.Rational = null,
+    f_number: ?tiff.Rational = null,
     focal_length: f32,
     color_matrix: [9]f32,
+    /// Calibration for more than one illuminant, for formats with DNG colour
+    /// tags; `color.of` falls back to `color_matrix` without it
+    color_profile: ?color.Profile = null,
     white_balance: [3]f32,
     black_level: [4]f32,
     white_level: [4]u32,
     raw_data_offset: u32,
     raw_data_size: u32,
+    cfa: CfaPattern = CfaPattern.rggb,
+    bits_per_sample: u32 = 16,
+    compression: u32 = 1,
+    /// Samples per pixel of the decoded image; 1 for CFA data
+    samples_per_pixel: u32 = 1,
+    /// The data needs no demosaicing (linear DNG, monochrome sensors)
+    linear: bool = false,
+    /// Default crop within width x height, if the file defines one
+    crop: ?Rect = null,
+    /// Optically black border to measure black levels from, for formats
+    /// that do not record them
+    masked_area: ?Rect = null,
+    /// Horizontal and vertical scale to square pixels
+    default_scale: [2]f32 = .{ 1.0, 1.0 },
+    /// Stops of exposure the file asks renderers to add (DNG
+    /// BaselineExposure plus BaselineExposureOffset)
+    baseline_exposure: f32 = 0.0,
+    /// Frames per second and timecode of CinemaDNG frames
+    frame_rate: f32 = 0.0,
+    timecode: ?Timecode = null,
+    /// Degrees Celsius, for bodies that record it
+    sensor_temperature: ?f32 = null,
     allocator: std.mem.Allocator,
 
     pub fn deinit(self: *RawMetadata) void {
         self.allocator.free(self.make);
         self.allocator.free(self.model);
+        self.allocator.free(self.lens);
+        self.allocator.free(self.canonical_make);
+        self.allocator.free(self.canonical_model);
+    }
+
+    /// Assigns black levels listed per colour in R, G, G, B order to the
+    /// 2x2 sites of the CFA
+    pub fn set_black_levels_rggb(self: *RawMetadata, levels: [4]u32) void {
+        var greens: usize = 0;
+        for (0..4) |site| {
+            const value = switch (self.cfa.color_at(site / 2, site % 2)) {
+                0 => levels[0],
+                2 => levels[3],
+                else => blk: {
+                    greens += 1;
+                    break :blk if (greens == 1) levels[1] else levels[2];
+                },
+            };
+            self.black_level[site] = @floatFromInt(value);
+        }
     }
 
     // --- THIS FUNCTION IS NOW CORRECT ---
@@ -46,6 +296,18 @@ pub const RawMetadata = struct {
         ffi_meta.orientation = self.orientation;
         ffi_meta.iso = self.iso;
         ffi_meta.focal_length = self.focal_length;
+        to_fraction(self.exposure_time, self.shutter_speed, &ffi_meta.shutter_speed_num, &ffi_meta.shutter_speed_den);
+        to_fraction(self.f_number, self.aperture, &ffi_meta.aperture_num, &ffi_meta.aperture_den);
+        ffi_meta.color_matrix = self.color_matrix;
+        ffi_meta.white_balance = self.white_balance;
+        ffi_meta.black_level = self.black_level;
+        ffi_meta.white_level = self.white_level;
+        ffi_meta.baseline_exposure = self.baseline_exposure;
+        ffi_meta.frame_rate = self.frame_rate;
+        if (self.timecode) |tc| {
+            ffi_meta.timecode = .{ tc.hours, tc.minutes, tc.seconds, tc.frames };
+            ffi_meta.timecode_flags = root.TIMECODE_PRESENT | if (tc.drop_frame) root.TIMECODE_DROP_FRAME else 0;
+        }
 
         // Copy strings, ensuring null termination.
         const make_len = @min(self.make.len, ffi_meta.make.len - 1);
@@ -67,21 +329,61 @@ pub fn detect(data: []const u8) !root.ZrRawFormat {
         return .RW2;
     }
 
+    // DNGs may carry any maker's Make tag
+    if (dng.detect(data)) return .DNG;
+    // IIQ files open with a TIFF header too
+    if (iiq.detect(data)) return .IIQ;
+    if (nef.detect(data)) return .NEF;
+    if (arw.detect(data)) return .ARW;
+    if (raf.detect(data)) return .RAF;
+    if (orf.detect(data)) return .ORF;
+    if (pef.detect(data)) return .PEF;
+    if (x3f.detect(data)) return .X3F;
+    if (hasselblad.detect_format(data)) |format| return format;
+    if (mos.detect(data)) return .MOS;
+    if (crw.detect(data)) return .CRW;
+    if (mrw.detect(data)) return .MRW;
+    if (kodak.detect_format(data)) |format| return format;
+    if (srw.detect(data)) return .SRW;
+    if (erf.detect(data)) return .ERF;
+
     // TODO: Add other format detections here
     // if (cr2.detect(data)) return .CR2;
-    // if (nef.detect(data)) return .NEF;
     // etc.
 
     return .Unknown;
 }
 
+/// Parses the format's own metadata, then fills its gaps from the camera database
 pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !RawMetadata {
+    var metadata = try parse_format_metadata(data, allocator);
+    errdefer metadata.deinit();
+    if (metadata.format != .Unknown) try cameras.apply(&metadata);
+    return metadata;
+}
+
+fn parse_format_metadata(data: []const u8, allocator: std.mem.Allocator) !RawMetadata {
     // Detect format first
     const format = try detect(data);
 
     // Parse based on detected format
     switch (format) {
         .RW2 => return rw2.parse_metadata(data, allocator),
+        .NEF => return nef.parse_metadata(data, allocator),
+        .ARW => return arw.parse_metadata(data, allocator),
+        .DNG => return dng.parse_metadata(data, allocator),
+        .RAF => return raf.parse_metadata(data, allocator),
+        .ORF => return orf.parse_metadata(data, allocator),
+        .PEF => return pef.parse_metadata(data, allocator),
+        .X3F => return x3f.parse_metadata(data, allocator),
+        .IIQ => return iiq.parse_metadata(data, allocator),
+        .@"3FR", .FFF => return hasselblad.parse_metadata(data, allocator),
+        .MOS => return mos.parse_metadata(data, allocator),
+        .CRW => return crw.parse_metadata(data, allocator),
+        .MRW => return mrw.parse_metadata(data, allocator),
+        .DCR, .KDC => return kodak.parse_metadata(data, allocator),
+        .SRW => return srw.parse_metadata(data, allocator),
+        .ERF => return erf.parse_metadata(data, allocator),
         .Unknown => {
             // Return stub metadata for unknown formats
             return RawMetadata{
@@ -114,6 +416,21 @@ pub fn extract_raw_data(data: []const u8, metadata: RawMetadata, allocator: std.
     // Extract raw data based on format
     switch (metadata.format) {
         .RW2 => return rw2.extract_raw_data(data, metadata, allocator),
+        .NEF => return nef.extract_raw_data(data, metadata, allocator),
+        .ARW => return arw.extract_raw_data(data, metadata, allocator),
+        .DNG => return dng.extract_raw_data(data, metadata, allocator),
+        .RAF => return raf.extract_raw_data(data, metadata, allocator),
+        .ORF => return orf.extract_raw_data(data, metadata, allocator),
+        .PEF => return pef.extract_raw_data(data, metadata, allocator),
+        .X3F => return x3f.extract_raw_data(data, metadata, allocator),
+        .IIQ => return iiq.extract_raw_data(data, metadata, allocator),
+        .@"3FR", .FFF => return hasselblad.extract_raw_data(data, metadata, allocator),
+        .MOS => return mos.extract_raw_data(data, metadata, allocator),
+        .CRW => return crw.extract_raw_data(data, metadata, allocator),
+        .MRW => return mrw.extract_raw_data(data, metadata, allocator),
+        .DCR, .KDC => return kodak.extract_raw_data(data, metadata, allocator),
+        .SRW => return srw.extract_raw_data(data, metadata, allocator),
+        .ERF => return erf.extract_raw_data(data, metadata, allocator),
         .Unknown => {
             // Return minimal stub data
             return try allocator.alloc(u16, 1);
@@ -124,3 +441,136 @@ pub fn extract_raw_data(data: []const u8, metadata: RawMetadata, allocator: std.
         },
     }
 }
+
+/// Decodes the sensor data and maps it to [0, 1] using the black and white levels
+pub fn extract_linear(data: []const u8, metadata: RawMetadata, allocator: std.mem.Allocator) !LinearImage {
+    if (metadata.format == .DNG) return dng.extract_linear(data, metadata, allocator);
+    if (metadata.format == .X3F) return x3f.extract_linear(data, metadata, allocator);
+
+    const width = metadata.width;
+    const height = metadata.height;
+    const raw = try extract_raw_data(data, metadata, allocator);
+    defer allocator.free(raw);
+    if (raw.len < @as(usize, width) * height) return FormatError.CorruptedData;
+
+    var black_level = metadata.black_level;
+    if (metadata.masked_area) |area| {
+        if (measure_black(raw, width, height, area)) |measured| black_level = measured;
+    }
+
+    var image = try LinearImage.init(allocator, width, height, 1);
+    errdefer image.deinit();
+    for (0..height) |row| {
+        for (0..width) |col| {
+            const site = (row & 1) * 2 + (col & 1);
+            const black = black_level[site];
+            const white: f32 = @floatFromInt(metadata.white_level[site]);
+            const range = @max(white - black, 1.0);
+            const value: f32 = @floatFromInt(raw[row * width + col]);
+            image.data[row * width + col] = @max(value - black, 0.0) / range;
+        }
+    }
+    return image;
+}
+
+/// Mean of each 2x2 site over a masked area, null if the area is off the image
+fn measure_black(raw: []const u16, width: u32, height: u32, area: Rect) ?[4]f32 {
+    const right = @min(@as(usize, area.x) + area.width, width);
+    const bottom = @min(@as(usize, area.y) + area.height, height);
+    var sums = [_]u64{0} ** 4;
+    var counts = [_]u64{0} ** 4;
+    for (area.y..bottom) |row| {
+        for (area.x..right) |col| {
+            const site = (row & 1) * 2 + (col & 1);
+            sums[site] += raw[row * width + col];
+            counts[site] += 1;
+        }
+    }
+    var levels: [4]f32 = undefined;
+    for (&levels, sums, counts) |*level, sum, count| {
+        if (count == 0) return null;
+        level.* = @as(f32, @floatFromInt(sum)) / @as(f32, @floatFromInt(count));
+    }
+    return levels;
+}
+
+/// Format-specific corrections to the demosaiced image (DNG OpcodeList3
+/// and gain table map)
+pub fn finish_rgb(data: []const u8, metadata: RawMetadata, image: *LinearImage) !void {
+    if (metadata.format == .DNG) try dng.finish_rgb(data, image);
+}
+
+pub const SemanticMask = dng.SemanticMask;
+
+/// Number of semantic masks in the file; only DNG 1.6 and later has them
+pub fn semantic_mask_count(data: []const u8) !u32 {
+    if (!dng.detect(data)) return 0;
+    return dng.semantic_mask_count(data);
+}
+
+pub fn extract_semantic_mask(data: []const u8, index: u32, allocator: std.mem.Allocator) !SemanticMask {
+    if (!dng.detect(data)) return error.InvalidInput;
+    return dng.extract_semantic_mask(data, index, allocator);
+}
+
+/// The EXIF capture settings of a raw file, wherever its format keeps them
+pub fn exif_info(data: []const u8) tiff.ExifInfo {
+    return switch (detect(data) catch root.ZrRawFormat.Unknown) {
+        .RAF => raf.exif_info(data),
+        .MRW => mrw.exif_info(data),
+        .IIQ => iiq.exif_info(data),
+        .X3F, .CRW, .Unknown => .{},
+        else => blk: {
+            const header = tiff.read_header(data) orelse break :blk .{};
+            const parser = tiff.IfdParser.init(data, header.byte_order);
+            const ifd0 = parser.parse_ifd(header.first_ifd) catch break :blk .{};
+            break :blk tiff.read_exif_info(ifd0);
+        },
+    };
+}
+
+pub const Previews = preview.Previews;
+
+/// Lists the embedded previews and thumbnails of a raw file
+pub fn find_previews(data: []const u8) Previews {
+    var found = Previews.init(data);
+    const format = detect(data) catch root.ZrRawFormat.Unknown;
+    switch (format) {
+        // Not TIFF containers; IIQ keeps its previews in a regular IFD0
+        // after the Phase One block
+        .RAF, .X3F, .CRW, .MRW, .Unknown => {},
+        else => if (tiff.read_header(data)) |header| {
+            const parser = tiff.IfdParser.init(data, header.byte_order);
+            if (parser.parse_ifd(header.first_ifd)) |ifd0| {
+                found.orientation = orientation_from_exif(tiff.read_exif_info(ifd0).orientation);
+                preview.scan_tiff(&found, ifd0);
+            } else |_| {}
+        },
+    }
+
+    // Previews only the makernote or a private header points at
+    switch (format) {
+        .NEF => nef.previews(data, &found),
+        .ORF => orf.previews(data, &found),
+        .PEF => pef.previews(data, &found),
+        .RW2 => rw2.previews(data, &found),
+        .RAF => raf.previews(data, &found),
+        .CRW => crw.previews(data, &found),
+        .X3F => x3f.previews(data, &found),
+        .MRW => mrw.previews(data, &found),
+        else => {},
+    }
+    return found;
+}
+
+test "exposure fractions keep what the file recorded" {
+    var num: u32 = 0;
+    var den: u32 = 0;
+    to_fraction(.{ .num = 6, .den = 10 }, 0.6, &num, &den);
+    try std.testing.expectEqual([2]u32{ 6, 10 }, [2]u32{ num, den });
+    // Without a recorded fraction, 0.6 s is not rounded to 1/2
+    to_fraction(null, 0.6, &num, &den);
+    try std.testing.expectEqual([2]u32{ 6, 10 }, [2]u32{ num, den });
+    to_fraction(null, 1.0 / 250.0, &num, &den);
+    try std.testing.expectEqual([2]u32{ 1, 250 }, [2]u32{ num, den });
+}
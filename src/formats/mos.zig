@@ -0,0 +1,227 @@
+// src/formats/mos.zig - Leaf MOS RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+/// Leaf's own metadata, a tree of PKTS packets
+const TAG_LEAF_DATA = 0x8606;
+
+const COMPRESSION_NONE = 1;
+const COMPRESSION_LJPEG = 7;
+const COMPRESSION_LJPEG_OLD = 99;
+
+/// Packets nest inside packet values; real files go a few levels deep
+const MAX_PACKET_DEPTH = 8;
+/// "PKTS", 4 bytes, a 40-byte name and the value size
+const PACKET_HEADER_SIZE = 52;
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+    packets: Packets,
+};
+
+/// The packet values this reader uses. They are stored as text.
+const Packets = struct {
+    romm_cam: ?[9]f32 = null,
+    neutrals: ?[4]f32 = null,
+    mosaic_pattern: ?[4]i32 = null,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return false;
+    return std.mem.startsWith(u8, make, "Leaf");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse tiff.find_sensor_ifd(ifd0) orelse
+        return formats.FormatError.UnsupportedFormat;
+
+    var packets = Packets{};
+    if (ifd0.get_bytes(TAG_LEAF_DATA) orelse raw_ifd.get_bytes(TAG_LEAF_DATA)) |bytes| {
+        read_packets(bytes, &packets, 0);
+    }
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = tiff.read_exif_info(ifd0),
+        .packets = packets,
+    };
+}
+
+/// Walks a run of big-endian packets and the packets inside their values
+fn read_packets(bytes: []const u8, packets: *Packets, depth: u32) void {
+    if (depth >= MAX_PACKET_DEPTH) return;
+    var pos: usize = 0;
+    while (pos + PACKET_HEADER_SIZE <= bytes.len and std.mem.eql(u8, bytes[pos..][0..4], "PKTS")) {
+        const name = std.mem.sliceTo(bytes[pos + 8 ..][0..40], 0);
+        const size = std.mem.readInt(u32, bytes[pos + 48 ..][0..4], .big);
+        const start = pos + PACKET_HEADER_SIZE;
+        if (size > bytes.len - start) return;
+        const value = bytes[start..][0..size];
+
+        if (std.mem.eql(u8, name, "CaptProf_color_matrix")) {
+            packets.romm_cam = parse_numbers(f32, 9, value);
+        } else if (std.mem.eql(u8, name, "NeutObj_neutrals")) {
+            packets.neutrals = parse_numbers(f32, 4, value);
+        } else if (std.mem.eql(u8, name, "CaptProf_mosaic_pattern")) {
+            packets.mosaic_pattern = parse_numbers(i32, 4, value);
+        }
+        read_packets(value, packets, depth + 1);
+        pos = start + size;
+    }
+}
+
+fn parse_numbers(comptime T: type, comptime n: usize, text: []const u8) ?[n]T {
+    var values: [n]T = undefined;
+    var tokens = std.mem.tokenizeAny(u8, text, " \t\r\n\x00");
+    for (&values) |*v| {
+        const token = tokens.next() orelse return null;
+        v.* = switch (@typeInfo(T)) {
+            .float => std.fmt.parseFloat(T, token) catch return null,
+            .int => std.fmt.parseInt(T, token, 10) catch return null,
+            else => @compileError("numbers only"),
+        };
+    }
+    return values;
+}
+
+/// The pattern lists the 2x2 sites going round the square, with 1 marking
+/// red; blue is diagonally opposite
+fn mosaic_cfa(pattern: [4]i32) ?formats.CfaPattern {
+    for (pattern, 0..) |value, c| {
+        if (value != 1) continue;
+        const red = c ^ (c >> 1);
+        var colors = [4]u8{ 1, 1, 1, 1 };
+        colors[red] = 0;
+        colors[3 - red] = 2;
+        return formats.CfaPattern.from_2x2(colors);
+    }
+    return null;
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 16;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE;
+    if (compression != COMPRESSION_NONE and compression != COMPRESSION_LJPEG and compression != COMPRESSION_LJPEG_OLD) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "Leaf");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Leaf Back");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .MOS,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(bits))) - 1} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = compression,
+        .allocator = allocator,
+    };
+
+    const packets = layout.packets;
+    if (packets.romm_cam) |romm_cam| metadata.color_matrix = formats.xyz_from_romm_matrix(romm_cam);
+    // Neutrals are a scale followed by the R, G, B response to white
+    if (packets.neutrals) |n| {
+        if (n[1] > 0.0 and n[2] > 0.0 and n[3] > 0.0) metadata.white_balance = .{ n[2] / n[1], 1.0, n[2] / n[3] };
+    }
+    if (packets.mosaic_pattern) |pattern| {
+        if (mosaic_cfa(pattern)) |cfa| metadata.cfa = cfa;
+    }
+    return metadata;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    if (metadata.compression == COMPRESSION_NONE) {
+        const unpack = decoders.unpack;
+        const bits = metadata.bits_per_sample;
+        if (bits == 16) {
+            const packing: unpack.Packing = if (layout.ifd0.parser.byte_order == .little) .u16_little else .u16_big;
+            try unpack.unpack(input, raw, width, height, 16, packing, @as(usize, width) * 2);
+        } else {
+            try unpack.unpack(input, raw, width, height, bits, .msb, unpack.row_bytes(width, bits, .msb));
+        }
+        return raw;
+    }
+
+    // The JPEG frame may be narrower with several components per row
+    var image = try decoders.ljpeg.decode(allocator, input);
+    defer image.deinit();
+    if (image.samples.len < raw.len) return formats.FormatError.CorruptedData;
+    @memcpy(raw, image.samples[0..raw.len]);
+    return raw;
+}
+
+test "mosaic pattern places red and blue diagonally" {
+    const cfa = mosaic_cfa(.{ 0, 0, 1, 0 }).?;
+    // The third site going round is bottom right
+    try std.testing.expectEqual(@as(u8, 2), cfa.color_at(0, 0));
+    try std.testing.expectEqual(@as(u8, 1), cfa.color_at(0, 1));
+    try std.testing.expectEqual(@as(u8, 0), cfa.color_at(1, 1));
+}
+
+test "packets are found inside packet values" {
+    var bytes = [_]u8{0} ** (PACKET_HEADER_SIZE * 2 + 8);
+    @memcpy(bytes[0..4], "PKTS");
+    @memcpy(bytes[8..][0..8], "CaptProf");
+    std.mem.writeInt(u32, bytes[48..52], PACKET_HEADER_SIZE + 8, .big);
+    const inner = bytes[PACKET_HEADER_SIZE..];
+    @memcpy(inner[0..4], "PKTS");
+    @memcpy(inner[8..][0..23], "CaptProf_mosaic_pattern");
+    std.mem.writeInt(u32, inner[48..52], 8, .big);
+    @memcpy(inner[PACKET_HEADER_SIZE..][0..7], "1 0 0 2");
+
+    var packets = Packets{};
+    read_packets(&bytes, &packets, 0);
+    try std.testing.expectEqual([4]i32{ 1, 0, 0, 2 }, packets.mosaic_pattern.?);
+}
@@ -0,0 +1,183 @@
+// src/formats/mrw.zig - Minolta MRW RAW format support
+const std = @import("std");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+const SIGNATURE = "\x00MRM";
+
+// Blocks before the image data, each a tag and a big-endian length
+const BLOCK_PICTURE = "\x00PRD";
+const BLOCK_WHITE_BALANCE = "\x00WBG";
+const BLOCK_TIFF = "\x00TTW";
+const BLOCK_HEADER_SIZE = 8;
+
+// Sample storage methods in the picture block
+const STORAGE_PACKED = 0x52;
+const STORAGE_UNPACKED = 0x59;
+
+// Bayer patterns in the picture block
+const BAYER_RGGB = 0x0001;
+const BAYER_GBRG = 0x0004;
+
+// Minolta makernote tags; offsets count from the start of the TTW block
+const MAKERNOTE_THUMBNAIL = 0x0081;
+const MAKERNOTE_PREVIEW_START = 0x0088;
+const MAKERNOTE_PREVIEW_LENGTH = 0x0089;
+
+const Layout = struct {
+    data_offset: usize,
+    width: u32 = 0,
+    height: u32 = 0,
+    bits: u32 = 12,
+    storage: u8 = STORAGE_UNPACKED,
+    bayer: u16 = BAYER_RGGB,
+    /// White balance levels in the order of the 2x2 CFA sites
+    wb_levels: ?[4]u16 = null,
+    exif: tiff.ExifInfo = .{},
+    /// The TIFF of the TTW block
+    ttw: ?[]const u8 = null,
+};
+
+pub fn detect(data: []const u8) bool {
+    return data.len >= BLOCK_HEADER_SIZE and std.mem.eql(u8, data[0..4], SIGNATURE);
+}
+
+fn locate(data: []const u8) !Layout {
+    if (!detect(data)) return formats.FormatError.InvalidHeader;
+    const data_offset = @as(usize, std.mem.readInt(u32, data[4..8], .big)) + BLOCK_HEADER_SIZE;
+    if (data_offset > data.len) return formats.FormatError.TruncatedFile;
+
+    var layout = Layout{ .data_offset = data_offset };
+    var pos: usize = BLOCK_HEADER_SIZE;
+    while (pos + BLOCK_HEADER_SIZE <= data_offset) {
+        const tag = data[pos..][0..4];
+        const len = std.mem.readInt(u32, data[pos + 4 ..][0..4], .big);
+        const start = pos + BLOCK_HEADER_SIZE;
+        if (len > data_offset - start) return formats.FormatError.CorruptedData;
+        const block = data[start..][0..len];
+
+        if (std.mem.eql(u8, tag, BLOCK_PICTURE) and block.len >= 24) {
+            layout.height = std.mem.readInt(u16, block[8..10], .big);
+            layout.width = std.mem.readInt(u16, block[10..12], .big);
+            layout.bits = block[17];
+            layout.storage = block[18];
+            layout.bayer = std.mem.readInt(u16, block[22..24], .big);
+        } else if (std.mem.eql(u8, tag, BLOCK_WHITE_BALANCE) and block.len >= 12) {
+            // Four bytes of scale denominators, then the levels
+            var levels: [4]u16 = undefined;
+            for (&levels, 0..) |*level, i| level.* = std.mem.readInt(u16, block[4 + i * 2 ..][0..2], .big);
+            layout.wb_levels = levels;
+        } else if (std.mem.eql(u8, tag, BLOCK_TIFF)) {
+            // A TIFF of its own, with offsets relative to the block
+            layout.ttw = block;
+            if (tiff.read_header(block)) |header| {
+                const parser = tiff.IfdParser.init(block, header.byte_order);
+                if (parser.parse_ifd(header.first_ifd)) |ifd0| {
+                    layout.exif = tiff.read_exif_info(ifd0);
+                } else |_| {}
+            }
+        }
+        pos = start + len;
+    }
+    return layout;
+}
+
+/// Capture settings from the TIFF block of the TTW segment
+pub fn exif_info(data: []const u8) tiff.ExifInfo {
+    const layout = locate(data) catch return .{};
+    return layout.exif;
+}
+
+/// Previews of the TTW block's TIFF, and the thumbnail and preview the
+/// Minolta makernote holds, a bare IFD
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    const layout = locate(data) catch return;
+    const block = layout.ttw orelse return;
+    const header = tiff.read_header(block) orelse return;
+    const parser = tiff.IfdParser.init(block, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return;
+    found.orientation = formats.orientation_from_exif(layout.exif.orientation);
+    formats.preview.scan_tiff(found, ifd0);
+
+    const entry = layout.exif.makernote orelse return;
+    const makernote = parser.parse_ifd(entry.value_offset) catch return;
+    if (makernote.get_bytes(MAKERNOTE_THUMBNAIL)) |thumbnail| found.add_jpeg(thumbnail);
+    const start = makernote.get_u32(MAKERNOTE_PREVIEW_START) orelse return;
+    const length = makernote.get_u32(MAKERNOTE_PREVIEW_LENGTH) orelse return;
+    if (!parser.in_bounds(start, length)) return;
+    // Some bodies store the preview's first byte as 0x00; add_jpeg skips those
+    found.add_jpeg(block[start..][0..length]);
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    if (layout.width == 0 or layout.height == 0) return formats.FormatError.CorruptedData;
+    if (layout.storage != STORAGE_PACKED and layout.storage != STORAGE_UNPACKED) return formats.FormatError.UnsupportedFormat;
+    if (layout.bits < 8 or layout.bits > 16) return formats.FormatError.CorruptedData;
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "Minolta");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Minolta Camera");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .MRW,
+        .width = layout.width,
+        .height = layout.height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(layout.bits))) - 1} ** 4,
+        .raw_data_offset = @intCast(layout.data_offset),
+        .raw_data_size = @intCast(data.len - layout.data_offset),
+        .cfa = if (layout.bayer == BAYER_GBRG) formats.CfaPattern.from_2x2(.{ 1, 2, 0, 1 }) else formats.CfaPattern.rggb,
+        .bits_per_sample = layout.bits,
+        .compression = layout.storage,
+        .allocator = allocator,
+    };
+
+    if (layout.wb_levels) |levels| {
+        var rgb = [3]f32{ 0.0, 0.0, 0.0 };
+        for (levels, 0..) |level, site| {
+            const color = metadata.cfa.color_at(site / 2, site % 2);
+            if (rgb[color] == 0.0) rgb[color] = @floatFromInt(level);
+        }
+        if (rgb[0] > 0.0 and rgb[1] > 0.0 and rgb[2] > 0.0) metadata.white_balance = .{ rgb[0] / rgb[1], 1.0, rgb[2] / rgb[1] };
+    }
+    return metadata;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    const unpack = decoders.unpack;
+    if (metadata.compression == STORAGE_PACKED) {
+        const bits = metadata.bits_per_sample;
+        try unpack.unpack(input, raw, width, height, bits, .msb, unpack.row_bytes(width, bits, .msb));
+    } else {
+        try unpack.unpack(input, raw, width, height, 16, .u16_big, @as(usize, width) * 2);
+    }
+    return raw;
+}
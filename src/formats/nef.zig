@@ -1,10 +1,372 @@
+// src/formats/nef.zig - Nikon NEF/NRW RAW format support
 const std = @import("std");
-const mod = @import("mod.zig");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
 
-pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !mod.RawMetadata {
-    return mod.parse_metadata(data, allocator);
+// Nikon makernote tags
+const NIKON_ISO = 0x0002;
+const NIKON_PREVIEW_IFD = 0x0011;
+const NIKON_WB_RB_LEVELS = 0x000C;
+const NIKON_BLACK_LEVEL = 0x003D;
+const NIKON_NEF_COMPRESSION = 0x0093;
+const NIKON_LINEARIZATION_TABLE = 0x0096;
+
+// TIFF compression value used for Huffman-coded NEF data
+const NIKON_COMPRESSED = 34713;
+
+// NEFCompression values for the TicoRAW based "High Efficiency" modes
+const NEF_HIGH_EFFICIENCY = 13;
+const NEF_HIGH_EFFICIENCY_STAR = 14;
+
+// Huffman trees selected by the linearization table version and bit depth:
+// 16 code-length counts followed by the symbols (low nibble = length,
+// high nibble = shift).
+const nikon_trees = [6][32]u8{
+    // 12-bit lossy
+    .{ 0, 1, 5, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 5, 4, 3, 6, 2, 7, 1, 0, 8, 9, 11, 10, 12, 0, 0, 0 },
+    // 12-bit lossy after split
+    .{ 0, 1, 5, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 0x39, 0x5a, 0x38, 0x27, 0x16, 5, 4, 3, 2, 1, 0, 11, 12, 12, 0, 0 },
+    // 12-bit lossless
+    .{ 0, 1, 4, 2, 3, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 4, 6, 3, 7, 2, 8, 1, 9, 0, 10, 11, 12, 0, 0, 0 },
+    // 14-bit lossy
+    .{ 0, 1, 4, 3, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 5, 6, 4, 7, 8, 3, 9, 2, 1, 0, 10, 11, 12, 13, 14, 0 },
+    // 14-bit lossy after split
+    .{ 0, 1, 5, 1, 1, 1, 1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 8, 0x5c, 0x4b, 0x3a, 0x29, 7, 6, 5, 4, 3, 2, 1, 0, 13, 14, 0 },
+    // 14-bit lossless
+    .{ 0, 1, 4, 2, 2, 3, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 7, 6, 8, 5, 9, 4, 10, 3, 11, 12, 2, 0, 1, 13, 14, 0 },
+};
+
+/// Nikon makernotes are an IFD of their own, usually behind an embedded TIFF header
+const Makernote = struct {
+    parser: tiff.IfdParser,
+    ifd: tiff.Ifd,
+};
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+    makernote: ?Makernote,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return false;
+    return std.mem.startsWith(u8, make, "NIKON");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+
+    // sNEF (YCbCr) files carry no CFA image
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse return formats.FormatError.UnsupportedFormat;
+    const exif = tiff.read_exif_info(ifd0);
+    const makernote = if (exif.makernote) |entry| open_makernote(parser, entry) else null;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = exif,
+        .makernote = makernote,
+    };
+}
+
+fn open_makernote(parser: tiff.IfdParser, entry: tiff.IfdEntry) ?Makernote {
+    const data = parser.data;
+    const start: usize = entry.value_offset;
+    if (!parser.in_bounds(start, 18)) return null;
+    const note = data[start..];
+
+    if (std.mem.eql(u8, note[0..6], "Nikon\x00")) {
+        if (tiff.read_header(note[10..])) |header| {
+            // Type 3: offsets are relative to the embedded TIFF header
+            const sub = tiff.IfdParser.init(note[10..], header.byte_order);
+            const ifd = sub.parse_ifd(header.first_ifd) catch return null;
+            return Makernote{ .parser = sub, .ifd = ifd };
+        }
+        // Type 1 (early Coolpix): IFD right after the 8-byte signature
+        const ifd = parser.parse_ifd(@intCast(start + 8)) catch return null;
+        return Makernote{ .parser = parser, .ifd = ifd };
+    }
+
+    // D1-era makernotes are a bare IFD with file-relative offsets
+    const ifd = parser.parse_ifd(@intCast(start)) catch return null;
+    return Makernote{ .parser = parser, .ifd = ifd };
+}
+
+/// Adds the JPEG behind the makernote's PreviewIFD
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    const header = tiff.read_header(data) orelse return;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return;
+    const entry = tiff.read_exif_info(ifd0).makernote orelse return;
+    const makernote = open_makernote(parser, entry) orelse return;
+    const preview_ifd = makernote.ifd.sub_ifd(NIKON_PREVIEW_IFD) orelse return;
+    formats.preview.scan_ifd(found, preview_ifd);
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 12;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse 1;
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "NIKON");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Nikon Camera");
+    errdefer allocator.free(model);
+
+    const max_value = (@as(u32, 1) << @as(u5, @intCast(bits))) - 1;
+    var metadata = formats.RawMetadata{
+        .format = .NEF,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        // Nikon does not store a colour matrix in the file; the camera database has it
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{max_value} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = compression,
+        .mode = switch (bits) {
+            12 => "12bit",
+            14 => "14bit",
+            else => "",
+        },
+        .allocator = allocator,
+    };
+
+    if (layout.makernote) |mn| {
+        try apply_makernote(&metadata, mn, allocator);
+    }
+
+    return metadata;
+}
+
+fn apply_makernote(metadata: *formats.RawMetadata, mn: Makernote, allocator: std.mem.Allocator) !void {
+    if (metadata.iso == 0) {
+        if (mn.ifd.find(NIKON_ISO)) |entry| metadata.iso = entry.u32_at(mn.parser, 1) orelse 0;
+    }
+
+    // As-shot red and blue multipliers relative to green
+    if (mn.ifd.find(NIKON_WB_RB_LEVELS)) |entry| {
+        const red = entry.f64_at(mn.parser, 0) orelse 0.0;
+        const blue = entry.f64_at(mn.parser, 1) orelse 0.0;
+        if (red > 0.0 and blue > 0.0) {
+            metadata.white_balance = .{ @floatCast(red), 1.0, @floatCast(blue) };
+        }
+    }
+
+    // Per-site black levels, stored at 14-bit precision
+    if (mn.ifd.find(NIKON_BLACK_LEVEL)) |entry| {
+        const shift: u5 = @intCast(14 -| @min(metadata.bits_per_sample, 14));
+        for (0..4) |i| {
+            const level = entry.u32_at(mn.parser, i) orelse break;
+            metadata.black_level[i] = @floatFromInt(level >> shift);
+        }
+    }
+
+    if (metadata.compression == NIKON_COMPRESSED) {
+        if (mn.ifd.find(NIKON_LINEARIZATION_TABLE)) |entry| {
+            const curve = try read_curve(allocator, mn.parser, entry.value_offset, metadata.bits_per_sample);
+            defer allocator.free(curve.table);
+            metadata.white_level = [_]u32{curve.table[curve.max - 1]} ** 4;
+        }
+    }
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+    const bits = metadata.bits_per_sample;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    if (metadata.compression == NIKON_COMPRESSED and !stored_uncompressed(input.len, width, height, bits)) {
+        const mn = layout.makernote orelse return formats.FormatError.CorruptedData;
+        if (mn.ifd.get_u32(NIKON_NEF_COMPRESSION)) |mode| {
+            if (mode == NEF_HIGH_EFFICIENCY or mode == NEF_HIGH_EFFICIENCY_STAR) {
+                return formats.FormatError.UnsupportedFormat;
+            }
+        }
+        const table = mn.ifd.find(NIKON_LINEARIZATION_TABLE) orelse return formats.FormatError.CorruptedData;
+        try decode_compressed(allocator, input, raw, width, height, bits, mn.parser, table.value_offset);
+    } else {
+        try decode_uncompressed(input, raw, width, height, bits, layout.ifd0.parser.byte_order);
+    }
+
+    return raw;
+}
+
+/// Some bodies (D100, early firmware) tag uncompressed data as compressed
+fn stored_uncompressed(size: usize, width: u32, height: u32, bits: u32) bool {
+    const pixels = @as(usize, width) * height;
+    return size == pixels * 2 or size == pixels * bits / 8;
+}
+
+fn decode_uncompressed(input: []const u8, raw: []u16, width: u32, height: u32, bits: u32, byte_order: tiff.ByteOrder) !void {
+    if (height == 0) return;
+    const unpack = decoders.unpack;
+    const stride = input.len / height;
+
+    if (stride >= unpack.row_bytes(width, bits, .u16_little)) {
+        const packing: unpack.Packing = if (byte_order == .little) .u16_little else .u16_big;
+        try unpack.unpack(input, raw, width, height, 16, packing, stride);
+    } else {
+        // Coolpix NRW files pack little-endian, NEF bodies big-endian
+        const packing: unpack.Packing = if (byte_order == .little) .lsb else .msb;
+        try unpack.unpack(input, raw, width, height, bits, packing, stride);
+    }
+}
+
+const Curve = struct {
+    table: []u16,
+    /// Number of meaningful entries in `table`
+    max: u32,
+    /// Row at which lossy files switch to the second Huffman tree (0 = never)
+    split: usize,
+    tree: usize,
+    vpred: [2][2]i32,
+};
+
+/// Reads the NEF linearization table (makernote 0x0096). The table is always
+/// big-endian, regardless of the byte order of the makernote.
+fn read_curve(allocator: std.mem.Allocator, mn: tiff.IfdParser, meta_offset: u32, bits: u32) !Curve {
+    const meta = tiff.IfdParser.init(mn.data, .big);
+    if (!meta.in_bounds(meta_offset, 2)) return formats.FormatError.CorruptedData;
+
+    const table = try allocator.alloc(u16, 0x10000);
+    errdefer allocator.free(table);
+    for (table, 0..) |*value, i| value.* = @intCast(i);
+
+    const ver0 = meta.data[meta_offset];
+    const ver1 = meta.data[meta_offset + 1];
+    var pos: usize = @as(usize, meta_offset) + 2;
+    if (ver0 == 0x49 or ver1 == 0x58) pos += 2110;
+
+    var tree: usize = if (ver0 == 0x46) 2 else 0;
+    if (bits == 14) tree += 3;
+
+    if (!meta.in_bounds(pos, 10)) return formats.FormatError.CorruptedData;
+    var vpred: [2][2]i32 = undefined;
+    for (0..4) |i| vpred[i / 2][i % 2] = meta.read_u16(pos + i * 2);
+    pos += 8;
+
+    var max: u32 = (@as(u32, 1) << @as(u5, @intCast(bits))) & 0x7fff;
+    const csize: u32 = meta.read_u16(pos);
+    pos += 2;
+    const step: u32 = if (csize > 1) max / (csize - 1) else 0;
+
+    var split: usize = 0;
+    if (ver0 == 0x44 and ver1 == 0x20 and step > 0) {
+        // Lossy: sparse knots, linearly interpolated
+        if (!meta.in_bounds(pos, @as(u64, csize) * 2) or (csize - 1) * step >= table.len) {
+            return formats.FormatError.CorruptedData;
+        }
+        for (0..csize) |i| table[i * step] = meta.read_u16(pos + i * 2);
+        for (0..max) |i| {
+            const base = i - i % step;
+            const frac = i % step;
+            const a: usize = table[base];
+            const b: usize = table[base + step];
+            table[i] = @intCast((a * (step - frac) + b * frac) / step);
+        }
+        if (meta.in_bounds(@as(u64, meta_offset) + 562, 2)) split = meta.read_u16(@as(usize, meta_offset) + 562);
+    } else if (ver0 != 0x46 and csize <= 0x4001 and csize > 0) {
+        if (!meta.in_bounds(pos, @as(u64, csize) * 2)) return formats.FormatError.CorruptedData;
+        for (0..csize) |i| table[i] = meta.read_u16(pos + i * 2);
+        max = csize;
+    }
+    while (max > 2 and table[max - 2] == table[max - 1]) max -= 1;
+    // 15- and 16-bit data without a usable table leave no entries
+    if (max == 0) return formats.FormatError.CorruptedData;
+
+    return Curve{ .table = table, .max = max, .split = split, .tree = tree, .vpred = vpred };
 }
 
-pub fn extract_raw_data(data: []const u8, metadata: mod.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
-    return mod.extract_raw_data(data, metadata, allocator);
-}
+fn decode_compressed(
+    allocator: std.mem.Allocator,
+    input: []const u8,
+    raw: []u16,
+    width: u32,
+    height: u32,
+    bits: u32,
+    mn: tiff.IfdParser,
+    meta_offset: u32,
+) !void {
+    const curve = try read_curve(allocator, mn, meta_offset, bits);
+    defer allocator.free(curve.table);
+
+    var tree = curve.tree;
+    var table = try decoders.HuffmanTable.init(nikon_trees[tree][0..16], nikon_trees[tree][16..]);
+    var pump = decoders.BitPumpMsb.init(input);
+    var vpred = curve.vpred;
+    var hpred: [2]i32 = .{ 0, 0 };
+
+    for (0..height) |row| {
+        if (curve.split != 0 and row == curve.split and tree + 1 < nikon_trees.len) {
+            tree += 1;
+            table = try decoders.HuffmanTable.init(nikon_trees[tree][0..16], nikon_trees[tree][16..]);
+        }
+        const out = raw[row * width ..][0..width];
+        for (out, 0..) |*px, col| {
+            const symbol = try table.decode(&pump);
+            const len: u5 = @intCast(symbol & 15);
+            const shl: u5 = @intCast(symbol >> 4);
+            if (shl > len) return formats.FormatError.CorruptedData;
+
+            var diff: i32 = 0;
+            if (len > 0) {
+                const value: i32 = @intCast(pump.get_bits(len - shl));
+                diff = (((value << 1) + 1) << shl) >> 1;
+                if ((diff & (@as(i32, 1) << (len - 1))) == 0) {
+                    diff -= (@as(i32, 1) << len) - @intFromBool(shl == 0);
+                }
+            }
+
+            // Wrapping, as crafted streams can run the predictors past i32;
+            // the table index below is clamped either way
+            if (col < 2) {
+                vpred[row & 1][col] +%= diff;
+                hpred[col] = vpred[row & 1][col];
+            } else {
+                hpred[col & 1] +%= diff;
+            }
+            px.* = curve.table[@intCast(std.math.clamp(hpred[col & 1], 0, 0x3fff))];
+        }
+    }
+}
@@ -0,0 +1,297 @@
+// src/formats/orf.zig - Olympus / OM System ORF RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+// TIFF magics: "IIRO" / "MMOR" and the older "IIRS"
+const ORF_MAGIC = 0x4F52;
+const ORF_MAGIC_RS = 0x5352;
+
+// Main makernote tags; sections are IFD pointers or embedded IFDs
+const OLYMPUS_COLOR_MATRIX = 0x1011;
+const OLYMPUS_BLACK_LEVEL = 0x1012;
+const OLYMPUS_RED_BALANCE = 0x1017;
+const OLYMPUS_BLUE_BALANCE = 0x1018;
+const OLYMPUS_EQUIPMENT = 0x2010;
+const OLYMPUS_CAMERA_SETTINGS = 0x2020;
+const OLYMPUS_IMAGE_PROCESSING = 0x2040;
+
+// Equipment section
+const EQUIPMENT_LENS_MODEL = 0x0203;
+
+// CameraSettings section
+const SETTINGS_PREVIEW_START = 0x0101;
+const SETTINGS_PREVIEW_LENGTH = 0x0102;
+
+// ImageProcessing section
+const PROCESSING_WB_RB_LEVELS = 0x0100;
+const PROCESSING_COLOR_MATRIX = 0x0200;
+const PROCESSING_BLACK_LEVEL_2 = 0x0600;
+const PROCESSING_VALID_BITS = 0x0611;
+const PROCESSING_CROP_LEFT = 0x0612;
+const PROCESSING_CROP_TOP = 0x0613;
+const PROCESSING_CROP_WIDTH = 0x0614;
+const PROCESSING_CROP_HEIGHT = 0x0615;
+
+// Sensor depth when the makernote does not give ValidBits
+const DEFAULT_BITS = 12;
+
+/// How the sensor data is stored
+const Encoding = enum {
+    /// 12-bit samples in 16-bit words; big-endian files align them to the top
+    unpacked,
+    /// 12-bit samples, most significant bit first
+    packed12,
+    /// Olympus' adaptive 12-bit compression
+    compressed,
+};
+
+const Makernote = struct {
+    parser: tiff.IfdParser,
+    ifd: tiff.Ifd,
+
+    /// Follows a section tag. Newer bodies store an IFD pointer, older ones
+    /// embed the section as UNDEFINED data.
+    fn section(self: Makernote, tag: u16) ?tiff.Ifd {
+        const entry = self.ifd.find(tag) orelse return null;
+        const offset = if (entry.data_type == tiff.TYPE_UNDEFINED)
+            entry.value_offset
+        else
+            entry.u32_at(self.parser, 0) orelse return null;
+        return self.parser.parse_ifd(offset) catch null;
+    }
+};
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    exif: tiff.ExifInfo,
+    makernote: ?Makernote,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    return header.magic == ORF_MAGIC or header.magic == ORF_MAGIC_RS;
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    // The sensor data lives in IFD0 itself
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const exif = tiff.read_exif_info(ifd0);
+    const makernote = if (exif.makernote) |entry| open_makernote(parser, entry) else null;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .exif = exif,
+        .makernote = makernote,
+    };
+}
+
+fn open_makernote(parser: tiff.IfdParser, entry: tiff.IfdEntry) ?Makernote {
+    const start: usize = entry.value_offset;
+    if (!parser.in_bounds(start, 16)) return null;
+    const note = parser.data[start..];
+
+    // "OLYMPUS\0" and "OM SYSTEM\0\0\0" notes have their own byte order
+    // mark, and their offsets are relative to the start of the note
+    const ifd_start: u32 = if (std.mem.startsWith(u8, note, "OLYMPUS\x00"))
+        12
+    else if (std.mem.startsWith(u8, note, "OM SYSTEM\x00\x00\x00"))
+        16
+    else
+        0;
+    if (ifd_start > 0) {
+        const mark = note[ifd_start - 4 ..][0..2];
+        const order: tiff.ByteOrder = if (std.mem.eql(u8, mark, "MM")) .big else .little;
+        const sub = tiff.IfdParser.init(note, order);
+        const ifd = sub.parse_ifd(ifd_start) catch return null;
+        return Makernote{ .parser = sub, .ifd = ifd };
+    }
+
+    // Pre-2004 "OLYMP\0" notes: IFD after an 8-byte header, file-relative offsets
+    if (std.mem.startsWith(u8, note, "OLYMP\x00")) {
+        const ifd = parser.parse_ifd(@intCast(start + 8)) catch return null;
+        return Makernote{ .parser = parser, .ifd = ifd };
+    }
+    return null;
+}
+
+/// ORFs tag every layout as uncompressed, so the strip size decides.
+/// High-res shot composites (80 MP on the OM-1) use the same layouts.
+fn classify(size: u64, width: u32, height: u32) Encoding {
+    const pixels = @as(u64, width) * height;
+    if (size >= pixels * 2) return .unpacked;
+    if (size == pixels * 3 / 2) return .packed12;
+    return .compressed;
+}
+
+/// Adds the preview JPEG the CameraSettings section points at; its offset
+/// is relative to the makernote
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    const layout = locate(data) catch return;
+    const makernote = layout.makernote orelse return;
+    const settings = makernote.section(OLYMPUS_CAMERA_SETTINGS) orelse return;
+    const start = settings.get_u32(SETTINGS_PREVIEW_START) orelse return;
+    const length = settings.get_u32(SETTINGS_PREVIEW_LENGTH) orelse return;
+    if (!makernote.parser.in_bounds(start, length)) return;
+    found.add_jpeg(makernote.parser.data[start..][0..length]);
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.ifd0;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const make = try allocator.dupe(u8, layout.exif.make orelse "OLYMPUS");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Olympus Camera");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .ORF,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        // BitsPerSample describes the container; the sensor depth is in the makernote
+        .white_level = [_]u32{(1 << DEFAULT_BITS) - 1} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = DEFAULT_BITS,
+        .compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse 1,
+        .allocator = allocator,
+    };
+    if (layout.makernote) |mn| {
+        try apply_makernote(&metadata, mn, allocator);
+    }
+
+    return metadata;
+}
+
+fn apply_makernote(metadata: *formats.RawMetadata, mn: Makernote, allocator: std.mem.Allocator) !void {
+    if (mn.section(OLYMPUS_EQUIPMENT)) |equipment| {
+        if (equipment.get_string(EQUIPMENT_LENS_MODEL)) |lens| {
+            if (lens.len > 0) metadata.lens = try allocator.dupe(u8, lens);
+        }
+    }
+
+    // Bodies before the E-3 keep these in the main note, later ones in ImageProcessing
+    const processing = mn.section(OLYMPUS_IMAGE_PROCESSING);
+
+    var red: ?u32 = if (mn.ifd.find(OLYMPUS_RED_BALANCE)) |entry| entry.u32_at(mn.parser, 0) else null;
+    var blue: ?u32 = if (mn.ifd.find(OLYMPUS_BLUE_BALANCE)) |entry| entry.u32_at(mn.parser, 0) else null;
+    var matrix = mn.ifd.find(OLYMPUS_COLOR_MATRIX);
+    var black = mn.ifd.find(OLYMPUS_BLACK_LEVEL);
+
+    if (processing) |ip| {
+        if (ip.find(PROCESSING_WB_RB_LEVELS)) |entry| {
+            red = entry.u32_at(ip.parser, 0);
+            blue = entry.u32_at(ip.parser, 1);
+        }
+        if (ip.find(PROCESSING_COLOR_MATRIX)) |entry| matrix = entry;
+        if (ip.find(PROCESSING_BLACK_LEVEL_2)) |entry| black = entry;
+
+        if (ip.get_u32(PROCESSING_VALID_BITS)) |valid| {
+            if (valid >= 10 and valid <= 16) {
+                metadata.bits_per_sample = valid;
+                metadata.white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(valid))) - 1} ** 4;
+            }
+        }
+        metadata.crop = read_crop(ip, metadata.width, metadata.height);
+    }
+
+    // Multipliers are relative to a green of 256
+    if (red != null and blue != null and red.? > 0 and blue.? > 0) {
+        metadata.white_balance = .{
+            @as(f32, @floatFromInt(red.?)) / 256.0,
+            1.0,
+            @as(f32, @floatFromInt(blue.?)) / 256.0,
+        };
+    }
+
+    if (black) |entry| {
+        if (read_values(4, mn.parser, entry)) |levels| metadata.set_black_levels_rggb(levels);
+    }
+
+    // Camera to sRGB, rows summing to 256. Some firmware types the signed
+    // values as SHORT, so they are reinterpreted here.
+    if (matrix) |entry| {
+        if (read_values(9, mn.parser, entry)) |stored| {
+            var rgb_cam: [9]f32 = undefined;
+            for (&rgb_cam, stored) |*v, raw| {
+                const value: i16 = @bitCast(@as(u16, @truncate(raw)));
+                v.* = @as(f32, @floatFromInt(value)) / 256.0;
+            }
+            metadata.color_matrix = formats.xyz_from_srgb_matrix(rgb_cam);
+        }
+    }
+}
+
+fn read_values(comptime n: usize, parser: tiff.IfdParser, entry: tiff.IfdEntry) ?[n]u32 {
+    var values: [n]u32 = undefined;
+    for (&values, 0..) |*v, i| v.* = entry.u32_at(parser, i) orelse return null;
+    return values;
+}
+
+/// The active area from ImageProcessing, if it fits the stored image
+fn read_crop(ip: tiff.Ifd, width: u32, height: u32) ?formats.Rect {
+    const x = ip.get_u32(PROCESSING_CROP_LEFT) orelse return null;
+    const y = ip.get_u32(PROCESSING_CROP_TOP) orelse return null;
+    const crop_width = ip.get_u32(PROCESSING_CROP_WIDTH) orelse return null;
+    const crop_height = ip.get_u32(PROCESSING_CROP_HEIGHT) orelse return null;
+    if (crop_width == 0 or crop_height == 0) return null;
+    if (@as(u64, x) + crop_width > width or @as(u64, y) + crop_height > height) return null;
+    if (x == 0 and y == 0 and crop_width == width and crop_height == height) return null;
+    return formats.Rect{ .x = x, .y = y, .width = crop_width, .height = crop_height };
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    const unpack = decoders.unpack;
+    switch (classify(metadata.raw_data_size, width, height)) {
+        .unpacked => {
+            if (layout.ifd0.parser.byte_order == .little) {
+                try unpack.unpack(input, raw, width, height, 16, .u16_little, @as(usize, width) * 2);
+            } else {
+                try unpack.unpack(input, raw, width, height, 16, .u16_big, @as(usize, width) * 2);
+                const shift: u4 = @intCast(16 - metadata.bits_per_sample);
+                for (raw) |*v| v.* >>= shift;
+            }
+        },
+        .packed12 => try unpack.unpack(input, raw, width, height, 12, .msb, unpack.row_bytes(width, 12, .msb)),
+        .compressed => {
+            if (width % 2 != 0) return formats.FormatError.CorruptedData;
+            try decoders.olympus.decode(input, raw, width, height);
+        },
+    }
+    return raw;
+}
@@ -0,0 +1,214 @@
+// src/formats/pef.zig - Pentax PEF RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+// Pentax makernote tags
+const PENTAX_PREVIEW_LENGTH = 0x0003;
+const PENTAX_PREVIEW_START = 0x0004;
+const PENTAX_CAMERA_TEMPERATURE = 0x0047;
+const PENTAX_BLACK_POINT = 0x0200;
+const PENTAX_WB_RGGB_LEVELS = 0x0201;
+const PENTAX_HUFFMAN_TABLE = 0x0220;
+
+// TIFF compression value of Pentax' Huffman-coded data
+pub const PENTAX_COMPRESSED = 65535;
+const COMPRESSION_NONE = 1;
+
+/// Pentax makernotes are a bare IFD after a short signature
+pub const Makernote = struct {
+    parser: tiff.IfdParser,
+    ifd: tiff.Ifd,
+
+    /// The difference table the compressed data was coded with
+    pub fn huffman_table(self: Makernote) !decoders.pentax.Table {
+        const bytes = self.ifd.get_bytes(PENTAX_HUFFMAN_TABLE) orelse return decoders.pentax.Table.default();
+        const order: std.builtin.Endian = if (self.parser.byte_order == .little) .little else .big;
+        return decoders.pentax.Table.from_makernote(bytes, order);
+    }
+};
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+    makernote: ?Makernote,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return false;
+    if (std.mem.startsWith(u8, make, "PENTAX")) return true;
+    // Bodies made after the Ricoh takeover report Ricoh as the maker
+    const model = ifd0.get_string(tiff.EXIF_MODEL) orelse return false;
+    return std.mem.startsWith(u8, make, "RICOH") and std.mem.startsWith(u8, model, "PENTAX");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse return formats.FormatError.UnsupportedFormat;
+    const exif = tiff.read_exif_info(ifd0);
+    const makernote = if (exif.makernote) |entry| open_makernote(parser, entry.value_offset) else null;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = exif,
+        .makernote = makernote,
+    };
+}
+
+/// Opens a Pentax makernote starting at `start` in `parser`'s data. Also
+/// used for the copy Pentax DNGs keep in DNGPrivateData.
+pub fn open_makernote(parser: tiff.IfdParser, start: usize) ?Makernote {
+    if (!parser.in_bounds(start, 12)) return null;
+    const note = parser.data[start..];
+
+    // "PENTAX \0" + byte order: offsets relative to the note
+    if (std.mem.startsWith(u8, note, "PENTAX \x00")) {
+        const order = byte_order_mark(note[8..10]) orelse return null;
+        const sub = tiff.IfdParser.init(note, order);
+        const ifd = sub.parse_ifd(10) catch return null;
+        return Makernote{ .parser = sub, .ifd = ifd };
+    }
+
+    // "AOC\0" + byte order: offsets relative to the file
+    if (std.mem.startsWith(u8, note, "AOC\x00")) {
+        const order = byte_order_mark(note[4..6]) orelse parser.byte_order;
+        const file = tiff.IfdParser.init(parser.data, order);
+        const ifd = file.parse_ifd(@intCast(start + 6)) catch return null;
+        return Makernote{ .parser = file, .ifd = ifd };
+    }
+    return null;
+}
+
+fn byte_order_mark(mark: []const u8) ?tiff.ByteOrder {
+    if (std.mem.eql(u8, mark, "II")) return .little;
+    if (std.mem.eql(u8, mark, "MM")) return .big;
+    return null;
+}
+
+/// Adds the preview JPEG listed in the makernote
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    const layout = locate(data) catch return;
+    const makernote = layout.makernote orelse return;
+    const start = makernote.ifd.get_u32(PENTAX_PREVIEW_START) orelse return;
+    const length = makernote.ifd.get_u32(PENTAX_PREVIEW_LENGTH) orelse return;
+    // Offsets count from the same base as the makernote's own
+    if (!makernote.parser.in_bounds(start, length)) return;
+    found.add_jpeg(makernote.parser.data[start..][0..length]);
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 12;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE;
+    if (compression != COMPRESSION_NONE and compression != PENTAX_COMPRESSED) return formats.FormatError.UnsupportedFormat;
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "PENTAX");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Pentax Camera");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .PEF,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        // Pentax does not store a colour matrix in the file
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(bits))) - 1} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = compression,
+        .allocator = allocator,
+    };
+
+    if (layout.makernote) |mn| apply_makernote(&metadata, mn);
+    return metadata;
+}
+
+fn apply_makernote(metadata: *formats.RawMetadata, mn: Makernote) void {
+    // Both lists are in R, G, G, B order
+    if (read_levels(mn, PENTAX_WB_RGGB_LEVELS)) |wb| {
+        if (wb[0] > 0 and wb[1] > 0 and wb[3] > 0) {
+            const green: f32 = @floatFromInt(wb[1]);
+            metadata.white_balance = .{
+                @as(f32, @floatFromInt(wb[0])) / green,
+                1.0,
+                @as(f32, @floatFromInt(wb[3])) / green,
+            };
+        }
+    }
+    if (read_levels(mn, PENTAX_BLACK_POINT)) |black| metadata.set_black_levels_rggb(black);
+    // A signed byte of whole degrees
+    if (mn.ifd.find(PENTAX_CAMERA_TEMPERATURE)) |entry| {
+        if (entry.i32_at(mn.parser, 0)) |celsius| metadata.sensor_temperature = @floatFromInt(celsius);
+    }
+}
+
+fn read_levels(mn: Makernote, tag: u16) ?[4]u32 {
+    const entry = mn.ifd.find(tag) orelse return null;
+    var levels: [4]u32 = undefined;
+    for (&levels, 0..) |*level, i| level.* = entry.u32_at(mn.parser, i) orelse return null;
+    return levels;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    if (metadata.compression == PENTAX_COMPRESSED) {
+        const table = if (layout.makernote) |mn| try mn.huffman_table() else decoders.pentax.Table.default();
+        try decoders.pentax.decode(input, raw, width, height, &table);
+        return raw;
+    }
+
+    // Uncompressed PEFs are packed big-endian, or 16-bit words in the file's order
+    const unpack = decoders.unpack;
+    if (input.len >= @as(usize, width) * height * 2) {
+        const packing: unpack.Packing = if (layout.ifd0.parser.byte_order == .little) .u16_little else .u16_big;
+        try unpack.unpack(input, raw, width, height, 16, packing, @as(usize, width) * 2);
+    } else {
+        const bits = metadata.bits_per_sample;
+        try unpack.unpack(input, raw, width, height, bits, .msb, unpack.row_bytes(width, bits, .msb));
+    }
+    return raw;
+}
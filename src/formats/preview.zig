@@ -0,0 +1,189 @@
+// src/formats/preview.zig - Embedded previews and thumbnails
+const std = @import("std");
+const root = @import("../root.zig");
+const tiff = @import("tiff/mod.zig");
+
+const TAG_JPEG_OFFSET = 0x0201; // JPEGInterchangeFormat
+const TAG_JPEG_LENGTH = 0x0202; // JPEGInterchangeFormatLength
+
+const COMPRESSION_NONE = 1;
+const COMPRESSION_OLD_JPEG = 6;
+const COMPRESSION_JPEG = 7;
+const PHOTOMETRIC_RGB = 2;
+const PHOTOMETRIC_YCBCR = 6;
+
+/// Files rarely carry more than four; the rest are dropped
+pub const MAX_PREVIEWS = 16;
+
+pub const Kind = enum(u32) {
+    /// A complete JPEG stream
+    jpeg = 0,
+    /// Uncompressed 8-bit RGB, row-major without padding
+    rgb8 = 1,
+};
+
+pub const Preview = struct {
+    offset: usize,
+    length: usize,
+    width: u32,
+    height: u32,
+    kind: Kind,
+};
+
+/// The previews found in a file, in the order the file stores them
+pub const Previews = struct {
+    data: []const u8,
+    items: [MAX_PREVIEWS]Preview = undefined,
+    len: usize = 0,
+    /// Orientation of the file, which its previews share
+    orientation: root.ZrRawOrientation = .Normal,
+
+    pub fn init(data: []const u8) Previews {
+        return Previews{ .data = data };
+    }
+
+    pub fn slice(self: *const Previews) []const Preview {
+        return self.items[0..self.len];
+    }
+
+    /// Adds the JPEG in `bytes`, which must lie within the file. Streams
+    /// without a baseline or progressive frame header are skipped.
+    pub fn add_jpeg(self: *Previews, bytes: []const u8) void {
+        const size = jpeg_size(bytes) orelse return;
+        self.add(.{
+            .offset = self.offset_of(bytes),
+            .length = bytes.len,
+            .width = size[0],
+            .height = size[1],
+            .kind = .jpeg,
+        });
+    }
+
+    /// Adds an uncompressed RGB image starting at `bytes`
+    pub fn add_rgb(self: *Previews, bytes: []const u8, width: u32, height: u32) void {
+        const length = @as(u64, width) * height * 3;
+        if (length == 0 or length > bytes.len) return;
+        self.add(.{
+            .offset = self.offset_of(bytes),
+            .length = @intCast(length),
+            .width = width,
+            .height = height,
+            .kind = .rgb8,
+        });
+    }
+
+    fn add(self: *Previews, preview: Preview) void {
+        if (self.len == MAX_PREVIEWS) return;
+        // Makernotes often point at an image the TIFF structure lists too
+        for (self.slice()) |existing| {
+            if (existing.offset == preview.offset) return;
+        }
+        self.items[self.len] = preview;
+        self.len += 1;
+    }
+
+    fn offset_of(self: *const Previews, bytes: []const u8) usize {
+        return @intFromPtr(bytes.ptr) - @intFromPtr(self.data.ptr);
+    }
+};
+
+/// Width and height from the frame header of a JPEG stream
+pub fn jpeg_size(jpeg: []const u8) ?[2]u32 {
+    if (jpeg.len < 4 or jpeg[0] != 0xFF or jpeg[1] != 0xD8) return null;
+    var pos: usize = 2;
+    while (pos + 4 <= jpeg.len) {
+        if (jpeg[pos] != 0xFF) return null;
+        const marker = jpeg[pos + 1];
+        // Fill bytes may pad any marker
+        if (marker == 0xFF) {
+            pos += 1;
+            continue;
+        }
+        if (marker == 0xD8 or marker == 0x01 or (marker >= 0xD0 and marker <= 0xD7)) {
+            pos += 2;
+            continue;
+        }
+        if (marker == 0xDA or marker == 0xD9) return null;
+        const length = std.mem.readInt(u16, jpeg[pos + 2 ..][0..2], .big);
+        // SOF0-SOF2; lossless and arithmetic-coded frames are no previews
+        if (marker >= 0xC0 and marker <= 0xC2) {
+            if (length < 8 or pos + 9 > jpeg.len) return null;
+            const height = std.mem.readInt(u16, jpeg[pos + 5 ..][0..2], .big);
+            const width = std.mem.readInt(u16, jpeg[pos + 7 ..][0..2], .big);
+            if (width == 0 or height == 0) return null;
+            return .{ width, height };
+        }
+        if (length < 2) return null;
+        pos += 2 + @as(usize, length);
+    }
+    return null;
+}
+
+/// Collects the previews of IFD0, the IFDs chained to it and their SubIFDs
+pub fn scan_tiff(found: *Previews, ifd0: tiff.Ifd) void {
+    var current: ?tiff.Ifd = ifd0;
+    var chained: u32 = 0;
+    while (current) |ifd| : (chained += 1) {
+        if (chained >= 16) break;
+        scan_ifd(found, ifd);
+        if (ifd.find(tiff.TAG_SUB_IFDS)) |entry| {
+            for (0..@min(entry.count, 16)) |i| {
+                const offset = entry.u32_at(ifd.parser, i) orelse continue;
+                if (offset == 0) continue;
+                const sub = ifd.parser.parse_ifd(offset) catch continue;
+                scan_ifd(found, sub);
+            }
+        }
+        current = ifd.next();
+    }
+}
+
+/// Adds the JPEG an IFD points at with JPEGInterchangeFormat, or the image
+/// in its strips when that is a JPEG or 8-bit RGB
+pub fn scan_ifd(found: *Previews, ifd: tiff.Ifd) void {
+    const parser = ifd.parser;
+    if (ifd.get_u32(TAG_JPEG_OFFSET)) |offset| {
+        const length = ifd.get_u32(TAG_JPEG_LENGTH) orelse 0;
+        if (parser.in_bounds(offset, length)) found.add_jpeg(parser.data[offset..][0..length]);
+    }
+
+    const strips = ifd.find(tiff.TAG_STRIP_OFFSETS) orelse return;
+    const start = strips.u32_at(parser, 0) orelse return;
+    // Strips of a preview follow one another
+    const length = ifd.strip_bytes();
+    if (!parser.in_bounds(start, length)) return;
+    const bytes = parser.data[start..][0..@intCast(length)];
+
+    const photometric = ifd.get_u32(tiff.TAG_PHOTOMETRIC) orelse 0;
+    switch (ifd.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE) {
+        COMPRESSION_OLD_JPEG, COMPRESSION_JPEG => {
+            if (photometric == PHOTOMETRIC_RGB or photometric == PHOTOMETRIC_YCBCR or photometric == 0) {
+                found.add_jpeg(bytes);
+            }
+        },
+        COMPRESSION_NONE => {
+            if (photometric != PHOTOMETRIC_RGB) return;
+            if ((ifd.get_u32(tiff.TAG_SAMPLES_PER_PIXEL) orelse 1) != 3) return;
+            if ((ifd.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 1) != 8) return;
+            const planar = ifd.get_u32(tiff.TAG_PLANAR_CONFIGURATION) orelse 1;
+            if (planar != 1) return;
+            const width = ifd.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return;
+            const height = ifd.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return;
+            found.add_rgb(bytes, width, height);
+        },
+        else => {},
+    }
+}
+
+test "jpeg size comes from the frame header" {
+    const jpeg = [_]u8{
+        0xFF, 0xD8,
+        0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00,
+        0xFF, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x60, 0x00, 0xA0, 0x01, 0x01, 0x11, 0x00,
+    };
+    try std.testing.expectEqual([2]u32{ 160, 96 }, jpeg_size(&jpeg).?);
+    // Lossless frames are sensor data, not previews
+    var lossless = jpeg;
+    lossless[10] = 0xC3;
+    try std.testing.expect(jpeg_size(&lossless) == null);
+}
@@ -1,10 +1,419 @@
+// src/formats/raf.zig - Fujifilm RAF RAW format support
 const std = @import("std");
-const mod = @import("mod.zig");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
 
-pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !mod.RawMetadata {
-    return mod.parse_metadata(data, allocator);
+const RAF_MAGIC = "FUJIFILMCCD-RAW ";
+
+// Big-endian header: camera model, then offset/length pairs of the
+// embedded JPEG, the RAF directory and the CFA section
+const HEADER_MODEL = 0x1C;
+const HEADER_JPEG = 0x54;
+const HEADER_DIRECTORY = 0x5C;
+const HEADER_CFA = 0x64;
+const HEADER_SIZE = 0x6C;
+
+// RAF directory records
+const RAF_RAW_FULL_SIZE = 0x0100;
+const RAF_CROP_TOP_LEFT = 0x0110;
+const RAF_CROPPED_SIZE = 0x0111;
+const RAF_LAYOUT = 0x0130;
+const RAF_XTRANS_LAYOUT = 0x0131;
+const RAF_WB_GRGB_LEVELS = 0x2FF0;
+
+// Tags of the TIFF-like IFD that newer bodies put in the CFA section
+const FUJI_RAW_IFD = 0xF000;
+const FUJI_RAW_WIDTH = 0xF001;
+const FUJI_RAW_HEIGHT = 0xF002;
+const FUJI_BITS_PER_SAMPLE = 0xF003;
+const FUJI_STRIP_OFFSETS = 0xF007;
+const FUJI_STRIP_BYTE_COUNTS = 0xF008;
+const FUJI_BLACK_LEVEL = 0xF00A;
+const FUJI_WB_GRB_LEVELS = 0xF00E;
+
+/// The records of the RAF directory this reader uses
+const Directory = struct {
+    /// Height and width of the stored image
+    full_size: ?[2]u32 = null,
+    crop_origin: ?[2]u32 = null,
+    crop_size: ?[2]u32 = null,
+    /// Set for SuperCCD sensors, whose data is rotated by 45 degrees
+    rotated: bool = false,
+    xtrans: ?[36]u8 = null,
+    wb_grgb: ?[4]u16 = null,
+};
+
+const Layout = struct {
+    exif: tiff.ExifInfo,
+    exif_ifd0: ?tiff.Ifd,
+    directory: Directory,
+    /// Offset of the CFA section in the file
+    section_offset: usize,
+    /// The 0xF000 IFD, absent in older files that store bare samples
+    raw_ifd: ?tiff.Ifd,
+};
+
+/// How the sensor data is stored
+const Encoding = enum {
+    /// One sample per 16-bit word in the section's byte order
+    unpacked,
+    /// 14-bit samples packed into byte-swapped 32-bit words
+    packed14,
+    /// Fuji's own lossless or lossy compression
+    compressed,
+};
+
+pub fn detect(data: []const u8) bool {
+    return data.len >= HEADER_SIZE and std.mem.startsWith(u8, data, RAF_MAGIC);
+}
+
+fn read_pair(data: []const u8, offset: usize) [2]u32 {
+    return .{
+        std.mem.readInt(u32, data[offset..][0..4], .big),
+        std.mem.readInt(u32, data[offset + 4 ..][0..4], .big),
+    };
+}
+
+fn locate(data: []const u8) !Layout {
+    if (!detect(data)) return formats.FormatError.InvalidHeader;
+
+    var layout = Layout{
+        .exif = .{},
+        .exif_ifd0 = null,
+        .directory = .{},
+        .section_offset = 0,
+        .raw_ifd = null,
+    };
+
+    // Capture settings live in the EXIF block of the embedded JPEG
+    const jpeg = read_pair(data, HEADER_JPEG);
+    if (jpeg[0] < data.len) {
+        const end = @min(data.len, @as(usize, jpeg[0]) + jpeg[1]);
+        if (jpeg_exif(data[jpeg[0]..end])) |block| {
+            if (tiff.read_header(block)) |header| {
+                const parser = tiff.IfdParser.init(block, header.byte_order);
+                if (parser.parse_ifd(header.first_ifd)) |ifd0| {
+                    layout.exif_ifd0 = ifd0;
+                    layout.exif = tiff.read_exif_info(ifd0);
+                } else |_| {}
+            }
+        }
+    }
+
+    const directory = read_pair(data, HEADER_DIRECTORY);
+    if (directory[0] >= data.len) return formats.FormatError.CorruptedData;
+    layout.directory = read_directory(data[directory[0]..@min(data.len, @as(usize, directory[0]) + directory[1])]);
+
+    const section = read_pair(data, HEADER_CFA);
+    if (section[0] == 0 or section[0] >= data.len) return formats.FormatError.CorruptedData;
+    layout.section_offset = section[0];
+    const cfa = data[section[0]..];
+    if (tiff.read_header(cfa)) |header| {
+        if (header.magic == tiff.TIFF_MAGIC) {
+            // Offsets in this IFD are relative to the section
+            const parser = tiff.IfdParser.init(cfa, header.byte_order);
+            if (parser.parse_ifd(header.first_ifd)) |ifd0| {
+                layout.raw_ifd = ifd0.sub_ifd(FUJI_RAW_IFD);
+            } else |_| {}
+        }
+    }
+    return layout;
+}
+
+/// Finds the TIFF structure inside the APP1 EXIF segment of a JPEG
+fn jpeg_exif(jpeg: []const u8) ?[]const u8 {
+    if (jpeg.len < 4 or jpeg[0] != 0xFF or jpeg[1] != 0xD8) return null;
+    var pos: usize = 2;
+    while (pos + 4 <= jpeg.len and jpeg[pos] == 0xFF) {
+        const marker = jpeg[pos + 1];
+        const length = std.mem.readInt(u16, jpeg[pos + 2 ..][0..2], .big);
+        // Metadata segments all precede the scan
+        if (marker == 0xDA or length < 2) return null;
+        const body = jpeg[pos + 4 .. @min(jpeg.len, pos + 2 + length)];
+        if (marker == 0xE1 and std.mem.startsWith(u8, body, "Exif\x00\x00")) return body[6..];
+        pos += 2 + @as(usize, length);
+    }
+    return null;
+}
+
+/// Parses the big-endian record list: a count, then tag, size and payload
+fn read_directory(block: []const u8) Directory {
+    var directory = Directory{};
+    if (block.len < 4) return directory;
+    const count = std.mem.readInt(u32, block[0..4], .big);
+    var pos: usize = 4;
+    var i: u32 = 0;
+    while (i < count and pos + 4 <= block.len) : (i += 1) {
+        const tag = std.mem.readInt(u16, block[pos..][0..2], .big);
+        const size = std.mem.readInt(u16, block[pos + 2 ..][0..2], .big);
+        pos += 4;
+        if (pos + size > block.len) break;
+        const payload = block[pos..][0..size];
+        pos += size;
+
+        switch (tag) {
+            RAF_RAW_FULL_SIZE, RAF_CROP_TOP_LEFT, RAF_CROPPED_SIZE => {
+                if (size < 4) continue;
+                const pair = [2]u32{
+                    std.mem.readInt(u16, payload[0..2], .big),
+                    std.mem.readInt(u16, payload[2..4], .big),
+                };
+                switch (tag) {
+                    RAF_RAW_FULL_SIZE => directory.full_size = pair,
+                    RAF_CROP_TOP_LEFT => directory.crop_origin = pair,
+                    else => directory.crop_size = pair,
+                }
+            },
+            RAF_LAYOUT => {
+                if (size >= 2) directory.rotated = (payload[1] & 8) == 0;
+            },
+            RAF_XTRANS_LAYOUT => {
+                if (size < 36) continue;
+                // Stored last site first
+                var colors: [36]u8 = undefined;
+                for (&colors, 0..) |*color, c| color.* = payload[35 - c] & 3;
+                directory.xtrans = colors;
+            },
+            RAF_WB_GRGB_LEVELS => {
+                if (size < 8) continue;
+                var levels: [4]u16 = undefined;
+                for (&levels, 0..) |*level, c| level.* = std.mem.readInt(u16, payload[c * 2 ..][0..2], .big);
+                directory.wb_grgb = levels;
+            },
+            else => {},
+        }
+    }
+    return directory;
 }
 
-pub fn extract_raw_data(data: []const u8, metadata: mod.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
-    return mod.extract_raw_data(data, metadata, allocator);
-}
+fn cfa_pattern(layout: Layout) formats.CfaPattern {
+    if (layout.directory.xtrans) |colors| {
+        var pattern = formats.CfaPattern{ .width = 6, .height = 6 };
+        // Values of 3 are not expected; treat them as green like other non-RGB sites
+        for (&pattern.colors, colors) |*dst, color| dst.* = if (color <= 2) color else 1;
+        return pattern;
+    }
+    if (layout.exif_ifd0) |ifd0| {
+        if (ifd0.sub_ifd(tiff.TAG_EXIF_IFD)) |exif| {
+            if (formats.cfa_from_ifd(exif)) |pattern| return pattern;
+        }
+    }
+    return formats.CfaPattern.rggb;
+}
+
+/// Capture settings from the EXIF block of the embedded JPEG
+pub fn exif_info(data: []const u8) tiff.ExifInfo {
+    const layout = locate(data) catch return .{};
+    return layout.exif;
+}
+
+/// Adds the JPEG the header points at and the thumbnail in its EXIF block
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    if (!detect(data)) return;
+    const jpeg = read_pair(data, HEADER_JPEG);
+    if (jpeg[0] >= data.len) return;
+    const bytes = data[jpeg[0]..@min(data.len, @as(usize, jpeg[0]) + jpeg[1])];
+    found.add_jpeg(bytes);
+
+    const block = jpeg_exif(bytes) orelse return;
+    const header = tiff.read_header(block) orelse return;
+    const parser = tiff.IfdParser.init(block, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return;
+    found.orientation = formats.orientation_from_exif(tiff.read_exif_info(ifd0).orientation);
+    formats.preview.scan_tiff(found, ifd0);
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const directory = layout.directory;
+    // SuperCCD data needs a 45 degree rotation before demosaicing
+    if (directory.rotated) return formats.FormatError.UnsupportedFormat;
+
+    var width: u32 = 0;
+    var height: u32 = 0;
+    var bits: u32 = 14;
+    var data_offset: usize = 0;
+    var data_size: usize = 0;
+    const section_size = data.len - layout.section_offset;
+    if (layout.raw_ifd) |raw| {
+        width = raw.get_u32(FUJI_RAW_WIDTH) orelse 0;
+        height = raw.get_u32(FUJI_RAW_HEIGHT) orelse 0;
+        bits = raw.get_u32(FUJI_BITS_PER_SAMPLE) orelse bits;
+        data_offset = raw.get_u32(FUJI_STRIP_OFFSETS) orelse 0;
+        data_size = raw.get_u32(FUJI_STRIP_BYTE_COUNTS) orelse section_size -| data_offset;
+    } else if (directory.full_size) |size| {
+        // Older bodies store bare big-endian samples at the start of the section
+        height = size[0];
+        width = size[1];
+        data_size = read_pair(data, HEADER_CFA)[1];
+    }
+    if (width == 0 or height == 0) return formats.FormatError.CorruptedData;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    if (data_offset >= section_size) return formats.FormatError.CorruptedData;
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "FUJIFILM");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse header_model(data));
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .RAF,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        // Fujifilm does not store a colour matrix in the file
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(bits))) - 1} ** 4,
+        .raw_data_offset = @intCast(layout.section_offset + data_offset),
+        .raw_data_size = @intCast(@min(data_size, std.math.maxInt(u32))),
+        .cfa = cfa_pattern(layout),
+        .bits_per_sample = bits,
+        .allocator = allocator,
+    };
+
+    if (directory.crop_origin) |origin| {
+        if (directory.crop_size) |size| {
+            const top = origin[0];
+            const left = origin[1];
+            if (size[0] > 0 and size[1] > 0 and top + size[0] <= height and left + size[1] <= width) {
+                metadata.crop = .{ .x = left, .y = top, .width = size[1], .height = size[0] };
+            }
+        }
+    }
+
+    apply_levels(&metadata, layout);
+    return metadata;
+}
+
+/// The model name from the RAF header, for files without a usable JPEG
+fn header_model(data: []const u8) []const u8 {
+    const field = data[HEADER_MODEL..][0..32];
+    const len = std.mem.indexOfScalar(u8, field, 0) orelse field.len;
+    return if (len > 0) field[0..len] else "Unknown Fujifilm Camera";
+}
+
+fn apply_levels(metadata: *formats.RawMetadata, layout: Layout) void {
+    // As-shot levels from the RAF directory, else from the raw IFD
+    var levels: ?[3]u32 = null;
+    if (layout.directory.wb_grgb) |grgb| {
+        levels = .{ grgb[1], grgb[0], grgb[3] };
+    } else if (layout.raw_ifd) |raw| {
+        if (raw.find(FUJI_WB_GRB_LEVELS)) |entry| {
+            const g = entry.u32_at(raw.parser, 0) orelse 0;
+            const r = entry.u32_at(raw.parser, 1) orelse 0;
+            const b = entry.u32_at(raw.parser, 2) orelse 0;
+            levels = .{ r, g, b };
+        }
+    }
+    if (levels) |rgb| {
+        if (rgb[0] > 0 and rgb[1] > 0 and rgb[2] > 0) {
+            const green: f32 = @floatFromInt(rgb[1]);
+            metadata.white_balance = .{
+                @as(f32, @floatFromInt(rgb[0])) / green,
+                1.0,
+                @as(f32, @floatFromInt(rgb[2])) / green,
+            };
+        }
+    }
+
+    // One level per CFA site (36 on X-Trans); the sites rarely differ, so
+    // their mean is used everywhere
+    if (layout.raw_ifd) |raw| {
+        if (raw.find(FUJI_BLACK_LEVEL)) |entry| {
+            var sum: u64 = 0;
+            var count: u32 = 0;
+            for (0..entry.count) |i| {
+                sum += entry.u32_at(raw.parser, i) orelse break;
+                count += 1;
+            }
+            if (count > 0) {
+                const mean = @as(f32, @floatFromInt(sum)) / @as(f32, @floatFromInt(count));
+                metadata.black_level = [_]f32{mean} ** 4;
+            }
+        }
+    }
+}
+
+fn classify(input: []const u8, width: u32, height: u32, bits: u32) !Encoding {
+    if (decoders.fuji_compressed.read_header(input) != null) return .compressed;
+    const pixels = @as(u64, width) * height;
+    if (input.len >= pixels * 2) return .unpacked;
+    if (bits == 14 and input.len >= pixels * 7 / 4) return .packed14;
+    return formats.FormatError.UnsupportedFormat;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    const input = data[offset..@min(data.len, offset + metadata.raw_data_size)];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    switch (try classify(input, width, height, metadata.bits_per_sample)) {
+        .compressed => {
+            const header = decoders.fuji_compressed.read_header(input).?;
+            if (header.width != width or header.height != height) return formats.FormatError.CorruptedData;
+            var colors: [6][6]u8 = undefined;
+            for (0..6) |row| {
+                for (0..6) |col| colors[row][col] = metadata.cfa.color_at(row, col);
+            }
+            try decoders.fuji_compressed.decode(allocator, input, header, &colors, raw);
+        },
+        .unpacked => {
+            // The section's TIFF header gives the byte order; bare sections are big-endian
+            const packing: decoders.unpack.Packing = if (layout.raw_ifd) |ifd|
+                (if (ifd.parser.byte_order == .little) .u16_little else .u16_big)
+            else
+                .u16_big;
+            try decoders.unpack.unpack(input, raw, width, height, 16, packing, @as(usize, width) * 2);
+        },
+        .packed14 => try decode_packed14(allocator, input, raw, width, height),
+    }
+    return raw;
+}
+
+/// 14-bit samples, most significant bit first, in 32-bit words whose bytes
+/// are stored in reverse order
+fn decode_packed14(allocator: std.mem.Allocator, input: []const u8, raw: []u16, width: u32, height: u32) !void {
+    const stride = @as(usize, width) * 14 / 8;
+    const size = stride * height;
+    if (input.len < size) return formats.FormatError.TruncatedFile;
+
+    const swapped = try allocator.dupe(u8, input[0..size]);
+    defer allocator.free(swapped);
+    var i: usize = 0;
+    while (i + 4 <= swapped.len) : (i += 4) std.mem.reverse(u8, swapped[i..][0..4]);
+    try decoders.unpack.unpack(swapped, raw, width, height, 14, .msb, stride);
+}
+
+test "directory records are read big-endian" {
+    const block = [_]u8{
+        0, 0, 0, 2,
+        0x01, 0x10, 0, 4, 0, 2, 0, 4,
+        0x2F, 0xF0, 0, 8, 1, 0, 2, 0, 1, 0, 0, 0x80,
+    };
+    const directory = read_directory(&block);
+    try std.testing.expectEqual([2]u32{ 2, 4 }, directory.crop_origin.?);
+    try std.testing.expectEqual([4]u16{ 256, 512, 256, 128 }, directory.wb_grgb.?);
+}
@@ -4,16 +4,20 @@ const root = @import("../root.zig");
 const formats = @import("mod.zig");
 const tiff = @import("tiff/mod.zig");
 
-// RW2 specific TIFF tags
+// RW2 IFD0 tags
 const PANASONIC_RAW_VERSION = 0x0001;
-const PANASONIC_RAW_DATA = 0x0118; // StripOffsets
-const PANASONIC_RAW_DATA_SIZE = 0x0117; // StripByteCounts
-const PANASONIC_IMAGE_WIDTH = 0x0100;
-const PANASONIC_IMAGE_LENGTH = 0x0101;
-const PANASONIC_BITS_PER_SAMPLE = 0x0102;
-const PANASONIC_COMPRESSION = 0x0103;
-const PANASONIC_BLACK_LEVEL = 0x0201;
-const PANASONIC_WHITE_LEVEL = 0x0202;
+const PANASONIC_SENSOR_WIDTH = 0x0002;
+const PANASONIC_SENSOR_HEIGHT = 0x0003;
+const PANASONIC_SENSOR_TOP_BORDER = 0x0004;
+const PANASONIC_SENSOR_LEFT_BORDER = 0x0005;
+const PANASONIC_SENSOR_BOTTOM_BORDER = 0x0006;
+const PANASONIC_SENSOR_RIGHT_BORDER = 0x0007;
+const PANASONIC_CFA_PATTERN = 0x0009;
+const PANASONIC_BITS_PER_SAMPLE = 0x000A;
+const PANASONIC_ISO = 0x0017;
+const PANASONIC_BLACK_LEVEL_RED = 0x001C; // Green and blue follow
+const PANASONIC_WB_RED_LEVEL = 0x0024; // Green and blue follow
+const PANASONIC_JPG_FROM_RAW = 0x002E;
 
 // RW2 magic bytes - it's a TIFF variant with specific structure
 const RW2_MAGIC_LE: [4]u8 = .{ 0x49, 0x49, 0x55, 0x00 }; // "II\x55\x00" - little endian
@@ -28,6 +32,15 @@ pub fn detect(data: []const u8) bool {
         std.mem.eql(u8, header, &RW2_MAGIC_BE);
 }
 
+/// Adds the full-size JPEG embedded in IFD0
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    const header = tiff.read_header(data) orelse return;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return;
+    const jpeg = ifd0.get_bytes(PANASONIC_JPG_FROM_RAW) orelse return;
+    found.add_jpeg(jpeg);
+}
+
 pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
     if (!detect(data)) {
         return formats.FormatError.UnsupportedFormat;
@@ -52,12 +65,12 @@ pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.R
         return formats.FormatError.CorruptedData;
     }
 
-    // For now, create a basic metadata structure with reasonable defaults
-    // This is a simplified implementation that can be expanded
+    // Defaults for whatever IFD0 leaves out; the colour matrix comes from
+    // the camera database
     var metadata = formats.RawMetadata{
         .format = .RW2,
-        .width = 4048, // Common RW2 width, will be overridden when we parse IFD
-        .height = 3024, // Common RW2 height, will be overridden when we parse IFD
+        .width = 4048,
+        .height = 3024,
         .orientation = .Normal,
         .make = try allocator.dupe(u8, "Panasonic"),
         .model = try allocator.dupe(u8, "Unknown Panasonic Camera"),
@@ -65,43 +78,82 @@ pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.R
         .shutter_speed = 1.0 / 60.0,
         .aperture = 2.8,
         .focal_length = 50.0,
-        .color_matrix = [_]f32{
-            1.7, -0.6, -0.1, // Red row
-            -0.4, 1.5,  -0.1, // Green row
-            0.0,  -0.4, 1.4, // Blue row
-        },
+        .color_matrix = [_]f32{0.0} ** 9,
         .white_balance = [_]f32{ 2.0, 1.0, 1.5 }, // Typical daylight WB for Panasonic
-        .black_level = [_]f32{ 16.0, 16.0, 16.0, 16.0 },
-        .white_level = [_]u32{ 4095, 4095, 4095, 4095 }, // 12-bit typical
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{4095} ** 4,
         .raw_data_offset = 0, // Will be updated when we find the raw data
         .raw_data_size = 0, // Will be updated when we find the raw data
+        .bits_per_sample = 12,
         .allocator = allocator,
     };
+    errdefer metadata.deinit();
 
-    // Try to extract basic information from the IFD
-    // This is a simplified approach - in a full implementation, we'd parse all the TIFF tags
-    if (try parse_basic_info(data, parser, first_ifd_offset, &metadata)) {
-        // Successfully parsed additional info
-    }
-
+    try parse_basic_info(parser, first_ifd_offset, &metadata);
     return metadata;
 }
 
-fn parse_basic_info(data: []const u8, parser: tiff.IfdParser, ifd_offset: u32, metadata: *formats.RawMetadata) !bool {
-    _ = data;
-    _ = parser;
-    _ = ifd_offset;
-    _ = metadata;
-
-    // TODO: Implement actual IFD parsing to extract:
-    // - Image dimensions from PANASONIC_IMAGE_WIDTH and PANASONIC_IMAGE_LENGTH
-    // - Raw data offset from PANASONIC_RAW_DATA
-    // - Raw data size from PANASONIC_RAW_DATA_SIZE
-    // - Camera make/model from EXIF_MAKE and EXIF_MODEL
-    // - Black/white levels from PANASONIC_BLACK_LEVEL and PANASONIC_WHITE_LEVEL
-
-    // For now, return false to indicate we used defaults
-    return false;
+/// Reads the make, model, sensor layout, levels and white balance of IFD0
+fn parse_basic_info(parser: tiff.IfdParser, ifd_offset: u32, metadata: *formats.RawMetadata) !void {
+    const ifd0 = parser.parse_ifd(ifd_offset) catch return;
+    const allocator = metadata.allocator;
+    const exif = tiff.read_exif_info(ifd0);
+
+    if (exif.make) |make| {
+        const owned = try allocator.dupe(u8, make);
+        allocator.free(metadata.make);
+        metadata.make = owned;
+    }
+    if (exif.model) |model| {
+        const owned = try allocator.dupe(u8, model);
+        allocator.free(metadata.model);
+        metadata.model = owned;
+    }
+    metadata.orientation = formats.orientation_from_exif(exif.orientation);
+    if (exif.iso != 0) {
+        metadata.iso = exif.iso;
+    } else if (ifd0.get_u32(PANASONIC_ISO)) |iso| metadata.iso = iso;
+    if (exif.exposure_time) |r| metadata.shutter_speed = r.to_f32();
+    if (exif.f_number) |r| metadata.aperture = r.to_f32();
+    metadata.exposure_time = exif.exposure_time;
+    metadata.f_number = exif.f_number;
+    if (exif.focal_length) |r| metadata.focal_length = r.to_f32();
+
+    if (ifd0.get_u32(PANASONIC_SENSOR_WIDTH)) |width| metadata.width = width;
+    if (ifd0.get_u32(PANASONIC_SENSOR_HEIGHT)) |height| metadata.height = height;
+    const top = ifd0.get_u32(PANASONIC_SENSOR_TOP_BORDER) orelse 0;
+    const left = ifd0.get_u32(PANASONIC_SENSOR_LEFT_BORDER) orelse 0;
+    const bottom = ifd0.get_u32(PANASONIC_SENSOR_BOTTOM_BORDER) orelse metadata.height;
+    const right = ifd0.get_u32(PANASONIC_SENSOR_RIGHT_BORDER) orelse metadata.width;
+    if (right > left and bottom > top and right <= metadata.width and bottom <= metadata.height) {
+        metadata.crop = .{ .x = left, .y = top, .width = right - left, .height = bottom - top };
+    }
+
+    metadata.cfa = switch (ifd0.get_u32(PANASONIC_CFA_PATTERN) orelse 1) {
+        2 => formats.CfaPattern.from_2x2(.{ 1, 0, 2, 1 }),
+        3 => formats.CfaPattern.from_2x2(.{ 1, 2, 0, 1 }),
+        4 => formats.CfaPattern.from_2x2(.{ 2, 1, 1, 0 }),
+        else => formats.CfaPattern.rggb,
+    };
+    if (ifd0.get_u32(PANASONIC_BITS_PER_SAMPLE)) |bits| {
+        if (bits >= 8 and bits <= 16) {
+            metadata.bits_per_sample = bits;
+            metadata.white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(bits))) - 1} ** 4;
+        }
+    }
+
+    // Per-colour levels, which Panasonic counts from 15 above the true black
+    var black: [3]u32 = undefined;
+    for (&black, 0..) |*level, i| {
+        level.* = (ifd0.get_u32(PANASONIC_BLACK_LEVEL_RED + @as(u16, @intCast(i))) orelse break) + 15;
+    } else metadata.set_black_levels_rggb(.{ black[0], black[1], black[1], black[2] });
+
+    var wb: [3]f32 = undefined;
+    for (&wb, 0..) |*level, i| {
+        level.* = @floatFromInt(ifd0.get_u32(PANASONIC_WB_RED_LEVEL + @as(u16, @intCast(i))) orelse break);
+    } else if (wb[0] > 0.0 and wb[1] > 0.0 and wb[2] > 0.0) {
+        metadata.white_balance = .{ wb[0] / wb[1], 1.0, wb[2] / wb[1] };
+    }
 }
 
 pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
@@ -0,0 +1,176 @@
+// src/formats/srw.zig - Samsung SRW RAW format support
+const std = @import("std");
+const formats = @import("mod.zig");
+const tiff = @import("tiff/mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+/// Offset of the per-row offset table of the first compressed scheme
+const TAG_ROW_OFFSETS = 0xA010;
+
+// Samsung makernote tags
+const SAMSUNG_WB_RGGB_LEVELS = 0xA021;
+const SAMSUNG_WB_RGGB_BLACK = 0xA028;
+
+const COMPRESSION_NONE = 1;
+/// Uncompressed, packed least significant bit first
+const COMPRESSION_SAMSUNG_PACKED = 32769;
+/// The first compressed scheme when the raw IFD lists row offsets, packed
+/// data otherwise
+const COMPRESSION_SAMSUNG_V0 = 32770;
+const COMPRESSION_SAMSUNG_V1 = 32772;
+const COMPRESSION_SAMSUNG_V2 = 32773;
+
+/// Samsung makernotes are a bare IFD with offsets relative to the note
+const Makernote = struct {
+    parser: tiff.IfdParser,
+    ifd: tiff.Ifd,
+};
+
+const Layout = struct {
+    ifd0: tiff.Ifd,
+    raw_ifd: tiff.Ifd,
+    exif: tiff.ExifInfo,
+    makernote: ?Makernote,
+};
+
+pub fn detect(data: []const u8) bool {
+    const header = tiff.read_header(data) orelse return false;
+    if (header.magic != tiff.TIFF_MAGIC) return false;
+
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = parser.parse_ifd(header.first_ifd) catch return false;
+    const make = ifd0.get_string(tiff.EXIF_MAKE) orelse return false;
+    return std.mem.startsWith(u8, make, "SAMSUNG");
+}
+
+fn locate(data: []const u8) !Layout {
+    const header = tiff.read_header(data) orelse return formats.FormatError.InvalidHeader;
+    const parser = tiff.IfdParser.init(data, header.byte_order);
+    const ifd0 = try parser.parse_ifd(header.first_ifd);
+    const raw_ifd = tiff.find_raw_ifd(ifd0) orelse return formats.FormatError.UnsupportedFormat;
+    const exif = tiff.read_exif_info(ifd0);
+    const makernote = if (exif.makernote) |entry| open_makernote(parser, entry.value_offset) else null;
+
+    return Layout{
+        .ifd0 = ifd0,
+        .raw_ifd = raw_ifd,
+        .exif = exif,
+        .makernote = makernote,
+    };
+}
+
+fn open_makernote(parser: tiff.IfdParser, start: usize) ?Makernote {
+    if (!parser.in_bounds(start, 2)) return null;
+    const sub = tiff.IfdParser.init(parser.data[start..], parser.byte_order);
+    const ifd = sub.parse_ifd(0) catch return null;
+    return Makernote{ .parser = sub, .ifd = ifd };
+}
+
+fn read_levels(mn: Makernote, tag: u16) ?[4]u32 {
+    const entry = mn.ifd.find(tag) orelse return null;
+    var levels: [4]u32 = undefined;
+    for (&levels, 0..) |*level, i| level.* = entry.u32_at(mn.parser, i) orelse return null;
+    return levels;
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const raw = layout.raw_ifd;
+
+    const width = raw.get_u32(tiff.TAG_IMAGE_WIDTH) orelse return formats.FormatError.CorruptedData;
+    const height = raw.get_u32(tiff.TAG_IMAGE_LENGTH) orelse return formats.FormatError.CorruptedData;
+    const bits = raw.get_u32(tiff.TAG_BITS_PER_SAMPLE) orelse 12;
+    if (bits < 8 or bits > 16) return formats.FormatError.CorruptedData;
+    const compression = raw.get_u32(tiff.TAG_COMPRESSION) orelse COMPRESSION_NONE;
+    switch (compression) {
+        COMPRESSION_NONE, COMPRESSION_SAMSUNG_PACKED, COMPRESSION_SAMSUNG_V0, COMPRESSION_SAMSUNG_V1, COMPRESSION_SAMSUNG_V2 => {},
+        else => return formats.FormatError.UnsupportedFormat,
+    }
+
+    const make = try allocator.dupe(u8, layout.exif.make orelse "SAMSUNG");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, layout.exif.model orelse "Unknown Samsung Camera");
+    errdefer allocator.free(model);
+
+    var metadata = formats.RawMetadata{
+        .format = .SRW,
+        .width = width,
+        .height = height,
+        .orientation = formats.orientation_from_exif(layout.exif.orientation),
+        .make = make,
+        .model = model,
+        .iso = layout.exif.iso,
+        .shutter_speed = if (layout.exif.exposure_time) |r| r.to_f32() else 0.0,
+        .aperture = if (layout.exif.f_number) |r| r.to_f32() else 0.0,
+        .exposure_time = layout.exif.exposure_time,
+        .f_number = layout.exif.f_number,
+        .focal_length = if (layout.exif.focal_length) |r| r.to_f32() else 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(bits))) - 1} ** 4,
+        .raw_data_offset = raw.get_u32(tiff.TAG_STRIP_OFFSETS) orelse 0,
+        .raw_data_size = @intCast(@min(raw.strip_bytes(), std.math.maxInt(u32))),
+        .cfa = formats.cfa_from_ifd(raw) orelse formats.CfaPattern.rggb,
+        .bits_per_sample = bits,
+        .compression = compression,
+        .allocator = allocator,
+    };
+
+    if (layout.makernote) |mn| {
+        // Both lists are in R, G, G, B order; the WB levels include the black
+        const black = read_levels(mn, SAMSUNG_WB_RGGB_BLACK);
+        if (black) |levels| metadata.set_black_levels_rggb(levels);
+        if (read_levels(mn, SAMSUNG_WB_RGGB_LEVELS)) |levels| {
+            var wb: [4]f32 = undefined;
+            for (&wb, levels, black orelse [_]u32{0} ** 4) |*w, level, b| w.* = @floatFromInt(level -| b);
+            if (wb[0] > 0.0 and wb[1] > 0.0 and wb[3] > 0.0) metadata.white_balance = .{ wb[0] / wb[1], 1.0, wb[3] / wb[1] };
+        }
+    }
+    return metadata;
+}
+
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const width = metadata.width;
+    const height = metadata.height;
+
+    const offset: usize = metadata.raw_data_offset;
+    if (offset == 0 or offset >= data.len) return formats.FormatError.CorruptedData;
+    // Compressed data may run past its strip byte count
+    const input = data[offset..];
+
+    const raw = try allocator.alloc(u16, @as(usize, width) * height);
+    errdefer allocator.free(raw);
+
+    const row_offsets = layout.raw_ifd.get_u32(TAG_ROW_OFFSETS);
+    switch (metadata.compression) {
+        COMPRESSION_SAMSUNG_V1 => {
+            try decoders.samsung.decode_v1(input, raw, width, height, metadata.bits_per_sample);
+            return raw;
+        },
+        COMPRESSION_SAMSUNG_V2 => {
+            try decoders.samsung.decode_v2(input, raw, width, height);
+            return raw;
+        },
+        COMPRESSION_SAMSUNG_V0 => if (row_offsets) |table| {
+            if (table >= data.len) return formats.FormatError.CorruptedData;
+            try decoders.samsung.decode_v0(input, data[table..], raw, width, height);
+            return raw;
+        },
+        else => {},
+    }
+
+    // Uncompressed: 12-bit data of the compressed type is packed the other way round
+    const unpack = decoders.unpack;
+    const bits = metadata.bits_per_sample;
+    const packing: unpack.Packing = if (metadata.compression == COMPRESSION_SAMSUNG_V0 and bits == 12) .msb else .lsb;
+    const stored = input[0..@min(input.len, metadata.raw_data_size)];
+    const stride = @max(stored.len / @max(height, 1), unpack.row_bytes(width, bits, packing));
+    try unpack.unpack(stored, raw, width, height, bits, packing, stride);
+    return raw;
+}
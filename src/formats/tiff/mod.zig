@@ -3,6 +3,51 @@ const std = @import("std");
 
 pub const ByteOrder = enum { little, big };
 
+pub const TIFF_MAGIC: u16 = 42;
+
+// TIFF field types
+pub const TYPE_BYTE: u16 = 1;
+pub const TYPE_ASCII: u16 = 2;
+pub const TYPE_SHORT: u16 = 3;
+pub const TYPE_LONG: u16 = 4;
+pub const TYPE_RATIONAL: u16 = 5;
+pub const TYPE_SBYTE: u16 = 6;
+pub const TYPE_UNDEFINED: u16 = 7;
+pub const TYPE_SSHORT: u16 = 8;
+pub const TYPE_SLONG: u16 = 9;
+pub const TYPE_SRATIONAL: u16 = 10;
+pub const TYPE_FLOAT: u16 = 11;
+pub const TYPE_DOUBLE: u16 = 12;
+pub const TYPE_IFD: u16 = 13;
+
+// Baseline TIFF tags
+pub const TAG_NEW_SUBFILE_TYPE = 0x00FE;
+pub const TAG_IMAGE_WIDTH = 0x0100;
+pub const TAG_IMAGE_LENGTH = 0x0101;
+pub const TAG_BITS_PER_SAMPLE = 0x0102;
+pub const TAG_COMPRESSION = 0x0103;
+pub const TAG_PHOTOMETRIC = 0x0106;
+pub const TAG_STRIP_OFFSETS = 0x0111;
+pub const TAG_SAMPLES_PER_PIXEL = 0x0115;
+pub const TAG_ROWS_PER_STRIP = 0x0116;
+pub const TAG_STRIP_BYTE_COUNTS = 0x0117;
+pub const TAG_PLANAR_CONFIGURATION = 0x011C;
+pub const TAG_PREDICTOR = 0x013D;
+pub const TAG_TILE_WIDTH = 0x0142;
+pub const TAG_TILE_LENGTH = 0x0143;
+pub const TAG_TILE_OFFSETS = 0x0144;
+pub const TAG_TILE_BYTE_COUNTS = 0x0145;
+pub const TAG_SUB_IFDS = 0x014A;
+pub const TAG_SAMPLE_FORMAT = 0x0153;
+pub const TAG_CFA_REPEAT_PATTERN_DIM = 0x828D;
+pub const TAG_CFA_PATTERN = 0x828E;
+pub const TAG_EXIF_IFD = 0x8769;
+pub const TAG_MAKERNOTE = 0x927C;
+pub const TAG_EXIF_CFA_PATTERN = 0xA302;
+
+pub const PHOTOMETRIC_CFA = 32803;
+pub const PHOTOMETRIC_LINEAR_RAW = 34892;
+
 // Common EXIF tags
 pub const EXIF_MAKE = 0x010F;
 pub const EXIF_MODEL = 0x0110;
@@ -11,22 +56,156 @@ pub const EXIF_ISO = 0x8827;
 pub const EXIF_SHUTTER_SPEED = 0x829A;
 pub const EXIF_APERTURE = 0x829D;
 pub const EXIF_FOCAL_LENGTH = 0x920A;
+pub const EXIF_DATE_TIME_ORIGINAL = 0x9003;
+pub const EXIF_OFFSET_TIME_ORIGINAL = 0x9011;
+pub const EXIF_SUB_SEC_TIME_ORIGINAL = 0x9291;
+pub const EXIF_BODY_SERIAL_NUMBER = 0xA431;
+pub const EXIF_LENS_SERIAL_NUMBER = 0xA435;
+pub const DNG_CAMERA_SERIAL_NUMBER = 0xC62F;
+
+/// Size in bytes of a single value of the given TIFF field type
+pub fn type_size(data_type: u16) u32 {
+    return switch (data_type) {
+        TYPE_BYTE, TYPE_ASCII, TYPE_SBYTE, TYPE_UNDEFINED => 1,
+        TYPE_SHORT, TYPE_SSHORT => 2,
+        TYPE_LONG, TYPE_SLONG, TYPE_FLOAT, TYPE_IFD => 4,
+        TYPE_RATIONAL, TYPE_SRATIONAL, TYPE_DOUBLE => 8,
+        else => 0,
+    };
+}
+
+pub const Rational = struct {
+    num: i64,
+    den: i64,
+
+    pub fn to_f32(self: Rational) f32 {
+        if (self.den == 0) return 0.0;
+        return @floatCast(@as(f64, @floatFromInt(self.num)) / @as(f64, @floatFromInt(self.den)));
+    }
+};
+
+pub const Header = struct {
+    byte_order: ByteOrder,
+    magic: u16,
+    first_ifd: u32,
+};
+
+/// Reads the 8-byte TIFF header. The magic number is returned as-is so that
+/// TIFF variants (RW2, ORF, ...) can validate their own signature.
+pub fn read_header(data: []const u8) ?Header {
+    if (data.len < 8) return null;
+    const byte_order: ByteOrder = if (data[0] == 'I' and data[1] == 'I')
+        .little
+    else if (data[0] == 'M' and data[1] == 'M')
+        .big
+    else
+        return null;
+
+    const parser = IfdParser.init(data, byte_order);
+    return Header{
+        .byte_order = byte_order,
+        .magic = parser.read_u16(2),
+        .first_ifd = parser.read_u32(4),
+    };
+}
 
 pub const IfdEntry = struct {
     tag: u16,
     data_type: u16,
     count: u32,
+    /// Absolute position of the value bytes (inline values point into the entry)
     value_offset: u32,
+
+    pub fn byte_size(self: IfdEntry) u64 {
+        return @as(u64, type_size(self.data_type)) * self.count;
+    }
+
+    pub fn bytes(self: IfdEntry, parser: IfdParser) ?[]const u8 {
+        const size = self.byte_size();
+        if (!parser.in_bounds(self.value_offset, size)) return null;
+        return parser.data[self.value_offset..][0..@intCast(size)];
+    }
+
+    fn value_pos(self: IfdEntry, parser: IfdParser, index: usize) ?usize {
+        if (index >= self.count) return null;
+        const size = type_size(self.data_type);
+        const pos = @as(u64, self.value_offset) + @as(u64, size) * index;
+        if (size == 0 or !parser.in_bounds(pos, size)) return null;
+        return @intCast(pos);
+    }
+
+    pub fn u32_at(self: IfdEntry, parser: IfdParser, index: usize) ?u32 {
+        const pos = self.value_pos(parser, index) orelse return null;
+        return switch (self.data_type) {
+            TYPE_BYTE, TYPE_ASCII, TYPE_SBYTE, TYPE_UNDEFINED => parser.data[pos],
+            TYPE_SHORT, TYPE_SSHORT => parser.read_u16(pos),
+            TYPE_LONG, TYPE_SLONG, TYPE_IFD => parser.read_u32(pos),
+            else => null,
+        };
+    }
+
+    pub fn i32_at(self: IfdEntry, parser: IfdParser, index: usize) ?i32 {
+        const pos = self.value_pos(parser, index) orelse return null;
+        return switch (self.data_type) {
+            TYPE_SBYTE => @as(i8, @bitCast(parser.data[pos])),
+            TYPE_SSHORT => @as(i16, @bitCast(parser.read_u16(pos))),
+            TYPE_SLONG => @as(i32, @bitCast(parser.read_u32(pos))),
+            else => if (self.u32_at(parser, index)) |v| @as(i32, @bitCast(v)) else null,
+        };
+    }
+
+    pub fn rational_at(self: IfdEntry, parser: IfdParser, index: usize) ?Rational {
+        const pos = self.value_pos(parser, index) orelse return null;
+        return switch (self.data_type) {
+            TYPE_RATIONAL => Rational{ .num = parser.read_u32(pos), .den = parser.read_u32(pos + 4) },
+            TYPE_SRATIONAL => Rational{
+                .num = @as(i32, @bitCast(parser.read_u32(pos))),
+                .den = @as(i32, @bitCast(parser.read_u32(pos + 4))),
+            },
+            else => if (self.i32_at(parser, index)) |v| Rational{ .num = v, .den = 1 } else null,
+        };
+    }
+
+    pub fn f64_at(self: IfdEntry, parser: IfdParser, index: usize) ?f64 {
+        const pos = self.value_pos(parser, index) orelse return null;
+        return switch (self.data_type) {
+            TYPE_FLOAT => @as(f32, @bitCast(parser.read_u32(pos))),
+            TYPE_DOUBLE => @as(f64, @bitCast(parser.read_u64(pos))),
+            TYPE_RATIONAL, TYPE_SRATIONAL => blk: {
+                const r = self.rational_at(parser, index).?;
+                if (r.den == 0) break :blk null;
+                break :blk @as(f64, @floatFromInt(r.num)) / @as(f64, @floatFromInt(r.den));
+            },
+            else => if (self.i32_at(parser, index)) |v| @as(f64, @floatFromInt(v)) else null,
+        };
+    }
 };
 
 pub const IfdParser = struct {
     data: []const u8,
     byte_order: ByteOrder,
+    /// File position that `data` starts at, for IFDs copied out of the file
+    /// whose entries still hold file-relative offsets (e.g. decrypted blocks)
+    offset_base: u32 = 0,
 
     pub fn init(data: []const u8, byte_order: ByteOrder) IfdParser {
         return IfdParser{ .data = data, .byte_order = byte_order };
     }
 
+    pub fn init_with_base(data: []const u8, byte_order: ByteOrder, offset_base: u32) IfdParser {
+        return IfdParser{ .data = data, .byte_order = byte_order, .offset_base = offset_base };
+    }
+
+    /// Maps an offset stored in an entry to a position in `data`
+    fn resolve(self: IfdParser, stored: u32) u32 {
+        if (stored < self.offset_base) return std.math.maxInt(u32);
+        return stored - self.offset_base;
+    }
+
+    pub fn in_bounds(self: IfdParser, offset: u64, len: u64) bool {
+        return offset <= self.data.len and len <= self.data.len - offset;
+    }
+
     pub fn read_u16(self: IfdParser, offset: usize) u16 {
         const bytes = self.data[offset .. offset + 2];
         return switch (self.byte_order) {
@@ -43,33 +222,204 @@ pub const IfdParser = struct {
         };
     }
 
+    pub fn read_u64(self: IfdParser, offset: usize) u64 {
+        const bytes = self.data[offset .. offset + 8];
+        return switch (self.byte_order) {
+            .little => std.mem.readInt(u64, bytes[0..8], .little),
+            .big => std.mem.readInt(u64, bytes[0..8], .big),
+        };
+    }
+
     pub fn parse_ifd(self: IfdParser, offset: u32) !Ifd {
-        // Implementation for parsing IFD entries...
-        // This would be a complete TIFF IFD parser
-        _ = self;
-        _ = offset;
-        return Ifd.init();
+        if (!self.in_bounds(offset, 2)) return error.CorruptedData;
+        const entry_count = self.read_u16(offset);
+        const entries_end = @as(u64, offset) + 2 + @as(u64, entry_count) * 12;
+        if (!self.in_bounds(offset, entries_end - offset)) return error.CorruptedData;
+
+        const next_offset = if (self.in_bounds(entries_end, 4)) self.read_u32(@intCast(entries_end)) else 0;
+        return Ifd{
+            .parser = self,
+            .offset = offset,
+            .entry_count = entry_count,
+            .next_offset = next_offset,
+        };
     }
 };
 
+/// A parsed IFD. Entries are read lazily from the underlying buffer, so an
+/// Ifd is cheap to copy and never needs to be freed.
 pub const Ifd = struct {
-    entries: std.ArrayList(IfdEntry),
+    parser: IfdParser,
+    offset: u32,
+    entry_count: u16,
+    next_offset: u32,
+
+    pub fn entry_at(self: Ifd, index: usize) IfdEntry {
+        const pos = @as(usize, self.offset) + 2 + index * 12;
+        const p = self.parser;
+        var entry = IfdEntry{
+            .tag = p.read_u16(pos),
+            .data_type = p.read_u16(pos + 2),
+            .count = p.read_u32(pos + 4),
+            .value_offset = 0,
+        };
+        // Values of up to four bytes are stored inline in the entry itself
+        entry.value_offset = if (entry.byte_size() <= 4) @intCast(pos + 8) else p.resolve(p.read_u32(pos + 8));
+        return entry;
+    }
 
-    pub fn init() Ifd {
-        return Ifd{ .entries = std.ArrayList(IfdEntry).init(std.heap.page_allocator) };
+    pub fn find(self: Ifd, tag: u16) ?IfdEntry {
+        for (0..self.entry_count) |i| {
+            const entry = self.entry_at(i);
+            if (entry.tag == tag) return entry;
+        }
+        return null;
     }
 
     pub fn get_string(self: Ifd, tag: u16) ?[]const u8 {
-        // Find and return string value for tag
-        _ = self;
-        _ = tag;
-        return null; // Placeholder
+        const entry = self.find(tag) orelse return null;
+        const raw = entry.bytes(self.parser) orelse return null;
+        const end = std.mem.indexOfScalar(u8, raw, 0) orelse raw.len;
+        return std.mem.trimRight(u8, raw[0..end], " ");
     }
 
     pub fn get_u32(self: Ifd, tag: u16) ?u32 {
-        // Find and return u32 value for tag
-        _ = self;
-        _ = tag;
-        return null; // Placeholder
+        const entry = self.find(tag) orelse return null;
+        return entry.u32_at(self.parser, 0);
+    }
+
+    pub fn get_f64(self: Ifd, tag: u16) ?f64 {
+        const entry = self.find(tag) orelse return null;
+        return entry.f64_at(self.parser, 0);
+    }
+
+    pub fn get_rational(self: Ifd, tag: u16) ?Rational {
+        const entry = self.find(tag) orelse return null;
+        return entry.rational_at(self.parser, 0);
+    }
+
+    pub fn get_bytes(self: Ifd, tag: u16) ?[]const u8 {
+        const entry = self.find(tag) orelse return null;
+        return entry.bytes(self.parser);
+    }
+
+    /// Follows a pointer tag (EXIF IFD, GPS IFD, ...) to the IFD it references
+    pub fn sub_ifd(self: Ifd, tag: u16) ?Ifd {
+        const offset = self.get_u32(tag) orelse return null;
+        if (offset == 0) return null;
+        return self.parser.parse_ifd(offset) catch null;
     }
+
+    pub fn next(self: Ifd) ?Ifd {
+        if (self.next_offset == 0) return null;
+        return self.parser.parse_ifd(self.next_offset) catch null;
+    }
+
+    /// Sum of all strip byte counts, for strips that are stored contiguously
+    pub fn strip_bytes(self: Ifd) u64 {
+        const entry = self.find(TAG_STRIP_BYTE_COUNTS) orelse return 0;
+        var total: u64 = 0;
+        for (0..entry.count) |i| total += entry.u32_at(self.parser, i) orelse 0;
+        return total;
+    }
+};
+
+/// Finds the largest CFA (or linear raw) image among IFD0, its chained IFDs
+/// and their SubIFDs. This is where TIFF-based raw formats keep sensor data.
+pub fn find_raw_ifd(ifd0: Ifd) ?Ifd {
+    return find_largest_ifd(ifd0, is_raw_ifd);
+}
+
+/// Like `find_raw_ifd`, but takes any stripped image of at least 12 bits.
+/// Some backs leave out Photometric or tag their sensor data as RGB.
+pub fn find_sensor_ifd(ifd0: Ifd) ?Ifd {
+    return find_largest_ifd(ifd0, is_sensor_ifd);
+}
+
+fn find_largest_ifd(ifd0: Ifd, comptime accept: fn (Ifd) bool) ?Ifd {
+    var best: ?Ifd = null;
+    var current: ?Ifd = ifd0;
+    var chained: u32 = 0;
+    while (current) |ifd| : (chained += 1) {
+        if (chained >= 16) break;
+        if (accept(ifd)) keep_larger(ifd, &best);
+        if (ifd.find(TAG_SUB_IFDS)) |entry| {
+            for (0..@min(entry.count, 16)) |i| {
+                const offset = entry.u32_at(ifd.parser, i) orelse continue;
+                if (offset == 0) continue;
+                const sub = ifd.parser.parse_ifd(offset) catch continue;
+                if (accept(sub)) keep_larger(sub, &best);
+            }
+        }
+        current = ifd.next();
+    }
+    return best;
+}
+
+fn is_raw_ifd(ifd: Ifd) bool {
+    const photometric = ifd.get_u32(TAG_PHOTOMETRIC) orelse return false;
+    return photometric == PHOTOMETRIC_CFA or photometric == PHOTOMETRIC_LINEAR_RAW;
+}
+
+fn is_sensor_ifd(ifd: Ifd) bool {
+    if (ifd.find(TAG_STRIP_OFFSETS) == null) return false;
+    return (ifd.get_u32(TAG_BITS_PER_SAMPLE) orelse 0) >= 12;
+}
+
+fn keep_larger(ifd: Ifd, best: *?Ifd) void {
+    const width = ifd.get_u32(TAG_IMAGE_WIDTH) orelse return;
+    const height = ifd.get_u32(TAG_IMAGE_LENGTH) orelse return;
+    if (best.*) |current| {
+        const best_width = current.get_u32(TAG_IMAGE_WIDTH) orelse 0;
+        const best_height = current.get_u32(TAG_IMAGE_LENGTH) orelse 0;
+        if (@as(u64, best_width) * best_height >= @as(u64, width) * height) return;
+    }
+    best.* = ifd;
+}
+
+/// The capture settings most raw formats store in IFD0 and the EXIF IFD
+pub const ExifInfo = struct {
+    make: ?[]const u8 = null,
+    model: ?[]const u8 = null,
+    orientation: u16 = 1,
+    iso: u32 = 0,
+    exposure_time: ?Rational = null,
+    f_number: ?Rational = null,
+    focal_length: ?Rational = null,
+    /// "YYYY:MM:DD HH:MM:SS", with the fraction and UTC offset kept apart
+    date_time_original: ?[]const u8 = null,
+    sub_sec_time_original: ?[]const u8 = null,
+    offset_time_original: ?[]const u8 = null,
+    body_serial: ?[]const u8 = null,
+    lens_serial: ?[]const u8 = null,
+    makernote: ?IfdEntry = null,
 };
+
+pub fn read_exif_info(ifd0: Ifd) ExifInfo {
+    var info = ExifInfo{
+        .make = ifd0.get_string(EXIF_MAKE),
+        .model = ifd0.get_string(EXIF_MODEL),
+    };
+    if (ifd0.get_u32(EXIF_ORIENTATION)) |orientation| info.orientation = @truncate(orientation);
+    info.body_serial = ifd0.get_string(DNG_CAMERA_SERIAL_NUMBER);
+
+    // TIFF/EP bodies may keep capture settings directly in IFD0
+    read_capture_settings(ifd0, &info);
+    if (ifd0.sub_ifd(TAG_EXIF_IFD)) |exif| {
+        read_capture_settings(exif, &info);
+        info.makernote = exif.find(TAG_MAKERNOTE);
+    }
+    return info;
+}
+
+fn read_capture_settings(ifd: Ifd, info: *ExifInfo) void {
+    if (ifd.get_u32(EXIF_ISO)) |iso| info.iso = iso;
+    if (ifd.get_rational(EXIF_SHUTTER_SPEED)) |r| info.exposure_time = r;
+    if (ifd.get_rational(EXIF_APERTURE)) |r| info.f_number = r;
+    if (ifd.get_rational(EXIF_FOCAL_LENGTH)) |r| info.focal_length = r;
+    if (ifd.get_string(EXIF_DATE_TIME_ORIGINAL)) |s| info.date_time_original = s;
+    if (ifd.get_string(EXIF_SUB_SEC_TIME_ORIGINAL)) |s| info.sub_sec_time_original = s;
+    if (ifd.get_string(EXIF_OFFSET_TIME_ORIGINAL)) |s| info.offset_time_original = s;
+    if (ifd.get_string(EXIF_BODY_SERIAL_NUMBER)) |s| info.body_serial = s;
+    if (ifd.get_string(EXIF_LENS_SERIAL_NUMBER)) |s| info.lens_serial = s;
+}
@@ -0,0 +1,587 @@
+// src/formats/x3f.zig - Sigma X3F (Foveon) RAW format support
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("mod.zig");
+const decoders = @import("../decoders/mod.zig");
+
+const X3F_MAGIC = "FOVb";
+
+// Little-endian file header
+const HEADER_VERSION = 4;
+const HEADER_ROTATION = 36;
+const HEADER_WHITE_BALANCE = 40;
+const HEADER_SIZE = 72;
+/// First version whose header names the white balance
+const VERSION_2_1 = 0x00020001;
+
+// Section identifiers, and directory entry types pointing at them
+const SECTION_DIRECTORY = "SECd";
+const SECTION_IMAGE = "SECi";
+const SECTION_PROPERTIES = "SECp";
+const SECTION_CAMF = "SECc";
+const ENTRY_IMAGE = "IMAG";
+const ENTRY_IMAGE2 = "IMA2";
+const ENTRY_PROPERTIES = "PROP";
+const ENTRY_CAMF = "CAMF";
+
+// Image section type (high half) and data format (low half)
+const IMAGE_RAW_TRUE = 0x0003001E;
+const IMAGE_RAW_MERRILL = 0x0001001E;
+const IMAGE_TYPE_PROCESSED = 2;
+const IMAGE_FORMAT_JPEG = 18;
+const IMAGE_HEADER_SIZE = 28;
+
+const CAMF_HEADER_SIZE = 28;
+const CAMF_ENTRY_HEADER_SIZE = 20;
+
+// CAMF matrix element types
+const MATRIX_INT16 = 0;
+const MATRIX_UINT32 = 1;
+const MATRIX_UINT32_ALT = 2;
+const MATRIX_FLOAT = 3;
+const MATRIX_UINT8 = 5;
+const MATRIX_UINT16 = 6;
+
+const DEFAULT_DEPTH = 12;
+
+const Image = struct {
+    type_format: u32,
+    columns: u32,
+    rows: u32,
+    /// Section payload after the image header
+    data: []const u8,
+    /// Offset of `data` in the file
+    offset: usize,
+};
+
+const Layout = struct {
+    version: u32,
+    raw: Image,
+    properties: ?[]const u8,
+    camf: ?[]const u8,
+};
+
+/// The TRUE-engine payload: one seed per plane, the difference table and
+/// the three Huffman-coded planes
+const TrueData = struct {
+    seeds: [3]u16,
+    table: decoders.foveon.Table,
+    planes: [3][]const u8,
+};
+
+pub fn detect(data: []const u8) bool {
+    return data.len >= HEADER_SIZE and std.mem.startsWith(u8, data, X3F_MAGIC);
+}
+
+fn read_u32(bytes: []const u8, offset: usize) u32 {
+    return std.mem.readInt(u32, bytes[offset..][0..4], .little);
+}
+
+/// Walks the section directory, whose offset is the file's last word
+const Directory = struct {
+    data: []const u8,
+    start: usize,
+    count: u32,
+    index: u32 = 0,
+
+    const Entry = struct {
+        kind: *const [4]u8,
+        offset: usize,
+        section: []const u8,
+    };
+
+    fn open(data: []const u8) !Directory {
+        if (!detect(data)) return formats.FormatError.InvalidHeader;
+        const start: usize = read_u32(data, data.len - 4);
+        if (start + 12 > data.len or !std.mem.eql(u8, data[start..][0..4], SECTION_DIRECTORY)) {
+            return formats.FormatError.CorruptedData;
+        }
+        return Directory{ .data = data, .start = start, .count = read_u32(data, start + 8) };
+    }
+
+    fn next(self: *Directory) !?Entry {
+        if (self.index == self.count) return null;
+        const entry = self.start + 12 + @as(usize, self.index) * 12;
+        self.index += 1;
+        if (entry + 12 > self.data.len) return formats.FormatError.CorruptedData;
+        const offset: usize = read_u32(self.data, entry);
+        const length: usize = read_u32(self.data, entry + 4);
+        if (offset + length > self.data.len) return formats.FormatError.CorruptedData;
+        return Entry{
+            .kind = self.data[entry + 8 ..][0..4],
+            .offset = offset,
+            .section = self.data[offset..][0..length],
+        };
+    }
+};
+
+fn is_image_entry(kind: *const [4]u8) bool {
+    return std.mem.eql(u8, kind, ENTRY_IMAGE) or std.mem.eql(u8, kind, ENTRY_IMAGE2);
+}
+
+fn locate(data: []const u8) !Layout {
+    var directory = try Directory.open(data);
+
+    var raw: ?Image = null;
+    var unsupported_raw = false;
+    var properties: ?[]const u8 = null;
+    var camf: ?[]const u8 = null;
+    while (try directory.next()) |entry| {
+        const section = entry.section;
+        if (is_image_entry(entry.kind)) {
+            if (section.len < IMAGE_HEADER_SIZE or !std.mem.eql(u8, section[0..4], SECTION_IMAGE)) continue;
+            const image_type = read_u32(section, 8);
+            const found = Image{
+                .type_format = (image_type << 16) | (read_u32(section, 12) & 0xffff),
+                .columns = read_u32(section, 16),
+                .rows = read_u32(section, 20),
+                .data = section[IMAGE_HEADER_SIZE..],
+                .offset = entry.offset + IMAGE_HEADER_SIZE,
+            };
+            if (image_type == IMAGE_TYPE_PROCESSED) continue;
+            if (found.type_format == IMAGE_RAW_TRUE or found.type_format == IMAGE_RAW_MERRILL) {
+                raw = found;
+            } else {
+                unsupported_raw = true;
+            }
+        } else if (std.mem.eql(u8, entry.kind, ENTRY_PROPERTIES)) {
+            if (section.len >= 24 and std.mem.eql(u8, section[0..4], SECTION_PROPERTIES)) properties = section;
+        } else if (std.mem.eql(u8, entry.kind, ENTRY_CAMF)) {
+            if (section.len >= CAMF_HEADER_SIZE and std.mem.eql(u8, section[0..4], SECTION_CAMF)) camf = section;
+        }
+    }
+
+    // Quattro and the older SD9/SD10 encodings are not TRUE-coded
+    const image = raw orelse return if (unsupported_raw) formats.FormatError.UnsupportedFormat else formats.FormatError.CorruptedData;
+    if (image.columns == 0 or image.rows == 0) return formats.FormatError.CorruptedData;
+    return Layout{
+        .version = read_u32(data, HEADER_VERSION),
+        .raw = image,
+        .properties = properties,
+        .camf = camf,
+    };
+}
+
+/// Copies the ASCII part of a UTF-16 property value into `buf`
+fn prop_string(section: []const u8, name: []const u8, buf: []u8) ?[]const u8 {
+    const count = read_u32(section, 8);
+    const strings = 24 + @as(usize, count) * 8;
+    if (strings > section.len) return null;
+    for (0..count) |i| {
+        const name_at = strings + @as(usize, read_u32(section, 24 + i * 8)) * 2;
+        const value_at = strings + @as(usize, read_u32(section, 28 + i * 8)) * 2;
+        var scratch: [32]u8 = undefined;
+        const key = utf16_ascii(section, name_at, &scratch) orelse continue;
+        if (std.mem.eql(u8, key, name)) return utf16_ascii(section, value_at, buf);
+    }
+    return null;
+}
+
+fn utf16_ascii(section: []const u8, start: usize, buf: []u8) ?[]const u8 {
+    var len: usize = 0;
+    var pos = start;
+    while (pos + 2 <= section.len) : (pos += 2) {
+        const unit = std.mem.readInt(u16, section[pos..][0..2], .little);
+        if (unit == 0) return buf[0..len];
+        if (len == buf.len) return null;
+        buf[len] = if (unit < 0x80) @intCast(unit) else '?';
+        len += 1;
+    }
+    return null;
+}
+
+fn prop_float(section: ?[]const u8, name: []const u8) ?f32 {
+    var buf: [32]u8 = undefined;
+    const text = prop_string(section orelse return null, name, &buf) orelse return null;
+    return std.fmt.parseFloat(f32, std.mem.trim(u8, text, " ")) catch null;
+}
+
+/// Decoded CAMF ("camera firmware") data: named matrices, property lists
+/// and text entries the camera's own converter uses
+const Camf = struct {
+    data: []u8,
+    allocator: std.mem.Allocator,
+
+    fn deinit(self: Camf) void {
+        self.allocator.free(self.data);
+    }
+
+    const Entry = struct {
+        bytes: []const u8,
+        value: usize,
+    };
+
+    fn find(self: Camf, kind: u8, name: []const u8) ?Entry {
+        var pos: usize = 0;
+        while (pos + CAMF_ENTRY_HEADER_SIZE <= self.data.len and std.mem.startsWith(u8, self.data[pos..], "CMb")) {
+            const size: usize = read_u32(self.data, pos + 8);
+            if (size < CAMF_ENTRY_HEADER_SIZE or pos + size > self.data.len) return null;
+            const bytes = self.data[pos..][0..size];
+            pos += size;
+
+            if (bytes[3] != kind) continue;
+            const name_at: usize = read_u32(bytes, 12);
+            const value: usize = read_u32(bytes, 16);
+            if (name_at >= value or value >= size) continue;
+            const entry_name = std.mem.sliceTo(bytes[name_at..value], 0);
+            if (std.mem.eql(u8, entry_name, name)) return Entry{ .bytes = bytes, .value = value };
+        }
+        return null;
+    }
+
+    /// Reads the matrix `name` into `out`, which must match its element count
+    fn matrix(self: Camf, name: []const u8, out: []f32) bool {
+        const entry = self.find('M', name) orelse return false;
+        const bytes = entry.bytes;
+        if (entry.value + 12 > bytes.len) return false;
+        const element_type = read_u32(bytes, entry.value);
+        const dims = read_u32(bytes, entry.value + 4);
+        const data_at: usize = read_u32(bytes, entry.value + 8);
+        if (dims == 0 or dims > 3 or entry.value + 12 + dims * 12 > bytes.len) return false;
+
+        var elements: usize = 1;
+        for (0..dims) |d| elements *= read_u32(bytes, entry.value + 12 + d * 12);
+        if (elements != out.len) return false;
+
+        const size: usize = switch (element_type) {
+            MATRIX_INT16, MATRIX_UINT16 => 2,
+            MATRIX_UINT32, MATRIX_UINT32_ALT, MATRIX_FLOAT => 4,
+            MATRIX_UINT8 => 1,
+            else => return false,
+        };
+        if (data_at + elements * size > bytes.len) return false;
+        for (out, 0..) |*value, i| {
+            const at = bytes[data_at + i * size ..];
+            value.* = switch (element_type) {
+                MATRIX_INT16 => @floatFromInt(std.mem.readInt(i16, at[0..2], .little)),
+                MATRIX_UINT16 => @floatFromInt(std.mem.readInt(u16, at[0..2], .little)),
+                MATRIX_FLOAT => @bitCast(std.mem.readInt(u32, at[0..4], .little)),
+                MATRIX_UINT8 => @floatFromInt(at[0]),
+                else => @floatFromInt(std.mem.readInt(u32, at[0..4], .little)),
+            };
+        }
+        return true;
+    }
+
+    /// Looks `key` up in the property list `name`
+    fn property(self: Camf, name: []const u8, key: []const u8) ?[]const u8 {
+        const entry = self.find('P', name) orelse return null;
+        const bytes = entry.bytes;
+        if (entry.value + 8 > bytes.len) return null;
+        const count = read_u32(bytes, entry.value);
+        const base: usize = read_u32(bytes, entry.value + 4);
+        for (0..count) |i| {
+            const pair = entry.value + 8 + i * 8;
+            if (pair + 8 > bytes.len) return null;
+            const name_at = base + read_u32(bytes, pair);
+            const value_at = base + read_u32(bytes, pair + 4);
+            if (name_at >= bytes.len or value_at >= bytes.len) return null;
+            if (std.mem.eql(u8, std.mem.sliceTo(bytes[name_at..], 0), key)) return std.mem.sliceTo(bytes[value_at..], 0);
+        }
+        return null;
+    }
+
+    /// Tries the list under its plain name, then with the DP1_ prefix some
+    /// compacts use
+    fn wb_property(self: Camf, name: []const u8, key: []const u8) ?[]const u8 {
+        if (self.property(name, key)) |value| return value;
+        var buf: [64]u8 = undefined;
+        const prefixed = std.fmt.bufPrint(&buf, "DP1_{s}", .{name}) catch return null;
+        return self.property(prefixed, key);
+    }
+};
+
+/// Decrypts or decompresses the CAMF section
+fn open_camf(section: []const u8, allocator: std.mem.Allocator) !?Camf {
+    const kind = read_u32(section, 8);
+    const payload = section[CAMF_HEADER_SIZE..];
+    switch (kind) {
+        2 => {
+            const data = try allocator.dupe(u8, payload);
+            var key = read_u32(section, 24);
+            for (data) |*byte| {
+                key = (key *% 1597 +% 51749) % 244944;
+                const tmp: u32 = @truncate(@as(u64, key) * 301593171 >> 24);
+                const mask: u8 = @truncate(((((key << 8) -% tmp) >> 1) +% tmp) >> 17);
+                byte.* ^= mask;
+            }
+            return Camf{ .data = data, .allocator = allocator };
+        },
+        4, 5 => {
+            const size = read_u32(section, 12);
+            const bias: i32 = @bitCast(read_u32(section, 16));
+            const parsed = try decoders.foveon.Table.read(payload);
+            const stream = payload[parsed.size..];
+
+            const data = try allocator.alloc(u8, size);
+            errdefer allocator.free(data);
+            if (kind == 5) {
+                try decoders.foveon.decode_bytes(stream, &parsed.table, data, bias);
+            } else {
+                try decode_camf_blocks(allocator, stream, &parsed.table, data, bias, read_u32(section, 20), read_u32(section, 24));
+            }
+            return Camf{ .data = data, .allocator = allocator };
+        },
+        else => return null,
+    }
+}
+
+/// Type 4: 12-bit values coded like an image plane of `block_count` rows of
+/// `block_size`, packed two to three bytes most significant nibble first
+fn decode_camf_blocks(
+    allocator: std.mem.Allocator,
+    stream: []const u8,
+    table: *const decoders.foveon.Table,
+    out: []u8,
+    bias: i32,
+    block_size: u32,
+    block_count: u32,
+) !void {
+    if (block_size == 0) return formats.FormatError.CorruptedData;
+    const needed = (out.len + 2) / 3 * 2;
+    // Only the rows the output needs are decoded; a short last pair reads as 0
+    const rows: u32 = @intCast(@min(block_count, (needed + block_size - 1) / block_size));
+    const values = try allocator.alloc(u16, @max(needed, @as(usize, block_size) * rows));
+    defer allocator.free(values);
+    @memset(values, 0);
+    try decoders.foveon.decode_plane(stream, table, values, block_size, rows, 1, 0, @truncate(@as(u32, @bitCast(bias))));
+
+    for (out, 0..) |*byte, i| {
+        const first = values[i / 3 * 2];
+        const second = values[i / 3 * 2 + 1];
+        byte.* = switch (i % 3) {
+            0 => @truncate(first >> 4),
+            1 => @truncate(((first & 0xf) << 4) | ((second >> 8) & 0xf)),
+            else => @truncate(second),
+        };
+    }
+}
+
+/// Adds the JPEG previews among the processed image sections. Quattro
+/// files have them too, even though their sensor data is unsupported.
+pub fn previews(data: []const u8, found: *formats.Previews) void {
+    var directory = Directory.open(data) catch return;
+    found.orientation = orientation(data);
+    while (directory.next() catch return) |entry| {
+        const section = entry.section;
+        if (!is_image_entry(entry.kind)) continue;
+        if (section.len < IMAGE_HEADER_SIZE or !std.mem.eql(u8, section[0..4], SECTION_IMAGE)) continue;
+        if (read_u32(section, 8) != IMAGE_TYPE_PROCESSED) continue;
+        if (read_u32(section, 12) & 0xffff == IMAGE_FORMAT_JPEG) found.add_jpeg(section[IMAGE_HEADER_SIZE..]);
+    }
+}
+
+fn orientation(data: []const u8) root.ZrRawOrientation {
+    return switch (read_u32(data, HEADER_ROTATION)) {
+        90 => .Rotate90,
+        180 => .Rotate180,
+        270 => .Rotate270,
+        else => .Normal,
+    };
+}
+
+pub fn parse_metadata(data: []const u8, allocator: std.mem.Allocator) !formats.RawMetadata {
+    if (!detect(data)) {
+        return formats.FormatError.UnsupportedFormat;
+    }
+
+    const layout = try locate(data);
+    const image = layout.raw;
+
+    var make_buf: [64]u8 = undefined;
+    var model_buf: [64]u8 = undefined;
+    var iso_buf: [16]u8 = undefined;
+    const props = layout.properties;
+    const make = try allocator.dupe(u8, if (props) |p| prop_string(p, "CAMMANUF", &make_buf) orelse "SIGMA" else "SIGMA");
+    errdefer allocator.free(make);
+    const model = try allocator.dupe(u8, if (props) |p| prop_string(p, "CAMMODEL", &model_buf) orelse "Unknown Sigma Camera" else "Unknown Sigma Camera");
+    errdefer allocator.free(model);
+    const iso = if (props) |p| (if (prop_string(p, "ISO", &iso_buf)) |text| std.fmt.parseInt(u32, text, 10) catch 0 else 0) else 0;
+
+    var metadata = formats.RawMetadata{
+        .format = .X3F,
+        .width = image.columns,
+        .height = image.rows,
+        .orientation = orientation(data),
+        .make = make,
+        .model = model,
+        .iso = iso,
+        // Stored in microseconds
+        .shutter_speed = if (prop_float(props, "EXPTIME")) |us| us / 1_000_000.0 else 0.0,
+        .aperture = prop_float(props, "APERTURE") orelse 0.0,
+        .focal_length = prop_float(props, "FLENGTH") orelse 0.0,
+        .color_matrix = [_]f32{0.0} ** 9,
+        .white_balance = [_]f32{ 1.0, 1.0, 1.0 },
+        // Measured from the dark shields when the data is decoded
+        .black_level = [_]f32{0.0} ** 4,
+        .white_level = [_]u32{(1 << DEFAULT_DEPTH) - 1} ** 4,
+        .raw_data_offset = @intCast(image.offset),
+        .raw_data_size = @intCast(image.data.len),
+        .bits_per_sample = DEFAULT_DEPTH,
+        .compression = image.type_format,
+        .samples_per_pixel = 3,
+        .linear = true,
+        .allocator = allocator,
+    };
+
+    if (layout.camf) |section| {
+        if (try open_camf(section, allocator)) |camf| {
+            defer camf.deinit();
+            apply_camf(&metadata, camf, white_balance_name(data, layout));
+        }
+    }
+    return metadata;
+}
+
+/// The as-shot white balance preset, which keys the CAMF colour tables
+fn white_balance_name(data: []const u8, layout: Layout) []const u8 {
+    if (layout.version >= VERSION_2_1) {
+        const field = data[HEADER_WHITE_BALANCE..][0..32];
+        const name = std.mem.sliceTo(field, 0);
+        if (name.len > 0) return name;
+    }
+    return "Auto";
+}
+
+fn apply_camf(metadata: *formats.RawMetadata, camf: Camf, wb: []const u8) void {
+    var levels: [3]f32 = undefined;
+    var depth: [1]f32 = undefined;
+    if (camf.matrix("RawSaturationLevel", &levels) or camf.matrix("SaturationLevel", &levels)) {
+        for (0..3) |c| metadata.white_level[c] = @intFromFloat(@max(levels[c], 1.0));
+        metadata.white_level[3] = metadata.white_level[1];
+    } else if (camf.matrix("ImageDepth", &depth) and depth[0] >= 8 and depth[0] <= 16) {
+        metadata.bits_per_sample = @intFromFloat(depth[0]);
+        metadata.white_level = [_]u32{(@as(u32, 1) << @as(u5, @intCast(metadata.bits_per_sample))) - 1} ** 4;
+    }
+
+    var area: [4]f32 = undefined;
+    if (camf.matrix("ActiveImageArea", &area)) {
+        if (to_rect(area, metadata.width, metadata.height)) |rect| metadata.crop = rect;
+    }
+
+    // Both tables map the preset to the name of a matrix
+    if (camf.wb_property("WhiteBalanceGains", wb)) |name| {
+        var gains: [3]f32 = undefined;
+        if (camf.matrix(name, &gains) and gains[1] > 0.0) {
+            metadata.white_balance = .{ gains[0] / gains[1], 1.0, gains[2] / gains[1] };
+        }
+    }
+    if (camf.wb_property("WhiteBalanceColorCorrections", wb)) |name| {
+        var rgb_cam: [9]f32 = undefined;
+        if (camf.matrix(name, &rgb_cam)) metadata.color_matrix = formats.xyz_from_srgb_matrix(rgb_cam);
+    }
+}
+
+/// CAMF rectangles are left, top, right, bottom with inclusive corners
+fn to_rect(corners: [4]f32, width: u32, height: u32) ?formats.Rect {
+    const left: u32 = @intFromFloat(@max(corners[0], 0.0));
+    const top: u32 = @intFromFloat(@max(corners[1], 0.0));
+    const right: u32 = @intFromFloat(@max(corners[2], 0.0));
+    const bottom: u32 = @intFromFloat(@max(corners[3], 0.0));
+    if (right < left or bottom < top or right >= width or bottom >= height) return null;
+    return .{ .x = left, .y = top, .width = right - left + 1, .height = bottom - top + 1 };
+}
+
+fn read_true(image: Image) !TrueData {
+    const bytes = image.data;
+    if (bytes.len < 8) return formats.FormatError.TruncatedFile;
+    var result = TrueData{ .seeds = undefined, .table = undefined, .planes = undefined };
+    for (&result.seeds, 0..) |*seed, c| seed.* = std.mem.readInt(u16, bytes[c * 2 ..][0..2], .little);
+
+    const parsed = try decoders.foveon.Table.read(bytes[8..]);
+    result.table = parsed.table;
+    var pos = 8 + parsed.size;
+    if (pos + 12 > bytes.len) return formats.FormatError.TruncatedFile;
+    var sizes: [3]usize = undefined;
+    for (&sizes, 0..) |*size, c| size.* = read_u32(bytes, pos + c * 4);
+    pos += 12;
+
+    // Each plane starts on a 16-byte boundary
+    for (&result.planes, sizes) |*plane, size| {
+        if (pos + size > bytes.len) return formats.FormatError.TruncatedFile;
+        plane.* = bytes[pos..][0..size];
+        pos += std.mem.alignForward(usize, size, 16);
+    }
+    return result;
+}
+
+/// Returns the three planes interleaved per pixel
+pub fn extract_raw_data(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) ![]u16 {
+    const layout = try locate(data);
+    const image = layout.raw;
+    if (image.columns != metadata.width or image.rows != metadata.height) return formats.FormatError.CorruptedData;
+    const coded = try read_true(image);
+
+    const raw = try allocator.alloc(u16, @as(usize, image.columns) * image.rows * 3);
+    errdefer allocator.free(raw);
+    for (coded.planes, coded.seeds, 0..) |plane, seed, c| {
+        try decoders.foveon.decode_plane(plane, &coded.table, raw, image.columns, image.rows, 3, @intCast(c), seed);
+    }
+    return raw;
+}
+
+/// Decodes the planes and normalises them, with black measured per plane
+/// on the covered sensor area the CAMF describes
+pub fn extract_linear(data: []const u8, metadata: formats.RawMetadata, allocator: std.mem.Allocator) !formats.LinearImage {
+    const width = metadata.width;
+    const height = metadata.height;
+    const raw = try extract_raw_data(data, metadata, allocator);
+    defer allocator.free(raw);
+
+    var black: [3]f32 = metadata.black_level[0..3].*;
+    const layout = try locate(data);
+    if (layout.camf) |section| {
+        if (try open_camf(section, allocator)) |camf| {
+            defer camf.deinit();
+            if (dark_shield_black(camf, raw, width, height)) |measured| black = measured;
+        }
+    }
+
+    var image = try formats.LinearImage.init(allocator, width, height, 3);
+    errdefer image.deinit();
+    for (image.data, raw, 0..) |*dst, value, i| {
+        const c = i % 3;
+        const white: f32 = @floatFromInt(metadata.white_level[c]);
+        const range = @max(white - black[c], 1.0);
+        dst.* = @max(@as(f32, @floatFromInt(value)) - black[c], 0.0) / range;
+    }
+    return image;
+}
+
+fn dark_shield_black(camf: Camf, raw: []const u16, width: u32, height: u32) ?[3]f32 {
+    var sums = [3]f64{ 0, 0, 0 };
+    var count: usize = 0;
+    for ([_][]const u8{ "DarkShieldTop", "DarkShieldBottom" }) |name| {
+        var corners: [4]f32 = undefined;
+        if (!camf.matrix(name, &corners)) continue;
+        const rect = to_rect(corners, width, height) orelse continue;
+        for (rect.y..rect.y + rect.height) |row| {
+            for (rect.x..rect.x + rect.width) |col| {
+                const px = raw[(row * width + col) * 3 ..][0..3];
+                for (&sums, px) |*sum, value| sum.* += @floatFromInt(value);
+            }
+        }
+        count += @as(usize, rect.width) * rect.height;
+    }
+    if (count == 0) return null;
+    var black: [3]f32 = undefined;
+    for (&black, sums) |*b, sum| b.* = @floatCast(sum / @as(f64, @floatFromInt(count)));
+    return black;
+}
+
+test "type 2 CAMF data is an XOR stream" {
+    var section = [_]u8{0} ** (CAMF_HEADER_SIZE + 1);
+    @memcpy(section[0..4], SECTION_CAMF);
+    section[8] = 2;
+    const camf = (try open_camf(&section, std.testing.allocator)).?;
+    defer camf.deinit();
+    // First keystream byte for key 0
+    try std.testing.expectEqual(@as(u8, 54), camf.data[0]);
+}
+
+test "CAMF rectangles include both corners" {
+    const rect = to_rect(.{ 2, 4, 9, 5 }, 10, 10).?;
+    try std.testing.expectEqual(@as(u32, 8), rect.width);
+    try std.testing.expectEqual(@as(u32, 2), rect.height);
+    try std.testing.expect(to_rect(.{ 0, 0, 10, 0 }, 10, 10) == null);
+}
@@ -0,0 +1,393 @@
+// src/processing/demosaic.zig - CFA interpolation
+const std = @import("std");
+const root = @import("../root.zig");
+const formats = @import("../formats/mod.zig");
+
+/// Interpolates a single-channel CFA plane into interleaved RGB.
+/// The caller owns the returned slice (width * height * 3 values).
+pub fn demosaic(
+    allocator: std.mem.Allocator,
+    cfa: []const f32,
+    width: u32,
+    height: u32,
+    pattern: formats.CfaPattern,
+    algorithm: root.ZrRawDemosaic,
+) ![]f32 {
+    const rgb = try allocator.alloc(f32, @as(usize, width) * height * 3);
+    errdefer allocator.free(rgb);
+
+    const is_bayer = pattern.width == 2 and pattern.height == 2;
+    const is_xtrans = pattern.width == 6 and pattern.height == 6;
+    const fits_kernel = width >= 5 and height >= 5;
+    switch (algorithm) {
+        .Fast => bilinear(cfa, rgb, width, height, pattern),
+        .Quality => if (is_bayer and fits_kernel)
+            malvar(cfa, rgb, width, height, pattern)
+        else
+            bilinear(cfa, rgb, width, height, pattern),
+        .Best, .Markesteijn => if (is_xtrans)
+            try markesteijn(allocator, cfa, rgb, width, height, pattern)
+        else if (is_bayer and fits_kernel)
+            malvar(cfa, rgb, width, height, pattern)
+        else
+            bilinear(cfa, rgb, width, height, pattern),
+    }
+    return rgb;
+}
+
+/// Mirrors an out-of-range coordinate back into the image
+fn reflect(pos: isize, size: u32) usize {
+    const last: isize = @as(isize, @intCast(size)) - 1;
+    var p = pos;
+    if (p < 0) p = -p;
+    if (p > last) p = 2 * last - p;
+    return @intCast(std.math.clamp(p, 0, last));
+}
+
+/// Averages same-coloured neighbours in a 3x3 window (5x5 where a colour is
+/// missing from the 3x3 window, as can happen with X-Trans).
+fn bilinear(cfa: []const f32, rgb: []f32, width: u32, height: u32, pattern: formats.CfaPattern) void {
+    for (0..height) |row| {
+        for (0..width) |col| {
+            const own = pattern.color_at(row, col);
+            const out = rgb[(row * width + col) * 3 ..][0..3];
+            for (0..3) |c| {
+                if (c == own) {
+                    out[c] = cfa[row * width + col];
+                    continue;
+                }
+                var radius: isize = 1;
+                while (radius <= 2) : (radius += 1) {
+                    var sum: f32 = 0.0;
+                    var count: u32 = 0;
+                    var dy: isize = -radius;
+                    while (dy <= radius) : (dy += 1) {
+                        var dx: isize = -radius;
+                        while (dx <= radius) : (dx += 1) {
+                            const y = reflect(@as(isize, @intCast(row)) + dy, height);
+                            const x = reflect(@as(isize, @intCast(col)) + dx, width);
+                            if (pattern.color_at(y, x) == c) {
+                                sum += cfa[y * width + x];
+                                count += 1;
+                            }
+                        }
+                    }
+                    if (count > 0) {
+                        out[c] = sum / @as(f32, @floatFromInt(count));
+                        break;
+                    }
+                } else out[c] = 0.0;
+            }
+        }
+    }
+}
+
+/// Reflected access to the neighbourhood of one CFA site
+const Window = struct {
+    cfa: []const f32,
+    width: u32,
+    height: u32,
+    row: isize,
+    col: isize,
+
+    fn at(self: Window, dy: isize, dx: isize) f32 {
+        const y = reflect(self.row + dy, self.height);
+        const x = reflect(self.col + dx, self.width);
+        return self.cfa[y * self.width + x];
+    }
+};
+
+/// Gradient-corrected bilinear interpolation (Malvar, He & Cutler) for 2x2 Bayer patterns
+fn malvar(cfa: []const f32, rgb: []f32, width: u32, height: u32, pattern: formats.CfaPattern) void {
+    for (0..height) |row| {
+        for (0..width) |col| {
+            const px = Window{
+                .cfa = cfa,
+                .width = width,
+                .height = height,
+                .row = @intCast(row),
+                .col = @intCast(col),
+            };
+
+            const center = px.at(0, 0);
+            const axial = px.at(-1, 0) + px.at(1, 0) + px.at(0, -1) + px.at(0, 1);
+            const diagonal = px.at(-1, -1) + px.at(-1, 1) + px.at(1, -1) + px.at(1, 1);
+            const far_axial = px.at(-2, 0) + px.at(2, 0) + px.at(0, -2) + px.at(0, 2);
+            const far_h = px.at(0, -2) + px.at(0, 2);
+            const far_v = px.at(-2, 0) + px.at(2, 0);
+            const near_h = px.at(0, -1) + px.at(0, 1);
+            const near_v = px.at(-1, 0) + px.at(1, 0);
+
+            const own = pattern.color_at(row, col);
+            const out = rgb[(row * width + col) * 3 ..][0..3];
+            out[own] = center;
+            if (own == 1) {
+                // Green site: one chroma neighbour horizontally, the other vertically
+                const along_row = (5.0 * center - diagonal - far_h + 0.5 * far_v + 4.0 * near_h) / 8.0;
+                const along_col = (5.0 * center - diagonal - far_v + 0.5 * far_h + 4.0 * near_v) / 8.0;
+                const row_color = pattern.color_at(row, col + 1);
+                const col_color = pattern.color_at(row + 1, col);
+                out[row_color] = @max(along_row, 0.0);
+                out[col_color] = @max(along_col, 0.0);
+            } else {
+                // Red or blue site: green from the axial cross, the other chroma diagonally
+                const other: usize = 2 - @as(usize, own);
+                out[1] = @max((4.0 * center + 2.0 * axial - far_axial) / 8.0, 0.0);
+                out[other] = @max((6.0 * center + 2.0 * diagonal - 1.5 * far_axial) / 8.0, 0.0);
+            }
+        }
+    }
+}
+
+/// Directions of the X-Trans green interpolation: horizontal, vertical and both diagonals
+const xtrans_directions = [4][2]isize{ .{ 0, 1 }, .{ 1, 0 }, .{ 1, 1 }, .{ 1, -1 } };
+
+// The X-Trans interpolation runs on overlapping tiles so that its
+// per-direction buffers stay small
+const xtrans_tile = 128;
+const xtrans_margin = 4;
+const xtrans_span = xtrans_tile + 2 * xtrans_margin;
+const xtrans_area = xtrans_span * xtrans_span;
+
+const Site = struct {
+    value: f32,
+    color: u8,
+};
+
+/// One tile of the X-Trans interpolation. Local (0, 0) lies `xtrans_margin`
+/// pixels above and left of the tile's first output pixel.
+const XTransTile = struct {
+    cfa: []const f32,
+    width: u32,
+    height: u32,
+    pattern: formats.CfaPattern,
+    top: isize,
+    left: isize,
+
+    fn site(self: XTransTile, y: isize, x: isize) Site {
+        const row = reflect(self.top + y, self.height);
+        const col = reflect(self.left + x, self.width);
+        return .{ .value = self.cfa[row * self.width + col], .color = self.pattern.color_at(row, col) };
+    }
+
+    /// Nearest green within two steps along (dy, dx)
+    fn nearest_green(self: XTransTile, y: isize, x: isize, dy: isize, dx: isize) ?struct { value: f32, distance: f32 } {
+        var step: isize = 1;
+        while (step <= 2) : (step += 1) {
+            const s = self.site(y + step * dy, x + step * dx);
+            if (s.color == 1) return .{ .value = s.value, .distance = @floatFromInt(step) };
+        }
+        return null;
+    }
+
+    /// Green along each direction, bounded by the greens around the pixel so
+    /// that interpolation across an edge cannot overshoot
+    fn interpolate_green(self: XTransTile, green: []f32) void {
+        for (0..xtrans_span) |y| {
+            for (0..xtrans_span) |x| {
+                const ly: isize = @intCast(y);
+                const lx: isize = @intCast(x);
+                const i = y * xtrans_span + x;
+                const center = self.site(ly, lx);
+                if (center.color == 1) {
+                    for (0..4) |d| green[d * xtrans_area + i] = center.value;
+                    continue;
+                }
+
+                var low = std.math.inf(f32);
+                var high = -std.math.inf(f32);
+                var sum: f32 = 0.0;
+                var count: u32 = 0;
+                var dy: isize = -1;
+                while (dy <= 1) : (dy += 1) {
+                    var dx: isize = -1;
+                    while (dx <= 1) : (dx += 1) {
+                        const s = self.site(ly + dy, lx + dx);
+                        if (s.color != 1) continue;
+                        low = @min(low, s.value);
+                        high = @max(high, s.value);
+                        sum += s.value;
+                        count += 1;
+                    }
+                }
+                if (count == 0) {
+                    for (0..4) |d| green[d * xtrans_area + i] = 0.0;
+                    continue;
+                }
+                const mean = sum / @as(f32, @floatFromInt(count));
+
+                for (xtrans_directions, 0..) |dir, d| {
+                    const ahead = self.nearest_green(ly, lx, dir[0], dir[1]);
+                    const behind = self.nearest_green(ly, lx, -dir[0], -dir[1]);
+                    var g = mean;
+                    if (ahead != null and behind != null) {
+                        const a = ahead.?;
+                        const b = behind.?;
+                        g = (a.value * b.distance + b.value * a.distance) / (a.distance + b.distance);
+                    } else if (ahead) |a| {
+                        g = a.value;
+                    } else if (behind) |b| {
+                        g = b.value;
+                    }
+                    green[d * xtrans_area + i] = std.math.clamp(g, low, high);
+                }
+            }
+        }
+    }
+
+    /// Red and blue from the mean colour difference to green among the
+    /// same-coloured sites of the 3x3 window, which X-Trans always provides
+    fn interpolate_chroma(self: XTransTile, green: []const f32, color: []f32) void {
+        for (0..xtrans_span) |y| {
+            for (0..xtrans_span) |x| {
+                const i = y * xtrans_span + x;
+                const center = self.site(@intCast(y), @intCast(x));
+                for (0..4) |d| {
+                    const plane = green[d * xtrans_area ..][0..xtrans_area];
+                    const g = plane[i];
+                    const out = color[(d * xtrans_area + i) * 3 ..][0..3];
+                    out[1] = g;
+                    for ([_]u8{ 0, 2 }) |c| {
+                        if (center.color == c) {
+                            out[c] = center.value;
+                            continue;
+                        }
+                        var sum: f32 = 0.0;
+                        var count: u32 = 0;
+                        for (y -| 1..@min(y + 2, xtrans_span)) |ny| {
+                            for (x -| 1..@min(x + 2, xtrans_span)) |nx| {
+                                const s = self.site(@intCast(ny), @intCast(nx));
+                                if (s.color != c) continue;
+                                sum += s.value - plane[ny * xtrans_span + nx];
+                                count += 1;
+                            }
+                        }
+                        const diff = if (count > 0) sum / @as(f32, @floatFromInt(count)) else 0.0;
+                        out[c] = @max(g + diff, 0.0);
+                    }
+                }
+            }
+        }
+    }
+};
+
+/// Perceptually spaced coordinates used to compare candidate colours
+fn perceptual(rgb: []const f32) [3]f32 {
+    const r = std.math.cbrt(@max(rgb[0], 0.0));
+    const g = std.math.cbrt(@max(rgb[1], 0.0));
+    const b = std.math.cbrt(@max(rgb[2], 0.0));
+    return .{ (r + 2.0 * g + b) / 4.0, r - g, g - b };
+}
+
+fn distance2(a: [3]f32, b: [3]f32) f32 {
+    var sum: f32 = 0.0;
+    for (a, b) |x, y| sum += (x - y) * (x - y);
+    return sum;
+}
+
+/// Markesteijn-style interpolation for 6x6 X-Trans patterns. Each pixel is
+/// reconstructed once per direction, and the candidates that vary least
+/// along their own direction over a 5x5 neighbourhood are averaged.
+fn markesteijn(
+    allocator: std.mem.Allocator,
+    cfa: []const f32,
+    rgb: []f32,
+    width: u32,
+    height: u32,
+    pattern: formats.CfaPattern,
+) !void {
+    const green = try allocator.alloc(f32, 4 * xtrans_area);
+    defer allocator.free(green);
+    const color = try allocator.alloc(f32, 4 * xtrans_area * 3);
+    defer allocator.free(color);
+    const variation = try allocator.alloc(f32, 4 * xtrans_area);
+    defer allocator.free(variation);
+
+    var top: usize = 0;
+    while (top < height) : (top += xtrans_tile) {
+        var left: usize = 0;
+        while (left < width) : (left += xtrans_tile) {
+            const tile = XTransTile{
+                .cfa = cfa,
+                .width = width,
+                .height = height,
+                .pattern = pattern,
+                .top = @as(isize, @intCast(top)) - xtrans_margin,
+                .left = @as(isize, @intCast(left)) - xtrans_margin,
+            };
+            tile.interpolate_green(green);
+            tile.interpolate_chroma(green, color);
+
+            // Colour change along each candidate's own direction
+            for (xtrans_directions, 0..) |dir, d| {
+                for (1..xtrans_span - 1) |y| {
+                    for (1..xtrans_span - 1) |x| {
+                        const i = y * xtrans_span + x;
+                        const ahead: usize = @intCast(@as(isize, @intCast(i)) + dir[0] * xtrans_span + dir[1]);
+                        const behind: usize = @intCast(@as(isize, @intCast(i)) - dir[0] * xtrans_span - dir[1]);
+                        const plane = color[d * xtrans_area * 3 ..][0 .. xtrans_area * 3];
+                        const here = perceptual(plane[i * 3 ..][0..3]);
+                        variation[d * xtrans_area + i] =
+                            distance2(here, perceptual(plane[ahead * 3 ..][0..3])) +
+                            distance2(here, perceptual(plane[behind * 3 ..][0..3]));
+                    }
+                }
+            }
+
+            for (0..xtrans_tile) |ty| {
+                const row = top + ty;
+                if (row >= height) break;
+                for (0..xtrans_tile) |tx| {
+                    const col = left + tx;
+                    if (col >= width) break;
+                    const y = ty + xtrans_margin;
+                    const x = tx + xtrans_margin;
+
+                    var score: [4]f32 = .{ 0.0, 0.0, 0.0, 0.0 };
+                    for (&score, 0..) |*sc, d| {
+                        for (y - 2..y + 3) |ny| {
+                            for (x - 2..x + 3) |nx| sc.* += variation[d * xtrans_area + ny * xtrans_span + nx];
+                        }
+                    }
+                    const best = @min(@min(score[0], score[1]), @min(score[2], score[3]));
+
+                    var sum: [3]f32 = .{ 0.0, 0.0, 0.0 };
+                    var count: f32 = 0.0;
+                    for (score, 0..) |sc, d| {
+                        if (sc > best * 1.125 + 1e-12) continue;
+                        const candidate = color[(d * xtrans_area + y * xtrans_span + x) * 3 ..][0..3];
+                        for (&sum, candidate) |*acc, v| acc.* += v;
+                        count += 1.0;
+                    }
+                    const out = rgb[(row * width + col) * 3 ..][0..3];
+                    for (out, sum) |*o, v| o.* = v / count;
+                }
+            }
+        }
+    }
+}
+
+test "bilinear fills missing colours on a flat field" {
+    const allocator = std.testing.allocator;
+    const cfa = [_]f32{0.5} ** 16;
+    const rgb = try demosaic(allocator, &cfa, 4, 4, formats.CfaPattern.rggb, .Fast);
+    defer allocator.free(rgb);
+    for (rgb) |v| try std.testing.expectApproxEqAbs(@as(f32, 0.5), v, 1e-6);
+}
+
+test "markesteijn keeps a flat X-Trans field flat" {
+    const allocator = std.testing.allocator;
+    const layout = [36]u8{
+        1, 1, 0, 1, 1, 2,
+        1, 1, 2, 1, 1, 0,
+        2, 0, 1, 0, 2, 1,
+        1, 1, 2, 1, 1, 0,
+        1, 1, 0, 1, 1, 2,
+        0, 2, 1, 2, 0, 1,
+    };
+    const pattern = formats.CfaPattern{ .width = 6, .height = 6, .colors = layout };
+    const cfa = [_]f32{0.25} ** (12 * 12);
+    const rgb = try demosaic(allocator, &cfa, 12, 12, pattern, .Markesteijn);
+    defer allocator.free(rgb);
+    for (rgb) |v| try std.testing.expectApproxEqAbs(@as(f32, 0.25), v, 1e-6);
+}
@@ -1,5 +1,10 @@
 const std = @import("std");
 const root = @import("../root.zig");
+const formats = @import("../formats/mod.zig");
+const demosaic = @import("demosaic.zig");
+const transform = @import("transform.zig");
+
+const native_endian = @import("builtin").cpu.arch.endian();
 
 pub const ProcessedImage = struct {
     width: u32,
@@ -22,21 +27,166 @@ pub const ProcessedImage = struct {
     }
 };
 
-pub fn process_raw(data: []const u8, params: root.ZrRawProcessParams) !ProcessedImage {
-    _ = data;
-    _ = params;
-
+pub fn process_raw(data: []const u8, metadata: formats.RawMetadata, params: root.ZrRawProcessParams) !ProcessedImage {
     const allocator = std.heap.c_allocator;
-    const dummy_data = try allocator.alloc(u8, 3);
-    dummy_data[0] = 255;
-    dummy_data[1] = 0;
-    dummy_data[2] = 0;
+
+    if (metadata.format == .Unknown) {
+        const dummy_data = try allocator.alloc(u8, 3);
+        dummy_data[0] = 255;
+        dummy_data[1] = 0;
+        dummy_data[2] = 0;
+
+        return ProcessedImage{
+            .width = 1,
+            .height = 1,
+            .channels = 3,
+            .bits_per_channel = 8,
+            .data = dummy_data,
+        };
+    }
+
+    var linear = try formats.extract_linear(data, metadata, allocator);
+    defer linear.deinit();
+    if (linear.width == 0 or linear.height == 0) return formats.FormatError.CorruptedData;
+    apply_white_balance(&linear, metadata);
+
+    var rgb = try to_rgb(allocator, linear, metadata, params.demosaic_algorithm);
+    defer rgb.deinit();
+    try formats.finish_rgb(data, metadata, &rgb);
+
+    if (metadata.crop) |rect| try transform.crop(&rgb, rect);
+    try transform.scale(&rgb, metadata.default_scale);
+
+    const width = rgb.width;
+    const height = rgb.height;
+    const pixel_count = @as(usize, width) * height;
+
+    const cam_to_srgb = balanced_to_srgb(metadata);
+    // Compensation is relative to the exposure the file asks for
+    const exposure = std.math.pow(f32, 2.0, params.exposure_compensation + metadata.baseline_exposure);
+    const gamma = if (params.output_gamma > 0.0) params.output_gamma else 2.2;
+
+    const channels: u32 = 3;
+    const bytes_per_sample: usize = if (params.output_16bit) 2 else 1;
+    const out = try allocator.alloc(u8, pixel_count * channels * bytes_per_sample);
+    errdefer allocator.free(out);
+
+    for (0..pixel_count) |i| {
+        const px = rgb.data[i * 3 ..][0..3];
+        var converted: [3]f32 = undefined;
+        for (0..3) |c| {
+            if (cam_to_srgb) |m| {
+                converted[c] = m[c * 3] * px[0] + m[c * 3 + 1] * px[1] + m[c * 3 + 2] * px[2];
+            } else {
+                converted[c] = px[c];
+            }
+        }
+        for (converted, 0..) |linear, c| {
+            const toned = tone(linear * exposure, params.shadow_lift, params.highlight_recovery);
+            const encoded = std.math.pow(f32, toned, 1.0 / gamma);
+            const sample = i * channels + c;
+            if (params.output_16bit) {
+                const value: u16 = @intFromFloat(@round(encoded * 65535.0));
+                std.mem.writeInt(u16, out[sample * 2 ..][0..2], value, native_endian);
+            } else {
+                out[sample] = @intFromFloat(@round(encoded * 255.0));
+            }
+        }
+    }
 
     return ProcessedImage{
-        .width = 1,
-        .height = 1,
-        .channels = 3,
-        .bits_per_channel = 8,
-        .data = dummy_data,
+        .width = width,
+        .height = height,
+        .channels = channels,
+        .bits_per_channel = if (params.output_16bit) 16 else 8,
+        .data = out,
     };
 }
+
+fn apply_white_balance(image: *formats.LinearImage, metadata: formats.RawMetadata) void {
+    const wb = normalized_white_balance(metadata.white_balance);
+    if (image.planes == 1) {
+        // Monochrome data has nothing to balance
+        if (metadata.linear) return;
+        for (0..image.height) |row| {
+            for (0..image.width) |col| {
+                image.data[row * image.width + col] *= wb[metadata.cfa.color_at(row, col)];
+            }
+        }
+    } else if (image.planes >= 3) {
+        var i: usize = 0;
+        while (i < image.data.len) : (i += image.planes) {
+            for (0..3) |c| image.data[i + c] *= wb[c];
+        }
+    }
+}
+
+/// Demosaics CFA data; linear data only needs reshaping to three planes
+fn to_rgb(
+    allocator: std.mem.Allocator,
+    image: formats.LinearImage,
+    metadata: formats.RawMetadata,
+    algorithm: root.ZrRawDemosaic,
+) !formats.LinearImage {
+    if (!metadata.linear) {
+        if (image.planes != 1) return formats.FormatError.CorruptedData;
+        const data = try demosaic.demosaic(allocator, image.data, image.width, image.height, metadata.cfa, algorithm);
+        return formats.LinearImage{ .width = image.width, .height = image.height, .planes = 3, .data = data, .allocator = allocator };
+    }
+
+    const rgb = try formats.LinearImage.init(allocator, image.width, image.height, 3);
+    const pixel_count = @as(usize, image.width) * image.height;
+    for (0..pixel_count) |i| {
+        const src = image.data[i * image.planes ..][0..image.planes];
+        for (0..3) |c| rgb.data[i * 3 + c] = if (image.planes >= 3) src[c] else src[0];
+    }
+    return rgb;
+}
+
+/// White balance multipliers relative to green; unusable values fall back to 1.0
+fn normalized_white_balance(wb: [3]f32) [3]f32 {
+    if (!(wb[1] > 0.0)) return .{ 1.0, 1.0, 1.0 };
+    var result: [3]f32 = undefined;
+    for (wb, 0..) |m, i| result[i] = if (m > 0.0) m / wb[1] else 1.0;
+    return result;
+}
+
+/// Camera to sRGB matrix for data balanced with `metadata.white_balance`,
+/// interpolated between the calibration illuminants for the white those
+/// multipliers imply. Rows are normalised so that balanced neutrals stay
+/// neutral. Returns null without colour calibration.
+fn balanced_to_srgb(metadata: formats.RawMetadata) ?[9]f32 {
+    const profile = formats.color.of(metadata) orelse return null;
+    const wb = normalized_white_balance(metadata.white_balance);
+    const neutral = [3]f64{ 1.0 / wb[0], 1.0 / wb[1], 1.0 / wb[2] };
+    const matrices = profile.matrices(.{ .neutral = neutral }) orelse return null;
+    // Balanced samples are the camera response divided by the neutral
+    const m = formats.color.mul(matrices.camera_to_srgb, formats.color.diagonal(neutral));
+
+    var result: [9]f32 = undefined;
+    for (0..3) |i| {
+        const row_sum = m[i * 3] + m[i * 3 + 1] + m[i * 3 + 2];
+        for (0..3) |j| {
+            const v = m[i * 3 + j];
+            result[i * 3 + j] = @floatCast(if (@abs(row_sum) > 1e-6) v / row_sum else v);
+        }
+    }
+    return result;
+}
+
+/// Shadow lift and soft highlight roll-off, clamped to [0, 1]
+fn tone(value: f32, shadow_lift: f32, highlight_recovery: f32) f32 {
+    var v = @max(value, 0.0);
+    if (shadow_lift > 0.0 and v < 1.0) {
+        v += std.math.clamp(shadow_lift, 0.0, 1.0) * 0.5 * (@sqrt(v) - v);
+    }
+    const recovery = std.math.clamp(highlight_recovery, 0.0, 1.0);
+    if (recovery > 0.0) {
+        const knee = 1.0 - 0.5 * recovery;
+        if (v > knee) {
+            const span = 1.0 - knee;
+            v = knee + span * (1.0 - @exp(-(v - knee) / span));
+        }
+    }
+    return std.math.clamp(v, 0.0, 1.0);
+}
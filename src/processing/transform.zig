@@ -0,0 +1,66 @@
+// src/processing/transform.zig - Geometric transforms on processed images
+const std = @import("std");
+const formats = @import("../formats/mod.zig");
+
+/// Keeps the part of `image` inside `rect`; the rectangle is clamped to the image
+pub fn crop(image: *formats.LinearImage, rect: formats.Rect) !void {
+    const x = @min(rect.x, image.width);
+    const y = @min(rect.y, image.height);
+    const width = @min(rect.width, image.width - x);
+    const height = @min(rect.height, image.height - y);
+    if (width == 0 or height == 0) return formats.FormatError.CorruptedData;
+    if (width == image.width and height == image.height) return;
+
+    var cropped = try formats.LinearImage.init(image.allocator, width, height, image.planes);
+    errdefer cropped.deinit();
+    const row_len = @as(usize, width) * image.planes;
+    for (0..height) |row| {
+        const src = image.data[image.index(y + row, x, 0)..][0..row_len];
+        @memcpy(cropped.data[row * row_len ..][0..row_len], src);
+    }
+    image.deinit();
+    image.* = cropped;
+}
+
+/// Resamples by the horizontal and vertical factors with bilinear interpolation
+pub fn scale(image: *formats.LinearImage, factors: [2]f32) !void {
+    const sx = if (factors[0] > 0.0) factors[0] else 1.0;
+    const sy = if (factors[1] > 0.0) factors[1] else 1.0;
+    if (sx == 1.0 and sy == 1.0) return;
+
+    const width: u32 = @max(1, @as(u32, @intFromFloat(@round(@as(f32, @floatFromInt(image.width)) * sx))));
+    const height: u32 = @max(1, @as(u32, @intFromFloat(@round(@as(f32, @floatFromInt(image.height)) * sy))));
+
+    var scaled = try formats.LinearImage.init(image.allocator, width, height, image.planes);
+    errdefer scaled.deinit();
+    const max_x: f32 = @floatFromInt(image.width - 1);
+    const max_y: f32 = @floatFromInt(image.height - 1);
+    for (0..height) |row| {
+        const fy = std.math.clamp((@as(f32, @floatFromInt(row)) + 0.5) / sy - 0.5, 0.0, max_y);
+        const y0: usize = @intFromFloat(@floor(fy));
+        const y1 = @min(y0 + 1, image.height - 1);
+        const ty = fy - @floor(fy);
+        for (0..width) |col| {
+            const fx = std.math.clamp((@as(f32, @floatFromInt(col)) + 0.5) / sx - 0.5, 0.0, max_x);
+            const x0: usize = @intFromFloat(@floor(fx));
+            const x1 = @min(x0 + 1, image.width - 1);
+            const tx = fx - @floor(fx);
+            for (0..image.planes) |p| {
+                const top = image.data[image.index(y0, x0, p)] * (1.0 - tx) + image.data[image.index(y0, x1, p)] * tx;
+                const bottom = image.data[image.index(y1, x0, p)] * (1.0 - tx) + image.data[image.index(y1, x1, p)] * tx;
+                scaled.data[scaled.index(row, col, p)] = top * (1.0 - ty) + bottom * ty;
+            }
+        }
+    }
+    image.deinit();
+    image.* = scaled;
+}
+
+test "crop keeps the requested window" {
+    const allocator = std.testing.allocator;
+    var image = try formats.LinearImage.init(allocator, 4, 3, 1);
+    defer image.deinit();
+    for (image.data, 0..) |*v, i| v.* = @floatFromInt(i);
+    try crop(&image, .{ .x = 1, .y = 1, .width = 2, .height = 2 });
+    try std.testing.expectEqualSlices(f32, &.{ 5, 6, 9, 10 }, image.data);
+}
@@ -12,12 +12,16 @@ pub const ZrRawError = enum(c_int) {
     OutOfMemory = -4,
     IoError = -5,
     CorruptedData = -6,
+    /// The data uses a compression zrraw cannot decode; zrraw_unsupported_compression names it
+    UnsupportedCompression = -7,
 
     pub fn from_zig_error(err: anyerror) ZrRawError {
         return switch (err) {
             error.OutOfMemory => .OutOfMemory,
             error.InvalidInput => .InvalidInput,
             error.UnsupportedFormat => .UnsupportedFormat,
+            error.CorruptedData, error.TruncatedFile => .CorruptedData,
+            error.UnsupportedCompression => .UnsupportedCompression,
             else => .ParseError,
         };
     }
@@ -35,6 +39,16 @@ pub const ZrRawFormat = enum(c_int) {
     RW2 = 7, // Panasonic
     PEF = 8, // Pentax
     X3F = 9, // Sigma
+    IIQ = 10, // Phase One
+    @"3FR" = 11, // Hasselblad
+    FFF = 12, // Hasselblad and Imacon
+    MOS = 13, // Leaf
+    CRW = 14, // Canon
+    MRW = 15, // Minolta
+    DCR = 16, // Kodak
+    KDC = 17, // Kodak
+    SRW = 18, // Samsung
+    ERF = 19, // Epson
 };
 
 /// Image orientation (EXIF standard)
@@ -54,6 +68,7 @@ pub const ZrRawDemosaic = enum(c_int) {
     Fast = 0, // Bilinear (speed)
     Quality = 1, // AHD/VNG (quality)
     Best = 2, // LMMSE/AMaZE (best quality)
+    Markesteijn = 3, // X-Trans directional interpolation; Bayer data falls back to Best
 };
 
 /// Processing parameters
@@ -75,6 +90,10 @@ pub const ZrRawProcessParams = extern struct {
     output_16bit: bool = false, // false = 8bit, true = 16bit
 };
 
+/// ZrRawMetadata.timecode_flags bits
+pub const TIMECODE_PRESENT: u32 = 1;
+pub const TIMECODE_DROP_FRAME: u32 = 2;
+
 /// Camera metadata
 pub const ZrRawMetadata = extern struct {
     // Format info
@@ -101,8 +120,15 @@ pub const ZrRawMetadata = extern struct {
     black_level: [4]f32, // RGGB black levels
     white_level: [4]u32, // RGGB white levels
 
+    baseline_exposure: f32, // Stops to add when rendering (DNG BaselineExposure)
+
+    // Video frames (CinemaDNG); 0 when absent
+    frame_rate: f32, // Frames per second
+    timecode: [4]u8, // Hours, minutes, seconds, frames
+    timecode_flags: u32, // TIMECODE_PRESENT | TIMECODE_DROP_FRAME
+
     // Internal use
-    _reserved: [32]u8,
+    _reserved: [16]u8,
 };
 
 /// Processed image data
@@ -119,6 +145,92 @@ pub const ZrRawImage = extern struct {
     _reserved: [16]u8,
 };
 
+/// Unprocessed sensor samples (active area, linearized)
+pub const ZrRawSensorData = extern struct {
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32, // 1 for CFA data
+    bits_per_sample: u32,
+    cfa_width: u32,
+    cfa_height: u32,
+    cfa: [36]u8, // Colour per pattern site, row-major: 0 = R, 1 = G, 2 = B
+    data: ?[*]u16, // Samples, interleaved per pixel (managed by zrraw)
+    data_len: usize, // Number of samples
+
+    // Internal use
+    _allocator: ?*anyopaque,
+    _reserved: [16]u8,
+};
+
+/// Semantic mask of a DNG (sky, skin, people, ...)
+pub const ZrRawMask = extern struct {
+    width: u32,
+    height: u32,
+    name: [64]u8, // SemanticName, null-terminated
+    instance_id: [64]u8, // SemanticInstanceID, null-terminated
+    data: ?[*]u8, // 8-bit coverage, row-major (managed by zrraw)
+    data_len: usize,
+
+    // Internal use
+    _allocator: ?*anyopaque,
+    _reserved: [16]u8,
+};
+
+/// Versioned key/value metadata; see formats/extended.zig for the layout
+pub const ZrRawExtendedMetadata = extern struct {
+    version: u32,
+    data: ?[*]u8, // Encoded entries, header included (managed by zrraw)
+    data_len: usize,
+
+    // Internal use
+    _allocator: ?*anyopaque,
+    _reserved: [16]u8,
+};
+
+/// Colour matrices interpolated for a white point
+pub const ZrRawColorMatrices = extern struct {
+    camera_to_xyz: [9]f32, // Camera, before white balance, to XYZ D50
+    camera_to_srgb: [9]f32, // Camera, before white balance, to linear sRGB
+    neutral: [3]f32, // Camera response to the white, largest component 1
+    white_xy: [2]f32, // CIE 1931 chromaticity of the white
+    temperature: f32, // Kelvin
+    tint: f32,
+
+    _reserved: [16]u8,
+};
+
+/// Encoding of an embedded preview
+pub const ZrRawPreviewFormat = enum(c_int) {
+    Jpeg = 0, // Complete JPEG stream
+    Rgb8 = 1, // Uncompressed 8-bit RGB, row-major
+};
+
+/// An embedded preview or thumbnail; the bytes stay in the caller's buffer
+pub const ZrRawPreview = extern struct {
+    offset: u64, // Position of the encoded image in the file
+    length: u64,
+    width: u32,
+    height: u32,
+    format: ZrRawPreviewFormat,
+    orientation: ZrRawOrientation,
+
+    _reserved: [16]u8,
+};
+
+/// A decoded lossless JPEG stream
+pub const ZrRawLjpegImage = extern struct {
+    width: u32,
+    height: u32,
+    components: u32, // Interleaved per pixel, 1 to 4
+    precision: u32, // Bits per sample, 2 to 16
+    data: ?[*]u16, // Row-major samples, components interleaved (managed by zrraw)
+    data_len: usize, // In samples
+
+    // Internal use
+    _allocator: ?*anyopaque,
+    _reserved: [16]u8,
+};
+
 // === PUBLIC API FUNCTIONS ===
 
 /// Detect raw format from file header
@@ -160,6 +272,124 @@ export fn zrraw_free_image(image: *ZrRawImage) void {
     free_image_internal(image);
 }
 
+/// Extract the undemosaiced sensor data
+/// Returns ZrRawError.Success on success
+export fn zrraw_extract_sensor_data(data: [*]const u8, data_len: usize, sensor: *ZrRawSensorData) ZrRawError {
+    extract_sensor_data_internal(data[0..data_len], sensor) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Free sensor data allocated by zrraw
+export fn zrraw_free_sensor_data(sensor: *ZrRawSensorData) void {
+    free_sensor_data_internal(sensor);
+}
+
+/// Count the semantic masks in a raw file (0 for formats without them)
+/// Returns ZrRawError.Success on success
+export fn zrraw_semantic_mask_count(data: [*]const u8, data_len: usize, count: *u32) ZrRawError {
+    count.* = formats.semantic_mask_count(data[0..data_len]) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Extract a semantic mask by index
+/// Returns ZrRawError.Success on success
+export fn zrraw_extract_semantic_mask(data: [*]const u8, data_len: usize, index: u32, mask: *ZrRawMask) ZrRawError {
+    extract_semantic_mask_internal(data[0..data_len], index, mask) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Free mask data allocated by zrraw
+export fn zrraw_free_semantic_mask(mask: *ZrRawMask) void {
+    free_semantic_mask_internal(mask);
+}
+
+/// Extract the metadata that does not fit ZrRawMetadata as a versioned
+/// key/value blob
+/// Returns ZrRawError.Success on success
+export fn zrraw_extract_extended_metadata(data: [*]const u8, data_len: usize, extended: *ZrRawExtendedMetadata) ZrRawError {
+    extract_extended_metadata_internal(data[0..data_len], extended) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Free an extended metadata blob allocated by zrraw
+export fn zrraw_free_extended_metadata(extended: *ZrRawExtendedMetadata) void {
+    free_extended_metadata_internal(extended);
+}
+
+/// Count the embedded previews and thumbnails in a raw file
+/// Returns ZrRawError.Success on success
+export fn zrraw_preview_count(data: [*]const u8, data_len: usize, count: *u32) ZrRawError {
+    const found = formats.find_previews(data[0..data_len]);
+    count.* = @intCast(found.len);
+    return .Success;
+}
+
+/// Describe an embedded preview by index
+/// Returns ZrRawError.Success on success
+export fn zrraw_preview_info(data: [*]const u8, data_len: usize, index: u32, preview: *ZrRawPreview) ZrRawError {
+    preview_info_internal(data[0..data_len], index, preview) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Decode the first scan of a lossless JPEG (ITU T.81 process 14) stream, as
+/// stored in CR2, DNG, NEF and other raw files
+/// Returns ZrRawError.Success on success
+export fn zrraw_ljpeg_decode(data: [*]const u8, data_len: usize, image: *ZrRawLjpegImage) ZrRawError {
+    ljpeg_decode_internal(data[0..data_len], image) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Free a lossless JPEG image allocated by zrraw
+export fn zrraw_free_ljpeg_image(image: *ZrRawLjpegImage) void {
+    free_ljpeg_image_internal(image);
+}
+
+/// Compression value behind the last UnsupportedCompression error returned
+/// on the calling thread
+export fn zrraw_unsupported_compression() u32 {
+    return formats.last_unsupported_compression;
+}
+
+/// Load a TOML camera database whose entries take precedence over the
+/// built-in ones; empty data removes it
+/// Returns ZrRawError.Success on success
+export fn zrraw_load_camera_database(data: [*]const u8, data_len: usize) ZrRawError {
+    formats.cameras.load_overrides(data[0..data_len]) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
+/// Compute the colour matrices for a white point: `temperature` and `tint`
+/// when the temperature is positive, else `neutral` (three floats) when not
+/// null, else the as-shot white balance
+/// Returns ZrRawError.InvalidInput if the file has no colour calibration
+export fn zrraw_color_matrices(
+    data: [*]const u8,
+    data_len: usize,
+    neutral: ?[*]const f32,
+    temperature: f32,
+    tint: f32,
+    matrices: *ZrRawColorMatrices,
+) ZrRawError {
+    color_matrices_internal(data[0..data_len], neutral, temperature, tint, matrices) catch |err| {
+        return ZrRawError.from_zig_error(err);
+    };
+    return .Success;
+}
+
 /// Get library version string
 export fn zrraw_version() [*:0]const u8 {
     return "zrraw 0.1.0";
@@ -167,13 +397,19 @@ export fn zrraw_version() [*:0]const u8 {
 
 /// Get supported formats as bit flags
 export fn zrraw_supported_formats() u32 {
-    return 0xFF; // All formats supported
+    // One bit per ZrRawFormat value
+    var flags: u32 = 0;
+    for ([_]ZrRawFormat{ .NEF, .ARW, .DNG, .RAF, .ORF, .RW2, .PEF, .X3F, .IIQ, .@"3FR", .FFF, .MOS, .CRW, .MRW, .DCR, .KDC, .SRW, .ERF }) |format| {
+        flags |= @as(u32, 1) << @intCast(@intFromEnum(format));
+    }
+    return flags;
 }
 
 // === INTERNAL IMPLEMENTATIONS ===
 
 const formats = @import("formats/mod.zig");
 const processing = @import("processing/mod.zig");
+const decoders = @import("decoders/mod.zig");
 
 fn detect_format_internal(data: []const u8, format: *ZrRawFormat) !void {
     format.* = try formats.detect(data);
@@ -196,7 +432,8 @@ fn process_image_internal(
 ) !void {
     // Use 'var' so we can call deinit()
     var parsed_meta = try formats.parse_metadata(data, std.heap.c_allocator);
-    const processed_img = try processing.process_raw(data, params.*);
+    errdefer parsed_meta.deinit();
+    const processed_img = try processing.process_raw(data, parsed_meta, params.*);
 
     // Copy data first
     result_image.* = processed_img.to_ffi();
@@ -206,6 +443,151 @@ fn process_image_internal(
     parsed_meta.deinit();
 }
 
+fn extract_sensor_data_internal(data: []const u8, sensor: *ZrRawSensorData) !void {
+    const allocator = std.heap.c_allocator;
+    var metadata = try formats.parse_metadata(data, allocator);
+    defer metadata.deinit();
+    if (metadata.format == .Unknown) return error.UnsupportedFormat;
+
+    const samples = try formats.extract_raw_data(data, metadata, allocator);
+    sensor.* = ZrRawSensorData{
+        .width = metadata.width,
+        .height = metadata.height,
+        .samples_per_pixel = metadata.samples_per_pixel,
+        .bits_per_sample = metadata.bits_per_sample,
+        .cfa_width = metadata.cfa.width,
+        .cfa_height = metadata.cfa.height,
+        .cfa = metadata.cfa.colors,
+        .data = samples.ptr,
+        .data_len = samples.len,
+        ._allocator = @as(*anyopaque, @ptrCast(@constCast(&std.heap.c_allocator))),
+        ._reserved = [_]u8{0} ** 16,
+    };
+}
+
+fn free_sensor_data_internal(sensor: *ZrRawSensorData) void {
+    if (sensor._allocator) |allocator_ptr| {
+        const allocator = @as(*std.mem.Allocator, @ptrCast(@alignCast(allocator_ptr)));
+        if (sensor.data) |data| {
+            allocator.free(data[0..sensor.data_len]);
+        }
+    }
+    sensor.* = std.mem.zeroes(ZrRawSensorData);
+}
+
+fn extract_semantic_mask_internal(data: []const u8, index: u32, mask: *ZrRawMask) !void {
+    const extracted = try formats.extract_semantic_mask(data, index, std.heap.c_allocator);
+    mask.* = std.mem.zeroes(ZrRawMask);
+    mask.width = extracted.width;
+    mask.height = extracted.height;
+    copy_c_string(&mask.name, extracted.name);
+    copy_c_string(&mask.instance_id, extracted.instance_id);
+    mask.data = extracted.data.ptr;
+    mask.data_len = extracted.data.len;
+    mask._allocator = @as(*anyopaque, @ptrCast(@constCast(&std.heap.c_allocator)));
+}
+
+fn ljpeg_decode_internal(data: []const u8, image: *ZrRawLjpegImage) !void {
+    const decoded = try decoders.ljpeg.decode(std.heap.c_allocator, data);
+    image.* = std.mem.zeroes(ZrRawLjpegImage);
+    image.width = decoded.frame.width;
+    image.height = decoded.frame.height;
+    image.components = decoded.frame.components;
+    image.precision = decoded.frame.precision;
+    image.data = decoded.samples.ptr;
+    image.data_len = decoded.samples.len;
+    image._allocator = @as(*anyopaque, @ptrCast(@constCast(&std.heap.c_allocator)));
+}
+
+fn extract_extended_metadata_internal(data: []const u8, extended: *ZrRawExtendedMetadata) !void {
+    var parsed = try formats.parse_metadata(data, std.heap.c_allocator);
+    defer parsed.deinit();
+    const blob = try formats.extended.encode(data, parsed, std.heap.c_allocator);
+    extended.* = std.mem.zeroes(ZrRawExtendedMetadata);
+    extended.version = formats.extended.VERSION;
+    extended.data = blob.ptr;
+    extended.data_len = blob.len;
+    extended._allocator = @as(*anyopaque, @ptrCast(@constCast(&std.heap.c_allocator)));
+}
+
+fn color_matrices_internal(data: []const u8, neutral: ?[*]const f32, temperature: f32, tint: f32, matrices: *ZrRawColorMatrices) !void {
+    var parsed = try formats.parse_metadata(data, std.heap.c_allocator);
+    defer parsed.deinit();
+    const profile = formats.color.of(parsed) orelse return error.InvalidInput;
+
+    const white: formats.color.White = if (temperature > 0.0)
+        .{ .temperature = .{ .kelvin = temperature, .tint = tint } }
+    else if (neutral) |n|
+        .{ .neutral = .{ n[0], n[1], n[2] } }
+    else blk: {
+        const wb = parsed.white_balance;
+        if (!(wb[0] > 0.0 and wb[1] > 0.0 and wb[2] > 0.0)) return error.InvalidInput;
+        break :blk .{ .neutral = .{ 1.0 / wb[0], 1.0 / wb[1], 1.0 / wb[2] } };
+    };
+    const solved = profile.matrices(white) orelse return error.InvalidInput;
+
+    matrices.* = std.mem.zeroes(ZrRawColorMatrices);
+    for (&matrices.camera_to_xyz, solved.camera_to_xyz) |*out, v| out.* = @floatCast(v);
+    for (&matrices.camera_to_srgb, solved.camera_to_srgb) |*out, v| out.* = @floatCast(v);
+    for (&matrices.neutral, solved.neutral) |*out, v| out.* = @floatCast(v);
+    for (&matrices.white_xy, solved.white_xy) |*out, v| out.* = @floatCast(v);
+    matrices.temperature = @floatCast(solved.temperature.kelvin);
+    matrices.tint = @floatCast(solved.temperature.tint);
+}
+
+fn preview_info_internal(data: []const u8, index: u32, preview: *ZrRawPreview) !void {
+    const found = formats.find_previews(data);
+    if (index >= found.len) return error.InvalidInput;
+    const item = found.items[index];
+    preview.* = std.mem.zeroes(ZrRawPreview);
+    preview.offset = item.offset;
+    preview.length = item.length;
+    preview.width = item.width;
+    preview.height = item.height;
+    preview.format = switch (item.kind) {
+        .jpeg => .Jpeg,
+        .rgb8 => .Rgb8,
+    };
+    preview.orientation = found.orientation;
+}
+
+/// Copies as much of `src` as fits, leaving room for the terminator
+fn copy_c_string(dest: []u8, src: []const u8) void {
+    const len = @min(src.len, dest.len - 1);
+    @memcpy(dest[0..len], src[0..len]);
+    dest[len] = 0;
+}
+
+fn free_semantic_mask_internal(mask: *ZrRawMask) void {
+    if (mask._allocator) |allocator_ptr| {
+        const allocator = @as(*std.mem.Allocator, @ptrCast(@alignCast(allocator_ptr)));
+        if (mask.data) |data| {
+            allocator.free(data[0..mask.data_len]);
+        }
+    }
+    mask.* = std.mem.zeroes(ZrRawMask);
+}
+
+fn free_ljpeg_image_internal(image: *ZrRawLjpegImage) void {
+    if (image._allocator) |allocator_ptr| {
+        const allocator = @as(*std.mem.Allocator, @ptrCast(@alignCast(allocator_ptr)));
+        if (image.data) |data| {
+            allocator.free(data[0..image.data_len]);
+        }
+    }
+    image.* = std.mem.zeroes(ZrRawLjpegImage);
+}
+
+fn free_extended_metadata_internal(extended: *ZrRawExtendedMetadata) void {
+    if (extended._allocator) |allocator_ptr| {
+        const allocator = @as(*std.mem.Allocator, @ptrCast(@alignCast(allocator_ptr)));
+        if (extended.data) |data| {
+            allocator.free(data[0..extended.data_len]);
+        }
+    }
+    extended.* = std.mem.zeroes(ZrRawExtendedMetadata);
+}
+
 fn free_image_internal(image: *ZrRawImage) void {
     if (image._allocator) |allocator_ptr| {
         // This now points to the stable c_allocator, so it's safe.
@@ -226,3 +608,9 @@ test "zrraw api" {
     const result = zrraw_detect_format("dummy".ptr, 5, &format);
     try testing.expect(result == .Success);
 }
+
+test {
+    _ = formats;
+    _ = processing;
+    _ = decoders;
+}